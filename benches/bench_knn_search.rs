@@ -1,10 +1,8 @@
-#[path = "shared.rs"]
-mod shared;
-use shared::*;
+use crate::shared::*;
 
 use criterion::{Criterion, criterion_group};
 use spart::geometry::{EuclideanDistance, Point2D, Point3D, Rectangle};
-use spart::{kdtree, octree, quadtree, rstar_tree, rtree};
+use spart::{frozen_kdtree, kdtree, octree, quadtree, rstar_tree, rtree};
 use std::hint::black_box;
 use tracing::info;
 
@@ -43,6 +41,21 @@ fn benchmark_knn_kdtree_2d(_c: &mut Criterion) {
     );
 }
 
+fn benchmark_knn_frozen_kdtree_2d(_c: &mut Criterion) {
+    info!("Setting up benchmark: knn_frozen_kdtree_2d");
+    let points = generate_2d_data();
+    let tree = frozen_kdtree::FrozenKdTree::new(&points).unwrap();
+    let target = Point2D::new(35.0, 45.0, None);
+    let mut cc = configure_criterion();
+    bench_knn_search(
+        "knn_frozen_kdtree_2d",
+        &tree,
+        &target,
+        |t, q, k| t.knn_search::<EuclideanDistance>(q, k),
+        &mut cc,
+    );
+}
+
 fn benchmark_knn_rtree_2d(_c: &mut Criterion) {
     info!("Setting up benchmark: knn_rtree_2d");
     let points = generate_2d_data();
@@ -181,6 +194,7 @@ criterion_group!(
     config = configure_criterion();
     targets =
     benchmark_knn_kdtree_2d,
+    benchmark_knn_frozen_kdtree_2d,
     benchmark_knn_rtree_2d,
     benchmark_knn_quadtree_2d,
     benchmark_knn_kdtree_3d,
@@ -6,6 +6,7 @@ mod bench_insert_bulk;
 mod bench_knn_search;
 mod bench_range_search;
 mod bench_serialization;
+mod shared;
 
 // Main entry point for running the benchmarks
 criterion_main!(
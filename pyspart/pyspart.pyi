@@ -4,7 +4,16 @@ Type stubs for PySpart - Python bindings for Spart Rust crate.
 This file provides type hints for IDEs and type checkers.
 """
 
-from typing import Optional, Dict, List, Tuple, Union, Any, Iterator, TypedDict, Type, TypeVar
+from typing import TYPE_CHECKING, Callable, Optional, Dict, List, Tuple, Union, Any, Iterator, TypedDict, Type, TypeVar, Protocol, Sequence
+
+
+if TYPE_CHECKING:
+    import numpy
+    import numpy.typing
+
+    IdArray = numpy.typing.NDArray[numpy.int64]
+else:
+    IdArray = Any
 
 
 # Basic geometry dictionaries accepted by constructors
@@ -42,6 +51,85 @@ class CubeDict(TypedDict):
     depth: float
 
 
+class MinMaxDict(TypedDict):
+    """A rectangle or cube given by its corners.
+
+    Keys:
+        min: The lower corner, (xmin, ymin) or (xmin, ymin, zmin).
+        max: The upper corner, (xmax, ymax) or (xmax, ymax, zmax).
+    """
+    min: Sequence[float]
+    max: Sequence[float]
+
+
+class HasBounds(Protocol):
+    """An object with (xmin, ymin, xmax, ymax) bounds, such as a Shapely geometry."""
+
+    @property
+    def bounds(self) -> Tuple[float, float, float, float]: ...
+
+
+# The forms accepted for boundaries and query windows
+RectangleLike = Union[RectangleDict, MinMaxDict, Tuple[float, float, float, float], List[float], HasBounds]
+CubeLike = Union[CubeDict, MinMaxDict, Tuple[float, float, float, float, float, float], List[float]]
+# A payload schema: field names mapped to int, float, bool, or str, as a dict, a list of pairs, or a dataclass
+SchemaLike = Union[Dict[str, Union[type, str]], Sequence[Tuple[str, Union[type, str]]], type]
+
+
+class TreeStatsDict(TypedDict):
+    """Statistics about the shape of a tree, as returned by `stats()`.
+
+    Keys:
+        size: The number of stored points.
+        node_count: The number of nodes.
+        leaf_count: The number of leaf nodes.
+        depth: The depth of the tree (the number of levels below the root).
+        fill_factor: The fraction of the node capacity in use over all nodes,
+            or None for k-d trees (whose nodes hold exactly one point).
+    """
+    size: int
+    node_count: int
+    leaf_count: int
+    depth: int
+    fill_factor: Optional[float]
+
+
+class CovarianceDict(TypedDict, total=False):
+    """The covariance of a neighborhood, as returned by `neighborhood_covariance()`.
+
+    Keys:
+        count: The number of neighbors.
+        mean: The mean of the neighbors.
+        covariance: The covariance matrix, as rows.
+        eigenvalues: The eigenvalues in ascending order (with `eigen=True`).
+        eigenvectors: The unit eigenvectors matching `eigenvalues` (with `eigen=True`).
+        normal: The eigenvector of the smallest eigenvalue (with `eigen=True`).
+        surface_variation: The smallest eigenvalue divided by their sum (with `eigen=True`).
+    """
+    count: int
+    mean: Tuple[float, float, float]
+    covariance: List[List[float]]
+    eigenvalues: List[float]
+    eigenvectors: List[List[float]]
+    normal: Tuple[float, float, float]
+    surface_variation: float
+
+class SpartError(ValueError):
+    """Base class of the errors raised by pyspart."""
+
+
+class InvalidCapacityError(SpartError):
+    """Raised when a tree is created with an invalid capacity or number of entries."""
+
+
+class DimensionMismatchError(SpartError):
+    """Raised when a point's dimension doesn't match the tree's dimension."""
+
+
+class OutOfBoundsError(SpartError):
+    """Raised when coordinates are out of range or not finite."""
+
+
 class Point2D:
     """A 2D point with associated user data.
 
@@ -109,6 +197,32 @@ class Point3D:
         ...
 
 
+class Batch:
+    """A batch of inserts and deletes, applied to a tree when the `with` block exits.
+
+    Created by the `batch()` method of the trees. The operations are applied
+    through the bulk paths of the tree, and discarded if the block raises.
+    """
+
+    def insert(self, point: Union[Point2D, Point3D]) -> None:
+        """Record a point to insert."""
+        ...
+
+    def delete(self, point: Union[Point2D, Point3D]) -> None:
+        """Record a point to delete (cancelling an earlier insert in the batch)."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of recorded operations."""
+        ...
+
+    def __enter__(self) -> "Batch":
+        ...
+
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool:
+        ...
+
+
 _TQT = TypeVar("_TQT", bound="Quadtree")
 _TOT = TypeVar("_TOT", bound="Octree")
 _TK2 = TypeVar("_TK2", bound="KdTree2D")
@@ -126,12 +240,14 @@ class Quadtree:
     using Euclidean distance.
     """
 
-    def __init__(self, boundary: RectangleDict, capacity: int) -> None:
+    def __init__(self, boundary: RectangleLike, capacity: int, ids_only: bool = False, schema: Optional[SchemaLike] = None) -> None:
         """Create a quadtree with a rectangular boundary.
 
         Args:
-            boundary: A rectangle dict specifying the root boundary.
+            boundary: The root boundary, as a dict, an (xmin, ymin, xmax, ymax) tuple, or a Shapely box.
             capacity: Max points per node before subdivision.
+            ids_only: Store integer ids as point data and return queries as NumPy arrays of ids.
+            schema: Store dict or dataclass payloads packed according to these fields.
         """
         ...
 
@@ -155,12 +271,95 @@ class Quadtree:
         """
         ...
 
-    def knn_search(self, point: Point2D, k: int) -> List[Point2D]:
-        """Find k nearest neighbors to the query point."""
+    def delete_bulk(self, points: Sequence[Point2D]) -> int:
+        """Delete several points at once.
+
+        Each point removes at most one matching stored point, as with `delete`.
+
+        Returns:
+            The number of points that were found and deleted.
+        """
         ...
 
-    def range_search(self, point: Point2D, radius: float) -> List[Point2D]:
-        """Find all points within a radius of the query point."""
+    def retain(self, keep: Callable[[Any], bool]) -> int:
+        """Keep only the points for which `keep(data)` is truthy, deleting the others.
+
+        If `keep` raises, the exception propagates; the points rejected before stay deleted.
+
+        Returns:
+            The number of points that were deleted.
+        """
+        ...
+
+    def knn_search(self, point: Point2D, k: int) -> Union[List[Point2D], IdArray]:
+        """Find k nearest neighbors to the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search(self, point: Point2D, radius: float) -> Union[List[Point2D], IdArray]:
+        """Find all points within a radius of the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search_where(self, point: Point2D, radius: float, **conditions: Any) -> List[Point2D]:
+        """Find the points within a radius whose payloads meet the conditions (needs a schema).
+
+        Each condition is a value the field must equal, or a (min, max) tuple of inclusive
+        bounds with None for an open end.
+        """
+        ...
+
+    def range_search_bbox(self, window: RectangleLike) -> Union[List[Point2D], IdArray]:
+        """Find all points inside a query window (their ids in ids-only mode)."""
+        ...
+
+    @property
+    def ids_only(self) -> bool:
+        """Whether point data are integer ids, returned by queries as NumPy arrays."""
+        ...
+
+    @property
+    def dims(self) -> int:
+        """The number of dimensions of the points (2)."""
+        ...
+
+    @property
+    def size(self) -> int:
+        """The number of stored points."""
+        ...
+
+    @property
+    def depth(self) -> int:
+        """The number of nodes on the longest path from the root to a leaf."""
+        ...
+
+    @property
+    def bounds(self) -> Tuple[float, ...]:
+        """The boundary of the tree, as an (xmin, ymin, xmax, ymax) tuple."""
+        ...
+
+    def batch(self) -> Batch:
+        """Start a batch of inserts and deletes, applied when the `with` block exits."""
+        ...
+
+    def copy(self: _TQT) -> _TQT:
+        """Return a copy of the tree, sharing the point data with the original."""
+        ...
+
+    def __copy__(self: _TQT) -> _TQT: ...
+
+    def content_eq(self: _TQT, other: _TQT) -> bool:
+        """Return True if both trees hold the same points, counting duplicates."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def check_invariants(self) -> None:
+        """Check the structural invariants of the tree.
+
+        Raises:
+            SpartError: If the tree is corrupted.
+        """
         ...
 
     def save(self, path: str) -> None:
@@ -168,7 +367,7 @@ class Quadtree:
         ...
 
     @classmethod
-    def load(cls: Type[_TQT], path: str) -> _TQT:
+    def load(cls: Type[_TQT], path: str, ids_only: bool = False) -> _TQT:
         """Load a quadtree from a file path."""
         ...
 
@@ -180,12 +379,14 @@ class Octree:
     using Euclidean distance.
     """
 
-    def __init__(self, boundary: CubeDict, capacity: int) -> None:
+    def __init__(self, boundary: CubeLike, capacity: int, ids_only: bool = False, schema: Optional[SchemaLike] = None) -> None:
         """Create an octree with a cubic boundary.
 
         Args:
-            boundary: A cube dict specifying the root boundary.
+            boundary: The root boundary, as a dict or an (xmin, ymin, zmin, xmax, ymax, zmax) tuple.
             capacity: Max points per node before subdivision.
+            ids_only: Store integer ids as point data and return queries as NumPy arrays of ids.
+            schema: Store dict or dataclass payloads packed according to these fields.
         """
         ...
 
@@ -209,100 +410,107 @@ class Octree:
         """
         ...
 
-    def knn_search(self, point: Point3D, k: int) -> List[Point3D]:
-        """Find k nearest neighbors to the query point."""
-        ...
-
-    def range_search(self, point: Point3D, radius: float) -> List[Point3D]:
-        """Find all points within a radius of the query point."""
-        ...
+    def delete_bulk(self, points: Sequence[Point3D]) -> int:
+        """Delete several points at once.
 
-    def save(self, path: str) -> None:
-        """Serialize and save the tree to a file path."""
-        ...
+        Each point removes at most one matching stored point, as with `delete`.
 
-    @classmethod
-    def load(cls: Type[_TOT], path: str) -> _TOT:
-        """Load an octree from a file path."""
+        Returns:
+            The number of points that were found and deleted.
+        """
         ...
 
+    def retain(self, keep: Callable[[Any], bool]) -> int:
+        """Keep only the points for which `keep(data)` is truthy, deleting the others.
 
-class KdTree2D:
-    """A k-d tree for 2D points supporting NN and range queries.
-
-    Uses Euclidean distance for searches.
-    """
+        If `keep` raises, the exception propagates; the points rejected before stay deleted.
 
-    def __init__(self) -> None:
-        """Create an empty 2D k-d tree."""
+        Returns:
+            The number of points that were deleted.
+        """
         ...
 
-    def insert(self, point: Point2D) -> None:
-        """Insert a point; raises ValueError on invalid input."""
+    def knn_search(self, point: Point3D, k: int) -> Union[List[Point3D], IdArray]:
+        """Find k nearest neighbors to the query point (their ids in ids-only mode)."""
         ...
 
-    def insert_bulk(self, points: List[Point2D]) -> None:
-        """Insert many points efficiently."""
+    def range_search(self, point: Point3D, radius: float) -> Union[List[Point3D], IdArray]:
+        """Find all points within a radius of the query point (their ids in ids-only mode)."""
         ...
 
-    def delete(self, point: Point2D) -> bool:
-        """Delete a point.
+    def range_search_where(self, point: Point3D, radius: float, **conditions: Any) -> List[Point3D]:
+        """Find the points within a radius whose payloads meet the conditions (needs a schema).
 
-        Returns:
-            True if the point was found and removed.
+        Each condition is a value the field must equal, or a (min, max) tuple of inclusive
+        bounds with None for an open end.
         """
         ...
 
-    def knn_search(self, point: Point2D, k: int) -> List[Point2D]:
-        """Find k nearest neighbors to the query point."""
+    @property
+    def ids_only(self) -> bool:
+        """Whether point data are integer ids, returned by queries as NumPy arrays."""
         ...
 
-    def range_search(self, point: Point2D, radius: float) -> List[Point2D]:
-        """Find all points within a radius of the query point."""
+    @property
+    def dims(self) -> int:
+        """The number of dimensions of the points (3)."""
         ...
 
-    def save(self, path: str) -> None:
-        """Serialize and save the tree to a file path."""
+    @property
+    def size(self) -> int:
+        """The number of stored points."""
         ...
 
-    @classmethod
-    def load(cls: Type[_TK2], path: str) -> _TK2:
-        """Load a 2D k-d tree from a file path."""
+    @property
+    def depth(self) -> int:
+        """The number of nodes on the longest path from the root to a leaf."""
         ...
 
+    @property
+    def bounds(self) -> Tuple[float, ...]:
+        """The boundary of the tree, as an (xmin, ymin, zmin, xmax, ymax, zmax) tuple."""
+        ...
 
-class KdTree3D:
-    """A k-d tree for 3D points supporting NN and range queries.
-
-    Uses Euclidean distance for searches.
-    """
+    def batch(self) -> Batch:
+        """Start a batch of inserts and deletes, applied when the `with` block exits."""
+        ...
 
-    def __init__(self) -> None:
-        """Create an empty 3D k-d tree."""
+    def copy(self: _TOT) -> _TOT:
+        """Return a copy of the tree, sharing the point data with the original."""
         ...
 
-    def insert(self, point: Point3D) -> None:
-        """Insert a point; raises ValueError on invalid input."""
+    def __copy__(self: _TOT) -> _TOT: ...
+
+    def content_eq(self: _TOT, other: _TOT) -> bool:
+        """Return True if both trees hold the same points, counting duplicates."""
         ...
 
-    def insert_bulk(self, points: List[Point3D]) -> None:
-        """Insert many points efficiently."""
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
         ...
 
-    def delete(self, point: Point3D) -> bool:
-        """Delete a point.
+    def neighborhood_covariance(
+        self, point: Point3D, k: Optional[int] = None, radius: Optional[float] = None, eigen: bool = False
+    ) -> Optional[CovarianceDict]:
+        """Compute the covariance of the k nearest points or the points within a radius.
 
-        Returns:
-            True if the point was found and removed.
+        Pass exactly one of k and radius; with eigen=True the eigen-decomposition and the
+        estimated normal are added. Returns None if no point lies within the radius.
         """
         ...
 
-    def knn_search(self, point: Point3D, k: int) -> List[Point3D]:
-        """Find k nearest neighbors to the query point."""
+    def neighborhood_covariances(
+        self, points: Sequence[Point3D], k: Optional[int] = None, radius: Optional[float] = None, eigen: bool = False
+    ) -> List[Optional[CovarianceDict]]:
+        """Compute `neighborhood_covariance` for each point, without holding the GIL."""
         ...
 
-    def range_search(self, point: Point3D, radius: float) -> List[Point3D]:
-        """Find all points within a radius of the query point."""
+    def check_invariants(self) -> None:
+        """Check the structural invariants of the tree.
+
+        Raises:
+            SpartError: If the tree is corrupted.
+        """
         ...
 
     def save(self, path: str) -> None:
@@ -310,27 +518,28 @@ class KdTree3D:
         ...
 
     @classmethod
-    def load(cls: Type[_TK3], path: str) -> _TK3:
-        """Load a 3D k-d tree from a file path."""
+    def load(cls: Type[_TOT], path: str, ids_only: bool = False) -> _TOT:
+        """Load an octree from a file path."""
         ...
 
 
-class RTree2D:
-    """An R-tree spatial index for 2D points.
+class KdTree2D:
+    """A k-d tree for 2D points supporting NN and range queries.
 
-    Balanced hierarchical index optimized for rectangle queries.
+    Uses Euclidean distance for searches.
     """
 
-    def __init__(self, max_entries: int) -> None:
-        """Create an R-tree.
+    def __init__(self, ids_only: bool = False, schema: Optional[SchemaLike] = None) -> None:
+        """Create an empty 2D k-d tree.
 
         Args:
-            max_entries: Maximum entries per node (branching factor).
+            ids_only: Store integer ids as point data and return queries as NumPy arrays of ids.
+            schema: Store dict or dataclass payloads packed according to these fields.
         """
         ...
 
     def insert(self, point: Point2D) -> None:
-        """Insert a point into the index."""
+        """Insert a point; raises DimensionMismatchError on invalid input."""
         ...
 
     def insert_bulk(self, points: List[Point2D]) -> None:
@@ -345,108 +554,91 @@ class RTree2D:
         """
         ...
 
-    def knn_search(self, point: Point2D, k: int) -> List[Point2D]:
-        """Find k nearest neighbors to the query point."""
-        ...
-
-    def range_search(self, point: Point2D, radius: float) -> List[Point2D]:
-        """Find all points within a radius of the query point."""
-        ...
+    def delete_bulk(self, points: Sequence[Point2D]) -> int:
+        """Delete several points at once.
 
-    def save(self, path: str) -> None:
-        """Serialize and save the tree to a file path."""
-        ...
+        Each point removes at most one matching stored point, as with `delete`.
 
-    @classmethod
-    def load(cls: Type[_TR2], path: str) -> _TR2:
-        """Load an R-tree from a file path."""
+        Returns:
+            The number of points that were found and deleted.
+        """
         ...
 
+    def retain(self, keep: Callable[[Any], bool]) -> int:
+        """Keep only the points for which `keep(data)` is truthy, deleting the others.
 
-class RTree3D:
-    """An R-tree spatial index for 3D points.
-
-    Balanced hierarchical index optimized for rectangle queries.
-    """
-
-    def __init__(self, max_entries: int) -> None:
-        """Create an R-tree.
+        If `keep` raises, the exception propagates; the points rejected before stay deleted.
 
-        Args:
-            max_entries: Maximum entries per node (branching factor).
+        Returns:
+            The number of points that were deleted.
         """
         ...
 
-    def insert(self, point: Point3D) -> None:
-        """Insert a point into the index."""
+    def knn_search(self, point: Point2D, k: int) -> Union[List[Point2D], IdArray]:
+        """Find k nearest neighbors to the query point (their ids in ids-only mode)."""
         ...
 
-    def insert_bulk(self, points: List[Point3D]) -> None:
-        """Insert many points efficiently."""
+    def range_search(self, point: Point2D, radius: float) -> Union[List[Point2D], IdArray]:
+        """Find all points within a radius of the query point (their ids in ids-only mode)."""
         ...
 
-    def delete(self, point: Point3D) -> bool:
-        """Delete a point.
+    def range_search_where(self, point: Point2D, radius: float, **conditions: Any) -> List[Point2D]:
+        """Find the points within a radius whose payloads meet the conditions (needs a schema).
 
-        Returns:
-            True if the point was found and removed.
+        Each condition is a value the field must equal, or a (min, max) tuple of inclusive
+        bounds with None for an open end.
         """
         ...
 
-    def knn_search(self, point: Point3D, k: int) -> List[Point3D]:
-        """Find k nearest neighbors to the query point."""
+    @property
+    def ids_only(self) -> bool:
+        """Whether point data are integer ids, returned by queries as NumPy arrays."""
         ...
 
-    def range_search(self, point: Point3D, radius: float) -> List[Point3D]:
-        """Find all points within a radius of the query point."""
+    @property
+    def dims(self) -> int:
+        """The number of dimensions of the points (2)."""
         ...
 
-    def save(self, path: str) -> None:
-        """Serialize and save the tree to a file path."""
+    @property
+    def size(self) -> int:
+        """The number of stored points."""
         ...
 
-    @classmethod
-    def load(cls: Type[_TR3], path: str) -> _TR3:
-        """Load an R-tree from a file path."""
+    @property
+    def depth(self) -> int:
+        """The number of nodes on the longest path from the root to a leaf."""
         ...
 
-
-class RStarTree2D:
-    """An R*-tree spatial index for 2D points.
-
-    Uses improved split heuristics over R-tree for better performance.
-    """
-
-    def __init__(self, max_entries: int) -> None:
-        """Create an R*-tree.
-
-        Args:
-            max_entries: Maximum entries per node (branching factor).
-        """
+    @property
+    def bounds(self) -> Optional[Tuple[float, ...]]:
+        """The extent of the stored points, as an (xmin, ymin, xmax, ymax) tuple, or None if empty."""
         ...
 
-    def insert(self, point: Point2D) -> None:
-        """Insert a point into the index."""
+    def batch(self) -> Batch:
+        """Start a batch of inserts and deletes, applied when the `with` block exits."""
         ...
 
-    def insert_bulk(self, points: List[Point2D]) -> None:
-        """Insert many points efficiently."""
+    def copy(self: _TK2) -> _TK2:
+        """Return a copy of the tree, sharing the point data with the original."""
         ...
 
-    def delete(self, point: Point2D) -> bool:
-        """Delete a point.
+    def __copy__(self: _TK2) -> _TK2: ...
 
-        Returns:
-            True if the point was found and removed.
-        """
+    def content_eq(self: _TK2, other: _TK2) -> bool:
+        """Return True if both trees hold the same points, counting duplicates."""
         ...
 
-    def knn_search(self, point: Point2D, k: int) -> List[Point2D]:
-        """Find k nearest neighbors to the query point."""
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
         ...
 
-    def range_search(self, point: Point2D, radius: float) -> List[Point2D]:
-        """Find all points within a radius of the query point."""
+    def check_invariants(self) -> None:
+        """Check the structural invariants of the tree.
+
+        Raises:
+            SpartError: If the tree is corrupted.
+        """
         ...
 
     def save(self, path: str) -> None:
@@ -454,27 +646,28 @@ class RStarTree2D:
         ...
 
     @classmethod
-    def load(cls: Type[_TRS2], path: str) -> _TRS2:
-        """Load an R*-tree from a file path."""
+    def load(cls: Type[_TK2], path: str, ids_only: bool = False) -> _TK2:
+        """Load a 2D k-d tree from a file path."""
         ...
 
 
-class RStarTree3D:
-    """An R*-tree spatial index for 3D points.
+class KdTree3D:
+    """A k-d tree for 3D points supporting NN and range queries.
 
-    Uses improved split heuristics over R-tree for better performance.
+    Uses Euclidean distance for searches.
     """
 
-    def __init__(self, max_entries: int) -> None:
-        """Create an R*-tree.
+    def __init__(self, ids_only: bool = False, schema: Optional[SchemaLike] = None) -> None:
+        """Create an empty 3D k-d tree.
 
         Args:
-            max_entries: Maximum entries per node (branching factor).
+            ids_only: Store integer ids as point data and return queries as NumPy arrays of ids.
+            schema: Store dict or dataclass payloads packed according to these fields.
         """
         ...
 
     def insert(self, point: Point3D) -> None:
-        """Insert a point into the index."""
+        """Insert a point; raises DimensionMismatchError on invalid input."""
         ...
 
     def insert_bulk(self, points: List[Point3D]) -> None:
@@ -489,19 +682,1120 @@ class RStarTree3D:
         """
         ...
 
-    def knn_search(self, point: Point3D, k: int) -> List[Point3D]:
-        """Find k nearest neighbors to the query point."""
+    def delete_bulk(self, points: Sequence[Point3D]) -> int:
+        """Delete several points at once.
+
+        Each point removes at most one matching stored point, as with `delete`.
+
+        Returns:
+            The number of points that were found and deleted.
+        """
         ...
 
-    def range_search(self, point: Point3D, radius: float) -> List[Point3D]:
-        """Find all points within a radius of the query point."""
+    def retain(self, keep: Callable[[Any], bool]) -> int:
+        """Keep only the points for which `keep(data)` is truthy, deleting the others.
+
+        If `keep` raises, the exception propagates; the points rejected before stay deleted.
+
+        Returns:
+            The number of points that were deleted.
+        """
         ...
 
-    def save(self, path: str) -> None:
-        """Serialize and save the tree to a file path."""
+    def knn_search(self, point: Point3D, k: int) -> Union[List[Point3D], IdArray]:
+        """Find k nearest neighbors to the query point (their ids in ids-only mode)."""
         ...
 
-    @classmethod
-    def load(cls: Type[_TRS3], path: str) -> _TRS3:
+    def range_search(self, point: Point3D, radius: float) -> Union[List[Point3D], IdArray]:
+        """Find all points within a radius of the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search_where(self, point: Point3D, radius: float, **conditions: Any) -> List[Point3D]:
+        """Find the points within a radius whose payloads meet the conditions (needs a schema).
+
+        Each condition is a value the field must equal, or a (min, max) tuple of inclusive
+        bounds with None for an open end.
+        """
+        ...
+
+    @property
+    def ids_only(self) -> bool:
+        """Whether point data are integer ids, returned by queries as NumPy arrays."""
+        ...
+
+    @property
+    def dims(self) -> int:
+        """The number of dimensions of the points (3)."""
+        ...
+
+    @property
+    def size(self) -> int:
+        """The number of stored points."""
+        ...
+
+    @property
+    def depth(self) -> int:
+        """The number of nodes on the longest path from the root to a leaf."""
+        ...
+
+    @property
+    def bounds(self) -> Optional[Tuple[float, ...]]:
+        """The extent of the stored points, as an (xmin, ymin, zmin, xmax, ymax, zmax) tuple, or None if empty."""
+        ...
+
+    def batch(self) -> Batch:
+        """Start a batch of inserts and deletes, applied when the `with` block exits."""
+        ...
+
+    def copy(self: _TK3) -> _TK3:
+        """Return a copy of the tree, sharing the point data with the original."""
+        ...
+
+    def __copy__(self: _TK3) -> _TK3: ...
+
+    def content_eq(self: _TK3, other: _TK3) -> bool:
+        """Return True if both trees hold the same points, counting duplicates."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def neighborhood_covariance(
+        self, point: Point3D, k: Optional[int] = None, radius: Optional[float] = None, eigen: bool = False
+    ) -> Optional[CovarianceDict]:
+        """Compute the covariance of the k nearest points or the points within a radius.
+
+        Pass exactly one of k and radius; with eigen=True the eigen-decomposition and the
+        estimated normal are added. Returns None if no point lies within the radius.
+        """
+        ...
+
+    def neighborhood_covariances(
+        self, points: Sequence[Point3D], k: Optional[int] = None, radius: Optional[float] = None, eigen: bool = False
+    ) -> List[Optional[CovarianceDict]]:
+        """Compute `neighborhood_covariance` for each point, without holding the GIL."""
+        ...
+
+    def check_invariants(self) -> None:
+        """Check the structural invariants of the tree.
+
+        Raises:
+            SpartError: If the tree is corrupted.
+        """
+        ...
+
+    def save(self, path: str) -> None:
+        """Serialize and save the tree to a file path."""
+        ...
+
+    @classmethod
+    def load(cls: Type[_TK3], path: str, ids_only: bool = False) -> _TK3:
+        """Load a 3D k-d tree from a file path."""
+        ...
+
+
+class RTree2D:
+    """An R-tree spatial index for 2D points.
+
+    Balanced hierarchical index optimized for rectangle queries.
+    """
+
+    def __init__(self, max_entries: int, ids_only: bool = False, schema: Optional[SchemaLike] = None) -> None:
+        """Create an R-tree.
+
+        Args:
+            max_entries: Maximum entries per node (branching factor).
+            ids_only: Store integer ids as point data and return queries as NumPy arrays of ids.
+            schema: Store dict or dataclass payloads packed according to these fields.
+        """
+        ...
+
+    def insert(self, point: Point2D) -> None:
+        """Insert a point into the index."""
+        ...
+
+    def insert_bulk(self, points: List[Point2D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point2D) -> bool:
+        """Delete a point.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def delete_bulk(self, points: Sequence[Point2D]) -> int:
+        """Delete several points at once.
+
+        Each point removes at most one matching stored point, as with `delete`.
+
+        Returns:
+            The number of points that were found and deleted.
+        """
+        ...
+
+    def retain(self, keep: Callable[[Any], bool]) -> int:
+        """Keep only the points for which `keep(data)` is truthy, deleting the others.
+
+        If `keep` raises, the exception propagates; the points rejected before stay deleted.
+
+        Returns:
+            The number of points that were deleted.
+        """
+        ...
+
+    def knn_search(self, point: Point2D, k: int) -> Union[List[Point2D], IdArray]:
+        """Find k nearest neighbors to the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search(self, point: Point2D, radius: float) -> Union[List[Point2D], IdArray]:
+        """Find all points within a radius of the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search_where(self, point: Point2D, radius: float, **conditions: Any) -> List[Point2D]:
+        """Find the points within a radius whose payloads meet the conditions (needs a schema).
+
+        Each condition is a value the field must equal, or a (min, max) tuple of inclusive
+        bounds with None for an open end.
+        """
+        ...
+
+    def range_search_bbox(self, window: RectangleLike) -> Union[List[Point2D], IdArray]:
+        """Find all points inside a query window (their ids in ids-only mode)."""
+        ...
+
+    @property
+    def ids_only(self) -> bool:
+        """Whether point data are integer ids, returned by queries as NumPy arrays."""
+        ...
+
+    @property
+    def dims(self) -> int:
+        """The number of dimensions of the points (2)."""
+        ...
+
+    @property
+    def size(self) -> int:
+        """The number of stored points."""
+        ...
+
+    @property
+    def depth(self) -> int:
+        """The number of nodes on the longest path from the root to a leaf."""
+        ...
+
+    @property
+    def bounds(self) -> Optional[Tuple[float, ...]]:
+        """The extent of the stored points, as an (xmin, ymin, xmax, ymax) tuple, or None if empty."""
+        ...
+
+    def batch(self) -> Batch:
+        """Start a batch of inserts and deletes, applied when the `with` block exits."""
+        ...
+
+    def copy(self: _TR2) -> _TR2:
+        """Return a copy of the tree, sharing the point data with the original."""
+        ...
+
+    def __copy__(self: _TR2) -> _TR2: ...
+
+    def content_eq(self: _TR2, other: _TR2) -> bool:
+        """Return True if both trees hold the same points, counting duplicates."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def check_invariants(self) -> None:
+        """Check the structural invariants of the tree.
+
+        Raises:
+            SpartError: If the tree is corrupted.
+        """
+        ...
+
+    def save(self, path: str) -> None:
+        """Serialize and save the tree to a file path."""
+        ...
+
+    @classmethod
+    def load(cls: Type[_TR2], path: str, ids_only: bool = False) -> _TR2:
+        """Load an R-tree from a file path."""
+        ...
+
+
+class RTree3D:
+    """An R-tree spatial index for 3D points.
+
+    Balanced hierarchical index optimized for rectangle queries.
+    """
+
+    def __init__(self, max_entries: int, ids_only: bool = False, schema: Optional[SchemaLike] = None) -> None:
+        """Create an R-tree.
+
+        Args:
+            max_entries: Maximum entries per node (branching factor).
+            ids_only: Store integer ids as point data and return queries as NumPy arrays of ids.
+            schema: Store dict or dataclass payloads packed according to these fields.
+        """
+        ...
+
+    def insert(self, point: Point3D) -> None:
+        """Insert a point into the index."""
+        ...
+
+    def insert_bulk(self, points: List[Point3D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point3D) -> bool:
+        """Delete a point.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def delete_bulk(self, points: Sequence[Point3D]) -> int:
+        """Delete several points at once.
+
+        Each point removes at most one matching stored point, as with `delete`.
+
+        Returns:
+            The number of points that were found and deleted.
+        """
+        ...
+
+    def retain(self, keep: Callable[[Any], bool]) -> int:
+        """Keep only the points for which `keep(data)` is truthy, deleting the others.
+
+        If `keep` raises, the exception propagates; the points rejected before stay deleted.
+
+        Returns:
+            The number of points that were deleted.
+        """
+        ...
+
+    def knn_search(self, point: Point3D, k: int) -> Union[List[Point3D], IdArray]:
+        """Find k nearest neighbors to the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search(self, point: Point3D, radius: float) -> Union[List[Point3D], IdArray]:
+        """Find all points within a radius of the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search_where(self, point: Point3D, radius: float, **conditions: Any) -> List[Point3D]:
+        """Find the points within a radius whose payloads meet the conditions (needs a schema).
+
+        Each condition is a value the field must equal, or a (min, max) tuple of inclusive
+        bounds with None for an open end.
+        """
+        ...
+
+    def range_search_bbox(self, window: CubeLike) -> Union[List[Point3D], IdArray]:
+        """Find all points inside a query window (their ids in ids-only mode)."""
+        ...
+
+    @property
+    def ids_only(self) -> bool:
+        """Whether point data are integer ids, returned by queries as NumPy arrays."""
+        ...
+
+    @property
+    def dims(self) -> int:
+        """The number of dimensions of the points (3)."""
+        ...
+
+    @property
+    def size(self) -> int:
+        """The number of stored points."""
+        ...
+
+    @property
+    def depth(self) -> int:
+        """The number of nodes on the longest path from the root to a leaf."""
+        ...
+
+    @property
+    def bounds(self) -> Optional[Tuple[float, ...]]:
+        """The extent of the stored points, as an (xmin, ymin, zmin, xmax, ymax, zmax) tuple, or None if empty."""
+        ...
+
+    def batch(self) -> Batch:
+        """Start a batch of inserts and deletes, applied when the `with` block exits."""
+        ...
+
+    def copy(self: _TR3) -> _TR3:
+        """Return a copy of the tree, sharing the point data with the original."""
+        ...
+
+    def __copy__(self: _TR3) -> _TR3: ...
+
+    def content_eq(self: _TR3, other: _TR3) -> bool:
+        """Return True if both trees hold the same points, counting duplicates."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def check_invariants(self) -> None:
+        """Check the structural invariants of the tree.
+
+        Raises:
+            SpartError: If the tree is corrupted.
+        """
+        ...
+
+    def save(self, path: str) -> None:
+        """Serialize and save the tree to a file path."""
+        ...
+
+    @classmethod
+    def load(cls: Type[_TR3], path: str, ids_only: bool = False) -> _TR3:
+        """Load an R-tree from a file path."""
+        ...
+
+
+class RStarTree2D:
+    """An R*-tree spatial index for 2D points.
+
+    Uses improved split heuristics over R-tree for better performance.
+    """
+
+    def __init__(self, max_entries: int, ids_only: bool = False, schema: Optional[SchemaLike] = None) -> None:
+        """Create an R*-tree.
+
+        Args:
+            max_entries: Maximum entries per node (branching factor).
+            ids_only: Store integer ids as point data and return queries as NumPy arrays of ids.
+            schema: Store dict or dataclass payloads packed according to these fields.
+        """
+        ...
+
+    def insert(self, point: Point2D) -> None:
+        """Insert a point into the index."""
+        ...
+
+    def insert_bulk(self, points: List[Point2D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point2D) -> bool:
+        """Delete a point.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def delete_bulk(self, points: Sequence[Point2D]) -> int:
+        """Delete several points at once.
+
+        Each point removes at most one matching stored point, as with `delete`.
+
+        Returns:
+            The number of points that were found and deleted.
+        """
+        ...
+
+    def retain(self, keep: Callable[[Any], bool]) -> int:
+        """Keep only the points for which `keep(data)` is truthy, deleting the others.
+
+        If `keep` raises, the exception propagates; the points rejected before stay deleted.
+
+        Returns:
+            The number of points that were deleted.
+        """
+        ...
+
+    def knn_search(self, point: Point2D, k: int) -> Union[List[Point2D], IdArray]:
+        """Find k nearest neighbors to the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search(self, point: Point2D, radius: float) -> Union[List[Point2D], IdArray]:
+        """Find all points within a radius of the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search_where(self, point: Point2D, radius: float, **conditions: Any) -> List[Point2D]:
+        """Find the points within a radius whose payloads meet the conditions (needs a schema).
+
+        Each condition is a value the field must equal, or a (min, max) tuple of inclusive
+        bounds with None for an open end.
+        """
+        ...
+
+    def range_search_bbox(self, window: RectangleLike) -> Union[List[Point2D], IdArray]:
+        """Find all points inside a query window (their ids in ids-only mode)."""
+        ...
+
+    @property
+    def ids_only(self) -> bool:
+        """Whether point data are integer ids, returned by queries as NumPy arrays."""
+        ...
+
+    @property
+    def dims(self) -> int:
+        """The number of dimensions of the points (2)."""
+        ...
+
+    @property
+    def size(self) -> int:
+        """The number of stored points."""
+        ...
+
+    @property
+    def depth(self) -> int:
+        """The number of nodes on the longest path from the root to a leaf."""
+        ...
+
+    @property
+    def bounds(self) -> Optional[Tuple[float, ...]]:
+        """The extent of the stored points, as an (xmin, ymin, xmax, ymax) tuple, or None if empty."""
+        ...
+
+    def batch(self) -> Batch:
+        """Start a batch of inserts and deletes, applied when the `with` block exits."""
+        ...
+
+    def copy(self: _TRS2) -> _TRS2:
+        """Return a copy of the tree, sharing the point data with the original."""
+        ...
+
+    def __copy__(self: _TRS2) -> _TRS2: ...
+
+    def content_eq(self: _TRS2, other: _TRS2) -> bool:
+        """Return True if both trees hold the same points, counting duplicates."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def check_invariants(self) -> None:
+        """Check the structural invariants of the tree.
+
+        Raises:
+            SpartError: If the tree is corrupted.
+        """
+        ...
+
+    def save(self, path: str) -> None:
+        """Serialize and save the tree to a file path."""
+        ...
+
+    @classmethod
+    def load(cls: Type[_TRS2], path: str, ids_only: bool = False) -> _TRS2:
         """Load an R*-tree from a file path."""
         ...
+
+
+class RStarTree3D:
+    """An R*-tree spatial index for 3D points.
+
+    Uses improved split heuristics over R-tree for better performance.
+    """
+
+    def __init__(self, max_entries: int, ids_only: bool = False, schema: Optional[SchemaLike] = None) -> None:
+        """Create an R*-tree.
+
+        Args:
+            max_entries: Maximum entries per node (branching factor).
+            ids_only: Store integer ids as point data and return queries as NumPy arrays of ids.
+            schema: Store dict or dataclass payloads packed according to these fields.
+        """
+        ...
+
+    def insert(self, point: Point3D) -> None:
+        """Insert a point into the index."""
+        ...
+
+    def insert_bulk(self, points: List[Point3D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point3D) -> bool:
+        """Delete a point.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def delete_bulk(self, points: Sequence[Point3D]) -> int:
+        """Delete several points at once.
+
+        Each point removes at most one matching stored point, as with `delete`.
+
+        Returns:
+            The number of points that were found and deleted.
+        """
+        ...
+
+    def retain(self, keep: Callable[[Any], bool]) -> int:
+        """Keep only the points for which `keep(data)` is truthy, deleting the others.
+
+        If `keep` raises, the exception propagates; the points rejected before stay deleted.
+
+        Returns:
+            The number of points that were deleted.
+        """
+        ...
+
+    def knn_search(self, point: Point3D, k: int) -> Union[List[Point3D], IdArray]:
+        """Find k nearest neighbors to the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search(self, point: Point3D, radius: float) -> Union[List[Point3D], IdArray]:
+        """Find all points within a radius of the query point (their ids in ids-only mode)."""
+        ...
+
+    def range_search_where(self, point: Point3D, radius: float, **conditions: Any) -> List[Point3D]:
+        """Find the points within a radius whose payloads meet the conditions (needs a schema).
+
+        Each condition is a value the field must equal, or a (min, max) tuple of inclusive
+        bounds with None for an open end.
+        """
+        ...
+
+    def range_search_bbox(self, window: CubeLike) -> Union[List[Point3D], IdArray]:
+        """Find all points inside a query window (their ids in ids-only mode)."""
+        ...
+
+    @property
+    def ids_only(self) -> bool:
+        """Whether point data are integer ids, returned by queries as NumPy arrays."""
+        ...
+
+    @property
+    def dims(self) -> int:
+        """The number of dimensions of the points (3)."""
+        ...
+
+    @property
+    def size(self) -> int:
+        """The number of stored points."""
+        ...
+
+    @property
+    def depth(self) -> int:
+        """The number of nodes on the longest path from the root to a leaf."""
+        ...
+
+    @property
+    def bounds(self) -> Optional[Tuple[float, ...]]:
+        """The extent of the stored points, as an (xmin, ymin, zmin, xmax, ymax, zmax) tuple, or None if empty."""
+        ...
+
+    def batch(self) -> Batch:
+        """Start a batch of inserts and deletes, applied when the `with` block exits."""
+        ...
+
+    def copy(self: _TRS3) -> _TRS3:
+        """Return a copy of the tree, sharing the point data with the original."""
+        ...
+
+    def __copy__(self: _TRS3) -> _TRS3: ...
+
+    def content_eq(self: _TRS3, other: _TRS3) -> bool:
+        """Return True if both trees hold the same points, counting duplicates."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def check_invariants(self) -> None:
+        """Check the structural invariants of the tree.
+
+        Raises:
+            SpartError: If the tree is corrupted.
+        """
+        ...
+
+    def save(self, path: str) -> None:
+        """Serialize and save the tree to a file path."""
+        ...
+
+    @classmethod
+    def load(cls: Type[_TRS3], path: str, ids_only: bool = False) -> _TRS3:
+        """Load an R*-tree from a file path."""
+        ...
+
+
+class ThreadSafeQuadtree:
+    """A quadtree that can be shared between Python threads.
+
+    Queries from several threads run concurrently and updates are applied one
+    at a time. The GIL is released while a method works on the tree.
+    """
+
+    def __init__(self, boundary: RectangleLike, capacity: int) -> None:
+        """Create an empty tree.
+
+        Args:
+            boundary: The root boundary, as a dict, an (xmin, ymin, xmax, ymax) tuple, or a Shapely box.
+            capacity: Max points per node before subdivision.
+        """
+        ...
+
+    def insert(self, point: Point2D) -> bool:
+        """Insert a point into the tree.
+
+        Returns:
+            True if insertion succeeded, False if the point lies outside the boundary.
+        """
+        ...
+
+    def insert_bulk(self, points: List[Point2D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point2D) -> bool:
+        """Delete a point from the tree.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def knn_search(self, point: Point2D, k: int) -> List[Point2D]:
+        """Find k nearest neighbors to the query point."""
+        ...
+
+    def range_search(self, point: Point2D, radius: float) -> List[Point2D]:
+        """Find all points within a radius of the query point."""
+        ...
+
+    async def knn_search_async(self, point: Point2D, k: int) -> List[Point2D]:
+        """Like `knn_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    async def range_search_async(self, point: Point2D, radius: float) -> List[Point2D]:
+        """Like `range_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of points in the tree."""
+        ...
+
+
+class ThreadSafeOctree:
+    """An octree that can be shared between Python threads.
+
+    Queries from several threads run concurrently and updates are applied one
+    at a time. The GIL is released while a method works on the tree.
+    """
+
+    def __init__(self, boundary: CubeLike, capacity: int) -> None:
+        """Create an empty tree.
+
+        Args:
+            boundary: The root boundary, as a dict or an (xmin, ymin, zmin, xmax, ymax, zmax) tuple.
+            capacity: Max points per node before subdivision.
+        """
+        ...
+
+    def insert(self, point: Point3D) -> bool:
+        """Insert a point into the tree.
+
+        Returns:
+            True if insertion succeeded, False if the point lies outside the boundary.
+        """
+        ...
+
+    def insert_bulk(self, points: List[Point3D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point3D) -> bool:
+        """Delete a point from the tree.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def knn_search(self, point: Point3D, k: int) -> List[Point3D]:
+        """Find k nearest neighbors to the query point."""
+        ...
+
+    def range_search(self, point: Point3D, radius: float) -> List[Point3D]:
+        """Find all points within a radius of the query point."""
+        ...
+
+    async def knn_search_async(self, point: Point3D, k: int) -> List[Point3D]:
+        """Like `knn_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    async def range_search_async(self, point: Point3D, radius: float) -> List[Point3D]:
+        """Like `range_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of points in the tree."""
+        ...
+
+
+class ThreadSafeKdTree2D:
+    """A 2D Kd-tree that can be shared between Python threads.
+
+    Queries from several threads run concurrently and updates are applied one
+    at a time. The GIL is released while a method works on the tree.
+    """
+
+    def __init__(self) -> None:
+        """Create an empty tree."""
+        ...
+
+    def insert(self, point: Point2D) -> bool:
+        """Insert a point into the tree.
+
+        Returns:
+            Always True.
+        """
+        ...
+
+    def insert_bulk(self, points: List[Point2D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point2D) -> bool:
+        """Delete a point from the tree.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def knn_search(self, point: Point2D, k: int) -> List[Point2D]:
+        """Find k nearest neighbors to the query point."""
+        ...
+
+    def range_search(self, point: Point2D, radius: float) -> List[Point2D]:
+        """Find all points within a radius of the query point."""
+        ...
+
+    async def knn_search_async(self, point: Point2D, k: int) -> List[Point2D]:
+        """Like `knn_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    async def range_search_async(self, point: Point2D, radius: float) -> List[Point2D]:
+        """Like `range_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of points in the tree."""
+        ...
+
+
+class ThreadSafeKdTree3D:
+    """A 3D Kd-tree that can be shared between Python threads.
+
+    Queries from several threads run concurrently and updates are applied one
+    at a time. The GIL is released while a method works on the tree.
+    """
+
+    def __init__(self) -> None:
+        """Create an empty tree."""
+        ...
+
+    def insert(self, point: Point3D) -> bool:
+        """Insert a point into the tree.
+
+        Returns:
+            Always True.
+        """
+        ...
+
+    def insert_bulk(self, points: List[Point3D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point3D) -> bool:
+        """Delete a point from the tree.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def knn_search(self, point: Point3D, k: int) -> List[Point3D]:
+        """Find k nearest neighbors to the query point."""
+        ...
+
+    def range_search(self, point: Point3D, radius: float) -> List[Point3D]:
+        """Find all points within a radius of the query point."""
+        ...
+
+    async def knn_search_async(self, point: Point3D, k: int) -> List[Point3D]:
+        """Like `knn_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    async def range_search_async(self, point: Point3D, radius: float) -> List[Point3D]:
+        """Like `range_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of points in the tree."""
+        ...
+
+
+class ThreadSafeRTree2D:
+    """A 2D R-tree that can be shared between Python threads.
+
+    Queries from several threads run concurrently and updates are applied one
+    at a time. The GIL is released while a method works on the tree.
+    """
+
+    def __init__(self, max_entries: int) -> None:
+        """Create an empty tree.
+
+        Args:
+            max_entries: Maximum entries per node (branching factor).
+        """
+        ...
+
+    def insert(self, point: Point2D) -> bool:
+        """Insert a point into the tree.
+
+        Returns:
+            Always True.
+        """
+        ...
+
+    def insert_bulk(self, points: List[Point2D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point2D) -> bool:
+        """Delete a point from the tree.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def knn_search(self, point: Point2D, k: int) -> List[Point2D]:
+        """Find k nearest neighbors to the query point."""
+        ...
+
+    def range_search(self, point: Point2D, radius: float) -> List[Point2D]:
+        """Find all points within a radius of the query point."""
+        ...
+
+    async def knn_search_async(self, point: Point2D, k: int) -> List[Point2D]:
+        """Like `knn_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    async def range_search_async(self, point: Point2D, radius: float) -> List[Point2D]:
+        """Like `range_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of points in the tree."""
+        ...
+
+
+class ThreadSafeRTree3D:
+    """A 3D R-tree that can be shared between Python threads.
+
+    Queries from several threads run concurrently and updates are applied one
+    at a time. The GIL is released while a method works on the tree.
+    """
+
+    def __init__(self, max_entries: int) -> None:
+        """Create an empty tree.
+
+        Args:
+            max_entries: Maximum entries per node (branching factor).
+        """
+        ...
+
+    def insert(self, point: Point3D) -> bool:
+        """Insert a point into the tree.
+
+        Returns:
+            Always True.
+        """
+        ...
+
+    def insert_bulk(self, points: List[Point3D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point3D) -> bool:
+        """Delete a point from the tree.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def knn_search(self, point: Point3D, k: int) -> List[Point3D]:
+        """Find k nearest neighbors to the query point."""
+        ...
+
+    def range_search(self, point: Point3D, radius: float) -> List[Point3D]:
+        """Find all points within a radius of the query point."""
+        ...
+
+    async def knn_search_async(self, point: Point3D, k: int) -> List[Point3D]:
+        """Like `knn_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    async def range_search_async(self, point: Point3D, radius: float) -> List[Point3D]:
+        """Like `range_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of points in the tree."""
+        ...
+
+
+class ThreadSafeRStarTree2D:
+    """A 2D R*-tree that can be shared between Python threads.
+
+    Queries from several threads run concurrently and updates are applied one
+    at a time. The GIL is released while a method works on the tree.
+    """
+
+    def __init__(self, max_entries: int) -> None:
+        """Create an empty tree.
+
+        Args:
+            max_entries: Maximum entries per node (branching factor).
+        """
+        ...
+
+    def insert(self, point: Point2D) -> bool:
+        """Insert a point into the tree.
+
+        Returns:
+            Always True.
+        """
+        ...
+
+    def insert_bulk(self, points: List[Point2D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point2D) -> bool:
+        """Delete a point from the tree.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def knn_search(self, point: Point2D, k: int) -> List[Point2D]:
+        """Find k nearest neighbors to the query point."""
+        ...
+
+    def range_search(self, point: Point2D, radius: float) -> List[Point2D]:
+        """Find all points within a radius of the query point."""
+        ...
+
+    async def knn_search_async(self, point: Point2D, k: int) -> List[Point2D]:
+        """Like `knn_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    async def range_search_async(self, point: Point2D, radius: float) -> List[Point2D]:
+        """Like `range_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of points in the tree."""
+        ...
+
+
+class ThreadSafeRStarTree3D:
+    """A 3D R*-tree that can be shared between Python threads.
+
+    Queries from several threads run concurrently and updates are applied one
+    at a time. The GIL is released while a method works on the tree.
+    """
+
+    def __init__(self, max_entries: int) -> None:
+        """Create an empty tree.
+
+        Args:
+            max_entries: Maximum entries per node (branching factor).
+        """
+        ...
+
+    def insert(self, point: Point3D) -> bool:
+        """Insert a point into the tree.
+
+        Returns:
+            Always True.
+        """
+        ...
+
+    def insert_bulk(self, points: List[Point3D]) -> None:
+        """Insert many points efficiently."""
+        ...
+
+    def delete(self, point: Point3D) -> bool:
+        """Delete a point from the tree.
+
+        Returns:
+            True if the point was found and removed.
+        """
+        ...
+
+    def knn_search(self, point: Point3D, k: int) -> List[Point3D]:
+        """Find k nearest neighbors to the query point."""
+        ...
+
+    def range_search(self, point: Point3D, radius: float) -> List[Point3D]:
+        """Find all points within a radius of the query point."""
+        ...
+
+    async def knn_search_async(self, point: Point3D, k: int) -> List[Point3D]:
+        """Like `knn_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    async def range_search_async(self, point: Point3D, radius: float) -> List[Point3D]:
+        """Like `range_search`, but runs on a worker thread without blocking the event loop."""
+        ...
+
+    def stats(self) -> TreeStatsDict:
+        """Return statistics about the shape of the tree."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the number of points in the tree."""
+        ...
@@ -0,0 +1,102 @@
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+/// A batch of inserts and deletes, applied to a tree in one go when the `with` block exits.
+///
+/// Batches are created by the `batch()` method of the trees. Operations are recorded in
+/// order and applied through the bulk insert and delete paths of the tree, so nodes are split,
+/// merged, and resized once per batch instead of once per point. If the `with` block raises
+/// an exception, the recorded operations are discarded and the tree is left unchanged.
+#[pyclass(name = "Batch")]
+pub struct PyBatch {
+    tree: PyObject,
+    /// The recorded operations, as `(is_insert, point)` pairs.
+    ops: Vec<(bool, PyObject)>,
+    closed: bool,
+}
+
+impl PyBatch {
+    pub fn new(tree: PyObject) -> Self {
+        PyBatch {
+            tree,
+            ops: Vec::new(),
+            closed: false,
+        }
+    }
+
+    fn record(&mut self, is_insert: bool, point: PyObject) -> PyResult<()> {
+        if self.closed {
+            return Err(PyRuntimeError::new_err(
+                "the batch has already been applied",
+            ));
+        }
+        self.ops.push((is_insert, point));
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PyBatch {
+    /// Records a point to insert when the batch is applied.
+    ///
+    /// Args:
+    ///     point: The point to insert.
+    fn insert(&mut self, point: PyObject) -> PyResult<()> {
+        self.record(true, point)
+    }
+
+    /// Records a point to delete when the batch is applied.
+    ///
+    /// Deleting a point inserted earlier in the same batch cancels the insert.
+    ///
+    /// Args:
+    ///     point: The point to delete.
+    fn delete(&mut self, point: PyObject) -> PyResult<()> {
+        self.record(false, point)
+    }
+
+    /// Returns the number of recorded operations.
+    fn __len__(&self) -> usize {
+        self.ops.len()
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Applies the recorded operations, unless the block raised an exception.
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        exc_type: Option<PyObject>,
+        _exc_value: Option<PyObject>,
+        _traceback: Option<PyObject>,
+    ) -> PyResult<bool> {
+        self.closed = true;
+        let ops = std::mem::take(&mut self.ops);
+        if exc_type.is_none() && !ops.is_empty() {
+            self.tree.call_method1(py, "_apply_batch", (ops,))?;
+        }
+        Ok(false)
+    }
+}
+
+/// Splits the operations of a batch into the points to delete and the points to insert.
+///
+/// A delete cancels the latest pending insert of an equal point. Applying the deletes before
+/// the inserts then gives the same result as applying the operations in order.
+pub fn split_ops<P: PartialEq>(ops: Vec<(bool, P)>) -> (Vec<P>, Vec<P>) {
+    let mut deletes = Vec::new();
+    let mut inserts: Vec<P> = Vec::new();
+    for (is_insert, point) in ops {
+        if is_insert {
+            inserts.push(point);
+        } else if let Some(pos) = inserts.iter().rposition(|p| *p == point) {
+            inserts.remove(pos);
+        } else {
+            deletes.push(point);
+        }
+    }
+    (deletes, inserts)
+}
@@ -0,0 +1,74 @@
+//! Python exception classes for the errors raised by the trees.
+//!
+//! `SpartError` is the base class of all of them and subclasses `ValueError`, so code written
+//! against earlier versions that catches `ValueError` keeps working. The subclasses let callers
+//! handle specific failures:
+//!
+//! - `InvalidCapacityError` - A tree was created with a capacity or entry count it can't use
+//! - `DimensionMismatchError` - A point doesn't have the dimension the tree expects
+//! - `OutOfBoundsError` - Coordinates are out of range or not finite
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use spart::errors::SpartError as CoreError;
+
+create_exception!(
+    pyspart,
+    SpartError,
+    PyValueError,
+    "Base class of the errors raised by pyspart."
+);
+create_exception!(
+    pyspart,
+    InvalidCapacityError,
+    SpartError,
+    "Raised when a tree is created with an invalid capacity or number of entries."
+);
+create_exception!(
+    pyspart,
+    DimensionMismatchError,
+    SpartError,
+    "Raised when a point's dimension doesn't match the tree's dimension."
+);
+create_exception!(
+    pyspart,
+    OutOfBoundsError,
+    SpartError,
+    "Raised when coordinates are out of range or not finite."
+);
+
+/// Converts an error from the trees into the matching Python exception.
+pub fn spart_error(e: CoreError) -> PyErr {
+    let message = e.to_string();
+    match e {
+        CoreError::InvalidCapacity { .. } => InvalidCapacityError::new_err(message),
+        CoreError::DimensionMismatch { .. } | CoreError::InvalidDimension { .. } => {
+            DimensionMismatchError::new_err(message)
+        }
+        CoreError::InvalidCoordinates { .. } => OutOfBoundsError::new_err(message),
+        _ => SpartError::new_err(message),
+    }
+}
+
+/// Converts a failure to save or load a tree into a `SpartError`.
+pub fn persistence_error(e: impl ToString) -> PyErr {
+    SpartError::new_err(e.to_string())
+}
+
+/// Adds the exception classes to the module.
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("SpartError", py.get_type::<SpartError>())?;
+    m.add(
+        "InvalidCapacityError",
+        py.get_type::<InvalidCapacityError>(),
+    )?;
+    m.add(
+        "DimensionMismatchError",
+        py.get_type::<DimensionMismatchError>(),
+    )?;
+    m.add("OutOfBoundsError", py.get_type::<OutOfBoundsError>())?;
+    Ok(())
+}
@@ -1,76 +1,152 @@
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyDict, PyList, PyString, PyTuple};
 use spart::geometry::{Cube, Rectangle};
 
+const RECTANGLE_FORMS: &str = "a dict with 'x', 'y', 'width' and 'height', a dict with 'min' \
+                               and 'max' corners, an (xmin, ymin, xmax, ymax) tuple, or an \
+                               object with a 'bounds' attribute such as a Shapely box";
+
+const CUBE_FORMS: &str = "a dict with 'x', 'y', 'z', 'width', 'height' and 'depth', a dict \
+                          with 'min' and 'max' corners, or an (xmin, ymin, zmin, xmax, ymax, \
+                          zmax) tuple";
+
+/// A rectangle (a boundary or query window) given in any of the accepted forms.
 #[derive(Clone)]
 pub struct PyRectangle(pub Rectangle);
 
 impl<'source> FromPyObject<'source> for PyRectangle {
     fn extract_bound(ob: &Bound<'source, PyAny>) -> PyResult<Self> {
-        let dict: &Bound<PyDict> = ob.downcast()?;
-        let x: f64 = dict
-            .get_item("x")?
-            .ok_or_else(|| PyValueError::new_err("missing 'x'"))?
-            .extract()?;
-        let y: f64 = dict
-            .get_item("y")?
-            .ok_or_else(|| PyValueError::new_err("missing 'y'"))?
-            .extract()?;
-        let width: f64 = dict
-            .get_item("width")?
-            .ok_or_else(|| PyValueError::new_err("missing 'width'"))?
-            .extract()?;
-        let height: f64 = dict
-            .get_item("height")?
-            .ok_or_else(|| PyValueError::new_err("missing 'height'"))?
-            .extract()?;
+        let bounds = if let Ok(dict) = ob.downcast::<PyDict>() {
+            if dict.contains("min")? || dict.contains("max")? {
+                corners(dict, 2, "rectangle")?
+            } else {
+                let v = dict_numbers(dict, &["x", "y", "width", "height"], "rectangle")?;
+                vec![v[0], v[1], v[0] + v[2], v[1] + v[3]]
+            }
+        } else if is_sequence(ob) {
+            numbers(ob, 4, "rectangle")?
+        } else if ob.hasattr("bounds")? {
+            numbers(&ob.getattr("bounds")?, 4, "rectangle bounds")?
+        } else {
+            return Err(unexpected(ob, RECTANGLE_FORMS));
+        };
+        let [min, max] = checked_corners::<2>(&bounds, ["x", "y"])?;
         Ok(PyRectangle(Rectangle {
-            x,
-            y,
-            width,
-            height,
+            x: min[0],
+            y: min[1],
+            width: max[0] - min[0],
+            height: max[1] - min[1],
         }))
     }
 }
 
+/// A cube (a boundary or query window) given in any of the accepted forms.
 #[derive(Clone)]
 pub struct PyCube(pub Cube);
 
 impl<'source> FromPyObject<'source> for PyCube {
     fn extract_bound(ob: &Bound<'source, PyAny>) -> PyResult<Self> {
-        let dict: &Bound<PyDict> = ob.downcast()?;
-        let x: f64 = dict
-            .get_item("x")?
-            .ok_or_else(|| PyValueError::new_err("missing 'x'"))?
-            .extract()?;
-        let y: f64 = dict
-            .get_item("y")?
-            .ok_or_else(|| PyValueError::new_err("missing 'y'"))?
-            .extract()?;
-        let z: f64 = dict
-            .get_item("z")?
-            .ok_or_else(|| PyValueError::new_err("missing 'z'"))?
-            .extract()?;
-        let width: f64 = dict
-            .get_item("width")?
-            .ok_or_else(|| PyValueError::new_err("missing 'width'"))?
-            .extract()?;
-        let height: f64 = dict
-            .get_item("height")?
-            .ok_or_else(|| PyValueError::new_err("missing 'height'"))?
-            .extract()?;
-        let depth: f64 = dict
-            .get_item("depth")?
-            .ok_or_else(|| PyValueError::new_err("missing 'depth'"))?
-            .extract()?;
+        let bounds = if let Ok(dict) = ob.downcast::<PyDict>() {
+            if dict.contains("min")? || dict.contains("max")? {
+                corners(dict, 3, "cube")?
+            } else {
+                let keys = ["x", "y", "z", "width", "height", "depth"];
+                let v = dict_numbers(dict, &keys, "cube")?;
+                vec![v[0], v[1], v[2], v[0] + v[3], v[1] + v[4], v[2] + v[5]]
+            }
+        } else if is_sequence(ob) {
+            numbers(ob, 6, "cube")?
+        } else {
+            return Err(unexpected(ob, CUBE_FORMS));
+        };
+        let [min, max] = checked_corners::<3>(&bounds, ["x", "y", "z"])?;
         Ok(PyCube(Cube {
-            x,
-            y,
-            z,
-            width,
-            height,
-            depth,
+            x: min[0],
+            y: min[1],
+            z: min[2],
+            width: max[0] - min[0],
+            height: max[1] - min[1],
+            depth: max[2] - min[2],
         }))
     }
 }
+
+fn is_sequence(ob: &Bound<'_, PyAny>) -> bool {
+    ob.is_instance_of::<PyTuple>() || ob.is_instance_of::<PyList>()
+}
+
+/// Reads the numbers stored under `keys` in a dict.
+fn dict_numbers(dict: &Bound<'_, PyDict>, keys: &[&str], what: &str) -> PyResult<Vec<f64>> {
+    keys.iter()
+        .map(|key| {
+            let item = dict
+                .get_item(key)?
+                .ok_or_else(|| PyValueError::new_err(format!("{what} is missing '{key}'")))?;
+            item.extract().map_err(|_| {
+                PyTypeError::new_err(format!("{what} '{key}' must be a number, got {item:?}"))
+            })
+        })
+        .collect()
+}
+
+/// Reads the `min` and `max` corners of a dict, each a sequence of `dims` numbers, as the
+/// numbers of the lower corner followed by those of the upper corner.
+fn corners(dict: &Bound<'_, PyDict>, dims: usize, what: &str) -> PyResult<Vec<f64>> {
+    let mut bounds = Vec::with_capacity(2 * dims);
+    for key in ["min", "max"] {
+        let corner = dict
+            .get_item(key)?
+            .ok_or_else(|| PyValueError::new_err(format!("{what} is missing '{key}'")))?;
+        bounds.extend(numbers(&corner, dims, &format!("{what} '{key}' corner"))?);
+    }
+    Ok(bounds)
+}
+
+/// Reads a sequence of exactly `count` numbers.
+fn numbers(ob: &Bound<'_, PyAny>, count: usize, what: &str) -> PyResult<Vec<f64>> {
+    if ob.is_instance_of::<PyString>() {
+        return Err(PyTypeError::new_err(format!(
+            "{what} must be a sequence of numbers, got a string"
+        )));
+    }
+    let values: Vec<f64> = ob.extract().map_err(|_| {
+        PyTypeError::new_err(format!("{what} must be a sequence of numbers, got {ob:?}"))
+    })?;
+    if values.len() != count {
+        return Err(PyValueError::new_err(format!(
+            "{what} must have {count} numbers, got {}",
+            values.len()
+        )));
+    }
+    Ok(values)
+}
+
+/// Splits `bounds` (the lower corner followed by the upper corner) into the two corners,
+/// checking that they are finite and ordered.
+fn checked_corners<const D: usize>(bounds: &[f64], axes: [&str; D]) -> PyResult<[[f64; D]; 2]> {
+    let min: [f64; D] = std::array::from_fn(|i| bounds[i]);
+    let max: [f64; D] = std::array::from_fn(|i| bounds[D + i]);
+    for (axis, (lo, hi)) in axes.iter().zip(min.iter().zip(&max)) {
+        if !lo.is_finite() || !hi.is_finite() {
+            return Err(PyValueError::new_err(format!(
+                "the {axis} bounds must be finite, got {lo} and {hi}"
+            )));
+        }
+        if hi < lo {
+            return Err(PyValueError::new_err(format!(
+                "the upper {axis} bound {hi} is less than the lower bound {lo}"
+            )));
+        }
+    }
+    Ok([min, max])
+}
+
+fn unexpected(ob: &Bound<'_, PyAny>, forms: &str) -> PyErr {
+    let type_name = ob
+        .get_type()
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|_| "object".to_string());
+    PyTypeError::new_err(format!("expected {forms}, got {type_name}"))
+}
@@ -5,6 +5,8 @@
 //!
 //! # Module Organization
 //!
+//! - `batch` - Context manager for batched inserts and deletes
+//! - `errors` - Python exception classes for the errors raised by the trees
 //! - `types` - PyData wrapper for bridging Python objects with Rust
 //! - `geometry` - Geometric boundary extractors (PyRectangle, PyCube)
 //! - `point2d` and `point3d` - Point type implementations
@@ -13,6 +15,8 @@
 //! - `kdtree` - K-dimensional trees for nearest neighbor search
 //! - `rtree` - R-tree spatial index
 //! - `rstar_tree` - R*-tree with improved split heuristics
+//! - `schema` - Payload schemas storing dict and dataclass payloads as packed values
+//! - `thread_safe` - Tree classes that can be shared between Python threads
 //!
 //! # Key Design Notes
 //!
@@ -44,6 +48,8 @@
 
 use pyo3::prelude::*;
 
+mod batch;
+mod errors;
 mod geometry;
 mod kdtree;
 mod octree;
@@ -52,8 +58,11 @@ mod point3d;
 mod quadtree;
 mod rstar_tree;
 mod rtree;
+mod schema;
+mod thread_safe;
 mod types;
 
+use batch::PyBatch;
 use kdtree::{PyKdTree2D, PyKdTree3D};
 use octree::PyOctree;
 use point2d::PyPoint2D;
@@ -61,9 +70,15 @@ use point3d::PyPoint3D;
 use quadtree::PyQuadtree;
 use rstar_tree::{PyRStarTree2D, PyRStarTree3D};
 use rtree::{PyRTree2D, PyRTree3D};
+use thread_safe::{
+    PyThreadSafeKdTree2D, PyThreadSafeKdTree3D, PyThreadSafeOctree, PyThreadSafeQuadtree,
+    PyThreadSafeRStarTree2D, PyThreadSafeRStarTree3D, PyThreadSafeRTree2D, PyThreadSafeRTree3D,
+};
 
 #[pymodule]
 fn pyspart(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    errors::register(m)?;
+    m.add_class::<PyBatch>()?;
     m.add_class::<PyPoint2D>()?;
     m.add_class::<PyPoint3D>()?;
     m.add_class::<PyQuadtree>()?;
@@ -74,5 +89,13 @@ fn pyspart(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyRTree3D>()?;
     m.add_class::<PyRStarTree2D>()?;
     m.add_class::<PyRStarTree3D>()?;
+    m.add_class::<PyThreadSafeQuadtree>()?;
+    m.add_class::<PyThreadSafeOctree>()?;
+    m.add_class::<PyThreadSafeKdTree2D>()?;
+    m.add_class::<PyThreadSafeKdTree3D>()?;
+    m.add_class::<PyThreadSafeRTree2D>()?;
+    m.add_class::<PyThreadSafeRTree3D>()?;
+    m.add_class::<PyThreadSafeRStarTree2D>()?;
+    m.add_class::<PyThreadSafeRStarTree3D>()?;
     Ok(())
 }
@@ -1,27 +1,101 @@
-use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::PyType;
+use pyo3::types::{PyDict, PyTuple, PyType};
 use std::fs::File;
+use std::sync::Arc;
 
 use spart::geometry::{EuclideanDistance, Point3D};
 use spart::octree::Octree;
 
+use crate::batch::{PyBatch, split_ops};
+use crate::errors::{persistence_error, spart_error};
 use crate::geometry::PyCube;
 use crate::point3d::PyPoint3D;
-use crate::types::PyData;
+use crate::schema::{PayloadSchema, RecordFilter};
+use crate::types::{
+    PyData, bounds_tuple, check_id, covariance_dict, ids_array, keep_payload, neighborhood_arg,
+    tree_stats_dict,
+};
 
 #[pyclass(name = "Octree")]
+#[derive(Clone)]
 pub struct PyOctree {
     tree: Octree<PyData>,
+    /// Whether the payloads are integer ids, returned by queries as NumPy arrays.
+    ids_only: bool,
+    /// The schema payloads are packed with, if any.
+    schema: Option<Arc<PayloadSchema>>,
 }
 
 #[pymethods]
 impl PyOctree {
     #[new]
-    fn new(boundary: PyCube, capacity: usize) -> PyResult<Self> {
-        let tree =
-            Octree::new(&boundary.0, capacity).map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(PyOctree { tree })
+    #[pyo3(signature = (boundary, capacity, ids_only = false, schema = None))]
+    fn new(
+        boundary: PyCube,
+        capacity: usize,
+        ids_only: bool,
+        schema: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let tree = Octree::new(&boundary.0, capacity).map_err(spart_error)?;
+        let schema = PayloadSchema::for_tree(ids_only, schema)?;
+        Ok(PyOctree {
+            tree,
+            ids_only,
+            schema,
+        })
+    }
+
+    /// Whether the payloads are integer ids, returned by queries as NumPy arrays.
+    #[getter]
+    fn ids_only(&self) -> bool {
+        self.ids_only
+    }
+
+    /// The number of dimensions of the points.
+    #[getter]
+    fn dims(&self) -> usize {
+        3
+    }
+
+    /// The number of stored points.
+    #[getter]
+    fn size(&self) -> usize {
+        self.tree.summary().size
+    }
+
+    /// The number of nodes on the longest path from the root to a leaf.
+    #[getter]
+    fn depth(&self) -> usize {
+        self.tree.summary().depth
+    }
+
+    /// The boundary of the tree, as an `(xmin, ymin, zmin, xmax, ymax, zmax)` tuple.
+    #[getter]
+    fn bounds<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyTuple>>> {
+        bounds_tuple(py, &self.tree.summary())
+    }
+
+    /// Returns a copy of the tree. The point data are shared with the original, not copied.
+    ///
+    /// Returns:
+    ///     The copy, in the same ids-only mode.
+    fn copy(&self) -> Self {
+        self.clone()
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    /// Checks whether two trees hold the same points, regardless of how they are arranged.
+    ///
+    /// Args:
+    ///     other: The tree to compare with.
+    ///
+    /// Returns:
+    ///     bool: True if both trees hold the same points, counting duplicates.
+    fn content_eq(&self, other: PyRef<'_, Self>) -> bool {
+        self.tree.content_eq(&other.tree)
     }
 
     /// Inserts a point into the octree.
@@ -31,17 +105,58 @@ impl PyOctree {
     ///
     /// Returns:
     ///     bool: True if the point was successfully inserted, False otherwise.
-    fn insert(&mut self, point: PyPoint3D) -> bool {
-        self.tree.insert(point.into())
+    fn insert(&mut self, point: PyPoint3D) -> PyResult<bool> {
+        check_id(self.ids_only, &point.data)?;
+        Ok(self
+            .tree
+            .insert(point.pack(self.schema.as_ref(), self.ids_only)?))
     }
 
     /// Inserts multiple points into the octree efficiently.
     ///
     /// Args:
     ///     points (list[Point3D]): A list of points to insert.
-    fn insert_bulk(&mut self, points: Vec<PyPoint3D>) {
-        let rust_points: Vec<Point3D<PyData>> = points.into_iter().map(|p| p.into()).collect();
+    fn insert_bulk(&mut self, points: Vec<PyPoint3D>) -> PyResult<()> {
+        for point in &points {
+            check_id(self.ids_only, &point.data)?;
+        }
+        let rust_points = points
+            .into_iter()
+            .map(|p| p.pack(self.schema.as_ref(), self.ids_only))
+            .collect::<PyResult<Vec<_>>>()?;
         self.tree.insert_bulk(&rust_points);
+        Ok(())
+    }
+
+    /// Starts a batch of inserts and deletes, applied when the `with` block exits.
+    ///
+    /// Returns:
+    ///     Batch: A context manager recording `insert` and `delete` calls.
+    fn batch(slf: Bound<'_, Self>) -> PyBatch {
+        PyBatch::new(slf.into_any().unbind())
+    }
+
+    fn _apply_batch(&mut self, ops: Vec<(bool, PyPoint3D)>) -> PyResult<()> {
+        for (is_insert, point) in &ops {
+            if *is_insert {
+                check_id(self.ids_only, &point.data)?;
+            }
+        }
+        let ops = ops
+            .into_iter()
+            .map(|(is_insert, p)| {
+                let p = if is_insert {
+                    p.pack(self.schema.as_ref(), self.ids_only)?
+                } else {
+                    p.into()
+                };
+                Ok((is_insert, p))
+            })
+            .collect::<PyResult<_>>()?;
+        let (deletes, inserts): (Vec<Point3D<PyData>>, _) = split_ops(ops);
+        self.tree.delete_bulk(&deletes);
+        self.tree.insert_bulk(&inserts);
+        Ok(())
     }
 
     /// Deletes a point from the octree.
@@ -56,6 +171,40 @@ impl PyOctree {
         self.tree.delete(&p)
     }
 
+    /// Deletes several points at once.
+    ///
+    /// Each point removes at most one matching stored point, as with `delete`.
+    ///
+    /// Args:
+    ///     points (list[Point3D]): The points to delete.
+    ///
+    /// Returns:
+    ///     int: The number of points that were found and deleted.
+    fn delete_bulk(&mut self, points: Vec<PyPoint3D>) -> usize {
+        let points: Vec<Point3D<PyData>> = points.into_iter().map(|p| p.into()).collect();
+        self.tree.delete_bulk(&points)
+    }
+
+    /// Keeps only the points whose data pass a test, deleting the others.
+    ///
+    /// Args:
+    ///     keep (Callable[[Any], bool]): Called with the data of each stored point; the point
+    ///         is kept if it returns a truthy value.
+    ///
+    /// Returns:
+    ///     int: The number of points that were deleted.
+    ///
+    /// Raises:
+    ///     Exception: Whatever `keep` raises. The points rejected before that stay deleted
+    ///         and no further points are deleted.
+    fn retain(&mut self, keep: Bound<'_, PyAny>) -> PyResult<usize> {
+        let mut error = None;
+        let deleted = self
+            .tree
+            .retain(|p| keep_payload(&keep, p.data.as_ref(), &mut error));
+        error.map_or(Ok(deleted), Err)
+    }
+
     /// Finds the k nearest neighbors to the given point.
     ///
     /// Args:
@@ -63,14 +212,16 @@ impl PyOctree {
     ///     k (int): The number of nearest neighbors to find.
     ///
     /// Returns:
-    ///     list[Point3D]: A list of the k nearest points found.
-    fn knn_search(&self, point: PyPoint3D, k: usize) -> Vec<PyPoint3D> {
+    ///     list[Point3D]: A list of the k nearest points found, or in ids-only mode a
+    ///     NumPy array of their ids.
+    fn knn_search(&self, py: Python<'_>, point: PyPoint3D, k: usize) -> PyResult<PyObject> {
         let p: Point3D<PyData> = point.into();
-        self.tree
-            .knn_search::<EuclideanDistance>(&p, k)
-            .into_iter()
-            .map(|p| (&p).into())
-            .collect()
+        let results = self.tree.knn_search::<EuclideanDistance>(&p, k);
+        if self.ids_only {
+            return ids_array(py, results.iter().map(|p| p.data.as_ref()));
+        }
+        let points: Vec<PyPoint3D> = results.into_iter().map(|p| (&p).into()).collect();
+        Ok(points.into_pyobject(py)?.into_any().unbind())
     }
 
     /// Finds all points within a given radius of the query point.
@@ -80,37 +231,164 @@ impl PyOctree {
     ///     radius (float): The search radius (using Euclidean distance).
     ///
     /// Returns:
-    ///     list[Point3D]: All points within the specified radius.
-    fn range_search(&self, point: PyPoint3D, radius: f64) -> Vec<PyPoint3D> {
+    ///     list[Point3D]: All points within the specified radius, or in ids-only mode a
+    ///     NumPy array of their ids.
+    fn range_search(&self, py: Python<'_>, point: PyPoint3D, radius: f64) -> PyResult<PyObject> {
+        let p: Point3D<PyData> = point.into();
+        let results = self.tree.range_search::<EuclideanDistance>(&p, radius);
+        if self.ids_only {
+            return ids_array(py, results.iter().map(|p| p.data.as_ref()));
+        }
+        let points: Vec<PyPoint3D> = results.into_iter().map(|p| (&p).into()).collect();
+        Ok(points.into_pyobject(py)?.into_any().unbind())
+    }
+
+    /// Finds the points within a given radius of the query point whose payloads meet all the
+    /// given conditions. The tree must have a payload schema; the search and the filtering run
+    /// without holding the GIL.
+    ///
+    /// Args:
+    ///     point (Point3D): The center point to search from.
+    ///     radius (float): The search radius (using Euclidean distance).
+    ///     **conditions: For each field to filter on, a value the field must equal, or a
+    ///         `(min, max)` tuple of inclusive bounds with None for an open end.
+    ///
+    /// Returns:
+    ///     list[Point3D]: The matching points within the specified radius.
+    #[pyo3(signature = (point, radius, **conditions))]
+    fn range_search_where(
+        &self,
+        py: Python<'_>,
+        point: PyPoint3D,
+        radius: f64,
+        conditions: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Vec<PyPoint3D>> {
+        let filter = RecordFilter::new(self.schema.as_ref(), conditions)?;
         let p: Point3D<PyData> = point.into();
-        self.tree
-            .range_search::<EuclideanDistance>(&p, radius)
+        let results = py.allow_threads(|| {
+            let mut results = self.tree.range_search::<EuclideanDistance>(&p, radius);
+            results.retain(|p| filter.matches(p.data.as_ref()));
+            results
+        });
+        Ok(results.into_iter().map(|p| (&p).into()).collect())
+    }
+
+    /// Returns statistics about the shape of the tree.
+    ///
+    /// Returns:
+    ///     dict: The number of stored points (`size`), nodes (`node_count`), and leaves
+    ///     (`leaf_count`), the `depth` of the tree, and the fraction of the node capacity in
+    ///     use over all nodes (`fill_factor`, None for k-d trees).
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        tree_stats_dict(py, &self.tree.stats())
+    }
+
+    /// Computes the covariance of the neighborhood of a point, e.g., to estimate the surface
+    /// normal of a point cloud there. Pass exactly one of `k` and `radius`.
+    ///
+    /// Args:
+    ///     point (Point3D): The query point.
+    ///     k (int, optional): The number of nearest points making up the neighborhood.
+    ///     radius (float, optional): The radius of the neighborhood.
+    ///     eigen (bool): Whether to add the eigen-decomposition of the covariance matrix.
+    ///
+    /// Returns:
+    ///     dict | None: The number of neighbors (`count`), their `mean` and `covariance`
+    ///     matrix, and with `eigen` the `eigenvalues` in ascending order, the matching unit
+    ///     `eigenvectors`, the estimated `normal`, and the `surface_variation`. None if no
+    ///     point lies within the radius.
+    ///
+    /// Raises:
+    ///     SpartError: If `k` is zero, the tree is empty, or the radius is negative.
+    #[pyo3(signature = (point, k = None, radius = None, eigen = false))]
+    fn neighborhood_covariance<'py>(
+        &self,
+        py: Python<'py>,
+        point: PyPoint3D,
+        k: Option<usize>,
+        radius: Option<f64>,
+        eigen: bool,
+    ) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let neighborhood = neighborhood_arg(k, radius)?;
+        let p: Point3D<PyData> = point.into();
+        let covariance = self
+            .tree
+            .neighborhood_covariance(&p, neighborhood)
+            .map_err(spart_error)?;
+        covariance_dict(py, covariance, eigen)
+    }
+
+    /// Computes the covariance of the neighborhood of each of several points, e.g., to
+    /// estimate the normals of a whole point cloud. The GIL is released while the
+    /// neighborhoods are searched and their covariances computed.
+    ///
+    /// Args:
+    ///     points (list[Point3D]): The query points.
+    ///     k (int, optional): The number of nearest points making up each neighborhood.
+    ///     radius (float, optional): The radius of each neighborhood.
+    ///     eigen (bool): Whether to add the eigen-decompositions of the covariance matrices.
+    ///
+    /// Returns:
+    ///     list[dict | None]: The result of `neighborhood_covariance` for each point.
+    ///
+    /// Raises:
+    ///     SpartError: If `k` is zero, the tree is empty, or the radius is negative.
+    #[pyo3(signature = (points, k = None, radius = None, eigen = false))]
+    fn neighborhood_covariances<'py>(
+        &self,
+        py: Python<'py>,
+        points: Vec<PyPoint3D>,
+        k: Option<usize>,
+        radius: Option<f64>,
+        eigen: bool,
+    ) -> PyResult<Vec<Option<Bound<'py, PyDict>>>> {
+        let neighborhood = neighborhood_arg(k, radius)?;
+        let points: Vec<Point3D<PyData>> = points.into_iter().map(|p| p.into()).collect();
+        let covariances = py
+            .allow_threads(|| self.tree.neighborhood_covariances(&points, neighborhood))
+            .map_err(spart_error)?;
+        covariances
             .into_iter()
-            .map(|p| (&p).into())
+            .map(|covariance| covariance_dict(py, covariance, eigen))
             .collect()
     }
 
+    /// Checks the structural invariants of the tree, e.g. after loading it from a file.
+    ///
+    /// Raises:
+    ///     SpartError: If the tree is corrupted, with a description of the first violation.
+    fn check_invariants(&self) -> PyResult<()> {
+        self.tree.check_invariants().map_err(spart_error)
+    }
+
     /// Saves the tree to a file.
     ///
     /// Args:
     ///     path (str): The path to the file.
     fn save(&self, path: &str) -> PyResult<()> {
         let file = File::create(path)?;
-        bincode::serialize_into(file, &self.tree).map_err(|e| PyValueError::new_err(e.to_string()))
+        bincode::serialize_into(file, &self.tree).map_err(persistence_error)
     }
 
     /// Loads a tree from a file.
     ///
     /// Args:
     ///     path (str): The path to the file.
+    ///     ids_only (bool): Whether to open the tree in ids-only mode (the mode is not saved).
+    ///
+    /// The payload schema is not saved either; payloads are loaded as plain objects.
     ///
     /// Returns:
     ///     The loaded tree.
     #[classmethod]
-    fn load(_cls: &Bound<PyType>, path: &str) -> PyResult<Self> {
+    #[pyo3(signature = (path, ids_only = false))]
+    fn load(_cls: &Bound<PyType>, path: &str, ids_only: bool) -> PyResult<Self> {
         let file = File::open(path)?;
-        let tree =
-            bincode::deserialize_from(file).map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(PyOctree { tree })
+        let tree = bincode::deserialize_from(file).map_err(persistence_error)?;
+        Ok(PyOctree {
+            tree,
+            ids_only,
+            schema: None,
+        })
     }
 }
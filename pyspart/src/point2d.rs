@@ -1,7 +1,9 @@
 use pyo3::basic::CompareOp;
 use pyo3::prelude::*;
 use spart::geometry::Point2D;
+use std::sync::Arc;
 
+use crate::schema::PayloadSchema;
 use crate::types::PyData;
 
 #[pyclass(name = "Point2D", get_all)]
@@ -55,9 +57,28 @@ impl PyPoint2D {
     }
 }
 
+impl PyPoint2D {
+    /// Converts the point for storage in a tree, packing its data if the tree has a payload
+    /// schema and storing its id natively if the tree is in ids-only mode.
+    pub fn pack(
+        self,
+        schema: Option<&Arc<PayloadSchema>>,
+        ids_only: bool,
+    ) -> PyResult<Point2D<PyData>> {
+        if ids_only {
+            return Ok(Point2D::new(self.x, self.y, Some(PyData::id(self.data))));
+        }
+        let Some(schema) = schema else {
+            return Ok(self.into());
+        };
+        let data = Python::with_gil(|py| schema.pack(self.data.bind(py)))?;
+        Ok(Point2D::new(self.x, self.y, Some(data)))
+    }
+}
+
 impl From<PyPoint2D> for Point2D<PyData> {
     fn from(p: PyPoint2D) -> Self {
-        Point2D::new(p.x, p.y, Some(PyData(p.data)))
+        Point2D::new(p.x, p.y, Some(PyData::new(p.data)))
     }
 }
 
@@ -70,8 +91,7 @@ impl From<&Point2D<PyData>> for PyPoint2D {
                 .data
                 .as_ref()
                 .expect("Point2D data should not be None in Python bindings")
-                .0
-                .clone_ref(py),
+                .to_object(py),
         })
     }
 }
@@ -1,7 +1,9 @@
 use pyo3::basic::CompareOp;
 use pyo3::prelude::*;
 use spart::geometry::Point3D;
+use std::sync::Arc;
 
+use crate::schema::PayloadSchema;
 use crate::types::PyData;
 
 #[pyclass(name = "Point3D", get_all)]
@@ -58,9 +60,33 @@ impl PyPoint3D {
     }
 }
 
+impl PyPoint3D {
+    /// Converts the point for storage in a tree, packing its data if the tree has a payload
+    /// schema and storing its id natively if the tree is in ids-only mode.
+    pub fn pack(
+        self,
+        schema: Option<&Arc<PayloadSchema>>,
+        ids_only: bool,
+    ) -> PyResult<Point3D<PyData>> {
+        if ids_only {
+            return Ok(Point3D::new(
+                self.x,
+                self.y,
+                self.z,
+                Some(PyData::id(self.data)),
+            ));
+        }
+        let Some(schema) = schema else {
+            return Ok(self.into());
+        };
+        let data = Python::with_gil(|py| schema.pack(self.data.bind(py)))?;
+        Ok(Point3D::new(self.x, self.y, self.z, Some(data)))
+    }
+}
+
 impl From<PyPoint3D> for Point3D<PyData> {
     fn from(p: PyPoint3D) -> Self {
-        Point3D::new(p.x, p.y, p.z, Some(PyData(p.data)))
+        Point3D::new(p.x, p.y, p.z, Some(PyData::new(p.data)))
     }
 }
 
@@ -74,8 +100,7 @@ impl From<&Point3D<PyData>> for PyPoint3D {
                 .data
                 .as_ref()
                 .expect("Point3D data should not be None in Python bindings")
-                .0
-                .clone_ref(py),
+                .to_object(py),
         })
     }
 }
@@ -1,35 +1,151 @@
-use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::PyType;
+use pyo3::types::{PyDict, PyTuple, PyType};
 use std::fs::File;
+use std::sync::Arc;
 
 use spart::geometry::{EuclideanDistance, Point2D, Point3D};
 use spart::rtree::RTree;
 
+use crate::batch::{PyBatch, split_ops};
+use crate::errors::{persistence_error, spart_error};
+use crate::geometry::{PyCube, PyRectangle};
 use crate::point2d::PyPoint2D;
 use crate::point3d::PyPoint3D;
-use crate::types::PyData;
+use crate::schema::{PayloadSchema, RecordFilter};
+use crate::types::{PyData, bounds_tuple, check_id, ids_array, keep_payload, tree_stats_dict};
 
 #[pyclass(name = "RTree2D")]
+#[derive(Clone)]
 pub struct PyRTree2D {
     tree: RTree<Point2D<PyData>>,
+    /// Whether the payloads are integer ids, returned by queries as NumPy arrays.
+    ids_only: bool,
+    /// The schema payloads are packed with, if any.
+    schema: Option<Arc<PayloadSchema>>,
 }
 
 #[pymethods]
 impl PyRTree2D {
     #[new]
-    fn new(max_entries: usize) -> PyResult<Self> {
-        let tree = RTree::new(max_entries).map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(PyRTree2D { tree })
+    #[pyo3(signature = (max_entries, ids_only = false, schema = None))]
+    fn new(
+        max_entries: usize,
+        ids_only: bool,
+        schema: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let tree = RTree::new(max_entries).map_err(spart_error)?;
+        let schema = PayloadSchema::for_tree(ids_only, schema)?;
+        Ok(PyRTree2D {
+            tree,
+            ids_only,
+            schema,
+        })
     }
 
-    fn insert(&mut self, point: PyPoint2D) {
-        self.tree.insert(point.into())
+    /// Whether the payloads are integer ids, returned by queries as NumPy arrays.
+    #[getter]
+    fn ids_only(&self) -> bool {
+        self.ids_only
     }
 
-    fn insert_bulk(&mut self, points: Vec<PyPoint2D>) {
-        let rust_points: Vec<Point2D<PyData>> = points.into_iter().map(|p| p.into()).collect();
+    /// The number of dimensions of the points.
+    #[getter]
+    fn dims(&self) -> usize {
+        2
+    }
+
+    /// The number of stored points.
+    #[getter]
+    fn size(&self) -> usize {
+        self.tree.summary().size
+    }
+
+    /// The number of nodes on the longest path from the root to a leaf.
+    #[getter]
+    fn depth(&self) -> usize {
+        self.tree.summary().depth
+    }
+
+    /// The extent of the stored points, as an `(xmin, ymin, xmax, ymax)` tuple, or
+    /// None if the tree is empty.
+    #[getter]
+    fn bounds<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyTuple>>> {
+        bounds_tuple(py, &self.tree.summary())
+    }
+
+    /// Returns a copy of the tree. The point data are shared with the original, not copied.
+    ///
+    /// Returns:
+    ///     The copy, in the same ids-only mode.
+    fn copy(&self) -> Self {
+        self.clone()
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    /// Checks whether two trees hold the same points, regardless of how they are arranged.
+    ///
+    /// Args:
+    ///     other: The tree to compare with.
+    ///
+    /// Returns:
+    ///     bool: True if both trees hold the same points, counting duplicates.
+    fn content_eq(&self, other: PyRef<'_, Self>) -> bool {
+        self.tree.content_eq(&other.tree)
+    }
+
+    fn insert(&mut self, point: PyPoint2D) -> PyResult<()> {
+        check_id(self.ids_only, &point.data)?;
+        self.tree
+            .insert(point.pack(self.schema.as_ref(), self.ids_only)?);
+        Ok(())
+    }
+
+    fn insert_bulk(&mut self, points: Vec<PyPoint2D>) -> PyResult<()> {
+        for point in &points {
+            check_id(self.ids_only, &point.data)?;
+        }
+        let rust_points = points
+            .into_iter()
+            .map(|p| p.pack(self.schema.as_ref(), self.ids_only))
+            .collect::<PyResult<Vec<_>>>()?;
         self.tree.insert_bulk(rust_points);
+        Ok(())
+    }
+
+    /// Starts a batch of inserts and deletes, applied when the `with` block exits.
+    ///
+    /// Returns:
+    ///     Batch: A context manager recording `insert` and `delete` calls.
+    fn batch(slf: Bound<'_, Self>) -> PyBatch {
+        PyBatch::new(slf.into_any().unbind())
+    }
+
+    fn _apply_batch(&mut self, ops: Vec<(bool, PyPoint2D)>) -> PyResult<()> {
+        for (is_insert, point) in &ops {
+            if *is_insert {
+                check_id(self.ids_only, &point.data)?;
+            }
+        }
+        let ops = ops
+            .into_iter()
+            .map(|(is_insert, p)| {
+                let p = if is_insert {
+                    p.pack(self.schema.as_ref(), self.ids_only)?
+                } else {
+                    p.into()
+                };
+                Ok((is_insert, p))
+            })
+            .collect::<PyResult<_>>()?;
+        let (deletes, inserts): (Vec<Point2D<PyData>>, _) = split_ops(ops);
+        for point in &deletes {
+            self.tree.delete(point);
+        }
+        self.tree.insert_bulk(inserts);
+        Ok(())
     }
 
     fn delete(&mut self, point: PyPoint2D) -> bool {
@@ -37,22 +153,124 @@ impl PyRTree2D {
         self.tree.delete(&p)
     }
 
-    fn knn_search(&self, point: PyPoint2D, k: usize) -> Vec<PyPoint2D> {
+    /// Deletes several points at once.
+    ///
+    /// Each point removes at most one matching stored point, as with `delete`.
+    ///
+    /// Args:
+    ///     points (list[Point2D]): The points to delete.
+    ///
+    /// Returns:
+    ///     int: The number of points that were found and deleted.
+    fn delete_bulk(&mut self, points: Vec<PyPoint2D>) -> usize {
+        let points: Vec<Point2D<PyData>> = points.into_iter().map(|p| p.into()).collect();
+        self.tree.delete_bulk(&points)
+    }
+
+    /// Keeps only the points whose data pass a test, deleting the others.
+    ///
+    /// Args:
+    ///     keep (Callable[[Any], bool]): Called with the data of each stored point; the point
+    ///         is kept if it returns a truthy value.
+    ///
+    /// Returns:
+    ///     int: The number of points that were deleted.
+    ///
+    /// Raises:
+    ///     Exception: Whatever `keep` raises. The points rejected before that stay deleted
+    ///         and no further points are deleted.
+    fn retain(&mut self, keep: Bound<'_, PyAny>) -> PyResult<usize> {
+        let mut error = None;
+        let deleted = self
+            .tree
+            .retain(|p| keep_payload(&keep, p.data.as_ref(), &mut error));
+        error.map_or(Ok(deleted), Err)
+    }
+
+    fn knn_search(&self, py: Python<'_>, point: PyPoint2D, k: usize) -> PyResult<PyObject> {
         let p: Point2D<PyData> = point.into();
-        self.tree
-            .knn_search::<EuclideanDistance>(&p, k)
-            .into_iter()
-            .map(|p| p.into())
-            .collect()
+        let results = self.tree.knn_search::<EuclideanDistance>(&p, k);
+        if self.ids_only {
+            return ids_array(py, results.iter().map(|p| p.data.as_ref()));
+        }
+        let points: Vec<PyPoint2D> = results.into_iter().map(|p| p.into()).collect();
+        Ok(points.into_pyobject(py)?.into_any().unbind())
     }
 
-    fn range_search(&self, point: PyPoint2D, radius: f64) -> Vec<PyPoint2D> {
+    fn range_search(&self, py: Python<'_>, point: PyPoint2D, radius: f64) -> PyResult<PyObject> {
         let p: Point2D<PyData> = point.into();
-        self.tree
-            .range_search::<EuclideanDistance>(&p, radius)
-            .into_iter()
-            .map(|p| p.into())
-            .collect()
+        let results = self.tree.range_search::<EuclideanDistance>(&p, radius);
+        if self.ids_only {
+            return ids_array(py, results.iter().map(|p| p.data.as_ref()));
+        }
+        let points: Vec<PyPoint2D> = results.into_iter().map(|p| p.into()).collect();
+        Ok(points.into_pyobject(py)?.into_any().unbind())
+    }
+
+    /// Finds the points within a given radius of the query point whose payloads meet all the
+    /// given conditions. The tree must have a payload schema; the search and the filtering run
+    /// without holding the GIL.
+    ///
+    /// Args:
+    ///     point (Point2D): The center point to search from.
+    ///     radius (float): The search radius (using Euclidean distance).
+    ///     **conditions: For each field to filter on, a value the field must equal, or a
+    ///         `(min, max)` tuple of inclusive bounds with None for an open end.
+    ///
+    /// Returns:
+    ///     list[Point2D]: The matching points within the specified radius.
+    #[pyo3(signature = (point, radius, **conditions))]
+    fn range_search_where(
+        &self,
+        py: Python<'_>,
+        point: PyPoint2D,
+        radius: f64,
+        conditions: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Vec<PyPoint2D>> {
+        let filter = RecordFilter::new(self.schema.as_ref(), conditions)?;
+        let p: Point2D<PyData> = point.into();
+        let results = py.allow_threads(|| {
+            let mut results = self.tree.range_search::<EuclideanDistance>(&p, radius);
+            results.retain(|p| filter.matches(p.data.as_ref()));
+            results
+        });
+        Ok(results.into_iter().map(|p| p.into()).collect())
+    }
+
+    /// Finds all points inside a query window.
+    ///
+    /// Args:
+    ///     window: The window, as a dict with `x`, `y`, `width` and `height`, a dict
+    ///         with `min` and `max` corners, an `(xmin, ymin, xmax, ymax)` tuple, or a Shapely box.
+    ///
+    /// Returns:
+    ///     list[Point2D]: The points inside the window, or in ids-only mode a NumPy array of
+    ///     their ids.
+    fn range_search_bbox(&self, py: Python<'_>, window: PyRectangle) -> PyResult<PyObject> {
+        let results = self.tree.range_search_bbox(&window.0);
+        if self.ids_only {
+            return ids_array(py, results.iter().map(|p| p.data.as_ref()));
+        }
+        let points: Vec<PyPoint2D> = results.into_iter().map(|p| p.into()).collect();
+        Ok(points.into_pyobject(py)?.into_any().unbind())
+    }
+
+    /// Returns statistics about the shape of the tree.
+    ///
+    /// Returns:
+    ///     dict: The number of stored points (`size`), nodes (`node_count`), and leaves
+    ///     (`leaf_count`), the `depth` of the tree, and the fraction of the node capacity in
+    ///     use over all nodes (`fill_factor`, None for k-d trees).
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        tree_stats_dict(py, &self.tree.stats())
+    }
+
+    /// Checks the structural invariants of the tree, e.g. after loading it from a file.
+    ///
+    /// Raises:
+    ///     SpartError: If the tree is corrupted, with a description of the first violation.
+    fn check_invariants(&self) -> PyResult<()> {
+        self.tree.check_invariants().map_err(spart_error)
     }
 
     /// Saves the tree to a file.
@@ -61,45 +279,164 @@ impl PyRTree2D {
     ///     path (str): The path to the file.
     fn save(&self, path: &str) -> PyResult<()> {
         let file = File::create(path)?;
-        bincode::serialize_into(file, &self.tree).map_err(|e| PyValueError::new_err(e.to_string()))
+        bincode::serialize_into(file, &self.tree).map_err(persistence_error)
     }
 
     /// Loads a tree from a file.
     ///
     /// Args:
     ///     path (str): The path to the file.
+    ///     ids_only (bool): Whether to open the tree in ids-only mode (the mode is not saved).
+    ///
+    /// The payload schema is not saved either; payloads are loaded as plain objects.
     ///
     /// Returns:
     ///     The loaded tree.
     #[classmethod]
-    fn load(_cls: &Bound<PyType>, path: &str) -> PyResult<Self> {
+    #[pyo3(signature = (path, ids_only = false))]
+    fn load(_cls: &Bound<PyType>, path: &str, ids_only: bool) -> PyResult<Self> {
         let file = File::open(path)?;
-        let tree =
-            bincode::deserialize_from(file).map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(PyRTree2D { tree })
+        let tree = bincode::deserialize_from(file).map_err(persistence_error)?;
+        Ok(PyRTree2D {
+            tree,
+            ids_only,
+            schema: None,
+        })
     }
 }
 
 #[pyclass(name = "RTree3D")]
+#[derive(Clone)]
 pub struct PyRTree3D {
     tree: RTree<Point3D<PyData>>,
+    /// Whether the payloads are integer ids, returned by queries as NumPy arrays.
+    ids_only: bool,
+    /// The schema payloads are packed with, if any.
+    schema: Option<Arc<PayloadSchema>>,
 }
 
 #[pymethods]
 impl PyRTree3D {
     #[new]
-    fn new(max_entries: usize) -> PyResult<Self> {
-        let tree = RTree::new(max_entries).map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(PyRTree3D { tree })
+    #[pyo3(signature = (max_entries, ids_only = false, schema = None))]
+    fn new(
+        max_entries: usize,
+        ids_only: bool,
+        schema: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let tree = RTree::new(max_entries).map_err(spart_error)?;
+        let schema = PayloadSchema::for_tree(ids_only, schema)?;
+        Ok(PyRTree3D {
+            tree,
+            ids_only,
+            schema,
+        })
     }
 
-    fn insert(&mut self, point: PyPoint3D) {
-        self.tree.insert(point.into())
+    /// Whether the payloads are integer ids, returned by queries as NumPy arrays.
+    #[getter]
+    fn ids_only(&self) -> bool {
+        self.ids_only
     }
 
-    fn insert_bulk(&mut self, points: Vec<PyPoint3D>) {
-        let rust_points: Vec<Point3D<PyData>> = points.into_iter().map(|p| p.into()).collect();
+    /// The number of dimensions of the points.
+    #[getter]
+    fn dims(&self) -> usize {
+        3
+    }
+
+    /// The number of stored points.
+    #[getter]
+    fn size(&self) -> usize {
+        self.tree.summary().size
+    }
+
+    /// The number of nodes on the longest path from the root to a leaf.
+    #[getter]
+    fn depth(&self) -> usize {
+        self.tree.summary().depth
+    }
+
+    /// The extent of the stored points, as an `(xmin, ymin, zmin, xmax, ymax, zmax)` tuple, or
+    /// None if the tree is empty.
+    #[getter]
+    fn bounds<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyTuple>>> {
+        bounds_tuple(py, &self.tree.summary())
+    }
+
+    /// Returns a copy of the tree. The point data are shared with the original, not copied.
+    ///
+    /// Returns:
+    ///     The copy, in the same ids-only mode.
+    fn copy(&self) -> Self {
+        self.clone()
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    /// Checks whether two trees hold the same points, regardless of how they are arranged.
+    ///
+    /// Args:
+    ///     other: The tree to compare with.
+    ///
+    /// Returns:
+    ///     bool: True if both trees hold the same points, counting duplicates.
+    fn content_eq(&self, other: PyRef<'_, Self>) -> bool {
+        self.tree.content_eq(&other.tree)
+    }
+
+    fn insert(&mut self, point: PyPoint3D) -> PyResult<()> {
+        check_id(self.ids_only, &point.data)?;
+        self.tree
+            .insert(point.pack(self.schema.as_ref(), self.ids_only)?);
+        Ok(())
+    }
+
+    fn insert_bulk(&mut self, points: Vec<PyPoint3D>) -> PyResult<()> {
+        for point in &points {
+            check_id(self.ids_only, &point.data)?;
+        }
+        let rust_points = points
+            .into_iter()
+            .map(|p| p.pack(self.schema.as_ref(), self.ids_only))
+            .collect::<PyResult<Vec<_>>>()?;
         self.tree.insert_bulk(rust_points);
+        Ok(())
+    }
+
+    /// Starts a batch of inserts and deletes, applied when the `with` block exits.
+    ///
+    /// Returns:
+    ///     Batch: A context manager recording `insert` and `delete` calls.
+    fn batch(slf: Bound<'_, Self>) -> PyBatch {
+        PyBatch::new(slf.into_any().unbind())
+    }
+
+    fn _apply_batch(&mut self, ops: Vec<(bool, PyPoint3D)>) -> PyResult<()> {
+        for (is_insert, point) in &ops {
+            if *is_insert {
+                check_id(self.ids_only, &point.data)?;
+            }
+        }
+        let ops = ops
+            .into_iter()
+            .map(|(is_insert, p)| {
+                let p = if is_insert {
+                    p.pack(self.schema.as_ref(), self.ids_only)?
+                } else {
+                    p.into()
+                };
+                Ok((is_insert, p))
+            })
+            .collect::<PyResult<_>>()?;
+        let (deletes, inserts): (Vec<Point3D<PyData>>, _) = split_ops(ops);
+        for point in &deletes {
+            self.tree.delete(point);
+        }
+        self.tree.insert_bulk(inserts);
+        Ok(())
     }
 
     fn delete(&mut self, point: PyPoint3D) -> bool {
@@ -107,22 +444,125 @@ impl PyRTree3D {
         self.tree.delete(&p)
     }
 
-    fn knn_search(&self, point: PyPoint3D, k: usize) -> Vec<PyPoint3D> {
+    /// Deletes several points at once.
+    ///
+    /// Each point removes at most one matching stored point, as with `delete`.
+    ///
+    /// Args:
+    ///     points (list[Point3D]): The points to delete.
+    ///
+    /// Returns:
+    ///     int: The number of points that were found and deleted.
+    fn delete_bulk(&mut self, points: Vec<PyPoint3D>) -> usize {
+        let points: Vec<Point3D<PyData>> = points.into_iter().map(|p| p.into()).collect();
+        self.tree.delete_bulk(&points)
+    }
+
+    /// Keeps only the points whose data pass a test, deleting the others.
+    ///
+    /// Args:
+    ///     keep (Callable[[Any], bool]): Called with the data of each stored point; the point
+    ///         is kept if it returns a truthy value.
+    ///
+    /// Returns:
+    ///     int: The number of points that were deleted.
+    ///
+    /// Raises:
+    ///     Exception: Whatever `keep` raises. The points rejected before that stay deleted
+    ///         and no further points are deleted.
+    fn retain(&mut self, keep: Bound<'_, PyAny>) -> PyResult<usize> {
+        let mut error = None;
+        let deleted = self
+            .tree
+            .retain(|p| keep_payload(&keep, p.data.as_ref(), &mut error));
+        error.map_or(Ok(deleted), Err)
+    }
+
+    fn knn_search(&self, py: Python<'_>, point: PyPoint3D, k: usize) -> PyResult<PyObject> {
         let p: Point3D<PyData> = point.into();
-        self.tree
-            .knn_search::<EuclideanDistance>(&p, k)
-            .into_iter()
-            .map(|p| p.into())
-            .collect()
+        let results = self.tree.knn_search::<EuclideanDistance>(&p, k);
+        if self.ids_only {
+            return ids_array(py, results.iter().map(|p| p.data.as_ref()));
+        }
+        let points: Vec<PyPoint3D> = results.into_iter().map(|p| p.into()).collect();
+        Ok(points.into_pyobject(py)?.into_any().unbind())
     }
 
-    fn range_search(&self, point: PyPoint3D, radius: f64) -> Vec<PyPoint3D> {
+    fn range_search(&self, py: Python<'_>, point: PyPoint3D, radius: f64) -> PyResult<PyObject> {
         let p: Point3D<PyData> = point.into();
-        self.tree
-            .range_search::<EuclideanDistance>(&p, radius)
-            .into_iter()
-            .map(|p| p.into())
-            .collect()
+        let results = self.tree.range_search::<EuclideanDistance>(&p, radius);
+        if self.ids_only {
+            return ids_array(py, results.iter().map(|p| p.data.as_ref()));
+        }
+        let points: Vec<PyPoint3D> = results.into_iter().map(|p| p.into()).collect();
+        Ok(points.into_pyobject(py)?.into_any().unbind())
+    }
+
+    /// Finds the points within a given radius of the query point whose payloads meet all the
+    /// given conditions. The tree must have a payload schema; the search and the filtering run
+    /// without holding the GIL.
+    ///
+    /// Args:
+    ///     point (Point3D): The center point to search from.
+    ///     radius (float): The search radius (using Euclidean distance).
+    ///     **conditions: For each field to filter on, a value the field must equal, or a
+    ///         `(min, max)` tuple of inclusive bounds with None for an open end.
+    ///
+    /// Returns:
+    ///     list[Point3D]: The matching points within the specified radius.
+    #[pyo3(signature = (point, radius, **conditions))]
+    fn range_search_where(
+        &self,
+        py: Python<'_>,
+        point: PyPoint3D,
+        radius: f64,
+        conditions: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Vec<PyPoint3D>> {
+        let filter = RecordFilter::new(self.schema.as_ref(), conditions)?;
+        let p: Point3D<PyData> = point.into();
+        let results = py.allow_threads(|| {
+            let mut results = self.tree.range_search::<EuclideanDistance>(&p, radius);
+            results.retain(|p| filter.matches(p.data.as_ref()));
+            results
+        });
+        Ok(results.into_iter().map(|p| p.into()).collect())
+    }
+
+    /// Finds all points inside a query window.
+    ///
+    /// Args:
+    ///     window: The window, as a dict with `x`, `y`, `z`, `width`, `height` and
+    ///         `depth`, a dict with `min` and `max` corners, or an
+    ///         `(xmin, ymin, zmin, xmax, ymax, zmax)` tuple.
+    ///
+    /// Returns:
+    ///     list[Point3D]: The points inside the window, or in ids-only mode a NumPy array of
+    ///     their ids.
+    fn range_search_bbox(&self, py: Python<'_>, window: PyCube) -> PyResult<PyObject> {
+        let results = self.tree.range_search_bbox(&window.0);
+        if self.ids_only {
+            return ids_array(py, results.iter().map(|p| p.data.as_ref()));
+        }
+        let points: Vec<PyPoint3D> = results.into_iter().map(|p| p.into()).collect();
+        Ok(points.into_pyobject(py)?.into_any().unbind())
+    }
+
+    /// Returns statistics about the shape of the tree.
+    ///
+    /// Returns:
+    ///     dict: The number of stored points (`size`), nodes (`node_count`), and leaves
+    ///     (`leaf_count`), the `depth` of the tree, and the fraction of the node capacity in
+    ///     use over all nodes (`fill_factor`, None for k-d trees).
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        tree_stats_dict(py, &self.tree.stats())
+    }
+
+    /// Checks the structural invariants of the tree, e.g. after loading it from a file.
+    ///
+    /// Raises:
+    ///     SpartError: If the tree is corrupted, with a description of the first violation.
+    fn check_invariants(&self) -> PyResult<()> {
+        self.tree.check_invariants().map_err(spart_error)
     }
 
     /// Saves the tree to a file.
@@ -131,21 +571,28 @@ impl PyRTree3D {
     ///     path (str): The path to the file.
     fn save(&self, path: &str) -> PyResult<()> {
         let file = File::create(path)?;
-        bincode::serialize_into(file, &self.tree).map_err(|e| PyValueError::new_err(e.to_string()))
+        bincode::serialize_into(file, &self.tree).map_err(persistence_error)
     }
 
     /// Loads a tree from a file.
     ///
     /// Args:
     ///     path (str): The path to the file.
+    ///     ids_only (bool): Whether to open the tree in ids-only mode (the mode is not saved).
+    ///
+    /// The payload schema is not saved either; payloads are loaded as plain objects.
     ///
     /// Returns:
     ///     The loaded tree.
     #[classmethod]
-    fn load(_cls: &Bound<PyType>, path: &str) -> PyResult<Self> {
+    #[pyo3(signature = (path, ids_only = false))]
+    fn load(_cls: &Bound<PyType>, path: &str, ids_only: bool) -> PyResult<Self> {
         let file = File::open(path)?;
-        let tree =
-            bincode::deserialize_from(file).map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(PyRTree3D { tree })
+        let tree = bincode::deserialize_from(file).map_err(persistence_error)?;
+        Ok(PyRTree3D {
+            tree,
+            ids_only,
+            schema: None,
+        })
     }
 }
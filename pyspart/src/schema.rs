@@ -0,0 +1,336 @@
+//! Payload schemas, which store dict or dataclass payloads as packed Rust values.
+//!
+//! A tree created with a schema converts the payload of each inserted point into a `Record`
+//! holding one native value per field. A record takes far less memory than the Python dict or
+//! object it was made from, and can be compared and filtered without the GIL. Payloads are turned
+//! back into dicts (or instances of the dataclass the schema was made from) on the way out.
+
+use std::fmt;
+use std::sync::Arc;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyString, PyTuple, PyType};
+
+use crate::types::PyData;
+
+/// The type of a field in a payload schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    Int,
+    Float,
+    Bool,
+    Str,
+}
+
+impl FieldKind {
+    /// Reads a field type given as `int`, `float`, `bool`, or `str`, or as the name of one.
+    fn from_py(ty: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let name: String = if ty.is_instance_of::<PyString>() {
+            ty.extract()?
+        } else if ty.is_instance_of::<PyType>() {
+            ty.getattr("__name__")?.extract()?
+        } else {
+            String::new()
+        };
+        match name.as_str() {
+            "int" => Ok(FieldKind::Int),
+            "float" => Ok(FieldKind::Float),
+            "bool" => Ok(FieldKind::Bool),
+            "str" => Ok(FieldKind::Str),
+            _ => Err(PyTypeError::new_err(format!(
+                "unsupported field type {}, expected int, float, bool, or str",
+                ty.repr()?
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldKind::Int => "int",
+            FieldKind::Float => "float",
+            FieldKind::Bool => "bool",
+            FieldKind::Str => "str",
+        })
+    }
+}
+
+/// The value of a field in a record.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum FieldValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(Box<str>),
+}
+
+impl FieldValue {
+    /// Converts a Python value into a field of the given type, or returns `None` if it has
+    /// another type. Integers are accepted for `float` fields.
+    fn extract(kind: FieldKind, value: &Bound<'_, PyAny>) -> Option<Self> {
+        let is_bool = value.is_instance_of::<PyBool>();
+        match kind {
+            FieldKind::Int if value.is_exact_instance_of::<PyInt>() => {
+                value.extract().ok().map(FieldValue::Int)
+            }
+            FieldKind::Float
+                if !is_bool
+                    && (value.is_instance_of::<PyFloat>() || value.is_instance_of::<PyInt>()) =>
+            {
+                value.extract().ok().map(FieldValue::Float)
+            }
+            FieldKind::Bool if is_bool => value.extract().ok().map(FieldValue::Bool),
+            FieldKind::Str if value.is_instance_of::<PyString>() => value
+                .extract::<String>()
+                .ok()
+                .map(|s| FieldValue::Str(s.into())),
+            _ => None,
+        }
+    }
+
+    fn to_object<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(match self {
+            FieldValue::Int(value) => value.into_pyobject(py)?.into_any(),
+            FieldValue::Float(value) => value.into_pyobject(py)?.into_any(),
+            FieldValue::Bool(value) => value.into_pyobject(py)?.to_owned().into_any(),
+            FieldValue::Str(value) => PyString::new(py, value).into_any(),
+        })
+    }
+}
+
+/// The names and types of the fields of the payloads stored in a tree.
+#[derive(Debug)]
+pub struct PayloadSchema {
+    fields: Vec<(String, FieldKind)>,
+    /// The dataclass payloads are rebuilt as, or `None` to return them as dicts.
+    class: Option<Py<PyType>>,
+}
+
+impl PayloadSchema {
+    /// Reads the `schema` argument of a tree constructor.
+    ///
+    /// The schema is a dict mapping field names to types, a list of `(name, type)` pairs, or a
+    /// dataclass whose fields all have one of the supported types.
+    pub fn for_tree(
+        ids_only: bool,
+        schema: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Option<Arc<PayloadSchema>>> {
+        let Some(schema) = schema else {
+            return Ok(None);
+        };
+        if ids_only {
+            return Err(PyValueError::new_err(
+                "a tree in ids-only mode cannot have a payload schema",
+            ));
+        }
+        let py = schema.py();
+        let dataclasses = py.import("dataclasses")?;
+        let mut class = None;
+        let pairs: Vec<(String, Bound<'_, PyAny>)> = if schema.is_instance_of::<PyType>()
+            && dataclasses
+                .call_method1("is_dataclass", (schema,))?
+                .is_truthy()?
+        {
+            class = Some(schema.downcast::<PyType>()?.clone().unbind());
+            dataclasses
+                .call_method1("fields", (schema,))?
+                .try_iter()?
+                .map(|field| {
+                    let field = field?;
+                    Ok((field.getattr("name")?.extract()?, field.getattr("type")?))
+                })
+                .collect::<PyResult<_>>()?
+        } else if let Ok(dict) = schema.downcast::<PyDict>() {
+            dict.iter()
+                .map(|(name, ty)| Ok((name.extract()?, ty)))
+                .collect::<PyResult<_>>()?
+        } else {
+            schema
+                .try_iter()?
+                .map(|pair| pair?.extract())
+                .collect::<PyResult<_>>()?
+        };
+        if pairs.is_empty() {
+            return Err(PyValueError::new_err(
+                "a payload schema needs at least one field",
+            ));
+        }
+        let fields = pairs
+            .into_iter()
+            .map(|(name, ty)| Ok((name, FieldKind::from_py(&ty)?)))
+            .collect::<PyResult<_>>()?;
+        Ok(Some(Arc::new(PayloadSchema { fields, class })))
+    }
+
+    /// Packs a payload into a record. The payload is a dict, or any object with an attribute
+    /// per field (such as a dataclass instance).
+    pub fn pack(self: &Arc<Self>, data: &Bound<'_, PyAny>) -> PyResult<PyData> {
+        let dict = data.downcast::<PyDict>().ok();
+        let values = self
+            .fields
+            .iter()
+            .map(|(name, kind)| {
+                let value = match dict {
+                    Some(dict) => dict.get_item(name)?,
+                    None => data.getattr(name.as_str()).ok(),
+                };
+                let value = value.ok_or_else(|| {
+                    PyTypeError::new_err(format!("point data is missing the field '{name}'"))
+                })?;
+                match FieldValue::extract(*kind, &value) {
+                    Some(value) => Ok(value),
+                    None => Err(PyTypeError::new_err(format!(
+                        "field '{name}' must be {kind}, got {}",
+                        value.repr()?
+                    ))),
+                }
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(PyData::Record(Box::new(Record {
+            schema: Arc::clone(self),
+            values,
+        })))
+    }
+
+    fn field(&self, name: &str) -> Option<(usize, FieldKind)> {
+        self.fields
+            .iter()
+            .position(|(field, _)| field == name)
+            .map(|index| (index, self.fields[index].1))
+    }
+}
+
+/// A payload packed according to a schema.
+#[derive(Debug, Clone)]
+pub struct Record {
+    schema: Arc<PayloadSchema>,
+    values: Box<[FieldValue]>,
+}
+
+impl Record {
+    /// Rebuilds the payload as a dict, or as an instance of the schema's dataclass.
+    pub fn to_object(&self, py: Python<'_>) -> PyObject {
+        let dict = PyDict::new(py);
+        for ((name, _), value) in self.schema.fields.iter().zip(&self.values) {
+            let value = value
+                .to_object(py)
+                .expect("a field value converts to a Python object");
+            dict.set_item(name, value)
+                .expect("a str key can be set on a dict");
+        }
+        match &self.schema.class {
+            Some(class) => class
+                .bind(py)
+                .call((), Some(&dict))
+                .map_or_else(|_| dict.into_any().unbind(), Bound::unbind),
+            None => dict.into_any().unbind(),
+        }
+    }
+
+    /// Returns `true` if both records have the same fields, so their values can be compared.
+    pub fn same_fields_as(&self, other: &Record) -> bool {
+        self.same_fields(&other.schema)
+    }
+
+    fn same_fields(&self, schema: &Arc<PayloadSchema>) -> bool {
+        Arc::ptr_eq(&self.schema, schema) || self.schema.fields == schema.fields
+    }
+}
+
+/// Compares the values of records, field by field; only meaningful for records with the same
+/// fields.
+impl PartialEq for Record {
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values
+    }
+}
+
+impl PartialOrd for Record {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.values.partial_cmp(&other.values)
+    }
+}
+
+/// A condition on one field of a record.
+enum Condition {
+    Equals(FieldValue),
+    /// Inclusive bounds, open where `None`.
+    Between(Option<FieldValue>, Option<FieldValue>),
+}
+
+/// Conditions on the fields of records, checked without the GIL.
+pub struct RecordFilter {
+    schema: Arc<PayloadSchema>,
+    conditions: Vec<(usize, Condition)>,
+}
+
+impl RecordFilter {
+    /// Builds a filter from the keyword arguments of a query, each giving a value for a field
+    /// to equal, or a `(min, max)` tuple of inclusive bounds with None for an open end.
+    pub fn new(
+        schema: Option<&Arc<PayloadSchema>>,
+        conditions: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Self> {
+        let schema = schema.ok_or_else(|| {
+            PyTypeError::new_err("filtering payloads needs a tree created with a payload schema")
+        })?;
+        let mut filter = RecordFilter {
+            schema: Arc::clone(schema),
+            conditions: Vec::new(),
+        };
+        for (name, condition) in conditions.into_iter().flat_map(|c| c.iter()) {
+            let name: String = name.extract()?;
+            let (index, kind) = schema.field(&name).ok_or_else(|| {
+                PyTypeError::new_err(format!("the payload schema has no field '{name}'"))
+            })?;
+            let value = |value: Bound<'_, PyAny>| -> PyResult<FieldValue> {
+                FieldValue::extract(kind, &value).ok_or_else(|| {
+                    PyTypeError::new_err(format!(
+                        "the condition on field '{name}' must be {kind}, got {}",
+                        value.repr().map(|r| r.to_string()).unwrap_or_default()
+                    ))
+                })
+            };
+            let condition = match condition.downcast::<PyTuple>() {
+                Ok(bounds) if bounds.len() == 2 => {
+                    let bound = |item: Bound<'_, PyAny>| -> PyResult<Option<FieldValue>> {
+                        if item.is_none() {
+                            Ok(None)
+                        } else {
+                            value(item).map(Some)
+                        }
+                    };
+                    Condition::Between(bound(bounds.get_item(0)?)?, bound(bounds.get_item(1)?)?)
+                }
+                Ok(_) => {
+                    return Err(PyValueError::new_err(format!(
+                        "the bounds for field '{name}' must be a (min, max) tuple"
+                    )));
+                }
+                Err(_) => Condition::Equals(value(condition)?),
+            };
+            filter.conditions.push((index, condition));
+        }
+        Ok(filter)
+    }
+
+    /// Returns `true` if `data` is a record with this filter's fields that meets all conditions.
+    pub fn matches(&self, data: Option<&PyData>) -> bool {
+        let Some(PyData::Record(record)) = data else {
+            return false;
+        };
+        record.same_fields(&self.schema)
+            && self.conditions.iter().all(|(index, condition)| {
+                let value = &record.values[*index];
+                match condition {
+                    Condition::Equals(expected) => value == expected,
+                    Condition::Between(min, max) => {
+                        min.as_ref().is_none_or(|min| min <= value)
+                            && max.as_ref().is_none_or(|max| value <= max)
+                    }
+                }
+            })
+    }
+}
@@ -0,0 +1,326 @@
+//! Thread-safe tree classes that can be shared between Python threads.
+//!
+//! Each class keeps its tree in a `SharedIndex`, so queries run concurrently and updates get
+//! exclusive access. The GIL is released while a method waits for the lock and works on the
+//! tree, which lets other Python threads run (and query the same tree) in the meantime. The lock
+//! is never waited for while holding the GIL: payload comparisons inside the tree may need the
+//! GIL, so doing so could deadlock.
+//!
+//! The `_async` query methods run the search on the blocking thread pool of a Tokio runtime and
+//! return an asyncio future, so async web servers can await large queries without blocking their
+//! event loop.
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3_async_runtimes::tokio::future_into_py;
+use tokio::task::spawn_blocking;
+
+use spart::concurrent::SharedIndex;
+use spart::geometry::{EuclideanDistance, Point2D, Point3D};
+use spart::kdtree::KdTree;
+use spart::octree::Octree;
+use spart::quadtree::Quadtree;
+use spart::rstar_tree::RStarTree;
+use spart::rtree::RTree;
+use spart::stats::TreeStats;
+
+use crate::errors::spart_error;
+use crate::geometry::{PyCube, PyRectangle};
+use crate::point2d::PyPoint2D;
+use crate::point3d::PyPoint3D;
+use crate::types::{PyData, tree_stats_dict};
+
+/// The operations of a tree used by the thread-safe classes, with uniform signatures.
+trait Index: Send + Sync + 'static {
+    type Point: Clone + Send;
+
+    fn insert(&mut self, point: Self::Point) -> PyResult<bool>;
+    fn insert_bulk(&mut self, points: Vec<Self::Point>) -> PyResult<()>;
+    fn delete(&mut self, point: &Self::Point) -> bool;
+    fn knn_search(&self, point: &Self::Point, k: usize) -> Vec<Self::Point>;
+    fn range_search(&self, point: &Self::Point, radius: f64) -> Vec<Self::Point>;
+    fn stats(&self) -> TreeStats;
+}
+
+macro_rules! impl_index_for_space_tree {
+    ($tree:ident, $point:ident) => {
+        impl Index for $tree<PyData> {
+            type Point = $point<PyData>;
+
+            fn insert(&mut self, point: Self::Point) -> PyResult<bool> {
+                Ok($tree::insert(self, point))
+            }
+            fn insert_bulk(&mut self, points: Vec<Self::Point>) -> PyResult<()> {
+                $tree::insert_bulk(self, &points);
+                Ok(())
+            }
+            fn delete(&mut self, point: &Self::Point) -> bool {
+                $tree::delete(self, point)
+            }
+            fn knn_search(&self, point: &Self::Point, k: usize) -> Vec<Self::Point> {
+                $tree::knn_search::<EuclideanDistance>(self, point, k)
+            }
+            fn range_search(&self, point: &Self::Point, radius: f64) -> Vec<Self::Point> {
+                $tree::range_search::<EuclideanDistance>(self, point, radius)
+            }
+            fn stats(&self) -> TreeStats {
+                $tree::stats(self)
+            }
+        }
+    };
+}
+
+impl_index_for_space_tree!(Quadtree, Point2D);
+impl_index_for_space_tree!(Octree, Point3D);
+
+macro_rules! impl_index_for_kdtree {
+    ($point:ident) => {
+        impl Index for KdTree<$point<PyData>> {
+            type Point = $point<PyData>;
+
+            fn insert(&mut self, point: Self::Point) -> PyResult<bool> {
+                KdTree::insert(self, point).map_err(spart_error)?;
+                Ok(true)
+            }
+            fn insert_bulk(&mut self, points: Vec<Self::Point>) -> PyResult<()> {
+                KdTree::insert_bulk(self, points).map_err(spart_error)
+            }
+            fn delete(&mut self, point: &Self::Point) -> bool {
+                KdTree::delete(self, point)
+            }
+            fn knn_search(&self, point: &Self::Point, k: usize) -> Vec<Self::Point> {
+                KdTree::knn_search::<EuclideanDistance>(self, point, k)
+            }
+            fn range_search(&self, point: &Self::Point, radius: f64) -> Vec<Self::Point> {
+                KdTree::range_search::<EuclideanDistance>(self, point, radius)
+            }
+            fn stats(&self) -> TreeStats {
+                KdTree::stats(self)
+            }
+        }
+    };
+}
+
+impl_index_for_kdtree!(Point2D);
+impl_index_for_kdtree!(Point3D);
+
+macro_rules! impl_index_for_rtree {
+    ($tree:ident, $point:ident) => {
+        impl Index for $tree<$point<PyData>> {
+            type Point = $point<PyData>;
+
+            fn insert(&mut self, point: Self::Point) -> PyResult<bool> {
+                <$tree<$point<PyData>>>::insert(self, point);
+                Ok(true)
+            }
+            fn insert_bulk(&mut self, points: Vec<Self::Point>) -> PyResult<()> {
+                <$tree<$point<PyData>>>::insert_bulk(self, points);
+                Ok(())
+            }
+            fn delete(&mut self, point: &Self::Point) -> bool {
+                <$tree<$point<PyData>>>::delete(self, point)
+            }
+            fn knn_search(&self, point: &Self::Point, k: usize) -> Vec<Self::Point> {
+                <$tree<$point<PyData>>>::knn_search::<EuclideanDistance>(self, point, k)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            }
+            fn range_search(&self, point: &Self::Point, radius: f64) -> Vec<Self::Point> {
+                <$tree<$point<PyData>>>::range_search::<EuclideanDistance>(self, point, radius)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            }
+            fn stats(&self) -> TreeStats {
+                <$tree<$point<PyData>>>::stats(self)
+            }
+        }
+    };
+}
+
+impl_index_for_rtree!(RTree, Point2D);
+impl_index_for_rtree!(RTree, Point3D);
+impl_index_for_rtree!(RStarTree, Point2D);
+impl_index_for_rtree!(RStarTree, Point3D);
+
+/// Runs `query` on the blocking thread pool of the async runtime and returns an asyncio future
+/// resolving to its result.
+///
+/// The tree is shared through its `SharedIndex`, so the query holds a read lock while it runs and
+/// never holds the GIL.
+fn offload<'py, F, R>(py: Python<'py>, query: F) -> PyResult<Bound<'py, PyAny>>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: for<'a> IntoPyObject<'a> + Send + 'static,
+{
+    future_into_py(py, async move {
+        spawn_blocking(query)
+            .await
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    })
+}
+
+/// Defines a thread-safe Python class around a tree type.
+///
+/// The constructor is given as its argument list and an expression building the tree.
+macro_rules! thread_safe_tree {
+    (
+        $(#[$meta:meta])*
+        $class:ident, $name:literal, $tree:ty, $py_point:ident,
+        ($($arg:ident: $arg_ty:ty),*) => $build:expr
+    ) => {
+        $(#[$meta])*
+        #[pyclass(name = $name, frozen)]
+        pub struct $class {
+            index: SharedIndex<$tree>,
+        }
+
+        #[pymethods]
+        impl $class {
+            #[new]
+            fn new($($arg: $arg_ty),*) -> PyResult<Self> {
+                let tree: $tree = $build?;
+                Ok($class {
+                    index: SharedIndex::new(tree),
+                })
+            }
+
+            /// Inserts a point into the tree.
+            ///
+            /// Returns:
+            ///     bool: True if the point was inserted, False if it lies outside the boundary.
+            fn insert(&self, py: Python<'_>, point: $py_point) -> PyResult<bool> {
+                let point = point.into();
+                py.allow_threads(|| self.index.write(|tree| Index::insert(tree, point)))
+            }
+
+            /// Inserts multiple points into the tree efficiently.
+            fn insert_bulk(&self, py: Python<'_>, points: Vec<$py_point>) -> PyResult<()> {
+                let points = points.into_iter().map(Into::into).collect();
+                py.allow_threads(|| self.index.write(|tree| Index::insert_bulk(tree, points)))
+            }
+
+            /// Deletes a point from the tree.
+            ///
+            /// Returns:
+            ///     bool: True if the point was found and deleted.
+            fn delete(&self, py: Python<'_>, point: $py_point) -> bool {
+                let point = point.into();
+                py.allow_threads(|| self.index.write(|tree| Index::delete(tree, &point)))
+            }
+
+            /// Finds the k nearest neighbors to the given point.
+            fn knn_search(&self, py: Python<'_>, point: $py_point, k: usize) -> Vec<$py_point> {
+                let point = point.into();
+                let results = py.allow_threads(|| self.index.read(|tree| Index::knn_search(tree, &point, k)));
+                results.iter().map(Into::into).collect()
+            }
+
+            /// Finds all points within a given radius of the query point.
+            fn range_search(
+                &self,
+                py: Python<'_>,
+                point: $py_point,
+                radius: f64,
+            ) -> Vec<$py_point> {
+                let point = point.into();
+                let results =
+                    py.allow_threads(|| self.index.read(|tree| Index::range_search(tree, &point, radius)));
+                results.iter().map(Into::into).collect()
+            }
+
+            /// Like `knn_search`, but runs the search on a worker thread and returns an
+            /// awaitable, so an asyncio event loop keeps running in the meantime.
+            fn knn_search_async<'py>(
+                &self,
+                py: Python<'py>,
+                point: $py_point,
+                k: usize,
+            ) -> PyResult<Bound<'py, PyAny>> {
+                let point = point.into();
+                let index = self.index.clone();
+                offload(py, move || {
+                    let results = index.read(|tree| Index::knn_search(tree, &point, k));
+                    results.iter().map(Into::into).collect::<Vec<$py_point>>()
+                })
+            }
+
+            /// Like `range_search`, but runs the search on a worker thread and returns an
+            /// awaitable, so an asyncio event loop keeps running in the meantime.
+            fn range_search_async<'py>(
+                &self,
+                py: Python<'py>,
+                point: $py_point,
+                radius: f64,
+            ) -> PyResult<Bound<'py, PyAny>> {
+                let point = point.into();
+                let index = self.index.clone();
+                offload(py, move || {
+                    let results = index.read(|tree| Index::range_search(tree, &point, radius));
+                    results.iter().map(Into::into).collect::<Vec<$py_point>>()
+                })
+            }
+
+            /// Returns statistics about the shape of the tree (see the non-thread-safe class).
+            fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                let stats = py.allow_threads(|| self.index.read(|tree| Index::stats(tree)));
+                tree_stats_dict(py, &stats)
+            }
+
+            fn __len__(&self, py: Python<'_>) -> usize {
+                py.allow_threads(|| self.index.read(|tree| Index::stats(tree).size))
+            }
+        }
+    };
+}
+
+thread_safe_tree!(
+    /// A quadtree that can be shared between Python threads.
+    PyThreadSafeQuadtree, "ThreadSafeQuadtree", Quadtree<PyData>, PyPoint2D,
+    (boundary: PyRectangle, capacity: usize) =>
+        Quadtree::new(&boundary.0, capacity).map_err(spart_error)
+);
+
+thread_safe_tree!(
+    /// An octree that can be shared between Python threads.
+    PyThreadSafeOctree, "ThreadSafeOctree", Octree<PyData>, PyPoint3D,
+    (boundary: PyCube, capacity: usize) => Octree::new(&boundary.0, capacity).map_err(spart_error)
+);
+
+thread_safe_tree!(
+    /// A 2D k-d tree that can be shared between Python threads.
+    PyThreadSafeKdTree2D, "ThreadSafeKdTree2D", KdTree<Point2D<PyData>>, PyPoint2D,
+    () => PyResult::Ok(KdTree::new())
+);
+
+thread_safe_tree!(
+    /// A 3D k-d tree that can be shared between Python threads.
+    PyThreadSafeKdTree3D, "ThreadSafeKdTree3D", KdTree<Point3D<PyData>>, PyPoint3D,
+    () => PyResult::Ok(KdTree::new())
+);
+
+thread_safe_tree!(
+    /// A 2D R-tree that can be shared between Python threads.
+    PyThreadSafeRTree2D, "ThreadSafeRTree2D", RTree<Point2D<PyData>>, PyPoint2D,
+    (max_entries: usize) => RTree::new(max_entries).map_err(spart_error)
+);
+
+thread_safe_tree!(
+    /// A 3D R-tree that can be shared between Python threads.
+    PyThreadSafeRTree3D, "ThreadSafeRTree3D", RTree<Point3D<PyData>>, PyPoint3D,
+    (max_entries: usize) => RTree::new(max_entries).map_err(spart_error)
+);
+
+thread_safe_tree!(
+    /// A 2D R*-tree that can be shared between Python threads.
+    PyThreadSafeRStarTree2D, "ThreadSafeRStarTree2D", RStarTree<Point2D<PyData>>, PyPoint2D,
+    (max_entries: usize) => RStarTree::new(max_entries).map_err(spart_error)
+);
+
+thread_safe_tree!(
+    /// A 3D R*-tree that can be shared between Python threads.
+    PyThreadSafeRStarTree3D, "ThreadSafeRStarTree3D", RStarTree<Point3D<PyData>>, PyPoint3D,
+    (max_entries: usize) => RStarTree::new(max_entries).map_err(spart_error)
+);
@@ -1,25 +1,78 @@
 use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyBytes;
+use pyo3::types::{PyByteArray, PyBytes, PyDict, PyInt, PyTuple};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use spart::covariance::{Covariance, Neighborhood};
+use spart::stats::{TreeStats, TreeSummary};
+
+use crate::schema::Record;
 
 /// A wrapper around PyObject to allow it to be used as a generic parameter in spart's data structures.
-pub struct PyData(pub PyObject);
+///
+/// Trees in ids-only mode store their integer ids natively, so they don't keep a Python object
+/// alive per point. Other payloads are kept as the objects the caller passed in.
+/// Trees with a payload schema store their payloads as packed records (see the `schema` module).
+pub enum PyData {
+    /// An arbitrary Python object.
+    Object(PyObject),
+    /// An integer payload.
+    Int(i64),
+    /// A payload packed according to a schema.
+    Record(Box<Record>),
+}
+
+impl PyData {
+    /// Wraps a Python object.
+    pub fn new(obj: PyObject) -> Self {
+        PyData::Object(obj)
+    }
+
+    /// Wraps the payload of a point in an ids-only tree, storing exact integers natively.
+    pub fn id(obj: PyObject) -> Self {
+        Python::with_gil(|py| match exact_int(obj.bind(py)) {
+            Some(value) => PyData::Int(value),
+            None => PyData::Object(obj),
+        })
+    }
+
+    /// Returns the payload as a Python object.
+    pub fn to_object(&self, py: Python<'_>) -> PyObject {
+        match self {
+            PyData::Object(obj) => obj.clone_ref(py),
+            PyData::Int(value) => value
+                .into_pyobject(py)
+                .map(|v| v.into_any().unbind())
+                .unwrap_or_else(|e| match e {}),
+            PyData::Record(record) => record.to_object(py),
+        }
+    }
+}
 
 impl Clone for PyData {
     fn clone(&self) -> Self {
-        Python::with_gil(|py| PyData(self.0.clone_ref(py)))
+        match self {
+            PyData::Object(obj) => Python::with_gil(|py| PyData::Object(obj.clone_ref(py))),
+            PyData::Int(value) => PyData::Int(*value),
+            PyData::Record(record) => PyData::Record(record.clone()),
+        }
     }
 }
 
 impl PartialEq for PyData {
     fn eq(&self, other: &Self) -> bool {
-        Python::with_gil(
-            |py| match self.0.bind(py).rich_compare(&other.0, CompareOp::Eq) {
+        match (self, other) {
+            (PyData::Int(a), PyData::Int(b)) => return a == b,
+            (PyData::Record(a), PyData::Record(b)) if a.same_fields_as(b) => return a == b,
+            _ => {}
+        }
+        Python::with_gil(|py| {
+            let (self_obj, other_obj) = (self.to_object(py), other.to_object(py));
+            match self_obj.bind(py).rich_compare(other_obj, CompareOp::Eq) {
                 Ok(result) => result.is_truthy().unwrap_or(false),
                 Err(_) => false,
-            },
-        )
+            }
+        })
     }
 }
 
@@ -27,9 +80,18 @@ impl Eq for PyData {}
 
 impl PartialOrd for PyData {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (PyData::Int(a), PyData::Int(b)) => return Some(a.cmp(b)),
+            (PyData::Record(a), PyData::Record(b)) if a.same_fields_as(b) => {
+                return a.partial_cmp(b);
+            }
+            _ => {}
+        }
         Python::with_gil(|py| {
-            let self_obj = self.0.bind(py);
-            let other_obj = other.0.bind(py);
+            let self_obj = self.to_object(py);
+            let self_obj = self_obj.bind(py);
+            let other_obj = other.to_object(py);
+            let other_obj = other_obj.bind(py);
             if let Ok(result) = self_obj.rich_compare(other_obj, CompareOp::Lt) {
                 if result.is_truthy().unwrap_or(false) {
                     return Some(std::cmp::Ordering::Less);
@@ -54,7 +116,7 @@ impl std::fmt::Debug for PyData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         Python::with_gil(|py| {
             let repr = self
-                .0
+                .to_object(py)
                 .bind(py)
                 .repr()
                 .map(|r| r.to_string())
@@ -71,9 +133,8 @@ impl Serialize for PyData {
     {
         Python::with_gil(|py| {
             let pickle = py.import("pickle").map_err(serde::ser::Error::custom)?;
-            let bound_self = self.0.bind(py);
             let bytes = pickle
-                .call_method1("dumps", (bound_self,))
+                .call_method1("dumps", (self.to_object(py),))
                 .map_err(serde::ser::Error::custom)?;
             let bytes: &[u8] = bytes.extract().map_err(serde::ser::Error::custom)?;
             serializer.serialize_bytes(bytes)
@@ -92,7 +153,146 @@ impl<'de> Deserialize<'de> for PyData {
             let obj = pickle
                 .call_method("loads", (PyBytes::new(py, &bytes),), None)
                 .map_err(serde::de::Error::custom)?;
-            Ok(PyData(obj.into()))
+            Ok(PyData::new(obj.into()))
         })
     }
 }
+
+/// Converts the statistics of a tree into a Python dictionary.
+pub fn tree_stats_dict<'py>(py: Python<'py>, stats: &TreeStats) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("size", stats.size)?;
+    dict.set_item("node_count", stats.node_count)?;
+    dict.set_item("leaf_count", stats.leaf_count)?;
+    dict.set_item("depth", stats.depth)?;
+    dict.set_item("fill_factor", stats.fill_factor)?;
+    Ok(dict)
+}
+
+/// Reads the `k` and `radius` arguments of a neighborhood query, exactly one of which is given.
+pub fn neighborhood_arg(k: Option<usize>, radius: Option<f64>) -> PyResult<Neighborhood> {
+    match (k, radius) {
+        (Some(k), None) => Ok(Neighborhood::Knn(k)),
+        (None, Some(radius)) => Ok(Neighborhood::Radius(radius)),
+        _ => Err(PyValueError::new_err("pass exactly one of k and radius")),
+    }
+}
+
+/// Converts the covariance of a neighborhood into a Python dictionary, with its
+/// eigen-decomposition if `eigen` is set, or `None` if the neighborhood is empty.
+pub fn covariance_dict(
+    py: Python<'_>,
+    covariance: Option<Covariance>,
+    eigen: bool,
+) -> PyResult<Option<Bound<'_, PyDict>>> {
+    let Some(covariance) = covariance else {
+        return Ok(None);
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("count", covariance.count)?;
+    dict.set_item("mean", PyTuple::new(py, &covariance.mean)?)?;
+    dict.set_item("covariance", &covariance.matrix)?;
+    if eigen {
+        let eigen = covariance.eigen();
+        dict.set_item("eigenvalues", &eigen.values)?;
+        dict.set_item("eigenvectors", &eigen.vectors)?;
+        dict.set_item("normal", PyTuple::new(py, &eigen.vectors[0])?)?;
+        dict.set_item("surface_variation", eigen.surface_variation())?;
+    }
+    Ok(Some(dict))
+}
+
+/// Converts the bounds of a tree summary into an `(xmin, ymin, ..., xmax, ymax, ...)` tuple, the
+/// form accepted for boundaries and query windows, or `None` if the summary has no bounds.
+pub fn bounds_tuple<'py>(
+    py: Python<'py>,
+    summary: &TreeSummary,
+) -> PyResult<Option<Bound<'py, PyTuple>>> {
+    if summary.bounds.is_empty() {
+        return Ok(None);
+    }
+    let mins = summary.bounds.iter().map(|&(min, _)| min);
+    let maxs = summary.bounds.iter().map(|&(_, max)| max);
+    let corners: Vec<f64> = mins.chain(maxs).collect();
+    PyTuple::new(py, corners).map(Some)
+}
+
+/// Calls the predicate of a `retain` on the payload of a point.
+///
+/// Returns whether to keep the point. Once the predicate has raised an exception, which is stored
+/// in `error`, it is not called again and all remaining points are kept.
+pub fn keep_payload(
+    keep: &Bound<'_, PyAny>,
+    data: Option<&PyData>,
+    error: &mut Option<PyErr>,
+) -> bool {
+    if error.is_some() {
+        return true;
+    }
+    let py = keep.py();
+    let data = data.map_or_else(|| py.None(), |d| d.to_object(py));
+    match keep.call1((data,)).and_then(|result| result.is_truthy()) {
+        Ok(keep) => keep,
+        Err(e) => {
+            *error = Some(e);
+            true
+        }
+    }
+}
+
+/// Returns the value of an exact `int` (not a subclass such as `bool`) that fits in 64 bits.
+fn exact_int(obj: &Bound<'_, PyAny>) -> Option<i64> {
+    if obj.is_exact_instance_of::<PyInt>() {
+        obj.extract().ok()
+    } else {
+        None
+    }
+}
+
+/// Checks that a payload is an integer id, as required by trees in ids-only mode.
+pub fn check_id(ids_only: bool, data: &PyObject) -> PyResult<()> {
+    if !ids_only {
+        return Ok(());
+    }
+    Python::with_gil(|py| {
+        let data = data.bind(py);
+        if exact_int(data).is_some() {
+            Ok(())
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "point data must be an integer id in ids-only mode, got {}",
+                data.repr()?
+            )))
+        }
+    })
+}
+
+/// Converts the payloads of query results into a NumPy array of integer ids.
+///
+/// The ids are written into a `bytearray` that NumPy then uses as the array's buffer, so NumPy is
+/// only needed at runtime.
+pub fn ids_array<'a>(
+    py: Python<'_>,
+    payloads: impl Iterator<Item = Option<&'a PyData>>,
+) -> PyResult<PyObject> {
+    let mut bytes = Vec::new();
+    for payload in payloads {
+        let id = match payload {
+            Some(PyData::Int(id)) => Some(*id),
+            Some(PyData::Object(obj)) => exact_int(obj.bind(py)),
+            _ => None,
+        };
+        let Some(id) = id else {
+            return Err(PyValueError::new_err(
+                "a stored point does not have an integer id as its data",
+            ));
+        };
+        bytes.extend(id.to_ne_bytes());
+    }
+    let numpy = py.import("numpy")?;
+    let array = numpy.call_method1(
+        "frombuffer",
+        (PyByteArray::new(py, &bytes), numpy.getattr("int64")?),
+    )?;
+    Ok(array.unbind())
+}
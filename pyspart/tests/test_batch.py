@@ -0,0 +1,66 @@
+import pytest
+
+from pyspart import (
+    Quadtree,
+    Octree,
+    KdTree2D,
+    KdTree3D,
+    RTree2D,
+    RTree3D,
+    RStarTree2D,
+    RStarTree3D,
+    Point2D,
+    Point3D,
+)
+
+
+def make_trees():
+    boundary2d = {"x": 0.0, "y": 0.0, "width": 100.0, "height": 100.0}
+    boundary3d = {"x": 0.0, "y": 0.0, "z": 0.0, "width": 100.0, "height": 100.0, "depth": 100.0}
+    return [
+        (Quadtree(boundary2d, 4), Point2D),
+        (Octree(boundary3d, 4), Point3D),
+        (KdTree2D(), Point2D),
+        (KdTree3D(), Point3D),
+        (RTree2D(4), Point2D),
+        (RTree3D(4), Point3D),
+        (RStarTree2D(4), Point2D),
+        (RStarTree3D(4), Point3D),
+    ]
+
+
+def make_point(point_type, i):
+    coords = [float(i % 10) * 9.0, float(i // 10) * 9.0]
+    if point_type is Point3D:
+        coords.append(1.0)
+    return point_type(*coords, i)
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_batch_applies_changes_on_exit(tree, point_type):
+    points = [make_point(point_type, i) for i in range(100)]
+    tree.insert_bulk(points[:50])
+    with tree.batch() as batch:
+        for p in points[50:]:
+            batch.insert(p)
+        for p in points[:25]:
+            batch.delete(p)
+        # Cancels the pending insert of the same point.
+        batch.delete(points[99])
+        assert len(batch) == 76
+        assert tree.stats()["size"] == 50
+
+    assert tree.stats()["size"] == 74
+    assert len(tree.knn_search(points[0], 100)) == 74
+    tree.check_invariants()
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_batch_is_discarded_on_error(tree, point_type):
+    with pytest.raises(KeyError):
+        with tree.batch() as batch:
+            batch.insert(make_point(point_type, 1))
+            raise KeyError("boom")
+    assert tree.stats()["size"] == 0
+    with pytest.raises(RuntimeError):
+        batch.insert(make_point(point_type, 2))
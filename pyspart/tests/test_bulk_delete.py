@@ -0,0 +1,67 @@
+import pytest
+
+from pyspart import (
+    Point2D, Point3D,
+    Quadtree, Octree,
+    KdTree2D, KdTree3D,
+    RTree2D, RTree3D,
+    RStarTree2D, RStarTree3D
+)
+
+
+def make_2d_trees():
+    boundary = {"x": 0.0, "y": 0.0, "width": 100.0, "height": 100.0}
+    return [Quadtree(boundary, 4), KdTree2D(), RTree2D(4), RStarTree2D(4)]
+
+
+def make_3d_trees():
+    boundary = {"x": 0.0, "y": 0.0, "z": 0.0, "width": 100.0, "height": 100.0, "depth": 100.0}
+    return [Octree(boundary, 4), KdTree3D(), RTree3D(4), RStarTree3D(4)]
+
+
+def all_data(tree, origin):
+    return sorted(p.data for p in tree.range_search(origin, 1000.0))
+
+
+@pytest.mark.parametrize("tree", make_2d_trees())
+def test_delete_bulk_and_retain_2d(tree):
+    points = [Point2D(float(i), float(i % 7), i) for i in range(50)]
+    tree.insert_bulk(points)
+
+    assert tree.delete_bulk(points[:10] + [Point2D(99.0, 99.0, "missing")]) == 10
+    assert tree.retain(lambda data: data % 2 == 0) == 20
+    assert all_data(tree, Point2D(0.0, 0.0, None)) == list(range(10, 50, 2))
+
+
+@pytest.mark.parametrize("tree", make_3d_trees())
+def test_delete_bulk_and_retain_3d(tree):
+    points = [Point3D(float(i), float(i % 7), float(i % 3), i) for i in range(50)]
+    tree.insert_bulk(points)
+
+    assert tree.delete_bulk(points[40:]) == 10
+    assert tree.retain(lambda data: data >= 30) == 30
+    assert all_data(tree, Point3D(0.0, 0.0, 0.0, None)) == list(range(30, 40))
+
+
+@pytest.mark.parametrize("tree", make_3d_trees())
+def test_delete_bulk_and_retain_count_duplicates(tree):
+    tree.insert_bulk([Point3D(float(i), 0.0, float(i), i) for i in range(20)])
+    tree.insert_bulk([Point3D(5.0, 0.0, 5.0, 5) for _ in range(3)])
+    duplicate = Point3D(5.0, 0.0, 5.0, 5)
+
+    assert tree.delete_bulk([duplicate]) == 1
+    assert len(all_data(tree, Point3D(0.0, 0.0, 0.0, None))) == 22
+    assert tree.retain(lambda data: data != 5) == 3
+    assert all_data(tree, Point3D(0.0, 0.0, 0.0, None)) == [i for i in range(20) if i != 5]
+
+
+def test_retain_propagates_exceptions():
+    tree = KdTree2D()
+    tree.insert_bulk([Point2D(float(i), 0.0, i) for i in range(5)])
+
+    def keep(data):
+        raise RuntimeError("boom")
+
+    with pytest.raises(RuntimeError, match="boom"):
+        tree.retain(keep)
+    assert len(tree.range_search(Point2D(0.0, 0.0, None), 10.0)) == 5
@@ -0,0 +1,45 @@
+import pytest
+
+from pyspart import KdTree3D, Octree, Point3D, SpartError
+
+
+def make_trees():
+    boundary = {"x": 0.0, "y": 0.0, "z": 0.0, "width": 20.0, "height": 20.0, "depth": 20.0}
+    return [Octree(boundary, 4), KdTree3D()]
+
+
+@pytest.mark.parametrize("tree", make_trees())
+def test_normal_of_a_plane(tree):
+    # Points on the plane y = 5.
+    tree.insert_bulk([Point3D(float(i % 10), 5.0, float(i // 10), i) for i in range(100)])
+
+    result = tree.neighborhood_covariance(Point3D(4.0, 5.0, 4.0, None), k=12, eigen=True)
+    assert result["count"] == 12
+    assert abs(abs(result["normal"][1]) - 1.0) < 1e-9
+    assert result["surface_variation"] < 1e-9
+    assert result["eigenvalues"] == sorted(result["eigenvalues"])
+
+    plain = tree.neighborhood_covariance(Point3D(4.0, 5.0, 4.0, None), radius=1.0)
+    assert plain["count"] == 5
+    assert "normal" not in plain
+    assert tree.neighborhood_covariance(Point3D(4.0, 15.0, 4.0, None), radius=1.0) is None
+
+
+@pytest.mark.parametrize("tree", make_trees())
+def test_batch_matches_single_queries(tree):
+    tree.insert_bulk([Point3D(float(i % 7), float(i % 5), float(i % 3), i) for i in range(105)])
+    queries = [Point3D(1.0, 2.0, 1.0, None), Point3D(5.0, 1.0, 0.0, None)]
+
+    batch = tree.neighborhood_covariances(queries, radius=2.0, eigen=True)
+    assert batch == [tree.neighborhood_covariance(q, radius=2.0, eigen=True) for q in queries]
+
+
+def test_invalid_neighborhoods():
+    tree = KdTree3D()
+    query = Point3D(0.0, 0.0, 0.0, None)
+    with pytest.raises(ValueError, match="exactly one of k and radius"):
+        tree.neighborhood_covariance(query)
+    with pytest.raises(ValueError, match="exactly one of k and radius"):
+        tree.neighborhood_covariance(query, k=3, radius=1.0)
+    with pytest.raises(SpartError):
+        tree.neighborhood_covariance(query, k=3)
@@ -0,0 +1,51 @@
+"""
+Test the exception classes raised by PySpart
+"""
+import pytest
+
+from pyspart import (
+    SpartError, InvalidCapacityError, DimensionMismatchError, OutOfBoundsError,
+    Quadtree, Octree,
+    KdTree2D,
+    RTree2D, RTree3D,
+    RStarTree2D, RStarTree3D,
+    ThreadSafeQuadtree, ThreadSafeRTree2D,
+)
+
+RECT = {"x": 0.0, "y": 0.0, "width": 100.0, "height": 100.0}
+CUBE = {"x": 0.0, "y": 0.0, "z": 0.0, "width": 100.0, "height": 100.0, "depth": 100.0}
+
+
+def test_hierarchy():
+    """All errors derive from SpartError, which stays a ValueError"""
+    assert issubclass(SpartError, ValueError)
+    for error in (InvalidCapacityError, DimensionMismatchError, OutOfBoundsError):
+        assert issubclass(error, SpartError)
+
+
+@pytest.mark.parametrize(
+    "make",
+    [
+        lambda: Quadtree(RECT, 0),
+        lambda: Octree(CUBE, 0),
+        lambda: RTree2D(0),
+        lambda: RTree3D(0),
+        lambda: RStarTree2D(0),
+        lambda: RStarTree3D(0),
+        lambda: ThreadSafeQuadtree(RECT, 0),
+        lambda: ThreadSafeRTree2D(0),
+    ],
+)
+def test_invalid_capacity(make):
+    """A capacity the tree can't use raises InvalidCapacityError"""
+    with pytest.raises(InvalidCapacityError, match="capacity"):
+        make()
+
+
+def test_load_of_damaged_file_raises_spart_error(tmp_path):
+    """Failing to load a tree raises the base SpartError"""
+    path = tmp_path / "empty.bin"
+    path.write_bytes(b"")
+    with pytest.raises(SpartError) as info:
+        KdTree2D.load(str(path))
+    assert not isinstance(info.value, InvalidCapacityError)
@@ -0,0 +1,90 @@
+import enum
+
+import pytest
+
+from pyspart import (
+    Quadtree,
+    Octree,
+    KdTree2D,
+    KdTree3D,
+    RTree2D,
+    RTree3D,
+    RStarTree2D,
+    RStarTree3D,
+    Point2D,
+    Point3D,
+)
+
+np = pytest.importorskip("numpy")
+
+
+def make_trees():
+    boundary2d = {"x": 0.0, "y": 0.0, "width": 100.0, "height": 100.0}
+    boundary3d = {"x": 0.0, "y": 0.0, "z": 0.0, "width": 100.0, "height": 100.0, "depth": 100.0}
+    return [
+        (Quadtree(boundary2d, 4, ids_only=True), Point2D),
+        (Octree(boundary3d, 4, ids_only=True), Point3D),
+        (KdTree2D(ids_only=True), Point2D),
+        (KdTree3D(ids_only=True), Point3D),
+        (RTree2D(4, ids_only=True), Point2D),
+        (RTree3D(4, ids_only=True), Point3D),
+        (RStarTree2D(4, ids_only=True), Point2D),
+        (RStarTree3D(4, ids_only=True), Point3D),
+    ]
+
+
+def make_point(point_type, x, y, data):
+    if point_type is Point3D:
+        return Point3D(x, y, 0.0, data)
+    return Point2D(x, y, data)
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_queries_return_id_arrays(tree, point_type):
+    assert tree.ids_only
+    tree.insert_bulk([make_point(point_type, float(i % 10), float(i // 10), i) for i in range(100)])
+    origin = make_point(point_type, 0.0, 0.0, None)
+
+    nearest = tree.knn_search(origin, 3)
+    assert isinstance(nearest, np.ndarray)
+    assert nearest.dtype == np.int64
+    assert sorted(nearest.tolist()) == [0, 1, 10]
+    assert sorted(tree.range_search(origin, 1.0).tolist()) == [0, 1, 10]
+
+    assert tree.delete(make_point(point_type, 0.0, 0.0, 0))
+    assert sorted(tree.knn_search(origin, 2).tolist()) == [1, 10]
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_non_integer_data_is_rejected(tree, point_type):
+    with pytest.raises(TypeError):
+        tree.insert(make_point(point_type, 1.0, 1.0, "a"))
+    with pytest.raises(TypeError):
+        tree.insert(make_point(point_type, 1.0, 1.0, True))
+    assert tree.stats()["size"] == 0
+
+
+def test_mode_is_chosen_on_load(tmp_path):
+    path = str(tmp_path / "tree.spart")
+    tree = KdTree2D()
+    tree.insert(Point2D(1.0, 2.0, 7))
+    tree.save(path)
+
+    assert KdTree2D.load(path, ids_only=True).knn_search(Point2D(0.0, 0.0, None), 1).tolist() == [7]
+    (point,) = KdTree2D.load(path).knn_search(Point2D(0.0, 0.0, None), 1)
+    assert point.data == 7
+
+
+class Color(enum.IntEnum):
+    RED = 1
+
+
+def test_default_mode_returns_the_inserted_objects():
+    tree = KdTree2D()
+    big = 2**40
+    tree.insert_bulk([Point2D(0.0, 0.0, big), Point2D(1.0, 0.0, Color.RED), Point2D(2.0, 0.0, True)])
+
+    found = {point.x: point.data for point in tree.knn_search(Point2D(0.0, 0.0, None), 3)}
+    assert found[0.0] is big
+    assert found[1.0] is Color.RED
+    assert found[2.0] is True
@@ -0,0 +1,111 @@
+from dataclasses import dataclass
+
+import pytest
+
+from pyspart import (
+    Quadtree,
+    Octree,
+    KdTree2D,
+    KdTree3D,
+    RTree2D,
+    RTree3D,
+    RStarTree2D,
+    RStarTree3D,
+    Point2D,
+    Point3D,
+)
+
+SCHEMA = {"name": str, "rating": float, "open": bool, "visits": int}
+
+
+@dataclass
+class Shop:
+    name: str
+    rating: float
+    open: bool
+    visits: int
+
+
+def make_trees(schema):
+    boundary2d = {"x": 0.0, "y": 0.0, "width": 100.0, "height": 100.0}
+    boundary3d = {"x": 0.0, "y": 0.0, "z": 0.0, "width": 100.0, "height": 100.0, "depth": 100.0}
+    return [
+        (Quadtree(boundary2d, 4, schema=schema), Point2D),
+        (Octree(boundary3d, 4, schema=schema), Point3D),
+        (KdTree2D(schema=schema), Point2D),
+        (KdTree3D(schema=schema), Point3D),
+        (RTree2D(4, schema=schema), Point2D),
+        (RTree3D(4, schema=schema), Point3D),
+        (RStarTree2D(4, schema=schema), Point2D),
+        (RStarTree3D(4, schema=schema), Point3D),
+    ]
+
+
+def make_point(point_type, x, y, data):
+    if point_type is Point3D:
+        return Point3D(x, y, 0.0, data)
+    return Point2D(x, y, data)
+
+
+def shops():
+    return [
+        ("bakery", 4.5, True, 120),
+        ("florist", 3.9, True, 40),
+        ("grocer", 4.8, False, 300),
+        ("tailor", 4.1, True, 15),
+    ]
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees(SCHEMA))
+def test_dict_payloads_round_trip(tree, point_type):
+    for i, (name, rating, is_open, visits) in enumerate(shops()):
+        data = {"name": name, "rating": rating, "open": is_open, "visits": visits}
+        tree.insert(make_point(point_type, float(i), float(i), data))
+
+    results = tree.knn_search(make_point(point_type, 0.0, 0.0, None), 1)
+    assert results[0].data == {"name": "bakery", "rating": 4.5, "open": True, "visits": 120}
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees(Shop))
+def test_range_search_where(tree, point_type):
+    tree.insert_bulk(
+        [make_point(point_type, float(i), float(i), Shop(*shop)) for i, shop in enumerate(shops())]
+    )
+
+    query = make_point(point_type, 0.0, 0.0, None)
+    results = tree.range_search_where(query, 10.0, open=True, rating=(4.0, None))
+    assert sorted(p.data.name for p in results) == ["bakery", "tailor"]
+    assert all(isinstance(p.data, Shop) for p in results)
+
+    results = tree.range_search_where(query, 10.0, visits=(None, 100))
+    assert sorted(p.data.name for p in results) == ["florist", "tailor"]
+
+    assert len(tree.range_search_where(query, 10.0)) == 4
+
+
+def test_deleting_a_packed_payload():
+    tree = KdTree2D(schema=[("name", str), ("visits", int)])
+    tree.insert(Point2D(1.0, 1.0, {"name": "bakery", "visits": 3}))
+    assert tree.delete(Point2D(1.0, 1.0, {"name": "bakery", "visits": 3}))
+    assert tree.size == 0
+
+
+def test_payloads_must_match_the_schema():
+    tree = RTree2D(4, schema=SCHEMA)
+    with pytest.raises(TypeError, match="missing the field 'visits'"):
+        tree.insert(Point2D(1.0, 1.0, {"name": "a", "rating": 1.0, "open": True}))
+    with pytest.raises(TypeError, match="field 'visits' must be int"):
+        tree.insert(Point2D(1.0, 1.0, {"name": "a", "rating": 1.0, "open": True, "visits": "3"}))
+
+
+def test_invalid_schemas_and_conditions():
+    with pytest.raises(TypeError, match="unsupported field type"):
+        KdTree2D(schema={"tags": list})
+    with pytest.raises(ValueError):
+        KdTree2D(ids_only=True, schema=SCHEMA)
+
+    tree = KdTree2D(schema=SCHEMA)
+    with pytest.raises(TypeError, match="no field 'color'"):
+        tree.range_search_where(Point2D(0.0, 0.0, None), 1.0, color="red")
+    with pytest.raises(TypeError, match="payload schema"):
+        KdTree2D().range_search_where(Point2D(0.0, 0.0, None), 1.0, name="a")
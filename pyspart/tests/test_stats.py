@@ -0,0 +1,102 @@
+import copy
+import os
+import pytest
+
+from pyspart import (
+    Quadtree,
+    Octree,
+    KdTree2D,
+    KdTree3D,
+    RTree2D,
+    RTree3D,
+    RStarTree2D,
+    RStarTree3D,
+    Point2D,
+    Point3D,
+)
+
+
+def make_trees():
+    boundary2d = {"x": 0.0, "y": 0.0, "width": 100.0, "height": 100.0}
+    boundary3d = {"x": 0.0, "y": 0.0, "z": 0.0, "width": 100.0, "height": 100.0, "depth": 100.0}
+    return [
+        (Quadtree(boundary2d, 4), Point2D),
+        (Octree(boundary3d, 4), Point3D),
+        (KdTree2D(), Point2D),
+        (KdTree3D(), Point3D),
+        (RTree2D(4), Point2D),
+        (RTree3D(4), Point3D),
+        (RStarTree2D(4), Point2D),
+        (RStarTree3D(4), Point3D),
+    ]
+
+
+def make_point(point_type, i):
+    coords = [float(i % 10) * 9.0, float(i // 10) * 9.0]
+    if point_type is Point3D:
+        coords.append(float(i % 7) * 9.0)
+    return point_type(*coords, i)
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_stats_reports_tree_shape(tree, point_type):
+    for i in range(50):
+        tree.insert(make_point(point_type, i))
+    stats = tree.stats()
+    assert stats["size"] == 50
+    assert stats["node_count"] >= stats["leaf_count"] >= 1
+    assert stats["depth"] >= 1
+    if isinstance(tree, (KdTree2D, KdTree3D)):
+        assert stats["fill_factor"] is None
+    else:
+        assert 0.0 < stats["fill_factor"] <= 1.0
+    assert tree.check_invariants() is None
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_invariants_hold_after_save_and_load(tree, point_type, tmp_path):
+    for i in range(50):
+        tree.insert(make_point(point_type, i))
+    for i in range(0, 50, 3):
+        tree.delete(make_point(point_type, i))
+    path = os.path.join(tmp_path, "tree.spart")
+    tree.save(path)
+    loaded = type(tree).load(path)
+    loaded.check_invariants()
+    assert loaded.stats() == tree.stats()
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_copies_have_equal_content(tree, point_type):
+    for i in range(50):
+        tree.insert(make_point(point_type, i))
+    copied = copy.copy(tree)
+    assert copied.content_eq(tree)
+    assert tree.copy().content_eq(copied)
+
+    copied.delete(make_point(point_type, 0))
+    assert not copied.content_eq(tree)
+    assert tree.stats()["size"] == 50
+    copied.insert(make_point(point_type, 0))
+    assert copied.content_eq(tree)
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_shape_properties(tree, point_type):
+    dims = 3 if point_type is Point3D else 2
+    assert tree.dims == dims
+    assert tree.size == 0
+    fixed_boundary = isinstance(tree, (Quadtree, Octree))
+    if not fixed_boundary:
+        assert tree.bounds is None
+
+    for i in range(40):
+        tree.insert(make_point(point_type, i))
+    assert tree.size == 40
+    assert tree.depth == tree.stats()["depth"]
+    assert len(tree.bounds) == 2 * dims
+    if fixed_boundary:
+        assert tree.bounds == (0.0,) * dims + (100.0,) * dims
+    else:
+        expected_max = (81.0, 27.0, 54.0)[:dims]
+        assert tree.bounds == (0.0,) * dims + expected_max
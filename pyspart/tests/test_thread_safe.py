@@ -0,0 +1,104 @@
+import asyncio
+import threading
+
+import pytest
+
+from pyspart import (
+    ThreadSafeQuadtree,
+    ThreadSafeOctree,
+    ThreadSafeKdTree2D,
+    ThreadSafeKdTree3D,
+    ThreadSafeRTree2D,
+    ThreadSafeRTree3D,
+    ThreadSafeRStarTree2D,
+    ThreadSafeRStarTree3D,
+    Point2D,
+    Point3D,
+)
+
+
+def make_trees():
+    boundary2d = {"x": 0.0, "y": 0.0, "width": 100.0, "height": 100.0}
+    boundary3d = {"x": 0.0, "y": 0.0, "z": 0.0, "width": 100.0, "height": 100.0, "depth": 100.0}
+    return [
+        (ThreadSafeQuadtree(boundary2d, 4), Point2D),
+        (ThreadSafeOctree(boundary3d, 4), Point3D),
+        (ThreadSafeKdTree2D(), Point2D),
+        (ThreadSafeKdTree3D(), Point3D),
+        (ThreadSafeRTree2D(4), Point2D),
+        (ThreadSafeRTree3D(4), Point3D),
+        (ThreadSafeRStarTree2D(4), Point2D),
+        (ThreadSafeRStarTree3D(4), Point3D),
+    ]
+
+
+def make_point(point_type, i, data=None):
+    coords = [float(i % 10) * 9.0, float(i // 10 % 10) * 9.0]
+    if point_type is Point3D:
+        coords.append(float(i % 7) * 9.0)
+    return point_type(*coords, data)
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_basic_operations(tree, point_type):
+    points = [make_point(point_type, i, i) for i in range(50)]
+    tree.insert_bulk(points[:25])
+    for point in points[25:]:
+        assert tree.insert(point)
+    assert len(tree) == 50
+    assert tree.stats()["size"] == 50
+
+    nearest = tree.knn_search(points[7], 1)
+    assert [p.data for p in nearest] == [7]
+    assert 7 in [p.data for p in tree.range_search(points[7], 1.0)]
+
+    assert tree.delete(points[7])
+    assert not tree.delete(points[7])
+    assert len(tree) == 49
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_concurrent_writers_and_readers(tree, point_type):
+    errors = []
+
+    def write(offset):
+        try:
+            for i in range(offset, 400, 4):
+                tree.insert(make_point(point_type, i, i))
+        except Exception as e:  # pragma: no cover - reported below
+            errors.append(e)
+
+    def read():
+        try:
+            query = make_point(point_type, 55)
+            for _ in range(100):
+                assert len(tree.knn_search(query, 3)) <= 3
+                tree.range_search(query, 10.0)
+        except Exception as e:  # pragma: no cover - reported below
+            errors.append(e)
+
+    threads = [threading.Thread(target=write, args=(i,)) for i in range(4)]
+    threads += [threading.Thread(target=read) for _ in range(4)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    assert errors == []
+    assert len(tree) == 400
+
+
+@pytest.mark.parametrize("tree,point_type", make_trees())
+def test_async_queries(tree, point_type):
+    points = [make_point(point_type, i, i) for i in range(50)]
+    tree.insert_bulk(points)
+
+    async def query():
+        return await asyncio.gather(
+            tree.knn_search_async(points[7], 1),
+            tree.range_search_async(points[7], 1.0),
+        )
+
+    nearest, nearby = asyncio.run(query())
+    assert [p.data for p in nearest] == [7]
+    assert sorted(p.data for p in nearby) == sorted(p.data for p in tree.range_search(points[7], 1.0))
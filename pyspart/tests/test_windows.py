@@ -0,0 +1,65 @@
+import pytest
+
+from pyspart import Quadtree, Octree, RTree2D, RTree3D, RStarTree2D, RStarTree3D, Point2D, Point3D
+
+
+class ShapelyLikeBox:
+    bounds = (0.0, 0.0, 2.5, 2.5)
+
+
+WINDOWS_2D = [
+    {"x": 0.0, "y": 0.0, "width": 2.5, "height": 2.5},
+    {"min": (0.0, 0.0), "max": [2.5, 2.5]},
+    (0, 0, 2.5, 2.5),
+    [0, 0, 2.5, 2.5],
+    ShapelyLikeBox(),
+]
+
+WINDOWS_3D = [
+    {"x": 0.0, "y": 0.0, "z": 0.0, "width": 2.5, "height": 2.5, "depth": 2.5},
+    {"min": (0.0, 0.0, 0.0), "max": (2.5, 2.5, 2.5)},
+    (0, 0, 0, 2.5, 2.5, 2.5),
+]
+
+
+@pytest.mark.parametrize("window", WINDOWS_2D)
+@pytest.mark.parametrize("make_tree", [lambda: Quadtree((0, 0, 10, 10), 2), lambda: RTree2D(4), lambda: RStarTree2D(4)])
+def test_2d_window_forms(make_tree, window):
+    tree = make_tree()
+    tree.insert_bulk([Point2D(float(i % 10), float(i // 10), i) for i in range(100)])
+    assert sorted(p.data for p in tree.range_search_bbox(window)) == [0, 1, 2, 10, 11, 12, 20, 21, 22]
+
+
+@pytest.mark.parametrize("window", WINDOWS_3D)
+@pytest.mark.parametrize("make_tree", [lambda: RTree3D(4), lambda: RStarTree3D(4)])
+def test_3d_window_forms(make_tree, window):
+    tree = make_tree()
+    tree.insert_bulk([Point3D(float(i % 10), float(i // 10), 1.0, i) for i in range(100)])
+    assert len(tree.range_search_bbox(window)) == 9
+
+
+@pytest.mark.parametrize("boundary", WINDOWS_3D)
+def test_octree_boundary_forms(boundary):
+    tree = Octree(boundary, 4)
+    assert tree.insert(Point3D(1.0, 1.0, 1.0, "a"))
+    assert not tree.insert(Point3D(3.0, 1.0, 1.0, "b"))
+
+
+@pytest.mark.parametrize(
+    "boundary,error,message",
+    [
+        ((0, 0, 1), ValueError, "must have 4 numbers"),
+        ((0, 0, "a", 1), TypeError, "sequence of numbers"),
+        ("0 0 1 1", TypeError, "expected a dict"),
+        (None, TypeError, "expected a dict"),
+        ({"x": 0, "y": 0, "width": 1}, ValueError, "missing 'height'"),
+        ({"x": 0, "y": 0, "width": "w", "height": 1}, TypeError, "'width' must be a number"),
+        ({"min": (0, 0)}, ValueError, "missing 'max'"),
+        ({"min": (0, 0, 0), "max": (1, 1, 1)}, ValueError, "must have 2 numbers"),
+        ((1, 0, 0, 1), ValueError, "less than the lower bound"),
+        ((0, 0, float("nan"), 1), ValueError, "must be finite"),
+    ],
+)
+def test_malformed_windows_raise_clear_errors(boundary, error, message):
+    with pytest.raises(error, match=message):
+        Quadtree(boundary, 4)
@@ -0,0 +1,434 @@
+//! ## Adaptive Indexes
+//!
+//! This module provides `AutoIndex`, an index that picks its own structure based on the
+//! number of points it holds, for applications that embed spart without knowing how much data
+//! they will get. Small point sets are kept in a `LinearIndex`, which is scanned on every
+//! query and is usually faster than a tree walk at that size. Once the index grows beyond a
+//! threshold, its points are moved into a `KdTree`; once it shrinks below a lower threshold,
+//! they are moved back. The gap between the two thresholds (the hysteresis) keeps an index
+//! whose size hovers around one threshold from being rebuilt on every change.
+//!
+//! Migrations happen inside `insert` and `delete` and are invisible to callers, apart from
+//! the time the rebuild takes.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::adaptive::AutoIndex;
+//! use spart::geometry::{EuclideanDistance, Point2D};
+//!
+//! let mut index = AutoIndex::new().with_thresholds(100, 50);
+//! for i in 0..150 {
+//!     index.insert(Point2D::new(i as f64, 0.0, Some(i))).unwrap();
+//! }
+//! assert!(index.uses_tree());
+//!
+//! let nearest = index.knn_search::<EuclideanDistance>(&Point2D::new(10.2, 0.0, None), 1);
+//! assert_eq!(nearest[0].data, Some(10));
+//!
+//! for i in 0..120 {
+//!     index.delete(&Point2D::new(i as f64, 0.0, Some(i)));
+//! }
+//! assert!(!index.uses_tree());
+//! assert_eq!(index.len(), 30);
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::DistanceMetric;
+use crate::kdtree::{KdPoint, KdTree};
+use crate::linear::LinearIndex;
+
+/// The size beyond which new indexes move their points into a Kd-tree.
+pub const DEFAULT_TREE_THRESHOLD: usize = 256;
+
+/// The size below which new indexes move their points back into a linear index.
+pub const DEFAULT_LINEAR_THRESHOLD: usize = 128;
+
+/// The structure currently holding the points of an `AutoIndex`.
+#[derive(Debug, Clone)]
+enum Backend<P: KdPoint> {
+    Linear(LinearIndex<P>),
+    Tree(KdTree<P>),
+}
+
+/// An index that switches between a linear scan and a Kd-tree depending on its size.
+///
+/// # Type Parameters
+///
+/// * `P`: The type of the stored points.
+#[derive(Debug, Clone)]
+pub struct AutoIndex<P: KdPoint> {
+    backend: Backend<P>,
+    len: usize,
+    dims: Option<usize>,
+    tree_threshold: usize,
+    linear_threshold: usize,
+}
+
+impl<P: KdPoint> Default for AutoIndex<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: KdPoint> AutoIndex<P> {
+    /// Creates a new, empty index with the default thresholds.
+    pub fn new() -> Self {
+        AutoIndex {
+            backend: Backend::Linear(LinearIndex::new()),
+            len: 0,
+            dims: None,
+            tree_threshold: DEFAULT_TREE_THRESHOLD,
+            linear_threshold: DEFAULT_LINEAR_THRESHOLD,
+        }
+    }
+
+    /// Sets the sizes at which the index changes structure.
+    ///
+    /// The points are moved into a Kd-tree once there are more than `tree_threshold` of
+    /// them, and back into a linear index once there are fewer than `linear_threshold`.
+    /// `linear_threshold` is capped at `tree_threshold`. The index migrates right away if its
+    /// current size calls for the other structure.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree_threshold` - The size beyond which a Kd-tree is used.
+    /// * `linear_threshold` - The size below which a linear index is used.
+    pub fn with_thresholds(mut self, tree_threshold: usize, linear_threshold: usize) -> Self {
+        self.tree_threshold = tree_threshold;
+        self.linear_threshold = linear_threshold.min(tree_threshold);
+        self.migrate();
+        self
+    }
+
+    /// Returns the size beyond which a Kd-tree is used.
+    pub fn tree_threshold(&self) -> usize {
+        self.tree_threshold
+    }
+
+    /// Returns the size below which a linear index is used.
+    pub fn linear_threshold(&self) -> usize {
+        self.linear_threshold
+    }
+
+    /// Returns `true` if the points are currently held in a Kd-tree.
+    pub fn uses_tree(&self) -> bool {
+        matches!(self.backend, Backend::Tree(_))
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts a point, moving the points into a Kd-tree if the index grows beyond the tree
+    /// threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::DimensionMismatch` if the point's dimension differs from that of
+    /// the stored points.
+    pub fn insert(&mut self, point: P) -> Result<(), SpartError> {
+        match self.dims {
+            Some(dims) if point.dims() != dims => {
+                return Err(SpartError::DimensionMismatch {
+                    expected: dims,
+                    actual: point.dims(),
+                });
+            }
+            _ => self.dims = Some(point.dims()),
+        }
+        match &mut self.backend {
+            Backend::Linear(index) => index.insert(point),
+            Backend::Tree(tree) => tree.insert(point)?,
+        }
+        self.len += 1;
+        self.migrate();
+        Ok(())
+    }
+
+    /// Deletes a point, moving the points back into a linear index if the index shrinks below
+    /// the linear threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to delete.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and deleted.
+    pub fn delete(&mut self, point: &P) -> bool {
+        let deleted = match &mut self.backend {
+            Backend::Linear(index) => index.delete(point),
+            Backend::Tree(tree) => tree.delete(point),
+        };
+        if deleted {
+            self.len -= 1;
+            if self.len == 0 {
+                self.dims = None;
+            }
+            self.migrate();
+        }
+        deleted
+    }
+
+    /// Returns `true` if a point equal to `point` is stored.
+    pub fn contains(&self, point: &P) -> bool {
+        match &self.backend {
+            Backend::Linear(index) => index.contains(point),
+            Backend::Tree(tree) => tree.contains(point),
+        }
+    }
+
+    /// Finds the `k` nearest neighbors of the target point.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search from.
+    /// * `k` - The number of neighbors to find.
+    ///
+    /// # Returns
+    ///
+    /// Up to `k` points, sorted from the nearest to the farthest.
+    ///
+    /// # Note
+    ///
+    /// Once a Kd-tree is in use, the search is pruned with Euclidean distance, as for
+    /// `KdTree::knn_search`.
+    pub fn knn_search<M: DistanceMetric<P>>(&self, target: &P, k: usize) -> Vec<P> {
+        match &self.backend {
+            Backend::Linear(index) if self.dims == Some(target.dims()) => {
+                index.knn_search::<M>(target, k)
+            }
+            Backend::Linear(_) => Vec::new(),
+            Backend::Tree(tree) => tree.knn_search::<M>(target, k),
+        }
+    }
+
+    /// Finds all points within `radius` of the center point.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// The points within the radius.
+    pub fn range_search<M: DistanceMetric<P>>(&self, center: &P, radius: f64) -> Vec<P> {
+        match &self.backend {
+            Backend::Linear(index) if self.dims == Some(center.dims()) => {
+                index.range_search::<M>(center, radius)
+            }
+            Backend::Linear(_) => Vec::new(),
+            Backend::Tree(tree) => tree.range_search::<M>(center, radius),
+        }
+    }
+
+    /// Moves the points into the structure suited to the current size, if they are not
+    /// there yet.
+    fn migrate(&mut self) {
+        let backend = match &mut self.backend {
+            Backend::Linear(index) if self.len > self.tree_threshold => {
+                let mut tree = KdTree::new();
+                tree.insert_bulk(index.as_slice().to_vec())
+                    .unwrap_or_else(|_| unreachable!("points share the index's dimension"));
+                Backend::Tree(tree)
+            }
+            Backend::Tree(tree) if self.len < self.linear_threshold => {
+                let points = std::mem::take(tree).into_points();
+                Backend::Linear(points.into_iter().collect())
+            }
+            _ => return,
+        };
+        self.backend = backend;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{EuclideanDistance, Point3D};
+
+    /// A point with any number of dimensions.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Flexible(Vec<f64>);
+
+    impl KdPoint for Flexible {
+        fn dims(&self) -> usize {
+            self.0.len()
+        }
+
+        fn coord(&self, axis: usize) -> Result<f64, SpartError> {
+            self.0
+                .get(axis)
+                .copied()
+                .ok_or(SpartError::InvalidDimension {
+                    requested: axis,
+                    available: self.0.len(),
+                })
+        }
+    }
+
+    impl DistanceMetric<Flexible> for EuclideanDistance {
+        fn distance_sq(a: &Flexible, b: &Flexible) -> f64 {
+            a.0.iter().zip(&b.0).map(|(x, y)| (x - y) * (x - y)).sum()
+        }
+    }
+
+    fn point(i: i32) -> Point3D<i32> {
+        Point3D::new(i as f64, (i * 7 % 5) as f64, 0.0, Some(i))
+    }
+
+    /// An index and a linear index holding the points `0..n`.
+    fn filled(n: i32) -> (AutoIndex<Point3D<i32>>, LinearIndex<Point3D<i32>>) {
+        let mut index = AutoIndex::new().with_thresholds(20, 10);
+        let mut reference = LinearIndex::new();
+        for i in 0..n {
+            index.insert(point(i)).unwrap();
+            reference.insert(point(i));
+        }
+        (index, reference)
+    }
+
+    #[test]
+    fn test_linear_threshold_is_capped() {
+        let index = AutoIndex::<Point3D<()>>::new().with_thresholds(20, 30);
+        assert_eq!((index.tree_threshold(), index.linear_threshold()), (20, 20));
+    }
+
+    #[test]
+    fn test_empty_index_finds_nothing() {
+        let mut index: AutoIndex<Point3D<i32>> = AutoIndex::new();
+        assert!(index.is_empty() && !index.uses_tree());
+        assert!(
+            index
+                .knn_search::<EuclideanDistance>(&point(0), 3)
+                .is_empty()
+        );
+        assert!(
+            index
+                .range_search::<EuclideanDistance>(&point(0), 10.0)
+                .is_empty()
+        );
+        assert!(!index.delete(&point(0)));
+    }
+
+    #[test]
+    fn test_insert_rejects_dimension_mismatch() {
+        for n in [3, 30] {
+            let mut index = AutoIndex::new().with_thresholds(20, 10);
+            for i in 0..n {
+                index.insert(Flexible(vec![i as f64, 0.0])).unwrap();
+            }
+            assert!(matches!(
+                index.insert(Flexible(vec![0.0, 0.0, 0.0])),
+                Err(SpartError::DimensionMismatch {
+                    expected: 2,
+                    actual: 3
+                })
+            ));
+            assert_eq!(index.len(), n);
+        }
+    }
+
+    #[test]
+    fn test_queries_with_other_dimension_find_nothing() {
+        let mut index = AutoIndex::new();
+        index.insert(Flexible(vec![0.0, 0.0])).unwrap();
+        let target = Flexible(vec![0.0, 0.0, 0.0]);
+        assert!(index.knn_search::<EuclideanDistance>(&target, 1).is_empty());
+        assert!(
+            index
+                .range_search::<EuclideanDistance>(&target, 1.0)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_emptied_index_accepts_new_dimension() {
+        let mut index = AutoIndex::new();
+        index.insert(Flexible(vec![0.0, 0.0])).unwrap();
+        assert!(index.delete(&Flexible(vec![0.0, 0.0])));
+        index.insert(Flexible(vec![0.0, 0.0, 0.0])).unwrap();
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn test_migrates_to_tree_beyond_threshold() {
+        let mut index = AutoIndex::new().with_thresholds(20, 10);
+        for i in 0..25 {
+            index.insert(point(i)).unwrap();
+            assert_eq!(index.uses_tree(), i >= 20);
+        }
+    }
+
+    #[test]
+    fn test_migrates_back_with_hysteresis() {
+        let (mut index, _) = filled(25);
+        for i in 0..16 {
+            assert!(index.delete(&point(i)));
+            // Stays a tree until the size drops below the lower threshold.
+            assert_eq!(index.uses_tree(), index.len() >= 10);
+        }
+        assert!(!index.uses_tree());
+        assert!(index.contains(&point(16)));
+        assert_eq!(index.len(), 9);
+    }
+
+    #[test]
+    fn test_delete_missing_point_returns_false() {
+        for n in [5, 25] {
+            let (mut index, _) = filled(n);
+            assert!(!index.delete(&point(n)));
+            assert!(index.delete(&point(0)));
+            assert!(!index.delete(&point(0)));
+            assert_eq!(index.len(), n as usize - 1);
+        }
+    }
+
+    #[test]
+    fn test_searches_match_linear_index() {
+        let target = Point3D::new(15.3, 2.0, 0.0, None);
+        let distance = |p: &Point3D<i32>| EuclideanDistance::distance_sq(p, &target);
+        for n in [12, 25] {
+            let (index, reference) = filled(n);
+            let found: Vec<f64> = index
+                .knn_search::<EuclideanDistance>(&target, 5)
+                .iter()
+                .map(distance)
+                .collect();
+            let expected: Vec<f64> = reference
+                .knn_search::<EuclideanDistance>(&target, 5)
+                .iter()
+                .map(distance)
+                .collect();
+            assert_eq!(found, expected);
+            assert_eq!(
+                index.range_search::<EuclideanDistance>(&target, 4.0).len(),
+                reference
+                    .range_search::<EuclideanDistance>(&target, 4.0)
+                    .len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_range_search_includes_points_at_radius() {
+        let center = Point3D::new(2.0, 2.0, 0.0, None);
+        for n in [12, 25] {
+            let (index, _) = filled(n);
+            let found = index.range_search::<EuclideanDistance>(&center, 1.0);
+            assert_eq!(found, [point(1)]);
+        }
+    }
+}
@@ -0,0 +1,991 @@
+//! ## Per-Node Aggregates
+//!
+//! This module provides `AggregateQuadtree`, a quadtree over 2D points that maintains a
+//! user-defined aggregate (for example a count, a sum, or a maximum of some payload value) in
+//! every node. The aggregate is kept up to date on insertion and deletion, which allows
+//! `aggregate_in_bbox` to combine whole subtrees that are fully covered by the query window
+//! without visiting their points.
+//!
+//! Aggregates are described by the `Aggregate` trait and must form a commutative monoid: an
+//! identity value and an associative, commutative `combine` operation. The `Stats` aggregate
+//! maintains the count, sum, minimum, and maximum of a numeric payload value at once, and adds
+//! rollup queries such as `min_in_bbox` and `sum_in_radius`.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::aggregate::{AggregateQuadtree, Sum};
+//! use spart::geometry::{Point2D, Rectangle};
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! // Sum the population stored as the payload of each point.
+//! let mut tree = AggregateQuadtree::new(&boundary, 4, Sum(|p: &Point2D<u32>| {
+//!     p.data.unwrap_or(0) as f64
+//! }))
+//! .unwrap();
+//! tree.insert(Point2D::new(10.0, 10.0, Some(120)));
+//! tree.insert(Point2D::new(20.0, 15.0, Some(80)));
+//! tree.insert(Point2D::new(90.0, 90.0, Some(1_000)));
+//!
+//! let window = Rectangle { x: 0.0, y: 0.0, width: 50.0, height: 50.0 };
+//! assert_eq!(tree.aggregate_in_bbox(&window), 200.0);
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{Point2D, Rectangle};
+use crate::quadtree::{MAX_DEPTH, child_boundaries, quadrant_index};
+use ordered_float::OrderedFloat;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use tracing::info;
+
+/// Trait describing an aggregate that can be maintained per node of an `AggregateQuadtree`.
+///
+/// Implementations must form a commutative monoid: `combine` must be associative and
+/// commutative, and `identity` must be its neutral element.
+pub trait Aggregate<T> {
+    /// The type of the aggregated value.
+    type Value: Clone;
+    /// Returns the neutral element of `combine`.
+    fn identity(&self) -> Self::Value;
+    /// Returns the aggregate of a single point.
+    fn lift(&self, point: &Point2D<T>) -> Self::Value;
+    /// Combines two aggregated values.
+    fn combine(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// Aggregate counting the number of points.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Count;
+
+impl<T> Aggregate<T> for Count {
+    type Value = usize;
+
+    fn identity(&self) -> usize {
+        0
+    }
+
+    fn lift(&self, _point: &Point2D<T>) -> usize {
+        1
+    }
+
+    fn combine(&self, a: &usize, b: &usize) -> usize {
+        a + b
+    }
+}
+
+/// Aggregate summing a value extracted from each point.
+#[derive(Debug, Clone, Copy)]
+pub struct Sum<F>(pub F);
+
+impl<T, F: Fn(&Point2D<T>) -> f64> Aggregate<T> for Sum<F> {
+    type Value = f64;
+
+    fn identity(&self) -> f64 {
+        0.0
+    }
+
+    fn lift(&self, point: &Point2D<T>) -> f64 {
+        (self.0)(point)
+    }
+
+    fn combine(&self, a: &f64, b: &f64) -> f64 {
+        a + b
+    }
+}
+
+/// Aggregate computing the maximum of a value extracted from each point.
+///
+/// The identity is `f64::NEG_INFINITY`, which is also the result for an empty region.
+#[derive(Debug, Clone, Copy)]
+pub struct Max<F>(pub F);
+
+impl<T, F: Fn(&Point2D<T>) -> f64> Aggregate<T> for Max<F> {
+    type Value = f64;
+
+    fn identity(&self) -> f64 {
+        f64::NEG_INFINITY
+    }
+
+    fn lift(&self, point: &Point2D<T>) -> f64 {
+        (self.0)(point)
+    }
+
+    fn combine(&self, a: &f64, b: &f64) -> f64 {
+        a.max(*b)
+    }
+}
+
+/// Aggregate computing the minimum of a value extracted from each point.
+///
+/// The identity is `f64::INFINITY`, which is also the result for an empty region.
+#[derive(Debug, Clone, Copy)]
+pub struct Min<F>(pub F);
+
+impl<T, F: Fn(&Point2D<T>) -> f64> Aggregate<T> for Min<F> {
+    type Value = f64;
+
+    fn identity(&self) -> f64 {
+        f64::INFINITY
+    }
+
+    fn lift(&self, point: &Point2D<T>) -> f64 {
+        (self.0)(point)
+    }
+
+    fn combine(&self, a: &f64, b: &f64) -> f64 {
+        a.min(*b)
+    }
+}
+
+/// The count, sum, minimum, and maximum of the values of a set of points, as maintained by the
+/// `Stats` aggregate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueStats {
+    /// The number of points.
+    pub count: usize,
+    /// The sum of the values.
+    pub sum: f64,
+    /// The smallest value (`f64::INFINITY` for an empty set).
+    pub min: f64,
+    /// The largest value (`f64::NEG_INFINITY` for an empty set).
+    pub max: f64,
+}
+
+impl ValueStats {
+    /// The statistics of an empty set of points.
+    pub const EMPTY: ValueStats = ValueStats {
+        count: 0,
+        sum: 0.0,
+        min: f64::INFINITY,
+        max: f64::NEG_INFINITY,
+    };
+
+    /// Returns the mean of the values, or `None` for an empty set.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// Aggregate maintaining the count, sum, minimum, and maximum of a value extracted from each
+/// point.
+#[derive(Debug, Clone, Copy)]
+pub struct Stats<F>(pub F);
+
+impl<T, F: Fn(&Point2D<T>) -> f64> Aggregate<T> for Stats<F> {
+    type Value = ValueStats;
+
+    fn identity(&self) -> ValueStats {
+        ValueStats::EMPTY
+    }
+
+    fn lift(&self, point: &Point2D<T>) -> ValueStats {
+        let value = (self.0)(point);
+        ValueStats {
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn combine(&self, a: &ValueStats, b: &ValueStats) -> ValueStats {
+        ValueStats {
+            count: a.count + b.count,
+            sum: a.sum + b.sum,
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+}
+
+/// Returns the squared distances from `(x, y)` to the nearest and farthest points of `rect`.
+fn distance_sq_bounds(rect: &Rectangle, x: f64, y: f64) -> (f64, f64) {
+    let near = |v: f64, lo: f64, len: f64| (lo - v).max(v - (lo + len)).max(0.0);
+    let far = |v: f64, lo: f64, len: f64| (v - lo).abs().max((lo + len - v).abs());
+    let (nx, ny) = (near(x, rect.x, rect.width), near(y, rect.y, rect.height));
+    let (fx, fy) = (far(x, rect.x, rect.width), far(y, rect.y, rect.height));
+    (nx * nx + ny * ny, fx * fx + fy * fy)
+}
+
+/// A node of an `AggregateQuadtree`.
+///
+/// Children are stored in the order northwest, northeast, southwest, southeast.
+#[derive(Debug, Clone)]
+struct AggregateNode<T, V> {
+    boundary: Rectangle,
+    points: Vec<Point2D<T>>,
+    children: Option<Box<[AggregateNode<T, V>; 4]>>,
+    len: usize,
+    value: V,
+}
+
+impl<T: Clone + PartialEq, V: Clone> AggregateNode<T, V> {
+    fn new(boundary: Rectangle, value: V) -> Self {
+        AggregateNode {
+            boundary,
+            points: Vec::new(),
+            children: None,
+            len: 0,
+            value,
+        }
+    }
+
+    fn insert<A: Aggregate<T, Value = V>>(
+        &mut self,
+        point: Point2D<T>,
+        capacity: usize,
+        depth: usize,
+        aggregator: &A,
+    ) {
+        self.value = aggregator.combine(&self.value, &aggregator.lift(&point));
+        self.len += 1;
+        if let Some(children) = self.children.as_mut() {
+            let idx = quadrant_index(&self.boundary, &point);
+            children[idx].insert(point, capacity, depth + 1, aggregator);
+            return;
+        }
+        self.points.push(point);
+        if self.points.len() > capacity && depth < MAX_DEPTH {
+            self.subdivide(capacity, depth, aggregator);
+        }
+    }
+
+    fn subdivide<A: Aggregate<T, Value = V>>(
+        &mut self,
+        capacity: usize,
+        depth: usize,
+        aggregator: &A,
+    ) {
+        let quadrant = |boundary: Rectangle| AggregateNode::new(boundary, aggregator.identity());
+        self.children = Some(Box::new(child_boundaries(&self.boundary).map(quadrant)));
+        let points = std::mem::take(&mut self.points);
+        if let Some(children) = self.children.as_mut() {
+            for point in points {
+                let idx = quadrant_index(&self.boundary, &point);
+                children[idx].insert(point, capacity, depth + 1, aggregator);
+            }
+        }
+    }
+
+    fn delete<A: Aggregate<T, Value = V>>(
+        &mut self,
+        point: &Point2D<T>,
+        capacity: usize,
+        aggregator: &A,
+    ) -> bool {
+        let deleted = if let Some(children) = self.children.as_mut() {
+            let idx = quadrant_index(&self.boundary, point);
+            children[idx].delete(point, capacity, aggregator)
+        } else if let Some(pos) = self.points.iter().position(|p| p == point) {
+            self.points.swap_remove(pos);
+            true
+        } else {
+            false
+        };
+        if deleted {
+            self.len -= 1;
+            if self.len <= capacity {
+                self.collapse();
+            }
+            self.recompute(aggregator);
+        }
+        deleted
+    }
+
+    /// Pulls all points of the subtree into this node and drops the children.
+    fn collapse(&mut self) {
+        if let Some(children) = self.children.take() {
+            for mut child in *children {
+                child.collapse();
+                self.points.append(&mut child.points);
+            }
+        }
+    }
+
+    /// Recomputes the aggregate of this node from its points or children.
+    fn recompute<A: Aggregate<T, Value = V>>(&mut self, aggregator: &A) {
+        let mut value = aggregator.identity();
+        match self.children.as_ref() {
+            Some(children) => {
+                for child in children.iter() {
+                    value = aggregator.combine(&value, &child.value);
+                }
+            }
+            None => {
+                for point in &self.points {
+                    value = aggregator.combine(&value, &aggregator.lift(point));
+                }
+            }
+        }
+        self.value = value;
+    }
+
+    fn aggregate_in_bbox<A: Aggregate<T, Value = V>>(
+        &self,
+        query: &Rectangle,
+        aggregator: &A,
+    ) -> V {
+        if self.len == 0 || !self.boundary.intersects(query) {
+            return aggregator.identity();
+        }
+        if query.contains_rect(&self.boundary) {
+            return self.value.clone();
+        }
+        let mut value = aggregator.identity();
+        match self.children.as_ref() {
+            Some(children) => {
+                for child in children.iter() {
+                    value = aggregator.combine(&value, &child.aggregate_in_bbox(query, aggregator));
+                }
+            }
+            None => {
+                for point in self.points.iter().filter(|p| query.contains(p)) {
+                    value = aggregator.combine(&value, &aggregator.lift(point));
+                }
+            }
+        }
+        value
+    }
+
+    fn aggregate_in_radius<A: Aggregate<T, Value = V>>(
+        &self,
+        center: &Point2D<T>,
+        radius_sq: f64,
+        aggregator: &A,
+    ) -> V {
+        if self.len == 0 {
+            return aggregator.identity();
+        }
+        let (near_sq, far_sq) = distance_sq_bounds(&self.boundary, center.x, center.y);
+        if near_sq > radius_sq {
+            return aggregator.identity();
+        }
+        if far_sq <= radius_sq {
+            return self.value.clone();
+        }
+        let mut value = aggregator.identity();
+        match self.children.as_ref() {
+            Some(children) => {
+                for child in children.iter() {
+                    value = aggregator.combine(
+                        &value,
+                        &child.aggregate_in_radius(center, radius_sq, aggregator),
+                    );
+                }
+            }
+            None => {
+                for point in &self.points {
+                    let (dx, dy) = (point.x - center.x, point.y - center.y);
+                    if dx * dx + dy * dy <= radius_sq {
+                        value = aggregator.combine(&value, &aggregator.lift(point));
+                    }
+                }
+            }
+        }
+        value
+    }
+
+    fn range_search_bbox(&self, query: &Rectangle, found: &mut Vec<Point2D<T>>) {
+        if self.len == 0 || !self.boundary.intersects(query) {
+            return;
+        }
+        match self.children.as_ref() {
+            Some(children) => {
+                for child in children.iter() {
+                    child.range_search_bbox(query, found);
+                }
+            }
+            None => found.extend(self.points.iter().filter(|p| query.contains(p)).cloned()),
+        }
+    }
+}
+
+/// A quadtree that maintains an aggregate of its points in every node.
+///
+/// # Type Parameters
+///
+/// * `T`: The type of additional data stored in each point.
+/// * `A`: The aggregate maintained per node.
+#[derive(Debug, Clone)]
+pub struct AggregateQuadtree<T: Clone + PartialEq, A: Aggregate<T>> {
+    root: AggregateNode<T, A::Value>,
+    capacity: usize,
+    aggregator: A,
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug, A: Aggregate<T>> AggregateQuadtree<T, A> {
+    /// Creates a new `AggregateQuadtree` with the specified boundary, capacity, and aggregate.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The rectangular region covered by the tree.
+    /// * `capacity` - The maximum number of points a leaf can hold before subdividing.
+    /// * `aggregator` - The aggregate maintained per node.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn new(boundary: &Rectangle, capacity: usize, aggregator: A) -> Result<Self, SpartError> {
+        if capacity == 0 {
+            return Err(SpartError::InvalidCapacity { capacity });
+        }
+        info!(
+            "Creating new AggregateQuadtree with boundary: {:?} and capacity: {}",
+            boundary, capacity
+        );
+        Ok(AggregateQuadtree {
+            root: AggregateNode::new(boundary.clone(), aggregator.identity()),
+            capacity,
+            aggregator,
+        })
+    }
+
+    /// Returns the aggregate maintained by the tree.
+    pub fn aggregator(&self) -> &A {
+        &self.aggregator
+    }
+
+    /// Returns the number of points stored in the tree.
+    pub fn len(&self) -> usize {
+        self.root.len
+    }
+
+    /// Returns `true` if the tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.root.len == 0
+    }
+
+    /// Inserts a point into the tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was inserted, `false` if it lies outside the tree's boundary.
+    pub fn insert(&mut self, point: Point2D<T>) -> bool {
+        if !self.root.boundary.contains(&point) {
+            return false;
+        }
+        self.root.insert(point, self.capacity, 0, &self.aggregator);
+        true
+    }
+
+    /// Inserts a bulk of points into the tree.
+    ///
+    /// Points outside the tree's boundary are ignored.
+    ///
+    /// # Returns
+    ///
+    /// The number of points that were inserted.
+    pub fn insert_bulk(&mut self, points: &[Point2D<T>]) -> usize {
+        points.iter().filter(|p| self.insert((*p).clone())).count()
+    }
+
+    /// Deletes a point from the tree, updating the aggregates along its path.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was found and deleted.
+    pub fn delete(&mut self, point: &Point2D<T>) -> bool {
+        if !self.root.boundary.contains(point) {
+            return false;
+        }
+        self.root.delete(point, self.capacity, &self.aggregator)
+    }
+
+    /// Returns the aggregate over all points in the tree.
+    pub fn aggregate(&self) -> A::Value {
+        self.root.value.clone()
+    }
+
+    /// Returns the aggregate over all points inside the query rectangle (boundary inclusive).
+    ///
+    /// Nodes fully covered by the query contribute their maintained aggregate directly, so
+    /// only the nodes along the query's border are visited point by point.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    pub fn aggregate_in_bbox(&self, query: &Rectangle) -> A::Value {
+        self.root.aggregate_in_bbox(query, &self.aggregator)
+    }
+
+    /// Returns the aggregate over all points within `radius` of `center` (Euclidean distance,
+    /// boundary inclusive).
+    ///
+    /// Like `aggregate_in_bbox`, nodes that lie entirely inside the circle contribute their
+    /// maintained aggregate directly.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the query circle.
+    /// * `radius` - The radius of the query circle.
+    ///
+    /// # Returns
+    ///
+    /// The aggregate of the points in the circle; the identity if `radius` is negative or NaN.
+    pub fn aggregate_in_radius(&self, center: &Point2D<T>, radius: f64) -> A::Value {
+        if radius >= 0.0 {
+            self.root
+                .aggregate_in_radius(center, radius * radius, &self.aggregator)
+        } else {
+            self.aggregator.identity()
+        }
+    }
+
+    /// Returns all points inside the query rectangle (boundary inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    pub fn range_search_bbox(&self, query: &Rectangle) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        self.root.range_search_bbox(query, &mut found);
+        found
+    }
+}
+
+/// A candidate visited by the best-first traversal of `top_k_in_bbox`.
+enum TopKCandidate<'a, T> {
+    Node(&'a AggregateNode<T, f64>),
+    Point(&'a Point2D<T>),
+}
+
+/// Heap entry ordering `TopKCandidate`s by their score (or score upper bound).
+struct TopKItem<'a, T> {
+    score: OrderedFloat<f64>,
+    candidate: TopKCandidate<'a, T>,
+}
+
+impl<T> PartialEq for TopKItem<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score == other.score
+    }
+}
+
+impl<T> Eq for TopKItem<'_, T> {}
+
+impl<T> PartialOrd for TopKItem<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for TopKItem<'_, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Points win ties against nodes so that results are emitted as early as possible.
+        self.score
+            .cmp(&other.score)
+            .then_with(|| match (&self.candidate, &other.candidate) {
+                (TopKCandidate::Point(_), TopKCandidate::Node(_)) => Ordering::Greater,
+                (TopKCandidate::Node(_), TopKCandidate::Point(_)) => Ordering::Less,
+                _ => Ordering::Equal,
+            })
+    }
+}
+
+impl<T, F> AggregateQuadtree<T, Max<F>>
+where
+    T: Clone + PartialEq + std::fmt::Debug,
+    F: Fn(&Point2D<T>) -> f64,
+{
+    /// Returns the `k` highest-scoring points inside the query rectangle (boundary inclusive).
+    ///
+    /// Points are scored with the function of the tree's `Max` aggregate. The per-node maximum
+    /// score is used as an upper bound in a best-first traversal, so subtrees that cannot beat
+    /// the current results are never visited.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    /// * `k` - The number of points to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A vector of at most `k` points, ordered from the highest to the lowest score.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::aggregate::{AggregateQuadtree, Max};
+    /// use spart::geometry::{Point2D, Rectangle};
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let rating = |p: &Point2D<f64>| p.data.unwrap_or(0.0);
+    /// let mut restaurants = AggregateQuadtree::new(&boundary, 4, Max(rating)).unwrap();
+    /// restaurants.insert(Point2D::new(10.0, 10.0, Some(4.5)));
+    /// restaurants.insert(Point2D::new(20.0, 20.0, Some(3.0)));
+    /// restaurants.insert(Point2D::new(30.0, 30.0, Some(4.8)));
+    /// restaurants.insert(Point2D::new(90.0, 90.0, Some(5.0)));
+    ///
+    /// let view = Rectangle { x: 0.0, y: 0.0, width: 50.0, height: 50.0 };
+    /// let best = restaurants.top_k_in_bbox(&view, 2);
+    /// assert_eq!(best[0].data, Some(4.8));
+    /// assert_eq!(best[1].data, Some(4.5));
+    /// ```
+    pub fn top_k_in_bbox(&self, query: &Rectangle, k: usize) -> Vec<Point2D<T>> {
+        let mut result = Vec::new();
+        if k == 0 {
+            return result;
+        }
+        let score = &self.aggregator.0;
+        let mut heap = BinaryHeap::new();
+        heap.push(TopKItem {
+            score: OrderedFloat(self.root.value),
+            candidate: TopKCandidate::Node(&self.root),
+        });
+        while let Some(item) = heap.pop() {
+            match item.candidate {
+                TopKCandidate::Point(point) => {
+                    result.push(point.clone());
+                    if result.len() == k {
+                        break;
+                    }
+                }
+                TopKCandidate::Node(node) => {
+                    if node.len == 0 || !node.boundary.intersects(query) {
+                        continue;
+                    }
+                    match node.children.as_ref() {
+                        Some(children) => {
+                            for child in children.iter() {
+                                heap.push(TopKItem {
+                                    score: OrderedFloat(child.value),
+                                    candidate: TopKCandidate::Node(child),
+                                });
+                            }
+                        }
+                        None => {
+                            for point in node.points.iter().filter(|p| query.contains(p)) {
+                                heap.push(TopKItem {
+                                    score: OrderedFloat(score(point)),
+                                    candidate: TopKCandidate::Point(point),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+impl<T, F> AggregateQuadtree<T, Stats<F>>
+where
+    T: Clone + PartialEq + std::fmt::Debug,
+    F: Fn(&Point2D<T>) -> f64,
+{
+    /// Returns the smallest value among the points inside the query rectangle, or `None` if
+    /// there are none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::aggregate::{AggregateQuadtree, Stats};
+    /// use spart::geometry::{Point2D, Rectangle};
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let price = |p: &Point2D<f64>| p.data.unwrap_or(0.0);
+    /// let mut listings = AggregateQuadtree::new(&boundary, 4, Stats(price)).unwrap();
+    /// listings.insert(Point2D::new(10.0, 10.0, Some(250.0)));
+    /// listings.insert(Point2D::new(12.0, 14.0, Some(180.0)));
+    /// listings.insert(Point2D::new(80.0, 80.0, Some(90.0)));
+    ///
+    /// let district = Rectangle { x: 0.0, y: 0.0, width: 50.0, height: 50.0 };
+    /// assert_eq!(listings.min_in_bbox(&district), Some(180.0));
+    /// assert_eq!(listings.sum_in_radius(&Point2D::new(11.0, 12.0, None), 5.0), 430.0);
+    /// assert_eq!(listings.stats_in_bbox(&district).mean(), Some(215.0));
+    /// ```
+    pub fn min_in_bbox(&self, query: &Rectangle) -> Option<f64> {
+        let stats = self.aggregate_in_bbox(query);
+        (stats.count > 0).then_some(stats.min)
+    }
+
+    /// Returns the largest value among the points inside the query rectangle, or `None` if
+    /// there are none.
+    pub fn max_in_bbox(&self, query: &Rectangle) -> Option<f64> {
+        let stats = self.aggregate_in_bbox(query);
+        (stats.count > 0).then_some(stats.max)
+    }
+
+    /// Returns the sum of the values of the points inside the query rectangle.
+    pub fn sum_in_bbox(&self, query: &Rectangle) -> f64 {
+        self.aggregate_in_bbox(query).sum
+    }
+
+    /// Returns the count, sum, minimum, and maximum of the values of the points inside the
+    /// query rectangle.
+    pub fn stats_in_bbox(&self, query: &Rectangle) -> ValueStats {
+        self.aggregate_in_bbox(query)
+    }
+
+    /// Returns the smallest value among the points within `radius` of `center`, or `None` if
+    /// there are none.
+    pub fn min_in_radius(&self, center: &Point2D<T>, radius: f64) -> Option<f64> {
+        let stats = self.aggregate_in_radius(center, radius);
+        (stats.count > 0).then_some(stats.min)
+    }
+
+    /// Returns the largest value among the points within `radius` of `center`, or `None` if
+    /// there are none.
+    pub fn max_in_radius(&self, center: &Point2D<T>, radius: f64) -> Option<f64> {
+        let stats = self.aggregate_in_radius(center, radius);
+        (stats.count > 0).then_some(stats.max)
+    }
+
+    /// Returns the sum of the values of the points within `radius` of `center`.
+    pub fn sum_in_radius(&self, center: &Point2D<T>, radius: f64) -> f64 {
+        self.aggregate_in_radius(center, radius).sum
+    }
+
+    /// Returns the count, sum, minimum, and maximum of the values of the points within
+    /// `radius` of `center`.
+    pub fn stats_in_radius(&self, center: &Point2D<T>, radius: f64) -> ValueStats {
+        self.aggregate_in_radius(center, radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boundary() -> Rectangle {
+        Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        }
+    }
+
+    fn grid_points() -> Vec<Point2D<u32>> {
+        let mut points = Vec::new();
+        for i in 0..20 {
+            for j in 0..20 {
+                points.push(Point2D::new(
+                    i as f64 * 5.0 + 0.5,
+                    j as f64 * 5.0 + 0.5,
+                    Some(i * 20 + j),
+                ));
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn test_count_matches_brute_force() {
+        let mut tree = AggregateQuadtree::new(&boundary(), 4, Count).unwrap();
+        let points = grid_points();
+        assert_eq!(tree.insert_bulk(&points), points.len());
+        assert_eq!(tree.aggregate(), points.len());
+
+        let queries = [
+            Rectangle {
+                x: 10.0,
+                y: 10.0,
+                width: 30.0,
+                height: 45.0,
+            },
+            Rectangle {
+                x: -5.0,
+                y: -5.0,
+                width: 200.0,
+                height: 200.0,
+            },
+            Rectangle {
+                x: 33.3,
+                y: 71.1,
+                width: 0.5,
+                height: 9.0,
+            },
+        ];
+        for query in &queries {
+            let expected = points.iter().filter(|p| query.contains(p)).count();
+            assert_eq!(tree.aggregate_in_bbox(query), expected);
+            assert_eq!(tree.range_search_bbox(query).len(), expected);
+        }
+    }
+
+    #[test]
+    fn test_sum_and_max_after_delete() {
+        let value = |p: &Point2D<u32>| p.data.unwrap_or(0) as f64;
+        let mut sum = AggregateQuadtree::new(&boundary(), 3, Sum(value)).unwrap();
+        let mut max = AggregateQuadtree::new(&boundary(), 3, Max(value)).unwrap();
+        let points = grid_points();
+        sum.insert_bulk(&points);
+        max.insert_bulk(&points);
+
+        let query = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 50.0,
+            height: 50.0,
+        };
+        for p in points.iter().filter(|p| p.data.unwrap() % 3 == 0) {
+            assert!(sum.delete(p));
+            assert!(max.delete(p));
+        }
+        let remaining: Vec<&Point2D<u32>> = points
+            .iter()
+            .filter(|p| p.data.unwrap() % 3 != 0 && query.contains(*p))
+            .collect();
+        let expected_sum: f64 = remaining.iter().map(|p| value(p)).sum();
+        let expected_max = remaining
+            .iter()
+            .map(|p| value(p))
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(sum.aggregate_in_bbox(&query), expected_sum);
+        assert_eq!(max.aggregate_in_bbox(&query), expected_max);
+    }
+
+    #[test]
+    fn test_empty_region_returns_identity() {
+        let mut tree = AggregateQuadtree::new(&boundary(), 4, Min(|p: &Point2D<()>| p.x)).unwrap();
+        tree.insert(Point2D::new(80.0, 80.0, None));
+        let query = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        assert_eq!(tree.aggregate_in_bbox(&query), f64::INFINITY);
+        assert!(!tree.delete(&Point2D::new(1.0, 1.0, None)));
+        assert!(tree.delete(&Point2D::new(80.0, 80.0, None)));
+        assert!(tree.is_empty());
+        assert_eq!(tree.aggregate(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_duplicates_do_not_recurse_forever() {
+        let mut tree = AggregateQuadtree::new(&boundary(), 1, Count).unwrap();
+        for _ in 0..50 {
+            assert!(tree.insert(Point2D::new(42.0, 42.0, Some(()))));
+        }
+        assert_eq!(tree.len(), 50);
+        assert!(!tree.insert(Point2D::new(142.0, 42.0, Some(()))));
+    }
+
+    #[test]
+    fn test_points_on_far_edge_are_found() {
+        // 0.3 + 0.35 + 0.35 rounds below 1.0, so the eastern and southern children would end
+        // slightly short of the root's far edge without rounding up their extents.
+        let boundary = Rectangle {
+            x: 0.3,
+            y: 0.3,
+            width: 0.7,
+            height: 0.7,
+        };
+        let coords = [0.3, 0.475, 0.65, 0.825, 1.0];
+        let mut tree = AggregateQuadtree::new(&boundary, 1, Count).unwrap();
+        for &x in &coords {
+            for &y in &coords {
+                assert!(tree.insert(Point2D::new(x, y, Some(()))));
+            }
+        }
+
+        let far_edge = Rectangle {
+            x: 1.0,
+            y: 0.0,
+            width: 1.0,
+            height: 2.0,
+        };
+        assert_eq!(tree.aggregate_in_bbox(&far_edge), coords.len());
+        assert_eq!(tree.range_search_bbox(&far_edge).len(), coords.len());
+        let corner = Point2D::new(1.0, 1.0, None);
+        assert_eq!(tree.aggregate_in_radius(&corner, 0.0), 1);
+    }
+
+    #[test]
+    fn test_zero_capacity_rejected() {
+        let result = AggregateQuadtree::<(), _>::new(&boundary(), 0, Count);
+        assert!(matches!(
+            result,
+            Err(SpartError::InvalidCapacity { capacity: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_top_k_in_bbox_matches_sorted_scan() {
+        let score = |p: &Point2D<u32>| ((p.data.unwrap_or(0) * 7919) % 1000) as f64;
+        let mut tree = AggregateQuadtree::new(&boundary(), 4, Max(score)).unwrap();
+        let points = grid_points();
+        tree.insert_bulk(&points);
+
+        let query = Rectangle {
+            x: 12.0,
+            y: 3.0,
+            width: 55.0,
+            height: 61.0,
+        };
+        let mut expected: Vec<f64> = points
+            .iter()
+            .filter(|p| query.contains(*p))
+            .map(score)
+            .collect();
+        expected.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        expected.truncate(10);
+
+        let top: Vec<f64> = tree.top_k_in_bbox(&query, 10).iter().map(score).collect();
+        assert_eq!(top, expected);
+        assert!(tree.top_k_in_bbox(&query, 0).is_empty());
+
+        let all = tree.top_k_in_bbox(&query, 10_000);
+        assert_eq!(
+            all.len(),
+            points.iter().filter(|p| query.contains(*p)).count()
+        );
+    }
+
+    #[test]
+    fn test_stats_in_radius_after_delete_matches_brute_force() {
+        let value = |p: &Point2D<u32>| ((p.data.unwrap_or(0) * 37) % 101) as f64 - 50.0;
+        let mut tree = AggregateQuadtree::new(&boundary(), 3, Stats(value)).unwrap();
+        let points = grid_points();
+        tree.insert_bulk(&points);
+        for p in points.iter().filter(|p| p.data.unwrap() % 4 == 1) {
+            assert!(tree.delete(p));
+        }
+        let remaining: Vec<&Point2D<u32>> =
+            points.iter().filter(|p| p.data.unwrap() % 4 != 1).collect();
+
+        let center = Point2D::new(41.0, 57.5, None);
+        for radius in [0.0, 3.0, 17.5, 40.0, 500.0] {
+            let values: Vec<f64> = remaining
+                .iter()
+                .filter(|p| (p.x - center.x).powi(2) + (p.y - center.y).powi(2) <= radius * radius)
+                .map(|p| value(p))
+                .collect();
+            let stats = tree.stats_in_radius(&center, radius);
+            assert_eq!(stats.count, values.len());
+            assert!((stats.sum - values.iter().sum::<f64>()).abs() < 1e-9);
+            assert!((tree.sum_in_radius(&center, radius) - stats.sum).abs() < 1e-9);
+            let min = values.iter().copied().reduce(f64::min);
+            let max = values.iter().copied().reduce(f64::max);
+            assert_eq!(tree.min_in_radius(&center, radius), min);
+            assert_eq!(tree.max_in_radius(&center, radius), max);
+        }
+        assert_eq!(tree.stats_in_radius(&center, -1.0), ValueStats::EMPTY);
+
+        let query = Rectangle {
+            x: 20.0,
+            y: 5.0,
+            width: 33.0,
+            height: 71.0,
+        };
+        let values: Vec<f64> = remaining
+            .iter()
+            .filter(|p| query.contains(*p))
+            .map(|p| value(p))
+            .collect();
+        assert_eq!(
+            tree.min_in_bbox(&query),
+            values.iter().copied().reduce(f64::min)
+        );
+        assert_eq!(
+            tree.max_in_bbox(&query),
+            values.iter().copied().reduce(f64::max)
+        );
+        assert!((tree.sum_in_bbox(&query) - values.iter().sum::<f64>()).abs() < 1e-9);
+        assert_eq!(tree.stats_in_bbox(&query).count, values.len());
+    }
+}
@@ -0,0 +1,838 @@
+//! ## Path-Compressed Quadtree
+//!
+//! This module implements `CompressedQuadtree`, a quadtree variant for highly clustered 2D
+//! data. A standard quadtree subdivides one level at a time, so a tight cluster far from the
+//! center of the boundary sits at the end of a long chain of internal nodes that each have a
+//! single non-empty child. The compressed quadtree skips these chains (like a patricia trie):
+//! every node covers the smallest quadtree cell that holds all points below it, and internal
+//! nodes always have at least two non-empty children. Depth and node count then grow with the
+//! number of clusters rather than with how small they are.
+//!
+//! Cells are the ones of `Quadtree` over the same boundary, with points routed by the same
+//! midpoint rules, down to 32 levels of subdivision. A leaf holds up to `capacity` points,
+//! except for a leaf at the deepest level, which holds all points falling into its cell.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::compressed_quadtree::CompressedQuadtree;
+//! use spart::geometry::{EuclideanDistance, Point2D, Rectangle};
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 1000.0, height: 1000.0 };
+//! let mut tree = CompressedQuadtree::new(&boundary, 2).unwrap();
+//! // Two tight clusters in opposite corners.
+//! for i in 0..3 {
+//!     tree.insert(Point2D::new(1.0 + i as f64 * 1e-3, 1.0, Some(i)));
+//!     tree.insert(Point2D::new(999.0, 999.0 - i as f64 * 1e-3, Some(10 + i)));
+//! }
+//! assert_eq!(tree.len(), 6);
+//! // The chains of single-child cells down to each cluster are skipped.
+//! assert!(tree.depth() <= 3);
+//!
+//! let nearest = tree.knn_search::<EuclideanDistance>(&Point2D::new(0.0, 0.0, None), 1);
+//! assert_eq!(nearest[0].data, Some(0));
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{DistanceMetric, Point2D, Rectangle};
+use crate::knn::KnnResults;
+use crate::logging::hot_trace;
+use crate::quadtree::{MAX_DEPTH, morton_code};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// The number of subdivision levels encoded in a Morton code.
+const MAX_LEVEL: u32 = MAX_DEPTH as u32;
+
+/// A quadtree that skips chains of single-child internal nodes.
+///
+/// # Type Parameters
+///
+/// * `T`: The type of additional data stored in each point.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompressedQuadtree<T: Clone + PartialEq> {
+    boundary: Rectangle,
+    capacity: usize,
+    len: usize,
+    root: Option<Box<Node<T>>>,
+}
+
+/// A node covering the cell given by the first `level` digits of `prefix`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Node<T: Clone + PartialEq> {
+    prefix: u64,
+    level: u32,
+    kind: NodeKind<T>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum NodeKind<T: Clone + PartialEq> {
+    Leaf(Vec<Point2D<T>>),
+    /// The children, indexed by their digit at the node's level.
+    Inner([Option<Box<Node<T>>>; 4]),
+}
+
+/// The bounds of a cell, derived with the same arithmetic as `morton_code`.
+///
+/// `x` and `y` are the lower corner and `width` and `height` the size used for the next
+/// subdivision. The upper corner is tracked separately, so that it is exactly the midpoint the
+/// points were routed by.
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl Cell {
+    fn root(boundary: &Rectangle) -> Self {
+        Cell {
+            x: boundary.x,
+            y: boundary.y,
+            width: boundary.width,
+            height: boundary.height,
+            max_x: boundary.x + boundary.width,
+            max_y: boundary.y + boundary.height,
+        }
+    }
+
+    /// Follows the digits of `code` from level `from` down to level `to`.
+    fn descend(mut self, code: u64, from: u32, to: u32) -> Self {
+        for level in from..to {
+            let digit = digit(code, level);
+            self.width /= 2.0;
+            self.height /= 2.0;
+            let mid_x = self.x + self.width;
+            let mid_y = self.y + self.height;
+            if digit & 1 == 1 {
+                self.x = mid_x;
+            } else {
+                self.max_x = mid_x;
+            }
+            if digit & 2 == 2 {
+                self.y = mid_y;
+            } else {
+                self.max_y = mid_y;
+            }
+        }
+        self
+    }
+
+    fn intersects(&self, query: &Rectangle) -> bool {
+        self.x <= query.x + query.width
+            && query.x <= self.max_x
+            && self.y <= query.y + query.height
+            && query.y <= self.max_y
+    }
+
+    fn min_distance_sq<T>(&self, target: &Point2D<T>) -> f64 {
+        let dx = (self.x - target.x).max(target.x - self.max_x).max(0.0);
+        let dy = (self.y - target.y).max(target.y - self.max_y).max(0.0);
+        dx * dx + dy * dy
+    }
+}
+
+/// Returns the digit of `code` at `level` (0 being the top level).
+fn digit(code: u64, level: u32) -> usize {
+    ((code >> (2 * (MAX_LEVEL - 1 - level))) & 3) as usize
+}
+
+/// Returns the number of leading digits two codes have in common.
+fn common_levels(a: u64, b: u64) -> u32 {
+    (a ^ b).leading_zeros() / 2
+}
+
+/// Keeps the first `level` digits of `code` and clears the rest.
+fn mask(code: u64, level: u32) -> u64 {
+    match level {
+        0 => 0,
+        MAX_LEVEL => code,
+        _ => code & !(u64::MAX >> (2 * level)),
+    }
+}
+
+impl<T: Clone + PartialEq> Node<T> {
+    fn leaf(code: u64, point: Point2D<T>) -> Box<Self> {
+        Box::new(Node {
+            prefix: code,
+            level: MAX_LEVEL,
+            kind: NodeKind::Leaf(vec![point]),
+        })
+    }
+
+    /// Builds the subtree for a non-empty set of points with their codes.
+    fn build(mut entries: Vec<(u64, Point2D<T>)>, capacity: usize) -> Box<Self> {
+        let first = entries[0].0;
+        let level = entries
+            .iter()
+            .map(|(code, _)| common_levels(first, *code))
+            .min()
+            .unwrap_or(MAX_LEVEL);
+        let prefix = mask(first, level);
+        if entries.len() <= capacity || level == MAX_LEVEL {
+            let points = entries.into_iter().map(|(_, point)| point).collect();
+            return Box::new(Node {
+                prefix,
+                level,
+                kind: NodeKind::Leaf(points),
+            });
+        }
+        let mut groups: [Vec<(u64, Point2D<T>)>; 4] = Default::default();
+        for entry in entries.drain(..) {
+            groups[digit(entry.0, level)].push(entry);
+        }
+        let children = groups.map(|group| {
+            if group.is_empty() {
+                None
+            } else {
+                Some(Node::build(group, capacity))
+            }
+        });
+        Box::new(Node {
+            prefix,
+            level,
+            kind: NodeKind::Inner(children),
+        })
+    }
+
+    fn insert(
+        self: &mut Box<Self>,
+        code: u64,
+        point: Point2D<T>,
+        capacity: usize,
+        boundary: &Rectangle,
+    ) {
+        let common = common_levels(self.prefix, code).min(self.level);
+        if common < self.level {
+            // The point lies outside this node's cell.
+            if let NodeKind::Leaf(points) = &mut self.kind {
+                if points.len() < capacity {
+                    points.push(point);
+                    self.level = common;
+                    self.prefix = mask(code, common);
+                    return;
+                }
+            }
+            let old_digit = digit(self.prefix, common);
+            let mut children: [Option<Box<Node<T>>>; 4] = Default::default();
+            children[digit(code, common)] = Some(Node::leaf(code, point));
+            let parent = Box::new(Node {
+                prefix: mask(code, common),
+                level: common,
+                kind: NodeKind::Inner(children),
+            });
+            let old = std::mem::replace(self, parent);
+            if let NodeKind::Inner(children) = &mut self.kind {
+                children[old_digit] = Some(old);
+            }
+            return;
+        }
+        let level = self.level;
+        match &mut self.kind {
+            NodeKind::Leaf(points) => {
+                if points.len() < capacity || level == MAX_LEVEL {
+                    points.push(point);
+                    return;
+                }
+                let mut entries: Vec<(u64, Point2D<T>)> = std::mem::take(points)
+                    .into_iter()
+                    .map(|p| (morton_code(boundary, &p), p))
+                    .collect();
+                entries.push((code, point));
+                *self = Node::build(entries, capacity);
+            }
+            NodeKind::Inner(children) => match &mut children[digit(code, level)] {
+                Some(child) => child.insert(code, point, capacity, boundary),
+                slot @ None => *slot = Some(Node::leaf(code, point)),
+            },
+        }
+    }
+
+    /// Counts the points below this node, stopping once `limit` is reached.
+    fn count_points(&self, limit: usize) -> usize {
+        match &self.kind {
+            NodeKind::Leaf(points) => points.len(),
+            NodeKind::Inner(children) => {
+                let mut count = 0;
+                for child in children.iter().flatten() {
+                    count += child.count_points(limit - count);
+                    if count >= limit {
+                        break;
+                    }
+                }
+                count
+            }
+        }
+    }
+
+    fn collect_points(self, out: &mut Vec<Point2D<T>>) {
+        match self.kind {
+            NodeKind::Leaf(points) => out.extend(points),
+            NodeKind::Inner(children) => {
+                for child in children.into_iter().flatten() {
+                    child.collect_points(out);
+                }
+            }
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match &self.kind {
+            NodeKind::Leaf(_) => 1,
+            NodeKind::Inner(children) => {
+                1 + children
+                    .iter()
+                    .flatten()
+                    .map(|child| child.depth())
+                    .max()
+                    .unwrap_or(0)
+            }
+        }
+    }
+
+    fn node_count(&self) -> usize {
+        match &self.kind {
+            NodeKind::Leaf(_) => 1,
+            NodeKind::Inner(children) => {
+                1 + children
+                    .iter()
+                    .flatten()
+                    .map(|child| child.node_count())
+                    .sum::<usize>()
+            }
+        }
+    }
+
+    fn find(&self, code: u64, point: &Point2D<T>) -> bool {
+        if common_levels(self.prefix, code) < self.level {
+            return false;
+        }
+        match &self.kind {
+            NodeKind::Leaf(points) => points.contains(point),
+            NodeKind::Inner(children) => children[digit(code, self.level)]
+                .as_ref()
+                .is_some_and(|child| child.find(code, point)),
+        }
+    }
+
+    fn range_search_bbox(&self, cell: Cell, query: &Rectangle, found: &mut Vec<Point2D<T>>) {
+        if !cell.intersects(query) {
+            return;
+        }
+        match &self.kind {
+            NodeKind::Leaf(points) => {
+                found.extend(points.iter().filter(|p| query.contains(p)).cloned());
+            }
+            NodeKind::Inner(children) => {
+                for child in children.iter().flatten() {
+                    let child_cell = cell.descend(child.prefix, self.level, child.level);
+                    child.range_search_bbox(child_cell, query, found);
+                }
+            }
+        }
+    }
+
+    fn range_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        cell: Cell,
+        center: &Point2D<T>,
+        radius_sq: f64,
+        found: &mut Vec<Point2D<T>>,
+    ) {
+        if cell.min_distance_sq(center) > radius_sq {
+            return;
+        }
+        match &self.kind {
+            NodeKind::Leaf(points) => {
+                found.extend(
+                    points
+                        .iter()
+                        .filter(|p| M::distance_sq(p, center) <= radius_sq)
+                        .cloned(),
+                );
+            }
+            NodeKind::Inner(children) => {
+                for child in children.iter().flatten() {
+                    let child_cell = cell.descend(child.prefix, self.level, child.level);
+                    child.range_search::<M>(child_cell, center, radius_sq, found);
+                }
+            }
+        }
+    }
+
+    fn knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        results: &mut KnnResults<Point2D<T>>,
+        cell: Cell,
+    ) {
+        match &self.kind {
+            NodeKind::Leaf(points) => {
+                for point in points {
+                    let dist_sq = M::distance_sq(point, target);
+                    if results.accepts(dist_sq) {
+                        results.push(dist_sq, point.clone());
+                    }
+                }
+            }
+            NodeKind::Inner(children) => {
+                // Visiting the closest children first tightens the pruning bound early.
+                let mut ordered: Vec<(f64, &Node<T>, Cell)> = children
+                    .iter()
+                    .flatten()
+                    .map(|child| {
+                        let child_cell = cell.descend(child.prefix, self.level, child.level);
+                        (child_cell.min_distance_sq(target), &**child, child_cell)
+                    })
+                    .collect();
+                ordered.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (min_distance_sq, child, child_cell) in ordered {
+                    if min_distance_sq > results.bound() {
+                        break;
+                    }
+                    child.knn_search::<M>(target, results, child_cell);
+                }
+            }
+        }
+    }
+}
+
+/// Deletes a point equal to `point` from the subtree in `slot`, compacting it on the way up.
+fn delete_from<T: Clone + PartialEq>(
+    slot: &mut Option<Box<Node<T>>>,
+    code: u64,
+    point: &Point2D<T>,
+    capacity: usize,
+    boundary: &Rectangle,
+) -> bool {
+    let Some(node) = slot else {
+        return false;
+    };
+    if common_levels(node.prefix, code) < node.level {
+        return false;
+    }
+    match &mut node.kind {
+        NodeKind::Leaf(points) => {
+            let Some(position) = points.iter().position(|p| p == point) else {
+                return false;
+            };
+            points.remove(position);
+            if points.is_empty() {
+                *slot = None;
+            } else {
+                // The remaining points may share a smaller cell.
+                let codes: Vec<u64> = points.iter().map(|p| morton_code(boundary, p)).collect();
+                let level = codes
+                    .iter()
+                    .map(|c| common_levels(codes[0], *c))
+                    .min()
+                    .unwrap_or(MAX_LEVEL);
+                node.prefix = mask(codes[0], level);
+                node.level = level;
+            }
+            true
+        }
+        NodeKind::Inner(children) => {
+            let child = &mut children[digit(code, node.level)];
+            if !delete_from(child, code, point, capacity, boundary) {
+                return false;
+            }
+            if children.iter().flatten().count() == 1 {
+                *slot = children.iter_mut().find_map(Option::take);
+            } else if node.count_points(capacity + 1) <= capacity {
+                let mut points = Vec::new();
+                if let Some(node) = slot.take() {
+                    (*node).collect_points(&mut points);
+                }
+                let entries = points.into_iter().map(|p| (morton_code(boundary, &p), p));
+                *slot = Some(Node::build(entries.collect(), capacity));
+            }
+            true
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> CompressedQuadtree<T> {
+    /// Creates a new, empty compressed quadtree.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The rectangular region covered by the tree.
+    /// * `capacity` - The maximum number of points a leaf can hold before it is split.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn new(boundary: &Rectangle, capacity: usize) -> Result<Self, SpartError> {
+        if capacity == 0 {
+            return Err(SpartError::InvalidCapacity { capacity });
+        }
+        info!(
+            "Creating new CompressedQuadtree with boundary: {:?} and capacity: {}",
+            boundary, capacity
+        );
+        Ok(CompressedQuadtree {
+            boundary: boundary.clone(),
+            capacity,
+            len: 0,
+            root: None,
+        })
+    }
+
+    /// Returns the boundary of the tree.
+    pub fn boundary(&self) -> &Rectangle {
+        &self.boundary
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of nodes on the longest path from the root to a leaf (0 if empty).
+    pub fn depth(&self) -> usize {
+        self.root.as_ref().map_or(0, |root| root.depth())
+    }
+
+    /// Returns the total number of nodes in the tree.
+    pub fn node_count(&self) -> usize {
+        self.root.as_ref().map_or(0, |root| root.node_count())
+    }
+
+    /// Inserts a point into the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was inserted, `false` if it lies outside the boundary.
+    pub fn insert(&mut self, point: Point2D<T>) -> bool {
+        if !self.boundary.contains(&point) {
+            return false;
+        }
+        let code = morton_code(&self.boundary, &point);
+        match &mut self.root {
+            Some(root) => root.insert(code, point, self.capacity, &self.boundary),
+            None => self.root = Some(Node::leaf(code, point)),
+        }
+        self.len += 1;
+        true
+    }
+
+    /// Inserts a bulk of points into the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to insert. Points outside the boundary are skipped.
+    pub fn insert_bulk(&mut self, points: &[Point2D<T>]) {
+        for point in points {
+            self.insert(point.clone());
+        }
+    }
+
+    /// Returns `true` if a point equal to `point` (coordinates and data) is stored.
+    pub fn contains(&self, point: &Point2D<T>) -> bool {
+        if !self.boundary.contains(point) {
+            return false;
+        }
+        let code = morton_code(&self.boundary, point);
+        self.root
+            .as_ref()
+            .is_some_and(|root| root.find(code, point))
+    }
+
+    /// Deletes a point equal to `point` (coordinates and data) from the tree.
+    ///
+    /// Internal nodes left with a single child are removed, and subtrees that fit into one
+    /// leaf again are merged, so the tree stays as compact as if the remaining points had been
+    /// inserted from scratch.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and removed.
+    pub fn delete(&mut self, point: &Point2D<T>) -> bool {
+        if !self.boundary.contains(point) {
+            return false;
+        }
+        let code = morton_code(&self.boundary, point);
+        let deleted = delete_from(&mut self.root, code, point, self.capacity, &self.boundary);
+        if deleted {
+            self.len -= 1;
+        }
+        deleted
+    }
+
+    /// Performs a range search, returning all points inside the query rectangle
+    /// (boundary inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points inside the query rectangle.
+    pub fn range_search_bbox(&self, query: &Rectangle) -> Vec<Point2D<T>> {
+        hot_trace!("Performing range search", query = query);
+        let mut found = Vec::new();
+        if let Some(root) = &self.root {
+            let cell = Cell::root(&self.boundary).descend(root.prefix, 0, root.level);
+            root.range_search_bbox(cell, query, &mut found);
+        }
+        found
+    }
+
+    /// Performs a range search, returning all points within the specified radius of the center point.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search range.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points within the range (empty if `radius` is negative).
+    pub fn range_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        if radius < 0.0 {
+            return found;
+        }
+        if let Some(root) = &self.root {
+            let cell = Cell::root(&self.boundary).descend(root.prefix, 0, root.level);
+            root.range_search::<M>(cell, center, radius * radius, &mut found);
+        }
+        found
+    }
+
+    /// Performs a k-nearest neighbor search.
+    ///
+    /// Cells are pruned by their Euclidean distance to the target, so the metric should be
+    /// Euclidean or never report smaller distances than it.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The target point.
+    /// * `k` - The number of nearest neighbors to return.
+    ///
+    /// # Returns
+    ///
+    /// A vector of up to `k` points, ordered from nearest to farthest.
+    pub fn knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+    ) -> Vec<Point2D<T>> {
+        let Some(root) = &self.root else {
+            return Vec::new();
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        let cell = Cell::root(&self.boundary).descend(root.prefix, 0, root.level);
+        root.knn_search::<M>(target, &mut results, cell);
+        results.into_sorted_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::EuclideanDistance;
+    use crate::quadtree::Quadtree;
+    use crate::testing::fixtures::{clustered, sorted_ids};
+
+    fn boundary() -> Rectangle {
+        Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 1000.0,
+            height: 1000.0,
+        }
+    }
+
+    /// A compressed and a standard quadtree holding the same 400 clustered points.
+    fn trees() -> (
+        Vec<Point2D<usize>>,
+        CompressedQuadtree<usize>,
+        Quadtree<usize>,
+    ) {
+        let points = clustered(400);
+        let mut compressed = CompressedQuadtree::new(&boundary(), 4).unwrap();
+        let mut plain = Quadtree::new(&boundary(), 4).unwrap();
+        compressed.insert_bulk(&points);
+        plain.insert_bulk(&points);
+        (points, compressed, plain)
+    }
+
+    fn targets() -> [Point2D<usize>; 3] {
+        [
+            Point2D::new(0.0, 0.0, None),
+            Point2D::new(500.0, 500.0, None),
+            Point2D::new(700.3005, 12.5005, None),
+        ]
+    }
+
+    #[test]
+    fn test_new_rejects_zero_capacity() {
+        assert!(matches!(
+            CompressedQuadtree::<usize>::new(&boundary(), 0),
+            Err(SpartError::InvalidCapacity { capacity: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_empty_tree_finds_nothing() {
+        let mut tree: CompressedQuadtree<usize> = CompressedQuadtree::new(&boundary(), 4).unwrap();
+        let target = Point2D::new(1.0, 1.0, None);
+        assert!(tree.is_empty());
+        assert_eq!((tree.depth(), tree.node_count()), (0, 0));
+        assert!(tree.range_search_bbox(&boundary()).is_empty());
+        assert!(tree.knn_search::<EuclideanDistance>(&target, 3).is_empty());
+        assert!(!tree.delete(&Point2D::new(1.0, 1.0, Some(0))));
+    }
+
+    #[test]
+    fn test_insert_rejects_points_outside_boundary() {
+        let mut tree = CompressedQuadtree::new(&boundary(), 4).unwrap();
+        assert!(!tree.insert(Point2D::new(-1.0, 0.0, Some(0))));
+        assert!(!tree.insert(Point2D::new(0.0, 1000.5, Some(1))));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_points_on_boundary_are_found() {
+        let mut tree = CompressedQuadtree::new(&boundary(), 1).unwrap();
+        let corners = [(0.0, 0.0), (1000.0, 0.0), (0.0, 1000.0), (1000.0, 1000.0)];
+        for (i, (x, y)) in corners.into_iter().enumerate() {
+            assert!(tree.insert(Point2D::new(x, y, Some(i))));
+        }
+        assert_eq!(
+            sorted_ids(tree.range_search_bbox(&boundary())),
+            [0, 1, 2, 3]
+        );
+        let query = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 1000.0,
+            height: 0.0,
+        };
+        assert_eq!(sorted_ids(tree.range_search_bbox(&query)), [0, 1]);
+    }
+
+    #[test]
+    fn test_range_search_bbox_matches_quadtree() {
+        let (_, compressed, plain) = trees();
+        assert_eq!(compressed.len(), 400);
+        let query = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 700.3,
+            height: 12.5,
+        };
+        assert_eq!(
+            sorted_ids(compressed.range_search_bbox(&query)),
+            sorted_ids(plain.range_search_bbox(&query))
+        );
+    }
+
+    #[test]
+    fn test_knn_search_matches_quadtree() {
+        let (_, compressed, plain) = trees();
+        for target in targets() {
+            let expected = plain.knn_search::<EuclideanDistance>(&target, 7);
+            let found = compressed.knn_search::<EuclideanDistance>(&target, 7);
+            let distances = |ps: &[Point2D<usize>]| -> Vec<f64> {
+                ps.iter().map(|p| p.distance_sq(&target)).collect()
+            };
+            assert_eq!(distances(&found), distances(&expected));
+        }
+    }
+
+    #[test]
+    fn test_range_search_matches_quadtree() {
+        let (_, compressed, plain) = trees();
+        for target in targets() {
+            assert_eq!(
+                sorted_ids(compressed.range_search::<EuclideanDistance>(&target, 400.0)),
+                sorted_ids(plain.range_search::<EuclideanDistance>(&target, 400.0))
+            );
+        }
+    }
+
+    #[test]
+    fn test_clusters_are_shallow() {
+        let (points, compressed, plain) = trees();
+        // The clusters are reached without the long chains a standard quadtree needs.
+        let deepest = points
+            .iter()
+            .filter_map(|p| plain.node_path(p))
+            .map(|path| path.len())
+            .max()
+            .unwrap();
+        assert!(deepest >= 10);
+        assert!(compressed.depth() < deepest / 2);
+    }
+
+    #[test]
+    fn test_delete_missing_point_returns_false() {
+        let points = clustered(20);
+        let mut tree = CompressedQuadtree::new(&boundary(), 4).unwrap();
+        tree.insert_bulk(&points[..10]);
+        assert!(!tree.delete(&points[15]));
+        assert!(tree.delete(&points[0]));
+        assert!(!tree.delete(&points[0]));
+        assert_eq!(tree.len(), 9);
+    }
+
+    #[test]
+    fn test_delete_restores_compact_shape() {
+        let points = clustered(200);
+        let mut tree = CompressedQuadtree::new(&boundary(), 4).unwrap();
+        tree.insert_bulk(&points[..8]);
+        let (depth, nodes) = (tree.depth(), tree.node_count());
+        tree.insert_bulk(&points[8..]);
+        assert!(tree.node_count() > nodes);
+
+        for point in &points[8..] {
+            assert!(tree.contains(point));
+            assert!(tree.delete(point));
+            assert!(!tree.contains(point));
+        }
+        assert_eq!(
+            (tree.len(), tree.depth(), tree.node_count()),
+            (8, depth, nodes)
+        );
+    }
+
+    #[test]
+    fn test_delete_all_empties_tree() {
+        let points = clustered(200);
+        let mut tree = CompressedQuadtree::new(&boundary(), 4).unwrap();
+        tree.insert_bulk(&points);
+        for point in &points {
+            assert!(tree.delete(point));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.node_count(), 0);
+        assert!(tree.range_search_bbox(&boundary()).is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+//! ## Shared Indexes
+//!
+//! This module provides `SharedIndex`, a cheaply cloneable handle to an index that can be used
+//! from several threads at once, e.g., by the request handlers of a web service that query one
+//! in-memory index. Any number of threads can read at the same time through `read`, while
+//! `write` gives one thread exclusive access for updates.
+//!
+//! The index is kept behind a read-write lock. A thread that panics while holding the lock does
+//! not make the index unusable: the next caller gets the index in whatever state the panicking
+//! call left it, as all trees stay structurally valid between individual updates.
+//!
+//! ### Thread Safety
+//!
+//! Every tree in this crate is `Send` and `Sync` whenever its points are, which is checked at
+//! compile time. The trees have no interior mutability: queries take `&self` and never modify
+//! the tree, so any number of threads can query the same tree at once without a lock (e.g.,
+//! through an `Arc` or with scoped threads), and a lock like the one of `SharedIndex` is only
+//! needed to update it while it is being queried. With the `parallel` feature, the trees also
+//! provide `par_knn_search` and `par_range_search`, which answer a batch of queries with
+//! Rayon.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::concurrent::SharedIndex;
+//! use spart::geometry::{EuclideanDistance, Point2D};
+//! use spart::kdtree::KdTree;
+//!
+//! let index = SharedIndex::new(KdTree::new());
+//! index.write(|tree| tree.insert(Point2D::new(1.0, 2.0, Some("a")))).unwrap();
+//!
+//! let handles: Vec<_> = (0..4)
+//!     .map(|_| {
+//!         let index = index.clone();
+//!         std::thread::spawn(move || {
+//!             let target = Point2D::new(0.0, 0.0, None);
+//!             index.read(|tree| tree.knn_search::<EuclideanDistance>(&target, 1).len())
+//!         })
+//!     })
+//!     .collect();
+//! for handle in handles {
+//!     assert_eq!(handle.join().unwrap(), 1);
+//! }
+//! ```
+
+use crate::compressed_quadtree::CompressedQuadtree;
+use crate::frozen_kdtree::FrozenKdTree;
+use crate::geometry::{Point2D, Point3D};
+use crate::kdtree::{KdPoint, KdTree};
+use crate::linear::LinearIndex;
+use crate::octree::Octree;
+use crate::quadtree::Quadtree;
+use crate::rstar_tree::{RStarTree, RStarTreeObject};
+use crate::rtree::{RTree, RTreeObject};
+use std::fmt::Debug;
+use std::sync::{Arc, PoisonError, RwLock};
+
+// Fails to compile if a tree holding thread-safe points is not itself thread-safe, e.g., if
+// it gains a field with interior mutability.
+const _: () = {
+    fn assert_send_sync<I: Send + Sync>() {}
+
+    // Never called; checking its body is enough.
+    #[allow(dead_code)]
+    fn trees_are_send_sync<T, P, O>()
+    where
+        T: Clone + PartialEq + Debug + Send + Sync,
+        P: KdPoint + Send + Sync,
+        O: RTreeObject + RStarTreeObject + Send + Sync,
+        <O as RTreeObject>::B: Send + Sync,
+        <O as RStarTreeObject>::B: Send + Sync,
+    {
+        assert_send_sync::<Quadtree<T>>();
+        assert_send_sync::<CompressedQuadtree<T>>();
+        assert_send_sync::<Octree<T>>();
+        assert_send_sync::<KdTree<P>>();
+        assert_send_sync::<FrozenKdTree<P>>();
+        assert_send_sync::<LinearIndex<P>>();
+        assert_send_sync::<RTree<O>>();
+        assert_send_sync::<RStarTree<O>>();
+        assert_send_sync::<RTree<Point2D<T>>>();
+        assert_send_sync::<RStarTree<Point3D<T>>>();
+        assert_send_sync::<SharedIndex<KdTree<P>>>();
+    }
+};
+
+/// A handle to an index shared between threads.
+///
+/// Clones refer to the same index.
+#[derive(Debug, Default)]
+pub struct SharedIndex<I> {
+    inner: Arc<RwLock<I>>,
+}
+
+impl<I> Clone for SharedIndex<I> {
+    fn clone(&self) -> Self {
+        SharedIndex {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<I> SharedIndex<I> {
+    /// Wraps an index for shared use.
+    pub fn new(index: I) -> Self {
+        SharedIndex {
+            inner: Arc::new(RwLock::new(index)),
+        }
+    }
+
+    /// Runs `f` with shared access to the index, waiting while another thread is writing.
+    ///
+    /// # Returns
+    ///
+    /// The result of `f`.
+    pub fn read<R>(&self, f: impl FnOnce(&I) -> R) -> R {
+        let index = self.inner.read().unwrap_or_else(PoisonError::into_inner);
+        f(&index)
+    }
+
+    /// Runs `f` with exclusive access to the index, waiting until no other thread is reading
+    /// or writing.
+    ///
+    /// # Returns
+    ///
+    /// The result of `f`.
+    pub fn write<R>(&self, f: impl FnOnce(&mut I) -> R) -> R {
+        let mut index = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        f(&mut index)
+    }
+
+    /// Returns the index if this is the only handle to it, or the handle itself otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the handle unchanged if it has been cloned and the clones are still alive.
+    pub fn try_unwrap(self) -> Result<I, Self> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(lock) => Ok(lock.into_inner().unwrap_or_else(PoisonError::into_inner)),
+            Err(inner) => Err(SharedIndex { inner }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point2D, Rectangle};
+    use crate::quadtree::Quadtree;
+
+    #[test]
+    fn test_concurrent_writers_and_readers() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let index = SharedIndex::new(Quadtree::new(&boundary, 4).unwrap());
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let index = index.clone();
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        let point = Point2D::new(t as f64 * 20.0, i as f64, Some(t * 100 + i));
+                        assert!(index.write(|tree| tree.insert(point)));
+                        index.read(|tree| tree.check_invariants().unwrap());
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(index.read(|tree| tree.stats().size), 200);
+
+        let clone = index.clone();
+        let index = index.try_unwrap().unwrap_err();
+        drop(clone);
+        assert_eq!(index.try_unwrap().unwrap().stats().size, 200);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_queries_match_sequential() {
+        use crate::geometry::EuclideanDistance;
+        use crate::rtree::RTree;
+
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let points: Vec<_> = (0..2000)
+            .map(|i| Point2D::new((i * 37 % 100) as f64, (i * 61 % 97) as f64, Some(i)))
+            .collect();
+        let mut quadtree = Quadtree::new(&boundary, 8).unwrap();
+        quadtree.insert_bulk(&points);
+        let mut rtree = RTree::new(8).unwrap();
+        rtree.insert_bulk(points.clone());
+        let targets = &points[..200];
+
+        let knn = quadtree.par_knn_search::<EuclideanDistance>(targets, 5);
+        let range = rtree.par_range_search::<EuclideanDistance>(targets, 4.0);
+        assert_eq!(knn.len(), targets.len());
+        for (i, target) in targets.iter().enumerate() {
+            assert_eq!(knn[i], quadtree.knn_search::<EuclideanDistance>(target, 5));
+            assert_eq!(
+                range[i],
+                rtree.range_search::<EuclideanDistance>(target, 4.0)
+            );
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! ## Neighborhood Covariance
+//!
+//! This module provides the covariance matrix of a set of points and its eigen-decomposition,
+//! the basis of normal and curvature estimation in point-cloud processing. The trees compute it
+//! for the neighborhood of a query point with `neighborhood_covariance`, where the neighborhood
+//! is given by `Neighborhood` as the `k` nearest points or the points within a radius, and for
+//! many query points at once with `neighborhood_covariances`.
+//!
+//! The eigenvector of the smallest eigenvalue is the direction in which the neighborhood
+//! varies least, i.e., the normal of the surface the points were sampled from. Its sign is
+//! arbitrary; orient normals (e.g., toward the sensor) before using them for shading.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::covariance::Neighborhood;
+//! use spart::geometry::Point3D;
+//! use spart::kdtree::KdTree;
+//!
+//! // Points on the plane z = 0.
+//! let mut tree = KdTree::new();
+//! for i in 0..100 {
+//!     tree.insert(Point3D::new((i % 10) as f64, (i / 10) as f64, 0.0, None::<()>)).unwrap();
+//! }
+//! let query = Point3D::new(4.0, 4.0, 0.0, None);
+//! let covariance = tree.neighborhood_covariance(&query, Neighborhood::Knn(12)).unwrap().unwrap();
+//! let normal = covariance.normal();
+//! assert!((normal[2].abs() - 1.0).abs() < 1e-9);
+//! ```
+
+use crate::kdtree::KdPoint;
+
+/// The maximum number of Jacobi sweeps; a few suffice for the small matrices of point clouds.
+const MAX_SWEEPS: usize = 64;
+
+/// The points around a query point that make up its neighborhood.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Neighborhood {
+    /// The `k` nearest stored points.
+    Knn(usize),
+    /// The stored points within a radius (inclusive).
+    Radius(f64),
+}
+
+/// The mean and covariance matrix of a set of points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Covariance {
+    /// The number of points.
+    pub count: usize,
+    /// The mean of the points, one coordinate per dimension.
+    pub mean: Vec<f64>,
+    /// The covariance matrix, as rows. It is normalized by the number of points (not by one
+    /// less), so a single point has a zero matrix.
+    pub matrix: Vec<Vec<f64>>,
+}
+
+impl Covariance {
+    /// Computes the mean and covariance matrix of the given points.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points, all with the same number of dimensions.
+    ///
+    /// # Returns
+    ///
+    /// The covariance, or `None` if `points` is empty.
+    pub fn from_points<P: KdPoint>(points: &[P]) -> Option<Self> {
+        let dims = points.first()?.dims();
+        let coords = |p: &P| -> Vec<f64> {
+            (0..dims)
+                .map(|axis| p.coord(axis).unwrap_or(f64::NAN))
+                .collect()
+        };
+        let n = points.len() as f64;
+        let mut mean = vec![0.0; dims];
+        for point in points {
+            for (sum, c) in mean.iter_mut().zip(coords(point)) {
+                *sum += c;
+            }
+        }
+        mean.iter_mut().for_each(|sum| *sum /= n);
+        // The points are centered first, which keeps the sums accurate for points far from
+        // the origin.
+        let mut matrix = vec![vec![0.0; dims]; dims];
+        for point in points {
+            let centered: Vec<f64> = coords(point)
+                .into_iter()
+                .zip(&mean)
+                .map(|(c, m)| c - m)
+                .collect();
+            for (i, row) in matrix.iter_mut().enumerate() {
+                for (j, entry) in row.iter_mut().enumerate().skip(i) {
+                    *entry += centered[i] * centered[j];
+                }
+            }
+        }
+        for i in 0..dims {
+            for j in i..dims {
+                matrix[i][j] /= n;
+                matrix[j][i] = matrix[i][j];
+            }
+        }
+        Some(Covariance {
+            count: points.len(),
+            mean,
+            matrix,
+        })
+    }
+
+    /// Computes the eigenvalues and eigenvectors of the covariance matrix with the Jacobi
+    /// method.
+    pub fn eigen(&self) -> Eigen {
+        let mut a = self.matrix.clone();
+        let n = a.len();
+        let mut v: Vec<Vec<f64>> = (0..n)
+            .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+            .collect();
+        let total: f64 = a.iter().flatten().map(|x| x * x).sum();
+        for _ in 0..MAX_SWEEPS {
+            let off: f64 = (0..n)
+                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+                .map(|(i, j)| a[i][j] * a[i][j])
+                .sum();
+            if off <= total * f64::EPSILON * f64::EPSILON {
+                break;
+            }
+            for p in 0..n {
+                for q in p + 1..n {
+                    if a[p][q] == 0.0 {
+                        continue;
+                    }
+                    // The rotation by (c, s) that zeroes a[p][q], taking the smaller angle.
+                    let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+                    for row in a.iter_mut() {
+                        let (x, y) = (row[p], row[q]);
+                        row[p] = c * x - s * y;
+                        row[q] = s * x + c * y;
+                    }
+                    for k in 0..n {
+                        let (x, y) = (a[p][k], a[q][k]);
+                        a[p][k] = c * x - s * y;
+                        a[q][k] = s * x + c * y;
+                    }
+                    for row in v.iter_mut() {
+                        let (x, y) = (row[p], row[q]);
+                        row[p] = c * x - s * y;
+                        row[q] = s * x + c * y;
+                    }
+                }
+            }
+        }
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&i, &j| a[i][i].total_cmp(&a[j][j]));
+        Eigen {
+            values: order.iter().map(|&i| a[i][i]).collect(),
+            vectors: order
+                .iter()
+                .map(|&i| v.iter().map(|row| row[i]).collect())
+                .collect(),
+        }
+    }
+
+    /// Returns the estimated normal: the unit eigenvector of the smallest eigenvalue.
+    pub fn normal(&self) -> Vec<f64> {
+        self.eigen().vectors.swap_remove(0)
+    }
+}
+
+/// The eigen-decomposition of a covariance matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eigen {
+    /// The eigenvalues, in ascending order.
+    pub values: Vec<f64>,
+    /// The unit eigenvectors, in the order of `values`.
+    pub vectors: Vec<Vec<f64>>,
+}
+
+impl Eigen {
+    /// Returns the surface variation, the smallest eigenvalue divided by their sum.
+    ///
+    /// It is 0 for points on a plane (or a line) and at most `1 / dims` for points spread
+    /// evenly in all directions, and is commonly used as a curvature estimate.
+    pub fn surface_variation(&self) -> f64 {
+        let sum: f64 = self.values.iter().sum();
+        if sum > 0.0 { self.values[0] / sum } else { 0.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Point3D;
+
+    /// Points along a curve that spreads them in all three directions.
+    fn curve() -> Vec<Point3D<()>> {
+        (0..40)
+            .map(|i| {
+                let t = i as f64;
+                Point3D::new(t.sin() * 3.0, t.cos() + 0.5 * t, (t * 0.7).sin(), None)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_from_points_of_no_points_is_none() {
+        assert!(Covariance::from_points::<Point3D<()>>(&[]).is_none());
+    }
+
+    #[test]
+    fn test_single_point_has_zero_matrix() {
+        let single = Covariance::from_points(&curve()[..1]).unwrap();
+        assert_eq!(single.count, 1);
+        assert!(single.matrix.iter().flatten().all(|&x| x == 0.0));
+        assert_eq!(single.eigen().surface_variation(), 0.0);
+    }
+
+    #[test]
+    fn test_from_points_normalizes_by_count() {
+        let points = [
+            Point3D::new(0.0, 1.0, 5.0, None::<()>),
+            Point3D::new(2.0, 3.0, 5.0, None),
+        ];
+        let covariance = Covariance::from_points(&points).unwrap();
+        assert_eq!(covariance.mean, [1.0, 2.0, 5.0]);
+        assert_eq!(
+            covariance.matrix,
+            [[1.0, 1.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn test_eigenvalues_are_ascending_and_sum_to_trace() {
+        let covariance = Covariance::from_points(&curve()).unwrap();
+        let eigen = covariance.eigen();
+        assert!(eigen.values.windows(2).all(|w| w[0] <= w[1]));
+        let trace: f64 = (0..3).map(|i| covariance.matrix[i][i]).sum();
+        assert!((eigen.values.iter().sum::<f64>() - trace).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_eigenvectors_are_unit_and_decompose_the_matrix() {
+        let covariance = Covariance::from_points(&curve()).unwrap();
+        let eigen = covariance.eigen();
+        for (value, vector) in eigen.values.iter().zip(&eigen.vectors) {
+            let norm: f64 = vector.iter().map(|x| x * x).sum();
+            assert!((norm - 1.0).abs() < 1e-9);
+            for (row, x) in covariance.matrix.iter().zip(vector) {
+                let product: f64 = row.iter().zip(vector).map(|(a, b)| a * b).sum();
+                assert!((product - value * x).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_normal_of_planar_points_is_perpendicular() {
+        let points: Vec<_> = (0..25)
+            .map(|i| Point3D::new((i % 5) as f64, (i / 5) as f64 * 0.5, 2.0, None::<()>))
+            .collect();
+        let covariance = Covariance::from_points(&points).unwrap();
+        let normal = covariance.normal();
+        assert!((normal[2].abs() - 1.0).abs() < 1e-9);
+        assert!(covariance.eigen().surface_variation().abs() < 1e-12);
+    }
+}
@@ -0,0 +1,291 @@
+//! ## Synthetic Datasets
+//!
+//! This module generates reproducible point sets for benchmarks and for comparing tree
+//! configurations. The shape of a dataset is given by a `Distribution`:
+//!
+//! - `Uniform` spreads the points evenly over the boundary, which is the easiest case for all
+//!   trees.
+//! - `Clustered` draws the points from a mixture of Gaussian clusters of equal size.
+//! - `Grid` places the points on a regular grid, so many of them share coordinates along each
+//!   axis, which is the worst case for trees that split at point coordinates.
+//! - `PowerLaw` draws the points from clusters whose sizes follow a power law (Zipf's law),
+//!   like the populations of cities: a few dense clusters hold most of the points and a long
+//!   tail of small clusters holds the rest.
+//!
+//! The generators take any `RandomSource`, so the same seed always gives the same dataset. The
+//! payload of each point is its index in the returned vector.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::datasets::{Distribution, points_2d};
+//! use spart::geometry::Rectangle;
+//! use spart::random::SplitMix64;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let clustered = Distribution::Clustered { clusters: 10, spread: 0.02 };
+//! let points = points_2d(&mut SplitMix64::new(7), &boundary, 1_000, &clustered);
+//! assert_eq!(points.len(), 1_000);
+//! assert!(points.iter().all(|p| boundary.contains(p)));
+//! assert_eq!(points, points_2d(&mut SplitMix64::new(7), &boundary, 1_000, &clustered));
+//! ```
+
+use crate::geometry::{Cube, Point2D, Point3D, Rectangle};
+use crate::random::RandomSource;
+use std::f64::consts::TAU;
+
+/// The shape of a synthetic dataset.
+///
+/// Sizes like `spread` are fractions of the extent of the boundary along each axis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distribution {
+    /// Points spread uniformly over the boundary.
+    Uniform,
+    /// Points drawn from `clusters` Gaussian clusters of equal size, centered uniformly at
+    /// random in the boundary.
+    Clustered {
+        /// The number of clusters (at least 1).
+        clusters: usize,
+        /// The standard deviation of each cluster along each axis, e.g., `0.02`.
+        spread: f64,
+    },
+    /// Points on a regular grid filling the boundary row by row, with the same number of
+    /// cells along each axis.
+    Grid,
+    /// Points drawn from `clusters` Gaussian clusters, where the `i`-th largest cluster holds
+    /// a share of the points proportional to `1 / i^exponent`.
+    ///
+    /// Larger clusters are also wider: the clusters cover areas (or volumes) proportional to
+    /// their sizes, with the largest one having a standard deviation of 5% of the boundary.
+    PowerLaw {
+        /// The number of clusters (at least 1).
+        clusters: usize,
+        /// The exponent of the power law, e.g., `1.0` for Zipf's law.
+        exponent: f64,
+    },
+}
+
+/// Generates 2D points inside a boundary.
+///
+/// # Arguments
+///
+/// * `rng` - The source of randomness; the same seed gives the same points.
+/// * `boundary` - The rectangle holding all points.
+/// * `n` - The number of points to generate.
+/// * `distribution` - The shape of the dataset.
+///
+/// # Returns
+///
+/// `n` points inside `boundary`, each with its index as payload.
+pub fn points_2d<R: RandomSource>(
+    rng: &mut R,
+    boundary: &Rectangle,
+    n: usize,
+    distribution: &Distribution,
+) -> Vec<Point2D<usize>> {
+    unit_points::<2, R>(rng, n, distribution)
+        .into_iter()
+        .enumerate()
+        .map(|(i, [x, y])| {
+            Point2D::new(
+                boundary.x + x * boundary.width,
+                boundary.y + y * boundary.height,
+                Some(i),
+            )
+        })
+        .collect()
+}
+
+/// Generates 3D points inside a boundary.
+///
+/// # Arguments
+///
+/// * `rng` - The source of randomness; the same seed gives the same points.
+/// * `boundary` - The cube holding all points.
+/// * `n` - The number of points to generate.
+/// * `distribution` - The shape of the dataset.
+///
+/// # Returns
+///
+/// `n` points inside `boundary`, each with its index as payload.
+pub fn points_3d<R: RandomSource>(
+    rng: &mut R,
+    boundary: &Cube,
+    n: usize,
+    distribution: &Distribution,
+) -> Vec<Point3D<usize>> {
+    unit_points::<3, R>(rng, n, distribution)
+        .into_iter()
+        .enumerate()
+        .map(|(i, [x, y, z])| {
+            Point3D::new(
+                boundary.x + x * boundary.width,
+                boundary.y + y * boundary.height,
+                boundary.z + z * boundary.depth,
+                Some(i),
+            )
+        })
+        .collect()
+}
+
+/// Generates `n` points in the unit cube `[0, 1)^D`.
+fn unit_points<const D: usize, R: RandomSource>(
+    rng: &mut R,
+    n: usize,
+    distribution: &Distribution,
+) -> Vec<[f64; D]> {
+    match *distribution {
+        Distribution::Uniform => (0..n).map(|_| uniform(rng)).collect(),
+        Distribution::Grid => {
+            let side = (1..)
+                .find(|&side: &usize| side.pow(D as u32) >= n)
+                .unwrap_or(1);
+            (0..n)
+                .map(|i| {
+                    let mut cell = i;
+                    std::array::from_fn(|_| {
+                        let coord = (cell % side) as f64 + 0.5;
+                        cell /= side;
+                        coord / side as f64
+                    })
+                })
+                .collect()
+        }
+        Distribution::Clustered { clusters, spread } => {
+            let centers: Vec<[f64; D]> = (0..clusters.max(1)).map(|_| uniform(rng)).collect();
+            (0..n)
+                .map(|_| {
+                    let center = &centers[rng.next_below(centers.len())];
+                    around(rng, center, spread)
+                })
+                .collect()
+        }
+        Distribution::PowerLaw { clusters, exponent } => {
+            let clusters = clusters.max(1);
+            let centers: Vec<[f64; D]> = (0..clusters).map(|_| uniform(rng)).collect();
+            let weights: Vec<f64> = (1..=clusters)
+                .map(|rank| (rank as f64).powf(-exponent))
+                .collect();
+            let total: f64 = weights.iter().sum();
+            let mut cumulative = Vec::with_capacity(clusters);
+            let mut sum = 0.0;
+            for weight in &weights {
+                sum += weight / total;
+                cumulative.push(sum);
+            }
+            (0..n)
+                .map(|_| {
+                    let u = rng.next_f64();
+                    let cluster = cumulative.partition_point(|&c| c <= u).min(clusters - 1);
+                    // The area (or volume) of a cluster grows with its share of the points.
+                    let spread = 0.05 * (weights[cluster] / weights[0]).powf(1.0 / D as f64);
+                    around(rng, &centers[cluster], spread)
+                })
+                .collect()
+        }
+    }
+}
+
+fn uniform<const D: usize, R: RandomSource>(rng: &mut R) -> [f64; D] {
+    std::array::from_fn(|_| rng.next_f64())
+}
+
+/// Returns a point drawn from a Gaussian around `center`, redrawing each coordinate until it
+/// falls inside the unit interval.
+fn around<const D: usize, R: RandomSource>(
+    rng: &mut R,
+    center: &[f64; D],
+    spread: f64,
+) -> [f64; D] {
+    let spread = if spread.is_finite() {
+        spread.abs()
+    } else {
+        0.0
+    };
+    std::array::from_fn(|axis| {
+        // The center is inside the interval, so each draw lands inside with a chance of at
+        // least one half.
+        loop {
+            let coord = center[axis] + spread * gaussian(rng);
+            if (0.0..1.0).contains(&coord) {
+                return coord;
+            }
+        }
+    })
+}
+
+/// Returns a standard normal sample (Box-Muller transform).
+fn gaussian<R: RandomSource>(rng: &mut R) -> f64 {
+    let radius = (-2.0 * (1.0 - rng.next_f64()).ln()).sqrt();
+    radius * (TAU * rng.next_f64()).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::SplitMix64;
+
+    const BOUNDARY: Cube = Cube {
+        x: -10.0,
+        y: 0.0,
+        z: 5.0,
+        width: 20.0,
+        height: 10.0,
+        depth: 1.0,
+    };
+
+    #[test]
+    fn test_all_distributions_stay_in_bounds_and_are_reproducible() {
+        let distributions = [
+            Distribution::Uniform,
+            Distribution::Grid,
+            Distribution::Clustered {
+                clusters: 5,
+                spread: 0.3,
+            },
+            Distribution::PowerLaw {
+                clusters: 20,
+                exponent: 1.0,
+            },
+        ];
+        for distribution in &distributions {
+            let points = points_3d(&mut SplitMix64::new(3), &BOUNDARY, 500, distribution);
+            assert_eq!(points.len(), 500);
+            assert!(points.iter().all(|p| BOUNDARY.contains(p)));
+            assert!(points.iter().enumerate().all(|(i, p)| p.data == Some(i)));
+            let again = points_3d(&mut SplitMix64::new(3), &BOUNDARY, 500, distribution);
+            assert_eq!(points, again);
+        }
+    }
+
+    #[test]
+    fn test_grid_and_cluster_shapes() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 4.0,
+            height: 4.0,
+        };
+        let grid = points_2d(&mut SplitMix64::new(0), &boundary, 16, &Distribution::Grid);
+        let mut coords: Vec<(f64, f64)> = grid.iter().map(|p| (p.x, p.y)).collect();
+        coords.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        coords.dedup();
+        assert_eq!(coords.len(), 16);
+        assert_eq!((grid[0].x, grid[0].y), (0.5, 0.5));
+        assert_eq!((grid[15].x, grid[15].y), (3.5, 3.5));
+
+        let single = Distribution::Clustered {
+            clusters: 1,
+            spread: 0.01,
+        };
+        let points = points_2d(&mut SplitMix64::new(1), &boundary, 200, &single);
+        let (mean_x, mean_y) = points
+            .iter()
+            .fold((0.0, 0.0), |(x, y), p| (x + p.x / 200.0, y + p.y / 200.0));
+        assert!(
+            points
+                .iter()
+                .all(|p| (p.x - mean_x).abs() < 0.3 && (p.y - mean_y).abs() < 0.3)
+        );
+    }
+}
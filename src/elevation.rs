@@ -0,0 +1,279 @@
+//! ## Elevation (2.5D) Data
+//!
+//! This module lets 2D trees index points by their planar position `(x, y)` while each point
+//! carries an elevation `z` in its payload. Planar indexing keeps the trees as small and fast
+//! as for plain 2D data, which suits terrain samples, building entrances, or sensor readings
+//! where most queries are planar and the elevation only narrows the results.
+//!
+//! A payload exposes its elevation through the `Elevation` trait, which is implemented for
+//! `f64` (a payload that is only the elevation) and for `Elevated`, which pairs an elevation
+//! with other data. `Quadtree` and `KdTree` over such points provide queries restricted to a
+//! range of elevations (`knn_search_in_z`, `range_search_bbox_in_z`, and `range_search_in_z`).
+//! The range is checked while the tree is traversed, so a k-nearest neighbor search continues
+//! past the points outside the range and still returns `k` points when enough match. Points
+//! without a payload have no elevation and never match.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::elevation::Elevated;
+//! use spart::geometry::{EuclideanDistance, Point2D, Rectangle};
+//! use spart::quadtree::Quadtree;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let mut doors = Quadtree::new(&boundary, 4).unwrap();
+//! doors.insert(Point2D::new(10.0, 10.0, Some(Elevated::new(0.0, "lobby"))));
+//! doors.insert(Point2D::new(11.0, 10.0, Some(Elevated::new(12.0, "roof"))));
+//! doors.insert(Point2D::new(40.0, 40.0, Some(Elevated::new(0.5, "garage"))));
+//!
+//! let here = Point2D::new(11.0, 10.0, None);
+//! let ground = doors.knn_search_in_z::<EuclideanDistance>(&here, 2, -1.0..=1.0);
+//! let names: Vec<_> = ground.iter().map(|p| p.data.as_ref().unwrap().data).collect();
+//! assert_eq!(names, ["lobby", "garage"]);
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::ops::RangeBounds;
+
+/// A payload that carries an elevation.
+pub trait Elevation {
+    /// Returns the elevation (`z` coordinate) of the point carrying this payload.
+    fn elevation(&self) -> f64;
+}
+
+impl Elevation for f64 {
+    fn elevation(&self) -> f64 {
+        *self
+    }
+}
+
+/// A payload pairing an elevation with other data.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Elevated<T> {
+    /// The elevation of the point.
+    pub z: f64,
+    /// The other data carried by the point.
+    pub data: T,
+}
+
+impl<T> Elevated<T> {
+    /// Creates a payload with elevation `z` and other data `data`.
+    pub fn new(z: f64, data: T) -> Self {
+        Elevated { z, data }
+    }
+}
+
+impl<T> Elevation for Elevated<T> {
+    fn elevation(&self) -> f64 {
+        self.z
+    }
+}
+
+/// Returns `true` if `data` carries an elevation inside `z_range`.
+pub(crate) fn in_z_range<E: Elevation>(data: Option<&E>, z_range: &impl RangeBounds<f64>) -> bool {
+    data.is_some_and(|d| z_range.contains(&d.elevation()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{EuclideanDistance, Point2D, Rectangle};
+    use crate::kdtree::KdTree;
+    use crate::quadtree::Quadtree;
+    use crate::random::{RandomSource, SplitMix64};
+
+    type Sample = Point2D<Elevated<i32>>;
+
+    const BOUNDARY: Rectangle = Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    /// 500 random samples at elevations between 0 and 50.
+    fn samples() -> Vec<Sample> {
+        let mut rng = SplitMix64::new(7);
+        (0..500)
+            .map(|i| {
+                let z = rng.next_f64() * 50.0;
+                let (x, y) = (rng.next_f64() * 100.0, rng.next_f64() * 100.0);
+                Point2D::new(x, y, Some(Elevated::new(z, i)))
+            })
+            .collect()
+    }
+
+    /// A quadtree and a Kd-tree holding `points` and a point without an elevation.
+    fn trees(points: &[Sample]) -> (Quadtree<Elevated<i32>>, KdTree<Sample>) {
+        let mut quadtree = Quadtree::new(&BOUNDARY, 4).unwrap();
+        let mut kdtree = KdTree::new();
+        for point in points
+            .iter()
+            .cloned()
+            .chain([Point2D::new(50.0, 50.0, None)])
+        {
+            assert!(quadtree.insert(point.clone()));
+            kdtree.insert(point).unwrap();
+        }
+        (quadtree, kdtree)
+    }
+
+    fn z(point: &Sample) -> f64 {
+        point.data.as_ref().map_or(f64::NAN, |d| d.z)
+    }
+
+    fn ids(points: &[Sample]) -> Vec<i32> {
+        let mut ids: Vec<i32> = points
+            .iter()
+            .filter_map(|p| p.data.as_ref())
+            .map(|d| d.data)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn target() -> Sample {
+        Point2D::new(50.0, 50.0, None)
+    }
+
+    fn distance(p: &Sample) -> f64 {
+        (p.x - 50.0).powi(2) + (p.y - 50.0).powi(2)
+    }
+
+    #[test]
+    fn test_empty_trees_find_nothing() {
+        let quadtree: Quadtree<Elevated<i32>> = Quadtree::new(&BOUNDARY, 4).unwrap();
+        let kdtree: KdTree<Sample> = KdTree::new();
+        assert!(
+            quadtree
+                .knn_search_in_z::<EuclideanDistance>(&target(), 3, ..)
+                .is_empty()
+        );
+        assert!(quadtree.range_search_bbox_in_z(&BOUNDARY, ..).is_empty());
+        assert!(
+            kdtree
+                .knn_search_in_z::<EuclideanDistance>(&target(), 3, ..)
+                .is_empty()
+        );
+        assert!(
+            kdtree
+                .range_search_in_z::<EuclideanDistance>(&target(), 100.0, ..)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_knn_search_in_z_matches_filtered_scan() {
+        let points = samples();
+        let (quadtree, kdtree) = trees(&points);
+        let z_range = 10.0..20.0;
+        let mut expected: Vec<f64> = points
+            .iter()
+            .filter(|p| z_range.contains(&z(p)))
+            .map(distance)
+            .collect();
+        expected.sort_by(f64::total_cmp);
+        expected.truncate(8);
+        let from_quadtree =
+            quadtree.knn_search_in_z::<EuclideanDistance>(&target(), 8, z_range.clone());
+        let from_kdtree = kdtree.knn_search_in_z::<EuclideanDistance>(&target(), 8, z_range);
+        for found in [&from_quadtree, &from_kdtree] {
+            assert_eq!(found.iter().map(distance).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_range_search_bbox_in_z_matches_filtered_scan() {
+        let points = samples();
+        let (quadtree, _) = trees(&points);
+        let window = Rectangle {
+            x: 20.0,
+            y: 30.0,
+            width: 45.0,
+            height: 25.0,
+        };
+        let expected: Vec<Sample> = points
+            .iter()
+            .filter(|p| (10.0..20.0).contains(&z(p)) && window.contains(*p))
+            .cloned()
+            .collect();
+        assert_eq!(
+            ids(&quadtree.range_search_bbox_in_z(&window, 10.0..20.0)),
+            ids(&expected)
+        );
+    }
+
+    #[test]
+    fn test_range_search_in_z_matches_filtered_scan() {
+        let points = samples();
+        let (_, kdtree) = trees(&points);
+        let expected: Vec<Sample> = points
+            .iter()
+            .filter(|p| distance(p) <= 225.0 && z(p) <= 10.0)
+            .cloned()
+            .collect();
+        assert_eq!(
+            ids(&kdtree.range_search_in_z::<EuclideanDistance>(&target(), 15.0, ..=10.0)),
+            ids(&expected)
+        );
+    }
+
+    #[test]
+    fn test_z_range_bounds_are_respected() {
+        let points: Vec<Sample> = [0.0, 5.0, 10.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, z)| Point2D::new(10.0 + i as f64, 10.0, Some(Elevated::new(z, i as i32))))
+            .collect();
+        let (quadtree, kdtree) = trees(&points);
+        assert_eq!(
+            ids(&quadtree.range_search_bbox_in_z(&BOUNDARY, 0.0..=10.0)),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            ids(&quadtree.range_search_bbox_in_z(&BOUNDARY, 0.0..10.0)),
+            [0, 1]
+        );
+        let near = |range| kdtree.range_search_in_z::<EuclideanDistance>(&points[1], 1.0, range);
+        assert_eq!(ids(&near(5.0..=10.0)), [1, 2]);
+    }
+
+    #[test]
+    fn test_points_without_elevation_never_match() {
+        let (quadtree, kdtree) = trees(&[]);
+        assert!(quadtree.range_search_bbox_in_z(&BOUNDARY, ..).is_empty());
+        assert!(
+            kdtree
+                .knn_search_in_z::<EuclideanDistance>(&target(), 1, ..)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_invalid_queries_find_nothing() {
+        let (quadtree, kdtree) = trees(&samples());
+        assert!(
+            kdtree
+                .range_search_in_z::<EuclideanDistance>(&target(), -1.0, ..)
+                .is_empty()
+        );
+        let nan = Point2D::new(f64::NAN, 50.0, None);
+        assert!(
+            kdtree
+                .range_search_in_z::<EuclideanDistance>(&nan, 10.0, ..)
+                .is_empty()
+        );
+        assert!(
+            quadtree
+                .range_search_bbox_in_z(&BOUNDARY, 20.0..10.0)
+                .is_empty()
+        );
+        assert!(
+            quadtree
+                .knn_search_in_z::<EuclideanDistance>(&target(), 5, 20.0..10.0)
+                .is_empty()
+        );
+    }
+}
@@ -1,6 +1,12 @@
 //! ## Custom Errors for Spart
 //!
 //! This module defines custom errors and exceptions that are used internally by Spart.
+//!
+//! The regular search methods of the trees are lenient: they return no results for a kNN
+//! search with `k = 0` or on an empty tree, a range search with a negative or NaN radius, and
+//! any search around a point with a NaN coordinate. Their `try_` variants (e.g.,
+//! `try_knn_search` and `try_range_search`) report these cases as errors instead, for callers
+//! that would rather catch the bug than get an empty result.
 
 use std::error::Error;
 use std::fmt;
@@ -31,6 +37,36 @@ pub enum SpartError {
         /// The actual dimension.
         actual: usize,
     },
+    /// Occurs when persisting or restoring an index fails (e.g., an I/O error or a corrupted
+    /// file).
+    Persistence {
+        /// A description of what went wrong.
+        reason: String,
+    },
+    /// Occurs when a consistency check finds a tree in an invalid state (e.g., after
+    /// restoring it from corrupted data).
+    InvariantViolation {
+        /// A description of the violated invariant.
+        reason: String,
+    },
+    /// Occurs when coordinates are out of range or not finite (e.g., a latitude above 90°).
+    InvalidCoordinates {
+        /// A description of the invalid coordinates.
+        reason: String,
+    },
+    /// Occurs when a query needs at least one stored object, e.g., a kNN search on an empty
+    /// tree.
+    EmptyTree,
+    /// Occurs when a search radius is negative or NaN.
+    InvalidRadius {
+        /// The radius that was provided.
+        radius: f64,
+    },
+    /// Occurs when the number of neighbors requested from a kNN search is zero.
+    InvalidK {
+        /// The number of neighbors that was requested.
+        k: usize,
+    },
 }
 
 impl fmt::Display for SpartError {
@@ -57,12 +93,61 @@ impl fmt::Display for SpartError {
                     "Dimension mismatch: expected {expected}, but got {actual}"
                 )
             }
+            SpartError::Persistence { reason } => {
+                write!(f, "Persistence error: {reason}")
+            }
+            SpartError::InvariantViolation { reason } => {
+                write!(f, "Invariant violation: {reason}")
+            }
+            SpartError::InvalidCoordinates { reason } => {
+                write!(f, "Invalid coordinates: {reason}")
+            }
+            SpartError::EmptyTree => write!(f, "The tree is empty"),
+            SpartError::InvalidRadius { radius } => {
+                write!(
+                    f,
+                    "Invalid radius: {radius}. Radius must be zero or greater."
+                )
+            }
+            SpartError::InvalidK { k } => {
+                write!(f, "Invalid k: {k}. k must be greater than zero.")
+            }
         }
     }
 }
 
 impl Error for SpartError {}
 
+/// Checks the arguments of a `try_knn_search` call.
+pub(crate) fn check_knn_query(k: usize, is_empty: bool) -> Result<(), SpartError> {
+    if k == 0 {
+        return Err(SpartError::InvalidK { k });
+    }
+    if is_empty {
+        return Err(SpartError::EmptyTree);
+    }
+    Ok(())
+}
+
+/// Checks that the coordinates of a query point are not NaN.
+pub(crate) fn check_query_coords(coords: &[f64]) -> Result<(), SpartError> {
+    if coords.iter().any(|c| c.is_nan()) {
+        return Err(SpartError::InvalidCoordinates {
+            reason: format!("the query coordinates {coords:?} include NaN"),
+        });
+    }
+    Ok(())
+}
+
+/// Checks the arguments of a `try_range_search` call.
+pub(crate) fn check_range_query(radius: f64) -> Result<(), SpartError> {
+    if radius >= 0.0 {
+        Ok(())
+    } else {
+        Err(SpartError::InvalidRadius { radius })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,4 +172,31 @@ mod tests {
             "Invalid capacity: 0. Capacity must be greater than zero."
         );
     }
+
+    #[test]
+    fn test_query_checks() {
+        assert!(matches!(
+            check_knn_query(0, false),
+            Err(SpartError::InvalidK { k: 0 })
+        ));
+        assert!(matches!(
+            check_knn_query(3, true),
+            Err(SpartError::EmptyTree)
+        ));
+        assert!(check_knn_query(3, false).is_ok());
+        assert!(check_range_query(0.0).is_ok());
+        assert!(check_query_coords(&[1.0, f64::INFINITY]).is_ok());
+        assert!(matches!(
+            check_query_coords(&[1.0, f64::NAN]),
+            Err(SpartError::InvalidCoordinates { .. })
+        ));
+        assert!(matches!(
+            check_range_query(f64::NAN),
+            Err(SpartError::InvalidRadius { .. })
+        ));
+        assert_eq!(
+            check_range_query(-1.0).unwrap_err().to_string(),
+            "Invalid radius: -1. Radius must be zero or greater."
+        );
+    }
 }
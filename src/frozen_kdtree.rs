@@ -0,0 +1,725 @@
+//! ## Frozen Kd‑tree
+//!
+//! This module provides `FrozenKdTree`, a read-only Kd‑tree built once from a slice of
+//! points. Unlike `KdTree`, whose nodes are allocated one by one as points are inserted, it is
+//! perfectly balanced (every node splits its points at the median) and stores all nodes in one
+//! contiguous array, without pointers.
+//!
+//! The nodes are ordered in the van Emde Boas layout: the top half of the levels of the tree
+//! is stored first, followed by each of the subtrees hanging below it, each laid out the same
+//! way. A root-to-leaf path then touches only `O(log_B n)` blocks of memory for any block
+//! size `B`, so searches make good use of every cache level without being tuned for any of
+//! them (the layout is "cache-oblivious"). This makes kNN and range searches faster than on a
+//! `KdTree` for datasets that do not change.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::frozen_kdtree::FrozenKdTree;
+//! use spart::geometry::{EuclideanDistance, Point2D};
+//!
+//! let points: Vec<Point2D<u32>> = (0..100)
+//!     .map(|i| Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)))
+//!     .collect();
+//! let tree = FrozenKdTree::new(&points).unwrap();
+//! assert_eq!(tree.len(), 100);
+//!
+//! let nearest = tree.knn_search::<EuclideanDistance>(&Point2D::new(3.1, 4.2, None), 1);
+//! assert_eq!(nearest[0].data, Some(43));
+//! assert_eq!(tree.range_search::<EuclideanDistance>(&Point2D::new(0.0, 0.0, None), 1.0).len(), 3);
+//! ```
+
+use crate::errors::{SpartError, check_knn_query, check_range_query};
+use crate::geometry::DistanceMetric;
+use crate::kdtree::{KdPoint, check_query_point, has_nan_coord};
+use crate::knn::{KnnResults, QueryContext};
+use ordered_float::OrderedFloat;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The child index of a missing child.
+pub(crate) const NO_CHILD: u32 = u32::MAX;
+
+/// A node of a `FrozenKdTree`, referring to its children by their index in the node array.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct FrozenNode<P> {
+    pub(crate) point: P,
+    pub(crate) left: u32,
+    pub(crate) right: u32,
+}
+
+/// A read-only, balanced Kd‑tree stored in a cache-oblivious layout.
+///
+/// Deserialized trees have their node count, dimensions, and child references checked, so
+/// that corrupted data is reported as `SpartError::Persistence` instead of making searches
+/// index out of bounds.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "FrozenKdTreeData<P>",
+        bound(deserialize = "P: KdPoint + Deserialize<'de>")
+    )
+)]
+pub struct FrozenKdTree<P> {
+    /// The nodes in van Emde Boas order; the root comes first.
+    pub(crate) nodes: Vec<FrozenNode<P>>,
+    pub(crate) dims: usize,
+}
+
+/// The serialized form of a `FrozenKdTree`, checked before it becomes a tree.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "FrozenKdTree")]
+struct FrozenKdTreeData<P> {
+    nodes: Vec<FrozenNode<P>>,
+    dims: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<P: KdPoint> TryFrom<FrozenKdTreeData<P>> for FrozenKdTree<P> {
+    type Error = SpartError;
+
+    /// Checks the node count, the dimensions of the points, and the child references.
+    fn try_from(data: FrozenKdTreeData<P>) -> Result<Self, SpartError> {
+        let corrupt = |reason: String| SpartError::Persistence { reason };
+        let FrozenKdTreeData { nodes, dims } = data;
+        if nodes.len() >= NO_CHILD as usize {
+            return Err(corrupt(format!("{} nodes are too many", nodes.len())));
+        }
+        if dims == 0 && !nodes.is_empty() {
+            return Err(corrupt("nodes with zero dimensions".to_string()));
+        }
+        if let Some(node) = nodes.iter().find(|n| n.point.dims() != dims) {
+            return Err(corrupt(format!(
+                "a point has {} dimensions instead of {dims}",
+                node.point.dims()
+            )));
+        }
+        check_links(nodes.len(), |i| [nodes[i].left, nodes[i].right]).map_err(corrupt)?;
+        Ok(FrozenKdTree { nodes, dims })
+    }
+}
+
+/// Checks that every child reference among `len` nodes is missing or points further into the
+/// node array, and that every node other than the root is referenced exactly once.
+///
+/// `children` returns the left and right child references of a node.
+pub(crate) fn check_links(len: usize, children: impl Fn(usize) -> [u32; 2]) -> Result<(), String> {
+    let mut referenced = vec![false; len];
+    for index in 0..len {
+        for child in children(index) {
+            if child == NO_CHILD {
+                continue;
+            }
+            let child = child as usize;
+            if child <= index || child >= len {
+                return Err(format!("node {index} refers to node {child}"));
+            }
+            if std::mem::replace(&mut referenced[child], true) {
+                return Err(format!("node {child} is referenced twice"));
+            }
+        }
+    }
+    match referenced.iter().skip(1).position(|&r| !r) {
+        Some(orphan) => Err(format!("node {} is not referenced", orphan + 1)),
+        None => Ok(()),
+    }
+}
+
+/// A node of the balanced tree while it is being built, before it is laid out.
+struct BuildNode {
+    point: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl<P: KdPoint> FrozenKdTree<P> {
+    /// Builds a tree from a slice of points.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to store; all must have the same number of dimensions.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::DimensionMismatch` if the points do not all have the same number of
+    /// dimensions, `SpartError::InvalidDimension` if they have zero dimensions, and
+    /// `SpartError::InvalidCapacity` if there are `u32::MAX` points or more.
+    pub fn new(points: &[P]) -> Result<Self, SpartError> {
+        if points.len() >= NO_CHILD as usize {
+            return Err(SpartError::InvalidCapacity {
+                capacity: points.len(),
+            });
+        }
+        let dims = points.first().map_or(0, KdPoint::dims);
+        if dims == 0 && !points.is_empty() {
+            return Err(SpartError::InvalidDimension {
+                requested: 0,
+                available: 0,
+            });
+        }
+        if let Some(point) = points.iter().find(|p| p.dims() != dims) {
+            return Err(SpartError::DimensionMismatch {
+                expected: dims,
+                actual: point.dims(),
+            });
+        }
+
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        let mut build = Vec::with_capacity(points.len());
+        let root = build_balanced(points, dims, &mut order, 0, &mut build);
+
+        let mut layout = Vec::with_capacity(points.len());
+        if let Some(root) = root {
+            let height = subtree_height(&build, root);
+            lay_out(&build, root, height, &mut layout);
+        }
+        // `position[i]` is the index in the layout of the build node `i`.
+        let mut position = vec![0u32; build.len()];
+        for (pos, &node) in layout.iter().enumerate() {
+            position[node] = pos as u32;
+        }
+        let child_index = |child: Option<usize>| child.map_or(NO_CHILD, |c| position[c]);
+        let nodes = layout
+            .iter()
+            .map(|&node| FrozenNode {
+                point: points[build[node].point].clone(),
+                left: child_index(build[node].left),
+                right: child_index(build[node].right),
+            })
+            .collect();
+        Ok(FrozenKdTree { nodes, dims })
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the tree holds no points.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the number of dimensions of the stored points (0 for an empty tree).
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    /// Returns an iterator over the stored points, in storage order.
+    pub fn iter(&self) -> impl Iterator<Item = &P> + '_ {
+        self.nodes.iter().map(|node| &node.point)
+    }
+
+    /// Performs a k‑nearest neighbor search.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the nearest points, ordered from nearest to farthest; empty if the target
+    /// has a different number of dimensions than the stored points.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
+    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
+    /// performance.
+    pub fn knn_search<M: DistanceMetric<P>>(&self, target: &P, k: usize) -> Vec<P> {
+        if k == 0 {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.knn_search_into::<M>(target, &mut results);
+        results.into_sorted_vec()
+    }
+
+    /// Returns `SpartError::DimensionMismatch` if `point` does not match the tree's dimension.
+    fn check_query_dims(&self, point: &P) -> Result<(), SpartError> {
+        if !self.is_empty() && point.dims() != self.dims {
+            return Err(SpartError::DimensionMismatch {
+                expected: self.dims,
+                actual: point.dims(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, `SpartError::DimensionMismatch` if `target` does not match the tree's
+    /// dimension, and `SpartError::InvalidCoordinates` if a coordinate of `target` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<P>>(
+        &self,
+        target: &P,
+        k: usize,
+    ) -> Result<Vec<P>, SpartError> {
+        check_knn_query(k, self.is_empty())?;
+        self.check_query_dims(target)?;
+        check_query_point(target)?;
+        Ok(self.knn_search::<M>(target, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<P>>(&self, targets: &[P], k: usize) -> Vec<Vec<P>>
+    where
+        P: Send + Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Performs a k‑nearest neighbor search like `knn_search`, reusing the buffers of `ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The query context, which holds the results until its next use.
+    /// * `target` - The point to search around.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// The nearest points, ordered from nearest to farthest.
+    pub fn knn_search_with<'c, M: DistanceMetric<P>>(
+        &self,
+        ctx: &'c mut QueryContext<P>,
+        target: &P,
+        k: usize,
+    ) -> &'c [P] {
+        ctx.knn(k, |results| self.knn_search_into::<M>(target, results))
+    }
+
+    fn knn_search_into<M: DistanceMetric<P>>(&self, target: &P, results: &mut KnnResults<P>) {
+        if !self.is_empty() && target.dims() == self.dims && !has_nan_coord(target) {
+            self.knn_search_rec::<M>(0, 0, target, results);
+        }
+    }
+
+    fn knn_search_rec<M: DistanceMetric<P>>(
+        &self,
+        index: u32,
+        depth: usize,
+        target: &P,
+        results: &mut KnnResults<P>,
+    ) {
+        let node = &self.nodes[index as usize];
+        let dist_sq = M::distance_sq(target, &node.point);
+        if results.accepts(dist_sq) {
+            results.push(dist_sq, node.point.clone());
+        }
+        let diff = self.axis_diff(target, &node.point, depth);
+        let (first, second) = if diff < 0.0 {
+            (node.left, node.right)
+        } else {
+            (node.right, node.left)
+        };
+        if first != NO_CHILD {
+            self.knn_search_rec::<M>(first, depth + 1, target, results);
+        }
+        if second != NO_CHILD && diff * diff < results.bound() {
+            self.knn_search_rec::<M>(second, depth + 1, target, results);
+        }
+    }
+
+    /// Performs a range search, returning all points within the specified radius of the center.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points within the specified radius.
+    pub fn range_search<M: DistanceMetric<P>>(&self, center: &P, radius: f64) -> Vec<P> {
+        let mut found = Vec::new();
+        self.range_search_into::<M>(center, radius, &mut found);
+        found
+    }
+
+    /// Like `range_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `range_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidRadius` if `radius` is negative or NaN,
+    /// `SpartError::DimensionMismatch` if `center` does not match the tree's dimension, and
+    /// `SpartError::InvalidCoordinates` if a coordinate of `center` is NaN.
+    pub fn try_range_search<M: DistanceMetric<P>>(
+        &self,
+        center: &P,
+        radius: f64,
+    ) -> Result<Vec<P>, SpartError> {
+        check_range_query(radius)?;
+        self.check_query_dims(center)?;
+        check_query_point(center)?;
+        Ok(self.range_search::<M>(center, radius))
+    }
+
+    /// Performs `range_search` for each of several centers, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `centers` - The query points.
+    /// * `radius` - The search radius around each center.
+    ///
+    /// # Returns
+    ///
+    /// The results of `range_search` for each center, in the order of `centers`.
+    #[cfg(feature = "parallel")]
+    pub fn par_range_search<M: DistanceMetric<P>>(&self, centers: &[P], radius: f64) -> Vec<Vec<P>>
+    where
+        P: Send + Sync,
+    {
+        use rayon::prelude::*;
+        centers
+            .par_iter()
+            .map(|center| self.range_search::<M>(center, radius))
+            .collect()
+    }
+
+    /// Performs a range search like `range_search`, reusing the buffers of `ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The query context, which holds the results until its next use.
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// The points within the specified radius.
+    pub fn range_search_with<'c, M: DistanceMetric<P>>(
+        &self,
+        ctx: &'c mut QueryContext<P>,
+        center: &P,
+        radius: f64,
+    ) -> &'c [P] {
+        ctx.collect(|found| self.range_search_into::<M>(center, radius, found))
+    }
+
+    /// Performs a range search like `range_search`, appending the points to `out` instead of
+    /// returning a new vector.
+    ///
+    /// High-frequency callers can reserve room for the expected number of results once and
+    /// reuse the same buffer for every query, clearing it in between; `out` is never cleared
+    /// here.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    /// * `out` - The buffer that receives the points.
+    pub fn range_search_into<M: DistanceMetric<P>>(
+        &self,
+        center: &P,
+        radius: f64,
+        out: &mut Vec<P>,
+    ) {
+        if !self.is_empty() && center.dims() == self.dims && radius >= 0.0 && !has_nan_coord(center)
+        {
+            self.range_search_rec::<M>(0, 0, center, radius, out);
+        }
+    }
+
+    fn range_search_rec<M: DistanceMetric<P>>(
+        &self,
+        index: u32,
+        depth: usize,
+        center: &P,
+        radius: f64,
+        found: &mut Vec<P>,
+    ) {
+        let node = &self.nodes[index as usize];
+        if M::distance_sq(center, &node.point) <= radius * radius {
+            found.push(node.point.clone());
+        }
+        let diff = self.axis_diff(center, &node.point, depth);
+        if node.left != NO_CHILD && diff <= radius {
+            self.range_search_rec::<M>(node.left, depth + 1, center, radius, found);
+        }
+        if node.right != NO_CHILD && -diff <= radius {
+            self.range_search_rec::<M>(node.right, depth + 1, center, radius, found);
+        }
+    }
+
+    /// Returns the signed distance from the splitting plane of a node at `depth` to `query`
+    /// (negative on the left side).
+    fn axis_diff(&self, query: &P, split: &P, depth: usize) -> f64 {
+        let axis = depth % self.dims;
+        let coord = |p: &P| {
+            p.coord(axis)
+                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"))
+        };
+        coord(query) - coord(split)
+    }
+}
+
+/// Builds a balanced tree over `order` by splitting at the median along the axis of `depth`,
+/// returning the index of its root in `build`.
+fn build_balanced<P: KdPoint>(
+    points: &[P],
+    dims: usize,
+    order: &mut [usize],
+    depth: usize,
+    build: &mut Vec<BuildNode>,
+) -> Option<usize> {
+    if order.is_empty() {
+        return None;
+    }
+    let axis = depth % dims;
+    let key = |&i: &usize| {
+        OrderedFloat(
+            points[i]
+                .coord(axis)
+                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid")),
+        )
+    };
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by_key(mid, key);
+    let (left, rest) = order.split_at_mut(mid);
+    let (median, right) = rest.split_at_mut(1);
+    let left = build_balanced(points, dims, left, depth + 1, build);
+    let right = build_balanced(points, dims, right, depth + 1, build);
+    build.push(BuildNode {
+        point: median[0],
+        left,
+        right,
+    });
+    Some(build.len() - 1)
+}
+
+/// Returns the number of levels of the subtree rooted at `node`.
+fn subtree_height(build: &[BuildNode], node: usize) -> usize {
+    let child_height = |child: Option<usize>| child.map_or(0, |c| subtree_height(build, c));
+    1 + child_height(build[node].left).max(child_height(build[node].right))
+}
+
+/// Appends the nodes of the top `height` levels of the subtree rooted at `node` to `out`, in
+/// van Emde Boas order.
+fn lay_out(build: &[BuildNode], node: usize, height: usize, out: &mut Vec<usize>) {
+    if height <= 1 {
+        out.push(node);
+        return;
+    }
+    // Lay out the top half of the levels, then each subtree hanging below it.
+    let top = height / 2;
+    lay_out(build, node, top, out);
+    let mut bottoms = Vec::new();
+    nodes_at_depth(build, node, top, &mut bottoms);
+    for bottom in bottoms {
+        lay_out(build, bottom, height - top, out);
+    }
+}
+
+/// Collects the nodes `depth` levels below `node`, from left to right.
+fn nodes_at_depth(build: &[BuildNode], node: usize, depth: usize, out: &mut Vec<usize>) {
+    if depth == 0 {
+        out.push(node);
+        return;
+    }
+    for child in [build[node].left, build[node].right].into_iter().flatten() {
+        nodes_at_depth(build, child, depth - 1, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{EuclideanDistance, Point2D, Point3D};
+    use crate::linear::LinearIndex;
+    use crate::random::{RandomSource, SplitMix64};
+
+    #[test]
+    fn test_searches_match_linear_scan() {
+        let mut rng = SplitMix64::new(3);
+        // Few distinct coordinates, so that many points tie on the splitting axes.
+        let points: Vec<Point3D<usize>> = (0..500)
+            .map(|i| {
+                let mut coord = || rng.next_below(20) as f64;
+                Point3D::new(coord(), coord(), coord(), Some(i))
+            })
+            .collect();
+        let tree = FrozenKdTree::new(&points).unwrap();
+        let linear: LinearIndex<_> = points.iter().cloned().collect();
+        assert_eq!(tree.len(), points.len());
+
+        for i in 0..50 {
+            let query = Point3D::new(i as f64 * 0.4, 10.0, 20.0 - i as f64 * 0.4, None);
+            let distances = |found: &[Point3D<usize>]| -> Vec<f64> {
+                found
+                    .iter()
+                    .map(|p| EuclideanDistance::distance_sq(&query, p))
+                    .collect()
+            };
+            for k in [1, 4, 9, 40] {
+                assert_eq!(
+                    distances(&tree.knn_search::<EuclideanDistance>(&query, k)),
+                    distances(&linear.knn_search::<EuclideanDistance>(&query, k))
+                );
+            }
+            let mut found = tree.range_search::<EuclideanDistance>(&query, 4.0);
+            let mut expected = linear.range_search::<EuclideanDistance>(&query, 4.0);
+            found.sort_by_key(|p| p.data);
+            expected.sort_by_key(|p| p.data);
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_layout_keeps_bottom_subtrees_contiguous() {
+        // A perfect tree with four levels: the top two levels come first, then each of the
+        // four three-node subtrees below them, with their children right after their root.
+        let points: Vec<Point2D<()>> = (0..15).map(|i| Point2D::new(i as f64, 0.0, None)).collect();
+        let tree = FrozenKdTree::new(&points).unwrap();
+        let root = &tree.nodes[0];
+        assert_eq!((root.left, root.right), (1, 2));
+        for subtree_root in [3, 6, 9, 12] {
+            let node = &tree.nodes[subtree_root as usize];
+            assert_eq!(
+                (node.left, node.right),
+                (subtree_root + 1, subtree_root + 2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_new_rejects_mixed_dimensions() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Flexible(Vec<f64>);
+        impl KdPoint for Flexible {
+            fn dims(&self) -> usize {
+                self.0.len()
+            }
+            fn coord(&self, axis: usize) -> Result<f64, SpartError> {
+                self.0
+                    .get(axis)
+                    .copied()
+                    .ok_or(SpartError::InvalidDimension {
+                        requested: axis,
+                        available: self.0.len(),
+                    })
+            }
+        }
+        let points = [Flexible(vec![0.0, 1.0]), Flexible(vec![0.0, 1.0, 2.0])];
+        assert!(matches!(
+            FrozenKdTree::new(&points),
+            Err(SpartError::DimensionMismatch {
+                expected: 2,
+                actual: 3
+            })
+        ));
+        let empty: FrozenKdTree<Point2D<()>> = FrozenKdTree::new(&[]).unwrap();
+        assert!(
+            empty
+                .knn_search::<EuclideanDistance>(&Point2D::new(0.0, 0.0, None), 1)
+                .is_empty()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_round_trip() {
+        let points: Vec<Point2D<u32>> = (0..50)
+            .map(|i| Point2D::new((i % 7) as f64, (i / 7) as f64, Some(i)))
+            .collect();
+        let tree = FrozenKdTree::new(&points).unwrap();
+        let bytes = bincode::serialize(&tree).unwrap();
+        let restored: FrozenKdTree<Point2D<u32>> = bincode::deserialize(&bytes).unwrap();
+        let target = Point2D::new(2.5, 3.5, None);
+        assert_eq!(
+            restored.knn_search::<EuclideanDistance>(&target, 5),
+            tree.knn_search::<EuclideanDistance>(&target, 5)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_rejects_bad_child_references() {
+        let points: Vec<Point2D<u32>> = (0..50)
+            .map(|i| Point2D::new((i % 7) as f64, (i / 7) as f64, Some(i)))
+            .collect();
+        let tree = FrozenKdTree::new(&points).unwrap();
+        let reason = |nodes: Vec<FrozenNode<Point2D<u32>>>| {
+            let data = FrozenKdTreeData { nodes, dims: 2 };
+            match FrozenKdTree::try_from(data) {
+                Err(SpartError::Persistence { reason }) => reason,
+                other => panic!("expected a persistence error, got {other:?}"),
+            }
+        };
+
+        let mut out_of_bounds = tree.nodes.clone();
+        out_of_bounds[0].left = 999;
+        assert_eq!(reason(out_of_bounds), "node 0 refers to node 999");
+        let mut backwards = tree.nodes.clone();
+        backwards[3].right = 1;
+        assert_eq!(reason(backwards), "node 3 refers to node 1");
+        let mut orphaned = tree.nodes.clone();
+        orphaned[0].left = NO_CHILD;
+        assert_eq!(reason(orphaned), "node 1 is not referenced");
+
+        // The check also runs when deserializing through serde.
+        let mut corrupted = tree.clone();
+        corrupted.nodes[0].left = 999;
+        let bytes = bincode::serialize(&corrupted).unwrap();
+        let error = bincode::deserialize::<FrozenKdTree<Point2D<u32>>>(&bytes).unwrap_err();
+        assert!(error.to_string().contains("node 0 refers to node 999"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_rejects_bad_dimensions() {
+        let points = [Point2D::new(1.0, 2.0, Some(0u32))];
+        let tree = FrozenKdTree::new(&points).unwrap();
+        for dims in [0, 3] {
+            let data = FrozenKdTreeData {
+                nodes: tree.nodes.clone(),
+                dims,
+            };
+            assert!(matches!(
+                FrozenKdTree::try_from(data),
+                Err(SpartError::Persistence { .. })
+            ));
+        }
+        let empty = FrozenKdTreeData::<Point2D<u32>> {
+            nodes: Vec::new(),
+            dims: 0,
+        };
+        assert!(FrozenKdTree::try_from(empty).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,449 @@
+//! ## Geographic Bounding Boxes
+//!
+//! This module provides `GeoBBox`, a bounding box in longitude and latitude (in degrees) for
+//! indexes that store geographic points as `Point2D`s with the longitude as `x` and the latitude
+//! as `y`. Unlike a `Rectangle`, a `GeoBBox` can cross the antimeridian (±180° longitude): its
+//! western edge then lies east of its eastern edge, e.g., `west = 170.0` and `east = -170.0` for
+//! a box 20° wide centered on the antimeridian. The trees search such a box as two windows, one
+//! on each side of the antimeridian (see `Quadtree::range_search_geo_bbox` and
+//! `RTree::range_search_geo_bbox`).
+//!
+//! `GeoBBox::around` builds the smallest box enclosing all points within a distance (in
+//! meters, along the surface of the Earth) of a center point, which can then be refined with
+//! `haversine_distance`. The trees do both steps in `within_radius_sorted_by_bearing`, which
+//! also orders the results by their `initial_bearing` from the center, as needed by radar or
+//! compass-style displays.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geo::GeoBBox;
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::quadtree::Quadtree;
+//!
+//! let world = Rectangle { x: -180.0, y: -90.0, width: 360.0, height: 180.0 };
+//! let mut tree = Quadtree::new(&world, 4).unwrap();
+//! tree.insert(Point2D::new(178.4, -18.1, Some("Suva")));
+//! tree.insert(Point2D::new(-171.8, -13.8, Some("Apia")));
+//! tree.insert(Point2D::new(151.2, -33.9, Some("Sydney")));
+//!
+//! let pacific = GeoBBox::new(170.0, -25.0, -170.0, -10.0).unwrap();
+//! let mut found: Vec<_> = tree
+//!     .range_search_geo_bbox(&pacific)
+//!     .into_iter()
+//!     .map(|p| p.data.unwrap())
+//!     .collect();
+//! found.sort();
+//! assert_eq!(found, vec!["Apia", "Suva"]);
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::errors::SpartError;
+use crate::geometry::{Point2D, Rectangle};
+
+/// The mean radius of the Earth in meters.
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Returns the great-circle distance in meters between two points given as longitude (`x`)
+/// and latitude (`y`) in degrees, using the haversine formula.
+pub fn haversine_distance<T>(a: &Point2D<T>, b: &Point2D<T>) -> f64 {
+    let (lat1, lat2) = (a.y.to_radians(), b.y.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.x - a.x).to_radians();
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
+}
+
+/// Returns the initial bearing, in degrees clockwise from north in `[0, 360)`, of the
+/// great-circle path from `from` to `to` (both given as longitude `x` and latitude `y` in
+/// degrees). The bearing from a point to itself is 0.
+pub fn initial_bearing<T>(from: &Point2D<T>, to: &Point2D<T>) -> f64 {
+    let (lat1, lat2) = (from.y.to_radians(), to.y.to_radians());
+    let dlon = (to.x - from.x).to_radians();
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    let bearing = y.atan2(x).to_degrees().rem_euclid(360.0);
+    // `rem_euclid` can round tiny negative angles up to exactly 360.
+    if bearing >= 360.0 { 0.0 } else { bearing }
+}
+
+/// Keeps the candidates within `radius` meters of `center` and pairs them with their bearings
+/// from `center`, sorted by bearing (and by distance for equal bearings).
+pub(crate) fn sort_by_bearing<T, P: std::borrow::Borrow<Point2D<T>>>(
+    center: &Point2D<T>,
+    radius: f64,
+    candidates: impl IntoIterator<Item = P>,
+) -> Vec<(P, f64)> {
+    let mut hits: Vec<(P, f64, f64)> = candidates
+        .into_iter()
+        .filter_map(|point| {
+            let distance = haversine_distance(center, point.borrow());
+            (distance <= radius).then(|| {
+                let bearing = initial_bearing(center, point.borrow());
+                (point, bearing, distance)
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.2.total_cmp(&b.2)));
+    hits.into_iter()
+        .map(|(point, bearing, _)| (point, bearing))
+        .collect()
+}
+
+/// A bounding box in longitude and latitude, in degrees, that may cross the antimeridian.
+///
+/// The box spans the latitudes from `south` to `north` and the longitudes going east from
+/// `west` to `east`. If `west` is greater than `east`, the box crosses the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GeoBBox {
+    /// The western edge, in degrees of longitude.
+    pub west: f64,
+    /// The southern edge, in degrees of latitude.
+    pub south: f64,
+    /// The eastern edge, in degrees of longitude.
+    pub east: f64,
+    /// The northern edge, in degrees of latitude.
+    pub north: f64,
+}
+
+impl GeoBBox {
+    /// Creates a bounding box from its edges.
+    ///
+    /// # Arguments
+    ///
+    /// * `west` - The western edge, between -180° and 180°.
+    /// * `south` - The southern edge, between -90° and 90°.
+    /// * `east` - The eastern edge, between -180° and 180°. It is less than `west` if the box
+    ///   crosses the antimeridian.
+    /// * `north` - The northern edge, between `south` and 90°.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if an edge is not finite or out of range, or if
+    /// `south` is greater than `north`.
+    pub fn new(west: f64, south: f64, east: f64, north: f64) -> Result<Self, SpartError> {
+        for (name, lon) in [("west", west), ("east", east)] {
+            if !(-180.0..=180.0).contains(&lon) {
+                return Err(SpartError::InvalidCoordinates {
+                    reason: format!("{name} longitude {lon} is not between -180 and 180"),
+                });
+            }
+        }
+        for (name, lat) in [("south", south), ("north", north)] {
+            if !(-90.0..=90.0).contains(&lat) {
+                return Err(SpartError::InvalidCoordinates {
+                    reason: format!("{name} latitude {lat} is not between -90 and 90"),
+                });
+            }
+        }
+        if south > north {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("south latitude {south} is greater than north latitude {north}"),
+            });
+        }
+        Ok(GeoBBox {
+            west,
+            south,
+            east,
+            north,
+        })
+    }
+
+    /// Returns the smallest bounding box that contains every point within `radius` meters of
+    /// `center` (given as longitude `x` and latitude `y` in degrees).
+    ///
+    /// Boxes reaching a pole span all longitudes, and boxes reaching past the antimeridian
+    /// cross it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if the center is out of range or the radius is
+    /// negative or not finite.
+    pub fn around<T>(center: &Point2D<T>, radius: f64) -> Result<Self, SpartError> {
+        GeoBBox::new(center.x, center.y, center.x, center.y)?;
+        if !(radius >= 0.0 && radius.is_finite()) {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("radius {radius} is not a finite non-negative distance"),
+            });
+        }
+        let angle = radius / EARTH_RADIUS_M;
+        let south = center.y - angle.to_degrees();
+        let north = center.y + angle.to_degrees();
+        if south <= -90.0 || north >= 90.0 {
+            return Ok(GeoBBox {
+                west: -180.0,
+                south: south.max(-90.0),
+                east: 180.0,
+                north: north.min(90.0),
+            });
+        }
+        // The meridians touching the circle, where it is widest. This stays below 90° as the
+        // circle does not reach a pole.
+        let half_width = (angle.sin() / center.y.to_radians().cos())
+            .asin()
+            .to_degrees();
+        let wrap = |lon: f64| {
+            if lon > 180.0 {
+                lon - 360.0
+            } else if lon < -180.0 {
+                lon + 360.0
+            } else {
+                lon
+            }
+        };
+        Ok(GeoBBox {
+            west: wrap(center.x - half_width),
+            south,
+            east: wrap(center.x + half_width),
+            north,
+        })
+    }
+
+    /// Returns `true` if the box crosses the antimeridian.
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.west > self.east
+    }
+
+    /// Checks if a point, given as longitude `x` and latitude `y` in degrees, lies inside the
+    /// box (edges included).
+    pub fn contains<T>(&self, point: &Point2D<T>) -> bool {
+        let in_lon = if self.crosses_antimeridian() {
+            point.x >= self.west || point.x <= self.east
+        } else {
+            point.x >= self.west && point.x <= self.east
+        };
+        in_lon && point.y >= self.south && point.y <= self.north
+    }
+
+    /// Returns the box as planar rectangles in longitude and latitude: one rectangle, or two
+    /// (the parts east of `west` and west of `east`) if the box crosses the antimeridian.
+    ///
+    /// The two parts never overlap (the first starts at `west` and the second ends at `east`,
+    /// which lies west of it), so searching each of them finds every point at most once, even
+    /// points on the antimeridian itself.
+    pub fn to_rectangles(&self) -> Vec<Rectangle> {
+        let part = |west: f64, east: f64| Rectangle {
+            x: west,
+            y: self.south,
+            width: east - west,
+            height: self.north - self.south,
+        };
+        if self.crosses_antimeridian() {
+            vec![part(self.west, 180.0), part(-180.0, self.east)]
+        } else {
+            vec![part(self.west, self.east)]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quadtree::Quadtree;
+    use crate::rstar_tree::RStarTree;
+    use crate::rtree::RTree;
+
+    #[test]
+    fn test_bbox_across_antimeridian() {
+        let bbox = GeoBBox::new(170.0, -10.0, -170.0, 10.0).unwrap();
+        assert!(bbox.crosses_antimeridian());
+        assert!(bbox.contains(&Point2D::new(179.0, 0.0, None::<()>)));
+        assert!(bbox.contains(&Point2D::new(-175.0, 5.0, None::<()>)));
+        assert!(!bbox.contains(&Point2D::new(0.0, 0.0, None::<()>)));
+        assert!(!bbox.contains(&Point2D::new(175.0, 20.0, None::<()>)));
+        assert_eq!(bbox.to_rectangles().len(), 2);
+
+        assert!(GeoBBox::new(0.0, 10.0, 1.0, 0.0).is_err());
+        assert!(GeoBBox::new(0.0, 0.0, 181.0, 1.0).is_err());
+        assert!(GeoBBox::new(f64::NAN, 0.0, 1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_around_wraps_and_reaches_poles() {
+        let center = Point2D::new(179.5, 0.0, None::<()>);
+        let bbox = GeoBBox::around(&center, 200_000.0).unwrap();
+        assert!(bbox.crosses_antimeridian());
+        let east = Point2D::new(-179.0, 0.0, None::<()>);
+        assert!(haversine_distance(&center, &east) < 200_000.0);
+        assert!(bbox.contains(&east));
+
+        let near_pole = Point2D::new(10.0, 89.0, None::<()>);
+        let bbox = GeoBBox::around(&near_pole, 200_000.0).unwrap();
+        assert_eq!((bbox.west, bbox.east, bbox.north), (-180.0, 180.0, 90.0));
+
+        assert!(GeoBBox::around(&center, -1.0).is_err());
+    }
+
+    #[test]
+    fn test_trees_search_across_antimeridian() {
+        let world = Rectangle {
+            x: -180.0,
+            y: -90.0,
+            width: 360.0,
+            height: 180.0,
+        };
+        let points: Vec<Point2D<i32>> = (0..360)
+            .map(|i| Point2D::new(-179.5 + i as f64, (i % 7) as f64, Some(i)))
+            .collect();
+        let mut quadtree = Quadtree::new(&world, 4).unwrap();
+        let mut rtree = RTree::new(4).unwrap();
+        let mut rstar = RStarTree::new(4).unwrap();
+        for point in &points {
+            quadtree.insert(point.clone());
+            rtree.insert(point.clone());
+            rstar.insert(point.clone());
+        }
+
+        let bbox = GeoBBox::new(175.0, 0.0, -175.0, 3.0).unwrap();
+        let mut expected: Vec<i32> = points
+            .iter()
+            .filter(|p| bbox.contains(*p))
+            .map(|p| p.data.unwrap())
+            .collect();
+        expected.sort();
+        assert!(!expected.is_empty());
+
+        let ids = |found: Vec<&Point2D<i32>>| {
+            let mut ids: Vec<i32> = found.into_iter().map(|p| p.data.unwrap()).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            ids(quadtree.range_search_geo_bbox(&bbox).iter().collect()),
+            expected
+        );
+        assert_eq!(ids(rtree.range_search_geo_bbox(&bbox)), expected);
+        assert_eq!(ids(rstar.range_search_geo_bbox(&bbox)), expected);
+    }
+
+    #[test]
+    fn test_antimeridian_searches_return_each_point_once() {
+        use crate::projection::ProjectedQuadtree;
+
+        let world = Rectangle {
+            x: -180.0,
+            y: -90.0,
+            width: 360.0,
+            height: 180.0,
+        };
+        let mut quadtree = Quadtree::new(&world, 2).unwrap();
+        let mut rtree = RTree::new(4).unwrap();
+        let mut projected = ProjectedQuadtree::new(crate::projection::WebMercator, 2).unwrap();
+        // Points on both sides of the seam, and on the seam itself at -180 and 180.
+        let lons = [-180.0, -179.0, -170.0, 0.0, 170.0, 179.0, 180.0];
+        for (i, &lon) in lons.iter().enumerate() {
+            for lat in [-10.0, 0.0, 10.0] {
+                let point = Point2D::new(lon, lat, Some(i * 3 + (lat as i32 + 10) as usize / 10));
+                quadtree.insert(point.clone());
+                rtree.insert(point.clone());
+                projected.insert(point);
+            }
+        }
+
+        for (west, east) in [
+            (180.0, -180.0),
+            (170.0, -170.0),
+            (179.0, 180.0),
+            (-180.0, 180.0),
+        ] {
+            let bbox = GeoBBox::new(west, -10.0, east, 10.0).unwrap();
+            let expected = lons
+                .iter()
+                .filter(|&&lon| bbox.contains(&Point2D::new(lon, 0.0, None::<()>)))
+                .count()
+                * 3;
+            let ids = |mut ids: Vec<usize>| {
+                ids.sort();
+                ids.dedup();
+                ids.len()
+            };
+            let found = quadtree.range_search_geo_bbox(&bbox);
+            assert_eq!(found.len(), expected, "{west} to {east}");
+            assert_eq!(
+                ids(found.iter().map(|p| p.data.unwrap()).collect()),
+                expected
+            );
+            let found = rtree.range_search_geo_bbox(&bbox);
+            assert_eq!(found.len(), expected, "{west} to {east}");
+            assert_eq!(
+                ids(found.iter().map(|p| p.data.unwrap()).collect()),
+                expected
+            );
+            let found = projected.range_search_geo_bbox(&bbox);
+            assert_eq!(found.len(), expected, "{west} to {east}");
+            assert_eq!(
+                ids(found.iter().map(|p| p.data.unwrap()).collect()),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_within_radius_sorted_by_bearing() {
+        let world = Rectangle {
+            x: -180.0,
+            y: -90.0,
+            width: 360.0,
+            height: 180.0,
+        };
+        let center = Point2D::new(179.9, 0.0, None);
+        // Roughly 55 km north, east (across the antimeridian), south, and west of the center,
+        // plus one point far away.
+        let points = [
+            Point2D::new(-179.6, 0.0, Some("east")),
+            Point2D::new(179.9, 0.5, Some("north")),
+            Point2D::new(179.4, 0.0, Some("west")),
+            Point2D::new(179.9, -0.5, Some("south")),
+            Point2D::new(170.0, 0.0, Some("far")),
+        ];
+        let mut quadtree = Quadtree::new(&world, 2).unwrap();
+        let mut rtree = RTree::new(4).unwrap();
+        let mut rstar = RStarTree::new(4).unwrap();
+        for point in points {
+            quadtree.insert(point.clone());
+            rtree.insert(point.clone());
+            rstar.insert(point);
+        }
+
+        let found = quadtree
+            .within_radius_sorted_by_bearing(&center, 60_000.0)
+            .unwrap();
+        let names: Vec<_> = found.iter().map(|(p, _)| p.data.unwrap()).collect();
+        assert_eq!(names, vec!["north", "east", "south", "west"]);
+        for ((_, bearing), expected) in found.iter().zip([0.0, 90.0, 180.0, 270.0]) {
+            assert!((bearing - expected).abs() < 0.01);
+        }
+
+        let names = |found: Vec<(&Point2D<&'static str>, f64)>| {
+            found
+                .into_iter()
+                .map(|(p, _)| p.data.unwrap())
+                .collect::<Vec<_>>()
+        };
+        let expected = vec!["north", "east", "south", "west"];
+        assert_eq!(
+            names(
+                rtree
+                    .within_radius_sorted_by_bearing(&center, 60_000.0)
+                    .unwrap()
+            ),
+            expected
+        );
+        assert_eq!(
+            names(
+                rstar
+                    .within_radius_sorted_by_bearing(&center, 60_000.0)
+                    .unwrap()
+            ),
+            expected
+        );
+        assert!(
+            quadtree
+                .within_radius_sorted_by_bearing(&center, -1.0)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,517 @@
+//! ## Geofencing
+//!
+//! This module provides `Geofences`, a set of named regions (polygons and circles) indexed by
+//! the bounding rectangles in an R-tree, and the tools to follow points moving through them:
+//!
+//! - `Geofences::fences_containing` finds the fences a point is inside of.
+//! - `FenceTracker` remembers which fences a moving object is inside of and reports the
+//!   fences it enters and exits at each new position.
+//! - `Geofences::evaluate` does the same for a stream of positions of one object.
+//!
+//! Fence boundaries count as inside. Coordinates are planar; for longitude and latitude data,
+//! project the fences and points first (see the `projection` module).
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geofence::{Fence, FenceEvent, Geofences};
+//! use spart::geometry::{Point2D, Polygon};
+//!
+//! let mut fences = Geofences::new();
+//! let depot = Polygon::new(vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]).unwrap();
+//! fences.add("depot", Fence::Polygon(depot));
+//! fences.add("station", Fence::circle(20.0, 5.0, 3.0).unwrap());
+//!
+//! let route = [(-5.0, 5.0), (5.0, 5.0), (19.0, 5.0)].map(|(x, y)| Point2D::new(x, y, None::<()>));
+//! let events: Vec<_> = fences.evaluate(route).collect();
+//! assert!(matches!(&events[0], FenceEvent::Entered { fence: "depot", .. }));
+//! assert!(matches!(&events[1], FenceEvent::Exited { fence: "depot", .. }));
+//! assert!(matches!(&events[2], FenceEvent::Entered { fence: "station", .. }));
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{Bounded, Point2D, Polygon, Rectangle};
+use crate::rtree::RTree;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The maximum number of entries per node of the R-tree indexing the fences.
+const FENCE_NODE_CAPACITY: usize = 8;
+
+/// A region that points can be inside of.
+#[derive(Debug, Clone)]
+pub enum Fence {
+    /// A polygon (see `Polygon`).
+    Polygon(Polygon),
+    /// A circle (a disk, including its boundary).
+    Circle {
+        /// The x-coordinate of the center.
+        x: f64,
+        /// The y-coordinate of the center.
+        y: f64,
+        /// The radius.
+        radius: f64,
+    },
+}
+
+impl Fence {
+    /// Creates a circular fence.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x-coordinate of the center.
+    /// * `y` - The y-coordinate of the center.
+    /// * `radius` - The radius.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidRadius` if `radius` is negative or not finite, and
+    /// `SpartError::InvalidCoordinates` if the center is not finite.
+    pub fn circle(x: f64, y: f64, radius: f64) -> Result<Self, SpartError> {
+        if !(radius.is_finite() && radius >= 0.0) {
+            return Err(SpartError::InvalidRadius { radius });
+        }
+        if !(x.is_finite() && y.is_finite()) {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("circle center ({x}, {y}) is not finite"),
+            });
+        }
+        Ok(Fence::Circle { x, y, radius })
+    }
+
+    /// Checks if a point lies inside the fence or on its boundary.
+    pub fn contains<T>(&self, point: &Point2D<T>) -> bool {
+        match self {
+            Fence::Polygon(polygon) => polygon.contains(point),
+            Fence::Circle { x, y, radius } => {
+                let (dx, dy) = (point.x - x, point.y - y);
+                dx * dx + dy * dy <= radius * radius
+            }
+        }
+    }
+
+    /// Returns the minimum bounding rectangle of the fence.
+    pub fn bbox(&self) -> Rectangle {
+        match self {
+            Fence::Polygon(polygon) => polygon.bbox().clone(),
+            Fence::Circle { x, y, radius } => {
+                Rectangle::from_corners([x - radius, y - radius], [x + radius, y + radius])
+            }
+        }
+    }
+}
+
+impl From<Polygon> for Fence {
+    fn from(polygon: Polygon) -> Self {
+        Fence::Polygon(polygon)
+    }
+}
+
+/// The entry of a fence in the R-tree: its key and bounding rectangle.
+#[derive(Debug, Clone)]
+struct FenceEntry<K> {
+    key: K,
+    bbox: Rectangle,
+}
+
+impl<K: PartialEq> PartialEq for FenceEntry<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: std::fmt::Debug + Clone> Bounded for FenceEntry<K> {
+    type B = Rectangle;
+    fn mbr(&self) -> Rectangle {
+        self.bbox.clone()
+    }
+}
+
+/// A fence entered or exited by a moving object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenceEvent<K, T> {
+    /// The object moved into the fence.
+    Entered {
+        /// The key of the fence.
+        fence: K,
+        /// The first position inside the fence.
+        point: Point2D<T>,
+    },
+    /// The object moved out of the fence (or the fence was removed).
+    Exited {
+        /// The key of the fence.
+        fence: K,
+        /// The first position outside the fence.
+        point: Point2D<T>,
+    },
+}
+
+/// A set of fences identified by keys of type `K`.
+#[derive(Debug, Clone)]
+pub struct Geofences<K: Clone + Eq + Hash + std::fmt::Debug> {
+    fences: HashMap<K, Fence>,
+    tree: RTree<FenceEntry<K>>,
+}
+
+impl<K: Clone + Eq + Hash + std::fmt::Debug> Default for Geofences<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone + Eq + Hash + std::fmt::Debug> Geofences<K> {
+    /// Creates an empty set of fences.
+    pub fn new() -> Self {
+        Geofences {
+            fences: HashMap::new(),
+            tree: RTree::new(FENCE_NODE_CAPACITY)
+                .unwrap_or_else(|_| unreachable!("the node capacity is valid")),
+        }
+    }
+
+    /// Returns the number of fences.
+    pub fn len(&self) -> usize {
+        self.fences.len()
+    }
+
+    /// Returns `true` if there are no fences.
+    pub fn is_empty(&self) -> bool {
+        self.fences.is_empty()
+    }
+
+    /// Returns the fence with the given key.
+    pub fn get(&self, key: &K) -> Option<&Fence> {
+        self.fences.get(key)
+    }
+
+    /// Adds a fence.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key identifying the fence.
+    /// * `fence` - The fence.
+    ///
+    /// # Returns
+    ///
+    /// The fence previously stored under `key`, which the new one replaces.
+    pub fn add(&mut self, key: K, fence: impl Into<Fence>) -> Option<Fence> {
+        let fence = fence.into();
+        let previous = self.remove(&key);
+        self.tree.insert(FenceEntry {
+            key: key.clone(),
+            bbox: fence.bbox(),
+        });
+        self.fences.insert(key, fence);
+        previous
+    }
+
+    /// Removes a fence.
+    ///
+    /// Trackers only report the exit from a removed fence at their next update.
+    ///
+    /// # Returns
+    ///
+    /// The removed fence, or `None` if there is no fence with the given key.
+    pub fn remove(&mut self, key: &K) -> Option<Fence> {
+        let fence = self.fences.remove(key)?;
+        self.tree.delete(&FenceEntry {
+            key: key.clone(),
+            bbox: fence.bbox(),
+        });
+        Some(fence)
+    }
+
+    /// Finds the fences a point is inside of (boundary included).
+    ///
+    /// The R-tree is searched with the point, and the fences whose bounding rectangle holds it
+    /// are then tested against the fence itself.
+    ///
+    /// # Returns
+    ///
+    /// The keys of the fences containing the point, in no particular order.
+    pub fn fences_containing<T>(&self, point: &Point2D<T>) -> Vec<&K> {
+        let query = Rectangle {
+            x: point.x,
+            y: point.y,
+            width: 0.0,
+            height: 0.0,
+        };
+        self.tree
+            .range_search_bbox(&query)
+            .into_iter()
+            .filter_map(|entry| {
+                let (key, fence) = self.fences.get_key_value(&entry.key)?;
+                fence.contains(point).then_some(key)
+            })
+            .collect()
+    }
+
+    /// Follows one object through a stream of its positions, yielding the fences it enters
+    /// and exits.
+    ///
+    /// The object starts outside of every fence, so the fences containing the first position
+    /// are reported as entered. At each position, exits are reported before entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The successive positions of the object.
+    pub fn evaluate<'a, T: Clone + 'a>(
+        &'a self,
+        points: impl IntoIterator<Item = Point2D<T>> + 'a,
+    ) -> impl Iterator<Item = FenceEvent<K, T>> + 'a {
+        let mut tracker = FenceTracker::new();
+        points
+            .into_iter()
+            .flat_map(move |point| tracker.update(self, &point))
+    }
+}
+
+/// The fences a moving object is inside of, updated with each of its new positions.
+///
+/// Keep one tracker per object to follow several objects through the same fences.
+#[derive(Debug, Clone)]
+pub struct FenceTracker<K> {
+    /// The fences the object is inside of, in the order it entered them.
+    inside: Vec<K>,
+}
+
+impl<K: Clone + Eq + Hash + std::fmt::Debug> Default for FenceTracker<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone + Eq + Hash + std::fmt::Debug> FenceTracker<K> {
+    /// Creates a tracker for an object outside of every fence.
+    pub fn new() -> Self {
+        FenceTracker { inside: Vec::new() }
+    }
+
+    /// Returns the keys of the fences the object is inside of, in the order it entered them.
+    pub fn inside(&self) -> &[K] {
+        &self.inside
+    }
+
+    /// Moves the object to `point`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fences` - The fences to check.
+    /// * `point` - The new position of the object.
+    ///
+    /// # Returns
+    ///
+    /// The fences exited (including removed fences the object was inside of), followed by the
+    /// fences entered.
+    pub fn update<T: Clone>(
+        &mut self,
+        fences: &Geofences<K>,
+        point: &Point2D<T>,
+    ) -> Vec<FenceEvent<K, T>> {
+        let containing = fences.fences_containing(point);
+        let mut events = Vec::new();
+        self.inside.retain(|key| {
+            let still_inside = containing.contains(&key);
+            if !still_inside {
+                events.push(FenceEvent::Exited {
+                    fence: key.clone(),
+                    point: point.clone(),
+                });
+            }
+            still_inside
+        });
+        for key in containing {
+            if !self.inside.contains(key) {
+                self.inside.push(key.clone());
+                events.push(FenceEvent::Entered {
+                    fence: key.clone(),
+                    point: point.clone(),
+                });
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{RandomSource, SplitMix64};
+
+    /// 40 random circles and triangles in a 100 by 100 square, keyed `0..40`.
+    fn random_fences(rng: &mut SplitMix64) -> Geofences<usize> {
+        let mut fences = Geofences::new();
+        for i in 0..40 {
+            let (x, y) = (rng.next_f64() * 100.0, rng.next_f64() * 100.0);
+            let fence = if i % 2 == 0 {
+                Fence::circle(x, y, 5.0 + rng.next_f64() * 10.0).unwrap()
+            } else {
+                Fence::Polygon(Polygon::new(vec![[x, y], [x + 15.0, y], [x, y + 15.0]]).unwrap())
+            };
+            assert!(fences.add(i, fence).is_none());
+        }
+        fences
+    }
+
+    fn containing(fences: &Geofences<usize>, point: &Point2D<usize>) -> Vec<usize> {
+        let mut found: Vec<_> = fences
+            .fences_containing(point)
+            .into_iter()
+            .copied()
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_circle_rejects_invalid_input() {
+        assert!(matches!(
+            Fence::circle(0.0, 0.0, -1.0),
+            Err(SpartError::InvalidRadius { .. })
+        ));
+        assert!(matches!(
+            Fence::circle(0.0, 0.0, f64::NAN),
+            Err(SpartError::InvalidRadius { .. })
+        ));
+        assert!(matches!(
+            Fence::circle(f64::INFINITY, 0.0, 1.0),
+            Err(SpartError::InvalidCoordinates { .. })
+        ));
+    }
+
+    #[test]
+    fn test_empty_fences_contain_nothing() {
+        let mut fences: Geofences<usize> = Geofences::new();
+        let point = Point2D::new(1.0, 1.0, Some(0));
+        assert!(fences.is_empty());
+        assert!(fences.fences_containing(&point).is_empty());
+        assert!(fences.remove(&0).is_none());
+        assert!(FenceTracker::new().update(&fences, &point).is_empty());
+    }
+
+    #[test]
+    fn test_add_replaces_fence_with_same_key() {
+        let mut fences = Geofences::new();
+        assert!(
+            fences
+                .add(0, Fence::circle(0.0, 0.0, 1.0).unwrap())
+                .is_none()
+        );
+        assert!(
+            fences
+                .add(0, Fence::circle(50.0, 50.0, 1.0).unwrap())
+                .is_some()
+        );
+        assert_eq!(fences.len(), 1);
+        assert!(
+            fences
+                .fences_containing(&Point2D::new(0.0, 0.0, Some(0)))
+                .is_empty()
+        );
+        assert_eq!(containing(&fences, &Point2D::new(50.0, 50.0, Some(0))), [0]);
+    }
+
+    #[test]
+    fn test_fence_boundaries_are_inside() {
+        let mut fences = Geofences::new();
+        fences.add(0, Fence::circle(0.0, 0.0, 5.0).unwrap());
+        let triangle = Polygon::new(vec![[10.0, 0.0], [20.0, 0.0], [10.0, 10.0]]).unwrap();
+        fences.add(1, triangle);
+        assert_eq!(containing(&fences, &Point2D::new(5.0, 0.0, Some(0))), [0]);
+        assert_eq!(containing(&fences, &Point2D::new(0.0, -5.0, Some(0))), [0]);
+        assert_eq!(containing(&fences, &Point2D::new(20.0, 0.0, Some(0))), [1]);
+        assert_eq!(containing(&fences, &Point2D::new(15.0, 5.0, Some(0))), [1]);
+        assert!(containing(&fences, &Point2D::new(5.1, 0.0, Some(0))).is_empty());
+    }
+
+    #[test]
+    fn test_fences_containing_matches_scan() {
+        let mut rng = SplitMix64::new(5);
+        let fences = random_fences(&mut rng);
+        for step in 0..200 {
+            let point = Point2D::new(rng.next_f64() * 100.0, rng.next_f64() * 100.0, Some(step));
+            let expected: Vec<_> = (0..40)
+                .filter(|key| fences.get(key).is_some_and(|f| f.contains(&point)))
+                .collect();
+            assert_eq!(containing(&fences, &point), expected);
+        }
+    }
+
+    #[test]
+    fn test_tracker_follows_fence_membership() {
+        let mut rng = SplitMix64::new(5);
+        let mut fences = random_fences(&mut rng);
+        let mut tracker = FenceTracker::new();
+        let mut inside = [false; 40];
+        for step in 0..200 {
+            if step == 100 {
+                assert!(fences.remove(&0).is_some());
+            }
+            let point = Point2D::new(rng.next_f64() * 100.0, rng.next_f64() * 100.0, Some(step));
+            for event in tracker.update(&fences, &point) {
+                match event {
+                    FenceEvent::Entered { fence, .. } => {
+                        assert!(!inside[fence]);
+                        inside[fence] = true;
+                    }
+                    FenceEvent::Exited { fence, .. } => {
+                        assert!(inside[fence]);
+                        inside[fence] = false;
+                    }
+                }
+            }
+            let expected = containing(&fences, &point);
+            let mut tracked = tracker.inside().to_vec();
+            tracked.sort();
+            assert_eq!(tracked, expected);
+            assert!((0..40).all(|key| inside[key] == expected.contains(&key)));
+        }
+    }
+
+    #[test]
+    fn test_removed_fence_is_exited() {
+        let mut fences = Geofences::new();
+        fences.add("home", Fence::circle(0.0, 0.0, 5.0).unwrap());
+        let mut tracker = FenceTracker::new();
+        let point = Point2D::new(1.0, 1.0, Some(0));
+        assert_eq!(tracker.update(&fences, &point).len(), 1);
+        fences.remove(&"home");
+        assert_eq!(
+            tracker.update(&fences, &point),
+            [FenceEvent::Exited {
+                fence: "home",
+                point: point.clone()
+            }]
+        );
+        assert!(tracker.inside().is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_reports_exits_before_entries() {
+        let mut fences = Geofences::new();
+        fences.add(0, Fence::circle(0.0, 0.0, 1.0).unwrap());
+        fences.add(1, Fence::circle(3.0, 0.0, 1.0).unwrap());
+        let path = [
+            Point2D::new(0.0, 0.0, Some(0)),
+            Point2D::new(3.0, 0.0, Some(1)),
+        ];
+        let events: Vec<_> = fences.evaluate(path.clone()).collect();
+        assert_eq!(
+            events,
+            [
+                FenceEvent::Entered {
+                    fence: 0,
+                    point: path[0].clone()
+                },
+                FenceEvent::Exited {
+                    fence: 0,
+                    point: path[1].clone()
+                },
+                FenceEvent::Entered {
+                    fence: 1,
+                    point: path[1].clone()
+                },
+            ]
+        );
+    }
+}
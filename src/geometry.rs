@@ -4,6 +4,9 @@
 //! It defines types such as `Point2D`, `Rectangle`, `Point3D`, and `Cube` along with their associated
 //! operations. These types form the basis for indexing and query algorithms in Spart.
 //!
+//! For more dimensions, `PointND` and `Aabb` (a box stored as its two corners) work with any
+//! number of dimensions given as a const generic parameter.
+//!
 //! In addition to the basic types, the module defines several traits for operations such as
 //! bounding volume calculations and minimum distance computations.
 
@@ -11,10 +14,11 @@ use ordered_float::OrderedFloat;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use tracing::debug;
 
 // Import custom errors from the exceptions module.
 use crate::errors::SpartError;
+use crate::logging::hot_trace;
+use crate::predicates::{covering_extent, orient2d};
 
 /// Represents a 2D point with an optional payload.
 ///
@@ -46,6 +50,34 @@ impl<T: PartialEq> PartialEq for Point2D<T> {
 
 impl<T: Eq> Eq for Point2D<T> {}
 
+impl<T: PartialEq> Point2D<T> {
+    /// Returns `true` if the points carry equal data and their coordinates differ by at most
+    /// `tolerance` along each axis.
+    ///
+    /// With a tolerance of zero this is the same as `==`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other point.
+    /// * `tolerance` - The maximum allowed difference per coordinate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// let a = Point2D::new(0.1 + 0.2, 1.0, Some("a"));
+    /// let b = Point2D::new(0.3, 1.0, Some("a"));
+    /// assert!(a != b);
+    /// assert!(a.approx_eq(&b, 1e-9));
+    /// ```
+    pub fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self == other
+            || ((self.x - other.x).abs() <= tolerance
+                && (self.y - other.y).abs() <= tolerance
+                && self.data == other.data)
+    }
+}
+
 impl<T: PartialOrd> PartialOrd for Point2D<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (OrderedFloat(self.x), OrderedFloat(self.y))
@@ -106,7 +138,7 @@ impl<T> Point2D<T> {
     /// ```
     pub fn new(x: f64, y: f64, data: Option<T>) -> Self {
         let pt = Self { x, y, data };
-        debug!("Point2D::new() -> x: {}, y: {}", pt.x, pt.y);
+        hot_trace!("Point2D::new()", x = pt.x, y = pt.y);
         pt
     }
 
@@ -126,10 +158,7 @@ impl<T> Point2D<T> {
     /// ```
     pub fn distance_sq(&self, other: &Point2D<T>) -> f64 {
         let dist = (self.x - other.x).powi(2) + (self.y - other.y).powi(2);
-        debug!(
-            "Point2D::distance_sq(): self: (x: {}, y: {}), other: (x: {}, y: {}), result: {}",
-            self.x, self.y, other.x, other.y, dist
-        );
+        hot_trace!("Point2D::distance_sq()", result = dist);
         dist
     }
 }
@@ -149,6 +178,44 @@ pub struct Rectangle {
 }
 
 impl Rectangle {
+    /// Creates the smallest rectangle spanning from the corner `min` to the corner `max`.
+    ///
+    /// The width and height are rounded up (see `predicates::covering_extent`) so that `max`
+    /// lies inside the rectangle under the comparisons made by `contains` and `intersects`.
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The corner with the smallest coordinates, as `[x, y]`.
+    /// * `max` - The corner with the largest coordinates, as `[x, y]`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spart::geometry::Rectangle;
+    /// let rect = Rectangle::from_corners([1.0, 2.0], [4.0, 6.0]);
+    /// assert_eq!((rect.x, rect.y, rect.width, rect.height), (1.0, 2.0, 3.0, 4.0));
+    /// assert_eq!(rect.min_corner(), [1.0, 2.0]);
+    /// assert_eq!(rect.max_corner(), [4.0, 6.0]);
+    /// ```
+    pub fn from_corners(min: [f64; 2], max: [f64; 2]) -> Rectangle {
+        Rectangle {
+            x: min[0],
+            y: min[1],
+            width: covering_extent(min[0], max[0]),
+            height: covering_extent(min[1], max[1]),
+        }
+    }
+
+    /// Returns the corner with the smallest coordinates, as `[x, y]`.
+    pub fn min_corner(&self) -> [f64; 2] {
+        [self.x, self.y]
+    }
+
+    /// Returns the corner with the largest coordinates, as `[x, y]`.
+    pub fn max_corner(&self) -> [f64; 2] {
+        [self.x + self.width, self.y + self.height]
+    }
+
     /// Determines if the rectangle contains the given point.
     ///
     /// # Arguments
@@ -168,13 +235,37 @@ impl Rectangle {
             && point.x <= self.x + self.width
             && point.y >= self.y
             && point.y <= self.y + self.height;
-        debug!(
-            "Rectangle::contains(): self: (x: {}, y: {}, w: {}, h: {}), point: (x: {}, y: {}), result: {}",
-            self.x, self.y, self.width, self.height, point.x, point.y, res
+        hot_trace!(
+            "Rectangle::contains()",
+            rect = self,
+            point = (point.x, point.y),
+            result = res
         );
         res
     }
 
+    /// Determines if the rectangle fully contains another rectangle.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The rectangle to test.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spart::geometry::Rectangle;
+    /// let outer = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+    /// let inner = Rectangle { x: 2.0, y: 2.0, width: 3.0, height: 3.0 };
+    /// assert!(outer.contains_rect(&inner));
+    /// assert!(!inner.contains_rect(&outer));
+    /// ```
+    pub fn contains_rect(&self, other: &Rectangle) -> bool {
+        other.x >= self.x
+            && other.x + other.width <= self.x + self.width
+            && other.y >= self.y
+            && other.y + other.height <= self.y + self.height
+    }
+
     /// Determines whether this rectangle intersects with another.
     ///
     /// # Arguments
@@ -190,21 +281,21 @@ impl Rectangle {
     /// assert!(a.intersects(&b));
     /// ```
     pub fn intersects(&self, other: &Rectangle) -> bool {
+        #[cfg(feature = "packed_mbr")]
+        let res = packed::intersects(
+            (self.min_corner(), self.max_corner()),
+            (other.min_corner(), other.max_corner()),
+        );
+        #[cfg(not(feature = "packed_mbr"))]
         let res = !(other.x > self.x + self.width
             || other.x + other.width < self.x
             || other.y > self.y + self.height
             || other.y + other.height < self.y);
-        debug!(
-            "Rectangle::intersects(): self: (x: {}, y: {}, w: {}, h: {}), other: (x: {}, y: {}, w: {}, h: {}), result: {}",
-            self.x,
-            self.y,
-            self.width,
-            self.height,
-            other.x,
-            other.y,
-            other.width,
-            other.height,
-            res
+        hot_trace!(
+            "Rectangle::intersects()",
+            rect = self,
+            other = other,
+            result = res
         );
         res
     }
@@ -220,10 +311,7 @@ impl Rectangle {
     /// ```
     pub fn area(&self) -> f64 {
         let area = self.width * self.height;
-        debug!(
-            "Rectangle::area(): (w: {}, h: {}) -> {}",
-            self.width, self.height, area
-        );
+        hot_trace!("Rectangle::area()", result = area);
         area
     }
 
@@ -245,40 +333,14 @@ impl Rectangle {
     /// assert_eq!(union_rect.x, 0.0);
     /// ```
     pub fn union(&self, other: &Rectangle) -> Rectangle {
-        let x1 = self.x.min(other.x);
-        let y1 = self.y.min(other.y);
-        let x2 = (self.x + self.width).max(other.x + other.width);
-        let y2 = (self.y + self.height).max(other.y + other.height);
-
-        // Add small epsilon to width/height to account for floating-point precision errors
-        // This guarantees that corner points are always contained in the union
-        let eps = f64::EPSILON * 4.0 * (x2.abs() + x1.abs()).max(1.0);
-        let width = (x2 - x1) + eps;
-
-        let eps_y = f64::EPSILON * 4.0 * (y2.abs() + y1.abs()).max(1.0);
-        let height = (y2 - y1) + eps_y;
-
-        let union_rect = Rectangle {
-            x: x1,
-            y: y1,
-            width,
-            height,
-        };
-        debug!(
-            "Rectangle::union(): self: (x: {}, y: {}, w: {}, h: {}), other: (x: {}, y: {}, w: {}, h: {}), result: (x: {}, y: {}, w: {}, h: {})",
-            self.x,
-            self.y,
-            self.width,
-            self.height,
-            other.x,
-            other.y,
-            other.width,
-            other.height,
-            union_rect.x,
-            union_rect.y,
-            union_rect.width,
-            union_rect.height
+        let (a, b) = (self.max_corner(), other.max_corner());
+        // `from_corners` rounds the extents up so that the far edges, as computed by `contains`
+        // and `intersects`, never fall short of the edges of the inputs.
+        let union_rect = Rectangle::from_corners(
+            [self.x.min(other.x), self.y.min(other.y)],
+            [a[0].max(b[0]), a[1].max(b[1])],
         );
+        hot_trace!("Rectangle::union()", result = union_rect);
         union_rect
     }
 
@@ -304,10 +366,7 @@ impl Rectangle {
         let self_area = self.area();
         let union_area = union_rect.area();
         let extra = union_area - self_area;
-        debug!(
-            "Rectangle::enlargement(): self area: {}, union area: {}, enlargement: {}",
-            self_area, union_area, extra
-        );
+        hot_trace!("Rectangle::enlargement()", result = extra);
         extra
     }
 }
@@ -344,6 +403,35 @@ impl<T: PartialEq> PartialEq for Point3D<T> {
 
 impl<T: Eq> Eq for Point3D<T> {}
 
+impl<T: PartialEq> Point3D<T> {
+    /// Returns `true` if the points carry equal data and their coordinates differ by at most
+    /// `tolerance` along each axis.
+    ///
+    /// With a tolerance of zero this is the same as `==`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other point.
+    /// * `tolerance` - The maximum allowed difference per coordinate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spart::geometry::Point3D;
+    /// let a: Point3D<()> = Point3D::new(0.1 + 0.2, 1.0, 2.0, None);
+    /// let b: Point3D<()> = Point3D::new(0.3, 1.0, 2.0, None);
+    /// assert!(a.approx_eq(&b, 1e-9));
+    /// assert!(!a.approx_eq(&b, 0.0));
+    /// ```
+    pub fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self == other
+            || ((self.x - other.x).abs() <= tolerance
+                && (self.y - other.y).abs() <= tolerance
+                && (self.z - other.z).abs() <= tolerance
+                && self.data == other.data)
+    }
+}
+
 impl<T: PartialOrd> PartialOrd for Point3D<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (
@@ -398,7 +486,7 @@ impl<T> Point3D<T> {
     /// ```
     pub fn new(x: f64, y: f64, z: f64, data: Option<T>) -> Self {
         let pt = Self { x, y, z, data };
-        debug!("Point3D::new() -> x: {}, y: {}, z: {}", pt.x, pt.y, pt.z);
+        hot_trace!("Point3D::new()", x = pt.x, y = pt.y, z = pt.z);
         pt
     }
 
@@ -419,10 +507,7 @@ impl<T> Point3D<T> {
     pub fn distance_sq(&self, other: &Point3D<T>) -> f64 {
         let dist =
             (self.x - other.x).powi(2) + (self.y - other.y).powi(2) + (self.z - other.z).powi(2);
-        debug!(
-            "Point3D::distance_sq(): self: (x: {}, y: {}, z: {}), other: (x: {}, y: {}, z: {}), result: {}",
-            self.x, self.y, self.z, other.x, other.y, other.z, dist
-        );
+        hot_trace!("Point3D::distance_sq()", result = dist);
         dist
     }
 }
@@ -446,6 +531,49 @@ pub struct Cube {
 }
 
 impl Cube {
+    /// Creates the smallest cube (box) spanning from the corner `min` to the corner `max`.
+    ///
+    /// The extents are rounded up (see `predicates::covering_extent`) so that `max` lies
+    /// inside the cube under the comparisons made by `contains` and `intersects`.
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The corner with the smallest coordinates, as `[x, y, z]`.
+    /// * `max` - The corner with the largest coordinates, as `[x, y, z]`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spart::geometry::Cube;
+    /// let cube = Cube::from_corners([0.0, 0.0, 1.0], [2.0, 3.0, 5.0]);
+    /// assert_eq!((cube.width, cube.height, cube.depth), (2.0, 3.0, 4.0));
+    /// assert_eq!(cube.max_corner(), [2.0, 3.0, 5.0]);
+    /// ```
+    pub fn from_corners(min: [f64; 3], max: [f64; 3]) -> Cube {
+        Cube {
+            x: min[0],
+            y: min[1],
+            z: min[2],
+            width: covering_extent(min[0], max[0]),
+            height: covering_extent(min[1], max[1]),
+            depth: covering_extent(min[2], max[2]),
+        }
+    }
+
+    /// Returns the corner with the smallest coordinates, as `[x, y, z]`.
+    pub fn min_corner(&self) -> [f64; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    /// Returns the corner with the largest coordinates, as `[x, y, z]`.
+    pub fn max_corner(&self) -> [f64; 3] {
+        [
+            self.x + self.width,
+            self.y + self.height,
+            self.z + self.depth,
+        ]
+    }
+
     /// Determines if the cube contains the given 3D point.
     ///
     /// # Arguments
@@ -467,18 +595,11 @@ impl Cube {
             && point.y <= self.y + self.height
             && point.z >= self.z
             && point.z <= self.z + self.depth;
-        debug!(
-            "Cube::contains(): self: (x: {}, y: {}, z: {}, w: {}, h: {}, d: {}), point: (x: {}, y: {}, z: {}), result: {}",
-            self.x,
-            self.y,
-            self.z,
-            self.width,
-            self.height,
-            self.depth,
-            point.x,
-            point.y,
-            point.z,
-            res
+        hot_trace!(
+            "Cube::contains()",
+            cube = self,
+            point = (point.x, point.y, point.z),
+            result = res
         );
         res
     }
@@ -498,27 +619,23 @@ impl Cube {
     /// assert!(a.intersects(&b));
     /// ```
     pub fn intersects(&self, other: &Cube) -> bool {
+        #[cfg(feature = "packed_mbr")]
+        let res = packed::intersects(
+            (self.min_corner(), self.max_corner()),
+            (other.min_corner(), other.max_corner()),
+        );
+        #[cfg(not(feature = "packed_mbr"))]
         let res = !(other.x > self.x + self.width
             || other.x + other.width < self.x
             || other.y > self.y + self.height
             || other.y + other.height < self.y
             || other.z > self.z + self.depth
             || other.z + other.depth < self.z);
-        debug!(
-            "Cube::intersects(): self: (x: {}, y: {}, z: {}, w: {}, h: {}, d: {}), other: (x: {}, y: {}, z: {}, w: {}, h: {}, d: {}), result: {}",
-            self.x,
-            self.y,
-            self.z,
-            self.width,
-            self.height,
-            self.depth,
-            other.x,
-            other.y,
-            other.z,
-            other.width,
-            other.height,
-            other.depth,
-            res
+        hot_trace!(
+            "Cube::intersects()",
+            cube = self,
+            other = other,
+            result = res
         );
         res
     }
@@ -534,10 +651,7 @@ impl Cube {
     /// ```
     pub fn area(&self) -> f64 {
         let vol = self.width * self.height * self.depth;
-        debug!(
-            "Cube::area(): (w: {}, h: {}, d: {}) -> {}",
-            self.width, self.height, self.depth, vol
-        );
+        hot_trace!("Cube::area()", result = vol);
         vol
     }
 
@@ -559,47 +673,18 @@ impl Cube {
     /// assert_eq!(union_cube.x, 0.0);
     /// ```
     pub fn union(&self, other: &Cube) -> Cube {
-        let x1 = self.x.min(other.x);
-        let y1 = self.y.min(other.y);
-        let z1 = self.z.min(other.z);
-        let x2 = (self.x + self.width).max(other.x + other.width);
-        let y2 = (self.y + self.height).max(other.y + other.height);
-        let z2 = (self.z + self.depth).max(other.z + other.depth);
-
-        // Add small epsilon to dimensions to account for floating-point precision errors
-        let eps_x = f64::EPSILON * 4.0 * (x2.abs() + x1.abs()).max(1.0);
-        let eps_y = f64::EPSILON * 4.0 * (y2.abs() + y1.abs()).max(1.0);
-        let eps_z = f64::EPSILON * 4.0 * (z2.abs() + z1.abs()).max(1.0);
-
-        let union_cube = Cube {
-            x: x1,
-            y: y1,
-            z: z1,
-            width: (x2 - x1) + eps_x,
-            height: (y2 - y1) + eps_y,
-            depth: (z2 - z1) + eps_z,
-        };
-        debug!(
-            "Cube::union(): self: (x: {}, y: {}, z: {}, w: {}, h: {}, d: {}), other: (x: {}, y: {}, z: {}, w: {}, h: {}, d: {}), result: (x: {}, y: {}, z: {}, w: {}, h: {}, d: {})",
-            self.x,
-            self.y,
-            self.z,
-            self.width,
-            self.height,
-            self.depth,
-            other.x,
-            other.y,
-            other.z,
-            other.width,
-            other.height,
-            other.depth,
-            union_cube.x,
-            union_cube.y,
-            union_cube.z,
-            union_cube.width,
-            union_cube.height,
-            union_cube.depth
+        let (a, b) = (self.max_corner(), other.max_corner());
+        // `from_corners` rounds the extents up so that the far faces, as computed by `contains`
+        // and `intersects`, never fall short of the faces of the inputs.
+        let union_cube = Cube::from_corners(
+            [
+                self.x.min(other.x),
+                self.y.min(other.y),
+                self.z.min(other.z),
+            ],
+            [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
         );
+        hot_trace!("Cube::union()", result = union_cube);
         union_cube
     }
 
@@ -625,14 +710,229 @@ impl Cube {
         let self_area = self.area();
         let union_area = union_cube.area();
         let extra = union_area - self_area;
-        debug!(
-            "Cube::enlargement(): self volume: {}, union volume: {}, enlargement: {}",
-            self_area, union_area, extra
-        );
+        hot_trace!("Cube::enlargement()", result = extra);
         extra
     }
 }
 
+/// Represents a point in D-dimensional space with an optional payload.
+///
+/// Together with `Aabb`, this makes the bounding-volume trees (`RTree` and `RStarTree`) and the
+/// `KdTree` usable for any number of dimensions, e.g. space + time + altitude in 4D or 5D.
+///
+/// ### Example
+///
+/// ```
+/// use spart::geometry::{EuclideanDistance, PointND};
+/// use spart::rstar_tree::RStarTree;
+///
+/// // x, y, altitude, and time.
+/// let mut tree: RStarTree<PointND<&str, 4>> = RStarTree::new(8).unwrap();
+/// tree.insert(PointND::new([1.0, 2.0, 300.0, 10.0], Some("a")));
+/// tree.insert(PointND::new([1.0, 2.0, 900.0, 10.0], Some("b")));
+/// let query = PointND::new([1.0, 2.0, 350.0, 12.0], None);
+/// let nearest = tree.knn_search::<EuclideanDistance>(&query, 1);
+/// assert_eq!(nearest[0].data, Some("a"));
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PointND<T, const D: usize> {
+    /// The coordinates of the point.
+    #[cfg_attr(feature = "serde", serde(with = "coord_array"))]
+    pub coords: [f64; D],
+    /// Optional associated data.
+    pub data: Option<T>,
+}
+
+impl<T: PartialEq, const D: usize> PartialEq for PointND<T, D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.coords
+            .iter()
+            .zip(&other.coords)
+            .all(|(a, b)| OrderedFloat(*a) == OrderedFloat(*b))
+            && self.data == other.data
+    }
+}
+
+impl<T: Eq, const D: usize> Eq for PointND<T, D> {}
+
+impl<T, const D: usize> DistanceMetric<PointND<T, D>> for EuclideanDistance {
+    fn distance_sq(p1: &PointND<T, D>, p2: &PointND<T, D>) -> f64 {
+        p1.distance_sq(p2)
+    }
+}
+
+impl<T, const D: usize> PointND<T, D> {
+    /// Creates a new `PointND` with the given coordinates and optional data.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The coordinates, one per dimension.
+    /// * `data` - Optional data associated with the point.
+    pub fn new(coords: [f64; D], data: Option<T>) -> Self {
+        let pt = Self { coords, data };
+        hot_trace!("PointND::new()", coords = pt.coords);
+        pt
+    }
+
+    /// Computes the squared Euclidean distance between this point and another.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spart::geometry::PointND;
+    /// let a: PointND<(), 4> = PointND::new([0.0; 4], None);
+    /// let b: PointND<(), 4> = PointND::new([1.0, 1.0, 1.0, 1.0], None);
+    /// assert_eq!(a.distance_sq(&b), 4.0);
+    /// ```
+    pub fn distance_sq(&self, other: &PointND<T, D>) -> f64 {
+        let dist = self
+            .coords
+            .iter()
+            .zip(&other.coords)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum();
+        hot_trace!("PointND::distance_sq()", result = dist);
+        dist
+    }
+}
+
+/// Represents an axis-aligned box in D-dimensional space, stored as its two corners.
+///
+/// Unlike `Rectangle` and `Cube`, the far corner is stored as is rather than as an offset from
+/// the near one, so `union` is exact and needs no rounding. A box with `min == max` along an
+/// axis is flat along that axis; boxes whose `min` exceeds their `max` are empty.
+///
+/// ### Example
+///
+/// ```
+/// use spart::geometry::{Aabb, PointND};
+/// let a = Aabb { min: [0.0; 4], max: [1.0; 4] };
+/// let b = Aabb { min: [2.0, 0.0, 0.0, 0.0], max: [3.0, 1.0, 1.0, 1.0] };
+/// assert!(!a.intersects(&b));
+/// assert_eq!(a.union(&b).volume(), 3.0);
+/// assert!(a.contains(&PointND::new([1.0, 0.5, 0.0, 1.0], None::<()>)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Aabb<const D: usize> {
+    /// The corner with the smallest coordinates.
+    #[cfg_attr(feature = "serde", serde(with = "coord_array"))]
+    pub min: [f64; D],
+    /// The corner with the largest coordinates.
+    #[cfg_attr(feature = "serde", serde(with = "coord_array"))]
+    pub max: [f64; D],
+}
+
+impl<const D: usize> Aabb<D> {
+    /// Returns `true` if the point lies inside the box (boundary included).
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to test.
+    pub fn contains<T>(&self, point: &PointND<T, D>) -> bool {
+        (0..D).all(|axis| {
+            self.min[axis] <= point.coords[axis] && point.coords[axis] <= self.max[axis]
+        })
+    }
+
+    /// Returns `true` if the boxes touch or overlap.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other box.
+    pub fn intersects(&self, other: &Aabb<D>) -> bool {
+        (0..D).all(|axis| other.min[axis] <= self.max[axis] && self.min[axis] <= other.max[axis])
+    }
+
+    /// Computes the volume of the box (the product of its side lengths).
+    pub fn volume(&self) -> f64 {
+        (0..D).map(|axis| self.max[axis] - self.min[axis]).product()
+    }
+
+    /// Returns the smallest box containing both `self` and `other`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other box.
+    pub fn union(&self, other: &Aabb<D>) -> Aabb<D> {
+        let union_box = Aabb {
+            min: std::array::from_fn(|axis| self.min[axis].min(other.min[axis])),
+            max: std::array::from_fn(|axis| self.max[axis].max(other.max[axis])),
+        };
+        hot_trace!("Aabb::union()", result = union_box);
+        union_box
+    }
+
+    /// Computes the Euclidean distance from the box to a point, which is zero for points inside
+    /// the box.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point.
+    pub fn min_distance<T>(&self, point: &PointND<T, D>) -> f64 {
+        (0..D)
+            .map(|axis| {
+                let c = point.coords[axis];
+                let gap = (self.min[axis] - c).max(c - self.max[axis]).max(0.0);
+                gap * gap
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+impl<const D: usize> BSPBounds for Aabb<D> {
+    const DIM: usize = D;
+    fn center(&self, dim: usize) -> Result<f64, SpartError> {
+        if dim >= D {
+            return Err(SpartError::InvalidDimension {
+                requested: dim,
+                available: D,
+            });
+        }
+        Ok((self.min[dim] + self.max[dim]) / 2.0)
+    }
+    fn extent(&self, dim: usize) -> Result<f64, SpartError> {
+        if dim >= D {
+            return Err(SpartError::InvalidDimension {
+                requested: dim,
+                available: D,
+            });
+        }
+        Ok(self.max[dim] - self.min[dim])
+    }
+}
+
+/// Serializes a coordinate array as a sequence, since `serde` has no implementations for arrays
+/// of arbitrary length.
+#[cfg(feature = "serde")]
+mod coord_array {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(super) fn serialize<S: Serializer, const D: usize>(
+        coords: &[f64; D],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        coords.as_slice().serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, De: Deserializer<'de>, const D: usize>(
+        deserializer: De,
+    ) -> Result<[f64; D], De::Error> {
+        let coords = Vec::<f64>::deserialize(deserializer)?;
+        let len = coords.len();
+        coords
+            .try_into()
+            .map_err(|_| De::Error::invalid_length(len, &"one coordinate per dimension"))
+    }
+}
+
 /// Trait for types that can provide the center and extent along a specified dimension.
 pub trait BSPBounds {
     /// The number of dimensions supported.
@@ -709,6 +1009,74 @@ impl BSPBounds for Cube {
     }
 }
 
+/// Returns the fraction of `volume` that lies inside `query`, assuming that whatever `volume`
+/// holds is spread evenly over it.
+///
+/// Along an axis where `volume` is flat, the fraction is 1 if `query` covers its coordinate and
+/// 0 otherwise.
+pub(crate) fn overlap_fraction<B: BSPBounds>(volume: &B, query: &B) -> f64 {
+    (0..B::DIM)
+        .map(|dim| {
+            let (Ok(c), Ok(e), Ok(qc), Ok(qe)) = (
+                volume.center(dim),
+                volume.extent(dim),
+                query.center(dim),
+                query.extent(dim),
+            ) else {
+                return 0.0;
+            };
+            let (lo, hi) = (c - e / 2.0, c + e / 2.0);
+            let (qlo, qhi) = (qc - qe / 2.0, qc + qe / 2.0);
+            if e <= 0.0 {
+                return if qlo <= lo && lo <= qhi { 1.0 } else { 0.0 };
+            }
+            ((hi.min(qhi) - lo.max(qlo)) / e).clamp(0.0, 1.0)
+        })
+        .product()
+}
+
+/// Splits the box `lo..=hi` into `parts` boxes holding roughly equal numbers of `coords`.
+///
+/// The box is cut in two across its longest side, at the coordinate that divides the points in
+/// the same ratio as the parts on either side, and both halves are split further in the same
+/// way. The returned boxes tile the original one; a half without points is cut in the middle.
+pub(crate) fn equi_depth_cells<const D: usize>(
+    lo: [f64; D],
+    hi: [f64; D],
+    coords: &mut [[f64; D]],
+    parts: usize,
+    out: &mut Vec<([f64; D], [f64; D])>,
+) {
+    if parts <= 1 {
+        if parts == 1 {
+            out.push((lo, hi));
+        }
+        return;
+    }
+    let axis = (0..D)
+        .max_by(|&a, &b| (hi[a] - lo[a]).total_cmp(&(hi[b] - lo[b])))
+        .unwrap_or(0);
+    let below = parts / 2;
+    let split = coords.len() * below / parts;
+    let cut = if split == 0 || split == coords.len() {
+        (lo[axis] + hi[axis]) / 2.0
+    } else {
+        coords.select_nth_unstable_by(split, |a, b| a[axis].total_cmp(&b[axis]));
+        let above = coords[split][axis];
+        let under = coords[..split]
+            .iter()
+            .map(|c| c[axis])
+            .fold(f64::NEG_INFINITY, f64::max);
+        ((under + above) / 2.0).clamp(lo[axis], hi[axis])
+    };
+    let (left, right) = coords.split_at_mut(split);
+    let (mut left_hi, mut right_lo) = (hi, lo);
+    left_hi[axis] = cut;
+    right_lo[axis] = cut;
+    equi_depth_cells(lo, left_hi, left, below, out);
+    equi_depth_cells(right_lo, hi, right, parts - below, out);
+}
+
 /// Trait representing a bounding volume, such as a rectangle or cube.
 ///
 /// This trait abstracts common operations for geometric volumes used in indexing.
@@ -733,22 +1101,133 @@ pub trait BoundingVolume: Clone {
     fn margin(&self) -> f64;
 }
 
+/// Trait for objects that can be stored in the bounding-volume trees (`RTree` and `RStarTree`).
+///
+/// Implementing this trait makes a type usable in every such tree, through the blanket
+/// implementations of `RTreeObject` and `RStarTreeObject`.
+///
+/// # Example
+///
+/// ```
+/// use spart::geometry::{Bounded, Rectangle};
+/// use spart::rstar_tree::RStarTree;
+/// use spart::rtree::RTree;
+///
+/// #[derive(Debug, Clone)]
+/// struct Building {
+///     footprint: Rectangle,
+/// }
+///
+/// impl Bounded for Building {
+///     type B = Rectangle;
+///     fn mbr(&self) -> Rectangle {
+///         self.footprint.clone()
+///     }
+/// }
+///
+/// let building = Building { footprint: Rectangle { x: 0.0, y: 0.0, width: 2.0, height: 3.0 } };
+/// let mut rtree = RTree::new(4).unwrap();
+/// rtree.insert(building.clone());
+/// let mut rstar = RStarTree::new(4).unwrap();
+/// rstar.insert(building);
+/// ```
+#[cfg(feature = "serde")]
+pub trait Bounded: std::fmt::Debug + Clone {
+    /// The type of the bounding volume (e.g. `Rectangle` for 2D objects or `Cube` for 3D objects).
+    type B: BoundingVolume
+        + std::fmt::Debug
+        + Clone
+        + serde::Serialize
+        + for<'de> serde::Deserialize<'de>;
+    /// Returns the minimum bounding volume of the object.
+    fn mbr(&self) -> Self::B;
+}
+#[cfg(not(feature = "serde"))]
+pub trait Bounded: std::fmt::Debug + Clone {
+    /// The type of the bounding volume (e.g. `Rectangle` for 2D objects or `Cube` for 3D objects).
+    type B: BoundingVolume + std::fmt::Debug + Clone;
+    /// Returns the minimum bounding volume of the object.
+    fn mbr(&self) -> Self::B;
+}
+
+// The side length of the bounding volume of a point, so that it is not degenerate.
+pub(crate) const POINT_MBR_SIZE: f64 = 1e-10;
+
+impl Bounded for Rectangle {
+    type B = Rectangle;
+    fn mbr(&self) -> Self::B {
+        self.clone()
+    }
+}
+
+impl Bounded for Cube {
+    type B = Cube;
+    fn mbr(&self) -> Self::B {
+        self.clone()
+    }
+}
+
+impl<T: std::fmt::Debug + Clone> Bounded for Point2D<T> {
+    type B = Rectangle;
+    fn mbr(&self) -> Self::B {
+        Rectangle {
+            x: self.x,
+            y: self.y,
+            width: POINT_MBR_SIZE,
+            height: POINT_MBR_SIZE,
+        }
+    }
+}
+
+impl<T: std::fmt::Debug + Clone> Bounded for Point3D<T> {
+    type B = Cube;
+    fn mbr(&self) -> Self::B {
+        Cube {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            width: POINT_MBR_SIZE,
+            height: POINT_MBR_SIZE,
+            depth: POINT_MBR_SIZE,
+        }
+    }
+}
+
+impl<T: std::fmt::Debug + Clone, const D: usize> Bounded for PointND<T, D> {
+    type B = Aabb<D>;
+    fn mbr(&self) -> Self::B {
+        Aabb {
+            min: self.coords,
+            max: self.coords.map(|c| c + POINT_MBR_SIZE),
+        }
+    }
+}
+
+impl<const D: usize> Bounded for Aabb<D> {
+    type B = Aabb<D>;
+    fn mbr(&self) -> Self::B {
+        *self
+    }
+}
+
 impl BoundingVolume for Rectangle {
     fn area(&self) -> f64 {
-        let a = Rectangle::area(self);
-        debug!("BoundingVolume (Rectangle)::area() -> {}", a);
-        a
+        Rectangle::area(self)
     }
     fn union(&self, other: &Self) -> Self {
-        let u = Rectangle::union(self, other);
-        debug!("BoundingVolume (Rectangle)::union() computed.");
-        u
+        Rectangle::union(self, other)
     }
     fn intersects(&self, other: &Self) -> bool {
-        let i = Rectangle::intersects(self, other);
-        debug!("BoundingVolume (Rectangle)::intersects() -> {}", i);
-        i
+        Rectangle::intersects(self, other)
     }
+    #[cfg(feature = "packed_mbr")]
+    fn overlap(&self, other: &Self) -> f64 {
+        packed::overlap(
+            (self.min_corner(), self.max_corner()),
+            (other.min_corner(), other.max_corner()),
+        )
+    }
+    #[cfg(not(feature = "packed_mbr"))]
     fn overlap(&self, other: &Self) -> f64 {
         let overlap_x = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
         let overlap_y = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
@@ -766,20 +1245,22 @@ impl BoundingVolume for Rectangle {
 
 impl BoundingVolume for Cube {
     fn area(&self) -> f64 {
-        let a = Cube::area(self);
-        debug!("BoundingVolume (Cube)::area() -> {}", a);
-        a
+        Cube::area(self)
     }
     fn union(&self, other: &Self) -> Self {
-        let u = Cube::union(self, other);
-        debug!("BoundingVolume (Cube)::union() computed.");
-        u
+        Cube::union(self, other)
     }
     fn intersects(&self, other: &Self) -> bool {
-        let i = Cube::intersects(self, other);
-        debug!("BoundingVolume (Cube)::intersects() -> {}", i);
-        i
+        Cube::intersects(self, other)
     }
+    #[cfg(feature = "packed_mbr")]
+    fn overlap(&self, other: &Self) -> f64 {
+        packed::overlap(
+            (self.min_corner(), self.max_corner()),
+            (other.min_corner(), other.max_corner()),
+        )
+    }
+    #[cfg(not(feature = "packed_mbr"))]
     fn overlap(&self, other: &Self) -> f64 {
         let overlap_x = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
         let overlap_y = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
@@ -796,6 +1277,74 @@ impl BoundingVolume for Cube {
     }
 }
 
+impl<const D: usize> BoundingVolume for Aabb<D> {
+    fn area(&self) -> f64 {
+        self.volume()
+    }
+    fn union(&self, other: &Self) -> Self {
+        Aabb::union(self, other)
+    }
+    fn intersects(&self, other: &Self) -> bool {
+        Aabb::intersects(self, other)
+    }
+    fn overlap(&self, other: &Self) -> f64 {
+        let mut product = 1.0;
+        for axis in 0..D {
+            let shared = self.max[axis].min(other.max[axis]) - self.min[axis].max(other.min[axis]);
+            if shared <= 0.0 {
+                return 0.0;
+            }
+            product *= shared;
+        }
+        product
+    }
+
+    fn margin(&self) -> f64 {
+        2.0 * (0..D)
+            .map(|axis| self.max[axis] - self.min[axis])
+            .sum::<f64>()
+    }
+}
+
+/// Branch-free bounding box math on corner arrays, used by `Rectangle` and `Cube` with the
+/// `packed_mbr` feature.
+///
+/// Every axis is evaluated without early exits, so the loops over the fixed-size arrays compile
+/// to packed `f64x2` (2D) or `f64x4` (3D) instructions. This pays off in the R-tree and R*-tree
+/// insertion paths, where choosing a subtree compares a candidate box against every sibling.
+/// The results are the same as those of the scalar versions.
+#[cfg(feature = "packed_mbr")]
+mod packed {
+    /// Returns `true` if the boxes `a` and `b` (given as their smallest and largest corners)
+    /// touch or overlap.
+    pub(super) fn intersects<const D: usize>(
+        (a_lo, a_hi): ([f64; D], [f64; D]),
+        (b_lo, b_hi): ([f64; D], [f64; D]),
+    ) -> bool {
+        let mut apart = false;
+        for axis in 0..D {
+            apart |= (b_lo[axis] > a_hi[axis]) | (b_hi[axis] < a_lo[axis]);
+        }
+        !apart
+    }
+
+    /// Returns the area (or volume) shared by the boxes `a` and `b`, which is zero unless they
+    /// overlap along every axis.
+    pub(super) fn overlap<const D: usize>(
+        (a_lo, a_hi): ([f64; D], [f64; D]),
+        (b_lo, b_hi): ([f64; D], [f64; D]),
+    ) -> f64 {
+        let mut product = 1.0;
+        let mut positive = true;
+        for axis in 0..D {
+            let shared = a_hi[axis].min(b_hi[axis]) - a_lo[axis].max(b_lo[axis]);
+            positive &= shared > 0.0;
+            product *= shared;
+        }
+        if positive { product } else { 0.0 }
+    }
+}
+
 /// Represents an item in a heap, typically used for nearest neighbor or best-first search algorithms.
 ///
 /// The `neg_distance` field is used to order items in a max-heap by their (negated) distance value.
@@ -872,6 +1421,21 @@ impl<T> BoundingVolumeFromPoint<Point2D<T>> for Rectangle {
     }
 }
 
+impl<T, const D: usize> HasMinDistance<PointND<T, D>> for Aabb<D> {
+    fn min_distance(&self, point: &PointND<T, D>) -> f64 {
+        Aabb::min_distance(self, point)
+    }
+}
+
+impl<T, const D: usize> BoundingVolumeFromPoint<PointND<T, D>> for Aabb<D> {
+    fn from_point_radius(query: &PointND<T, D>, radius: f64) -> Self {
+        Aabb {
+            min: query.coords.map(|c| c - radius),
+            max: query.coords.map(|c| c + radius),
+        }
+    }
+}
+
 impl<T> HasMinDistance<Point3D<T>> for Cube {
     fn min_distance(&self, point: &Point3D<T>) -> f64 {
         let dx = if point.x < self.x {
@@ -912,6 +1476,369 @@ impl<T> BoundingVolumeFromPoint<Point3D<T>> for Cube {
     }
 }
 
+/// Specifies which edge of a tiling extent holds tile row 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TileOrigin {
+    /// Row 0 is at the largest `y` coordinate (the XYZ / slippy map convention).
+    MaxY,
+    /// Row 0 is at the smallest `y` coordinate (the TMS convention).
+    MinY,
+}
+
+/// A tiling scheme that divides a rectangular extent into `2^z × 2^z` tiles at zoom level `z`.
+///
+/// Every point inside the extent belongs to exactly one tile per zoom level: tiles are
+/// half-open on their far edges, except for the tiles on the far edges of the extent,
+/// which also include the extent's boundary.
+///
+/// # Examples
+///
+/// ```
+/// use spart::geometry::{Point2D, TileScheme};
+///
+/// let scheme = TileScheme::web_mercator();
+/// let origin: Point2D<()> = Point2D::new(0.0, 0.0, None);
+/// // The origin lies on the corner shared by the four tiles of zoom level 1 and is
+/// // assigned to the tile north-east of it.
+/// assert_eq!(scheme.tile_for(1, &origin), Some((1, 0)));
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TileScheme {
+    /// The region covered by the tile at zoom level 0.
+    pub extent: Rectangle,
+    /// The edge of the extent that holds tile row 0.
+    pub origin: TileOrigin,
+}
+
+impl TileScheme {
+    /// Half the side length, in meters, of the square Web-Mercator (EPSG:3857) extent.
+    pub const WEB_MERCATOR_HALF_EXTENT: f64 = 20_037_508.342_789_244;
+
+    /// The largest supported zoom level.
+    pub const MAX_ZOOM: u8 = 31;
+
+    /// Creates a tiling scheme over the given extent.
+    ///
+    /// # Arguments
+    ///
+    /// * `extent` - The region covered by the tile at zoom level 0.
+    /// * `origin` - The edge of the extent that holds tile row 0.
+    pub fn new(extent: Rectangle, origin: TileOrigin) -> Self {
+        TileScheme { extent, origin }
+    }
+
+    /// Returns the standard XYZ tiling scheme over Web-Mercator (EPSG:3857) coordinates in meters.
+    pub fn web_mercator() -> Self {
+        let half = Self::WEB_MERCATOR_HALF_EXTENT;
+        TileScheme {
+            extent: Rectangle {
+                x: -half,
+                y: -half,
+                width: 2.0 * half,
+                height: 2.0 * half,
+            },
+            origin: TileOrigin::MaxY,
+        }
+    }
+
+    /// Returns the number of tiles along each axis at zoom level `z`.
+    fn tiles_per_axis(z: u8) -> Option<u32> {
+        if z > Self::MAX_ZOOM {
+            None
+        } else {
+            Some(1u32 << z)
+        }
+    }
+
+    /// Returns the bounds of tile `(x, y)` at zoom level `z`.
+    ///
+    /// # Returns
+    ///
+    /// The tile's rectangle, or `None` if the zoom level or tile coordinates are out of range.
+    pub fn tile_bounds(&self, z: u8, x: u32, y: u32) -> Option<Rectangle> {
+        let n = Self::tiles_per_axis(z)?;
+        if x >= n || y >= n {
+            return None;
+        }
+        let width = self.extent.width / n as f64;
+        let height = self.extent.height / n as f64;
+        let row = match self.origin {
+            TileOrigin::MaxY => n - 1 - y,
+            TileOrigin::MinY => y,
+        };
+        Some(Rectangle {
+            x: self.extent.x + x as f64 * width,
+            y: self.extent.y + row as f64 * height,
+            width,
+            height,
+        })
+    }
+
+    /// Returns the tile `(x, y)` at zoom level `z` that the given point belongs to.
+    ///
+    /// # Returns
+    ///
+    /// The tile coordinates, or `None` if the zoom level is out of range or the point lies
+    /// outside the extent.
+    pub fn tile_for<T>(&self, z: u8, point: &Point2D<T>) -> Option<(u32, u32)> {
+        let n = Self::tiles_per_axis(z)?;
+        if !self.extent.contains(point) {
+            return None;
+        }
+        let index = |offset: f64, length: f64| {
+            let i = (offset / length * n as f64).floor();
+            (i.max(0.0) as u32).min(n - 1)
+        };
+        let x = index(point.x - self.extent.x, self.extent.width);
+        let row = index(point.y - self.extent.y, self.extent.height);
+        let y = match self.origin {
+            TileOrigin::MaxY => n - 1 - row,
+            TileOrigin::MinY => row,
+        };
+        Some((x, y))
+    }
+
+    /// Returns the bounds of tile `(x, y)` at zoom level `z` slightly enlarged, so that a closed
+    /// range search over it cannot miss points that `tile_for` assigns to the tile.
+    pub(crate) fn tile_search_bounds(&self, z: u8, x: u32, y: u32) -> Option<Rectangle> {
+        let bounds = self.tile_bounds(z, x, y)?;
+        let pad_x = bounds.width * 1e-9;
+        let pad_y = bounds.height * 1e-9;
+        Some(Rectangle {
+            x: bounds.x - pad_x,
+            y: bounds.y - pad_y,
+            width: bounds.width + 2.0 * pad_x,
+            height: bounds.height + 2.0 * pad_y,
+        })
+    }
+}
+
+/// Returns the cross product of `a - o` and `b - o`.
+///
+/// The result is positive if `o`, `a`, `b` make a counter-clockwise turn (with the `y` axis
+/// pointing up), negative for a clockwise turn, and zero if the points are collinear.
+pub(crate) fn cross<T>(o: &Point2D<T>, a: &Point2D<T>, b: &Point2D<T>) -> f64 {
+    orient2d([o.x, o.y], [a.x, a.y], [b.x, b.y])
+}
+
+/// Computes the convex hull of the referenced points with Andrew's monotone chain algorithm.
+///
+/// See `convex_hull` for the shape of the result.
+pub(crate) fn convex_hull_refs<T>(mut points: Vec<&Point2D<T>>) -> Vec<&Point2D<T>> {
+    points.retain(|p| !p.x.is_nan() && !p.y.is_nan());
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup_by(|a, b| a.x == b.x && a.y == b.y);
+    if points.len() < 3 {
+        return points;
+    }
+
+    // Lower chain from left to right, then upper chain back from right to left. Both keep
+    // only counter-clockwise turns, which also drops collinear points.
+    let mut hull: Vec<&Point2D<T>> = Vec::with_capacity(points.len() + 1);
+    for &p in &points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    let lower_len = hull.len() + 1;
+    for &p in points.iter().rev().skip(1) {
+        while hull.len() >= lower_len && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+        {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    // The last point closes the upper chain and repeats the first one.
+    hull.pop();
+    hull
+}
+
+/// Computes the convex hull of a set of 2D points.
+///
+/// The hull is computed with Andrew's monotone chain algorithm in `O(n log n)` time.
+///
+/// # Arguments
+///
+/// * `points` - The points to enclose.
+///
+/// # Returns
+///
+/// The hull vertices in counter-clockwise order (with the `y` axis pointing up), starting at
+/// the point with the smallest `x` (and then smallest `y`) coordinate. The first vertex is not
+/// repeated at the end, and points on the hull's edges are not included. Duplicate
+/// coordinates are reported once and points with NaN coordinates are ignored. If all points
+/// are collinear, only the two endpoints are returned.
+///
+/// # Examples
+///
+/// ```
+/// use spart::geometry::{convex_hull, Point2D};
+///
+/// let points: Vec<Point2D<()>> = vec![
+///     Point2D::new(0.0, 0.0, None),
+///     Point2D::new(2.0, 0.0, None),
+///     Point2D::new(1.0, 1.0, None),
+///     Point2D::new(2.0, 2.0, None),
+///     Point2D::new(0.0, 2.0, None),
+/// ];
+/// let hull = convex_hull(&points);
+/// let corners: Vec<(f64, f64)> = hull.iter().map(|p| (p.x, p.y)).collect();
+/// assert_eq!(corners, vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]);
+/// ```
+pub fn convex_hull<T: Clone>(points: &[Point2D<T>]) -> Vec<Point2D<T>> {
+    convex_hull_refs(points.iter().collect())
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+/// A simple polygon in 2D, given by its vertices in order (either orientation).
+///
+/// Points on the boundary count as inside the polygon. Self-intersecting polygons are
+/// accepted and use the even-odd rule for their interior.
+///
+/// # Examples
+///
+/// ```
+/// use spart::geometry::{Point2D, Polygon};
+///
+/// let triangle = Polygon::new(vec![[0.0, 0.0], [4.0, 0.0], [0.0, 4.0]]).unwrap();
+/// assert!(triangle.contains(&Point2D::new(1.0, 1.0, None::<()>)));
+/// assert!(triangle.contains(&Point2D::new(2.0, 2.0, None::<()>)));
+/// assert!(!triangle.contains(&Point2D::new(3.0, 3.0, None::<()>)));
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Polygon {
+    vertices: Vec<[f64; 2]>,
+    bbox: Rectangle,
+}
+
+impl Polygon {
+    /// Creates a polygon from its vertices.
+    ///
+    /// The first vertex may be repeated at the end to close the ring.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if a vertex is not finite or there are fewer
+    /// than three vertices.
+    pub fn new(mut vertices: Vec<[f64; 2]>) -> Result<Self, SpartError> {
+        if vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        if vertices.len() < 3 {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("a polygon needs 3 vertices, got {}", vertices.len()),
+            });
+        }
+        if let Some(v) = vertices
+            .iter()
+            .find(|v| !v[0].is_finite() || !v[1].is_finite())
+        {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("polygon vertex {v:?} is not finite"),
+            });
+        }
+        let (mut min, mut max) = (vertices[0], vertices[0]);
+        for v in &vertices {
+            min = [min[0].min(v[0]), min[1].min(v[1])];
+            max = [max[0].max(v[0]), max[1].max(v[1])];
+        }
+        let bbox = Rectangle::from_corners(min, max);
+        Ok(Polygon { vertices, bbox })
+    }
+
+    /// Returns the vertices of the polygon (without the closing vertex).
+    pub fn vertices(&self) -> &[[f64; 2]] {
+        &self.vertices
+    }
+
+    /// Returns the minimum bounding rectangle of the polygon.
+    pub fn bbox(&self) -> &Rectangle {
+        &self.bbox
+    }
+
+    /// Returns the edges of the polygon as pairs of consecutive vertices.
+    fn edges(&self) -> impl Iterator<Item = ([f64; 2], [f64; 2])> + '_ {
+        let n = self.vertices.len();
+        (0..n).map(move |i| (self.vertices[i], self.vertices[(i + 1) % n]))
+    }
+
+    /// Checks if a point lies inside the polygon or on its boundary.
+    pub fn contains<T>(&self, point: &Point2D<T>) -> bool {
+        if !self.bbox.contains(point) {
+            return false;
+        }
+        let p = [point.x, point.y];
+        let mut inside = false;
+        for (a, b) in self.edges() {
+            if on_segment(a, b, p) {
+                return true;
+            }
+            if (a[1] > p[1]) != (b[1] > p[1]) {
+                let x = a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+                if p[0] < x {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    /// Checks if the polygon (including its boundary) intersects a rectangle.
+    pub fn intersects_rect(&self, rect: &Rectangle) -> bool {
+        if !self.bbox.intersects(rect) {
+            return false;
+        }
+        let inside_rect = |v: &[f64; 2]| rect.contains(&Point2D::<()>::new(v[0], v[1], None));
+        if self.vertices.iter().any(inside_rect) {
+            return true;
+        }
+        let (x0, y0) = (rect.x, rect.y);
+        let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+        let corners = [[x0, y0], [x1, y0], [x1, y1], [x0, y1]];
+        if corners
+            .iter()
+            .any(|c| self.contains(&Point2D::<()>::new(c[0], c[1], None)))
+        {
+            return true;
+        }
+        self.edges().any(|(a, b)| {
+            (0..4).any(|i| segments_intersect(a, b, corners[i], corners[(i + 1) % 4]))
+        })
+    }
+}
+
+/// Returns `true` if `p` lies on the closed segment from `a` to `b`.
+pub(crate) fn on_segment(a: [f64; 2], b: [f64; 2], p: [f64; 2]) -> bool {
+    orient2d(a, b, p) == 0.0
+        && p[0] >= a[0].min(b[0])
+        && p[0] <= a[0].max(b[0])
+        && p[1] >= a[1].min(b[1])
+        && p[1] <= a[1].max(b[1])
+}
+
+/// Returns `true` if the closed segments `a1`-`a2` and `b1`-`b2` intersect.
+pub(crate) fn segments_intersect(a1: [f64; 2], a2: [f64; 2], b1: [f64; 2], b2: [f64; 2]) -> bool {
+    let d1 = orient2d(b1, b2, a1);
+    let d2 = orient2d(b1, b2, a2);
+    let d3 = orient2d(a1, a2, b1);
+    let d4 = orient2d(a1, a2, b2);
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+    on_segment(b1, b2, a1)
+        || on_segment(b1, b2, a2)
+        || on_segment(a1, a2, b1)
+        || on_segment(a1, a2, b2)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1073,4 +2000,176 @@ mod tests {
         assert!(union.contains(&r2_min));
         assert!(union.contains(&r2_max));
     }
+
+    #[test]
+    fn test_corner_form_round_trips_and_covers_max_corner() {
+        let rect = Rectangle::from_corners([-191.20362538993982, 0.5], [111.08676433386941, 2.0]);
+        assert_eq!(rect.min_corner(), [-191.20362538993982, 0.5]);
+        let [x, y] = rect.max_corner();
+        assert!(x >= 111.08676433386941 && y >= 2.0);
+        assert!(rect.contains(&Point2D::new(111.08676433386941, 2.0, None::<()>)));
+
+        let cube = Cube::from_corners([-0.1, -0.2, -0.3], [0.7, 0.2, 0.1]);
+        assert_eq!(cube.min_corner(), [-0.1, -0.2, -0.3]);
+        assert!(cube.contains(&Point3D::new(0.7, 0.2, 0.1, None::<()>)));
+
+        let a = Cube::from_corners([0.0; 3], [1.0; 3]);
+        let b = Cube::from_corners([2.0, -1.0, 0.5], [3.0, 0.5, 4.0]);
+        let union = a.union(&b);
+        assert_eq!(union.min_corner(), [0.0, -1.0, 0.0]);
+        assert_eq!(union.max_corner(), [3.0, 1.0, 4.0]);
+    }
+
+    #[test]
+    fn test_convex_hull_drops_interior_collinear_and_duplicate_points() {
+        let points = vec![
+            Point2D::new(1.0, 1.0, Some(0)),
+            Point2D::new(0.0, 0.0, Some(1)),
+            Point2D::new(1.0, 0.0, Some(2)),
+            Point2D::new(2.0, 0.0, Some(3)),
+            Point2D::new(2.0, 2.0, Some(4)),
+            Point2D::new(2.0, 2.0, Some(5)),
+            Point2D::new(0.0, 2.0, Some(6)),
+            Point2D::new(f64::NAN, 1.0, Some(7)),
+        ];
+        let hull: Vec<Option<i32>> = convex_hull(&points).into_iter().map(|p| p.data).collect();
+        assert_eq!(hull, vec![Some(1), Some(3), Some(4), Some(6)]);
+    }
+
+    #[test]
+    fn test_convex_hull_degenerate_inputs() {
+        let empty: Vec<Point2D<()>> = Vec::new();
+        assert!(convex_hull(&empty).is_empty());
+
+        let single = vec![
+            Point2D::new(1.0, 2.0, Some(0)),
+            Point2D::new(1.0, 2.0, Some(1)),
+        ];
+        assert_eq!(convex_hull(&single).len(), 1);
+
+        let line: Vec<Point2D<i32>> = (0..5)
+            .map(|i| Point2D::new(i as f64, 2.0 * i as f64, Some(i)))
+            .collect();
+        let hull: Vec<Option<i32>> = convex_hull(&line).into_iter().map(|p| p.data).collect();
+        assert_eq!(hull, vec![Some(0), Some(4)]);
+    }
+
+    #[test]
+    fn test_polygon_contains_and_intersects_rect() {
+        // A "C" shape: a square with the middle of its right side cut out.
+        let polygon = Polygon::new(vec![
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 3.0],
+            [3.0, 3.0],
+            [3.0, 7.0],
+            [10.0, 7.0],
+            [10.0, 10.0],
+            [0.0, 10.0],
+        ])
+        .unwrap();
+        let at = |x, y| Point2D::new(x, y, None::<()>);
+        assert!(polygon.contains(&at(1.0, 5.0)));
+        assert!(polygon.contains(&at(3.0, 5.0)));
+        assert!(polygon.contains(&at(10.0, 1.0)));
+        assert!(!polygon.contains(&at(5.0, 5.0)));
+        assert!(!polygon.contains(&at(11.0, 1.0)));
+
+        let rect = |x, y, width, height| Rectangle {
+            x,
+            y,
+            width,
+            height,
+        };
+        // Inside the cut-out, touching its edge, containing the polygon, and crossing an edge
+        // without containing a vertex on either side.
+        assert!(!polygon.intersects_rect(&rect(4.0, 4.0, 2.0, 2.0)));
+        assert!(polygon.intersects_rect(&rect(3.0, 4.0, 2.0, 2.0)));
+        assert!(polygon.intersects_rect(&rect(-1.0, -1.0, 12.0, 12.0)));
+        assert!(polygon.intersects_rect(&rect(5.0, 2.0, 1.0, 2.0)));
+
+        assert!(Polygon::new(vec![[0.0, 0.0], [1.0, 1.0], [0.0, 0.0]]).is_err());
+        assert!(Polygon::new(vec![[0.0, 0.0], [1.0, f64::NAN], [0.0, 1.0]]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "packed_mbr")]
+    fn test_packed_box_math_matches_scalar() {
+        use crate::random::{RandomSource, SplitMix64};
+
+        let mut rng = SplitMix64::new(4);
+        let coord = |rng: &mut SplitMix64| (rng.next_below(8) as f64) - 2.0;
+        for _ in 0..2000 {
+            let a = Cube {
+                x: coord(&mut rng),
+                y: coord(&mut rng),
+                z: coord(&mut rng),
+                width: rng.next_below(4) as f64,
+                height: rng.next_below(4) as f64,
+                depth: rng.next_below(4) as f64,
+            };
+            let b = Cube {
+                x: coord(&mut rng),
+                y: coord(&mut rng),
+                z: f64::NAN,
+                width: rng.next_below(4) as f64,
+                height: rng.next_below(4) as f64,
+                depth: 1.0,
+            };
+            let b = if rng.next_below(4) == 0 {
+                b
+            } else {
+                Cube {
+                    z: coord(&mut rng),
+                    ..b
+                }
+            };
+            let axes = [
+                (a.x, a.width, b.x, b.width),
+                (a.y, a.height, b.y, b.height),
+                (a.z, a.depth, b.z, b.depth),
+            ];
+            let apart = axes
+                .iter()
+                .any(|&(al, aw, bl, bw)| bl > al + aw || bl + bw < al);
+            assert_eq!(a.intersects(&b), !apart, "{a:?} {b:?}");
+            let shared: Vec<f64> = axes
+                .iter()
+                .map(|&(al, aw, bl, bw)| (al + aw).min(bl + bw) - al.max(bl))
+                .collect();
+            let expected = if shared.iter().all(|&s| s > 0.0) {
+                shared.iter().product()
+            } else {
+                0.0
+            };
+            assert_eq!(BoundingVolume::overlap(&a, &b), expected);
+
+            let (ra, rb) = (
+                Rectangle {
+                    x: a.x,
+                    y: a.y,
+                    width: a.width,
+                    height: a.height,
+                },
+                Rectangle {
+                    x: b.x,
+                    y: b.y,
+                    width: b.width,
+                    height: b.height,
+                },
+            );
+            assert_eq!(
+                ra.intersects(&rb),
+                !axes[..2]
+                    .iter()
+                    .any(|&(al, aw, bl, bw)| bl > al + aw || bl + bw < al)
+            );
+            let expected_2d = if shared[..2].iter().all(|&s| s > 0.0) {
+                shared[0] * shared[1]
+            } else {
+                0.0
+            };
+            assert_eq!(BoundingVolume::overlap(&ra, &rb), expected_2d);
+        }
+    }
 }
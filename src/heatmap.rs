@@ -0,0 +1,398 @@
+//! ## Density Heatmaps
+//!
+//! This module provides `DensityGrid`, a raster of point counts over a rectangle, which shows
+//! at a glance how the indexed data is distributed (and so how deep the index gets where).
+//! Grids are built with `Quadtree::density_grid`, or filled point by point with `add`.
+//! `Quadtree::to_occupancy_grid` builds a grid of square cells of a given size over the whole
+//! tree, whose `occupancy` tells which cells contain points, as used by path planners.
+//!
+//! With the `image` feature enabled, a grid can be rendered to a PNG image with one of the
+//! `Colormap`s, and `Quadtree::render_heatmap_png` does both steps in one call. The encoder is
+//! built in and writes uncompressed images, so the feature pulls in no extra dependencies.
+//!
+//! Row 0 of a grid covers the smallest `y` values, so images show the data the way the trees
+//! see it (with "north" at the top) and geographic data with latitude as `y` appears upside
+//! down.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::heatmap::DensityGrid;
+//!
+//! let bounds = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+//! let mut grid = DensityGrid::new(&bounds, 2, 2);
+//! grid.add(&Point2D::new(1.0, 1.0, None::<()>));
+//! grid.add(&Point2D::new(2.0, 3.0, None::<()>));
+//! grid.add(&Point2D::new(10.0, 10.0, None::<()>));
+//! assert!(!grid.add(&Point2D::new(11.0, 1.0, None::<()>)));
+//!
+//! assert_eq!(grid.counts(), &[2, 0, 0, 1]);
+//! assert_eq!(grid.get(1, 1), Some(1));
+//! ```
+
+#[cfg(feature = "image")]
+use crate::errors::SpartError;
+use crate::geometry::{Point2D, Rectangle};
+#[cfg(feature = "image")]
+use std::path::Path;
+
+/// A grid of point counts over a rectangle, stored row by row.
+#[derive(Debug, Clone)]
+pub struct DensityGrid {
+    bounds: Rectangle,
+    width: usize,
+    height: usize,
+    counts: Vec<u32>,
+}
+
+impl DensityGrid {
+    /// Creates a grid of zero counts.
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds` - The rectangle covered by the grid.
+    /// * `width` - The number of columns (at least one).
+    /// * `height` - The number of rows (at least one).
+    pub fn new(bounds: &Rectangle, width: usize, height: usize) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        DensityGrid {
+            bounds: bounds.clone(),
+            width,
+            height,
+            counts: vec![0; width * height],
+        }
+    }
+
+    /// Counts a point in the cell it falls into.
+    ///
+    /// Points on the upper edges of the bounds count towards the last column or row.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point lies inside the bounds and was counted.
+    pub fn add<T>(&mut self, point: &Point2D<T>) -> bool {
+        if !self.bounds.contains(point) {
+            return false;
+        }
+        let column = Self::bin(point.x, self.bounds.x, self.bounds.width, self.width);
+        let row = Self::bin(point.y, self.bounds.y, self.bounds.height, self.height);
+        let count = &mut self.counts[row * self.width + column];
+        *count = count.saturating_add(1);
+        true
+    }
+
+    /// Returns the cell index of `value` along one axis.
+    fn bin(value: f64, start: f64, extent: f64, cells: usize) -> usize {
+        // The cast saturates, which also covers a zero extent.
+        (((value - start) / extent * cells as f64) as usize).min(cells - 1)
+    }
+
+    /// Returns the rectangle covered by the grid.
+    pub fn bounds(&self) -> &Rectangle {
+        &self.bounds
+    }
+
+    /// Returns the number of columns.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the number of rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the count of a cell, or `None` if it is outside the grid.
+    pub fn get(&self, column: usize, row: usize) -> Option<u32> {
+        if column >= self.width || row >= self.height {
+            return None;
+        }
+        Some(self.counts[row * self.width + column])
+    }
+
+    /// Returns all counts, row by row, starting with the row of the smallest `y` values.
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// Returns the largest count of any cell.
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the cell `(column, row)` a point falls into, or `None` if it lies outside the
+    /// bounds.
+    pub fn cell_of<T>(&self, point: &Point2D<T>) -> Option<(usize, usize)> {
+        if !self.bounds.contains(point) {
+            return None;
+        }
+        Some((
+            Self::bin(point.x, self.bounds.x, self.bounds.width, self.width),
+            Self::bin(point.y, self.bounds.y, self.bounds.height, self.height),
+        ))
+    }
+
+    /// Returns whether a cell holds any points, or `None` if it is outside the grid.
+    pub fn is_occupied(&self, column: usize, row: usize) -> Option<bool> {
+        self.get(column, row).map(|count| count > 0)
+    }
+
+    /// Returns for every cell, row by row, whether it holds any points, as used by occupancy
+    /// grids.
+    pub fn occupancy(&self) -> Vec<bool> {
+        self.counts.iter().map(|&count| count > 0).collect()
+    }
+}
+
+/// Picks up to `max_points` of the candidates inside `bbox`, preferring high priorities while
+/// spreading the picks over a grid of about `max_points` cells: the best candidate of every
+/// cell is picked before the second best of any cell, and so on, and each such round is picked
+/// in order of priority. Candidates outside of `bbox` are skipped, and NaN priorities rank
+/// last.
+pub(crate) fn thin_by_priority<T, P: std::borrow::Borrow<Point2D<T>>>(
+    bbox: &Rectangle,
+    candidates: impl IntoIterator<Item = P>,
+    max_points: usize,
+    priority: impl Fn(&Point2D<T>) -> f64,
+) -> Vec<P> {
+    if max_points == 0 {
+        return Vec::new();
+    }
+    let side = (max_points as f64).sqrt().ceil() as usize;
+    let grid = DensityGrid::new(bbox, side, side);
+    let mut cells: Vec<Vec<(f64, P)>> = (0..side * side).map(|_| Vec::new()).collect();
+    for candidate in candidates {
+        if let Some((column, row)) = grid.cell_of(candidate.borrow()) {
+            let rank = priority(candidate.borrow());
+            let rank = if rank.is_nan() {
+                f64::NEG_INFINITY
+            } else {
+                rank
+            };
+            cells[row * side + column].push((rank, candidate));
+        }
+    }
+    let mut cells: Vec<_> = cells
+        .into_iter()
+        .filter(|cell| !cell.is_empty())
+        .map(|mut cell| {
+            cell.sort_by(|a, b| b.0.total_cmp(&a.0));
+            cell.into_iter()
+        })
+        .collect();
+
+    let mut picked = Vec::with_capacity(max_points);
+    while picked.len() < max_points && !cells.is_empty() {
+        let mut round: Vec<(f64, P)> = cells.iter_mut().filter_map(Iterator::next).collect();
+        cells.retain(|cell| !cell.as_slice().is_empty());
+        round.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let take = round.len().min(max_points - picked.len());
+        picked.extend(round.into_iter().take(take).map(|(_, p)| p));
+    }
+    picked
+}
+
+/// The color scales available for rendering a `DensityGrid`.
+///
+/// Counts are scaled logarithmically before they are colored, so sparse areas stay visible
+/// next to dense clusters. Empty cells get the first color of the scale.
+#[cfg(feature = "image")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colormap {
+    /// From black (empty) to white (densest).
+    Grayscale,
+    /// From black through red and yellow to white.
+    #[default]
+    Heat,
+    /// The perceptually uniform scale from dark purple through teal to yellow.
+    Viridis,
+}
+
+#[cfg(feature = "image")]
+impl Colormap {
+    /// Returns the color for an intensity between 0 and 1.
+    pub fn color(self, intensity: f64) -> [u8; 3] {
+        let stops: &[[u8; 3]] = match self {
+            Colormap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+            Colormap::Heat => &[[0, 0, 0], [255, 0, 0], [255, 255, 0], [255, 255, 255]],
+            Colormap::Viridis => &[
+                [68, 1, 84],
+                [59, 82, 139],
+                [33, 145, 140],
+                [94, 201, 98],
+                [253, 231, 37],
+            ],
+        };
+        let position = intensity.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let index = (position as usize).min(stops.len() - 2);
+        let fraction = position - index as f64;
+        let (from, to) = (stops[index], stops[index + 1]);
+        std::array::from_fn(|c| {
+            (from[c] as f64 + (to[c] as f64 - from[c] as f64) * fraction).round() as u8
+        })
+    }
+}
+
+#[cfg(feature = "image")]
+impl DensityGrid {
+    /// Renders the grid as a PNG image with one pixel per cell.
+    ///
+    /// # Arguments
+    ///
+    /// * `colormap` - The color scale to use.
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the PNG file.
+    pub fn to_png(&self, colormap: Colormap) -> Vec<u8> {
+        let scale = (self.max() as f64).ln_1p();
+        let mut pixels = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.counts.chunks(self.width) {
+            // Each row starts with its filter type, which is "none".
+            pixels.push(0);
+            for &count in row {
+                let intensity = if scale > 0.0 {
+                    (count as f64).ln_1p() / scale
+                } else {
+                    0.0
+                };
+                pixels.extend(colormap.color(intensity));
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filtering, and no interlacing.
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&pixels));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Renders the grid as a PNG image (see `to_png`) and writes it to a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the file cannot be written.
+    pub fn write_png(&self, path: impl AsRef<Path>, colormap: Colormap) -> Result<(), SpartError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_png(colormap)).map_err(|e| SpartError::Persistence {
+            reason: format!("cannot write image {}: {e}", path.display()),
+        })
+    }
+}
+
+/// Appends a PNG chunk with its length and checksum.
+#[cfg(feature = "image")]
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Wraps data in a zlib stream of uncompressed ("stored") deflate blocks.
+#[cfg(feature = "image")]
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    out.extend([0x78, 0x01]);
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(feature = "image")]
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(feature = "image")]
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(all(test, feature = "image"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_layout_and_checksums() {
+        // Known values from the zlib and PNG specifications.
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+
+        let bounds = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 300.0,
+            height: 300.0,
+        };
+        // Large enough to need several stored blocks.
+        let mut grid = DensityGrid::new(&bounds, 300, 300);
+        grid.add(&Point2D::new(0.0, 0.0, None::<()>));
+        let png = grid.to_png(Colormap::Grayscale);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 1, 44, 0, 0, 1, 44]);
+        assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        let zlib = &png[41..41 + idat_len];
+        let mut pixels = Vec::new();
+        let mut at = 2;
+        loop {
+            let last = zlib[at] == 1;
+            let len = u16::from_le_bytes([zlib[at + 1], zlib[at + 2]]) as usize;
+            pixels.extend(&zlib[at + 5..at + 5 + len]);
+            at += 5 + len;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(pixels.len(), 300 * (300 * 3 + 1));
+        assert_eq!(&pixels[..7], &[0, 255, 255, 255, 0, 0, 0]);
+        assert_eq!(&zlib[at..], &adler32(&pixels).to_be_bytes());
+
+        assert_eq!(Colormap::Heat.color(0.0), [0, 0, 0]);
+        assert_eq!(Colormap::Heat.color(0.5), [255, 128, 0]);
+        assert_eq!(Colormap::Viridis.color(2.0), [253, 231, 37]);
+    }
+}
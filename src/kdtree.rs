@@ -25,14 +25,29 @@
 //! assert!(!neighbors3d.is_empty());
 //! ```
 
-use std::{cmp::Ordering, collections::BinaryHeap};
-
 use ordered_float::OrderedFloat;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::ops::RangeBounds;
+
+use crate::logging::hot_trace;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
-use crate::{errors::SpartError, geometry::DistanceMetric};
+use crate::{
+    covariance::{Covariance, Neighborhood},
+    elevation::{Elevation, in_z_range},
+    errors::{SpartError, check_knn_query, check_query_coords, check_range_query},
+    geometry::{DistanceMetric, Point2D},
+    knn::{
+        DistinctKnnResults, ExcludingSink, KnnGraph, KnnResults, KnnSink, LimitedResults,
+        QueryContext,
+    },
+    random::{RandomSource, sample_indices},
+    stats::{NnDistanceStats, TreeStats, TreeSummary},
+    testing::same_multiset,
+};
 
 /// Trait representing a point that can be stored in the Kd‑tree implementation.
 ///
@@ -48,6 +63,20 @@ pub trait KdPoint: Clone + PartialEq + std::fmt::Debug {
     ///
     /// Returns `SpartError::InvalidDimension` if the axis is invalid.
     fn coord(&self, axis: usize) -> Result<f64, SpartError>;
+
+    /// Returns `true` if `other` matches this point within `tolerance`.
+    ///
+    /// The default implementation accepts equal points, and points whose coordinates differ by
+    /// at most `tolerance` along every axis. It cannot see any payload, so types that carry one
+    /// should override it to compare the payloads as well.
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self == other
+            || (self.dims() == other.dims()
+                && (0..self.dims()).all(|axis| match (self.coord(axis), other.coord(axis)) {
+                    (Ok(a), Ok(b)) => (a - b).abs() <= tolerance,
+                    _ => false,
+                }))
+    }
 }
 
 impl<T> KdPoint for crate::geometry::Point2D<T>
@@ -57,6 +86,9 @@ where
     fn dims(&self) -> usize {
         2
     }
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        crate::geometry::Point2D::approx_eq(self, other, tolerance)
+    }
     fn coord(&self, axis: usize) -> Result<f64, SpartError> {
         match axis {
             0 => Ok(self.x),
@@ -76,6 +108,9 @@ where
     fn dims(&self) -> usize {
         3
     }
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        crate::geometry::Point3D::approx_eq(self, other, tolerance)
+    }
     fn coord(&self, axis: usize) -> Result<f64, SpartError> {
         match axis {
             0 => Ok(self.x),
@@ -89,30 +124,21 @@ where
     }
 }
 
-/// Internal structure used to store items in the k‑nearest neighbor heap.
-#[derive(Debug, Clone)]
-struct HeapItem<P> {
-    dist: OrderedFloat<f64>,
-    point: P,
-}
-
-impl<P> PartialEq for HeapItem<P> {
-    fn eq(&self, other: &Self) -> bool {
-        self.dist.eq(&other.dist)
-    }
-}
-
-impl<P> Eq for HeapItem<P> {}
-
-impl<P> PartialOrd for HeapItem<P> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl<T, const D: usize> KdPoint for crate::geometry::PointND<T, D>
+where
+    T: std::fmt::Debug + Clone + PartialEq,
+{
+    fn dims(&self) -> usize {
+        D
     }
-}
-
-impl<P> Ord for HeapItem<P> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.dist.cmp(&other.dist)
+    fn coord(&self, axis: usize) -> Result<f64, SpartError> {
+        self.coords
+            .get(axis)
+            .copied()
+            .ok_or(SpartError::InvalidDimension {
+                requested: axis,
+                available: D,
+            })
     }
 }
 
@@ -136,6 +162,48 @@ impl<P: KdPoint> KdNode<P> {
     }
 }
 
+/// The result of a k‑nearest neighbor query, kept around to warm-start the next query.
+///
+/// Returned by `KdTree::knn_search_state` and `KdTree::knn_search_from`.
+#[derive(Debug, Clone)]
+pub struct KnnState<P> {
+    query: P,
+    neighbors: Vec<P>,
+}
+
+impl<P> KnnState<P> {
+    /// Returns the query point this state was computed for.
+    pub fn query(&self) -> &P {
+        &self.query
+    }
+
+    /// Returns the neighbors found for the query, ordered from nearest to farthest.
+    pub fn neighbors(&self) -> &[P] {
+        &self.neighbors
+    }
+
+    /// Consumes the state and returns the neighbors, ordered from nearest to farthest.
+    pub fn into_neighbors(self) -> Vec<P> {
+        self.neighbors
+    }
+}
+
+/// How `KdTree::delete` restructures the subtree below a deleted point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DeleteStrategy {
+    /// Replace the deleted point with the point of its subtree that is nearest to it along the
+    /// node's axis, and repeat the deletion for that point further down (the default). This
+    /// touches one path per level, but its correctness depends on finding the right successor.
+    #[default]
+    Promote,
+    /// Collect the other points of the deleted point's subtree and rebuild the subtree from
+    /// them with median splits. This takes time proportional to the size of the subtree (the
+    /// whole tree when deleting the root), but it only relies on the same construction as
+    /// `insert_bulk`, so the tree invariants are preserved by design.
+    Rebuild,
+}
+
 /// Kd‑tree for points implementing `KdPoint`.
 ///
 /// The tree stores points in k‑dimensional space (where `k` is provided during creation)
@@ -145,6 +213,10 @@ impl<P: KdPoint> KdNode<P> {
 pub struct KdTree<P: KdPoint> {
     root: Option<Box<KdNode<P>>>,
     k: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    tolerance: f64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    delete_strategy: DeleteStrategy,
 }
 
 impl<P: KdPoint> Default for KdTree<P> {
@@ -159,6 +231,8 @@ impl<P: KdPoint> KdTree<P> {
         KdTree {
             root: None,
             k: None,
+            tolerance: 0.0,
+            delete_strategy: DeleteStrategy::Promote,
         }
     }
 
@@ -167,23 +241,96 @@ impl<P: KdPoint> KdTree<P> {
         KdTree {
             root: None,
             k: Some(k),
+            tolerance: 0.0,
+            delete_strategy: DeleteStrategy::Promote,
         }
     }
 
-    /// Returns true if the exact point exists in the tree.
+    /// Sets the coordinate tolerance used to match points in `contains`, `delete`, and `update`.
+    ///
+    /// With a positive tolerance, a stored point matches if `KdPoint::approx_eq` accepts it, so
+    /// points whose coordinates were rounded (e.g., by a serialization round trip) can still be
+    /// found. Negative and NaN values are treated as zero, which means exact matching (the
+    /// default).
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - The maximum allowed difference per coordinate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::kdtree::KdTree;
+    ///
+    /// let mut tree = KdTree::new().with_tolerance(1e-9);
+    /// tree.insert(Point2D::new(0.1 + 0.2, 1.0, Some("a"))).unwrap();
+    /// assert!(tree.contains(&Point2D::new(0.3, 1.0, Some("a"))));
+    /// ```
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance.max(0.0);
+        self
+    }
+
+    /// Returns the coordinate tolerance used to match points.
+    pub fn tolerance(&self) -> f64 {
+        self.tolerance
+    }
+
+    /// Sets how `delete` (and `update`) restructure the tree below a deleted point.
+    ///
+    /// `DeleteStrategy::Rebuild` is slower than the default, but it is the safer choice for
+    /// applications where a missed point would be worse than a slow deletion.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The delete strategy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::kdtree::{DeleteStrategy, KdTree};
+    ///
+    /// let mut tree = KdTree::new().with_delete_strategy(DeleteStrategy::Rebuild);
+    /// for i in 0..10 {
+    ///     tree.insert(Point2D::new(i as f64, (i % 3) as f64, Some(i))).unwrap();
+    /// }
+    /// assert!(tree.delete(&Point2D::new(0.0, 0.0, Some(0))));
+    /// assert!(tree.check_invariants().is_ok());
+    /// ```
+    pub fn with_delete_strategy(mut self, strategy: DeleteStrategy) -> Self {
+        self.delete_strategy = strategy;
+        self
+    }
+
+    /// Returns the delete strategy of the tree.
+    pub fn delete_strategy(&self) -> DeleteStrategy {
+        self.delete_strategy
+    }
+
+    /// Returns true if the point exists in the tree.
+    ///
+    /// Points match if they are equal, or within the tree's tolerance (see `with_tolerance`).
     pub fn contains(&self, point: &P) -> bool {
         let k = match self.k {
             Some(k) => k,
             None => return false,
         };
-        Self::contains_rec(&self.root, point, 0, k)
+        Self::contains_rec(&self.root, point, 0, k, self.tolerance)
     }
 
-    fn contains_rec(node: &Option<Box<KdNode<P>>>, point: &P, depth: usize, k: usize) -> bool {
+    fn contains_rec(
+        node: &Option<Box<KdNode<P>>>,
+        point: &P,
+        depth: usize,
+        k: usize,
+        tolerance: f64,
+    ) -> bool {
         match node {
             None => false,
             Some(n) => {
-                if n.point == *point {
+                if n.point.approx_eq(point, tolerance) {
                     return true;
                 }
                 let axis = depth % k;
@@ -194,14 +341,14 @@ impl<P: KdPoint> KdTree<P> {
                     .point
                     .coord(axis)
                     .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-                if p_coord < c_coord {
-                    Self::contains_rec(&n.left, point, depth + 1, k)
-                } else if p_coord > c_coord {
-                    Self::contains_rec(&n.right, point, depth + 1, k)
+                if p_coord + tolerance < c_coord {
+                    Self::contains_rec(&n.left, point, depth + 1, k, tolerance)
+                } else if p_coord - tolerance > c_coord {
+                    Self::contains_rec(&n.right, point, depth + 1, k, tolerance)
                 } else {
-                    // Equal on this axis, could be in either subtree.
-                    Self::contains_rec(&n.right, point, depth + 1, k)
-                        || Self::contains_rec(&n.left, point, depth + 1, k)
+                    // Equal on this axis (within tolerance), could be in either subtree.
+                    Self::contains_rec(&n.right, point, depth + 1, k, tolerance)
+                        || Self::contains_rec(&n.left, point, depth + 1, k, tolerance)
                 }
             }
         }
@@ -236,7 +383,7 @@ impl<P: KdPoint> KdTree<P> {
                 k
             }
         };
-        info!("Inserting point: {:?}", point);
+        hot_trace!("Inserting point into KdTree", point = point);
         self.root = Some(Self::insert_rec(self.root.take(), point, 0, k));
         Ok(())
     }
@@ -273,32 +420,41 @@ impl<P: KdPoint> KdTree<P> {
             }
         }
 
-        if self.root.is_some() {
-            let mut existing = Vec::new();
-            Self::collect_points(&self.root, &mut existing);
-            points.extend(existing);
-        }
+        let existing = self.root.take();
+        Self::drain_points(existing, &mut points);
 
         // Pass k explicitly to avoid unwraps inside recursion
-        self.root = Self::insert_bulk_rec(&mut points[..], 0, k);
+        self.root = Self::insert_bulk_rec(points, 0, k);
         Ok(())
     }
 
-    fn collect_points(node: &Option<Box<KdNode<P>>>, result: &mut Vec<P>) {
+    /// Consumes the tree, returning its points in no particular order.
+    pub(crate) fn into_points(self) -> Vec<P> {
+        let mut points = Vec::new();
+        Self::drain_points(self.root, &mut points);
+        points
+    }
+
+    /// Moves all points out of the given subtree into `result`.
+    fn drain_points(node: Option<Box<KdNode<P>>>, result: &mut Vec<P>) {
         if let Some(n) = node {
-            result.push(n.point.clone());
-            Self::collect_points(&n.left, result);
-            Self::collect_points(&n.right, result);
+            let KdNode { point, left, right } = *n;
+            result.push(point);
+            Self::drain_points(left, result);
+            Self::drain_points(right, result);
         }
     }
 
-    fn insert_bulk_rec(points: &mut [P], depth: usize, k: usize) -> Option<Box<KdNode<P>>> {
+    fn insert_bulk_rec(mut points: Vec<P>, depth: usize, k: usize) -> Option<Box<KdNode<P>>> {
         if points.is_empty() {
             return None;
         }
 
         let axis = depth % k;
-        points.sort_by(|a, b| {
+        let median_idx = points.len() / 2;
+        // Partition around the median in linear time; everything before it is <= and
+        // everything after it is >= on this axis, which is all the tree invariant needs.
+        points.select_nth_unstable_by(median_idx, |a, b| {
             let ac = a
                 .coord(axis)
                 .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
@@ -307,14 +463,15 @@ impl<P: KdPoint> KdTree<P> {
                 .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
             ac.partial_cmp(&bc).unwrap_or(Ordering::Equal)
         });
-        let median_idx = points.len() / 2;
 
-        let mut node = KdNode::new(points[median_idx].clone());
-        let (left_slice, right_slice) = points.split_at_mut(median_idx);
-        let right_slice = &mut right_slice[1..];
+        let right_points = points.split_off(median_idx + 1);
+        let median = points
+            .pop()
+            .unwrap_or_else(|| unreachable!("median index is within a non-empty vector"));
 
-        node.left = Self::insert_bulk_rec(left_slice, depth + 1, k);
-        node.right = Self::insert_bulk_rec(right_slice, depth + 1, k);
+        let mut node = KdNode::new(median);
+        node.left = Self::insert_bulk_rec(points, depth + 1, k);
+        node.right = Self::insert_bulk_rec(right_points, depth + 1, k);
 
         Some(Box::new(node))
     }
@@ -345,198 +502,1311 @@ impl<P: KdPoint> KdTree<P> {
         }
     }
 
-    /// Performs a k‑nearest neighbor search for the given target point.
+    /// Returns the stored point with the smallest coordinate along `axis`.
+    ///
+    /// At nodes that split on `axis`, only the subtree holding the smaller coordinates is
+    /// searched, so a balanced tree is searched in roughly `O(n^(1 - 1/k))` time. If several
+    /// points share the smallest coordinate, any one of them is returned.
     ///
     /// # Arguments
     ///
-    /// * `target` - The point to search around.
-    /// * `k_neighbors` - The number of nearest neighbors to retrieve.
+    /// * `axis` - The axis to compare.
     ///
     /// # Returns
     ///
-    /// A vector of the nearest points, ordered from nearest to farthest.
-    pub fn knn_search<M: DistanceMetric<P>>(&self, target: &P, k_neighbors: usize) -> Vec<P> {
-        if k_neighbors == 0 {
-            return Vec::new();
-        }
+    /// The point, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is not below the tree's dimension.
+    pub fn min_by_axis(&self, axis: usize) -> Result<Option<&P>, SpartError> {
+        self.extreme_by_axis(axis, false)
+    }
+
+    /// Returns the stored point with the largest coordinate along `axis`.
+    ///
+    /// This is the counterpart of `min_by_axis` and prunes the tree the same way.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The axis to compare.
+    ///
+    /// # Returns
+    ///
+    /// The point, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is not below the tree's dimension.
+    pub fn max_by_axis(&self, axis: usize) -> Result<Option<&P>, SpartError> {
+        self.extreme_by_axis(axis, true)
+    }
+
+    fn extreme_by_axis(&self, axis: usize, max: bool) -> Result<Option<&P>, SpartError> {
         let k = match self.k {
             Some(k) => k,
-            None => return Vec::new(),
+            None => return Ok(None),
         };
-        if target.dims() != k {
-            return Vec::new();
+        if axis >= k {
+            return Err(SpartError::InvalidDimension {
+                requested: axis,
+                available: k,
+            });
         }
-        info!(
-            "Performing k‑NN search for target {:?} with k={}",
-            target, k_neighbors
-        );
-        let mut heap: BinaryHeap<HeapItem<P>> = BinaryHeap::new();
-        Self::knn_search_rec::<M>(&self.root, target, k_neighbors, 0, &mut heap);
-        let mut result: Vec<(f64, P)> = heap
-            .into_iter()
-            .map(|item| (item.dist.into_inner(), item.point))
-            .collect();
-        result.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
-        result.into_iter().map(|(_d, p)| p).collect()
+        let mut best = None;
+        Self::extreme_rec(&self.root, axis, max, 0, k, &mut best);
+        Ok(best.map(|(_, point)| point))
     }
 
-    fn knn_search_rec<M: DistanceMetric<P>>(
-        node: &Option<Box<KdNode<P>>>,
-        target: &P,
-        k_neighbors: usize,
+    /// Updates `best` with the points of the subtree that beat it along `axis`.
+    ///
+    /// Keys are negated when looking for the minimum, so a larger key is always better.
+    fn extreme_rec<'a>(
+        node: &'a Option<Box<KdNode<P>>>,
+        axis: usize,
+        max: bool,
         depth: usize,
-        heap: &mut BinaryHeap<HeapItem<P>>,
+        k: usize,
+        best: &mut Option<(f64, &'a P)>,
     ) {
-        if let Some(n) = node {
-            let dist_sq = M::distance_sq(target, &n.point);
-            let dist = OrderedFloat(dist_sq);
-            if heap.len() < k_neighbors {
-                heap.push(HeapItem {
-                    dist,
-                    point: n.point.clone(),
-                });
-            } else if let Some(top) = heap.peek() {
-                if dist < top.dist {
-                    heap.pop();
-                    heap.push(HeapItem {
-                        dist,
-                        point: n.point.clone(),
-                    });
-                }
-            }
-            let axis = depth % target.dims();
-            let target_coord = target
-                .coord(axis)
-                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-            let node_coord = n
-                .point
-                .coord(axis)
-                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-            let (first, second) = if target_coord < node_coord {
-                (&n.left, &n.right)
+        let Some(n) = node else {
+            return;
+        };
+        let coord = n
+            .point
+            .coord(axis)
+            .unwrap_or_else(|_| unreachable!("axis checked by extreme_by_axis"));
+        let key = if max { coord } else { -coord };
+        if best.is_none_or(|(best_key, _)| key > best_key) {
+            *best = Some((key, &n.point));
+        }
+        let (near, far) = if max {
+            (&n.right, &n.left)
+        } else {
+            (&n.left, &n.right)
+        };
+        Self::extreme_rec(near, axis, max, depth + 1, k, best);
+        // When splitting on `axis`, the far subtree holds no coordinate beyond this node's.
+        if depth % k != axis {
+            Self::extreme_rec(far, axis, max, depth + 1, k, best);
+        }
+    }
+
+    /// Picks initial centroids for k-means clustering with the k-means++ strategy.
+    ///
+    /// The first centroid is a stored point chosen uniformly at random; each further one is a
+    /// stored point chosen with probability proportional to its squared Euclidean distance to
+    /// the nearest centroid picked so far. These distances are kept per node, together with
+    /// the largest and the total distance of every subtree, so adding a centroid only visits
+    /// subtrees whose cells are close enough to it to hold a point that gets closer, and each
+    /// pick walks a single path down the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - The number of centroids to pick.
+    /// * `rng` - The source of randomness (e.g., `random::SplitMix64`).
+    ///
+    /// # Returns
+    ///
+    /// The centroids, in the order they were picked. Fewer than `k` centroids are returned if
+    /// the tree holds fewer than `k` distinct points.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::kdtree::KdTree;
+    /// use spart::random::SplitMix64;
+    ///
+    /// let mut tree = KdTree::new();
+    /// for i in 0..50 {
+    ///     let offset = if i % 2 == 0 { 0.0 } else { 100.0 };
+    ///     tree.insert(Point2D::new(offset + (i % 5) as f64, offset, Some(i))).unwrap();
+    /// }
+    /// let centroids = tree.kmeans_pp_seed(2, &mut SplitMix64::new(1));
+    /// let assignment = tree.assign_to_centroids(&centroids).unwrap();
+    /// assert_eq!(assignment.len(), 50);
+    /// ```
+    pub fn kmeans_pp_seed<R: RandomSource>(&self, k: usize, rng: &mut R) -> Vec<P> {
+        let Some(mut flat) = FlatTree::new(self) else {
+            return Vec::new();
+        };
+        let mut centroids = Vec::with_capacity(k.min(flat.points.len()));
+        while centroids.len() < k {
+            let index = if centroids.is_empty() {
+                rng.next_below(flat.points.len())
             } else {
-                (&n.right, &n.left)
+                let total = flat.sub_sum[0];
+                // Every remaining point coincides with a centroid.
+                if total <= 0.0 || total.is_nan() {
+                    break;
+                }
+                flat.sample(rng.next_f64() * total)
             };
-            Self::knn_search_rec::<M>(first, target, k_neighbors, depth + 1, heap);
-            let diff = (target_coord - node_coord).abs();
-            let diff_sq = diff * diff;
-            if heap.len() < k_neighbors
-                || heap
-                    .peek()
-                    .map(|h| diff_sq < h.dist.into_inner())
-                    .unwrap_or(true)
-            {
-                Self::knn_search_rec::<M>(second, target, k_neighbors, depth + 1, heap);
-            }
+            let centroid = flat.points[index];
+            flat.add_centroid(centroid);
+            centroids.push(centroid.clone());
         }
+        info!("Picked {} k-means++ centroids", centroids.len());
+        centroids
     }
 
-    /// Performs a range search, returning all points within the specified radius of the center.
+    /// Assigns every stored point to its nearest centroid (by Euclidean distance).
+    ///
+    /// The tree is traversed once while the list of candidate centroids is narrowed for each
+    /// node's cell: a centroid is dropped when it is farther from every position in the cell
+    /// than another centroid is from the farthest one. Once a single candidate is left, the
+    /// whole subtree is assigned to it without computing any distance.
     ///
     /// # Arguments
     ///
-    /// * `center` - The center of the search.
-    /// * `radius` - The search radius.
+    /// * `centroids` - The centroids, e.g. from `kmeans_pp_seed`.
     ///
     /// # Returns
     ///
-    /// A vector of points within the specified radius.
-    pub fn range_search<M: DistanceMetric<P>>(&self, center: &P, radius: f64) -> Vec<P> {
-        info!("Finding points within radius {} of {:?}", radius, center);
-        let k = match self.k {
-            Some(k) => k,
-            None => return Vec::new(),
+    /// A pair of each stored point and the index of its nearest centroid in `centroids`, in
+    /// no particular order. Ties go to the lowest index. The result is empty if there are no
+    /// centroids.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::DimensionMismatch` if a centroid does not match the tree's
+    /// dimension.
+    pub fn assign_to_centroids(&self, centroids: &[P]) -> Result<Vec<(&P, usize)>, SpartError> {
+        if let Some(k) = self.k {
+            if let Some(c) = centroids.iter().find(|c| c.dims() != k) {
+                return Err(SpartError::DimensionMismatch {
+                    expected: k,
+                    actual: c.dims(),
+                });
+            }
+        }
+        let Some(flat) = FlatTree::new(self) else {
+            return Ok(Vec::new());
         };
-        if center.dims() != k {
-            return Vec::new();
+        if centroids.is_empty() {
+            return Ok(Vec::new());
         }
-        let mut found = Vec::new();
-        let radius_sq = radius * radius;
-        Self::range_search_rec::<M>(&self.root, center, radius_sq, 0, radius, &mut found);
-        found
+        let mut assignment = Vec::with_capacity(flat.points.len());
+        let (mut lo, mut hi) = (flat.lo.clone(), flat.hi.clone());
+        let candidates = (0..centroids.len()).collect();
+        flat.assign(0, &mut lo, &mut hi, candidates, centroids, &mut assignment);
+        Ok(assignment)
     }
 
-    fn range_search_rec<M: DistanceMetric<P>>(
-        node: &Option<Box<KdNode<P>>>,
-        center: &P,
-        radius_sq: f64,
-        depth: usize,
-        radius: f64,
-        found: &mut Vec<P>,
-    ) {
-        if let Some(n) = node {
-            let dist_sq = M::distance_sq(center, &n.point);
-            if dist_sq <= radius_sq {
-                found.push(n.point.clone());
-            }
-            let axis = depth % center.dims();
-            let center_coord = center
-                .coord(axis)
-                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-            let node_coord = n
-                .point
-                .coord(axis)
-                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-            if center_coord - radius <= node_coord {
-                Self::range_search_rec::<M>(&n.left, center, radius_sq, depth + 1, radius, found);
+    /// Assigns every demand point to its nearest stored point (e.g., its nearest facility), by
+    /// Euclidean distance.
+    ///
+    /// Instead of one nearest-neighbor query per demand point, the demand points are arranged
+    /// in a temporary Kd-tree and matched with this tree in a single dual-tree traversal (as in
+    /// `self_knn`).
+    ///
+    /// # Arguments
+    ///
+    /// * `demand` - The points to assign.
+    ///
+    /// # Returns
+    ///
+    /// For each demand point, in the order of `demand`, its nearest stored point and the
+    /// distance to it. Entries are `None` if the tree is empty or the demand point has a NaN
+    /// coordinate. Ties go to either point.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::DimensionMismatch` if a demand point does not match the tree's
+    /// dimension.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::kdtree::KdTree;
+    ///
+    /// let mut depots = KdTree::new();
+    /// depots.insert(Point2D::new(0.0, 0.0, Some("west"))).unwrap();
+    /// depots.insert(Point2D::new(10.0, 0.0, Some("east"))).unwrap();
+    /// let stores = [Point2D::new(2.0, 0.0, None), Point2D::new(7.0, 0.0, None)];
+    /// let assignment = depots.assign_nearest(&stores).unwrap();
+    /// assert_eq!(assignment[0].map(|(depot, d)| (depot.data, d)), Some((Some("west"), 2.0)));
+    /// assert_eq!(assignment[1].map(|(depot, d)| (depot.data, d)), Some((Some("east"), 3.0)));
+    /// ```
+    pub fn assign_nearest(&self, demand: &[P]) -> Result<Vec<Option<(&P, f64)>>, SpartError> {
+        self.assign_nearest_capacitated(demand, |_| usize::MAX)
+    }
+
+    /// Like `assign_nearest`, but each stored point can serve a limited number of demand
+    /// points.
+    ///
+    /// The assignment is greedy: the demand point and stored point with spare capacity that
+    /// are closest to each other are matched first, then the next closest pair, and so on.
+    /// This is not guaranteed to minimize the total distance, but no demand point is assigned
+    /// farther than necessary at the time it is matched.
+    ///
+    /// # Arguments
+    ///
+    /// * `demand` - The points to assign.
+    /// * `capacity` - The number of demand points each stored point can serve.
+    ///
+    /// # Returns
+    ///
+    /// For each demand point, in the order of `demand`, the stored point it was assigned to and
+    /// the distance to it. Entries are also `None` for demand points left over once every
+    /// stored point is at capacity.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::DimensionMismatch` if a demand point does not match the tree's
+    /// dimension.
+    pub fn assign_nearest_capacitated(
+        &self,
+        demand: &[P],
+        capacity: impl Fn(&P) -> usize,
+    ) -> Result<Vec<Option<(&P, f64)>>, SpartError> {
+        let mut assignment = vec![None; demand.len()];
+        let (Some(k), Some(reference)) = (self.k, Cells::of_tree(self)) else {
+            return Ok(assignment);
+        };
+        if let Some(p) = demand.iter().find(|p| p.dims() != k) {
+            return Err(SpartError::DimensionMismatch {
+                expected: k,
+                actual: p.dims(),
+            });
+        }
+        let mut remaining: Vec<usize> = reference.points.iter().map(|p| capacity(p)).collect();
+        let full: Vec<bool> = remaining.iter().map(|&c| c == 0).collect();
+        let (query, ids) = Cells::of_points(demand, k);
+        let mut search = NearestAssignment::new(query, reference, &full);
+        search.search_all();
+
+        // Matches are made in order of distance; a demand point whose nearest stored point
+        // filled up in the meantime looks for the nearest one left and goes back in the queue.
+        let mut queue: BinaryHeap<Reverse<(OrderedFloat<f64>, usize, usize)>> = search
+            .best
+            .iter()
+            .enumerate()
+            .filter_map(|(q, &(dist_sq, r))| Some(Reverse((OrderedFloat(dist_sq), q, r?))))
+            .collect();
+        while let Some(Reverse((OrderedFloat(dist_sq), q, r))) = queue.pop() {
+            if remaining[r] == 0 {
+                search.research(q);
+                if let (dist_sq, Some(r)) = search.best[q] {
+                    queue.push(Reverse((OrderedFloat(dist_sq), q, r)));
+                }
+                continue;
             }
-            if center_coord + radius >= node_coord {
-                Self::range_search_rec::<M>(&n.right, center, radius_sq, depth + 1, radius, found);
+            assignment[ids[q]] = Some((search.reference.points[r], dist_sq.sqrt()));
+            remaining[r] -= 1;
+            if remaining[r] == 0 {
+                search.close(r);
             }
         }
+        Ok(assignment)
     }
 
-    /// Deletes a point from the Kd‑tree.
+    /// Summarizes the nearest-neighbor distances of a random sample of the stored points.
+    ///
+    /// For each sampled point, the distance to its nearest other stored point is found with a
+    /// kNN query on this tree, so the cost is about `sample_size` queries rather than a scan
+    /// per point. Distances are Euclidean.
     ///
     /// # Arguments
     ///
-    /// * `point` - The point to delete.
+    /// * `sample_size` - The number of points to sample; all points are used if the tree holds
+    ///   fewer.
+    /// * `rng` - The source of randomness used to draw the sample (e.g., `random::SplitMix64`).
     ///
     /// # Returns
     ///
-    /// `true` if the point was found and deleted, otherwise `false`.
-    pub fn delete(&mut self, point: &P) -> bool {
-        if self.root.is_none() {
-            return false;
-        }
-        info!("Attempting to delete point: {:?}", point);
-        let k = match self.k {
-            Some(k) => k,
-            None => return false,
-        };
-        let (new_root, deleted) = Self::delete_rec(self.root.take(), point, 0, k);
-        self.root = new_root;
-        if self.root.is_none() {
-            self.k = None;
+    /// The summary, or `None` if the tree holds fewer than two points or `sample_size` is zero.
+    pub fn nn_distance_stats<R: RandomSource>(
+        &self,
+        sample_size: usize,
+        rng: &mut R,
+    ) -> Option<NnDistanceStats> {
+        let mut points = Vec::new();
+        Self::collect_points(&self.root, &mut points);
+        if points.len() < 2 {
+            return None;
         }
-        deleted
+        let distances = sample_indices(points.len(), sample_size, rng)
+            .into_iter()
+            .filter_map(|i| {
+                let point = points[i];
+                // The nearest result is the point itself (or a copy of it at distance zero).
+                let neighbors = self.knn_search::<CoordinateDistance>(point, 2);
+                neighbors
+                    .get(1)
+                    .map(|n| CoordinateDistance::distance_sq(point, n).sqrt())
+            })
+            .collect();
+        NnDistanceStats::from_distances(distances)
     }
 
-    fn delete_rec(
-        node: Option<Box<KdNode<P>>>,
+    /// Computes the covariance of the neighborhood of a point, e.g., to estimate the surface
+    /// normal or curvature of a point cloud there (see the `covariance` module).
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The query point.
+    /// * `neighborhood` - The `k` nearest points or the points within a radius of `point`.
+    ///
+    /// # Returns
+    ///
+    /// The covariance of the neighbors, or `None` if there are none within the radius.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `try_knn_search` or `try_range_search`: `SpartError::InvalidK`
+    /// for `k == 0`, `SpartError::EmptyTree` for a kNN neighborhood in an empty tree,
+    /// `SpartError::InvalidRadius` for a negative radius, and `SpartError::InvalidCoordinates`
+    /// if `point` has a NaN coordinate.
+    pub fn neighborhood_covariance(
+        &self,
         point: &P,
-        depth: usize,
-        k: usize,
-    ) -> (Option<Box<KdNode<P>>>, bool) {
-        match node {
-            None => (None, false),
-            Some(mut current) => {
-                let axis = depth % k;
-                if current.point == *point {
-                    // Delete a single instance: replace with successor from right subtree if available,
-                    // otherwise promote left subtree, or remove leaf.
+        neighborhood: Neighborhood,
+    ) -> Result<Option<Covariance>, SpartError> {
+        let neighbors = match neighborhood {
+            Neighborhood::Knn(k) => self.try_knn_search::<CoordinateDistance>(point, k)?,
+            Neighborhood::Radius(radius) => {
+                self.try_range_search::<CoordinateDistance>(point, radius)?
+            }
+        };
+        Ok(Covariance::from_points(&neighbors))
+    }
+
+    /// Computes the covariance of the neighborhood of each of several points, as
+    /// `neighborhood_covariance` does for one, e.g., to estimate the normals of a whole point
+    /// cloud in one call.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The query points.
+    /// * `neighborhood` - The neighborhood of each query point.
+    ///
+    /// # Returns
+    ///
+    /// The covariance of the neighbors of each point, in the order of `points`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error `neighborhood_covariance` returns.
+    pub fn neighborhood_covariances(
+        &self,
+        points: &[P],
+        neighborhood: Neighborhood,
+    ) -> Result<Vec<Option<Covariance>>, SpartError> {
+        points
+            .iter()
+            .map(|point| self.neighborhood_covariance(point, neighborhood))
+            .collect()
+    }
+
+    /// Finds the `k` nearest other stored points of every stored point (by Euclidean
+    /// distance).
+    ///
+    /// Instead of one kNN query per point, all points are matched in a single dual-tree
+    /// traversal: pairs of subtrees are visited together, and a pair is skipped when the
+    /// subtrees' bounding boxes are farther apart than the current `k`-th neighbor of every
+    /// point in the first one.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - The number of neighbors per point.
+    ///
+    /// # Returns
+    ///
+    /// The neighbor lists of all stored points. Copies of a point at distance zero count as
+    /// its neighbors, the point itself does not.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::kdtree::KdTree;
+    ///
+    /// let mut tree = KdTree::new();
+    /// for i in 0..10 {
+    ///     tree.insert(Point2D::new(i as f64, 0.0, Some(i))).unwrap();
+    /// }
+    /// let graph = tree.self_knn(2);
+    /// assert_eq!(graph.len(), 10);
+    /// for (i, point) in graph.points().iter().enumerate() {
+    ///     let neighbors = graph.neighbors(i);
+    ///     assert_eq!(neighbors.len(), 2);
+    ///     assert_eq!(neighbors[0].1, 1.0);
+    ///     assert!(graph.points()[neighbors[0].0].x != point.x);
+    /// }
+    /// ```
+    pub fn self_knn(&self, k: usize) -> KnnGraph<P> {
+        let Some(mut dual) = DualTree::new(self, k) else {
+            return KnnGraph::new(k, Vec::new(), Vec::new());
+        };
+        if k > 0 {
+            dual.search(0, 0);
+        }
+        let neighbors = dual
+            .results
+            .into_iter()
+            .map(|results| {
+                results
+                    .into_sorted_vec()
+                    .into_iter()
+                    .map(|(j, dist_sq): (usize, f64)| (j, dist_sq.sqrt()))
+                    .collect()
+            })
+            .collect();
+        let points = dual.cells.points.into_iter().cloned().collect();
+        KnnGraph::new(k, points, neighbors)
+    }
+
+    /// Scores every stored point by its local outlier factor (LOF) among its `k` nearest
+    /// neighbors (by Euclidean distance).
+    ///
+    /// The neighborhoods are found with `self_knn`; see `KnnGraph::local_outlier_factors`
+    /// for how the scores are computed. Scores near 1 mean a point is as dense as its
+    /// neighborhood, and larger scores mean it is more isolated.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - The neighborhood size, e.g., 10 to 20 (at least 1).
+    ///
+    /// # Returns
+    ///
+    /// A pair of each stored point and its score, in no particular order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::kdtree::KdTree;
+    ///
+    /// let mut tree = KdTree::new();
+    /// for i in 0..25 {
+    ///     tree.insert(Point2D::new((i % 5) as f64, (i / 5) as f64, Some(i))).unwrap();
+    /// }
+    /// tree.insert(Point2D::new(20.0, 20.0, Some(99))).unwrap();
+    /// let scores = tree.lof(5);
+    /// let (outlier, score) = scores
+    ///     .iter()
+    ///     .max_by(|a, b| a.1.total_cmp(&b.1))
+    ///     .unwrap();
+    /// assert_eq!(outlier.data, Some(99));
+    /// assert!(*score > 5.0);
+    /// ```
+    pub fn lof(&self, k: usize) -> Vec<(P, f64)> {
+        let graph = self.self_knn(k.max(1));
+        let scores = graph.local_outlier_factors();
+        graph.into_points().into_iter().zip(scores).collect()
+    }
+
+    /// Returns statistics about the shape of the tree.
+    ///
+    /// Every node holds exactly one point, so the fill factor is `None`.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats {
+            size: 0,
+            node_count: 0,
+            leaf_count: 0,
+            depth: 0,
+            fill_factor: None,
+        };
+        Self::stats_rec(self.root.as_deref(), 1, &mut stats);
+        stats
+    }
+
+    /// Returns `true` if both trees hold the same points, counting duplicates, regardless of
+    /// their tolerances or how the points are arranged in the nodes.
+    pub fn content_eq(&self, other: &Self) -> bool {
+        let mut mine = Vec::new();
+        Self::collect_points(&self.root, &mut mine);
+        let mut theirs = Vec::new();
+        Self::collect_points(&other.root, &mut theirs);
+        same_multiset(mine, theirs, |p| {
+            (0..p.dims())
+                .filter_map(|axis| p.coord(axis).ok())
+                .collect()
+        })
+    }
+
+    /// Returns a concise description of the tree: its size, depth, and the extent of its
+    /// points.
+    pub fn summary(&self) -> TreeSummary {
+        let stats = self.stats();
+        let mut bounds = Vec::new();
+        Self::bounds_rec(self.root.as_deref(), &mut bounds);
+        TreeSummary {
+            kind: "KdTree",
+            size: stats.size,
+            depth: stats.depth,
+            capacity: None,
+            bounds,
+        }
+    }
+
+    fn bounds_rec(node: Option<&KdNode<P>>, bounds: &mut Vec<(f64, f64)>) {
+        let Some(node) = node else {
+            return;
+        };
+        if bounds.is_empty() {
+            bounds.resize(node.point.dims(), (f64::INFINITY, f64::NEG_INFINITY));
+        }
+        for (axis, (min, max)) in bounds.iter_mut().enumerate() {
+            if let Ok(coord) = node.point.coord(axis) {
+                *min = min.min(coord);
+                *max = max.max(coord);
+            }
+        }
+        Self::bounds_rec(node.left.as_deref(), bounds);
+        Self::bounds_rec(node.right.as_deref(), bounds);
+    }
+
+    fn stats_rec(node: Option<&KdNode<P>>, depth: usize, stats: &mut TreeStats) {
+        let Some(node) = node else {
+            return;
+        };
+        stats.size += 1;
+        stats.node_count += 1;
+        stats.depth = stats.depth.max(depth);
+        if node.left.is_none() && node.right.is_none() {
+            stats.leaf_count += 1;
+        }
+        Self::stats_rec(node.left.as_deref(), depth + 1, stats);
+        Self::stats_rec(node.right.as_deref(), depth + 1, stats);
+    }
+
+    /// Checks the structural invariants of the tree.
+    ///
+    /// Every point has the tree's dimension and lies on the correct side of the splitting
+    /// plane of each of its ancestors: at most the splitting coordinate in a left subtree and
+    /// at least it in a right subtree. Points with NaN coordinates fail the check. A tree built
+    /// through the public API from valid points always passes; the check is meant for trees
+    /// restored from untrusted or possibly corrupted data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvariantViolation` describing the first violation found.
+    pub fn check_invariants(&self) -> Result<(), SpartError> {
+        let Some(root) = &self.root else {
+            return Ok(());
+        };
+        let Some(k) = self.k else {
+            return Err(SpartError::InvariantViolation {
+                reason: "non-empty tree has no dimension".to_string(),
+            });
+        };
+        let mut lower = vec![f64::NEG_INFINITY; k];
+        let mut upper = vec![f64::INFINITY; k];
+        Self::check_rec(root, 0, k, &mut lower, &mut upper)
+    }
+
+    fn check_rec(
+        node: &KdNode<P>,
+        depth: usize,
+        k: usize,
+        lower: &mut [f64],
+        upper: &mut [f64],
+    ) -> Result<(), SpartError> {
+        if node.point.dims() != k {
+            return Err(SpartError::InvariantViolation {
+                reason: format!(
+                    "point at depth {depth} has {} dimensions instead of {k}",
+                    node.point.dims()
+                ),
+            });
+        }
+        for axis in 0..k {
+            let coord = coord_of(&node.point, axis);
+            if !(lower[axis] <= coord && coord <= upper[axis]) {
+                return Err(SpartError::InvariantViolation {
+                    reason: format!(
+                        "point at depth {depth} lies on the wrong side of a splitting plane on axis {axis}"
+                    ),
+                });
+            }
+        }
+        let axis = depth % k;
+        let split = coord_of(&node.point, axis);
+        if let Some(left) = &node.left {
+            let saved = std::mem::replace(&mut upper[axis], split);
+            Self::check_rec(left, depth + 1, k, lower, upper)?;
+            upper[axis] = saved;
+        }
+        if let Some(right) = &node.right {
+            let saved = std::mem::replace(&mut lower[axis], split);
+            Self::check_rec(right, depth + 1, k, lower, upper)?;
+            lower[axis] = saved;
+        }
+        Ok(())
+    }
+
+    /// Appends references to all points of the subtree to `out`.
+    fn collect_points<'a>(node: &'a Option<Box<KdNode<P>>>, out: &mut Vec<&'a P>) {
+        if let Some(n) = node {
+            out.push(&n.point);
+            Self::collect_points(&n.left, out);
+            Self::collect_points(&n.right, out);
+        }
+    }
+
+    /// Performs a k‑nearest neighbor search for the given target point.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k_neighbors` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the nearest points, ordered from nearest to farthest.
+    pub fn knn_search<M: DistanceMetric<P>>(&self, target: &P, k_neighbors: usize) -> Vec<P> {
+        hot_trace!("Performing kNN search", target = target, k = k_neighbors);
+        self.knn_search_bounded::<M>(target, k_neighbors, f64::INFINITY)
+    }
+
+    /// Performs a k‑nearest neighbor search that skips the stored points equal to `target`.
+    ///
+    /// This finds the neighbors of a stored point among the other points, without asking for
+    /// `k + 1` neighbors and filtering the point out afterwards. Points are compared with `==`,
+    /// so for points with a payload only copies carrying the same payload are skipped; every
+    /// such copy is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k_neighbors` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the nearest points other than `target`, ordered from nearest to farthest.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{EuclideanDistance, Point2D};
+    /// use spart::kdtree::KdTree;
+    ///
+    /// let mut tree = KdTree::new();
+    /// for i in 0..10 {
+    ///     tree.insert(Point2D::new(i as f64, 0.0, Some(i))).unwrap();
+    /// }
+    /// let stored = Point2D::new(4.0, 0.0, Some(4));
+    /// let neighbors = tree.knn_search_excluding_self::<EuclideanDistance>(&stored, 2);
+    /// assert!(neighbors.iter().all(|p| p.data == Some(3) || p.data == Some(5)));
+    /// ```
+    pub fn knn_search_excluding_self<M: DistanceMetric<P>>(
+        &self,
+        target: &P,
+        k_neighbors: usize,
+    ) -> Vec<P> {
+        self.knn_search_excluding::<M>(target, k_neighbors, |p| p == target)
+    }
+
+    /// Performs a k‑nearest neighbor search that skips the points for which `exclude` returns
+    /// `true`.
+    ///
+    /// The search continues past the skipped points, so up to `k_neighbors` other points are
+    /// returned however many points are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k_neighbors` - The number of nearest neighbors to retrieve.
+    /// * `exclude` - Returns `true` for the points to leave out of the results.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the nearest points that are not excluded, ordered from nearest to farthest.
+    pub fn knn_search_excluding<M: DistanceMetric<P>>(
+        &self,
+        target: &P,
+        k_neighbors: usize,
+        exclude: impl Fn(&P) -> bool,
+    ) -> Vec<P> {
+        if k_neighbors == 0 {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k_neighbors);
+        self.knn_search_into::<M>(
+            target,
+            f64::INFINITY,
+            &mut ExcludingSink::new(&mut results, exclude),
+        );
+        results.into_sorted_vec()
+    }
+
+    /// Returns `SpartError::DimensionMismatch` if `point` does not match the tree's dimension.
+    fn check_query_dims(&self, point: &P) -> Result<(), SpartError> {
+        match self.k {
+            Some(k) if point.dims() != k => Err(SpartError::DimensionMismatch {
+                expected: k,
+                actual: point.dims(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, `SpartError::DimensionMismatch` if `target` does not match the tree's
+    /// dimension, and `SpartError::InvalidCoordinates` if a coordinate of `target` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<P>>(
+        &self,
+        target: &P,
+        k: usize,
+    ) -> Result<Vec<P>, SpartError> {
+        check_knn_query(k, self.root.is_none())?;
+        self.check_query_dims(target)?;
+        check_query_point(target)?;
+        Ok(self.knn_search::<M>(target, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<P>>(&self, targets: &[P], k: usize) -> Vec<Vec<P>>
+    where
+        P: Send + Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Performs a k‑nearest neighbor search and returns the result as a `KnnState`
+    /// that can be used to warm-start the next query with `knn_search_from`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k_neighbors` - The number of nearest neighbors to retrieve.
+    pub fn knn_search_state<M: DistanceMetric<P>>(
+        &self,
+        target: &P,
+        k_neighbors: usize,
+    ) -> KnnState<P> {
+        KnnState {
+            query: target.clone(),
+            neighbors: self.knn_search::<M>(target, k_neighbors),
+        }
+    }
+
+    /// Performs a k‑nearest neighbor search warm-started from the result of a previous query.
+    ///
+    /// The distances from `target` to the previously found neighbors give an upper bound on the
+    /// distance of the new k‑th nearest neighbor, so whole subtrees beyond that bound are skipped
+    /// from the start instead of being pruned only after the heap fills up. This pays off when
+    /// consecutive queries are close to each other (e.g., an agent moving a little every tick).
+    ///
+    /// The result is always exact: if the previous neighbors cannot provide a usable bound (for
+    /// example because some of them were deleted in the meantime), the search falls back to a
+    /// regular k‑nearest neighbor search.
+    ///
+    /// # Arguments
+    ///
+    /// * `prev_state` - The state returned by a previous `knn_search_state` or `knn_search_from` call.
+    /// * `target` - The new point to search around.
+    /// * `k_neighbors` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A new `KnnState` holding the nearest points, ordered from nearest to farthest.
+    pub fn knn_search_from<M: DistanceMetric<P>>(
+        &self,
+        prev_state: &KnnState<P>,
+        target: &P,
+        k_neighbors: usize,
+    ) -> KnnState<P> {
+        hot_trace!(
+            "Performing warm-started kNN search",
+            target = target,
+            k = k_neighbors
+        );
+        let mut neighbors = Vec::new();
+        if k_neighbors > 0 && prev_state.neighbors.len() >= k_neighbors {
+            let mut bounds: Vec<f64> = prev_state
+                .neighbors
+                .iter()
+                .map(|p| M::distance_sq(target, p))
+                .collect();
+            bounds.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            let bound_sq = bounds[k_neighbors - 1];
+            if !bound_sq.is_nan() {
+                neighbors = self.knn_search_bounded::<M>(target, k_neighbors, bound_sq);
+            }
+        }
+        if neighbors.len() < k_neighbors {
+            neighbors = self.knn_search_bounded::<M>(target, k_neighbors, f64::INFINITY);
+        }
+        KnnState {
+            query: target.clone(),
+            neighbors,
+        }
+    }
+
+    fn knn_search_bounded<M: DistanceMetric<P>>(
+        &self,
+        target: &P,
+        k_neighbors: usize,
+        bound_sq: f64,
+    ) -> Vec<P> {
+        if k_neighbors == 0 {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k_neighbors);
+        self.knn_search_into::<M>(target, bound_sq, &mut results);
+        results.into_sorted_vec()
+    }
+
+    fn knn_search_into<M: DistanceMetric<P>>(
+        &self,
+        target: &P,
+        bound_sq: f64,
+        results: &mut impl KnnSink<P>,
+    ) {
+        if self.k == Some(target.dims()) && !has_nan_coord(target) {
+            Self::knn_search_rec::<M>(&self.root, target, 0, bound_sq, results);
+        }
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// The nearest point of each of the (up to) `k` keys with the nearest points, ordered from
+    /// nearest to farthest.
+    pub fn knn_distinct_by<M: DistanceMetric<P>, K: PartialEq>(
+        &self,
+        target: &P,
+        k: usize,
+        key: impl Fn(&P) -> K,
+    ) -> Vec<P> {
+        let mut results = DistinctKnnResults::new(k, key);
+        if k > 0 {
+            self.knn_search_into::<M>(target, f64::INFINITY, &mut results);
+        }
+        results.into_sorted_vec()
+    }
+
+    /// Performs a k‑nearest neighbor search like `knn_search`, reusing the buffers of `ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The query context, which holds the results until its next use.
+    /// * `target` - The point to search around.
+    /// * `k_neighbors` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// The nearest points, ordered from nearest to farthest.
+    pub fn knn_search_with<'c, M: DistanceMetric<P>>(
+        &self,
+        ctx: &'c mut QueryContext<P>,
+        target: &P,
+        k_neighbors: usize,
+    ) -> &'c [P] {
+        ctx.knn(k_neighbors, |results| {
+            self.knn_search_into::<M>(target, f64::INFINITY, results)
+        })
+    }
+
+    fn knn_search_rec<M: DistanceMetric<P>>(
+        node: &Option<Box<KdNode<P>>>,
+        target: &P,
+        depth: usize,
+        bound_sq: f64,
+        results: &mut impl KnnSink<P>,
+    ) {
+        if let Some(n) = node {
+            let dist_sq = M::distance_sq(target, &n.point);
+            // Points outside the warm-start bound cannot be among the k nearest.
+            if dist_sq <= bound_sq && results.accepts(dist_sq) {
+                results.push(dist_sq, n.point.clone());
+            }
+            let axis = depth % target.dims();
+            let target_coord = target
+                .coord(axis)
+                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+            let node_coord = n
+                .point
+                .coord(axis)
+                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+            let (first, second) = if target_coord < node_coord {
+                (&n.left, &n.right)
+            } else {
+                (&n.right, &n.left)
+            };
+            Self::knn_search_rec::<M>(first, target, depth + 1, bound_sq, results);
+            let diff = (target_coord - node_coord).abs();
+            let diff_sq = diff * diff;
+            let explore_second = if results.is_full() {
+                diff_sq < results.bound()
+            } else {
+                diff_sq <= bound_sq
+            };
+            if explore_second {
+                Self::knn_search_rec::<M>(second, target, depth + 1, bound_sq, results);
+            }
+        }
+    }
+
+    /// Performs a range search, returning all points within the specified radius of the center.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points within the specified radius.
+    pub fn range_search<M: DistanceMetric<P>>(&self, center: &P, radius: f64) -> Vec<P> {
+        hot_trace!("Performing range search", center = center, radius = radius);
+        let mut found = Vec::new();
+        self.range_search_into::<M>(center, radius, &mut found);
+        found
+    }
+
+    /// Like `range_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `range_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidRadius` if `radius` is negative or NaN,
+    /// `SpartError::DimensionMismatch` if `center` does not match the tree's dimension, and
+    /// `SpartError::InvalidCoordinates` if a coordinate of `center` is NaN.
+    pub fn try_range_search<M: DistanceMetric<P>>(
+        &self,
+        center: &P,
+        radius: f64,
+    ) -> Result<Vec<P>, SpartError> {
+        check_range_query(radius)?;
+        self.check_query_dims(center)?;
+        check_query_point(center)?;
+        Ok(self.range_search::<M>(center, radius))
+    }
+
+    /// Performs `range_search` for each of several centers, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `centers` - The query points.
+    /// * `radius` - The search radius around each center.
+    ///
+    /// # Returns
+    ///
+    /// The results of `range_search` for each center, in the order of `centers`.
+    #[cfg(feature = "parallel")]
+    pub fn par_range_search<M: DistanceMetric<P>>(&self, centers: &[P], radius: f64) -> Vec<Vec<P>>
+    where
+        P: Send + Sync,
+    {
+        use rayon::prelude::*;
+        centers
+            .par_iter()
+            .map(|center| self.range_search::<M>(center, radius))
+            .collect()
+    }
+
+    /// Performs a range search like `range_search`, appending the points to `out` instead of
+    /// returning a new vector.
+    ///
+    /// High-frequency callers can reserve room for the expected number of results once and
+    /// reuse the same buffer for every query, clearing it in between; `out` is never cleared
+    /// here.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    /// * `out` - The buffer that receives the points.
+    pub fn range_search_into<M: DistanceMetric<P>>(
+        &self,
+        center: &P,
+        radius: f64,
+        out: &mut Vec<P>,
+    ) {
+        // A negative radius would otherwise search the circle of its absolute value.
+        if self.k == Some(center.dims()) && radius >= 0.0 && !has_nan_coord(center) {
+            let query = RangeQuery::new(center, radius, |_: &P| true, usize::MAX);
+            Self::range_search_rec::<M>(&self.root, &query, 0, out);
+        }
+    }
+
+    /// Performs a range search like `range_search`, stopping once `max_results` points are
+    /// found.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    /// * `max_results` - The maximum number of points to return.
+    ///
+    /// # Returns
+    ///
+    /// Up to `max_results` points within the range, and whether more points matched. Which
+    /// points are returned when the search is truncated depends on the layout of the tree.
+    pub fn range_search_limited<M: DistanceMetric<P>>(
+        &self,
+        center: &P,
+        radius: f64,
+        max_results: usize,
+    ) -> LimitedResults<P> {
+        let mut found = Vec::new();
+        if self.k == Some(center.dims()) && radius >= 0.0 && !has_nan_coord(center) {
+            let limit = LimitedResults::<P>::limit_for(max_results);
+            let query = RangeQuery::new(center, radius, |_: &P| true, limit);
+            Self::range_search_rec::<M>(&self.root, &query, 0, &mut found);
+        }
+        LimitedResults::from_overfull(found, max_results)
+    }
+
+    /// Performs a range search like `range_search`, reusing the buffers of `ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The query context, which holds the results until its next use.
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// The points within the specified radius.
+    pub fn range_search_with<'c, M: DistanceMetric<P>>(
+        &self,
+        ctx: &'c mut QueryContext<P>,
+        center: &P,
+        radius: f64,
+    ) -> &'c [P] {
+        ctx.collect(|found| self.range_search_into::<M>(center, radius, found))
+    }
+
+    /// Collects the points of the subtree that match `query`, stopping once `found` holds
+    /// `query.limit` points.
+    fn range_search_rec<M: DistanceMetric<P>>(
+        node: &Option<Box<KdNode<P>>>,
+        query: &RangeQuery<'_, P, impl Fn(&P) -> bool>,
+        depth: usize,
+        found: &mut Vec<P>,
+    ) {
+        if found.len() >= query.limit {
+            return;
+        }
+        if let Some(n) = node {
+            let RangeQuery { center, radius, .. } = *query;
+            let dist_sq = M::distance_sq(center, &n.point);
+            if dist_sq <= query.radius_sq && (query.keep)(&n.point) {
+                found.push(n.point.clone());
+            }
+            let axis = depth % center.dims();
+            let center_coord = center
+                .coord(axis)
+                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+            let node_coord = n
+                .point
+                .coord(axis)
+                .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+            if center_coord - radius <= node_coord {
+                Self::range_search_rec::<M>(&n.left, query, depth + 1, found);
+            }
+            if center_coord + radius >= node_coord {
+                Self::range_search_rec::<M>(&n.right, query, depth + 1, found);
+            }
+        }
+    }
+
+    /// Deletes a point from the Kd‑tree.
+    ///
+    /// The subtree below the deleted point is restructured according to the tree's
+    /// `DeleteStrategy` (see `with_delete_strategy`).
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to delete.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was found and deleted, otherwise `false`.
+    pub fn delete(&mut self, point: &P) -> bool {
+        if self.root.is_none() {
+            return false;
+        }
+        hot_trace!("Deleting point from KdTree", point = point);
+        let k = match self.k {
+            Some(k) => k,
+            None => return false,
+        };
+        let (new_root, deleted) = Self::delete_rec(
+            self.root.take(),
+            point,
+            0,
+            k,
+            self.tolerance,
+            self.delete_strategy,
+        );
+        self.root = new_root;
+        if self.root.is_none() {
+            self.k = None;
+        }
+        deleted
+    }
+
+    /// Deletes several points from the Kd‑tree.
+    ///
+    /// Each point removes at most one matching stored point, as with `delete`.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to delete.
+    ///
+    /// # Returns
+    ///
+    /// The number of points that were found and deleted.
+    pub fn delete_bulk(&mut self, points: &[P]) -> usize {
+        points.iter().filter(|point| self.delete(point)).count()
+    }
+
+    /// Keeps only the points for which `keep` returns `true`, deleting the others.
+    ///
+    /// The remaining points are rebuilt into a balanced tree, as with `insert_bulk`.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - Called once for each stored point.
+    ///
+    /// # Returns
+    ///
+    /// The number of points that were deleted.
+    pub fn retain(&mut self, mut keep: impl FnMut(&P) -> bool) -> usize {
+        let Some(k) = self.k else {
+            return 0;
+        };
+        let mut points = Vec::new();
+        Self::drain_points(self.root.take(), &mut points);
+        let before = points.len();
+        points.retain(|p| keep(p));
+        let deleted = before - points.len();
+        if points.is_empty() {
+            self.k = None;
+        } else {
+            self.root = Self::insert_bulk_rec(points, 0, k);
+        }
+        deleted
+    }
+
+    /// Replaces a point matching `old` with `new`.
+    ///
+    /// Points are matched like in `delete`, so the tree's tolerance applies to `old`.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The point to replace.
+    /// * `new` - The replacement point.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(true)` if a matching point was found and replaced, `Ok(false)` if none was found.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::DimensionMismatch` if `new` does not match the tree's dimension;
+    /// the tree is left unchanged in that case.
+    pub fn update(&mut self, old: &P, new: P) -> Result<bool, SpartError> {
+        if let Some(k) = self.k {
+            if new.dims() != k {
+                return Err(SpartError::DimensionMismatch {
+                    expected: k,
+                    actual: new.dims(),
+                });
+            }
+        }
+        if !self.delete(old) {
+            return Ok(false);
+        }
+        self.insert(new)?;
+        Ok(true)
+    }
+
+    fn delete_rec(
+        node: Option<Box<KdNode<P>>>,
+        point: &P,
+        depth: usize,
+        k: usize,
+        tolerance: f64,
+        strategy: DeleteStrategy,
+    ) -> (Option<Box<KdNode<P>>>, bool) {
+        match node {
+            None => (None, false),
+            Some(mut current) => {
+                let axis = depth % k;
+                if current.point.approx_eq(point, tolerance) && strategy == DeleteStrategy::Rebuild
+                {
+                    let mut rest = Vec::new();
+                    Self::drain_points(current.left.take(), &mut rest);
+                    Self::drain_points(current.right.take(), &mut rest);
+                    (Self::insert_bulk_rec(rest, depth, k), true)
+                } else if current.point.approx_eq(point, tolerance) {
+                    // Delete a single instance: replace with successor from right subtree if available,
+                    // otherwise promote left subtree, or remove leaf.
                     if let Some(right_subtree) = current.right.take() {
                         let successor = Self::find_min(&right_subtree, axis, depth + 1, k).clone();
-                        let (new_right, _) =
-                            Self::delete_rec(Some(right_subtree), &successor, depth + 1, k);
+                        let (new_right, _) = Self::delete_rec(
+                            Some(right_subtree),
+                            &successor,
+                            depth + 1,
+                            k,
+                            0.0,
+                            strategy,
+                        );
                         current.point = successor;
                         current.right = new_right;
                         (Some(current), true)
                     } else if let Some(left_subtree) = current.left.take() {
                         // Replace with min from left subtree on current axis, then delete that min
                         let successor = Self::find_min(&left_subtree, axis, depth + 1, k).clone();
-                        let (mut new_left, _) =
-                            Self::delete_rec(Some(left_subtree), &successor, depth + 1, k);
+                        let (mut new_left, _) = Self::delete_rec(
+                            Some(left_subtree),
+                            &successor,
+                            depth + 1,
+                            k,
+                            0.0,
+                            strategy,
+                        );
                         current.point = successor;
                         // As per standard kd-tree deletion, attach the adjusted left subtree as right child
                         current.right = new_left.take();
@@ -554,80 +1824,914 @@ impl<P: KdPoint> KdTree<P> {
                         .coord(axis)
                         .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
 
-                    if p_coord < c_coord {
-                        let (new_left, deleted) =
-                            Self::delete_rec(current.left.take(), point, depth + 1, k);
-                        current.left = new_left;
-                        (Some(current), deleted)
-                    } else if p_coord > c_coord {
-                        let (new_right, deleted) =
-                            Self::delete_rec(current.right.take(), point, depth + 1, k);
-                        current.right = new_right;
-                        (Some(current), deleted)
-                    } else {
-                        // Equal on this axis but not equal overall: the point could be in either subtree.
-                        // Search right first, then left if not found.
-                        let (new_right, deleted_right) =
-                            Self::delete_rec(current.right.take(), point, depth + 1, k);
-                        current.right = new_right;
-                        if deleted_right {
-                            (Some(current), true)
-                        } else {
-                            let (new_left, deleted_left) =
-                                Self::delete_rec(current.left.take(), point, depth + 1, k);
-                            current.left = new_left;
-                            (Some(current), deleted_left)
-                        }
-                    }
-                }
-            }
+                    if p_coord + tolerance < c_coord {
+                        let (new_left, deleted) = Self::delete_rec(
+                            current.left.take(),
+                            point,
+                            depth + 1,
+                            k,
+                            tolerance,
+                            strategy,
+                        );
+                        current.left = new_left;
+                        (Some(current), deleted)
+                    } else if p_coord - tolerance > c_coord {
+                        let (new_right, deleted) = Self::delete_rec(
+                            current.right.take(),
+                            point,
+                            depth + 1,
+                            k,
+                            tolerance,
+                            strategy,
+                        );
+                        current.right = new_right;
+                        (Some(current), deleted)
+                    } else {
+                        // Equal on this axis (within tolerance) but not matching overall: the point
+                        // could be in either subtree. Search right first, then left if not found.
+                        let (new_right, deleted_right) = Self::delete_rec(
+                            current.right.take(),
+                            point,
+                            depth + 1,
+                            k,
+                            tolerance,
+                            strategy,
+                        );
+                        current.right = new_right;
+                        if deleted_right {
+                            (Some(current), true)
+                        } else {
+                            let (new_left, deleted_left) = Self::delete_rec(
+                                current.left.take(),
+                                point,
+                                depth + 1,
+                                k,
+                                tolerance,
+                                strategy,
+                            );
+                            current.left = new_left;
+                            (Some(current), deleted_left)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn find_min(node: &KdNode<P>, d: usize, depth: usize, k: usize) -> &P {
+        let axis = depth % k;
+        let mut min = &node.point;
+
+        if axis == d {
+            if let Some(ref left) = node.left {
+                let left_min = Self::find_min(left, d, depth + 1, k);
+                let left_c = left_min
+                    .coord(d)
+                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+                let min_c = min
+                    .coord(d)
+                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+                if left_c < min_c {
+                    min = left_min;
+                }
+            }
+        } else {
+            if let Some(ref left) = node.left {
+                let left_min = Self::find_min(left, d, depth + 1, k);
+                let left_c = left_min
+                    .coord(d)
+                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+                let min_c = min
+                    .coord(d)
+                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+                if left_c < min_c {
+                    min = left_min;
+                }
+            }
+            if let Some(ref right) = node.right {
+                let right_min = Self::find_min(right, d, depth + 1, k);
+                let right_c = right_min
+                    .coord(d)
+                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+                let min_c = min
+                    .coord(d)
+                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+                if right_c < min_c {
+                    min = right_min;
+                }
+            }
+        }
+        min
+    }
+}
+
+/// The Euclidean metric for any `KdPoint`, computed from its coordinates.
+struct CoordinateDistance;
+
+impl<P: KdPoint> DistanceMetric<P> for CoordinateDistance {
+    fn distance_sq(p1: &P, p2: &P) -> f64 {
+        euclidean_sq(p1, p2)
+    }
+}
+
+/// Returns the squared Euclidean distance between two points of the same dimension.
+fn euclidean_sq<P: KdPoint>(a: &P, b: &P) -> f64 {
+    (0..a.dims())
+        .map(|axis| {
+            let d = coord_of(a, axis) - coord_of(b, axis);
+            d * d
+        })
+        .sum()
+}
+
+fn coord_of<P: KdPoint>(point: &P, axis: usize) -> f64 {
+    point
+        .coord(axis)
+        .unwrap_or_else(|_| unreachable!("axis below the tree's dimension"))
+}
+
+/// The parameters of a recursive Kd-tree range search.
+struct RangeQuery<'q, P, F> {
+    center: &'q P,
+    radius: f64,
+    radius_sq: f64,
+    /// Only the points for which `keep` returns `true` are collected.
+    keep: F,
+    /// The search stops once this many points are collected.
+    limit: usize,
+}
+
+impl<'q, P, F: Fn(&P) -> bool> RangeQuery<'q, P, F> {
+    fn new(center: &'q P, radius: f64, keep: F, limit: usize) -> Self {
+        RangeQuery {
+            center,
+            radius,
+            radius_sq: radius * radius,
+            keep,
+            limit,
+        }
+    }
+}
+
+/// Returns `true` if any coordinate of `point` is NaN, which makes every distance to it NaN.
+///
+/// The lenient searches return no results for such a query instead of arbitrary ones.
+pub(crate) fn has_nan_coord<P: KdPoint>(point: &P) -> bool {
+    (0..point.dims()).any(|axis| point.coord(axis).is_ok_and(f64::is_nan))
+}
+
+/// Returns `SpartError::InvalidCoordinates` if any coordinate of the query `point` is NaN.
+pub(crate) fn check_query_point<P: KdPoint>(point: &P) -> Result<(), SpartError> {
+    let coords: Vec<f64> = (0..point.dims())
+        .filter_map(|axis| point.coord(axis).ok())
+        .collect();
+    check_query_coords(&coords)
+}
+
+/// Returns the smallest and largest squared distance from `point` to the box `lo..=hi`.
+fn cell_distance_sq<P: KdPoint>(lo: &[f64], hi: &[f64], point: &P) -> (f64, f64) {
+    let mut min = 0.0;
+    let mut max = 0.0;
+    for axis in 0..lo.len() {
+        let c = coord_of(point, axis);
+        let below = lo[axis] - c;
+        let above = c - hi[axis];
+        let gap = below.max(above).max(0.0);
+        min += gap * gap;
+        let far = (c - lo[axis]).abs().max((hi[axis] - c).abs());
+        max += far * far;
+    }
+    (min, max)
+}
+
+/// A pre-order view of a Kd-tree with per-node weights, used by the clustering helpers.
+///
+/// Node `i`'s subtree is described by `left[i]` and `right[i]`; its cell is the part of the
+/// bounding box `lo..=hi` of all points that is left over by the splits on the way down.
+struct FlatTree<'a, P> {
+    points: Vec<&'a P>,
+    left: Vec<Option<usize>>,
+    right: Vec<Option<usize>>,
+    axis: Vec<usize>,
+    lo: Vec<f64>,
+    hi: Vec<f64>,
+    /// The squared distance of each point to its nearest centroid.
+    dist_sq: Vec<f64>,
+    /// The largest `dist_sq` in each subtree.
+    sub_max: Vec<f64>,
+    /// The sum of `dist_sq` over each subtree.
+    sub_sum: Vec<f64>,
+}
+
+impl<'a, P: KdPoint> FlatTree<'a, P> {
+    fn new(tree: &'a KdTree<P>) -> Option<Self> {
+        let k = tree.k?;
+        let root = tree.root.as_deref()?;
+        let mut flat = FlatTree {
+            points: Vec::new(),
+            left: Vec::new(),
+            right: Vec::new(),
+            axis: Vec::new(),
+            lo: vec![f64::INFINITY; k],
+            hi: vec![f64::NEG_INFINITY; k],
+            dist_sq: Vec::new(),
+            sub_max: Vec::new(),
+            sub_sum: Vec::new(),
+        };
+        flat.push(root, 0, k);
+        let n = flat.points.len();
+        flat.dist_sq = vec![f64::INFINITY; n];
+        flat.sub_max = vec![f64::INFINITY; n];
+        flat.sub_sum = vec![f64::INFINITY; n];
+        Some(flat)
+    }
+
+    /// Appends the subtree rooted at `node` in pre-order and returns the index of `node`.
+    fn push(&mut self, node: &'a KdNode<P>, depth: usize, k: usize) -> usize {
+        let i = self.points.len();
+        self.points.push(&node.point);
+        self.left.push(None);
+        self.right.push(None);
+        self.axis.push(depth % k);
+        for axis in 0..k {
+            let c = coord_of(&node.point, axis);
+            self.lo[axis] = self.lo[axis].min(c);
+            self.hi[axis] = self.hi[axis].max(c);
+        }
+        if let Some(left) = node.left.as_deref() {
+            self.left[i] = Some(self.push(left, depth + 1, k));
+        }
+        if let Some(right) = node.right.as_deref() {
+            self.right[i] = Some(self.push(right, depth + 1, k));
+        }
+        i
+    }
+
+    fn add_centroid(&mut self, centroid: &P) {
+        let (mut lo, mut hi) = (self.lo.clone(), self.hi.clone());
+        self.update(0, &mut lo, &mut hi, centroid);
+    }
+
+    /// Lowers the distances in the subtree of node `i`, whose cell is `lo..=hi`, to account
+    /// for a new centroid.
+    fn update(&mut self, i: usize, lo: &mut [f64], hi: &mut [f64], centroid: &P) {
+        // No point of the cell can get closer than it already is to its nearest centroid.
+        if cell_distance_sq(lo, hi, centroid).0 >= self.sub_max[i] {
+            return;
+        }
+        self.dist_sq[i] = self.dist_sq[i].min(euclidean_sq(self.points[i], centroid));
+        let axis = self.axis[i];
+        let split = coord_of(self.points[i], axis);
+        if let Some(left) = self.left[i] {
+            let saved = hi[axis];
+            hi[axis] = saved.min(split);
+            self.update(left, lo, hi, centroid);
+            hi[axis] = saved;
+        }
+        if let Some(right) = self.right[i] {
+            let saved = lo[axis];
+            lo[axis] = saved.max(split);
+            self.update(right, lo, hi, centroid);
+            lo[axis] = saved;
+        }
+        let mut max = self.dist_sq[i];
+        let mut sum = self.dist_sq[i];
+        for child in [self.left[i], self.right[i]].into_iter().flatten() {
+            max = max.max(self.sub_max[child]);
+            sum += self.sub_sum[child];
+        }
+        self.sub_max[i] = max;
+        self.sub_sum[i] = sum;
+    }
+
+    /// Returns the node at which the running sum of `dist_sq`, taken in in-order, first exceeds
+    /// `target`.
+    fn sample(&self, mut target: f64) -> usize {
+        let mut i = 0;
+        loop {
+            let left_sum = self.left[i].map_or(0.0, |l| self.sub_sum[l]);
+            if let Some(left) = self.left[i] {
+                if target < left_sum {
+                    i = left;
+                    continue;
+                }
+            }
+            target -= left_sum;
+            if target < self.dist_sq[i] {
+                return i;
+            }
+            target -= self.dist_sq[i];
+            match self.right[i] {
+                Some(right) if self.sub_sum[right] > 0.0 => i = right,
+                // Rounding can leave `target` just past the last positive weight.
+                _ => return self.first_positive(i),
+            }
+        }
+    }
+
+    /// Returns the first node with a positive weight in the subtree of node `i`.
+    fn first_positive(&self, i: usize) -> usize {
+        let mut stack = vec![i];
+        while let Some(j) = stack.pop() {
+            if self.dist_sq[j] > 0.0 {
+                return j;
+            }
+            stack.extend([self.right[j], self.left[j]].into_iter().flatten());
+        }
+        i
+    }
+
+    /// Assigns the points in the subtree of node `i`, whose cell is `lo..=hi`, to the nearest
+    /// of the `candidates`.
+    fn assign(
+        &self,
+        i: usize,
+        lo: &mut [f64],
+        hi: &mut [f64],
+        mut candidates: Vec<usize>,
+        centroids: &[P],
+        out: &mut Vec<(&'a P, usize)>,
+    ) {
+        if candidates.len() > 1 {
+            let bounds: Vec<(f64, f64)> = candidates
+                .iter()
+                .map(|&c| cell_distance_sq(lo, hi, &centroids[c]))
+                .collect();
+            let best_max = bounds.iter().map(|b| b.1).fold(f64::INFINITY, f64::min);
+            let mut bounds = bounds.into_iter();
+            candidates.retain(|_| bounds.next().is_some_and(|(min, _)| min <= best_max));
+        }
+        if let [only] = candidates[..] {
+            let mut stack = vec![i];
+            while let Some(j) = stack.pop() {
+                out.push((self.points[j], only));
+                stack.extend([self.right[j], self.left[j]].into_iter().flatten());
+            }
+            return;
+        }
+
+        let point = self.points[i];
+        if let Some(&nearest) = candidates.iter().min_by(|&&a, &&b| {
+            euclidean_sq(point, &centroids[a]).total_cmp(&euclidean_sq(point, &centroids[b]))
+        }) {
+            out.push((point, nearest));
+        }
+        let axis = self.axis[i];
+        let split = coord_of(point, axis);
+        if let Some(left) = self.left[i] {
+            let saved = hi[axis];
+            hi[axis] = saved.min(split);
+            self.assign(left, lo, hi, candidates.clone(), centroids, out);
+            hi[axis] = saved;
+        }
+        if let Some(right) = self.right[i] {
+            let saved = lo[axis];
+            lo[axis] = saved.max(split);
+            self.assign(right, lo, hi, candidates, centroids, out);
+            lo[axis] = saved;
+        }
+    }
+}
+
+/// A pre-order view of a set of points arranged as a Kd-tree, with the bounding box of every
+/// subtree, for traversals that visit pairs of subtrees.
+struct Cells<'a, P> {
+    points: Vec<&'a P>,
+    left: Vec<Option<usize>>,
+    right: Vec<Option<usize>>,
+    /// The bounding box of each subtree, `dims` coordinates per node.
+    lo: Vec<f64>,
+    hi: Vec<f64>,
+    dims: usize,
+}
+
+impl<'a, P: KdPoint> Cells<'a, P> {
+    fn empty(dims: usize) -> Self {
+        Cells {
+            points: Vec::new(),
+            left: Vec::new(),
+            right: Vec::new(),
+            lo: Vec::new(),
+            hi: Vec::new(),
+            dims,
+        }
+    }
+
+    /// Returns the view of a tree's nodes, or `None` if the tree is empty.
+    fn of_tree(tree: &'a KdTree<P>) -> Option<Self> {
+        let mut cells = Cells::empty(tree.k?);
+        cells.push_node(tree.root.as_deref()?);
+        Some(cells)
+    }
+
+    /// Returns the view of a balanced Kd-tree over `points`, along with the index in `points`
+    /// of the point at each node.
+    fn of_points(points: &'a [P], dims: usize) -> (Self, Vec<usize>) {
+        let mut cells = Cells::empty(dims);
+        let mut items: Vec<(usize, &'a P)> = points.iter().enumerate().collect();
+        let mut ids = Vec::with_capacity(points.len());
+        cells.push_median(&mut items, 0, &mut ids);
+        (cells, ids)
+    }
+
+    /// Appends a node holding `point`, with a box around the point alone, and returns its index.
+    fn push_point(&mut self, point: &'a P) -> usize {
+        let i = self.points.len();
+        self.points.push(point);
+        self.left.push(None);
+        self.right.push(None);
+        for axis in 0..self.dims {
+            let c = coord_of(point, axis);
+            self.lo.push(c);
+            self.hi.push(c);
+        }
+        i
+    }
+
+    /// Attaches subtree `j` to node `i` and grows the box of `i` to cover it.
+    fn attach(&mut self, i: usize, j: usize, side: usize) {
+        if side == 0 {
+            self.left[i] = Some(j);
+        } else {
+            self.right[i] = Some(j);
+        }
+        for axis in 0..self.dims {
+            self.lo[i * self.dims + axis] =
+                self.lo[i * self.dims + axis].min(self.lo[j * self.dims + axis]);
+            self.hi[i * self.dims + axis] =
+                self.hi[i * self.dims + axis].max(self.hi[j * self.dims + axis]);
+        }
+    }
+
+    /// Appends the subtree rooted at `node` in pre-order and returns the index of `node`.
+    fn push_node(&mut self, node: &'a KdNode<P>) -> usize {
+        let i = self.push_point(&node.point);
+        let children = [node.left.as_deref(), node.right.as_deref()];
+        for (side, child) in children.into_iter().enumerate() {
+            let Some(child) = child else { continue };
+            let j = self.push_node(child);
+            self.attach(i, j, side);
+        }
+        i
+    }
+
+    /// Appends a subtree over `items` split at the median of each level, in pre-order, and
+    /// returns the index of its root.
+    fn push_median(
+        &mut self,
+        items: &mut [(usize, &'a P)],
+        depth: usize,
+        ids: &mut Vec<usize>,
+    ) -> Option<usize> {
+        if items.is_empty() {
+            return None;
+        }
+        let axis = depth % self.dims;
+        items.sort_by(|a, b| coord_of(a.1, axis).total_cmp(&coord_of(b.1, axis)));
+        let mid = items.len() / 2;
+        let i = self.push_point(items[mid].1);
+        ids.push(items[mid].0);
+        let (below, rest) = items.split_at_mut(mid);
+        for (side, part) in [below, &mut rest[1..]].into_iter().enumerate() {
+            if let Some(j) = self.push_median(part, depth + 1, ids) {
+                self.attach(i, j, side);
+            }
+        }
+        Some(i)
+    }
+
+    fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    fn children(&self, i: usize) -> impl Iterator<Item = usize> + use<P> {
+        [self.left[i], self.right[i]].into_iter().flatten()
+    }
+
+    fn cell(&self, i: usize) -> (&[f64], &[f64]) {
+        let range = i * self.dims..(i + 1) * self.dims;
+        (&self.lo[range.clone()], &self.hi[range])
+    }
+
+    /// Returns the smallest squared distance between the box of subtree `a` and the box of
+    /// subtree `b` of `other`.
+    fn gap_sq(&self, a: usize, other: &Cells<'_, P>, b: usize) -> f64 {
+        let (a_lo, a_hi) = self.cell(a);
+        let (b_lo, b_hi) = other.cell(b);
+        (0..self.dims)
+            .map(|axis| {
+                let gap = (a_lo[axis] - b_hi[axis])
+                    .max(b_lo[axis] - a_hi[axis])
+                    .max(0.0);
+                gap * gap
+            })
+            .sum()
+    }
+
+    /// Returns the smallest squared distance from `point` to the box of subtree `i`.
+    fn point_gap_sq(&self, point: &P, i: usize) -> f64 {
+        let (lo, hi) = self.cell(i);
+        cell_distance_sq(lo, hi, point).0
+    }
+}
+
+/// A view of a Kd-tree used to find the nearest neighbors of all points at once.
+struct DualTree<'a, P> {
+    cells: Cells<'a, P>,
+    /// The nearest neighbors found so far for each point, with their squared distances.
+    results: Vec<KnnResults<(usize, f64)>>,
+    /// An upper bound on the `k`-th neighbor distance of every point in each subtree.
+    bound: Vec<f64>,
+}
+
+impl<'a, P: KdPoint> DualTree<'a, P> {
+    fn new(tree: &'a KdTree<P>, k: usize) -> Option<Self> {
+        let cells = Cells::of_tree(tree)?;
+        let n = cells.len();
+        Some(DualTree {
+            cells,
+            results: (0..n).map(|_| KnnResults::new(k)).collect(),
+            bound: vec![f64::INFINITY; n],
+        })
+    }
+
+    fn children(&self, i: usize) -> impl Iterator<Item = usize> + use<P> {
+        self.cells.children(i)
+    }
+
+    /// Returns the smallest squared distance between the bounding boxes of two subtrees.
+    fn gap_sq(&self, a: usize, b: usize) -> f64 {
+        self.cells.gap_sq(a, &self.cells, b)
+    }
+
+    /// Returns the smallest squared distance from point `p` to the bounding box of subtree `i`.
+    fn point_gap_sq(&self, p: usize, i: usize) -> f64 {
+        self.cells.point_gap_sq(self.cells.points[p], i)
+    }
+
+    /// Offers point `r` as a neighbor of point `q`.
+    fn offer(&mut self, q: usize, r: usize) {
+        if q != r {
+            let dist_sq = euclidean_sq(self.cells.points[q], self.cells.points[r]);
+            self.results[q].push(dist_sq, (r, dist_sq));
+        }
+    }
+
+    /// Recomputes the bound of subtree `i` from its point and its children.
+    fn refresh(&mut self, i: usize) {
+        let mut bound = self.results[i].bound();
+        for child in self.children(i) {
+            bound = bound.max(self.bound[child]);
+        }
+        self.bound[i] = bound;
+    }
+
+    /// Matches every point of subtree `q` with every point of subtree `r`.
+    fn search(&mut self, q: usize, r: usize) {
+        if self.gap_sq(q, r) >= self.bound[q] {
+            return;
+        }
+        // The pairs split into: point `q` with subtree `r`, the children of `q` with point
+        // `r`, and the children of `q` with the children of `r`.
+        self.search_point(q, r);
+        for child in self.children(q) {
+            self.offer_point(child, r);
+        }
+        for q_child in self.children(q) {
+            let mut r_children: Vec<(f64, usize)> = self
+                .children(r)
+                .map(|r_child| (self.gap_sq(q_child, r_child), r_child))
+                .collect();
+            r_children.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for (_, r_child) in r_children {
+                self.search(q_child, r_child);
+            }
+        }
+        self.refresh(q);
+    }
+
+    /// Offers every point of subtree `r` as a neighbor of point `q`, nearest subtrees first.
+    fn search_point(&mut self, q: usize, r: usize) {
+        if !self.results[q].accepts(self.point_gap_sq(q, r)) {
+            return;
+        }
+        self.offer(q, r);
+        let mut children: Vec<(f64, usize)> = self
+            .children(r)
+            .map(|child| (self.point_gap_sq(q, child), child))
+            .collect();
+        children.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, child) in children {
+            self.search_point(q, child);
+        }
+    }
+
+    /// Offers point `r` as a neighbor of every point of subtree `q`.
+    fn offer_point(&mut self, q: usize, r: usize) {
+        if self.point_gap_sq(r, q) >= self.bound[q] {
+            return;
+        }
+        self.offer(q, r);
+        for child in self.children(q) {
+            self.offer_point(child, r);
+        }
+        self.refresh(q);
+    }
+}
+
+/// Finds the nearest reference point (facility) of every query point (demand point), over the
+/// views of two Kd-trees, optionally skipping the reference points that are full.
+struct NearestAssignment<'a, 'b, P> {
+    query: Cells<'a, P>,
+    reference: Cells<'b, P>,
+    /// The nearest reference point found so far for each query point, with the squared
+    /// distance to it.
+    best: Vec<(f64, Option<usize>)>,
+    /// An upper bound on the nearest distance of every query point in each subtree.
+    bound: Vec<f64>,
+    /// The number of reference points with spare capacity in each reference subtree.
+    open: Vec<usize>,
+    /// The parent of each reference node.
+    parent: Vec<Option<usize>>,
+}
+
+impl<'a, 'b, P: KdPoint> NearestAssignment<'a, 'b, P> {
+    fn new(query: Cells<'a, P>, reference: Cells<'b, P>, full: &[bool]) -> Self {
+        let mut parent = vec![None; reference.len()];
+        let mut open: Vec<usize> = full.iter().map(|&f| usize::from(!f)).collect();
+        // Children come after their parent in pre-order, so a reverse pass sums the subtrees.
+        for i in (0..reference.len()).rev() {
+            for child in reference.children(i) {
+                parent[child] = Some(i);
+                open[i] += open[child];
+            }
+        }
+        let n = query.len();
+        NearestAssignment {
+            query,
+            reference,
+            best: vec![(f64::INFINITY, None); n],
+            bound: vec![f64::INFINITY; n],
+            open,
+            parent,
+        }
+    }
+
+    fn is_full(&self, r: usize) -> bool {
+        let children: usize = self.reference.children(r).map(|c| self.open[c]).sum();
+        self.open[r] == children
+    }
+
+    /// Marks reference point `r` as full.
+    fn close(&mut self, r: usize) {
+        let mut node = Some(r);
+        while let Some(i) = node {
+            self.open[i] -= 1;
+            node = self.parent[i];
+        }
+    }
+
+    /// Offers reference point `r` as the nearest one of query point `q`.
+    fn offer(&mut self, q: usize, r: usize) {
+        if self.is_full(r) {
+            return;
+        }
+        let dist_sq = euclidean_sq(self.query.points[q], self.reference.points[r]);
+        if dist_sq < self.best[q].0 {
+            self.best[q] = (dist_sq, Some(r));
+        }
+    }
+
+    fn refresh(&mut self, q: usize) {
+        let mut bound = self.best[q].0;
+        for child in self.query.children(q) {
+            bound = bound.max(self.bound[child]);
+        }
+        self.bound[q] = bound;
+    }
+
+    /// Matches every point of query subtree `q` with every point of reference subtree `r`.
+    fn search(&mut self, q: usize, r: usize) {
+        if self.open[r] == 0 || self.query.gap_sq(q, &self.reference, r) >= self.bound[q] {
+            return;
+        }
+        // As in `DualTree::search`: point `q` with subtree `r`, the children of `q` with
+        // point `r`, and the children of `q` with the children of `r`.
+        self.search_point(q, r);
+        for child in self.query.children(q) {
+            self.offer_point(child, r);
+        }
+        for q_child in self.query.children(q) {
+            let mut r_children: Vec<(f64, usize)> = self
+                .reference
+                .children(r)
+                .map(|r_child| {
+                    (
+                        self.query.gap_sq(q_child, &self.reference, r_child),
+                        r_child,
+                    )
+                })
+                .collect();
+            r_children.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for (_, r_child) in r_children {
+                self.search(q_child, r_child);
+            }
+        }
+        self.refresh(q);
+    }
+
+    /// Offers every point of reference subtree `r` to query point `q`, nearest subtrees first.
+    fn search_point(&mut self, q: usize, r: usize) {
+        let gap_sq = self.reference.point_gap_sq(self.query.points[q], r);
+        if self.open[r] == 0 || gap_sq >= self.best[q].0 {
+            return;
+        }
+        self.offer(q, r);
+        let mut children: Vec<(f64, usize)> = self
+            .reference
+            .children(r)
+            .map(|child| {
+                (
+                    self.reference.point_gap_sq(self.query.points[q], child),
+                    child,
+                )
+            })
+            .collect();
+        children.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, child) in children {
+            self.search_point(q, child);
+        }
+    }
+
+    /// Offers reference point `r` to every point of query subtree `q`.
+    fn offer_point(&mut self, q: usize, r: usize) {
+        if self.query.point_gap_sq(self.reference.points[r], q) >= self.bound[q] {
+            return;
+        }
+        self.offer(q, r);
+        for child in self.query.children(q) {
+            self.offer_point(child, r);
+        }
+        self.refresh(q);
+    }
+
+    /// Finds the nearest reference point of every query point.
+    fn search_all(&mut self) {
+        if self.query.len() > 0 && self.reference.len() > 0 {
+            self.search(0, 0);
+        }
+    }
+
+    /// Finds the nearest reference point with spare capacity of query point `q` again.
+    fn research(&mut self, q: usize) {
+        self.best[q] = (f64::INFINITY, None);
+        if self.reference.len() > 0 {
+            self.search_point(q, 0);
+        }
+    }
+}
+
+impl<T> KdTree<Point2D<T>>
+where
+    T: Elevation + std::fmt::Debug + Clone + PartialEq,
+{
+    /// Finds the k nearest neighbors of a point among the points whose elevation lies in
+    /// `z_range`.
+    ///
+    /// Points are indexed by their planar position only, and their elevation is read from
+    /// their payload (see the `elevation` module). Points outside the range are skipped during
+    /// the search, which continues past them.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search near; only its planar position is used.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    /// * `z_range` - The elevations the neighbors must have.
+    ///
+    /// # Returns
+    ///
+    /// A vector of up to k points, ordered from nearest to farthest in the plane.
+    pub fn knn_search_in_z<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+        z_range: impl RangeBounds<f64>,
+    ) -> Vec<Point2D<T>> {
+        self.knn_search_excluding::<M>(target, k, |p| !in_z_range(p.data.as_ref(), &z_range))
+    }
+
+    /// Finds the points within `radius` of `center` whose elevation lies in `z_range`.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search; only its planar position is used.
+    /// * `radius` - The search radius in the plane.
+    /// * `z_range` - The elevations the points must have.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the matching points; empty if `radius` is negative or NaN.
+    pub fn range_search_in_z<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+        z_range: impl RangeBounds<f64>,
+    ) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        if self.k == Some(2) && radius >= 0.0 && !has_nan_coord(center) {
+            let keep = |p: &Point2D<T>| in_z_range(p.data.as_ref(), &z_range);
+            let query = RangeQuery::new(center, radius, keep, usize::MAX);
+            Self::range_search_rec::<M>(&self.root, &query, 0, &mut found);
+        }
+        found
+    }
+}
+
+impl<P: KdPoint> KdTree<P> {
+    /// Returns an iterator over the stored points, without running a query.
+    ///
+    /// The points are visited in pre-order, each node before its subtrees.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::kdtree::KdTree;
+    ///
+    /// let mut tree = KdTree::new();
+    /// for i in 0..5 {
+    ///     tree.insert(Point2D::new(i as f64, 9.0 - i as f64, Some(i))).unwrap();
+    /// }
+    /// let mut ids: Vec<_> = tree.iter().filter_map(|p| p.data).collect();
+    /// ids.sort();
+    /// assert_eq!(ids, [0, 1, 2, 3, 4]);
+    /// assert_eq!(tree.into_iter().count(), 5);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, P> {
+        Iter {
+            stack: self.root.as_deref().into_iter().collect(),
         }
     }
+}
 
-    fn find_min(node: &KdNode<P>, d: usize, depth: usize, k: usize) -> &P {
-        let axis = depth % k;
-        let mut min = &node.point;
+impl<'a, P: KdPoint> IntoIterator for &'a KdTree<P> {
+    type Item = &'a P;
+    type IntoIter = Iter<'a, P>;
 
-        if axis == d {
-            if let Some(ref left) = node.left {
-                let left_min = Self::find_min(left, d, depth + 1, k);
-                let left_c = left_min
-                    .coord(d)
-                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-                let min_c = min
-                    .coord(d)
-                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-                if left_c < min_c {
-                    min = left_min;
-                }
-            }
-        } else {
-            if let Some(ref left) = node.left {
-                let left_min = Self::find_min(left, d, depth + 1, k);
-                let left_c = left_min
-                    .coord(d)
-                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-                let min_c = min
-                    .coord(d)
-                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-                if left_c < min_c {
-                    min = left_min;
-                }
-            }
-            if let Some(ref right) = node.right {
-                let right_min = Self::find_min(right, d, depth + 1, k);
-                let right_c = right_min
-                    .coord(d)
-                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-                let min_c = min
-                    .coord(d)
-                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
-                if right_c < min_c {
-                    min = right_min;
-                }
-            }
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<P: KdPoint> IntoIterator for KdTree<P> {
+    type Item = P;
+    type IntoIter = IntoIter<P>;
+
+    /// Consumes the tree, yielding its points in the same order as `iter`.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            stack: self.root.into_iter().collect(),
         }
-        min
+    }
+}
+
+/// An iterator over the points of a [`KdTree`], created by [`KdTree::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a, P: KdPoint> {
+    stack: Vec<&'a KdNode<P>>,
+}
+
+impl<'a, P: KdPoint> Iterator for Iter<'a, P> {
+    type Item = &'a P;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.right.as_deref());
+        self.stack.extend(node.left.as_deref());
+        Some(&node.point)
+    }
+}
+
+/// An iterator that moves the points out of a [`KdTree`], created by its `into_iter`.
+#[derive(Debug)]
+pub struct IntoIter<P: KdPoint> {
+    stack: Vec<Box<KdNode<P>>>,
+}
+
+impl<P: KdPoint> Iterator for IntoIter<P> {
+    type Item = P;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let KdNode { point, left, right } = *self.stack.pop()?;
+        self.stack.extend(right);
+        self.stack.extend(left);
+        Some(point)
+    }
+}
+
+impl<P: KdPoint> std::fmt::Display for KdTree<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.summary().fmt(f)
     }
 }
 
@@ -660,6 +2764,57 @@ mod tests {
         assert_eq!(knn.len(), 4);
     }
 
+    #[derive(Debug, PartialEq)]
+    struct CountingPoint(f64, f64);
+
+    static CLONES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    impl Clone for CountingPoint {
+        fn clone(&self) -> Self {
+            CLONES.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            CountingPoint(self.0, self.1)
+        }
+    }
+
+    impl KdPoint for CountingPoint {
+        fn dims(&self) -> usize {
+            2
+        }
+        fn coord(&self, axis: usize) -> Result<f64, SpartError> {
+            match axis {
+                0 => Ok(self.0),
+                1 => Ok(self.1),
+                _ => Err(SpartError::InvalidDimension {
+                    requested: axis,
+                    available: 2,
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_bulk_moves_points_without_cloning() {
+        let mut tree: KdTree<CountingPoint> = KdTree::new();
+        let first: Vec<_> = (0..500)
+            .map(|i| CountingPoint((i % 37) as f64, (i % 11) as f64))
+            .collect();
+        let second: Vec<_> = (0..500)
+            .map(|i| CountingPoint((i % 13) as f64, (i % 29) as f64))
+            .collect();
+        tree.insert_bulk(first).unwrap();
+        tree.insert_bulk(second).unwrap();
+        assert_eq!(CLONES.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        for i in 0..500 {
+            assert!(tree.contains(&CountingPoint((i % 37) as f64, (i % 11) as f64)));
+            assert!(tree.contains(&CountingPoint((i % 13) as f64, (i % 29) as f64)));
+        }
+        for i in 0..500 {
+            assert!(tree.delete(&CountingPoint((i % 37) as f64, (i % 11) as f64)));
+        }
+        assert!(!tree.contains(&CountingPoint(36.0, 10.0)));
+    }
+
     #[test]
     fn test_insert_bulk_dimension_mismatch() {
         let mut tree: KdTree<Point2D<()>> = KdTree::with_dimension(3);
@@ -922,4 +3077,606 @@ mod tests {
         let results = tree.range_search::<EuclideanDistance>(&target, 1.0);
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_knn_search_from_matches_cold_search() {
+        let mut tree: KdTree<Point2D<usize>> = KdTree::new();
+        let mut points = Vec::new();
+        for i in 0..20 {
+            for j in 0..20 {
+                points.push(Point2D::new(i as f64, j as f64, Some(i * 20 + j)));
+            }
+        }
+        tree.insert_bulk(points).unwrap();
+
+        let mut query = Point2D::new(3.3, 4.1, None);
+        let mut state = tree.knn_search_state::<EuclideanDistance>(&query, 5);
+        for _ in 0..30 {
+            query = Point2D::new(query.x + 0.37, query.y + 0.21, None);
+            state = tree.knn_search_from::<EuclideanDistance>(&state, &query, 5);
+            let cold = tree.knn_search::<EuclideanDistance>(&query, 5);
+            let warm_dists: Vec<f64> = state
+                .neighbors()
+                .iter()
+                .map(|p| EuclideanDistance::distance_sq(&query, p))
+                .collect();
+            let cold_dists: Vec<f64> = cold
+                .iter()
+                .map(|p| EuclideanDistance::distance_sq(&query, p))
+                .collect();
+            assert_eq!(warm_dists, cold_dists);
+            assert_eq!(state.query(), &query);
+        }
+    }
+
+    #[test]
+    fn test_knn_search_from_after_deleting_previous_neighbors() {
+        let mut tree: KdTree<Point2D<&str>> = KdTree::new();
+        let near = Point2D::new(0.0, 0.0, Some("near"));
+        let far = Point2D::new(50.0, 50.0, Some("far"));
+        tree.insert(near.clone()).unwrap();
+        tree.insert(far.clone()).unwrap();
+
+        let query = Point2D::new(0.1, 0.1, None);
+        let state = tree.knn_search_state::<EuclideanDistance>(&query, 1);
+        assert_eq!(state.neighbors(), &[near.clone()]);
+
+        assert!(tree.delete(&near));
+        let state = tree.knn_search_from::<EuclideanDistance>(&state, &query, 1);
+        assert_eq!(state.into_neighbors(), vec![far]);
+    }
+
+    #[test]
+    fn test_knn_search_from_with_larger_k_than_previous() {
+        let mut tree: KdTree<Point2D<()>> = KdTree::new();
+        for i in 0..10 {
+            tree.insert(Point2D::new(i as f64, 0.0, None)).unwrap();
+        }
+        let query = Point2D::new(0.0, 0.0, None);
+        let state = tree.knn_search_state::<EuclideanDistance>(&query, 2);
+        let state = tree.knn_search_from::<EuclideanDistance>(&state, &query, 4);
+        assert_eq!(state.neighbors().len(), 4);
+        assert_eq!(state.neighbors()[3].x, 3.0);
+    }
+
+    #[test]
+    fn test_tolerance_contains_delete_update() {
+        let mut tree: KdTree<Point2D<i32>> = KdTree::new().with_tolerance(1e-6);
+        for i in 0..50 {
+            let v = i as f64 / 10.0;
+            tree.insert(Point2D::new(v, 5.0 - v, Some(i))).unwrap();
+        }
+        // Rounded coordinates still match, but only with equal data.
+        let rounded = Point2D::new(0.3 + 1e-9, 4.7 - 1e-9, Some(3));
+        assert!(tree.contains(&rounded));
+        assert!(!tree.contains(&Point2D::new(0.3, 4.7, Some(4))));
+        assert!(!tree.contains(&Point2D::new(0.3 + 1e-3, 4.7, Some(3))));
+
+        assert!(
+            tree.update(&rounded, Point2D::new(9.0, 9.0, Some(3)))
+                .unwrap()
+        );
+        assert!(!tree.contains(&rounded));
+        assert!(tree.contains(&Point2D::new(9.0, 9.0, Some(3))));
+        assert!(tree.delete(&Point2D::new(1.2 - 1e-9, 3.8, Some(12))));
+        assert!(
+            !tree
+                .update(&rounded, Point2D::new(1.0, 1.0, Some(3)))
+                .unwrap()
+        );
+
+        let exact: KdTree<Point2D<i32>> = KdTree::new().with_tolerance(-1.0);
+        assert_eq!(exact.tolerance(), 0.0);
+    }
+
+    #[test]
+    fn test_min_max_by_axis_match_full_scan() {
+        let mut tree: KdTree<Point3D<u32>> = KdTree::new();
+        assert!(tree.min_by_axis(5).unwrap().is_none());
+        let mut seed = 4242u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 8) % 50) as f64
+        };
+        // Few distinct coordinates, so many points tie with the node they are compared to.
+        let points: Vec<Point3D<u32>> = (0..200)
+            .map(|i| Point3D::new(next(), next(), next(), Some(i)))
+            .collect();
+        tree.insert_bulk(points[..100].to_vec()).unwrap();
+        for p in &points[100..] {
+            tree.insert(p.clone()).unwrap();
+        }
+
+        for axis in 0..3 {
+            let coord = |p: &Point3D<u32>| p.coord(axis).unwrap();
+            let min = points.iter().map(coord).fold(f64::INFINITY, f64::min);
+            let max = points.iter().map(coord).fold(f64::NEG_INFINITY, f64::max);
+            assert_eq!(coord(tree.min_by_axis(axis).unwrap().unwrap()), min);
+            assert_eq!(coord(tree.max_by_axis(axis).unwrap().unwrap()), max);
+        }
+        assert!(matches!(
+            tree.max_by_axis(3),
+            Err(SpartError::InvalidDimension {
+                requested: 3,
+                available: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_kmeans_pp_seed_picks_one_point_per_blob() {
+        use crate::random::SplitMix64;
+
+        let centers = [(0.0, 0.0), (100.0, 0.0), (0.0, 100.0), (100.0, 100.0)];
+        let mut tree = KdTree::new();
+        let mut rng = SplitMix64::new(3);
+        for i in 0..400 {
+            let (cx, cy) = centers[i % 4];
+            let x = cx + rng.next_f64() - 0.5;
+            let y = cy + rng.next_f64() - 0.5;
+            tree.insert(Point2D::new(x, y, Some(i % 4))).unwrap();
+        }
+
+        for seed in 0..5 {
+            let seeds = tree.kmeans_pp_seed(4, &mut SplitMix64::new(seed));
+            let mut blobs: Vec<usize> = seeds.iter().map(|p| p.data.unwrap()).collect();
+            blobs.sort_unstable();
+            assert_eq!(blobs, vec![0, 1, 2, 3]);
+        }
+
+        // Only two distinct positions: no more than two centroids can be picked.
+        let mut duplicates = KdTree::new();
+        for _ in 0..3 {
+            duplicates
+                .insert(Point2D::new(1.0, 1.0, None::<()>))
+                .unwrap();
+            duplicates
+                .insert(Point2D::new(2.0, 2.0, None::<()>))
+                .unwrap();
+        }
+        assert_eq!(
+            duplicates.kmeans_pp_seed(5, &mut SplitMix64::new(0)).len(),
+            2
+        );
+        let empty: KdTree<Point2D<()>> = KdTree::new();
+        assert!(empty.kmeans_pp_seed(3, &mut SplitMix64::new(0)).is_empty());
+    }
+
+    #[test]
+    fn test_assign_to_centroids_matches_brute_force() {
+        use crate::random::{RandomSource, SplitMix64};
+
+        let mut rng = SplitMix64::new(11);
+        let points: Vec<Point3D<usize>> = (0..500)
+            .map(|i| {
+                Point3D::new(
+                    (rng.next_below(20)) as f64,
+                    (rng.next_below(20)) as f64,
+                    (rng.next_below(20)) as f64,
+                    Some(i),
+                )
+            })
+            .collect();
+        let mut tree = KdTree::new();
+        tree.insert_bulk(points.clone()).unwrap();
+        let mut centroids: Vec<Point3D<usize>> = (0..7)
+            .map(|_| {
+                Point3D::new(
+                    rng.next_f64() * 20.0,
+                    rng.next_f64() * 20.0,
+                    rng.next_f64() * 20.0,
+                    None,
+                )
+            })
+            .collect();
+        // A duplicated centroid always loses the tie to the lower index.
+        centroids.push(centroids[2].clone());
+
+        let mut assignment: Vec<(usize, usize)> = tree
+            .assign_to_centroids(&centroids)
+            .unwrap()
+            .into_iter()
+            .map(|(p, c)| (p.data.unwrap(), c))
+            .collect();
+        assignment.sort_unstable();
+        let expected: Vec<(usize, usize)> = points
+            .iter()
+            .map(|p| {
+                let nearest = (0..centroids.len())
+                    .min_by(|&a, &b| {
+                        EuclideanDistance::distance_sq(p, &centroids[a])
+                            .total_cmp(&EuclideanDistance::distance_sq(p, &centroids[b]))
+                    })
+                    .unwrap();
+                (p.data.unwrap(), nearest)
+            })
+            .collect();
+        assert_eq!(assignment, expected);
+
+        assert!(matches!(
+            tree.assign_to_centroids(&[Point3D::new(0.0, 0.0, 0.0, None)]),
+            Ok(a) if a.iter().all(|&(_, c)| c == 0)
+        ));
+        assert!(tree.assign_to_centroids(&[]).unwrap().is_empty());
+        let empty: KdTree<Point3D<usize>> = KdTree::new();
+        assert!(empty.assign_to_centroids(&centroids).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stats_and_invariants() {
+        let mut tree: KdTree<Point2D<i32>> = KdTree::new();
+        assert_eq!(tree.stats().depth, 0);
+        assert!(tree.check_invariants().is_ok());
+        tree.insert_bulk(
+            (0..31)
+                .map(|i| Point2D::new(i as f64, (i % 5) as f64, Some(i)))
+                .collect(),
+        )
+        .unwrap();
+        for i in 0..10 {
+            tree.delete(&Point2D::new(
+                i as f64 * 3.0,
+                (i * 3 % 5) as f64,
+                Some(i * 3),
+            ));
+        }
+        assert!(tree.check_invariants().is_ok());
+        let stats = tree.stats();
+        assert_eq!((stats.size, stats.node_count), (21, 21));
+        assert!(stats.depth >= 5 && stats.leaf_count > 0);
+        assert_eq!(stats.fill_factor, None);
+
+        if let Some(root) = tree.root.as_mut() {
+            root.point.x = f64::NAN;
+        }
+        assert!(tree.check_invariants().is_err());
+    }
+
+    #[test]
+    fn test_self_knn_matches_brute_force() {
+        use crate::datasets::{Distribution, points_3d};
+        use crate::geometry::Cube;
+        use crate::random::SplitMix64;
+
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 10.0,
+            height: 10.0,
+            depth: 10.0,
+        };
+        let clustered = Distribution::Clustered {
+            clusters: 4,
+            spread: 0.05,
+        };
+        let mut points = points_3d(&mut SplitMix64::new(5), &boundary, 400, &clustered);
+        // Copies of a point are each other's neighbors at distance zero.
+        points.push(Point3D::new(
+            points[0].x,
+            points[0].y,
+            points[0].z,
+            Some(400),
+        ));
+        let mut tree = KdTree::new();
+        tree.insert_bulk(points).unwrap();
+
+        for k in [0, 1, 5, 12] {
+            let graph = tree.self_knn(k);
+            assert_eq!(graph.len(), 401);
+            let all = graph.points();
+            for (i, point) in all.iter().enumerate() {
+                let mut expected: Vec<f64> = (0..all.len())
+                    .filter(|&j| j != i)
+                    .map(|j| euclidean_sq(point, &all[j]).sqrt())
+                    .collect();
+                expected.sort_by(f64::total_cmp);
+                expected.truncate(k);
+                let neighbors = graph.neighbors(i);
+                let actual: Vec<f64> = neighbors.iter().map(|&(_, dist)| dist).collect();
+                assert_eq!(actual, expected, "point {i}, k = {k}");
+                assert!(
+                    neighbors.iter().all(|&(j, dist)| {
+                        j != i && dist == euclidean_sq(point, &all[j]).sqrt()
+                    })
+                );
+            }
+        }
+        let graph = tree.self_knn(1);
+        let copy = graph
+            .points()
+            .iter()
+            .position(|p| p.data == Some(400))
+            .unwrap();
+        assert_eq!(graph.neighbors(copy)[0].1, 0.0);
+        assert!(graph.is_mutual(copy, graph.neighbors(copy)[0].0));
+
+        let small: KdTree<Point2D<()>> = KdTree::new();
+        assert!(small.self_knn(3).is_empty());
+    }
+
+    #[test]
+    fn test_lof_scores_clusters_and_outliers() {
+        let mut tree = KdTree::new();
+        for i in 0..4 {
+            // A dense unit square and a sparse one, ten times larger.
+            let (x, y) = ((i % 2) as f64, (i / 2) as f64);
+            tree.insert(Point2D::new(x, y, Some(i))).unwrap();
+            tree.insert(Point2D::new(100.0 + x * 10.0, y * 10.0, Some(i + 4)))
+                .unwrap();
+        }
+        tree.insert(Point2D::new(50.0, 50.0, Some(8))).unwrap();
+        for (point, score) in tree.lof(3) {
+            match point.data {
+                // Both squares are uniform, whatever their density.
+                Some(i) if i < 8 => assert!((score - 1.0).abs() < 1e-9, "{point:?}: {score}"),
+                _ => assert!(score > 3.0, "{score}"),
+            }
+        }
+
+        let mut copies = KdTree::new();
+        for i in 0..3 {
+            copies.insert(Point2D::new(1.0, 1.0, Some(i))).unwrap();
+        }
+        copies.insert(Point2D::new(2.0, 1.0, Some(3))).unwrap();
+        let scores = copies.lof(2);
+        assert!(scores.iter().all(|(point, score)| match point.data {
+            Some(3) => score.is_infinite(),
+            _ => *score == 1.0,
+        }));
+        assert_eq!(KdTree::<Point2D<()>>::new().lof(3), Vec::new());
+    }
+
+    #[test]
+    fn test_rebuild_delete_strategy_keeps_invariants() {
+        use crate::random::SplitMix64;
+
+        let mut rng = SplitMix64::new(11);
+        // Few distinct coordinates, so many points tie on the splitting axes.
+        let mut points: Vec<Point2D<usize>> = (0..300)
+            .map(|i| {
+                let x = rng.next_below(8) as f64;
+                Point2D::new(x, rng.next_below(8) as f64, Some(i))
+            })
+            .collect();
+        let mut tree = KdTree::new().with_delete_strategy(DeleteStrategy::Rebuild);
+        assert_eq!(tree.delete_strategy(), DeleteStrategy::Rebuild);
+        for point in &points {
+            tree.insert(point.clone()).unwrap();
+        }
+        while !points.is_empty() {
+            let point = points.swap_remove(rng.next_below(points.len()));
+            assert!(tree.delete(&point));
+            assert!(!tree.contains(&point));
+            assert!(tree.check_invariants().is_ok());
+            if points.len() % 50 == 0 {
+                let mut stored = Vec::new();
+                KdTree::collect_points(&tree.root, &mut stored);
+                assert!(same_multiset(stored, points.iter().collect(), |p| vec![
+                    p.x,
+                    p.y,
+                    p.data.unwrap_or(0) as f64
+                ]));
+            }
+        }
+        assert!(tree.root.is_none());
+    }
+
+    #[test]
+    fn test_try_searches_report_invalid_queries() {
+        let mut tree: KdTree<Point2D<i32>> = KdTree::new();
+        let target = Point2D::new(0.0, 0.0, None);
+        assert!(matches!(
+            tree.try_knn_search::<EuclideanDistance>(&target, 1),
+            Err(SpartError::EmptyTree)
+        ));
+        assert!(
+            tree.try_range_search::<EuclideanDistance>(&target, 1.0)
+                .unwrap()
+                .is_empty()
+        );
+        tree.insert(Point2D::new(1.0, 0.0, Some(1))).unwrap();
+        assert!(matches!(
+            tree.try_knn_search::<EuclideanDistance>(&target, 0),
+            Err(SpartError::InvalidK { k: 0 })
+        ));
+        assert!(matches!(
+            tree.try_range_search::<EuclideanDistance>(&target, -1.0),
+            Err(SpartError::InvalidRadius { .. })
+        ));
+        assert_eq!(
+            tree.try_knn_search::<EuclideanDistance>(&target, 3)
+                .unwrap(),
+            tree.knn_search::<EuclideanDistance>(&target, 3)
+        );
+
+        let tree3d: KdTree<Point3D<i32>> = KdTree::with_dimension(2);
+        let target3d = Point3D::new(0.0, 0.0, 0.0, None);
+        assert!(matches!(
+            tree3d.try_range_search::<EuclideanDistance>(&target3d, 1.0),
+            Err(SpartError::DimensionMismatch {
+                expected: 2,
+                actual: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_degenerate_queries_return_nothing() {
+        let mut tree: KdTree<Point2D<i32>> = KdTree::new();
+        for i in 0..20 {
+            tree.insert(Point2D::new(i as f64, 0.0, Some(i))).unwrap();
+        }
+        let origin = Point2D::new(0.0, 0.0, None);
+        let nan = Point2D::new(f64::NAN, 0.0, None);
+        assert!(
+            tree.range_search::<EuclideanDistance>(&origin, -5.0)
+                .is_empty()
+        );
+        assert!(
+            tree.range_search::<EuclideanDistance>(&origin, f64::NAN)
+                .is_empty()
+        );
+        assert!(tree.knn_search::<EuclideanDistance>(&nan, 3).is_empty());
+        assert!(tree.range_search::<EuclideanDistance>(&nan, 5.0).is_empty());
+        assert!(matches!(
+            tree.try_knn_search::<EuclideanDistance>(&nan, 3),
+            Err(SpartError::InvalidCoordinates { .. })
+        ));
+
+        let points: Vec<_> = (0..20)
+            .map(|i| Point2D::new(i as f64, 0.0, Some(i)))
+            .collect();
+        let frozen = crate::frozen_kdtree::FrozenKdTree::new(&points).unwrap();
+        assert!(frozen.knn_search::<EuclideanDistance>(&nan, 3).is_empty());
+        assert!(matches!(
+            frozen.try_range_search::<EuclideanDistance>(&nan, 1.0),
+            Err(SpartError::InvalidCoordinates { .. })
+        ));
+    }
+
+    #[test]
+    fn test_knn_excluding_self_skips_all_copies() {
+        let mut tree: KdTree<Point2D<i32>> = KdTree::new();
+        let points: Vec<_> = (0..200)
+            .map(|i| Point2D::new((i * 37 % 61) as f64, (i * 17 % 43) as f64, Some(i % 50)))
+            .collect();
+        tree.insert_bulk(points.clone()).unwrap();
+        for query in points.iter().step_by(7) {
+            let found = tree.knn_search_excluding_self::<EuclideanDistance>(query, 5);
+            assert_eq!(found.len(), 5);
+            assert!(found.iter().all(|p| p != query));
+            let mut expected: Vec<f64> = points
+                .iter()
+                .filter(|p| *p != query)
+                .map(|p| query.distance_sq(p))
+                .collect();
+            expected.sort_by(f64::total_cmp);
+            let dists: Vec<f64> = found.iter().map(|p| query.distance_sq(p)).collect();
+            assert_eq!(dists, expected[..5]);
+        }
+        let odd = tree.knn_search_excluding::<EuclideanDistance>(&points[0], 3, |p| {
+            p.data.is_some_and(|d| d % 2 == 0)
+        });
+        assert_eq!(odd.len(), 3);
+        assert!(odd.iter().all(|p| p.data.unwrap() % 2 == 1));
+        assert!(
+            tree.knn_search_excluding_self::<EuclideanDistance>(&points[0], 0)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_assign_nearest_matches_greedy_brute_force() {
+        use crate::random::SplitMix64;
+        let mut rng = SplitMix64::new(3);
+        let mut facilities = KdTree::new();
+        for i in 0..40 {
+            let p = Point2D::new(rng.next_f64() * 100.0, rng.next_f64() * 100.0, Some(i));
+            facilities.insert(p).unwrap();
+        }
+        let demand: Vec<_> = (0..300)
+            .map(|i| Point2D::new(rng.next_f64() * 100.0, rng.next_f64() * 100.0, Some(i)))
+            .collect();
+        let mut all = Vec::new();
+        KdTree::collect_points(&facilities.root, &mut all);
+
+        let nearest = facilities.assign_nearest(&demand).unwrap();
+        for (d, assigned) in demand.iter().zip(&nearest) {
+            let best = all
+                .iter()
+                .map(|f| EuclideanDistance::distance_sq(d, f).sqrt())
+                .fold(f64::INFINITY, f64::min);
+            assert_eq!(assigned.map(|(_, dist)| dist), Some(best));
+        }
+
+        // Brute force: match the closest remaining pairs first.
+        let capacity = |f: &Point2D<i32>| f.data.unwrap_or(0) as usize % 7;
+        let mut pairs: Vec<(f64, usize, usize)> = (0..demand.len())
+            .flat_map(|d| (0..all.len()).map(move |f| (d, f)))
+            .map(|(d, f)| (EuclideanDistance::distance_sq(&demand[d], all[f]), d, f))
+            .collect();
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut left: Vec<usize> = all.iter().map(|f| capacity(f)).collect();
+        let mut expected = vec![None; demand.len()];
+        for (dist_sq, d, f) in pairs {
+            if expected[d].is_none() && left[f] > 0 {
+                left[f] -= 1;
+                expected[d] = Some((all[f].data, dist_sq.sqrt()));
+            }
+        }
+        let capacitated = facilities
+            .assign_nearest_capacitated(&demand, capacity)
+            .unwrap();
+        let found: Vec<_> = capacitated
+            .iter()
+            .map(|a| a.map(|(f, dist)| (f.data, dist)))
+            .collect();
+        assert_eq!(found, expected);
+        assert!(found.iter().any(Option::is_none));
+    }
+
+    #[test]
+    fn test_range_search_limited_reports_truncation() {
+        let mut tree = KdTree::new();
+        for i in 0..500 {
+            let point = Point3D::new(
+                (i % 10) as f64,
+                (i / 10 % 10) as f64,
+                (i / 100) as f64,
+                Some(i),
+            );
+            tree.insert(point).unwrap();
+        }
+        let center = Point3D::new(4.5, 4.5, 2.0, None);
+        let all = tree.range_search::<EuclideanDistance>(&center, 3.0);
+        let limited = tree.range_search_limited::<EuclideanDistance>(&center, 3.0, 10);
+        assert_eq!((limited.results.len(), limited.truncated), (10, true));
+        assert!(limited.results.iter().all(|p| all.contains(p)));
+        let limited = tree.range_search_limited::<EuclideanDistance>(&center, 3.0, all.len());
+        assert_eq!(
+            (limited.results.len(), limited.truncated),
+            (all.len(), false)
+        );
+        let limited = tree.range_search_limited::<EuclideanDistance>(&center, -1.0, 10);
+        assert!(limited.results.is_empty() && !limited.truncated);
+    }
+
+    #[test]
+    fn test_retain_and_delete_bulk() {
+        let mut tree = KdTree::new();
+        let points: Vec<_> = (0..100)
+            .map(|i| Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)))
+            .collect();
+        for point in &points {
+            tree.insert(point.clone()).unwrap();
+        }
+        assert_eq!(tree.delete_bulk(&points[..20]), 20);
+        assert_eq!(tree.delete_bulk(&points[..20]), 0);
+        assert_eq!(tree.retain(|p| p.data.is_some_and(|i| i % 4 == 0)), 60);
+        assert!(tree.check_invariants().is_ok());
+        let nearest = tree.knn_search::<EuclideanDistance>(&Point2D::new(0.0, 0.0, None), 1);
+        assert_eq!(nearest[0].data, Some(20));
+        assert_eq!(tree.retain(|_| false), 20);
+        assert_eq!(tree.stats().size, 0);
+    }
+
+    #[test]
+    fn test_iter_and_into_iter_yield_every_point() {
+        let mut tree = KdTree::new();
+        for i in 0..150 {
+            tree.insert(Point2D::new(
+                (i * 7 % 100) as f64,
+                (i * 13 % 100) as f64,
+                Some(i),
+            ))
+            .unwrap();
+        }
+        let mut ids: Vec<_> = (&tree).into_iter().filter_map(|p| p.data).collect();
+        ids.sort();
+        assert_eq!(ids, (0..150).collect::<Vec<_>>());
+        let borrowed: Vec<_> = tree.iter().cloned().collect();
+        let owned: Vec<_> = tree.into_iter().collect();
+        assert_eq!(owned, borrowed);
+    }
 }
@@ -0,0 +1,706 @@
+//! ## Query Buffers
+//!
+//! This module provides `QueryContext`, which holds the buffers used by kNN and range
+//! searches so that they can be reused across queries. Searches in hot loops (e.g., one kNN
+//! query per point of a large dataset) can pass the same context to the `knn_search_with` and
+//! `range_search_with` methods of `Quadtree`, `Octree`, and `KdTree` to avoid allocating for
+//! every query; the regular search methods are unaffected. Range searches can also append
+//! to a vector owned by the caller with `range_search_into`, which every tree provides.
+//!
+//! Internally, the `k` nearest candidates found so far are kept in a fixed-size array sorted
+//! by insertion for small `k` (the common 1-NN to 8-NN queries), and in a binary heap for
+//! larger `k`.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{EuclideanDistance, Point2D};
+//! use spart::kdtree::KdTree;
+//! use spart::knn::QueryContext;
+//!
+//! let mut tree = KdTree::new();
+//! for i in 0..100 {
+//!     tree.insert(Point2D::new(i as f64, (i % 10) as f64, Some(i))).unwrap();
+//! }
+//!
+//! let mut ctx = QueryContext::new();
+//! let mut total = 0.0;
+//! for i in 0..100 {
+//!     let query = Point2D::new(i as f64 + 0.5, 0.0, None);
+//!     let neighbors = tree.knn_search_with::<EuclideanDistance>(&mut ctx, &query, 3);
+//!     total += neighbors[0].x;
+//! }
+//! assert!(total > 0.0);
+//! ```
+//!
+//! Range searches that may match a large part of an index can be capped with the
+//! `range_search_limited` and `range_search_bbox_limited` methods of the trees, which stop
+//! traversing once `max_results` points are found and return them as `LimitedResults`, with
+//! a flag telling whether more points matched.
+//!
+//! It also provides `KnnGraph`, the result of `KdTree::self_knn`: the `k` nearest other
+//! stored points of every stored point, as an adjacency list. It is the starting point of
+//! density-based pipelines such as mutual-kNN graphs, and it computes local outlier factors
+//! (`KnnGraph::local_outlier_factors`).
+
+use ordered_float::OrderedFloat;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// The largest `k` for which the candidates are kept in an inline array.
+pub(crate) const SMALL_K: usize = 8;
+
+/// Reusable buffers for kNN and range searches in hot loops.
+///
+/// Passing the same context to many `knn_search_with` or `range_search_with` calls lets them
+/// reuse its memory instead of allocating for every query. The results of the last search
+/// are kept in the context and returned as a slice into it.
+#[derive(Debug)]
+pub struct QueryContext<P> {
+    heap: BinaryHeap<Farthest<P>>,
+    results: Vec<P>,
+}
+
+impl<P> Default for QueryContext<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> QueryContext<P> {
+    /// Creates a context with empty buffers, which grow to fit the searches they are used
+    /// for.
+    pub fn new() -> Self {
+        QueryContext {
+            heap: BinaryHeap::new(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Returns the results of the last search.
+    pub fn results(&self) -> &[P] {
+        &self.results
+    }
+
+    /// Runs a kNN search that fills a result set reusing the context's heap, and stores its
+    /// results, sorted from the nearest to the farthest.
+    pub(crate) fn knn(&mut self, k: usize, search: impl FnOnce(&mut KnnResults<P>)) -> &[P] {
+        self.results.clear();
+        if k > 0 {
+            let mut results = KnnResults::with_heap(k, std::mem::take(&mut self.heap));
+            search(&mut results);
+            self.heap = results.finish_into(&mut self.results);
+        }
+        &self.results
+    }
+
+    /// Clears the results and runs a search that appends the new results to them.
+    pub(crate) fn collect(&mut self, search: impl FnOnce(&mut Vec<P>)) -> &[P] {
+        self.results.clear();
+        search(&mut self.results);
+        &self.results
+    }
+}
+
+/// The results of a range search capped at a maximum number of results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitedResults<T> {
+    /// The results found, at most the maximum number asked for.
+    pub results: Vec<T>,
+    /// `true` if the search stopped early because more results matched than asked for.
+    pub truncated: bool,
+}
+
+impl<T> LimitedResults<T> {
+    /// Wraps the results of a search that collected up to `max_results + 1` of them (see
+    /// `limit_for`), dropping the extra one, which only shows that the cap was exceeded.
+    pub(crate) fn from_overfull(mut results: Vec<T>, max_results: usize) -> Self {
+        let truncated = results.len() > max_results;
+        results.truncate(max_results);
+        LimitedResults { results, truncated }
+    }
+
+    /// Returns the number of results a search capped at `max_results` must collect before
+    /// stopping: one more than the cap, to tell whether it was exceeded.
+    pub(crate) fn limit_for(max_results: usize) -> usize {
+        max_results.saturating_add(1)
+    }
+}
+
+/// The `k` nearest other points of every point in a set, as an adjacency list.
+///
+/// Points are referred to by their index in `points()`. Each neighbor list holds pairs of a
+/// neighbor's index and its distance, sorted from the nearest to the farthest. A point is
+/// never its own neighbor, but its copies at distance zero are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnnGraph<P> {
+    k: usize,
+    points: Vec<P>,
+    neighbors: Vec<Vec<(usize, f64)>>,
+}
+
+impl<P> KnnGraph<P> {
+    pub(crate) fn new(k: usize, points: Vec<P>, neighbors: Vec<Vec<(usize, f64)>>) -> Self {
+        KnnGraph {
+            k,
+            points,
+            neighbors,
+        }
+    }
+
+    /// Returns the number of neighbors asked for per point. Points have fewer if the set holds
+    /// at most `k` points.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the number of points.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns `true` if the graph has no points.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Returns the points, in the order their indices refer to.
+    pub fn points(&self) -> &[P] {
+        &self.points
+    }
+
+    /// Returns the neighbors of the point at `index`, as pairs of index and distance sorted
+    /// from the nearest to the farthest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not below `len()`.
+    pub fn neighbors(&self, index: usize) -> &[(usize, f64)] {
+        &self.neighbors[index]
+    }
+
+    /// Returns `true` if each of the two points is among the neighbors of the other.
+    pub fn is_mutual(&self, a: usize, b: usize) -> bool {
+        let lists = |from: usize, to: usize| {
+            self.neighbors
+                .get(from)
+                .is_some_and(|list| list.iter().any(|&(j, _)| j == to))
+        };
+        lists(a, b) && lists(b, a)
+    }
+
+    /// Consumes the graph and returns its points.
+    pub fn into_points(self) -> Vec<P> {
+        self.points
+    }
+
+    /// Computes the local outlier factor (LOF) of every point.
+    ///
+    /// The local reachability density of a point is the inverse of the mean reachability
+    /// distance to its neighbors, where the reachability distance to a neighbor is at least
+    /// the distance of that neighbor's own farthest neighbor. A point's LOF is the mean density
+    /// of its neighbors divided by its own: about 1 inside a cluster and well above 1 for an
+    /// outlier.
+    ///
+    /// # Returns
+    ///
+    /// The scores, indexed like `points()`. Points without neighbors, and points whose
+    /// neighbors all coincide with them (and with their own neighbors), score 1. A point next
+    /// to such a group of copies, but not part of it, scores infinity.
+    pub fn local_outlier_factors(&self) -> Vec<f64> {
+        let k_distance: Vec<f64> = self
+            .neighbors
+            .iter()
+            .map(|list| list.last().map_or(0.0, |&(_, dist)| dist))
+            .collect();
+        let density: Vec<f64> = self
+            .neighbors
+            .iter()
+            .map(|list| {
+                let reach: f64 = list.iter().map(|&(j, dist)| dist.max(k_distance[j])).sum();
+                list.len() as f64 / reach
+            })
+            .collect();
+        self.neighbors
+            .iter()
+            .zip(&density)
+            .map(|(list, &own)| {
+                if list.is_empty() || own.is_infinite() {
+                    return 1.0;
+                }
+                let mean = list.iter().map(|&(j, _)| density[j]).sum::<f64>() / list.len() as f64;
+                mean / own
+            })
+            .collect()
+    }
+
+    /// Returns an iterator over all directed edges, as triples of a point's index, a
+    /// neighbor's index, and their distance.
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.neighbors
+            .iter()
+            .enumerate()
+            .flat_map(|(i, list)| list.iter().map(move |&(j, dist)| (i, j, dist)))
+    }
+}
+
+/// The `k` nearest candidates found so far, with their squared distances.
+pub(crate) struct KnnResults<T> {
+    k: usize,
+    /// Up to `SMALL_K` candidates, sorted from the nearest to the farthest; the first
+    /// `small_len` slots are filled. Used if `k <= SMALL_K`.
+    small: [Option<(OrderedFloat<f64>, T)>; SMALL_K],
+    small_len: usize,
+    /// A max-heap with the farthest candidate on top. Used if `k > SMALL_K`.
+    heap: BinaryHeap<Farthest<T>>,
+}
+
+/// A heap entry ordered by distance only.
+#[derive(Debug)]
+struct Farthest<T> {
+    dist: OrderedFloat<f64>,
+    item: T,
+}
+
+impl<T> PartialEq for Farthest<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.dist == other.dist
+    }
+}
+
+impl<T> Eq for Farthest<T> {}
+
+impl<T> PartialOrd for Farthest<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Farthest<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist.cmp(&other.dist)
+    }
+}
+
+impl<T> KnnResults<T> {
+    /// Creates an empty result set for the `k` nearest candidates.
+    pub(crate) fn new(k: usize) -> Self {
+        Self::with_heap(k, BinaryHeap::new())
+    }
+
+    /// Creates an empty result set that reuses the memory of `heap` if `k` is large.
+    fn with_heap(k: usize, mut heap: BinaryHeap<Farthest<T>>) -> Self {
+        heap.clear();
+        if k > SMALL_K {
+            heap.reserve(k + 1);
+        }
+        KnnResults {
+            k,
+            small: [const { None }; SMALL_K],
+            small_len: 0,
+            heap,
+        }
+    }
+
+    fn is_small(&self) -> bool {
+        self.k <= SMALL_K
+    }
+
+    /// Returns the number of candidates kept.
+    pub(crate) fn len(&self) -> usize {
+        if self.is_small() {
+            self.small_len
+        } else {
+            self.heap.len()
+        }
+    }
+
+    /// Returns `true` once `k` candidates are kept.
+    pub(crate) fn is_full(&self) -> bool {
+        self.len() >= self.k
+    }
+
+    /// Returns the squared distance of the farthest candidate kept once the set is full, and
+    /// infinity before, i.e., the distance beyond which nothing can enter the set (negative
+    /// infinity if `k` is zero).
+    pub(crate) fn bound(&self) -> f64 {
+        if self.k == 0 {
+            return f64::NEG_INFINITY;
+        }
+        if !self.is_full() {
+            return f64::INFINITY;
+        }
+        let farthest = if self.is_small() {
+            self.small[self.small_len - 1]
+                .as_ref()
+                .map(|(dist, _)| *dist)
+        } else {
+            self.heap.peek().map(|top| top.dist)
+        };
+        farthest.map_or(f64::INFINITY, OrderedFloat::into_inner)
+    }
+
+    /// Returns `true` if a candidate at squared distance `dist_sq` would be kept.
+    pub(crate) fn accepts(&self, dist_sq: f64) -> bool {
+        self.k > 0 && (!self.is_full() || OrderedFloat(dist_sq) < OrderedFloat(self.bound()))
+    }
+
+    /// Offers a candidate, which is kept if it is nearer than the farthest one kept (or the
+    /// set is not full yet). Candidates at the same distance keep their order of insertion.
+    pub(crate) fn push(&mut self, dist_sq: f64, item: T) {
+        if !self.accepts(dist_sq) {
+            return;
+        }
+        let dist = OrderedFloat(dist_sq);
+        if self.is_small() {
+            let items = &mut self.small;
+            let mut pos = self.small_len.min(self.k - 1);
+            // Shift farther candidates one slot back; the last one drops out if full.
+            while pos > 0 && items[pos - 1].as_ref().is_some_and(|(d, _)| *d > dist) {
+                items.swap(pos - 1, pos);
+                pos -= 1;
+            }
+            items[pos] = Some((dist, item));
+            self.small_len = (self.small_len + 1).min(self.k);
+        } else {
+            self.heap.push(Farthest { dist, item });
+            if self.heap.len() > self.k {
+                self.heap.pop();
+            }
+        }
+    }
+
+    /// Returns the candidates, sorted from the nearest to the farthest.
+    pub(crate) fn into_sorted_vec(self) -> Vec<T> {
+        let mut out = Vec::with_capacity(self.len());
+        self.finish_into(&mut out);
+        out
+    }
+
+    /// Appends the candidates to `out`, sorted from the nearest to the farthest, and returns
+    /// the (emptied) heap for reuse.
+    fn finish_into(self, out: &mut Vec<T>) -> BinaryHeap<Farthest<T>> {
+        if self.is_small() {
+            let len = self.small_len;
+            out.extend(
+                self.small
+                    .into_iter()
+                    .take(len)
+                    .flatten()
+                    .map(|(_, item)| item),
+            );
+            return self.heap;
+        }
+        let mut sorted = self.heap.into_sorted_vec();
+        out.extend(sorted.drain(..).map(|entry| entry.item));
+        BinaryHeap::from(sorted)
+    }
+}
+
+/// A set of kNN candidates that the tree searches fill, and prune their traversal with.
+pub(crate) trait KnnSink<T> {
+    /// Returns `true` once no more candidates can be kept without dropping one.
+    fn is_full(&self) -> bool;
+
+    /// Returns the squared distance beyond which no candidate can enter the set.
+    fn bound(&self) -> f64;
+
+    /// Returns `true` if a candidate at squared distance `dist_sq` would be kept.
+    fn accepts(&self, dist_sq: f64) -> bool;
+
+    /// Offers a candidate at squared distance `dist_sq`.
+    fn push(&mut self, dist_sq: f64, item: T);
+}
+
+impl<T> KnnSink<T> for KnnResults<T> {
+    fn is_full(&self) -> bool {
+        KnnResults::is_full(self)
+    }
+
+    fn bound(&self) -> f64 {
+        KnnResults::bound(self)
+    }
+
+    fn accepts(&self, dist_sq: f64) -> bool {
+        KnnResults::accepts(self, dist_sq)
+    }
+
+    fn push(&mut self, dist_sq: f64, item: T) {
+        KnnResults::push(self, dist_sq, item)
+    }
+}
+
+/// A view of another set of kNN candidates that drops the candidates matching `skip`, so a
+/// search continues past them as if they were not stored.
+pub(crate) struct ExcludingSink<'s, S, F> {
+    inner: &'s mut S,
+    skip: F,
+}
+
+impl<'s, S, F> ExcludingSink<'s, S, F> {
+    /// Wraps `inner`, dropping the candidates for which `skip` returns `true`.
+    pub(crate) fn new(inner: &'s mut S, skip: F) -> Self {
+        ExcludingSink { inner, skip }
+    }
+}
+
+impl<T, S: KnnSink<T>, F: Fn(&T) -> bool> KnnSink<T> for ExcludingSink<'_, S, F> {
+    fn is_full(&self) -> bool {
+        self.inner.is_full()
+    }
+
+    fn bound(&self) -> f64 {
+        self.inner.bound()
+    }
+
+    fn accepts(&self, dist_sq: f64) -> bool {
+        self.inner.accepts(dist_sq)
+    }
+
+    fn push(&mut self, dist_sq: f64, item: T) {
+        if !(self.skip)(&item) {
+            self.inner.push(dist_sq, item);
+        }
+    }
+}
+
+/// The nearest candidates with `k` distinct keys: for each key, only its nearest candidate is
+/// kept, and only the `k` keys with the nearest candidates.
+pub(crate) struct DistinctKnnResults<T, K, F> {
+    k: usize,
+    key: F,
+    /// The kept candidates with their keys, sorted from the nearest to the farthest.
+    kept: Vec<(f64, K, T)>,
+}
+
+impl<T, K: PartialEq, F: Fn(&T) -> K> DistinctKnnResults<T, K, F> {
+    /// Creates an empty result set for the `k` nearest distinct keys.
+    pub(crate) fn new(k: usize, key: F) -> Self {
+        DistinctKnnResults {
+            k,
+            key,
+            kept: Vec::with_capacity(k.min(1024)),
+        }
+    }
+
+    /// Returns the candidates, sorted from the nearest to the farthest.
+    pub(crate) fn into_sorted_vec(self) -> Vec<T> {
+        self.kept.into_iter().map(|(_, _, item)| item).collect()
+    }
+
+    /// Inserts a candidate after the kept candidates at the same or a smaller distance.
+    fn insert_sorted(&mut self, dist_sq: f64, key: K, item: T) {
+        let pos = self.kept.partition_point(|(d, _, _)| *d <= dist_sq);
+        self.kept.insert(pos, (dist_sq, key, item));
+    }
+}
+
+impl<T, K: PartialEq, F: Fn(&T) -> K> KnnSink<T> for DistinctKnnResults<T, K, F> {
+    fn is_full(&self) -> bool {
+        self.kept.len() >= self.k
+    }
+
+    fn bound(&self) -> f64 {
+        if self.k == 0 {
+            return f64::NEG_INFINITY;
+        }
+        match self.kept.last() {
+            Some((dist, _, _)) if self.is_full() => *dist,
+            _ => f64::INFINITY,
+        }
+    }
+
+    fn accepts(&self, dist_sq: f64) -> bool {
+        self.k > 0 && (!self.is_full() || dist_sq < self.bound())
+    }
+
+    fn push(&mut self, dist_sq: f64, item: T) {
+        // A candidate nearer than the one kept for its key is always accepted, as the kept
+        // one is within the bound.
+        if !self.accepts(dist_sq) {
+            return;
+        }
+        let key = (self.key)(&item);
+        if let Some(pos) = self.kept.iter().position(|(_, k, _)| *k == key) {
+            if dist_sq < self.kept[pos].0 {
+                self.kept.remove(pos);
+                self.insert_sorted(dist_sq, key, item);
+            }
+            return;
+        }
+        if self.is_full() {
+            // The farthest key drops out. If it has nearer candidates, they are found later
+            // and compete for a place again.
+            self.kept.pop();
+        }
+        self.insert_sorted(dist_sq, key, item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_and_large_sets_agree() {
+        let distances = [5.0, 1.0, 9.0, 3.0, 3.0, 7.0, 0.5, 2.0, 8.0, 4.0, 6.0, 1.5];
+        for k in 0..=distances.len() + 2 {
+            let mut results = KnnResults::new(k);
+            for (i, &d) in distances.iter().enumerate() {
+                results.push(d, i);
+            }
+            let mut expected: Vec<usize> = (0..distances.len()).collect();
+            expected.sort_by(|&a, &b| distances[a].total_cmp(&distances[b]));
+            expected.truncate(k);
+            let found = results.into_sorted_vec();
+            let found_distances: Vec<f64> = found.iter().map(|&i| distances[i]).collect();
+            let expected_distances: Vec<f64> = expected.iter().map(|&i| distances[i]).collect();
+            assert_eq!(found_distances, expected_distances, "k = {k}");
+        }
+    }
+
+    #[test]
+    fn test_context_searches_match_regular_searches() {
+        use crate::geometry::{EuclideanDistance, Point2D, Rectangle};
+        use crate::kdtree::KdTree;
+        use crate::quadtree::Quadtree;
+        use crate::random::{RandomSource, SplitMix64};
+
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut quadtree = Quadtree::new(&boundary, 4).unwrap();
+        let mut kdtree = KdTree::new();
+        let mut rng = SplitMix64::new(11);
+        for i in 0..300 {
+            let point = Point2D::new(rng.next_f64() * 100.0, rng.next_f64() * 100.0, Some(i));
+            quadtree.insert(point.clone());
+            kdtree.insert(point).unwrap();
+        }
+
+        let mut quadtree_ctx = QueryContext::new();
+        let mut kdtree_ctx = QueryContext::new();
+        // Alternate between small and large k, so the context switches between both sets.
+        for (i, k) in [1, 20, 0, 5, 64, 8, 9].into_iter().enumerate() {
+            let query = Point2D::new(i as f64 * 13.0, 50.0, None);
+            let expected = quadtree.knn_search::<EuclideanDistance>(&query, k);
+            let found = quadtree.knn_search_with::<EuclideanDistance>(&mut quadtree_ctx, &query, k);
+            assert_eq!(found, expected.as_slice());
+            let expected = kdtree.knn_search::<EuclideanDistance>(&query, k);
+            let found = kdtree.knn_search_with::<EuclideanDistance>(&mut kdtree_ctx, &query, k);
+            assert_eq!(found, expected.as_slice());
+
+            let radius = k as f64;
+            let expected = quadtree.range_search::<EuclideanDistance>(&query, radius);
+            let found =
+                quadtree.range_search_with::<EuclideanDistance>(&mut quadtree_ctx, &query, radius);
+            assert_eq!(found, expected.as_slice());
+            let expected = kdtree.range_search::<EuclideanDistance>(&query, radius);
+            let found =
+                kdtree.range_search_with::<EuclideanDistance>(&mut kdtree_ctx, &query, radius);
+            assert_eq!(found, expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_distinct_searches_match_brute_force() {
+        use crate::geometry::{DistanceMetric, EuclideanDistance, Point2D, Rectangle};
+        use crate::kdtree::KdTree;
+        use crate::quadtree::Quadtree;
+        use crate::random::{RandomSource, SplitMix64};
+        use crate::rstar_tree::RStarTree;
+        use crate::rtree::RTree;
+
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut quadtree = Quadtree::new(&boundary, 4).unwrap();
+        let mut kdtree = KdTree::new();
+        let mut rtree = RTree::new(4).unwrap();
+        let mut rstar = RStarTree::new(4).unwrap();
+        let mut points = Vec::new();
+        let mut rng = SplitMix64::new(5);
+        for i in 0..400u32 {
+            // Key 0 holds a dense cluster near the queries, so over-fetching would miss keys.
+            let (x, y) = if i < 200 {
+                (50.0 + rng.next_f64(), 50.0 + rng.next_f64())
+            } else {
+                (rng.next_f64() * 100.0, rng.next_f64() * 100.0)
+            };
+            let key = if i < 200 { 0 } else { i % 13 };
+            points.push(Point2D::new(x, y, Some(key)));
+        }
+        for point in &points {
+            quadtree.insert(point.clone());
+            kdtree.insert(point.clone()).unwrap();
+            rtree.insert(point.clone());
+            rstar.insert(point.clone());
+        }
+
+        let key = |p: &Point2D<u32>| p.data;
+        for k in [0, 1, 3, 13, 20] {
+            let query = Point2D::new(50.5, 50.5, None);
+            let mut nearest_per_key: Vec<(f64, Option<u32>)> = Vec::new();
+            for point in &points {
+                let dist = EuclideanDistance::distance_sq(&query, point);
+                match nearest_per_key
+                    .iter_mut()
+                    .find(|(_, key)| *key == point.data)
+                {
+                    Some(entry) => entry.0 = entry.0.min(dist),
+                    None => nearest_per_key.push((dist, point.data)),
+                }
+            }
+            nearest_per_key.sort_by(|a, b| a.0.total_cmp(&b.0));
+            nearest_per_key.truncate(k);
+            let expected: Vec<f64> = nearest_per_key.iter().map(|(d, _)| *d).collect();
+
+            let distances = |found: Vec<Point2D<u32>>| -> Vec<f64> {
+                let mut keys: Vec<_> = found.iter().map(|p| p.data).collect();
+                keys.sort();
+                keys.dedup();
+                assert_eq!(keys.len(), found.len(), "keys are distinct");
+                found
+                    .iter()
+                    .map(|p| EuclideanDistance::distance_sq(&query, p))
+                    .collect()
+            };
+            let found = quadtree.knn_distinct_by::<EuclideanDistance, _>(&query, k, key);
+            assert_eq!(distances(found), expected, "quadtree, k = {k}");
+            let found = kdtree.knn_distinct_by::<EuclideanDistance, _>(&query, k, key);
+            assert_eq!(distances(found), expected, "kdtree, k = {k}");
+            let found = rtree.knn_distinct_by::<EuclideanDistance, _>(&query, k, key);
+            assert_eq!(
+                distances(found.into_iter().cloned().collect()),
+                expected,
+                "rtree, k = {k}"
+            );
+            let found = rstar.knn_distinct_by::<EuclideanDistance, _>(&query, k, key);
+            assert_eq!(
+                distances(found.into_iter().cloned().collect()),
+                expected,
+                "rstar, k = {k}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_bound_tracks_farthest_kept() {
+        let mut results = KnnResults::new(2);
+        assert_eq!(results.bound(), f64::INFINITY);
+        results.push(4.0, 'a');
+        assert_eq!(results.bound(), f64::INFINITY);
+        results.push(9.0, 'b');
+        assert_eq!(results.bound(), 9.0);
+        assert!(!results.accepts(9.0));
+        results.push(1.0, 'c');
+        assert_eq!(results.bound(), 4.0);
+        assert_eq!(results.into_sorted_vec(), vec!['c', 'a']);
+    }
+}
@@ -1,9 +1,47 @@
+pub mod adaptive;
+pub mod aggregate;
+mod checksum;
+pub mod compressed_quadtree;
+pub mod concurrent;
+pub mod covariance;
+pub mod datasets;
+pub mod elevation;
 pub mod errors;
+pub mod frozen_kdtree;
+pub mod geo;
+pub mod geofence;
 pub mod geometry;
+pub mod heatmap;
 pub mod kdtree;
+pub mod knn;
+pub mod linear;
 mod logging;
+pub mod mapped_kdtree;
+#[cfg(feature = "instrumentation")]
+pub mod metrics;
+pub mod multimap;
+pub mod observer;
 pub mod octree;
+#[cfg(feature = "tokio")]
+pub mod paged_rtree;
+pub mod payload;
+pub mod persistent;
+pub mod predicates;
+pub mod projection;
+pub mod pyramid;
 pub mod quadtree;
+pub mod random;
 pub mod rstar_tree;
 pub mod rtree;
-mod rtree_common;
+pub mod rtree_common;
+pub mod slab_octree;
+pub mod slab_quadtree;
+pub mod stats;
+pub mod testing;
+pub mod tpr_tree;
+pub mod trajectory;
+pub mod tuning;
+pub mod units;
+pub mod versioned;
+#[cfg(feature = "serde")]
+pub mod wal;
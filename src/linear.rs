@@ -0,0 +1,283 @@
+//! ## Linear Index
+//!
+//! This module provides `LinearIndex`, an index that keeps its points in a plain list and
+//! answers every query by scanning all of them. It supports the same basic operations as the
+//! trees (insertion, deletion, kNN search, and range search), so it can stand in for any of
+//! them:
+//!
+//! - As a correctness oracle: its results do not depend on any pruning, so they are exact
+//!   for every distance metric, including custom metrics the trees cannot prune with. It is
+//!   the reference index used by `spart::testing`.
+//! - As a baseline for small point sets, where a scan is often faster than a tree walk.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{EuclideanDistance, Point2D, Rectangle};
+//! use spart::linear::LinearIndex;
+//!
+//! let mut index = LinearIndex::new();
+//! index.insert(Point2D::new(1.0, 2.0, Some("a")));
+//! index.insert(Point2D::new(3.0, 4.0, Some("b")));
+//! index.insert(Point2D::new(8.0, 8.0, Some("c")));
+//!
+//! let nearest = index.knn_search::<EuclideanDistance>(&Point2D::new(2.0, 2.0, None), 2);
+//! assert_eq!(nearest[0].data, Some("a"));
+//! assert_eq!(nearest[1].data, Some("b"));
+//!
+//! let bbox = Rectangle { x: 0.0, y: 0.0, width: 5.0, height: 5.0 };
+//! assert_eq!(index.range_search_bbox(&bbox).len(), 2);
+//! ```
+
+use crate::geometry::{Cube, DistanceMetric, Point2D, Point3D, Rectangle};
+use ordered_float::OrderedFloat;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An index that answers queries by scanning all of its points.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LinearIndex<P> {
+    points: Vec<P>,
+}
+
+impl<P> Default for LinearIndex<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> LinearIndex<P> {
+    /// Creates a new, empty index.
+    pub fn new() -> Self {
+        LinearIndex { points: Vec::new() }
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns `true` if no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Returns an iterator over the stored points, in insertion order (as changed by
+    /// deletions, which move the last point into the gap).
+    pub fn iter(&self) -> std::slice::Iter<'_, P> {
+        self.points.iter()
+    }
+
+    /// Returns the stored points, in the order of `iter`.
+    pub fn as_slice(&self) -> &[P] {
+        &self.points
+    }
+
+    /// Inserts a point.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    pub fn insert(&mut self, point: P) {
+        self.points.push(point);
+    }
+
+    /// Inserts multiple points.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to insert.
+    pub fn insert_bulk(&mut self, points: &[P])
+    where
+        P: Clone,
+    {
+        self.points.extend_from_slice(points);
+    }
+
+    /// Finds the `k` nearest neighbors of the target point.
+    ///
+    /// Unlike the trees, the search does not prune, so the results are exact for any
+    /// distance metric.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search from.
+    /// * `k` - The number of neighbors to find.
+    ///
+    /// # Returns
+    ///
+    /// Up to `k` points, sorted from the nearest to the farthest. Ties keep the order of
+    /// `iter`.
+    pub fn knn_search<M: DistanceMetric<P>>(&self, target: &P, k: usize) -> Vec<P>
+    where
+        P: Clone,
+    {
+        if k == 0 {
+            return Vec::new();
+        }
+        // The position breaks ties, so that the selection keeps the order of `iter`.
+        let mut candidates: Vec<(OrderedFloat<f64>, usize)> = self
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (OrderedFloat(M::distance_sq(target, p)), i))
+            .collect();
+        if k < candidates.len() {
+            candidates.select_nth_unstable(k - 1);
+            candidates.truncate(k);
+        }
+        candidates.sort_unstable();
+        candidates
+            .into_iter()
+            .map(|(_, i)| self.points[i].clone())
+            .collect()
+    }
+
+    /// Finds all points within `radius` of the center point.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// The points within the radius, in the order of `iter`.
+    pub fn range_search<M: DistanceMetric<P>>(&self, center: &P, radius: f64) -> Vec<P>
+    where
+        P: Clone,
+    {
+        if radius < 0.0 {
+            return Vec::new();
+        }
+        let radius_sq = radius * radius;
+        self.points
+            .iter()
+            .filter(|p| M::distance_sq(center, p) <= radius_sq)
+            .cloned()
+            .collect()
+    }
+}
+
+impl<P: PartialEq> LinearIndex<P> {
+    /// Returns `true` if a point equal to `point` is stored.
+    pub fn contains(&self, point: &P) -> bool {
+        self.points.contains(point)
+    }
+
+    /// Deletes one point equal to `point`.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to delete.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and deleted.
+    pub fn delete(&mut self, point: &P) -> bool {
+        match self.points.iter().position(|p| p == point) {
+            Some(pos) => {
+                self.points.swap_remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<T: Clone> LinearIndex<Point2D<T>> {
+    /// Finds all points within a rectangle (boundary inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The rectangle to search.
+    ///
+    /// # Returns
+    ///
+    /// The points within the rectangle, in the order of `iter`.
+    pub fn range_search_bbox(&self, query: &Rectangle) -> Vec<Point2D<T>> {
+        self.points
+            .iter()
+            .filter(|p| query.contains(p))
+            .cloned()
+            .collect()
+    }
+}
+
+impl<T: Clone> LinearIndex<Point3D<T>> {
+    /// Finds all points within a cube (boundary inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The cube to search.
+    ///
+    /// # Returns
+    ///
+    /// The points within the cube, in the order of `iter`.
+    pub fn range_search_bbox(&self, query: &Cube) -> Vec<Point3D<T>> {
+        self.points
+            .iter()
+            .filter(|p| query.contains(p))
+            .cloned()
+            .collect()
+    }
+}
+
+impl<P> FromIterator<P> for LinearIndex<P> {
+    fn from_iter<I: IntoIterator<Item = P>>(iter: I) -> Self {
+        LinearIndex {
+            points: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::EuclideanDistance;
+
+    /// The squared Manhattan distance, which the trees cannot prune with.
+    struct ManhattanDistance;
+
+    impl<T> DistanceMetric<Point2D<T>> for ManhattanDistance {
+        fn distance_sq(p1: &Point2D<T>, p2: &Point2D<T>) -> f64 {
+            ((p1.x - p2.x).abs() + (p1.y - p2.y).abs()).powi(2)
+        }
+    }
+
+    #[test]
+    fn test_knn_is_exact_for_non_euclidean_metric() {
+        let index: LinearIndex<Point2D<u32>> = [(3.0, 0.0, 1), (2.0, 2.0, 2), (0.0, 5.0, 3)]
+            .into_iter()
+            .map(|(x, y, id)| Point2D::new(x, y, Some(id)))
+            .collect();
+        let origin = Point2D::new(0.0, 0.0, None);
+
+        let ids = |points: Vec<Point2D<u32>>| -> Vec<u32> {
+            points.into_iter().filter_map(|p| p.data).collect()
+        };
+        assert_eq!(
+            ids(index.knn_search::<EuclideanDistance>(&origin, 3)),
+            vec![2, 1, 3]
+        );
+        assert_eq!(
+            ids(index.knn_search::<ManhattanDistance>(&origin, 2)),
+            vec![1, 2]
+        );
+        assert!(index.knn_search::<EuclideanDistance>(&origin, 0).is_empty());
+    }
+
+    #[test]
+    fn test_delete_removes_one_match() {
+        let mut index = LinearIndex::new();
+        let point = Point3D::new(1.0, 1.0, 1.0, Some('a'));
+        index.insert(point.clone());
+        index.insert(point.clone());
+        assert!(index.delete(&point));
+        assert_eq!(index.len(), 1);
+        assert!(index.delete(&point));
+        assert!(!index.delete(&point));
+        assert!(index.is_empty());
+    }
+}
@@ -3,7 +3,14 @@
 //! This module initializes the logging configuration for Spart at startup.
 //! The logging behavior is controlled by the `DEBUG_SPART` environment variable.
 //! If `DEBUG_SPART` is not set or is set to a falsy value ("0", "false", or empty),
-//! logging will remain disabled. Otherwise, logging is enabled with a maximum level of DEBUG.
+//! logging will remain disabled. If it is set to "trace", logging is enabled with a maximum
+//! level of TRACE; otherwise, it is enabled with a maximum level of DEBUG.
+//!
+//! Operations that run once per point or per query (insertions, deletions, searches, node
+//! splits, and geometric primitives) log through `hot_trace!`, which emits a TRACE event with a
+//! static message. The points and other arguments of these operations are attached to the event
+//! only with the `trace_payloads` feature, so that formatting them (which can be expensive for
+//! large payloads) is compiled out of the hot paths by default.
 
 #[cfg(feature = "setup_tracing")]
 use ctor::ctor;
@@ -21,8 +28,30 @@ fn set_debug_level() {
         let _ =
             tracing::subscriber::set_global_default(tracing::subscriber::NoSubscriber::default());
     } else {
-        tracing_subscriber::fmt()
-            .with_max_level(Level::DEBUG)
-            .init();
+        let level = match std::env::var("DEBUG_SPART") {
+            Ok(v) if v.eq_ignore_ascii_case("trace") => Level::TRACE,
+            _ => Level::DEBUG,
+        };
+        tracing_subscriber::fmt().with_max_level(level).init();
     }
 }
+
+/// Emits a TRACE event with a static message from a hot path.
+///
+/// The `name = value` fields are recorded (with their `Debug` formatting) only if the
+/// `trace_payloads` feature is enabled; otherwise, they are not evaluated.
+#[cfg(feature = "trace_payloads")]
+macro_rules! hot_trace {
+    ($message:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        ::tracing::trace!($($name = ?$value,)* $message)
+    };
+}
+
+#[cfg(not(feature = "trace_payloads"))]
+macro_rules! hot_trace {
+    ($message:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        ::tracing::trace!($message)
+    };
+}
+
+pub(crate) use hot_trace;
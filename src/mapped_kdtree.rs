@@ -0,0 +1,565 @@
+//! ## Mapped Kd‑tree
+//!
+//! This module provides a flat binary format for `FrozenKdTree`s and `MappedKdTree`, which
+//! runs kNN and range searches directly on bytes in that format. The bytes can come from a
+//! file read into memory or from a memory-mapped file: opening a `MappedKdTree` checks the
+//! header and the checksums in one pass over the bytes, and searches read the nodes they visit
+//! in place, so read-only consumers never pay for deserializing the tree. Writers publish a file with `FrozenKdTree::write_file`, which
+//! writes to a temporary file and renames it over the target, so readers opening the file see
+//! either the previous or the new index, never a partial one.
+//!
+//! The format stores coordinates and a 64-bit id per point instead of the points themselves,
+//! so that it does not depend on the payload type. The id is chosen by the writer (e.g., a row
+//! number in a table holding the payloads) and returned by the searches.
+//!
+//! ### File Format
+//!
+//! All numbers are little-endian. A 24-byte header holds the magic bytes `SPKD`, the format
+//! version (`u32`), the number of dimensions (`u32`), the number of nodes (`u32`), and the
+//! CRC-32 checksums (`u32`) of the first 16 bytes of the header and of the nodes. The nodes
+//! follow in the van Emde Boas order of the frozen tree, each made of its coordinates
+//! (one `f64` per dimension), its id (`u64`), and the positions of its left and right children
+//! in the node array (`u32`, with `u32::MAX` for a missing child). The root is the first node.
+//!
+//! Files damaged after they were written are rejected by their checksums. Searches also only
+//! follow child references that point further into the node array, so even bytes with valid
+//! checksums cannot make them loop or read out of bounds; `validate` checks all references up
+//! front, and `verify` checks a whole file.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::frozen_kdtree::FrozenKdTree;
+//! use spart::geometry::Point2D;
+//! use spart::mapped_kdtree::MappedKdTree;
+//!
+//! let points: Vec<Point2D<u64>> = (0..100)
+//!     .map(|i| Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)))
+//!     .collect();
+//! let bytes = FrozenKdTree::new(&points).unwrap().to_bytes(|p| p.data.unwrap());
+//!
+//! let tree = MappedKdTree::new(&bytes).unwrap();
+//! let nearest = tree.knn_search(&[3.1, 4.2], 1);
+//! assert_eq!(nearest[0].0, 43);
+//! assert_eq!(tree.range_search(&[0.0, 0.0], 1.0).len(), 3);
+//! ```
+
+use crate::checksum::crc32;
+use crate::errors::SpartError;
+use crate::frozen_kdtree::{FrozenKdTree, NO_CHILD, check_links};
+use crate::kdtree::KdPoint;
+use crate::knn::KnnResults;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The magic bytes at the start of the format.
+const MAGIC: &[u8; 4] = b"SPKD";
+
+/// The version of the format written by this crate.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The size of the header in bytes.
+const HEADER_LEN: usize = 24;
+
+/// The size of the part of the header covered by its checksum.
+const CHECKED_HEADER_LEN: usize = 16;
+
+/// Returns the size in bytes of a node with `dims` coordinates.
+fn node_len(dims: usize) -> usize {
+    8 * dims + 16
+}
+
+impl<P: KdPoint> FrozenKdTree<P> {
+    /// Encodes the tree in the format read by `MappedKdTree`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Returns the id stored for a point.
+    ///
+    /// # Returns
+    ///
+    /// The encoded tree.
+    pub fn to_bytes(&self, id: impl Fn(&P) -> u64) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.len() * node_len(self.dims));
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        for value in [self.dims, self.len()] {
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        // The checksums are filled in once the nodes are written.
+        bytes.extend_from_slice(&[0; 8]);
+        for node in &self.nodes {
+            for axis in 0..self.dims {
+                let coord = node
+                    .point
+                    .coord(axis)
+                    .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"));
+                bytes.extend_from_slice(&coord.to_le_bytes());
+            }
+            bytes.extend_from_slice(&id(&node.point).to_le_bytes());
+            bytes.extend_from_slice(&node.left.to_le_bytes());
+            bytes.extend_from_slice(&node.right.to_le_bytes());
+        }
+        let header_checksum = crc32(&bytes[..CHECKED_HEADER_LEN]);
+        let nodes_checksum = crc32(&bytes[HEADER_LEN..]);
+        bytes[CHECKED_HEADER_LEN..CHECKED_HEADER_LEN + 4]
+            .copy_from_slice(&header_checksum.to_le_bytes());
+        bytes[CHECKED_HEADER_LEN + 4..HEADER_LEN].copy_from_slice(&nodes_checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Writes the tree to a file in the format read by `MappedKdTree`.
+    ///
+    /// The tree is written to a temporary file next to `path` first, which is then renamed
+    /// over `path`, so that readers never see a partially written file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to write.
+    /// * `id` - Returns the id stored for a point.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the file cannot be written.
+    pub fn write_file(&self, path: &Path, id: impl Fn(&P) -> u64) -> Result<(), SpartError> {
+        let bytes = self.to_bytes(id);
+        let mut tmp_path = OsString::from(path.as_os_str());
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let write = || -> std::io::Result<()> {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, path)
+        };
+        write().map_err(|e| SpartError::Persistence {
+            reason: format!("cannot write index {}: {e}", path.display()),
+        })
+    }
+}
+
+/// A read-only Kd‑tree searched in place in its encoded form.
+#[derive(Debug, Clone, Copy)]
+pub struct MappedKdTree<'a> {
+    bytes: &'a [u8],
+    dims: usize,
+    len: usize,
+}
+
+impl<'a> MappedKdTree<'a> {
+    /// Opens an encoded tree, checking its header, size, and checksums.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded tree, as written by `FrozenKdTree::to_bytes` or
+    ///   `FrozenKdTree::write_file`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the bytes do not start with a valid header, their
+    /// size does not match the number of nodes in the header, or a checksum does not match.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, SpartError> {
+        let corrupt = |reason: String| SpartError::Persistence { reason };
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(corrupt("not an encoded Kd-tree".to_string()));
+        }
+        if crc32(&bytes[..CHECKED_HEADER_LEN]) != read_u32(bytes, CHECKED_HEADER_LEN) {
+            return Err(corrupt("header checksum mismatch".to_string()));
+        }
+        let version = read_u32(bytes, 4);
+        if version != FORMAT_VERSION {
+            return Err(corrupt(format!("unsupported format version {version}")));
+        }
+        let dims = read_u32(bytes, 8) as usize;
+        let len = read_u32(bytes, 12) as usize;
+        if dims == 0 && len > 0 {
+            return Err(corrupt("nodes with zero dimensions".to_string()));
+        }
+        let expected = len
+            .checked_mul(node_len(dims))
+            .and_then(|nodes| nodes.checked_add(HEADER_LEN));
+        if expected != Some(bytes.len()) {
+            return Err(corrupt(format!(
+                "{} bytes do not hold {len} nodes of {dims} dimensions",
+                bytes.len()
+            )));
+        }
+        if crc32(&bytes[HEADER_LEN..]) != read_u32(bytes, CHECKED_HEADER_LEN + 4) {
+            return Err(corrupt("node checksum mismatch".to_string()));
+        }
+        Ok(MappedKdTree { bytes, dims, len })
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of dimensions of the stored points.
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    /// Checks that every child reference points further into the node array and that every
+    /// node other than the root is referenced exactly once.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvariantViolation` describing the first problem found.
+    pub fn validate(&self) -> Result<(), SpartError> {
+        check_links(self.len, |index| self.children(index))
+            .map_err(|reason| SpartError::InvariantViolation { reason })
+    }
+
+    /// Performs a k‑nearest neighbor search with Euclidean distance.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The coordinates to search around.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// The ids of the nearest points paired with their distances, ordered from nearest to
+    /// farthest; empty if `target` has a different number of dimensions than the stored
+    /// points or a NaN coordinate.
+    pub fn knn_search(&self, target: &[f64], k: usize) -> Vec<(u64, f64)> {
+        if k == 0 || !self.accepts(target) {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.knn_search_rec(0, 0, target, &mut results);
+        results
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(id, dist_sq)| (id, dist_sq.sqrt()))
+            .collect()
+    }
+
+    /// Performs a range search with Euclidean distance.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The coordinates of the center of the search.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// The ids of the points within `radius` of `center`, in storage order; empty if `center`
+    /// has a different number of dimensions than the stored points or a NaN coordinate, or if
+    /// `radius` is negative or NaN.
+    pub fn range_search(&self, center: &[f64], radius: f64) -> Vec<u64> {
+        let mut found = Vec::new();
+        if self.accepts(center) && radius >= 0.0 {
+            self.range_search_rec(0, 0, center, radius, &mut found);
+        }
+        found
+    }
+
+    /// Returns `true` if `query` can be searched around: the tree holds points with as many
+    /// dimensions, and no coordinate is NaN.
+    fn accepts(&self, query: &[f64]) -> bool {
+        !self.is_empty() && query.len() == self.dims && !query.iter().any(|c| c.is_nan())
+    }
+
+    fn knn_search_rec(
+        &self,
+        index: usize,
+        depth: usize,
+        target: &[f64],
+        results: &mut KnnResults<(u64, f64)>,
+    ) {
+        let dist_sq = self.distance_sq(index, target);
+        if results.accepts(dist_sq) {
+            results.push(dist_sq, (self.id(index), dist_sq));
+        }
+        let diff = target[depth % self.dims] - self.coord(index, depth % self.dims);
+        let [left, right] = self.children(index);
+        let (first, second) = if diff < 0.0 {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        if let Some(first) = self.follow(index, first) {
+            self.knn_search_rec(first, depth + 1, target, results);
+        }
+        if let Some(second) = self.follow(index, second) {
+            if diff * diff < results.bound() {
+                self.knn_search_rec(second, depth + 1, target, results);
+            }
+        }
+    }
+
+    fn range_search_rec(
+        &self,
+        index: usize,
+        depth: usize,
+        center: &[f64],
+        radius: f64,
+        found: &mut Vec<u64>,
+    ) {
+        if self.distance_sq(index, center) <= radius * radius {
+            found.push(self.id(index));
+        }
+        let diff = center[depth % self.dims] - self.coord(index, depth % self.dims);
+        let [left, right] = self.children(index);
+        if let Some(left) = self.follow(index, left) {
+            if diff <= radius {
+                self.range_search_rec(left, depth + 1, center, radius, found);
+            }
+        }
+        if let Some(right) = self.follow(index, right) {
+            if -diff <= radius {
+                self.range_search_rec(right, depth + 1, center, radius, found);
+            }
+        }
+    }
+
+    /// Returns the byte offset of the node at `index`.
+    fn offset(&self, index: usize) -> usize {
+        HEADER_LEN + index * node_len(self.dims)
+    }
+
+    fn coord(&self, index: usize, axis: usize) -> f64 {
+        read_f64(self.bytes, self.offset(index) + 8 * axis)
+    }
+
+    fn id(&self, index: usize) -> u64 {
+        read_u64(self.bytes, self.offset(index) + 8 * self.dims)
+    }
+
+    fn children(&self, index: usize) -> [u32; 2] {
+        let offset = self.offset(index) + 8 * self.dims + 8;
+        [
+            read_u32(self.bytes, offset),
+            read_u32(self.bytes, offset + 4),
+        ]
+    }
+
+    /// Returns the index of a child of the node at `index`, unless it is missing or does not
+    /// point further into the node array.
+    fn follow(&self, index: usize, child: u32) -> Option<usize> {
+        let child = child as usize;
+        (child != NO_CHILD as usize && child > index && child < self.len).then_some(child)
+    }
+
+    fn distance_sq(&self, index: usize, query: &[f64]) -> f64 {
+        query
+            .iter()
+            .enumerate()
+            .map(|(axis, c)| (c - self.coord(index, axis)).powi(2))
+            .sum()
+    }
+}
+
+/// Checks that a file holds a valid encoded tree, as written by `FrozenKdTree::write_file`.
+///
+/// # Arguments
+///
+/// * `path` - The file to check.
+///
+/// # Errors
+///
+/// Returns `SpartError::Persistence` if the file cannot be read or fails the checks of
+/// `MappedKdTree::new`, and `SpartError::InvariantViolation` if it fails those of
+/// `MappedKdTree::validate`.
+pub fn verify(path: &Path) -> Result<(), SpartError> {
+    let bytes = fs::read(path).map_err(|e| SpartError::Persistence {
+        reason: format!("cannot read index {}: {e}", path.display()),
+    })?;
+    MappedKdTree::new(&bytes)?.validate()
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(read_array(bytes, offset))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(read_array(bytes, offset))
+}
+
+fn read_f64(bytes: &[u8], offset: usize) -> f64 {
+    f64::from_le_bytes(read_array(bytes, offset))
+}
+
+fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> [u8; N] {
+    bytes[offset..offset + N]
+        .try_into()
+        .unwrap_or_else(|_| unreachable!("the slice has length N"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{DistanceMetric, EuclideanDistance, Point3D};
+    use crate::random::{RandomSource, SplitMix64};
+
+    /// 600 points on an integer grid, so that many distances tie.
+    fn grid_points() -> Vec<Point3D<u64>> {
+        let mut rng = SplitMix64::new(3495);
+        (0..600)
+            .map(|i| {
+                let mut coord = || rng.next_below(30) as f64;
+                Point3D::new(coord(), coord(), coord(), Some(i))
+            })
+            .collect()
+    }
+
+    fn encode(points: &[Point3D<u64>]) -> (FrozenKdTree<Point3D<u64>>, Vec<u8>) {
+        let frozen = FrozenKdTree::new(points).unwrap();
+        let bytes = frozen.to_bytes(|p| p.data.unwrap());
+        (frozen, bytes)
+    }
+
+    fn queries() -> impl Iterator<Item = Point3D<u64>> {
+        (0..30).map(|i| Point3D::new(i as f64, 15.0, 30.0 - i as f64, None))
+    }
+
+    /// Encoded bytes whose root points back at itself, with checksums recomputed as by a
+    /// faulty writer.
+    fn self_referencing(bytes: &[u8]) -> Vec<u8> {
+        let mut corrupt = bytes.to_vec();
+        let root_children = HEADER_LEN + 8 * 3 + 8;
+        corrupt[root_children..root_children + 4].copy_from_slice(&0u32.to_le_bytes());
+        let nodes_checksum = crc32(&corrupt[HEADER_LEN..]);
+        corrupt[CHECKED_HEADER_LEN + 4..HEADER_LEN].copy_from_slice(&nodes_checksum.to_le_bytes());
+        corrupt
+    }
+
+    #[test]
+    fn test_write_file_round_trips() {
+        let (frozen, bytes) = encode(&grid_points());
+        let path = std::env::temp_dir().join(format!("spart-mapped-{}.kd", std::process::id()));
+        frozen.write_file(&path, |p| p.data.unwrap()).unwrap();
+        verify(&path).unwrap();
+        let written = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, bytes);
+        assert!(matches!(verify(&path), Err(SpartError::Persistence { .. })));
+    }
+
+    #[test]
+    fn test_empty_tree_finds_nothing() {
+        let (_, bytes) = encode(&[]);
+        let mapped = MappedKdTree::new(&bytes).unwrap();
+        mapped.validate().unwrap();
+        assert!(mapped.is_empty());
+        assert!(mapped.knn_search(&[0.0, 0.0, 0.0], 3).is_empty());
+        assert!(mapped.range_search(&[0.0, 0.0, 0.0], 10.0).is_empty());
+    }
+
+    #[test]
+    fn test_knn_search_matches_frozen_tree() {
+        let points = grid_points();
+        let (frozen, bytes) = encode(&points);
+        let mapped = MappedKdTree::new(&bytes).unwrap();
+        assert_eq!((mapped.len(), mapped.dims()), (600, 3));
+        for query in queries() {
+            let expected: Vec<f64> = frozen
+                .knn_search::<EuclideanDistance>(&query, 7)
+                .iter()
+                .map(|p| EuclideanDistance::distance_sq(&query, p).sqrt())
+                .collect();
+            let found = mapped.knn_search(&[query.x, query.y, query.z], 7);
+            assert_eq!(found.iter().map(|n| n.1).collect::<Vec<_>>(), expected);
+            for (id, distance) in found {
+                let point = &points[id as usize];
+                assert_eq!(
+                    EuclideanDistance::distance_sq(&query, point).sqrt(),
+                    distance
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_search_matches_frozen_tree() {
+        let (frozen, bytes) = encode(&grid_points());
+        let mapped = MappedKdTree::new(&bytes).unwrap();
+        for query in queries() {
+            let mut expected: Vec<u64> = frozen
+                .range_search::<EuclideanDistance>(&query, 5.0)
+                .iter()
+                .map(|p| p.data.unwrap())
+                .collect();
+            let mut found = mapped.range_search(&[query.x, query.y, query.z], 5.0);
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_range_search_includes_points_at_radius() {
+        let points = [
+            Point3D::new(0.0, 0.0, 0.0, Some(0)),
+            Point3D::new(3.0, 4.0, 0.0, Some(1)),
+            Point3D::new(3.0, 4.0, 0.5, Some(2)),
+        ];
+        let (_, bytes) = encode(&points);
+        let mapped = MappedKdTree::new(&bytes).unwrap();
+        let mut found = mapped.range_search(&[0.0, 0.0, 0.0], 5.0);
+        found.sort();
+        assert_eq!(found, [0, 1]);
+    }
+
+    #[test]
+    fn test_invalid_queries_find_nothing() {
+        let (_, bytes) = encode(&grid_points());
+        let mapped = MappedKdTree::new(&bytes).unwrap();
+        assert!(mapped.knn_search(&[1.0, 2.0], 3).is_empty());
+        assert!(mapped.knn_search(&[1.0, 2.0, f64::NAN], 3).is_empty());
+        assert!(mapped.knn_search(&[1.0, 2.0, 3.0], 0).is_empty());
+        assert!(mapped.range_search(&[1.0, 2.0, 3.0], -1.0).is_empty());
+    }
+
+    #[test]
+    fn test_new_rejects_truncated_bytes() {
+        let (_, bytes) = encode(&grid_points());
+        assert!(matches!(
+            MappedKdTree::new(&bytes[..bytes.len() - 1]),
+            Err(SpartError::Persistence { .. })
+        ));
+        assert!(matches!(
+            MappedKdTree::new(&bytes[..HEADER_LEN - 1]),
+            Err(SpartError::Persistence { .. })
+        ));
+    }
+
+    #[test]
+    fn test_new_rejects_checksum_mismatch() {
+        let (_, bytes) = encode(&grid_points());
+        let mut corrupt = bytes.clone();
+        corrupt[HEADER_LEN + 3] ^= 1;
+        assert!(matches!(
+            MappedKdTree::new(&corrupt),
+            Err(SpartError::Persistence { reason }) if reason.contains("checksum")
+        ));
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_child_references() {
+        let (_, bytes) = encode(&grid_points());
+        let corrupt = self_referencing(&bytes);
+        let mapped = MappedKdTree::new(&corrupt).unwrap();
+        assert!(matches!(
+            mapped.validate(),
+            Err(SpartError::InvariantViolation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_search_does_not_follow_self_reference() {
+        let (_, bytes) = encode(&grid_points());
+        let corrupt = self_referencing(&bytes);
+        let mapped = MappedKdTree::new(&corrupt).unwrap();
+        assert!(mapped.knn_search(&[0.0, 0.0, 0.0], 1000).len() < 600);
+    }
+}
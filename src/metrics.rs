@@ -0,0 +1,227 @@
+//! ## Index Metrics
+//!
+//! This module provides `IndexMetrics`, a small set of index health metrics that can be
+//! rendered in the [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/),
+//! for services that embed an index and expose a `/metrics` endpoint. It tracks:
+//!
+//! - `<prefix>_index_size`: the number of stored points or objects (a gauge),
+//! - `<prefix>_index_depth`: the depth or height of the tree (a gauge),
+//! - `<prefix>_query_duration_seconds`: the latency of queries (a histogram).
+//!
+//! The gauges are set by the caller (e.g., after each batch of updates) and queries are timed
+//! with `time_query` or reported with `observe_query`. All methods take `&self` and are safe to
+//! call from several threads, so one instance can be shared between request handlers.
+//!
+//! This module is available with the `instrumentation` feature.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{EuclideanDistance, Point2D};
+//! use spart::metrics::IndexMetrics;
+//! use spart::rtree::RTree;
+//!
+//! let metrics = IndexMetrics::new("places");
+//! let mut tree = RTree::new(4).unwrap();
+//! for i in 0..100 {
+//!     tree.insert(Point2D::new(i as f64, 0.0, Some(i)));
+//! }
+//! metrics.set_size(100);
+//! metrics.set_depth(tree.height());
+//!
+//! let target = Point2D::new(5.0, 0.0, None);
+//! let nearest = metrics.time_query(|| tree.knn_search::<EuclideanDistance>(&target, 3));
+//! assert_eq!(nearest.len(), 3);
+//!
+//! let text = metrics.render();
+//! assert!(text.contains("places_index_size 100\n"));
+//! assert!(text.contains("places_query_duration_seconds_count 1\n"));
+//! ```
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// The upper bounds (in seconds) of the latency buckets used when none are given.
+const DEFAULT_BUCKETS: [f64; 11] = [
+    0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0,
+];
+
+/// Index size, depth, and query latency metrics, rendered in the Prometheus text format.
+#[derive(Debug)]
+pub struct IndexMetrics {
+    prefix: String,
+    size: AtomicU64,
+    depth: AtomicU64,
+    buckets: Vec<f64>,
+    /// The number of queries per bucket (not cumulative), with one extra bucket for `+Inf`.
+    bucket_counts: Vec<AtomicU64>,
+    latency_sum_nanos: AtomicU64,
+}
+
+impl IndexMetrics {
+    /// Creates a set of metrics with all values at zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix of the metric names. Characters that are not allowed in
+    ///   Prometheus metric names are replaced with underscores.
+    pub fn new(prefix: &str) -> Self {
+        let mut prefix: String = prefix
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        if prefix.is_empty() || prefix.starts_with(|c: char| c.is_ascii_digit()) {
+            prefix.insert(0, '_');
+        }
+        IndexMetrics {
+            prefix,
+            size: AtomicU64::new(0),
+            depth: AtomicU64::new(0),
+            buckets: DEFAULT_BUCKETS.to_vec(),
+            bucket_counts: (0..=DEFAULT_BUCKETS.len())
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            latency_sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the upper bounds (in seconds) of the latency histogram buckets.
+    ///
+    /// The bounds are sorted, and NaN, duplicate, and non-positive bounds are dropped. An
+    /// empty list keeps the defaults, which range from 10 microseconds to 1 second. Queries
+    /// observed before the call are discarded.
+    pub fn with_buckets(mut self, mut buckets: Vec<f64>) -> Self {
+        buckets.retain(|b| *b > 0.0);
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+        if !buckets.is_empty() {
+            self.bucket_counts = (0..=buckets.len()).map(|_| AtomicU64::new(0)).collect();
+            self.latency_sum_nanos = AtomicU64::new(0);
+            self.buckets = buckets;
+        }
+        self
+    }
+
+    /// Sets the number of stored points or objects.
+    pub fn set_size(&self, size: usize) {
+        self.size.store(size as u64, Ordering::Relaxed);
+    }
+
+    /// Sets the depth (or height) of the tree.
+    pub fn set_depth(&self, depth: usize) {
+        self.depth.store(depth as u64, Ordering::Relaxed);
+    }
+
+    /// Records the latency of one query.
+    pub fn observe_query(&self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        let bucket = self.buckets.partition_point(|bound| *bound < seconds);
+        self.bucket_counts[bucket].fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.latency_sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Runs a query and records its latency.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query.
+    pub fn time_query<R>(&self, query: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = query();
+        self.observe_query(start.elapsed());
+        result
+    }
+
+    /// Returns the number of recorded queries.
+    pub fn query_count(&self) -> u64 {
+        self.bucket_counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let prefix = &self.prefix;
+        let mut out = String::new();
+        // Writing to a `String` cannot fail.
+        let _ = writeln!(
+            out,
+            "# HELP {prefix}_index_size Number of entries stored in the index.\n\
+             # TYPE {prefix}_index_size gauge\n\
+             {prefix}_index_size {}",
+            self.size.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP {prefix}_index_depth Depth of the index tree.\n\
+             # TYPE {prefix}_index_depth gauge\n\
+             {prefix}_index_depth {}",
+            self.depth.load(Ordering::Relaxed)
+        );
+
+        let name = format!("{prefix}_query_duration_seconds");
+        let _ = writeln!(out, "# HELP {name} Latency of index queries in seconds.");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (bound, count) in self.buckets.iter().zip(&self.bucket_counts) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        if let Some(overflow) = self.bucket_counts.last() {
+            cumulative += overflow.load(Ordering::Relaxed);
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}");
+        let sum = self.latency_sum_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {cumulative}");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_histogram_and_gauges() {
+        let metrics = IndexMetrics::new("geo-index").with_buckets(vec![0.01, f64::NAN, 0.001]);
+        metrics.set_size(42);
+        metrics.set_depth(3);
+        metrics.observe_query(Duration::from_micros(500));
+        metrics.observe_query(Duration::from_millis(1));
+        metrics.observe_query(Duration::from_millis(5));
+        metrics.observe_query(Duration::from_secs(2));
+        assert_eq!(metrics.query_count(), 4);
+
+        let expected = "\
+# HELP geo_index_index_size Number of entries stored in the index.
+# TYPE geo_index_index_size gauge
+geo_index_index_size 42
+# HELP geo_index_index_depth Depth of the index tree.
+# TYPE geo_index_index_depth gauge
+geo_index_index_depth 3
+# HELP geo_index_query_duration_seconds Latency of index queries in seconds.
+# TYPE geo_index_query_duration_seconds histogram
+geo_index_query_duration_seconds_bucket{le=\"0.001\"} 2
+geo_index_query_duration_seconds_bucket{le=\"0.01\"} 3
+geo_index_query_duration_seconds_bucket{le=\"+Inf\"} 4
+geo_index_query_duration_seconds_sum 2.0065
+geo_index_query_duration_seconds_count 4
+";
+        assert_eq!(metrics.render(), expected);
+        assert!(
+            IndexMetrics::new("9lives")
+                .render()
+                .contains("_9lives_index_size 0\n")
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! ## Multiple Payloads per Location
+//!
+//! This module provides `MultiMap`, which stores any number of payloads at one location. Each
+//! distinct location is a single point in the underlying tree, whose data holds the payloads
+//! as a vector, so datasets with many payloads at identical coordinates (e.g., events at the
+//! same venue) keep the tree small and do not pile up duplicate points in one leaf.
+//!
+//! Use `MultiQuadtree` for 2D locations and `MultiOctree` for 3D locations. Coordinates are
+//! compared exactly; the tolerance of the tree is not used. Spatial queries go through
+//! `tree`, whose points carry all payloads of their location.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::Rectangle;
+//! use spart::multimap::MultiQuadtree;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let mut events: MultiQuadtree<&str> = MultiQuadtree::new(&boundary, 4).unwrap();
+//! events.insert(10.0, 20.0, "concert");
+//! events.insert(10.0, 20.0, "market");
+//! events.insert(50.0, 50.0, "parade");
+//!
+//! assert_eq!(events.get(10.0, 20.0), &["concert", "market"]);
+//! assert_eq!((events.len(), events.location_count()), (3, 2));
+//!
+//! assert!(events.remove(10.0, 20.0, &"concert"));
+//! assert_eq!(events.get(10.0, 20.0), &["market"]);
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{Cube, Point2D, Point3D, Rectangle};
+use crate::octree::Octree;
+use crate::quadtree::Quadtree;
+
+/// A tree storing a vector of payloads at each distinct location.
+///
+/// `len` counts payloads and `location_count` counts the points in the tree.
+#[derive(Debug, Clone)]
+pub struct MultiMap<I> {
+    tree: I,
+    len: usize,
+    locations: usize,
+}
+
+/// A `MultiMap` of 2D locations, backed by a `Quadtree`.
+pub type MultiQuadtree<T> = MultiMap<Quadtree<Vec<T>>>;
+
+/// A `MultiMap` of 3D locations, backed by an `Octree`.
+pub type MultiOctree<T> = MultiMap<Octree<Vec<T>>>;
+
+impl<I> MultiMap<I> {
+    /// Returns the underlying tree, for spatial queries.
+    pub fn tree(&self) -> &I {
+        &self.tree
+    }
+
+    /// Returns the number of stored payloads.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no payloads are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of distinct locations holding at least one payload.
+    pub fn location_count(&self) -> usize {
+        self.locations
+    }
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> MultiMap<Quadtree<Vec<T>>> {
+    /// Creates an empty map over a new `Quadtree`.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The rectangular region covered by the map.
+    /// * `capacity` - The maximum number of locations a node can hold before subdividing.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn new(boundary: &Rectangle, capacity: usize) -> Result<Self, SpartError> {
+        Ok(MultiMap {
+            tree: Quadtree::new(boundary, capacity)?,
+            len: 0,
+            locations: 0,
+        })
+    }
+
+    /// Adds a payload at a location.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the payload was stored, `false` if the location lies outside the boundary.
+    pub fn insert(&mut self, x: f64, y: f64, payload: T) -> bool {
+        let at = Point2D::new(x, y, None);
+        if let Some(payloads) = self.tree.point_at_mut(&at).and_then(|p| p.data.as_mut()) {
+            payloads.push(payload);
+        } else if self.tree.insert(Point2D::new(x, y, Some(vec![payload]))) {
+            self.locations += 1;
+        } else {
+            return false;
+        }
+        self.len += 1;
+        true
+    }
+
+    /// Returns the payloads stored at a location, in insertion order.
+    pub fn get(&self, x: f64, y: f64) -> &[T] {
+        self.tree
+            .point_at(&Point2D::new(x, y, None))
+            .and_then(|p| p.data.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Removes one payload equal to `payload` from a location.
+    ///
+    /// The location is removed from the tree when its last payload is removed.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching payload was found and removed.
+    pub fn remove(&mut self, x: f64, y: f64, payload: &T) -> bool {
+        let at = Point2D::new(x, y, None);
+        let Some(payloads) = self.tree.point_at_mut(&at).and_then(|p| p.data.as_mut()) else {
+            return false;
+        };
+        let Some(position) = payloads.iter().position(|p| p == payload) else {
+            return false;
+        };
+        payloads.remove(position);
+        if payloads.is_empty() {
+            self.tree.delete(&Point2D::new(x, y, Some(Vec::new())));
+            self.locations -= 1;
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Removes a location with all its payloads.
+    ///
+    /// # Returns
+    ///
+    /// The removed payloads, in insertion order (empty if there were none).
+    pub fn remove_all(&mut self, x: f64, y: f64) -> Vec<T> {
+        let at = Point2D::new(x, y, None);
+        let Some(payloads) = self.tree.point_at_mut(&at).and_then(|p| p.data.as_mut()) else {
+            return Vec::new();
+        };
+        let removed = std::mem::take(payloads);
+        self.tree.delete(&Point2D::new(x, y, Some(Vec::new())));
+        self.locations -= 1;
+        self.len -= removed.len();
+        removed
+    }
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> MultiMap<Octree<Vec<T>>> {
+    /// Creates an empty map over a new `Octree`.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The cubic region covered by the map.
+    /// * `capacity` - The maximum number of locations a node can hold before subdividing.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn new(boundary: &Cube, capacity: usize) -> Result<Self, SpartError> {
+        Ok(MultiMap {
+            tree: Octree::new(boundary, capacity)?,
+            len: 0,
+            locations: 0,
+        })
+    }
+
+    /// Adds a payload at a location.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the payload was stored, `false` if the location lies outside the boundary.
+    pub fn insert(&mut self, x: f64, y: f64, z: f64, payload: T) -> bool {
+        let at = Point3D::new(x, y, z, None);
+        if let Some(payloads) = self.tree.point_at_mut(&at).and_then(|p| p.data.as_mut()) {
+            payloads.push(payload);
+        } else if self.tree.insert(Point3D::new(x, y, z, Some(vec![payload]))) {
+            self.locations += 1;
+        } else {
+            return false;
+        }
+        self.len += 1;
+        true
+    }
+
+    /// Returns the payloads stored at a location, in insertion order.
+    pub fn get(&self, x: f64, y: f64, z: f64) -> &[T] {
+        self.tree
+            .point_at(&Point3D::new(x, y, z, None))
+            .and_then(|p| p.data.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Removes one payload equal to `payload` from a location.
+    ///
+    /// The location is removed from the tree when its last payload is removed.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching payload was found and removed.
+    pub fn remove(&mut self, x: f64, y: f64, z: f64, payload: &T) -> bool {
+        let at = Point3D::new(x, y, z, None);
+        let Some(payloads) = self.tree.point_at_mut(&at).and_then(|p| p.data.as_mut()) else {
+            return false;
+        };
+        let Some(position) = payloads.iter().position(|p| p == payload) else {
+            return false;
+        };
+        payloads.remove(position);
+        if payloads.is_empty() {
+            self.tree.delete(&Point3D::new(x, y, z, Some(Vec::new())));
+            self.locations -= 1;
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Removes a location with all its payloads.
+    ///
+    /// # Returns
+    ///
+    /// The removed payloads, in insertion order (empty if there were none).
+    pub fn remove_all(&mut self, x: f64, y: f64, z: f64) -> Vec<T> {
+        let at = Point3D::new(x, y, z, None);
+        let Some(payloads) = self.tree.point_at_mut(&at).and_then(|p| p.data.as_mut()) else {
+            return Vec::new();
+        };
+        let removed = std::mem::take(payloads);
+        self.tree.delete(&Point3D::new(x, y, z, Some(Vec::new())));
+        self.locations -= 1;
+        self.len -= removed.len();
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::EuclideanDistance;
+
+    #[test]
+    fn test_payloads_share_one_location() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let mut map: MultiQuadtree<u32> = MultiQuadtree::new(&boundary, 1).unwrap();
+        for i in 0..100 {
+            assert!(map.insert(5.0, 5.0, i));
+        }
+        assert!(map.insert(1.0, 1.0, 100));
+        assert!(!map.insert(20.0, 1.0, 101));
+        assert_eq!((map.len(), map.location_count()), (101, 2));
+        // One point per location, so a kNN query sees each location once.
+        let nearest = map
+            .tree()
+            .knn_search::<EuclideanDistance>(&Point2D::new(4.0, 4.0, None), 2);
+        assert_eq!(nearest[0].data.as_ref().map(Vec::len), Some(100));
+        assert_eq!(nearest[1].data.as_deref(), Some(&[100][..]));
+
+        assert!(map.remove(5.0, 5.0, &42));
+        assert!(!map.remove(5.0, 5.0, &42));
+        assert!(!map.remove(5.0, 6.0, &1));
+        assert_eq!(map.get(5.0, 5.0).len(), 99);
+        assert_eq!(map.remove_all(5.0, 5.0).len(), 99);
+        assert!(map.get(5.0, 5.0).is_empty());
+        assert!(map.remove(1.0, 1.0, &100));
+        assert!(map.is_empty());
+        assert_eq!(map.location_count(), 0);
+        assert!(map.tree().range_search_bbox(&boundary).is_empty());
+    }
+
+    #[test]
+    fn test_octree_locations() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 10.0,
+            height: 10.0,
+            depth: 10.0,
+        };
+        let mut map: MultiOctree<char> = MultiOctree::new(&boundary, 2).unwrap();
+        for (i, c) in "abcdef".chars().enumerate() {
+            map.insert(i as f64, 1.0, 1.0, c);
+            map.insert(i as f64, 1.0, 1.0, c.to_ascii_uppercase());
+        }
+        assert_eq!((map.len(), map.location_count()), (12, 6));
+        assert_eq!(map.get(3.0, 1.0, 1.0), &['d', 'D']);
+        assert!(map.remove(3.0, 1.0, 1.0, &'d'));
+        assert!(map.remove(3.0, 1.0, 1.0, &'D'));
+        assert_eq!(map.location_count(), 5);
+        assert_eq!(map.remove_all(0.0, 1.0, 1.0), vec!['a', 'A']);
+        assert_eq!(map.len(), 8);
+    }
+}
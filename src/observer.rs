@@ -0,0 +1,428 @@
+//! ## Change Notifications
+//!
+//! This module provides `Observed`, a wrapper that reports every change made to a tree to a
+//! set of observers, so that applications can keep derived state (e.g., a rendered map
+//! layer, per-region counters, or a cache of query results) in sync without diffing the
+//! tree. Each insertion, deletion, and update that succeeds produces a `ChangeEvent` holding
+//! the points involved and the boundary of the leaf node they are (or were) stored in.
+//!
+//! Observers are either callbacks, run synchronously right after the change, or channels,
+//! whose receivers can live on other threads. A channel is dropped once its receiver is gone.
+//!
+//! Observation is enabled with `with_observers` on `Quadtree` and `Octree`; see the
+//! `ObservableTree` trait.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::observer::ChangeEvent;
+//! use spart::quadtree::Quadtree;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let mut tree = Quadtree::new(&boundary, 4).unwrap().with_observers();
+//! let events = tree.subscribe_channel();
+//!
+//! tree.insert(Point2D::new(10.0, 20.0, Some("bike")));
+//! tree.update(&Point2D::new(10.0, 20.0, Some("bike")), Point2D::new(60.0, 70.0, Some("bike")));
+//!
+//! let received: Vec<_> = events.try_iter().collect();
+//! assert!(matches!(&received[0], ChangeEvent::Inserted { point, .. } if point.x == 10.0));
+//! assert!(matches!(&received[1], ChangeEvent::Updated { new, .. } if new.x == 60.0));
+//! ```
+
+use crate::geometry::{Cube, Point2D, Point3D, Rectangle};
+use crate::octree::Octree;
+use crate::quadtree::Quadtree;
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// A tree whose changes `Observed` can report.
+pub trait ObservableTree {
+    /// The type of the points stored in the tree.
+    type Point: Clone;
+    /// The type of the boundaries of the tree's leaf nodes.
+    type Cell: Clone;
+
+    /// Inserts a point, returning whether it was stored.
+    fn insert_point(&mut self, point: Self::Point) -> bool;
+
+    /// Deletes a point matching `point`, returning whether one was found.
+    fn delete_point(&mut self, point: &Self::Point) -> bool;
+
+    /// Replaces a point matching `old` with `new`, returning whether it was replaced.
+    fn update_point(&mut self, old: &Self::Point, new: Self::Point) -> bool;
+
+    /// Returns the boundary of the leaf node that holds (or would hold) `point`.
+    fn leaf_cell(&self, point: &Self::Point) -> Option<Self::Cell>;
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> ObservableTree for Quadtree<T> {
+    type Point = Point2D<T>;
+    type Cell = Rectangle;
+
+    fn insert_point(&mut self, point: Point2D<T>) -> bool {
+        self.insert(point)
+    }
+
+    fn delete_point(&mut self, point: &Point2D<T>) -> bool {
+        self.delete(point)
+    }
+
+    fn update_point(&mut self, old: &Point2D<T>, new: Point2D<T>) -> bool {
+        self.update(old, new)
+    }
+
+    fn leaf_cell(&self, point: &Point2D<T>) -> Option<Rectangle> {
+        self.leaf_boundary(point)
+    }
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> ObservableTree for Octree<T> {
+    type Point = Point3D<T>;
+    type Cell = Cube;
+
+    fn insert_point(&mut self, point: Point3D<T>) -> bool {
+        self.insert(point)
+    }
+
+    fn delete_point(&mut self, point: &Point3D<T>) -> bool {
+        self.delete(point)
+    }
+
+    fn update_point(&mut self, old: &Point3D<T>, new: Point3D<T>) -> bool {
+        self.update(old, new)
+    }
+
+    fn leaf_cell(&self, point: &Point3D<T>) -> Option<Cube> {
+        self.leaf_boundary(point)
+    }
+}
+
+/// A change made to an observed tree.
+///
+/// The leaf boundaries are those of the leaf node holding the point right after an insertion,
+/// and right before a deletion. They are `None` only for points outside the tree's boundary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeEvent<P, C> {
+    /// A point was inserted.
+    Inserted {
+        /// The inserted point.
+        point: P,
+        /// The boundary of the leaf node the point was stored in.
+        leaf: Option<C>,
+    },
+    /// A point was deleted.
+    Deleted {
+        /// The point passed to `delete` (with a tolerance, the stored point may differ
+        /// slightly).
+        point: P,
+        /// The boundary of the leaf node the point was deleted from.
+        leaf: Option<C>,
+    },
+    /// A point was replaced by another one.
+    Updated {
+        /// The point passed to `update` as the one to replace.
+        old: P,
+        /// The replacement point.
+        new: P,
+        /// The boundary of the leaf node `old` was deleted from.
+        old_leaf: Option<C>,
+        /// The boundary of the leaf node `new` was stored in.
+        new_leaf: Option<C>,
+    },
+}
+
+/// The change events of a tree of type `I`.
+pub type TreeEvent<I> = ChangeEvent<<I as ObservableTree>::Point, <I as ObservableTree>::Cell>;
+
+/// A registered observer.
+enum Observer<E> {
+    Callback(Box<dyn FnMut(&E) + Send>),
+    Channel(Sender<E>),
+}
+
+/// A tree together with the observers notified of its changes.
+///
+/// Updates go through `insert`, `delete`, and `update`, which notify the observers of every
+/// change that succeeds. Spatial queries go through `tree`.
+pub struct Observed<I: ObservableTree> {
+    tree: I,
+    observers: Vec<Observer<TreeEvent<I>>>,
+}
+
+impl<I: ObservableTree + std::fmt::Debug> std::fmt::Debug for Observed<I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Observed")
+            .field("tree", &self.tree)
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+impl<I: ObservableTree> Observed<I> {
+    /// Wraps a tree, with no observers yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The tree to wrap.
+    pub fn new(tree: I) -> Self {
+        Observed {
+            tree,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Returns the wrapped tree, for spatial queries.
+    pub fn tree(&self) -> &I {
+        &self.tree
+    }
+
+    /// Unwraps the tree, dropping the observers.
+    pub fn into_inner(self) -> I {
+        self.tree
+    }
+
+    /// Returns the number of registered observers.
+    pub fn observer_count(&self) -> usize {
+        self.observers.len()
+    }
+
+    /// Registers a callback, called with every change right after it is made.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The function to call with each event.
+    pub fn subscribe(&mut self, callback: impl FnMut(&TreeEvent<I>) + Send + 'static) {
+        self.observers.push(Observer::Callback(Box::new(callback)));
+    }
+
+    /// Registers a channel and returns its receiving end, which gets a copy of every change.
+    ///
+    /// The channel is unbounded, so events pile up until they are received; dropping the
+    /// receiver unregisters the channel at the next change.
+    pub fn subscribe_channel(&mut self) -> Receiver<TreeEvent<I>>
+    where
+        TreeEvent<I>: Send,
+    {
+        let (sender, receiver) = channel();
+        self.observers.push(Observer::Channel(sender));
+        receiver
+    }
+
+    /// Inserts a point into the tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was stored; observers are only notified in that case.
+    pub fn insert(&mut self, point: I::Point) -> bool {
+        if !self.tree.insert_point(point.clone()) {
+            return false;
+        }
+        let leaf = self.tree.leaf_cell(&point);
+        self.notify(ChangeEvent::Inserted { point, leaf });
+        true
+    }
+
+    /// Deletes a point matching `point` from the tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and removed; observers are only notified in that
+    /// case.
+    pub fn delete(&mut self, point: &I::Point) -> bool {
+        let leaf = self.tree.leaf_cell(point);
+        if !self.tree.delete_point(point) {
+            return false;
+        }
+        self.notify(ChangeEvent::Deleted {
+            point: point.clone(),
+            leaf,
+        });
+        true
+    }
+
+    /// Replaces a point matching `old` with `new`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was replaced; observers are only notified in that case.
+    pub fn update(&mut self, old: &I::Point, new: I::Point) -> bool {
+        let old_leaf = self.tree.leaf_cell(old);
+        if !self.tree.update_point(old, new.clone()) {
+            return false;
+        }
+        let new_leaf = self.tree.leaf_cell(&new);
+        self.notify(ChangeEvent::Updated {
+            old: old.clone(),
+            new,
+            old_leaf,
+            new_leaf,
+        });
+        true
+    }
+
+    /// Passes `event` to every observer, dropping the channels whose receiver is gone.
+    fn notify(&mut self, event: TreeEvent<I>) {
+        self.observers.retain_mut(|observer| match observer {
+            Observer::Callback(callback) => {
+                callback(&event);
+                true
+            }
+            Observer::Channel(sender) => sender.send(event.clone()).is_ok(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const BOUNDARY: Cube = Cube {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        width: 8.0,
+        height: 8.0,
+        depth: 8.0,
+    };
+
+    fn observed_octree() -> Observed<Octree<i32>> {
+        Octree::new(&BOUNDARY, 2).unwrap().with_observers()
+    }
+
+    fn point(x: f64, id: i32) -> Point3D<i32> {
+        Point3D::new(x, 1.0, 1.0, Some(id))
+    }
+
+    #[test]
+    fn test_empty_tree_reports_nothing() {
+        let mut tree = observed_octree();
+        let events = tree.subscribe_channel();
+        assert!(!tree.delete(&point(1.0, 0)));
+        assert!(!tree.update(&point(1.0, 0), point(2.0, 0)));
+        assert_eq!(events.try_iter().count(), 0);
+        assert_eq!(tree.tree().iter().count(), 0);
+    }
+
+    #[test]
+    fn test_rejected_insert_is_not_reported() {
+        let mut tree = observed_octree();
+        let events = tree.subscribe_channel();
+        assert!(!tree.insert(point(20.0, 0)));
+        assert!(!tree.insert(Point3D::new(f64::NAN, 1.0, 1.0, Some(1))));
+        assert_eq!(events.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_missing_delete_is_not_reported() {
+        let mut tree = observed_octree();
+        assert!(tree.insert(point(1.0, 0)));
+        let events = tree.subscribe_channel();
+        assert!(!tree.delete(&point(7.5, 9)));
+        assert!(!tree.update(&point(7.5, 9), point(2.0, 9)));
+        assert_eq!(events.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_insert_reports_leaf_holding_point() {
+        let mut tree = observed_octree();
+        let events = tree.subscribe_channel();
+        for i in 0..6 {
+            assert!(tree.insert(point(i as f64, i)));
+        }
+        let received: Vec<_> = events.try_iter().collect();
+        assert_eq!(received.len(), 6);
+        for event in &received {
+            let ChangeEvent::Inserted { point, leaf } = event else {
+                panic!("expected an insertion, got {event:?}");
+            };
+            // The leaf reported at insertion time held the point then; later splits may
+            // have subdivided it since.
+            assert!(leaf.as_ref().is_some_and(|cell| cell.contains(point)));
+        }
+    }
+
+    #[test]
+    fn test_point_on_boundary_has_leaf() {
+        let mut tree = observed_octree();
+        let events = tree.subscribe_channel();
+        let corner = Point3D::new(8.0, 8.0, 8.0, Some(0));
+        assert!(tree.insert(corner.clone()));
+        assert!(tree.delete(&corner));
+        let received: Vec<_> = events.try_iter().collect();
+        assert_eq!(received.len(), 2);
+        for event in received {
+            let (ChangeEvent::Inserted { point, leaf } | ChangeEvent::Deleted { point, leaf }) =
+                &event
+            else {
+                panic!("expected an insertion or a deletion, got {event:?}");
+            };
+            assert!(leaf.as_ref().is_some_and(|cell| cell.contains(point)));
+        }
+    }
+
+    #[test]
+    fn test_update_reports_both_leaves() {
+        let mut tree = observed_octree();
+        for i in 0..6 {
+            tree.insert(point(i as f64, i));
+        }
+        let events = tree.subscribe_channel();
+        let moved = Point3D::new(7.0, 7.0, 7.0, Some(0));
+        assert!(tree.update(&point(0.0, 0), moved.clone()));
+        let Ok(ChangeEvent::Updated {
+            old_leaf: Some(old_leaf),
+            new_leaf: Some(new_leaf),
+            ..
+        }) = events.try_recv()
+        else {
+            panic!("expected an update with both leaves");
+        };
+        assert!(!old_leaf.contains(&moved) && new_leaf.contains(&moved));
+    }
+
+    #[test]
+    fn test_delete_reports_leaf() {
+        let mut tree = observed_octree();
+        tree.insert(point(3.0, 0));
+        let events = tree.subscribe_channel();
+        assert!(tree.delete(&point(3.0, 0)));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(ChangeEvent::Deleted { point, leaf: Some(cell) }) if cell.contains(&point)
+        ));
+    }
+
+    #[test]
+    fn test_callbacks_and_channels_see_every_change() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 8.0,
+            height: 8.0,
+        };
+        let mut tree = Quadtree::new(&boundary, 2).unwrap().with_observers();
+        let count = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&count);
+        tree.subscribe(move |_| *counter.lock().unwrap() += 1);
+        let events = tree.subscribe_channel();
+        for i in 0..4 {
+            tree.insert(Point2D::new(i as f64, 1.0, Some(i)));
+        }
+        tree.delete(&Point2D::new(0.0, 1.0, Some(0)));
+        assert_eq!(*count.lock().unwrap(), 5);
+        assert_eq!(events.try_iter().count(), 5);
+    }
+
+    #[test]
+    fn test_dropped_channel_is_unregistered() {
+        let mut tree = observed_octree();
+        let kept = tree.subscribe_channel();
+        drop(tree.subscribe_channel());
+        assert_eq!(tree.observer_count(), 2);
+        tree.insert(point(1.0, 0));
+        assert_eq!(tree.observer_count(), 1);
+        assert_eq!(kept.try_iter().count(), 1);
+    }
+}
@@ -26,14 +26,89 @@
 //! assert!(!neighbors.is_empty());
 //! ```
 
-use crate::errors::SpartError;
-use crate::geometry::{Cube, DistanceMetric, HeapItem, Point3D};
-use ordered_float::OrderedFloat;
+use crate::covariance::{Covariance, Neighborhood};
+use crate::errors::{SpartError, check_knn_query, check_range_query};
+use crate::geometry::{
+    Cube, DistanceMetric, EuclideanDistance, Point3D, equi_depth_cells, overlap_fraction,
+};
+use crate::kdtree::{check_query_point, has_nan_coord};
+use crate::knn::{DistinctKnnResults, KnnResults, KnnSink, LimitedResults, QueryContext};
+use crate::logging::hot_trace;
+use crate::observer::Observed;
+use crate::payload::PayloadIndexed;
+use crate::quadtree::OverflowStrategy;
+use crate::random::{RandomSource, sample_indices};
+use crate::stats::{BalanceReport, LevelStats, NnDistanceStats, TreeStats, TreeSummary};
+use crate::testing::same_multiset;
+use crate::tuning::{CapacityAdvice, CapacityAdvisor};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::collections::BinaryHeap;
+use std::collections::BTreeMap;
 use tracing::info;
 
+/// One of the eight child octants of an octree node.
+///
+/// Octants are listed in Morton (Z-order) order: `index()` has bit 0 set for the right half
+/// (larger `x`), bit 1 for the bottom half (larger `y`), and bit 2 for the back half (larger `z`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Octant {
+    /// The octant with the smaller `x`, `y`, and `z` coordinates.
+    FrontTopLeft,
+    /// The octant with the larger `x` and smaller `y` and `z` coordinates.
+    FrontTopRight,
+    /// The octant with the larger `y` and smaller `x` and `z` coordinates.
+    FrontBottomLeft,
+    /// The octant with the larger `x` and `y` and smaller `z` coordinates.
+    FrontBottomRight,
+    /// The octant with the larger `z` and smaller `x` and `y` coordinates.
+    BackTopLeft,
+    /// The octant with the larger `x` and `z` and smaller `y` coordinates.
+    BackTopRight,
+    /// The octant with the larger `y` and `z` and smaller `x` coordinates.
+    BackBottomLeft,
+    /// The octant with the larger `x`, `y`, and `z` coordinates.
+    BackBottomRight,
+}
+
+impl Octant {
+    /// Returns the Morton digit (0 to 7) of the octant.
+    pub fn index(self) -> usize {
+        match self {
+            Octant::FrontTopLeft => 0,
+            Octant::FrontTopRight => 1,
+            Octant::FrontBottomLeft => 2,
+            Octant::FrontBottomRight => 3,
+            Octant::BackTopLeft => 4,
+            Octant::BackTopRight => 5,
+            Octant::BackBottomLeft => 6,
+            Octant::BackBottomRight => 7,
+        }
+    }
+}
+
+/// The point kept for each occupied voxel by `Octree::downsample`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoxelRepresentative {
+    /// The centroid of the voxel's points, carrying the data of the first of them.
+    #[default]
+    Centroid,
+    /// The first of the voxel's points met in the tree.
+    First,
+}
+
+/// The number of subdivision levels below the root. Leaves at this depth hold all points
+/// routed to them instead of subdividing further, which would never separate duplicate points.
+pub const MAX_DEPTH: usize = 32;
+
+/// The merge threshold of new trees: children are merged once they fit into their parent.
+fn default_merge_threshold() -> f64 {
+    1.0
+}
+
+/// The distance to, node of, and points in the nearest occupied cell found so far.
+type NearestCell<'a, T> = (f64, &'a Octree<T>, Vec<&'a Point3D<T>>);
+
 /// An octree for indexing of 3D points.
 ///
 /// # Type Parameters
@@ -45,10 +120,15 @@ use tracing::info;
 /// Panics with `SpartError::InvalidCapacity` if `capacity` is zero.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "OctreeData<T>"))]
 pub struct Octree<T: Clone + PartialEq> {
     boundary: Cube,
     points: Vec<Point3D<T>>,
     capacity: usize,
+    tolerance: f64,
+    overflow: OverflowStrategy,
+    merge_threshold: f64,
+    depth: usize,
     divided: bool,
     front_top_left: Option<Box<Octree<T>>>,
     front_top_right: Option<Box<Octree<T>>>,
@@ -60,6 +140,69 @@ pub struct Octree<T: Clone + PartialEq> {
     back_bottom_right: Option<Box<Octree<T>>>,
 }
 
+/// The serialized form of a `Octree`.
+///
+/// Trees serialized before the depth of each node was stored lack the `depth` field, so the
+/// depths are recomputed from the position of each node when a tree is restored.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "Octree")]
+struct OctreeData<T: Clone + PartialEq> {
+    boundary: Cube,
+    points: Vec<Point3D<T>>,
+    capacity: usize,
+    #[serde(default)]
+    tolerance: f64,
+    #[serde(default)]
+    overflow: OverflowStrategy,
+    #[serde(default = "default_merge_threshold")]
+    merge_threshold: f64,
+    #[serde(default, rename = "depth")]
+    _depth: usize,
+    divided: bool,
+    front_top_left: Option<Box<OctreeData<T>>>,
+    front_top_right: Option<Box<OctreeData<T>>>,
+    front_bottom_left: Option<Box<OctreeData<T>>>,
+    front_bottom_right: Option<Box<OctreeData<T>>>,
+    back_top_left: Option<Box<OctreeData<T>>>,
+    back_top_right: Option<Box<OctreeData<T>>>,
+    back_bottom_left: Option<Box<OctreeData<T>>>,
+    back_bottom_right: Option<Box<OctreeData<T>>>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone + PartialEq> OctreeData<T> {
+    /// Converts the node and its descendants, giving the node the depth `depth`.
+    fn into_tree(self, depth: usize) -> Octree<T> {
+        let child = |node: Option<Box<Self>>| node.map(|n| Box::new(n.into_tree(depth + 1)));
+        Octree {
+            boundary: self.boundary,
+            points: self.points,
+            capacity: self.capacity,
+            tolerance: self.tolerance,
+            overflow: self.overflow,
+            merge_threshold: self.merge_threshold,
+            depth,
+            divided: self.divided,
+            front_top_left: child(self.front_top_left),
+            front_top_right: child(self.front_top_right),
+            front_bottom_left: child(self.front_bottom_left),
+            front_bottom_right: child(self.front_bottom_right),
+            back_top_left: child(self.back_top_left),
+            back_top_right: child(self.back_top_right),
+            back_bottom_left: child(self.back_bottom_left),
+            back_bottom_right: child(self.back_bottom_right),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone + PartialEq> From<OctreeData<T>> for Octree<T> {
+    fn from(data: OctreeData<T>) -> Self {
+        data.into_tree(0)
+    }
+}
+
 impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
     /// Creates a new `Octree` with the specified boundary and capacity.
     ///
@@ -83,6 +226,10 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
             boundary: boundary.clone(),
             points: Vec::new(),
             capacity,
+            tolerance: 0.0,
+            overflow: OverflowStrategy::Split,
+            merge_threshold: default_merge_threshold(),
+            depth: 0,
             divided: false,
             front_top_left: None,
             front_top_right: None,
@@ -95,11 +242,171 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
         })
     }
 
+    /// Sets the coordinate tolerance used to match points in `contains`, `delete`, and `update`.
+    ///
+    /// With a positive tolerance, a stored point matches if its data is equal and each of its
+    /// coordinates differs by at most `tolerance`, so points whose coordinates were rounded
+    /// (e.g., by a serialization round trip) can still be found. Negative and NaN values are
+    /// treated as zero, which means exact matching (the default).
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - The maximum allowed difference per coordinate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Cube, Point3D};
+    /// use spart::octree::Octree;
+    ///
+    /// let boundary = Cube { x: 0.0, y: 0.0, z: 0.0, width: 10.0, height: 10.0, depth: 10.0 };
+    /// let mut tree = Octree::new(&boundary, 4).unwrap().with_tolerance(1e-9);
+    /// tree.insert(Point3D::new(0.1 + 0.2, 5.0, 5.0, Some(1)));
+    /// assert!(tree.delete(&Point3D::new(0.3, 5.0, 5.0, Some(1))));
+    /// ```
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance.max(0.0);
+        self
+    }
+
+    /// Returns the coordinate tolerance used to match points.
+    pub fn tolerance(&self) -> f64 {
+        self.tolerance
+    }
+
+    /// Sets what a full leaf does when another point is inserted into it: subdivide right away
+    /// (`OverflowStrategy::Split`, the default), or first hold a bounded number of extra points
+    /// (`OverflowStrategy::Spill`), which avoids splitting and merging the same node over and
+    /// over when points are inserted and deleted around its capacity.
+    ///
+    /// The strategy applies to all nodes, existing and future.
+    ///
+    /// # Arguments
+    ///
+    /// * `overflow` - The overflow strategy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Cube, Point3D};
+    /// use spart::octree::Octree;
+    /// use spart::quadtree::OverflowStrategy;
+    ///
+    /// let boundary = Cube { x: 0.0, y: 0.0, z: 0.0, width: 10.0, height: 10.0, depth: 10.0 };
+    /// let mut tree = Octree::new(&boundary, 4)
+    ///     .unwrap()
+    ///     .with_overflow(OverflowStrategy::Spill { extra: 2 });
+    /// for i in 0..6 {
+    ///     tree.insert(Point3D::new(i as f64, 1.0, 1.0, Some(i)));
+    /// }
+    /// // Six points fit into the root without subdividing it.
+    /// assert_eq!(tree.depth(), 1);
+    /// ```
+    pub fn with_overflow(mut self, overflow: OverflowStrategy) -> Self {
+        self.set_overflow(overflow);
+        self
+    }
+
+    /// Returns the overflow strategy of the tree.
+    pub fn overflow(&self) -> OverflowStrategy {
+        self.overflow
+    }
+
+    /// Sets the overflow strategy of this node and all of its descendants.
+    fn set_overflow(&mut self, overflow: OverflowStrategy) {
+        self.overflow = overflow;
+        for child in self.children_mut() {
+            child.set_overflow(overflow);
+        }
+    }
+
+    /// Sets how empty the children of a node must become before deletions merge them back
+    /// into it, as a fraction of the capacity.
+    ///
+    /// With the default of 1, children are merged as soon as they hold at most `capacity`
+    /// points in total, so a workload deleting and inserting points around that count splits
+    /// and merges the same node over and over. A lower threshold, e.g. 0.5, keeps the children
+    /// until they are half empty. Values are clamped to `[0, 1]`, and NaN is treated as 1; at
+    /// 0, children are only merged once they are empty.
+    ///
+    /// The threshold applies to all nodes, existing and future.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The fraction of the capacity at or below which children are merged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Cube, Point3D};
+    /// use spart::octree::Octree;
+    ///
+    /// let boundary = Cube { x: 0.0, y: 0.0, z: 0.0, width: 10.0, height: 10.0, depth: 10.0 };
+    /// let mut tree = Octree::new(&boundary, 4).unwrap().with_merge_threshold(0.5);
+    /// let points: Vec<_> = (0..5)
+    ///     .map(|i| Point3D::new(i as f64 * 2.0 + 0.5, 1.0, 1.0, Some(i)))
+    ///     .collect();
+    /// tree.insert_bulk(&points);
+    /// assert_eq!(tree.depth(), 2);
+    /// // Four points would fit into one node again, but only two are merged back.
+    /// tree.delete(&points[4]);
+    /// assert_eq!(tree.depth(), 2);
+    /// tree.delete(&points[3]);
+    /// tree.delete(&points[2]);
+    /// assert_eq!(tree.depth(), 1);
+    /// ```
+    pub fn with_merge_threshold(mut self, threshold: f64) -> Self {
+        let threshold = if threshold.is_nan() {
+            1.0
+        } else {
+            threshold.clamp(0.0, 1.0)
+        };
+        self.set_merge_threshold(threshold);
+        self
+    }
+
+    /// Returns the fraction of the capacity at or below which children are merged.
+    pub fn merge_threshold(&self) -> f64 {
+        self.merge_threshold
+    }
+
+    /// Sets the merge threshold of this node and all of its descendants.
+    fn set_merge_threshold(&mut self, threshold: f64) {
+        self.merge_threshold = threshold;
+        for child in self.children_mut() {
+            child.set_merge_threshold(threshold);
+        }
+    }
+
+    /// Copies the overflow strategy and merge threshold of `parent` to this new node, one level
+    /// below it.
+    fn with_settings_of(mut self, parent: &Self) -> Self {
+        self.overflow = parent.overflow;
+        self.merge_threshold = parent.merge_threshold;
+        self.depth = parent.depth + 1;
+        self
+    }
+
+    /// Adds a hash table from payloads to points, for lookups and deletions by payload.
+    ///
+    /// The points already stored are added to the table. See `payload::PayloadIndexed`.
+    pub fn with_payload_index(self) -> PayloadIndexed<Self>
+    where
+        T: std::hash::Hash + Eq,
+    {
+        PayloadIndexed::new(self)
+    }
+
+    /// Wraps the tree so that its changes are reported to observers. See `observer::Observed`.
+    pub fn with_observers(self) -> Observed<Self> {
+        Observed::new(self)
+    }
+
     /// Subdivides the current octree node into eight child octants.
     ///
     /// After subdivision, all existing points are reinserted into the appropriate children.
     fn subdivide(&mut self) {
-        info!("Subdividing Octree at boundary: {:?}", self.boundary);
+        hot_trace!("Subdividing Octree node", boundary = self.boundary);
         let x = self.boundary.x;
         let y = self.boundary.y;
         let z = self.boundary.z;
@@ -120,7 +427,7 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
                 self.capacity,
             );
             match child {
-                Ok(c) => c,
+                Ok(c) => c.with_settings_of(self),
                 Err(_) => unreachable!("capacity validated at construction"),
             }
         }));
@@ -137,7 +444,7 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
                 self.capacity,
             );
             match child {
-                Ok(c) => c,
+                Ok(c) => c.with_settings_of(self),
                 Err(_) => unreachable!("capacity validated at construction"),
             }
         }));
@@ -154,7 +461,7 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
                 self.capacity,
             );
             match child {
-                Ok(c) => c,
+                Ok(c) => c.with_settings_of(self),
                 Err(_) => unreachable!("capacity validated at construction"),
             }
         }));
@@ -171,7 +478,7 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
                 self.capacity,
             );
             match child {
-                Ok(c) => c,
+                Ok(c) => c.with_settings_of(self),
                 Err(_) => unreachable!("capacity validated at construction"),
             }
         }));
@@ -188,7 +495,7 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
                 self.capacity,
             );
             match child {
-                Ok(c) => c,
+                Ok(c) => c.with_settings_of(self),
                 Err(_) => unreachable!("capacity validated at construction"),
             }
         }));
@@ -205,7 +512,7 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
                 self.capacity,
             );
             match child {
-                Ok(c) => c,
+                Ok(c) => c.with_settings_of(self),
                 Err(_) => unreachable!("capacity validated at construction"),
             }
         }));
@@ -222,7 +529,7 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
                 self.capacity,
             );
             match child {
-                Ok(c) => c,
+                Ok(c) => c.with_settings_of(self),
                 Err(_) => unreachable!("capacity validated at construction"),
             }
         }));
@@ -239,7 +546,7 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
                 self.capacity,
             );
             match child {
-                Ok(c) => c,
+                Ok(c) => c.with_settings_of(self),
                 Err(_) => unreachable!("capacity validated at construction"),
             }
         }));
@@ -248,7 +555,98 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
         // Reinsert existing points into the appropriate children.
         let points = std::mem::take(&mut self.points);
         for point in points {
-            self.insert(point);
+            self.insert_unchecked(point);
+        }
+    }
+
+    /// Returns the child octant a point inside this node's boundary is routed to.
+    ///
+    /// Points on a split plane go to the left, top, or front side. Routing by the midpoint
+    /// (rather than by testing the children's boundaries) assigns every point in the closed
+    /// boundary to exactly one child, even when rounding makes the children slightly smaller
+    /// than the parent.
+    fn octant_for(&self, point: &Point3D<T>) -> Octant {
+        let right = point.x > self.boundary.x + self.boundary.width / 2.0;
+        let bottom = point.y > self.boundary.y + self.boundary.height / 2.0;
+        let back = point.z > self.boundary.z + self.boundary.depth / 2.0;
+        match (back, bottom, right) {
+            (false, false, false) => Octant::FrontTopLeft,
+            (false, false, true) => Octant::FrontTopRight,
+            (false, true, false) => Octant::FrontBottomLeft,
+            (false, true, true) => Octant::FrontBottomRight,
+            (true, false, false) => Octant::BackTopLeft,
+            (true, false, true) => Octant::BackTopRight,
+            (true, true, false) => Octant::BackBottomLeft,
+            (true, true, true) => Octant::BackBottomRight,
+        }
+    }
+
+    /// Returns the child node for the given octant, if the node is divided.
+    fn child(&self, octant: Octant) -> Option<&Octree<T>> {
+        match octant {
+            Octant::FrontTopLeft => self.front_top_left.as_deref(),
+            Octant::FrontTopRight => self.front_top_right.as_deref(),
+            Octant::FrontBottomLeft => self.front_bottom_left.as_deref(),
+            Octant::FrontBottomRight => self.front_bottom_right.as_deref(),
+            Octant::BackTopLeft => self.back_top_left.as_deref(),
+            Octant::BackTopRight => self.back_top_right.as_deref(),
+            Octant::BackBottomLeft => self.back_bottom_left.as_deref(),
+            Octant::BackBottomRight => self.back_bottom_right.as_deref(),
+        }
+    }
+
+    /// Returns the mutable child node for the given octant, if the node is divided.
+    fn child_mut(&mut self, octant: Octant) -> Option<&mut Octree<T>> {
+        match octant {
+            Octant::FrontTopLeft => self.front_top_left.as_deref_mut(),
+            Octant::FrontTopRight => self.front_top_right.as_deref_mut(),
+            Octant::FrontBottomLeft => self.front_bottom_left.as_deref_mut(),
+            Octant::FrontBottomRight => self.front_bottom_right.as_deref_mut(),
+            Octant::BackTopLeft => self.back_top_left.as_deref_mut(),
+            Octant::BackTopRight => self.back_top_right.as_deref_mut(),
+            Octant::BackBottomLeft => self.back_bottom_left.as_deref_mut(),
+            Octant::BackBottomRight => self.back_bottom_right.as_deref_mut(),
+        }
+    }
+
+    /// Returns the octants that may hold a point matching `point` within `tolerance`,
+    /// starting with the octant `point` itself is routed to.
+    fn candidate_octants(&self, point: &Point3D<T>, tolerance: f64) -> Vec<Octant> {
+        let routed = self.octant_for(point);
+        let mut octants = vec![routed];
+        if tolerance > 0.0 {
+            let window = Self::tolerance_window(point, tolerance);
+            for octant in [
+                Octant::FrontTopLeft,
+                Octant::FrontTopRight,
+                Octant::FrontBottomLeft,
+                Octant::FrontBottomRight,
+                Octant::BackTopLeft,
+                Octant::BackTopRight,
+                Octant::BackBottomLeft,
+                Octant::BackBottomRight,
+            ] {
+                if octant != routed
+                    && self
+                        .child(octant)
+                        .is_some_and(|c| c.boundary.intersects(&window))
+                {
+                    octants.push(octant);
+                }
+            }
+        }
+        octants
+    }
+
+    /// Returns the region of positions that match `point` within `tolerance`.
+    fn tolerance_window(point: &Point3D<T>, tolerance: f64) -> Cube {
+        Cube {
+            x: point.x - tolerance,
+            y: point.y - tolerance,
+            z: point.z - tolerance,
+            width: 2.0 * tolerance,
+            height: 2.0 * tolerance,
+            depth: 2.0 * tolerance,
         }
     }
 
@@ -373,73 +771,26 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
         if !self.boundary.contains(&point) {
             return false;
         }
+        self.insert_unchecked(point);
+        true
+    }
 
+    /// Inserts a point that is already known to lie inside this node's boundary.
+    fn insert_unchecked(&mut self, point: Point3D<T>) {
         if !self.divided {
-            if self.points.len() < self.capacity {
+            if self.points.len() < self.overflow.leaf_limit(self.capacity)
+                || self.depth >= MAX_DEPTH
+            {
                 self.points.push(point);
-                return true;
+                return;
             }
             self.subdivide();
         }
-
-        if self
-            .front_top_left
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .front_top_right
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .front_bottom_left
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .front_bottom_right
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .back_top_left
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .back_top_right
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .back_bottom_left
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .back_bottom_right
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
+        let octant = self.octant_for(&point);
+        match self.child_mut(octant) {
+            Some(child) => child.insert_unchecked(point),
+            None => self.points.push(point),
         }
-
-        unreachable!("A point within the parent boundary should always fit in a child boundary.");
     }
 
     /// Inserts a bulk of points into the octree.
@@ -448,10 +799,6 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
     ///
     /// * `points` - The points to insert.
     pub fn insert_bulk(&mut self, points: &[Point3D<T>]) {
-        if points.is_empty() {
-            return;
-        }
-
         let points_within_boundary: Vec<Point3D<T>> = points
             .iter()
             .filter(|p| self.boundary.contains(p))
@@ -461,275 +808,1550 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
         if points_within_boundary.is_empty() {
             return;
         }
+        self.insert_bulk_unchecked(points_within_boundary);
+    }
 
-        if !self.divided && self.points.len() + points_within_boundary.len() <= self.capacity {
-            self.points.extend(points_within_boundary);
-            return;
-        }
-
+    /// Inserts points that are already known to lie inside this node's boundary.
+    ///
+    /// If the points do not fit into an undivided node, the node is subdivided and the points
+    /// are partitioned among the children, recursing until every point has been stored.
+    fn insert_bulk_unchecked(&mut self, points: Vec<Point3D<T>>) {
         if !self.divided {
+            if self.points.len() + points.len() <= self.overflow.leaf_limit(self.capacity)
+                || self.depth >= MAX_DEPTH
+            {
+                self.points.extend(points);
+                return;
+            }
             self.subdivide();
         }
 
-        let mut points_to_insert = points_within_boundary;
-        if self.divided {
-            let mut children_points: [Vec<Point3D<T>>; 8] = [
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-            ];
-
-            for point in points_to_insert.drain(..) {
-                if self
-                    .front_top_left
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[0].push(point);
-                } else if self
-                    .front_top_right
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[1].push(point);
-                } else if self
-                    .front_bottom_left
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[2].push(point);
-                } else if self
-                    .front_bottom_right
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[3].push(point);
-                } else if self
-                    .back_top_left
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[4].push(point);
-                } else if self
-                    .back_top_right
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[5].push(point);
-                } else if self
-                    .back_bottom_left
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[6].push(point);
-                } else if self
-                    .back_bottom_right
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[7].push(point);
-                }
-            }
-
-            if !children_points[0].is_empty() {
-                if let Some(c) = self.front_top_left.as_mut() {
-                    c.insert_bulk(&children_points[0]);
-                }
-            }
-            if !children_points[1].is_empty() {
-                if let Some(c) = self.front_top_right.as_mut() {
-                    c.insert_bulk(&children_points[1]);
-                }
-            }
-            if !children_points[2].is_empty() {
-                if let Some(c) = self.front_bottom_left.as_mut() {
-                    c.insert_bulk(&children_points[2]);
-                }
-            }
-            if !children_points[3].is_empty() {
-                if let Some(c) = self.front_bottom_right.as_mut() {
-                    c.insert_bulk(&children_points[3]);
-                }
-            }
-            if !children_points[4].is_empty() {
-                if let Some(c) = self.back_top_left.as_mut() {
-                    c.insert_bulk(&children_points[4]);
-                }
-            }
-            if !children_points[5].is_empty() {
-                if let Some(c) = self.back_top_right.as_mut() {
-                    c.insert_bulk(&children_points[5]);
-                }
-            }
-            if !children_points[6].is_empty() {
-                if let Some(c) = self.back_bottom_left.as_mut() {
-                    c.insert_bulk(&children_points[6]);
-                }
+        let mut children_points: [Vec<Point3D<T>>; 8] = Default::default();
+        for point in points {
+            children_points[self.octant_for(&point).index()].push(point);
+        }
+        let octants = [
+            Octant::FrontTopLeft,
+            Octant::FrontTopRight,
+            Octant::FrontBottomLeft,
+            Octant::FrontBottomRight,
+            Octant::BackTopLeft,
+            Octant::BackTopRight,
+            Octant::BackBottomLeft,
+            Octant::BackBottomRight,
+        ];
+        for (octant, child_points) in octants.into_iter().zip(children_points) {
+            if child_points.is_empty() {
+                continue;
             }
-            if !children_points[7].is_empty() {
-                if let Some(c) = self.back_bottom_right.as_mut() {
-                    c.insert_bulk(&children_points[7]);
-                }
+            match self.child_mut(octant) {
+                Some(child) => child.insert_bulk_unchecked(child_points),
+                None => self.points.extend(child_points),
             }
         }
     }
 
-    /// Performs a k-nearest neighbor search for the target point.
+    /// Returns the Morton (Z-order) key of a point relative to the tree's boundary.
+    ///
+    /// Keys order points the way `export_sorted` lists them, so data can be sorted by this key
+    /// externally (e.g., in an on-disk sort) and then loaded with `import_sorted`.
     ///
     /// # Arguments
     ///
-    /// * `target` - The 3D point for which to find the k nearest neighbors.
-    /// * `k` - The number of nearest neighbors to retrieve.
+    /// * `point` - The point to compute the key for.
     ///
     /// # Returns
     ///
-    /// A vector of the k nearest 3D points, ordered from nearest to farthest.
-    ///
-    /// # Note
-    ///
-    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
-    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
-    /// performance.
-    pub fn knn_search<M: DistanceMetric<Point3D<T>>>(
-        &self,
-        target: &Point3D<T>,
-        k: usize,
-    ) -> Vec<Point3D<T>> {
-        if k == 0 {
-            return Vec::new();
+    /// The key, or `None` if the point lies outside the tree's boundary.
+    pub fn morton_key(&self, point: &Point3D<T>) -> Option<u64> {
+        if !self.boundary.contains(point) {
+            return None;
         }
-        let mut heap: BinaryHeap<HeapItem<T>> = BinaryHeap::new();
-        self.knn_search_helper::<M>(target, k, &mut heap);
-        heap.into_sorted_vec()
-            .into_iter()
-            .filter_map(|item| item.point_3d)
-            .collect()
+        Some(morton_code(&self.boundary, point))
     }
 
-    /// Helper method for recursively performing the k-nearest neighbor search.
-    fn knn_search_helper<M: DistanceMetric<Point3D<T>>>(
-        &self,
-        target: &Point3D<T>,
-        k: usize,
-        heap: &mut BinaryHeap<HeapItem<T>>,
-    ) {
-        for point in &self.points {
-            let dist_sq = M::distance_sq(point, target);
-            let item = HeapItem {
-                neg_distance: OrderedFloat(-dist_sq),
-                point_2d: None,
-                point_3d: Some(point.clone()),
-            };
-            heap.push(item);
-            if heap.len() > k {
-                heap.pop();
-            }
-        }
-        if self.divided {
-            for child in self.children() {
-                if heap.len() == k {
-                    if let Some(top) = heap.peek() {
-                        let current_farthest = -top.neg_distance.into_inner();
-                        if child.min_distance_sq(target) > current_farthest {
-                            continue;
+    /// Returns an iterator over all points in Morton (Z-order) order.
+    ///
+    /// The tree is walked leaf by leaf, sorting only the points of one leaf at a time, so the
+    /// points are streamed without collecting them first. The order is that of `morton_key`.
+    pub fn export_sorted(&self) -> impl Iterator<Item = &Point3D<T>> + '_ {
+        let mut stack = vec![self];
+        let mut leaf_points = Vec::new().into_iter();
+        std::iter::from_fn(move || {
+            loop {
+                if let Some(point) = leaf_points.next() {
+                    return Some(point);
+                }
+                let node = stack.pop()?;
+                if node.divided {
+                    // Pushed in reverse so that the children are visited in Morton order.
+                    for octant in [
+                        Octant::BackBottomRight,
+                        Octant::BackBottomLeft,
+                        Octant::BackTopRight,
+                        Octant::BackTopLeft,
+                        Octant::FrontBottomRight,
+                        Octant::FrontBottomLeft,
+                        Octant::FrontTopRight,
+                        Octant::FrontTopLeft,
+                    ] {
+                        if let Some(child) = node.child(octant) {
+                            stack.push(child);
                         }
                     }
                 }
-                child.knn_search_helper::<M>(target, k, heap);
+                let mut points: Vec<&Point3D<T>> = node.points.iter().collect();
+                points.sort_by_cached_key(|p| morton_code(&node.boundary, p));
+                leaf_points = points.into_iter();
             }
-        }
+        })
     }
 
-    /// Performs a range search, returning all points within the specified radius of the center point.
-    ///
-    /// # Arguments
+    /// Builds a tree from points sorted in Morton order, such as the output of `export_sorted`.
     ///
-    /// * `center` - The center of the search range.
-    /// * `radius` - The search radius.
+    /// Each node's points are split among its children by moving contiguous runs, which takes
+    /// linear time per level for sorted input and never clones a point. Unsorted input is
+    /// accepted too, but is sorted level by level and therefore slower. Points outside the
+    /// boundary are ignored.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A vector of 3D points within the specified range.
+    /// * `boundary` - The region covered by the tree.
+    /// * `capacity` - The maximum number of points a node can hold before subdividing.
+    /// * `points` - The points, ideally sorted by `morton_key`.
     ///
-    /// # Note
+    /// # Errors
     ///
-    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
-    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
-    /// performance.
-    pub fn range_search<M: DistanceMetric<Point3D<T>>>(
-        &self,
-        center: &Point3D<T>,
-        radius: f64,
-    ) -> Vec<Point3D<T>> {
-        if radius < 0.0 {
-            return Vec::new();
-        }
-        let mut found = Vec::new();
-        let radius_sq = radius * radius;
-        if self.min_distance_sq(center) > radius_sq {
-            return found;
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn import_sorted(
+        boundary: &Cube,
+        capacity: usize,
+        mut points: Vec<Point3D<T>>,
+    ) -> Result<Self, SpartError> {
+        let mut tree = Self::new(boundary, capacity)?;
+        points.retain(|p| tree.boundary.contains(p));
+        tree.build_sorted(points);
+        Ok(tree)
+    }
+
+    /// Fills an empty node with points that lie inside its boundary.
+    fn build_sorted(&mut self, mut points: Vec<Point3D<T>>) {
+        if points.len() <= self.capacity || self.depth >= MAX_DEPTH {
+            self.points = points;
+            return;
         }
-        for point in &self.points {
-            if M::distance_sq(point, center) <= radius_sq {
-                found.push(point.clone());
+        self.subdivide();
+        // A stable sort is linear on input that is already in Morton order.
+        points.sort_by_key(|p| self.octant_for(p).index());
+        for octant in [
+            Octant::BackBottomRight,
+            Octant::BackBottomLeft,
+            Octant::BackTopRight,
+            Octant::BackTopLeft,
+            Octant::FrontBottomRight,
+            Octant::FrontBottomLeft,
+            Octant::FrontTopRight,
+            Octant::FrontTopLeft,
+        ] {
+            let start = points.partition_point(|p| self.octant_for(p).index() < octant.index());
+            let run = points.split_off(start);
+            if run.is_empty() {
+                continue;
             }
-        }
-        if self.divided {
-            for child in self.children() {
-                found.extend(child.range_search::<M>(center, radius));
+            match self.child_mut(octant) {
+                Some(child) => child.build_sorted(run),
+                None => self.points.extend(run),
             }
         }
-        found
     }
 
-    /// Deletes a point from the octree.
+    /// Returns the stored point with the smallest coordinate along `axis`.
     ///
-    /// Returns `true` if the point was found and deleted.
+    /// Subtrees whose region cannot hold a smaller coordinate than the best point found so far
+    /// are skipped, so usually only the nodes along one side of the boundary are visited. If
+    /// several points share the smallest coordinate, any one of them is returned.
     ///
     /// # Arguments
     ///
-    /// * `point` - The 3D point to delete.
-    pub fn delete(&mut self, point: &Point3D<T>) -> bool {
-        if !self.boundary.contains(point) {
-            return false;
-        }
-        let mut deleted = false;
-        if self.divided {
-            for child in self.children_mut() {
-                if child.delete(point) {
-                    deleted = true;
-                    break;
-                }
-            }
-            self.try_merge();
-            return deleted;
-        }
-        if let Some(pos) = self.points.iter().position(|p| p == point) {
-            self.points.remove(pos);
-            info!("Deleting point {:?} from Octree", point);
-            true
-        } else {
-            false
-        }
+    /// * `axis` - The axis to compare (0 for `x`, 1 for `y`, 2 for `z`).
+    ///
+    /// # Returns
+    ///
+    /// The point, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is greater than 2.
+    pub fn min_by_axis(&self, axis: usize) -> Result<Option<&Point3D<T>>, SpartError> {
+        self.extreme_by_axis(axis, false)
     }
 
-    /// Attempts to merge child nodes back into the parent node if possible.
+    /// Returns the stored point with the largest coordinate along `axis`.
     ///
-    /// If all children are not divided and their total number of points is within capacity,
-    /// the children are merged into the parent node.
+    /// This is the counterpart of `min_by_axis` and prunes the tree the same way.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The axis to compare (0 for `x`, 1 for `y`, 2 for `z`).
+    ///
+    /// # Returns
+    ///
+    /// The point, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is greater than 2.
+    pub fn max_by_axis(&self, axis: usize) -> Result<Option<&Point3D<T>>, SpartError> {
+        self.extreme_by_axis(axis, true)
+    }
+
+    fn extreme_by_axis(&self, axis: usize, max: bool) -> Result<Option<&Point3D<T>>, SpartError> {
+        let (lo, hi) = match axis {
+            0 => (self.boundary.x, self.boundary.x + self.boundary.width),
+            1 => (self.boundary.y, self.boundary.y + self.boundary.height),
+            2 => (self.boundary.z, self.boundary.z + self.boundary.depth),
+            _ => {
+                return Err(SpartError::InvalidDimension {
+                    requested: axis,
+                    available: 3,
+                });
+            }
+        };
+        let mut best = None;
+        self.extreme_rec(axis, max, lo, hi, &mut best);
+        Ok(best.map(|(_, point)| point))
+    }
+
+    /// Updates `best` with the points of the subtree that beat it along `axis`.
+    ///
+    /// `lo..=hi` is the range of coordinates along `axis` that can be routed to this node; it
+    /// is tracked from the root rather than read from the node's boundary, which may be off by
+    /// rounding. Keys are negated when looking for the minimum, so a larger key is always
+    /// better.
+    fn extreme_rec<'a>(
+        &'a self,
+        axis: usize,
+        max: bool,
+        lo: f64,
+        hi: f64,
+        best: &mut Option<(f64, &'a Point3D<T>)>,
+    ) {
+        let bound = if max { hi } else { -lo };
+        if best.is_some_and(|(key, _)| bound <= key) {
+            return;
+        }
+        for p in &self.points {
+            let coord = match axis {
+                0 => p.x,
+                1 => p.y,
+                2 => p.z,
+                _ => unreachable!("axis checked by extreme_by_axis"),
+            };
+            let key = if max { coord } else { -coord };
+            if best.is_none_or(|(best_key, _)| key > best_key) {
+                *best = Some((key, p));
+            }
+        }
+        if !self.divided {
+            return;
+        }
+        let mid = match axis {
+            0 => self.boundary.x + self.boundary.width / 2.0,
+            1 => self.boundary.y + self.boundary.height / 2.0,
+            2 => self.boundary.z + self.boundary.depth / 2.0,
+            _ => unreachable!("axis checked by extreme_by_axis"),
+        };
+        // The half holding the larger (or smaller) coordinates is searched first, which makes
+        // pruning the other half likely.
+        let halves = if max { [1, 0] } else { [0, 1] };
+        for half in halves {
+            let (child_lo, child_hi) = if half == 1 { (mid, hi) } else { (lo, mid) };
+            for octant in [
+                Octant::FrontTopLeft,
+                Octant::FrontTopRight,
+                Octant::FrontBottomLeft,
+                Octant::FrontBottomRight,
+                Octant::BackTopLeft,
+                Octant::BackTopRight,
+                Octant::BackBottomLeft,
+                Octant::BackBottomRight,
+            ] {
+                if (octant.index() >> axis) & 1 == half {
+                    if let Some(child) = self.child(octant) {
+                        child.extreme_rec(axis, max, child_lo, child_hi, best);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Summarizes the nearest-neighbor distances of a random sample of the stored points.
+    ///
+    /// For each sampled point, the distance to its nearest other stored point is found with a
+    /// kNN query on this tree, so the cost is about `sample_size` queries rather than a scan
+    /// per point. Distances are Euclidean.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_size` - The number of points to sample; all points are used if the tree holds
+    ///   fewer.
+    /// * `rng` - The source of randomness used to draw the sample (e.g., `random::SplitMix64`).
+    ///
+    /// # Returns
+    ///
+    /// The summary, or `None` if the tree holds fewer than two points or `sample_size` is zero.
+    pub fn nn_distance_stats<R: RandomSource>(
+        &self,
+        sample_size: usize,
+        rng: &mut R,
+    ) -> Option<NnDistanceStats> {
+        let mut points = Vec::new();
+        self.collect_points(&mut points);
+        if points.len() < 2 {
+            return None;
+        }
+        let distances = sample_indices(points.len(), sample_size, rng)
+            .into_iter()
+            .filter_map(|i| {
+                let point = points[i];
+                // The nearest result is the point itself (or a copy of it at distance zero).
+                let neighbors = self.knn_search::<EuclideanDistance>(point, 2);
+                neighbors
+                    .get(1)
+                    .map(|n| EuclideanDistance::distance_sq(point, n).sqrt())
+            })
+            .collect();
+        NnDistanceStats::from_distances(distances)
+    }
+
+    /// Recommends a capacity for the points stored in this tree.
+    ///
+    /// The stored points are used as the sample: for each candidate capacity of the advisor, a
+    /// new tree with this tree's boundary is built over them and timed (see
+    /// `tuning::CapacityAdvisor::octree`). This tree itself is not modified.
+    ///
+    /// # Arguments
+    ///
+    /// * `advisor` - The advisor holding the candidates and the measurement settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if a candidate capacity is zero.
+    pub fn advise_capacity(&self, advisor: &CapacityAdvisor) -> Result<CapacityAdvice, SpartError> {
+        let mut points = Vec::new();
+        self.collect_points(&mut points);
+        let sample: Vec<Point3D<T>> = points.into_iter().cloned().collect();
+        advisor.octree(&self.boundary, &sample)
+    }
+
+    /// Returns statistics about the shape of the tree.
+    ///
+    /// Every node counts towards the fill factor, including divided nodes, which keep the
+    /// points they held before subdividing.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats {
+            size: 0,
+            node_count: 0,
+            leaf_count: 0,
+            depth: 0,
+            fill_factor: None,
+        };
+        self.stats_helper(1, &mut stats);
+        stats.fill_factor = Some(stats.size as f64 / (stats.node_count * self.capacity) as f64);
+        stats
+    }
+
+    /// Returns `true` if both trees hold the same points, counting duplicates, regardless of
+    /// their boundaries, capacities, or how the points are arranged in the nodes.
+    pub fn content_eq(&self, other: &Self) -> bool {
+        let mut mine = Vec::new();
+        self.collect_points(&mut mine);
+        let mut theirs = Vec::new();
+        other.collect_points(&mut theirs);
+        same_multiset(mine, theirs, |p| vec![p.x, p.y, p.z])
+    }
+
+    /// Returns a concise description of the tree: its size, depth, capacity, and boundary.
+    pub fn summary(&self) -> TreeSummary {
+        let stats = self.stats();
+        TreeSummary {
+            kind: "Octree",
+            size: stats.size,
+            depth: stats.depth,
+            capacity: Some(self.capacity),
+            bounds: vec![
+                (self.boundary.x, self.boundary.x + self.boundary.width),
+                (self.boundary.y, self.boundary.y + self.boundary.height),
+                (self.boundary.z, self.boundary.z + self.boundary.depth),
+            ],
+        }
+    }
+
+    /// Returns the number of levels of the tree, i.e., the number of nodes on the longest
+    /// path from the root to a leaf (1 for an undivided tree).
+    pub fn depth(&self) -> usize {
+        if self.divided {
+            1 + self
+                .children()
+                .into_iter()
+                .map(|child| child.depth())
+                .max()
+                .unwrap_or(0)
+        } else {
+            1
+        }
+    }
+
+    /// Returns the number of nodes and points at each level of the tree, to detect
+    /// pathological subdivision caused by clustered data.
+    ///
+    /// # Returns
+    ///
+    /// A `BalanceReport` with one entry per level, starting with the root.
+    pub fn balance_report(&self) -> BalanceReport {
+        let mut levels = Vec::new();
+        self.balance_report_helper(0, &mut levels);
+        BalanceReport {
+            levels,
+            capacity: self.capacity,
+            fanout: 8,
+        }
+    }
+
+    /// Helper method for counting the nodes and points of the subtree at `level`.
+    fn balance_report_helper(&self, level: usize, levels: &mut Vec<LevelStats>) {
+        if levels.len() <= level {
+            levels.push(LevelStats::default());
+        }
+        levels[level].nodes += 1;
+        levels[level].points += self.points.len();
+        if self.divided {
+            for child in self.children() {
+                child.balance_report_helper(level + 1, levels);
+            }
+        } else {
+            levels[level].leaves += 1;
+        }
+    }
+
+    /// Helper method for accumulating the statistics of the subtree at `depth`.
+    fn stats_helper(&self, depth: usize, stats: &mut TreeStats) {
+        stats.size += self.points.len();
+        stats.node_count += 1;
+        stats.depth = stats.depth.max(depth);
+        if self.divided {
+            for child in self.children() {
+                child.stats_helper(depth + 1, stats);
+            }
+        } else {
+            stats.leaf_count += 1;
+        }
+    }
+
+    /// Checks the structural invariants of the tree.
+    ///
+    /// Every point lies inside the boundary of its node and, below the root, in the octant its
+    /// parent routes it to, and a node has all eight children exactly when it is divided.
+    /// A tree built through the public API always passes; the check is meant for trees
+    /// restored from untrusted or possibly corrupted data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvariantViolation` describing the first violation found.
+    pub fn check_invariants(&self) -> Result<(), SpartError> {
+        self.check_invariants_helper(0)
+    }
+
+    /// Helper method for checking the invariants of the subtree at `depth`.
+    fn check_invariants_helper(&self, depth: usize) -> Result<(), SpartError> {
+        let violation = |reason: String| Err(SpartError::InvariantViolation { reason });
+        if let Some(point) = self.points.iter().find(|p| !self.boundary.contains(p)) {
+            return violation(format!(
+                "point ({}, {}, {}) at depth {depth} lies outside its node",
+                point.x, point.y, point.z
+            ));
+        }
+        let octants = [
+            Octant::FrontTopLeft,
+            Octant::FrontTopRight,
+            Octant::FrontBottomLeft,
+            Octant::FrontBottomRight,
+            Octant::BackTopLeft,
+            Octant::BackTopRight,
+            Octant::BackBottomLeft,
+            Octant::BackBottomRight,
+        ];
+        let child_count = octants.iter().filter_map(|q| self.child(*q)).count();
+        if child_count != if self.divided { 8 } else { 0 } {
+            return violation(format!(
+                "node at depth {depth} is {} but has {child_count} children",
+                if self.divided {
+                    "divided"
+                } else {
+                    "not divided"
+                }
+            ));
+        }
+        for octant in octants {
+            let Some(child) = self.child(octant) else {
+                continue;
+            };
+            if let Some(point) = child.points.iter().find(|p| self.octant_for(p) != octant) {
+                return violation(format!(
+                    "point ({}, {}, {}) at depth {} is stored outside the octant it belongs to",
+                    point.x,
+                    point.y,
+                    point.z,
+                    depth + 1
+                ));
+            }
+            child.check_invariants_helper(depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Appends references to all points of the subtree to `out`.
+    fn collect_points<'a>(&'a self, out: &mut Vec<&'a Point3D<T>>) {
+        out.extend(self.points.iter());
+        for octant in [
+            Octant::FrontTopLeft,
+            Octant::FrontTopRight,
+            Octant::FrontBottomLeft,
+            Octant::FrontBottomRight,
+            Octant::BackTopLeft,
+            Octant::BackTopRight,
+            Octant::BackBottomLeft,
+            Octant::BackBottomRight,
+        ] {
+            if let Some(child) = self.child(octant) {
+                child.collect_points(out);
+            }
+        }
+    }
+
+    /// Returns a new tree holding only the points inside `region`.
+    ///
+    /// The new tree has the same boundary, capacity, and tolerance as this one. Subtrees whose
+    /// region lies entirely inside `region` are cloned as a whole rather than rebuilt point by
+    /// point, and subtrees entirely outside it are skipped, so most of the work is spent on the
+    /// nodes crossing the border of `region`. Nodes left with few points are merged as after a
+    /// deletion. This tree is not modified.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The region to keep. Points on its border are kept.
+    pub fn crop(&self, region: &Cube) -> Octree<T> {
+        let region = (region.min_corner(), region.max_corner());
+        let range = (self.boundary.min_corner(), self.boundary.max_corner());
+        let mut cropped = self.crop_rec(&region, range);
+        cropped.try_merge();
+        cropped
+    }
+
+    /// Returns a copy of the subtree holding only the points inside `region`.
+    ///
+    /// `region` and `range` hold the smallest and largest coordinates per axis; `range` covers
+    /// the positions that can be routed to this node, tracked from the root like in
+    /// `extreme_rec`.
+    fn crop_rec(&self, region: &([f64; 3], [f64; 3]), range: ([f64; 3], [f64; 3])) -> Octree<T> {
+        let ((region_min, region_max), (lo, hi)) = (region, range);
+        if (0..3).all(|axis| region_min[axis] <= lo[axis] && hi[axis] <= region_max[axis]) {
+            return self.clone();
+        }
+        let mut node = Octree {
+            boundary: self.boundary.clone(),
+            points: Vec::new(),
+            capacity: self.capacity,
+            tolerance: self.tolerance,
+            overflow: self.overflow,
+            merge_threshold: self.merge_threshold,
+            depth: self.depth,
+            divided: false,
+            front_top_left: None,
+            front_top_right: None,
+            front_bottom_left: None,
+            front_bottom_right: None,
+            back_top_left: None,
+            back_top_right: None,
+            back_bottom_left: None,
+            back_bottom_right: None,
+        };
+        if (0..3).any(|axis| lo[axis] > region_max[axis] || hi[axis] < region_min[axis]) {
+            return node;
+        }
+        if self.divided {
+            node.subdivide();
+            let mid = [
+                self.boundary.x + self.boundary.width / 2.0,
+                self.boundary.y + self.boundary.height / 2.0,
+                self.boundary.z + self.boundary.depth / 2.0,
+            ];
+            for octant in [
+                Octant::FrontTopLeft,
+                Octant::FrontTopRight,
+                Octant::FrontBottomLeft,
+                Octant::FrontBottomRight,
+                Octant::BackTopLeft,
+                Octant::BackTopRight,
+                Octant::BackBottomLeft,
+                Octant::BackBottomRight,
+            ] {
+                let (mut child_lo, mut child_hi) = (lo, hi);
+                for axis in 0..3 {
+                    if (octant.index() >> axis) & 1 == 1 {
+                        child_lo[axis] = mid[axis];
+                    } else {
+                        child_hi[axis] = mid[axis];
+                    }
+                }
+                if let (Some(child), Some(slot)) = (self.child(octant), node.child_mut(octant)) {
+                    *slot = child.crop_rec(region, (child_lo, child_hi));
+                }
+            }
+        }
+        node.points = self
+            .points
+            .iter()
+            .filter(|p| {
+                let coords = [p.x, p.y, p.z];
+                (0..3).all(|axis| {
+                    region_min[axis] <= coords[axis] && coords[axis] <= region_max[axis]
+                })
+            })
+            .cloned()
+            .collect();
+        node
+    }
+
+    /// Splits the tree into independent trees, one for each subtree at the given depth.
+    ///
+    /// Each node at `depth` (the root is at depth 0) becomes the root of a returned tree, with
+    /// its own boundary and the capacity and tolerance of this tree. Leaves above `depth` are
+    /// returned as they are, and empty subtrees are left out, so every stored point ends up in
+    /// exactly one returned tree. The trees are returned in Morton order. This tree is
+    /// consumed; clone it first to keep it.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the nodes that become the roots of the returned trees.
+    pub fn split_at(self, depth: usize) -> Vec<Octree<T>> {
+        let mut parts = Vec::new();
+        self.split_rec(depth, &mut parts);
+        parts
+    }
+
+    /// Partitions the boundary into cubes holding roughly equal numbers of points, e.g., to
+    /// shard the points or to spread their processing over threads.
+    ///
+    /// The boundary is cut recursively across its longest side, each time at the coordinate
+    /// that splits the points in proportion to the number of cubes on either side, so every
+    /// cube holds about `len / n` points. Points on a cut lie on the face of two cubes and may
+    /// be counted by both.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of cubes.
+    ///
+    /// # Returns
+    ///
+    /// `n` cubes (boxes, in general) that tile the boundary.
+    pub fn partition(&self, n: usize) -> Vec<Cube> {
+        let mut points = Vec::new();
+        self.collect_points(&mut points);
+        let mut coords: Vec<[f64; 3]> = points.iter().map(|p| [p.x, p.y, p.z]).collect();
+        let b = &self.boundary;
+        let mut cells = Vec::with_capacity(n);
+        equi_depth_cells(b.min_corner(), b.max_corner(), &mut coords, n, &mut cells);
+        cells
+            .into_iter()
+            .map(|(lo, hi)| Cube::from_corners(lo, hi))
+            .collect()
+    }
+
+    /// Appends the non-empty subtrees `depth` levels below this node to `out`.
+    fn split_rec(mut self, depth: usize, out: &mut Vec<Octree<T>>) {
+        if depth == 0 || !self.divided {
+            if self.has_points() {
+                out.push(self);
+            }
+            return;
+        }
+        // Points held by an inner node move down, so that they are not lost with the node.
+        for point in std::mem::take(&mut self.points) {
+            self.insert_unchecked(point);
+        }
+        let children = [
+            self.front_top_left.take(),
+            self.front_top_right.take(),
+            self.front_bottom_left.take(),
+            self.front_bottom_right.take(),
+            self.back_top_left.take(),
+            self.back_top_right.take(),
+            self.back_bottom_left.take(),
+            self.back_bottom_right.take(),
+        ];
+        for child in children.into_iter().flatten() {
+            child.split_rec(depth - 1, out);
+        }
+    }
+
+    /// Returns whether the subtree holds at least one point.
+    fn has_points(&self) -> bool {
+        !self.points.is_empty() || self.children().iter().any(|child| child.has_points())
+    }
+
+    /// Performs a k-nearest neighbor search for the target point.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The 3D point for which to find the k nearest neighbors.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the k nearest 3D points, ordered from nearest to farthest.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
+    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
+    /// performance.
+    pub fn knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        target: &Point3D<T>,
+        k: usize,
+    ) -> Vec<Point3D<T>> {
+        if k == 0 || has_nan_coord(target) {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.knn_search_helper::<M>(target, &mut results);
+        results.into_sorted_vec()
+    }
+
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, and `SpartError::InvalidCoordinates` if a coordinate of `target` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        target: &Point3D<T>,
+        k: usize,
+    ) -> Result<Vec<Point3D<T>>, SpartError> {
+        check_knn_query(k, !self.has_points())?;
+        check_query_point(target)?;
+        Ok(self.knn_search::<M>(target, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        targets: &[Point3D<T>],
+        k: usize,
+    ) -> Vec<Vec<Point3D<T>>>
+    where
+        T: Send + Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// The nearest point of each of the (up to) `k` keys with the nearest points, ordered from
+    /// nearest to farthest.
+    pub fn knn_distinct_by<M: DistanceMetric<Point3D<T>>, K: PartialEq>(
+        &self,
+        target: &Point3D<T>,
+        k: usize,
+        key: impl Fn(&Point3D<T>) -> K,
+    ) -> Vec<Point3D<T>> {
+        let mut results = DistinctKnnResults::new(k, key);
+        if k > 0 && !has_nan_coord(target) {
+            self.knn_search_helper::<M>(target, &mut results);
+        }
+        results.into_sorted_vec()
+    }
+
+    /// Helper method for recursively performing the k-nearest neighbor search.
+    fn knn_search_helper<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        target: &Point3D<T>,
+        results: &mut impl KnnSink<Point3D<T>>,
+    ) {
+        for point in &self.points {
+            let dist_sq = M::distance_sq(point, target);
+            if results.accepts(dist_sq) {
+                results.push(dist_sq, point.clone());
+            }
+        }
+        if self.divided {
+            for child in self.children() {
+                if child.min_distance_sq(target) > results.bound() {
+                    continue;
+                }
+                child.knn_search_helper::<M>(target, results);
+            }
+        }
+    }
+
+    /// Performs a range search, returning all points within the specified radius of the center point.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search range.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of 3D points within the specified range.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
+    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
+    /// performance.
+    pub fn range_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        center: &Point3D<T>,
+        radius: f64,
+    ) -> Vec<Point3D<T>> {
+        let mut found = Vec::new();
+        self.range_search_into::<M>(center, radius, &mut found);
+        found
+    }
+
+    /// Performs a range search like `range_search`, appending the points to `out` instead of
+    /// returning a new vector.
+    ///
+    /// High-frequency callers can reserve room for the expected number of results once and
+    /// reuse the same buffer for every query, clearing it in between; `out` is never cleared
+    /// here.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    /// * `out` - The buffer that receives the points.
+    pub fn range_search_into<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        center: &Point3D<T>,
+        radius: f64,
+        out: &mut Vec<Point3D<T>>,
+    ) {
+        if radius >= 0.0 {
+            self.range_search_helper::<M>(center, radius * radius, usize::MAX, out);
+        }
+    }
+
+    /// Performs a range search like `range_search`, stopping once `max_results` points are
+    /// found.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    /// * `max_results` - The maximum number of points to return.
+    ///
+    /// # Returns
+    ///
+    /// Up to `max_results` points within the range, and whether more points matched. Which
+    /// points are returned when the search is truncated depends on the layout of the tree.
+    pub fn range_search_limited<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        center: &Point3D<T>,
+        radius: f64,
+        max_results: usize,
+    ) -> LimitedResults<Point3D<T>> {
+        let mut found = Vec::new();
+        if radius >= 0.0 {
+            let limit = LimitedResults::<Point3D<T>>::limit_for(max_results);
+            self.range_search_helper::<M>(center, radius * radius, limit, &mut found);
+        }
+        LimitedResults::from_overfull(found, max_results)
+    }
+
+    /// Like `range_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `range_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidRadius` if `radius` is negative or NaN, and
+    /// `SpartError::InvalidCoordinates` if a coordinate of `center` is NaN.
+    pub fn try_range_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        center: &Point3D<T>,
+        radius: f64,
+    ) -> Result<Vec<Point3D<T>>, SpartError> {
+        check_range_query(radius)?;
+        check_query_point(center)?;
+        Ok(self.range_search::<M>(center, radius))
+    }
+
+    /// Performs `range_search` for each of several centers, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `centers` - The query points.
+    /// * `radius` - The search radius around each center.
+    ///
+    /// # Returns
+    ///
+    /// The results of `range_search` for each center, in the order of `centers`.
+    #[cfg(feature = "parallel")]
+    pub fn par_range_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        centers: &[Point3D<T>],
+        radius: f64,
+    ) -> Vec<Vec<Point3D<T>>>
+    where
+        T: Send + Sync,
+    {
+        use rayon::prelude::*;
+        centers
+            .par_iter()
+            .map(|center| self.range_search::<M>(center, radius))
+            .collect()
+    }
+
+    /// Helper method for performing the recursive range search, which stops once `found`
+    /// holds `limit` points.
+    fn range_search_helper<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        center: &Point3D<T>,
+        radius_sq: f64,
+        limit: usize,
+        found: &mut Vec<Point3D<T>>,
+    ) {
+        if found.len() >= limit || self.min_distance_sq(center) > radius_sq {
+            return;
+        }
+        for point in &self.points {
+            if M::distance_sq(point, center) <= radius_sq {
+                found.push(point.clone());
+                if found.len() >= limit {
+                    return;
+                }
+            }
+        }
+        if self.divided {
+            for child in self.children() {
+                child.range_search_helper::<M>(center, radius_sq, limit, found);
+            }
+        }
+    }
+
+    /// Estimates the number of points inside `query`, without reading the points.
+    ///
+    /// Each node intersecting the query counts for its number of points times the fraction of
+    /// its boundary inside the query, so the estimate is close when the points are spread evenly
+    /// within each node. It is cheap enough for a query planner to call before choosing between
+    /// the index and another access path.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query cube.
+    ///
+    /// # Returns
+    ///
+    /// The estimated number of points inside `query`.
+    pub fn estimate_count_in_bbox(&self, query: &Cube) -> f64 {
+        if !self.boundary.intersects(query) {
+            return 0.0;
+        }
+        let own = self.points.len() as f64 * overlap_fraction(&self.boundary, query);
+        own + self
+            .children()
+            .into_iter()
+            .map(|child| child.estimate_count_in_bbox(query))
+            .sum::<f64>()
+    }
+
+    /// Performs a k-nearest neighbor search like `knn_search`, reusing the buffers of `ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The query context, which holds the results until its next use.
+    /// * `target` - The point for which to find the k nearest neighbors.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// The k nearest points, ordered from nearest to farthest.
+    pub fn knn_search_with<'c, M: DistanceMetric<Point3D<T>>>(
+        &self,
+        ctx: &'c mut QueryContext<Point3D<T>>,
+        target: &Point3D<T>,
+        k: usize,
+    ) -> &'c [Point3D<T>] {
+        ctx.knn(k, |results| {
+            if !has_nan_coord(target) {
+                self.knn_search_helper::<M>(target, results);
+            }
+        })
+    }
+
+    /// Performs a range search like `range_search`, reusing the buffers of `ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The query context, which holds the results until its next use.
+    /// * `center` - The center of the search range.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// The points within the range.
+    pub fn range_search_with<'c, M: DistanceMetric<Point3D<T>>>(
+        &self,
+        ctx: &'c mut QueryContext<Point3D<T>>,
+        center: &Point3D<T>,
+        radius: f64,
+    ) -> &'c [Point3D<T>] {
+        ctx.collect(|found| self.range_search_into::<M>(center, radius, found))
+    }
+
+    /// Returns `true` if the tree holds a point matching `point`.
+    ///
+    /// Points match if they are equal, or within the tree's tolerance (see `with_tolerance`).
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to look for.
+    pub fn contains(&self, point: &Point3D<T>) -> bool {
+        let window = Self::tolerance_window(point, self.tolerance);
+        if !self.boundary.intersects(&window) {
+            return false;
+        }
+        self.contains_unchecked(point, self.tolerance)
+    }
+
+    /// Checks whether the subtree holds a point matching `point` within `tolerance`.
+    fn contains_unchecked(&self, point: &Point3D<T>, tolerance: f64) -> bool {
+        if self.divided {
+            return self
+                .candidate_octants(point, tolerance)
+                .into_iter()
+                .any(|octant| {
+                    self.child(octant)
+                        .is_some_and(|child| child.contains_unchecked(point, tolerance))
+                });
+        }
+        self.points.iter().any(|p| p.approx_eq(point, tolerance))
+    }
+
+    /// Replaces a point matching `old` with `new`.
+    ///
+    /// Points are matched like in `delete`, so the tree's tolerance applies to `old`.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The point to replace.
+    /// * `new` - The replacement point.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and replaced. If `new` lies outside the boundary,
+    /// the tree is left unchanged and `false` is returned.
+    pub fn update(&mut self, old: &Point3D<T>, new: Point3D<T>) -> bool {
+        if !self.boundary.contains(&new) || !self.delete(old) {
+            return false;
+        }
+        self.insert_unchecked(new);
+        true
+    }
+
+    /// Deletes a point from the octree.
+    ///
+    /// Returns `true` if the point was found and deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The 3D point to delete.
+    pub fn delete(&mut self, point: &Point3D<T>) -> bool {
+        let window = Self::tolerance_window(point, self.tolerance);
+        if !self.boundary.intersects(&window) {
+            return false;
+        }
+        self.delete_unchecked(point, self.tolerance, true)
+    }
+
+    /// Deletes several points from the octree.
+    ///
+    /// Each point removes at most one matching stored point, as with `delete`. Nodes are
+    /// merged once after all points have been removed instead of after every deletion, which
+    /// makes this much faster than calling `delete` in a loop.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to delete.
+    ///
+    /// # Returns
+    ///
+    /// The number of points that were found and deleted.
+    pub fn delete_bulk(&mut self, points: &[Point3D<T>]) -> usize {
+        let mut deleted = 0;
+        for point in points {
+            let window = Self::tolerance_window(point, self.tolerance);
+            if self.boundary.intersects(&window)
+                && self.delete_unchecked(point, self.tolerance, false)
+            {
+                deleted += 1;
+            }
+        }
+        self.try_merge();
+        deleted
+    }
+
+    /// Keeps only the points for which `keep` returns `true`, deleting the others.
+    ///
+    /// Nodes are merged once after all points have been visited, as in `delete_bulk`.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - Called once for each stored point.
+    ///
+    /// # Returns
+    ///
+    /// The number of points that were deleted.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Point3D<T>) -> bool) -> usize {
+        let deleted = self.retain_unmerged(&mut keep);
+        self.try_merge();
+        deleted
+    }
+
+    /// Keeps only the points of the subtree for which `keep` returns `true`, without merging
+    /// nodes.
+    fn retain_unmerged(&mut self, keep: &mut impl FnMut(&Point3D<T>) -> bool) -> usize {
+        let before = self.points.len();
+        self.points.retain(|p| keep(p));
+        let mut deleted = before - self.points.len();
+        for child in self.children_mut() {
+            deleted += child.retain_unmerged(keep);
+        }
+        deleted
+    }
+
+    /// Deletes a point matching `point` within `tolerance` from the subtree, merging the
+    /// nodes along the way if `merge` is set.
+    fn delete_unchecked(&mut self, point: &Point3D<T>, tolerance: f64, merge: bool) -> bool {
+        if self.divided {
+            let mut deleted = false;
+            for octant in self.candidate_octants(point, tolerance) {
+                if self
+                    .child_mut(octant)
+                    .is_some_and(|child| child.delete_unchecked(point, tolerance, merge))
+                {
+                    deleted = true;
+                    break;
+                }
+            }
+            if merge {
+                self.try_merge();
+            }
+            return deleted;
+        }
+        if let Some(pos) = self
+            .points
+            .iter()
+            .position(|p| p.approx_eq(point, tolerance))
+        {
+            self.points.remove(pos);
+            hot_trace!("Deleting point from Octree", point = point);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the sequence of octants leading from the root to the leaf that contains
+    /// (or would contain) the given point.
+    ///
+    /// The path follows the same routing as `insert`, so it identifies the cell a point landed
+    /// in. Concatenating the octants' `index()` digits yields the cell's Morton prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to locate.
+    ///
+    /// # Returns
+    ///
+    /// The path of octants (empty if the root is a leaf), or `None` if the point lies
+    /// outside the tree's boundary.
+    pub fn node_path(&self, point: &Point3D<T>) -> Option<Vec<Octant>> {
+        self.locate(point).map(|(path, _)| path)
+    }
+
+    /// Returns the boundary of the leaf node that contains (or would contain) the given point.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to locate.
+    ///
+    /// # Returns
+    ///
+    /// The leaf's boundary, or `None` if the point lies outside the tree's boundary.
+    pub fn leaf_boundary(&self, point: &Point3D<T>) -> Option<Cube> {
+        self.locate(point).map(|(_, leaf)| leaf.boundary.clone())
+    }
+
+    /// Computes the covariance of the neighborhood of a point, e.g., to estimate the surface
+    /// normal or curvature of a point cloud there (see the `covariance` module).
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The query point.
+    /// * `neighborhood` - The `k` nearest points or the points within a radius of `point`.
+    ///
+    /// # Returns
+    ///
+    /// The covariance of the neighbors, or `None` if there are none within the radius.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `try_knn_search` or `try_range_search`: `SpartError::InvalidK`
+    /// for `k == 0`, `SpartError::EmptyTree` for a kNN neighborhood in an empty tree,
+    /// `SpartError::InvalidRadius` for a negative radius, and `SpartError::InvalidCoordinates`
+    /// if `point` has a NaN coordinate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::covariance::Neighborhood;
+    /// use spart::geometry::{Cube, Point3D};
+    /// use spart::octree::Octree;
+    ///
+    /// let boundary = Cube { x: 0.0, y: 0.0, z: 0.0, width: 10.0, height: 10.0, depth: 10.0 };
+    /// let mut tree = Octree::new(&boundary, 4).unwrap();
+    /// // Points on the plane x = 5.
+    /// for i in 0..25 {
+    ///     tree.insert(Point3D::new(5.0, (i % 5) as f64, (i / 5) as f64, None::<()>));
+    /// }
+    /// let query = Point3D::new(5.0, 2.0, 2.0, None);
+    /// let covariance = tree
+    ///     .neighborhood_covariance(&query, Neighborhood::Radius(1.5))
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!(covariance.count, 9);
+    /// assert!((covariance.normal()[0].abs() - 1.0).abs() < 1e-9);
+    /// ```
+    pub fn neighborhood_covariance(
+        &self,
+        point: &Point3D<T>,
+        neighborhood: Neighborhood,
+    ) -> Result<Option<Covariance>, SpartError> {
+        let neighbors = match neighborhood {
+            Neighborhood::Knn(k) => self.try_knn_search::<EuclideanDistance>(point, k)?,
+            Neighborhood::Radius(radius) => {
+                self.try_range_search::<EuclideanDistance>(point, radius)?
+            }
+        };
+        Ok(Covariance::from_points(&neighbors))
+    }
+
+    /// Computes the covariance of the neighborhood of each of several points, as
+    /// `neighborhood_covariance` does for one, e.g., to estimate the normals of a whole point
+    /// cloud in one call.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The query points.
+    /// * `neighborhood` - The neighborhood of each query point.
+    ///
+    /// # Returns
+    ///
+    /// The covariance of the neighbors of each point, in the order of `points`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error `neighborhood_covariance` returns.
+    pub fn neighborhood_covariances(
+        &self,
+        points: &[Point3D<T>],
+        neighborhood: Neighborhood,
+    ) -> Result<Vec<Option<Covariance>>, SpartError> {
+        points
+            .iter()
+            .map(|point| self.neighborhood_covariance(point, neighborhood))
+            .collect()
+    }
+
+    /// Downsamples the points to one per occupied voxel, the usual first step of point-cloud
+    /// pipelines.
+    ///
+    /// The voxels are cubes of side `cell_size` on a grid starting at the lower corner of the
+    /// tree's boundary. Nodes that fit in a single voxel are added to it as a whole, without
+    /// locating each of their points.
+    ///
+    /// # Arguments
+    ///
+    /// * `cell_size` - The side length of a voxel.
+    /// * `representative` - Which point to keep for each voxel.
+    ///
+    /// # Returns
+    ///
+    /// One point per occupied voxel, ordered by voxel.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if `cell_size` is not positive and finite.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Cube, Point3D};
+    /// use spart::octree::{Octree, VoxelRepresentative};
+    ///
+    /// let boundary = Cube { x: 0.0, y: 0.0, z: 0.0, width: 10.0, height: 10.0, depth: 10.0 };
+    /// let mut tree = Octree::new(&boundary, 2).unwrap();
+    /// tree.insert(Point3D::new(1.0, 1.0, 1.0, Some(1)));
+    /// tree.insert(Point3D::new(3.0, 3.0, 3.0, Some(2)));
+    /// tree.insert(Point3D::new(8.0, 8.0, 8.0, Some(3)));
+    ///
+    /// let points = tree.downsample(5.0, VoxelRepresentative::Centroid).unwrap();
+    /// assert_eq!(points.len(), 2);
+    /// assert_eq!((points[0].x, points[0].y, points[0].z), (2.0, 2.0, 2.0));
+    /// assert_eq!(points[1].data, Some(3));
+    /// ```
+    pub fn downsample(
+        &self,
+        cell_size: f64,
+        representative: VoxelRepresentative,
+    ) -> Result<Vec<Point3D<T>>, SpartError> {
+        if !(cell_size > 0.0 && cell_size.is_finite()) {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("cell size {cell_size} is not a positive finite voxel size"),
+            });
+        }
+        let grid = VoxelGrid {
+            origin: [self.boundary.x, self.boundary.y, self.boundary.z],
+            cell_size,
+        };
+        let mut voxels = BTreeMap::new();
+        self.downsample_rec(&grid, &mut voxels);
+        Ok(voxels
+            .into_values()
+            .map(|voxel| voxel.representative(representative))
+            .collect())
+    }
+
+    /// Adds the points of the subtree to the voxels they fall in.
+    fn downsample_rec<'a>(
+        &'a self,
+        grid: &VoxelGrid,
+        voxels: &mut BTreeMap<[i64; 3], Voxel<'a, T>>,
+    ) {
+        let b = &self.boundary;
+        let low = grid.key(b.x, b.y, b.z);
+        if low == grid.key(b.x + b.width, b.y + b.height, b.z + b.depth) {
+            let mut points = Vec::new();
+            self.collect_points(&mut points);
+            for point in points {
+                Voxel::add(voxels, low, point);
+            }
+            return;
+        }
+        for point in &self.points {
+            Voxel::add(voxels, grid.key(point.x, point.y, point.z), point);
+        }
+        for child in self.children() {
+            child.downsample_rec(grid, voxels);
+        }
+    }
+
+    /// Finds the occupied cell at a given depth that is nearest to a point, e.g., for sparse
+    /// voxel lookups or to fall back to nearby samples when the point's own cell is empty.
+    ///
+    /// The cells are the nodes at `depth` (the root is at depth 0), and the leaves above it
+    /// where the tree is shallower. A cell is occupied if any stored point lies in it, and its
+    /// distance to the point is the distance to its boundary, so a cell containing the point
+    /// is at distance 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The query point.
+    /// * `depth` - The depth of the cells to search.
+    ///
+    /// # Returns
+    ///
+    /// The boundary of the nearest occupied cell and the points in it, or `None` if the tree
+    /// is empty or the point has a NaN coordinate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Cube, Point3D};
+    /// use spart::octree::Octree;
+    ///
+    /// let boundary = Cube { x: 0.0, y: 0.0, z: 0.0, width: 100.0, height: 100.0, depth: 100.0 };
+    /// let mut tree = Octree::new(&boundary, 1).unwrap();
+    /// tree.insert(Point3D::new(10.0, 10.0, 10.0, Some(1)));
+    /// tree.insert(Point3D::new(90.0, 60.0, 60.0, Some(2)));
+    ///
+    /// // The voxel around (80, 90, 90) is empty; the nearest occupied one holds point 2.
+    /// let query = Point3D::new(80.0, 90.0, 90.0, None);
+    /// let (cell, points) = tree.nearest_occupied_cell(&query, 1).unwrap();
+    /// assert_eq!(cell.x, 50.0);
+    /// assert_eq!(points[0].data, Some(2));
+    /// ```
+    pub fn nearest_occupied_cell(
+        &self,
+        point: &Point3D<T>,
+        depth: usize,
+    ) -> Option<(Cube, Vec<Point3D<T>>)> {
+        if has_nan_coord(point) {
+            return None;
+        }
+        let mut nearest = None;
+        self.nearest_cell_rec(point, depth, Vec::new(), &mut nearest);
+        nearest
+            .map(|(_, node, points)| (node.boundary.clone(), points.into_iter().cloned().collect()))
+    }
+
+    /// Visits the cells at `depth` below this node nearest first, skipping those farther away
+    /// than the nearest occupied cell found so far. `inherited` holds the points of the
+    /// ancestors that lie in this node, so that they count toward the cell they fall in.
+    fn nearest_cell_rec<'a>(
+        &'a self,
+        point: &Point3D<T>,
+        depth: usize,
+        mut inherited: Vec<&'a Point3D<T>>,
+        nearest: &mut Option<NearestCell<'a, T>>,
+    ) {
+        let distance = self.boundary.min_distance(point);
+        if nearest
+            .as_ref()
+            .is_some_and(|(best, _, _)| distance >= *best)
+        {
+            return;
+        }
+        if depth == 0 || !self.divided {
+            self.collect_points(&mut inherited);
+            if !inherited.is_empty() {
+                *nearest = Some((distance, self, inherited));
+            }
+            return;
+        }
+        inherited.extend(self.points.iter());
+        let mut children: Vec<_> = [
+            Octant::FrontTopLeft,
+            Octant::FrontTopRight,
+            Octant::FrontBottomLeft,
+            Octant::FrontBottomRight,
+            Octant::BackTopLeft,
+            Octant::BackTopRight,
+            Octant::BackBottomLeft,
+            Octant::BackBottomRight,
+        ]
+        .into_iter()
+        .filter_map(|octant| self.child(octant).map(|child| (octant, child)))
+        .collect();
+        children.sort_by(|(_, a), (_, b)| {
+            a.boundary
+                .min_distance(point)
+                .total_cmp(&b.boundary.min_distance(point))
+        });
+        for (octant, child) in children {
+            let points = inherited
+                .iter()
+                .filter(|p| self.octant_for(p) == octant)
+                .copied()
+                .collect();
+            child.nearest_cell_rec(point, depth - 1, points, nearest);
+        }
+    }
+
+    /// Descends to the leaf a point is routed to, recording the octants along the way.
+    fn locate(&self, point: &Point3D<T>) -> Option<(Vec<Octant>, &Octree<T>)> {
+        if !self.boundary.contains(point) {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = self;
+        while node.divided {
+            let octant = node.octant_for(point);
+            match node.child(octant) {
+                Some(child) => {
+                    path.push(octant);
+                    node = child;
+                }
+                None => break,
+            }
+        }
+        Some((path, node))
+    }
+
+    /// Returns the stored point at exactly the coordinates of `point`, ignoring its data and
+    /// the tolerance.
+    pub(crate) fn point_at(&self, point: &Point3D<T>) -> Option<&Point3D<T>> {
+        let (_, node) = self.locate(point)?;
+        node.points
+            .iter()
+            .find(|p| p.x == point.x && p.y == point.y && p.z == point.z)
+    }
+
+    /// Returns the stored point at exactly the coordinates of `point` for modification.
+    ///
+    /// Callers must not change the coordinates, since the point would no longer be where the
+    /// tree routes it.
+    pub(crate) fn point_at_mut(&mut self, point: &Point3D<T>) -> Option<&mut Point3D<T>> {
+        if self.divided {
+            let octant = self.octant_for(point);
+            if self.child(octant).is_some() {
+                return self
+                    .child_mut(octant)
+                    .and_then(|child| child.point_at_mut(point));
+            }
+        }
+        self.points
+            .iter_mut()
+            .find(|p| p.x == point.x && p.y == point.y && p.z == point.z)
+    }
+
+    /// Attempts to merge child nodes back into the parent node if possible.
+    ///
+    /// If all children are not divided and their total number of points is within the merge
+    /// threshold (by default, the capacity), the children are merged into the parent node.
     fn try_merge(&mut self) {
         if !self.divided {
             return;
@@ -737,47 +2359,284 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Octree<T> {
         for child in self.children_mut() {
             child.try_merge();
         }
-        let children = self.children();
-        if children.iter().all(|child| !child.divided) {
-            let total_points: usize = children.iter().map(|child| child.points.len()).sum();
-            if total_points <= self.capacity {
-                let mut merged_points = Vec::with_capacity(total_points);
-                if let Some(child) = self.front_top_left.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.front_top_right.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.front_bottom_left.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.front_bottom_right.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.back_top_left.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.back_top_right.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.back_bottom_left.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.back_bottom_right.take() {
-                    merged_points.extend(child.points);
-                }
-                info!(
-                    "Merging children into parent node at boundary {:?} with {} points",
-                    self.boundary,
-                    merged_points.len()
-                );
-                self.points = merged_points;
-                self.divided = false;
+        let children = self.children();
+        if children.iter().all(|child| !child.divided) {
+            let total_points: usize = children.iter().map(|child| child.points.len()).sum();
+            let limit = (self.capacity as f64 * self.merge_threshold).floor() as usize;
+            if total_points <= limit {
+                let mut merged_points = Vec::with_capacity(total_points);
+                if let Some(child) = self.front_top_left.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.front_top_right.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.front_bottom_left.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.front_bottom_right.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.back_top_left.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.back_top_right.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.back_bottom_left.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.back_bottom_right.take() {
+                    merged_points.extend(child.points);
+                }
+                hot_trace!(
+                    "Merging children into parent node",
+                    boundary = self.boundary,
+                    points = merged_points.len()
+                );
+                self.points = merged_points;
+                self.divided = false;
+            }
+        }
+    }
+}
+
+/// Computes the Morton code of `point` relative to `boundary`, 21 levels deep.
+///
+/// Each level contributes one octant digit, chosen with the same midpoint routing as
+/// `Octree::insert`, so sorting points by their code lists them leaf by leaf in the order
+/// the tree stores them.
+fn morton_code<T>(boundary: &Cube, point: &Point3D<T>) -> u64 {
+    let mut x = boundary.x;
+    let mut y = boundary.y;
+    let mut z = boundary.z;
+    let mut w = boundary.width;
+    let mut h = boundary.height;
+    let mut d = boundary.depth;
+    let mut code = 0u64;
+    for _ in 0..21 {
+        w /= 2.0;
+        h /= 2.0;
+        d /= 2.0;
+        let right = point.x > x + w;
+        let bottom = point.y > y + h;
+        let back = point.z > z + d;
+        if right {
+            x += w;
+        }
+        if bottom {
+            y += h;
+        }
+        if back {
+            z += d;
+        }
+        code = (code << 3) | (right as u64) | ((bottom as u64) << 1) | ((back as u64) << 2);
+    }
+    code
+}
+
+/// A grid of cubic voxels used by `Octree::downsample`.
+struct VoxelGrid {
+    origin: [f64; 3],
+    cell_size: f64,
+}
+
+impl VoxelGrid {
+    /// Returns the grid coordinates of the voxel containing a position.
+    fn key(&self, x: f64, y: f64, z: f64) -> [i64; 3] {
+        let cell = |value: f64, origin: f64| ((value - origin) / self.cell_size).floor() as i64;
+        [
+            cell(x, self.origin[0]),
+            cell(y, self.origin[1]),
+            cell(z, self.origin[2]),
+        ]
+    }
+}
+
+/// The points of one voxel seen so far by `Octree::downsample`.
+struct Voxel<'a, T> {
+    first: &'a Point3D<T>,
+    sum: [f64; 3],
+    count: usize,
+}
+
+impl<'a, T: Clone> Voxel<'a, T> {
+    /// Adds a point to the voxel with the given key, creating the voxel if needed.
+    fn add(voxels: &mut BTreeMap<[i64; 3], Voxel<'a, T>>, key: [i64; 3], point: &'a Point3D<T>) {
+        let voxel = voxels.entry(key).or_insert(Voxel {
+            first: point,
+            sum: [0.0; 3],
+            count: 0,
+        });
+        voxel.sum[0] += point.x;
+        voxel.sum[1] += point.y;
+        voxel.sum[2] += point.z;
+        voxel.count += 1;
+    }
+
+    /// Returns the point kept for the voxel.
+    fn representative(self, representative: VoxelRepresentative) -> Point3D<T> {
+        match representative {
+            VoxelRepresentative::First => self.first.clone(),
+            VoxelRepresentative::Centroid => {
+                let n = self.count as f64;
+                Point3D::new(
+                    self.sum[0] / n,
+                    self.sum[1] / n,
+                    self.sum[2] / n,
+                    self.first.data.clone(),
+                )
+            }
+        }
+    }
+}
+
+impl<T: Clone + PartialEq> Octree<T> {
+    /// Returns an iterator over the stored points, without running a query.
+    ///
+    /// The points are visited node by node, depth first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Cube, Point3D};
+    /// use spart::octree::Octree;
+    ///
+    /// let boundary = Cube { x: 0.0, y: 0.0, z: 0.0, width: 10.0, height: 10.0, depth: 10.0 };
+    /// let mut tree = Octree::new(&boundary, 1).unwrap();
+    /// for i in 0..5 {
+    ///     tree.insert(Point3D::new(i as f64, 9.0 - i as f64, i as f64, Some(i)));
+    /// }
+    /// let mut ids: Vec<_> = tree.iter().filter_map(|p| p.data).collect();
+    /// ids.sort();
+    /// assert_eq!(ids, [0, 1, 2, 3, 4]);
+    /// assert_eq!(tree.into_iter().count(), 5);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            points: [].iter(),
+            stack: Vec::new(),
+        };
+        iter.enter(self);
+        iter
+    }
+}
+
+impl<'a, T: Clone + PartialEq> IntoIterator for &'a Octree<T> {
+    type Item = &'a Point3D<T>;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Clone + PartialEq> IntoIterator for Octree<T> {
+    type Item = Point3D<T>;
+    type IntoIter = IntoIter<T>;
+
+    /// Consumes the tree, yielding its points in the same order as `iter`.
+    fn into_iter(self) -> Self::IntoIter {
+        let mut iter = IntoIter {
+            points: Vec::new().into_iter(),
+            stack: Vec::new(),
+        };
+        iter.enter(self);
+        iter
+    }
+}
+
+/// An iterator over the points of a [`Octree`], created by [`Octree::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a, T: Clone + PartialEq> {
+    points: std::slice::Iter<'a, Point3D<T>>,
+    stack: Vec<&'a Octree<T>>,
+}
+
+impl<'a, T: Clone + PartialEq> Iter<'a, T> {
+    /// Yields the points of `node` next and queues its children.
+    fn enter(&mut self, node: &'a Octree<T>) {
+        self.points = node.points.iter();
+        self.stack.extend(
+            [
+                node.back_bottom_right.as_deref(),
+                node.back_bottom_left.as_deref(),
+                node.back_top_right.as_deref(),
+                node.back_top_left.as_deref(),
+                node.front_bottom_right.as_deref(),
+                node.front_bottom_left.as_deref(),
+                node.front_top_right.as_deref(),
+                node.front_top_left.as_deref(),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+    }
+}
+
+impl<'a, T: Clone + PartialEq> Iterator for Iter<'a, T> {
+    type Item = &'a Point3D<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(point) = self.points.next() {
+                return Some(point);
+            }
+            let node = self.stack.pop()?;
+            self.enter(node);
+        }
+    }
+}
+
+/// An iterator that moves the points out of a [`Octree`], created by its `into_iter`.
+#[derive(Debug)]
+pub struct IntoIter<T: Clone + PartialEq> {
+    points: std::vec::IntoIter<Point3D<T>>,
+    stack: Vec<Octree<T>>,
+}
+
+impl<T: Clone + PartialEq> IntoIter<T> {
+    /// Yields the points of `node` next and queues its children.
+    fn enter(&mut self, mut node: Octree<T>) {
+        self.points = std::mem::take(&mut node.points).into_iter();
+        self.stack.extend(
+            [
+                node.back_bottom_right.take(),
+                node.back_bottom_left.take(),
+                node.back_top_right.take(),
+                node.back_top_left.take(),
+                node.front_bottom_right.take(),
+                node.front_bottom_left.take(),
+                node.front_top_right.take(),
+                node.front_top_left.take(),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|child| *child),
+        );
+    }
+}
+
+impl<T: Clone + PartialEq> Iterator for IntoIter<T> {
+    type Item = Point3D<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(point) = self.points.next() {
+                return Some(point);
             }
+            let node = self.stack.pop()?;
+            self.enter(node);
         }
     }
 }
 
+impl<T: Clone + PartialEq + std::fmt::Debug> std::fmt::Display for Octree<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.summary().fmt(f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -998,4 +2857,561 @@ mod tests {
         let results = tree.range_search::<EuclideanDistance>(&target, -1.0);
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_node_path_and_leaf_boundary() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 100.0,
+            height: 100.0,
+            depth: 100.0,
+        };
+        let mut tree: Octree<&str> = Octree::new(&boundary, 1).unwrap();
+        assert!(
+            tree.node_path(&Point3D::new(-1.0, 0.0, 0.0, None))
+                .is_none()
+        );
+
+        let a = Point3D::new(10.0, 10.0, 10.0, Some("A"));
+        let b = Point3D::new(90.0, 10.0, 90.0, Some("B"));
+        tree.insert(a.clone());
+        assert_eq!(tree.node_path(&a), Some(vec![]));
+        tree.insert(b.clone());
+
+        assert_eq!(tree.node_path(&a), Some(vec![Octant::FrontTopLeft]));
+        let path = tree.node_path(&b).unwrap();
+        assert_eq!(path, vec![Octant::BackTopRight]);
+        assert_eq!(path[0].index(), 5);
+
+        let leaf = tree.leaf_boundary(&b).unwrap();
+        assert!(leaf.contains(&b));
+        assert_eq!((leaf.x, leaf.y, leaf.z), (50.0, 0.0, 50.0));
+    }
+
+    #[test]
+    fn test_insert_bulk_keeps_points_on_split_planes() {
+        // 0.3 + 0.35 + 0.35 rounds below 1.0, so the far children end slightly short of the
+        // root's far faces.
+        let boundary = Cube {
+            x: 0.3,
+            y: 0.3,
+            z: 0.3,
+            width: 0.7,
+            height: 0.7,
+            depth: 0.7,
+        };
+        let coords = [0.3, 0.65, 1.0];
+        let mut points = Vec::new();
+        for &x in &coords {
+            for &y in &coords {
+                for &z in &coords {
+                    points.push(Point3D::new(x, y, z, Some((x, y, z))));
+                }
+            }
+        }
+
+        let mut bulk: Octree<(f64, f64, f64)> = Octree::new(&boundary, 1).unwrap();
+        bulk.insert_bulk(&points);
+        let mut single: Octree<(f64, f64, f64)> = Octree::new(&boundary, 1).unwrap();
+        for p in &points {
+            assert!(single.insert(p.clone()));
+        }
+
+        let center = Point3D::new(0.65, 0.65, 0.65, None);
+        for tree in [&bulk, &single] {
+            let found = tree.range_search::<EuclideanDistance>(&center, 1.0);
+            assert_eq!(found.len(), points.len());
+        }
+        for p in &points {
+            assert_eq!(bulk.node_path(p), single.node_path(p));
+            assert!(bulk.delete(p));
+            assert!(single.delete(p));
+        }
+        assert!(
+            bulk.range_search::<EuclideanDistance>(&center, 1.0)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_points_stop_subdividing_at_max_depth() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 10.0,
+            height: 10.0,
+            depth: 10.0,
+        };
+        let points = vec![Point3D::new(3.0, 7.0, 5.0, None::<()>); 3];
+        let mut single = Octree::new(&boundary, 2).unwrap();
+        for p in &points {
+            assert!(single.insert(p.clone()));
+        }
+        let mut bulk = Octree::new(&boundary, 2).unwrap();
+        bulk.insert_bulk(&points);
+        let mut spill = Octree::new(&boundary, 2)
+            .unwrap()
+            .with_overflow(OverflowStrategy::Spill { extra: 1 });
+        for p in points.iter().chain(&points) {
+            assert!(spill.insert(p.clone()));
+        }
+
+        let center = Point3D::new(5.0, 5.0, 5.0, None);
+        for (tree, count) in [(&single, 3), (&bulk, 3), (&spill, 6)] {
+            assert_eq!(tree.depth(), MAX_DEPTH + 1);
+            assert_eq!(
+                tree.range_search::<EuclideanDistance>(&center, 9.0).len(),
+                count
+            );
+            tree.check_invariants().unwrap();
+        }
+        assert!(single.delete(&points[0]));
+        assert_eq!(
+            single.range_search::<EuclideanDistance>(&center, 9.0).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_tolerance_contains_delete_update() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 10.0,
+            height: 10.0,
+            depth: 10.0,
+        };
+        let mut tree: Octree<i32> = Octree::new(&boundary, 2).unwrap().with_tolerance(1e-6);
+        for i in 0..=20 {
+            let v = i as f64 / 2.0;
+            assert!(tree.insert(Point3D::new(v, 5.0, v, Some(i))));
+        }
+        assert!(tree.contains(&Point3D::new(5.0 + 1e-9, 5.0 - 1e-9, 5.0, Some(10))));
+        assert!(!tree.contains(&Point3D::new(5.0, 5.0, 5.0, Some(11))));
+
+        assert!(tree.delete(&Point3D::new(2.5 - 1e-9, 5.0 + 1e-9, 2.5, Some(5))));
+        assert!(!tree.contains(&Point3D::new(2.5, 5.0, 2.5, Some(5))));
+        assert!(tree.update(
+            &Point3D::new(7.5, 5.0, 7.5 - 1e-9, Some(15)),
+            Point3D::new(1.0, 1.0, 1.0, Some(15))
+        ));
+        assert!(tree.contains(&Point3D::new(1.0, 1.0, 1.0, Some(15))));
+    }
+
+    #[test]
+    fn test_export_import_sorted_round_trip() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 10.0,
+            height: 10.0,
+            depth: 10.0,
+        };
+        let mut tree: Octree<usize> = Octree::new(&boundary, 3).unwrap();
+        for i in 0..300 {
+            let x = (i * 37 % 101) as f64 / 10.1;
+            let y = (i * 53 % 97) as f64 / 9.7;
+            let z = (i * 71 % 89) as f64 / 8.9;
+            tree.insert(Point3D::new(x, y, z, Some(i)));
+        }
+
+        let exported: Vec<Point3D<usize>> = tree.export_sorted().cloned().collect();
+        assert_eq!(exported.len(), 300);
+        let keys: Vec<u64> = exported
+            .iter()
+            .map(|p| tree.morton_key(p).unwrap())
+            .collect();
+        assert!(keys.windows(2).all(|w| w[0] <= w[1]));
+
+        let imported = Octree::import_sorted(&boundary, 3, exported.clone()).unwrap();
+        let reexported: Vec<u64> = imported
+            .export_sorted()
+            .map(|p| imported.morton_key(p).unwrap())
+            .collect();
+        assert_eq!(reexported, keys);
+        for p in &exported {
+            assert!(imported.contains(p));
+        }
+        assert!(Octree::import_sorted(&boundary, 0, exported).is_err());
+    }
+
+    #[test]
+    fn test_min_max_by_axis_match_full_scan() {
+        let boundary = Cube {
+            x: 0.3,
+            y: 0.3,
+            z: 0.3,
+            width: 0.7,
+            height: 0.7,
+            depth: 0.7,
+        };
+        let mut tree: Octree<u32> = Octree::new(&boundary, 2).unwrap();
+        assert!(tree.max_by_axis(2).unwrap().is_none());
+        let mut points = Vec::new();
+        let mut seed = 777u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            0.3 + (seed >> 8) as f64 % 700.0 / 1000.0
+        };
+        for i in 0..300 {
+            points.push(Point3D::new(next(), next(), next(), Some(i)));
+        }
+        // Points on the far faces are routed to children that end short of them by rounding.
+        points.push(Point3D::new(1.0, 0.5, 0.5, Some(300)));
+        points.push(Point3D::new(0.5, 1.0, 0.5, Some(301)));
+        points.push(Point3D::new(0.5, 0.5, 1.0, Some(302)));
+        tree.insert_bulk(&points);
+
+        for axis in 0..3 {
+            let coord = |p: &Point3D<u32>| [p.x, p.y, p.z][axis];
+            let min = points.iter().map(coord).fold(f64::INFINITY, f64::min);
+            let max = points.iter().map(coord).fold(f64::NEG_INFINITY, f64::max);
+            assert_eq!(coord(tree.min_by_axis(axis).unwrap().unwrap()), min);
+            assert_eq!(coord(tree.max_by_axis(axis).unwrap().unwrap()), max);
+        }
+        assert!(matches!(
+            tree.min_by_axis(3),
+            Err(SpartError::InvalidDimension {
+                requested: 3,
+                available: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_nn_distance_stats_match_brute_force() {
+        use crate::random::SplitMix64;
+
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 100.0,
+            height: 100.0,
+            depth: 100.0,
+        };
+        let mut rng = SplitMix64::new(5);
+        let mut points: Vec<Point3D<usize>> = (0..200)
+            .map(|i| {
+                Point3D::new(
+                    rng.next_f64() * 100.0,
+                    rng.next_f64() * 100.0,
+                    rng.next_f64() * 100.0,
+                    Some(i),
+                )
+            })
+            .collect();
+        // A duplicate has a nearest-neighbor distance of zero.
+        points.push(Point3D {
+            data: Some(200),
+            ..points[0].clone()
+        });
+        let mut tree = Octree::new(&boundary, 4).unwrap();
+        tree.insert_bulk(&points);
+
+        let mut expected: Vec<f64> = points
+            .iter()
+            .map(|p| {
+                points
+                    .iter()
+                    .filter(|q| q.data != p.data)
+                    .map(|q| EuclideanDistance::distance_sq(p, q).sqrt())
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect();
+        expected.sort_by(f64::total_cmp);
+
+        // Sampling at least as many points as stored covers every point.
+        let stats = tree.nn_distance_stats(1000, &mut rng).unwrap();
+        assert_eq!(stats.count, points.len());
+        assert_eq!(stats.min, 0.0);
+        assert_eq!(stats.distances(), &expected[..]);
+        assert_eq!(tree.nn_distance_stats(10, &mut rng).unwrap().count, 10);
+        assert!(tree.nn_distance_stats(0, &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_merge_threshold_delays_merging() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 8.0,
+            height: 8.0,
+            depth: 8.0,
+        };
+        let tree = Octree::<u32>::new(&boundary, 4).unwrap();
+        assert_eq!(tree.merge_threshold(), 1.0);
+        assert_eq!(
+            tree.clone().with_merge_threshold(-1.0).merge_threshold(),
+            0.0
+        );
+        assert_eq!(
+            tree.clone()
+                .with_merge_threshold(f64::NAN)
+                .merge_threshold(),
+            1.0
+        );
+
+        let points: Vec<_> = (0..9)
+            .map(|i| Point3D::new(i as f64 * 0.8 + 0.5, 1.0, 1.0, Some(i)))
+            .collect();
+        let mut tree = tree.with_merge_threshold(0.0);
+        tree.insert_bulk(&points);
+        // The setting reaches the nodes created by splits.
+        tree.check_invariants().unwrap();
+        for point in &points[1..] {
+            assert!(tree.delete(point));
+            assert!(tree.depth() > 1);
+        }
+        assert!(tree.delete(&points[0]));
+        assert_eq!(tree.depth(), 1);
+    }
+
+    #[test]
+    fn test_crop_and_split_at_keep_the_right_points() {
+        use crate::random::SplitMix64;
+
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 32.0,
+            height: 32.0,
+            depth: 32.0,
+        };
+        let mut rng = SplitMix64::new(9);
+        let points: Vec<Point3D<usize>> = (0..500)
+            .map(|i| {
+                Point3D::new(
+                    rng.next_f64() * 32.0,
+                    rng.next_f64() * 32.0,
+                    rng.next_f64() * 32.0,
+                    Some(i),
+                )
+            })
+            .collect();
+        let mut tree = Octree::new(&boundary, 4).unwrap();
+        tree.insert_bulk(&points);
+        let ids = |found: Vec<&Point3D<usize>>| {
+            let mut ids: Vec<usize> = found.into_iter().filter_map(|p| p.data).collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        let region = Cube {
+            x: 0.0,
+            y: 5.0,
+            z: 0.0,
+            width: 20.0,
+            height: 27.0,
+            depth: 16.0,
+        };
+        let cropped = tree.crop(&region);
+        assert_eq!(
+            ids(cropped.export_sorted().collect()),
+            ids(points.iter().filter(|p| region.contains(p)).collect())
+        );
+
+        let parts = tree.split_at(1);
+        assert_eq!(parts.len(), 8);
+        let mut all = Vec::new();
+        for part in &parts {
+            let found: Vec<_> = part.export_sorted().collect();
+            assert!(found.iter().all(|p| part.boundary.contains(p)));
+            all.extend(found);
+        }
+        assert_eq!(ids(all), (0..500).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_partition_balances_counts_and_tiles_boundary() {
+        use crate::datasets::{Distribution, points_3d};
+        use crate::random::SplitMix64;
+
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 40.0,
+            height: 20.0,
+            depth: 10.0,
+        };
+        let skewed = Distribution::PowerLaw {
+            clusters: 8,
+            exponent: 1.0,
+        };
+        let mut tree = Octree::new(&boundary, 8).unwrap();
+        for point in points_3d(&mut SplitMix64::new(9), &boundary, 700, &skewed) {
+            assert!(tree.insert(point));
+        }
+        let mut all = Vec::new();
+        tree.collect_points(&mut all);
+        let parts = tree.partition(7);
+        assert_eq!(parts.len(), 7);
+        let volume: f64 = parts.iter().map(|c| c.width * c.height * c.depth).sum();
+        assert!((volume - 8000.0).abs() < 1e-6);
+        let mut total = 0;
+        for part in &parts {
+            let inside = [
+                (part.x, part.width, 40.0),
+                (part.y, part.height, 20.0),
+                (part.z, part.depth, 10.0),
+            ];
+            assert!(
+                inside
+                    .iter()
+                    .all(|&(lo, len, max)| lo >= 0.0 && lo + len <= max)
+            );
+            let count = all.iter().filter(|p| part.contains(p)).count();
+            assert!((98..=102).contains(&count), "{part:?}: {count}");
+            total += count;
+        }
+        assert!((700..=710).contains(&total));
+
+        assert!(tree.partition(0).is_empty());
+        let empty: Octree<()> = Octree::new(&boundary, 8).unwrap();
+        let halves = empty.partition(2);
+        assert_eq!((halves[0].width, halves[1].x), (20.0, 20.0));
+    }
+    #[test]
+    fn test_nearest_occupied_cell_matches_split_cells() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 100.0,
+            height: 100.0,
+            depth: 100.0,
+        };
+        let mut tree = Octree::new(&boundary, 2).unwrap();
+        // The points cluster in the lower left, leaving most cells empty.
+        for i in 0..60 {
+            let x = (i * 7 % 40) as f64;
+            let y = (i * 13 % 35) as f64;
+            tree.insert(Point3D::new(x, y, (i * 11 % 30) as f64, Some(i)));
+        }
+        for depth in 0..5 {
+            let cells = tree.clone().split_at(depth);
+            for query in [
+                (95.0, 95.0, 95.0),
+                (10.0, 90.0, 5.0),
+                (20.0, 20.0, 20.0),
+                (70.0, 5.0, 50.0),
+            ] {
+                let query = Point3D::new(query.0, query.1, query.2, None);
+                let (cell, mut points) = tree.nearest_occupied_cell(&query, depth).unwrap();
+                let nearest = cells
+                    .iter()
+                    .map(|part| part.boundary.min_distance(&query))
+                    .fold(f64::INFINITY, f64::min);
+                assert_eq!(cell.min_distance(&query), nearest);
+                let corners = |b: &Cube| (b.x, b.y, b.z, b.width, b.height, b.depth);
+                let part = cells
+                    .iter()
+                    .find(|part| corners(&part.boundary) == corners(&cell))
+                    .unwrap();
+                let mut expected = Vec::new();
+                part.collect_points(&mut expected);
+                let mut expected: Vec<_> = expected.into_iter().cloned().collect();
+                expected.sort_by_key(|p| p.data);
+                points.sort_by_key(|p| p.data);
+                assert_eq!(points, expected);
+            }
+        }
+        let empty = Octree::<i32>::new(&boundary, 2).unwrap();
+        assert!(
+            empty
+                .nearest_occupied_cell(&Point3D::new(1.0, 1.0, 1.0, None), 1)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_iter_and_into_iter_yield_every_point() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 100.0,
+            height: 100.0,
+            depth: 100.0,
+        };
+        let mut tree = Octree::new(&boundary, 3).unwrap();
+        for i in 0..150 {
+            let (x, y, z) = (
+                (i * 7 % 100) as f64,
+                (i * 13 % 100) as f64,
+                (i * 3 % 100) as f64,
+            );
+            tree.insert(Point3D::new(x, y, z, Some(i)));
+        }
+        let mut ids: Vec<_> = (&tree).into_iter().filter_map(|p| p.data).collect();
+        ids.sort();
+        assert_eq!(ids, (0..150).collect::<Vec<_>>());
+        let borrowed: Vec<_> = tree.iter().cloned().collect();
+        let owned: Vec<_> = tree.into_iter().collect();
+        assert_eq!(owned, borrowed);
+    }
+
+    #[test]
+    fn test_downsample_keeps_one_point_per_voxel() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 100.0,
+            height: 100.0,
+            depth: 100.0,
+        };
+        let mut tree = Octree::new(&boundary, 4).unwrap();
+        let points: Vec<_> = (0..500)
+            .map(|i| {
+                // The points are distinct, with x = i % 100 and z telling the hundreds apart.
+                let x = (i % 100) as f64;
+                let y = (i * 13 % 97) as f64;
+                let z = (i / 100 * 20 + i * 29 % 19) as f64;
+                Point3D::new(x, y, z, Some(i))
+            })
+            .collect();
+        for point in &points {
+            tree.insert(point.clone());
+        }
+        for cell_size in [3.0, 10.0, 25.0, 200.0] {
+            let key = |p: &Point3D<i32>| {
+                [
+                    (p.x / cell_size).floor() as i64,
+                    (p.y / cell_size).floor() as i64,
+                    (p.z / cell_size).floor() as i64,
+                ]
+            };
+            let mut expected: BTreeMap<_, Vec<&Point3D<i32>>> = BTreeMap::new();
+            for point in &points {
+                expected.entry(key(point)).or_default().push(point);
+            }
+            let centroids = tree
+                .downsample(cell_size, VoxelRepresentative::Centroid)
+                .unwrap();
+            let firsts = tree
+                .downsample(cell_size, VoxelRepresentative::First)
+                .unwrap();
+            assert_eq!(centroids.len(), expected.len());
+            assert_eq!(firsts.len(), expected.len());
+            for ((centroid, first), members) in centroids.iter().zip(&firsts).zip(expected.values())
+            {
+                assert!(members.contains(&first));
+                assert_eq!(key(centroid), key(first));
+                let n = members.len() as f64;
+                let mean_x = members.iter().map(|p| p.x).sum::<f64>() / n;
+                assert!((centroid.x - mean_x).abs() < 1e-9);
+                assert_eq!(centroid.data, first.data);
+            }
+        }
+        assert!(tree.downsample(0.0, VoxelRepresentative::First).is_err());
+    }
 }
@@ -0,0 +1,888 @@
+//! ## Paged R‑tree
+//!
+//! This module provides a disk-friendly R‑tree over 2D points, stored as fixed-size pages, and
+//! `PagedRTree`, which runs kNN and range searches on it with `async` methods that await each
+//! page they visit. The pages are read through a `PageSource`: `FilePages` reads them from a
+//! file on Tokio's blocking thread pool, so that a service can run many spatial queries
+//! concurrently and overlap their I/O instead of blocking its worker threads. Any other
+//! storage (e.g., an object store or a page cache) can be used by implementing `PageSource`.
+//!
+//! Like `mapped_kdtree`, the format stores coordinates and a 64-bit id per point instead of the
+//! points themselves, so that it does not depend on the payload type. The tree is bulk loaded
+//! with the sort-tile-recursive method by `to_pages` or `write_file` and is read-only.
+//!
+//! This module is only available with the `tokio` feature.
+//!
+//! ### File Format
+//!
+//! All numbers are little-endian. The file is a sequence of pages of the same size. The first
+//! page holds the header: the magic bytes `SPRT`, the format version (`u32`), the page size
+//! (`u32`), the number of pages (`u32`), the page of the root (`u32`), the height of the tree
+//! (`u32`), the number of points (`u64`), and the CRC-32 checksum (`u32`) of these 32 bytes.
+//! Every other page holds one node: its kind (`u32`, 0 for a leaf and 1 for an inner node),
+//! its number of entries (`u32`), the entries, and the CRC-32 checksum (`u32`) of the rest of
+//! the page in its last four bytes. A leaf entry is a point (two `f64`s) and its id (`u64`);
+//! an inner entry is the bounding box of a child (`min_x`, `min_y`, `max_x`, `max_y` as
+//! `f64`s) and the page of the child (`u32`).
+//!
+//! Children are written before their parents, and searches only follow references to earlier
+//! pages, so even pages with valid checksums cannot make them loop.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::paged_rtree::{PagedRTree, to_pages};
+//!
+//! let points: Vec<Point2D<u64>> = (0..100)
+//!     .map(|i| Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)))
+//!     .collect();
+//! let pages = to_pages(&points, |p| p.data.unwrap(), 256).unwrap();
+//!
+//! let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+//! runtime.block_on(async {
+//!     let tree = PagedRTree::open(pages).await.unwrap();
+//!     let window = Rectangle { x: 0.0, y: 0.0, width: 1.0, height: 1.0 };
+//!     // Independent queries on the same tree run concurrently.
+//!     let (nearest, found) = tokio::join!(
+//!         tree.knn_search([3.1, 4.2], 1),
+//!         tree.range_search_bbox(&window),
+//!     );
+//!     assert_eq!(nearest.unwrap()[0].0, 43);
+//!     assert_eq!(found.unwrap().len(), 4);
+//! });
+//! ```
+
+use crate::checksum::crc32;
+use crate::errors::SpartError;
+use crate::geometry::{Point2D, Rectangle};
+use crate::knn::KnnResults;
+use ordered_float::OrderedFloat;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+/// The magic bytes at the start of the format.
+const MAGIC: &[u8; 4] = b"SPRT";
+
+/// The version of the format written by this crate.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The size of the part of the header covered by its checksum.
+const CHECKED_HEADER_LEN: usize = 32;
+
+/// The size of the header in bytes.
+const HEADER_LEN: usize = CHECKED_HEADER_LEN + 4;
+
+/// The size of the kind and entry count at the start of a node page.
+const NODE_HEADER_LEN: usize = 8;
+
+/// The size of a leaf entry: a point and its id.
+const LEAF_ENTRY_LEN: usize = 24;
+
+/// The size of an inner entry: a bounding box and the page of the child.
+const INNER_ENTRY_LEN: usize = 36;
+
+/// The smallest page size, which holds two inner entries.
+pub const MIN_PAGE_SIZE: usize = NODE_HEADER_LEN + 2 * INNER_ENTRY_LEN + 4;
+
+const LEAF: u32 = 0;
+const INNER: u32 = 1;
+
+/// Storage that the pages of a `PagedRTree` are read from.
+pub trait PageSource {
+    /// Reads `len` bytes starting at byte `offset`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the bytes cannot be read.
+    fn read_at(
+        &self,
+        offset: u64,
+        len: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, SpartError>> + Send;
+}
+
+/// Pages held in memory, as returned by `to_pages`.
+impl PageSource for Vec<u8> {
+    fn read_at(
+        &self,
+        offset: u64,
+        len: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, SpartError>> + Send {
+        let bytes = usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.get(start..start.checked_add(len)?))
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| SpartError::Persistence {
+                reason: format!("cannot read {len} bytes at offset {offset}"),
+            });
+        std::future::ready(bytes)
+    }
+}
+
+/// Pages read from a file on Tokio's blocking thread pool.
+///
+/// Reads use positioned I/O on a shared file handle, so concurrent queries read their pages
+/// in parallel. They must be awaited within a Tokio runtime.
+#[derive(Debug, Clone)]
+pub struct FilePages {
+    file: Arc<File>,
+}
+
+impl FilePages {
+    /// Opens a file written by `write_file`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the file cannot be opened.
+    pub async fn open(path: &Path) -> Result<Self, SpartError> {
+        let path = path.to_path_buf();
+        let opened = tokio::task::spawn_blocking(move || {
+            File::open(&path).map_err(|e| SpartError::Persistence {
+                reason: format!("cannot open index {}: {e}", path.display()),
+            })
+        })
+        .await
+        .map_err(|e| SpartError::Persistence {
+            reason: format!("cannot open index: {e}"),
+        })??;
+        Ok(FilePages {
+            file: Arc::new(opened),
+        })
+    }
+}
+
+impl PageSource for FilePages {
+    fn read_at(
+        &self,
+        offset: u64,
+        len: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, SpartError>> + Send {
+        let file = Arc::clone(&self.file);
+        async move {
+            tokio::task::spawn_blocking(move || {
+                let mut buf = vec![0; len];
+                read_exact_at(&file, &mut buf, offset).map(|()| buf)
+            })
+            .await
+            .map_err(|e| SpartError::Persistence {
+                reason: format!("page read did not complete: {e}"),
+            })?
+            .map_err(|e| SpartError::Persistence {
+                reason: format!("cannot read {len} bytes at offset {offset}: {e}"),
+            })
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        match std::os::windows::fs::FileExt::seek_read(file, buf, offset)? {
+            0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_exact_at(_file: &File, _buf: &mut [u8], _offset: u64) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// An entry of a node being written: a bounding box and a point id or child page.
+struct Entry {
+    bbox: [f64; 4],
+    value: u64,
+}
+
+/// Splits `entries` into groups of at most `capacity` with the sort-tile-recursive method.
+fn tile(mut entries: Vec<Entry>, capacity: usize) -> Vec<Vec<Entry>> {
+    let center = |e: &Entry, axis: usize| OrderedFloat(e.bbox[axis] + e.bbox[axis + 2]);
+    let groups = entries.len().div_ceil(capacity);
+    let slices = (groups as f64).sqrt().ceil() as usize;
+    let slice_len = capacity * groups.div_ceil(slices.max(1));
+    entries.sort_by_key(|e| center(e, 0));
+    let mut tiles = Vec::with_capacity(groups);
+    let mut rest = entries.into_iter().peekable();
+    while rest.peek().is_some() {
+        let mut slice: Vec<Entry> = rest.by_ref().take(slice_len).collect();
+        slice.sort_by_key(|e| center(e, 1));
+        let mut slice = slice.into_iter().peekable();
+        while slice.peek().is_some() {
+            tiles.push(slice.by_ref().take(capacity).collect());
+        }
+    }
+    tiles
+}
+
+/// Appends a node page holding `entries` to `bytes` and returns the entry pointing to it.
+fn write_node(bytes: &mut Vec<u8>, page_size: usize, kind: u32, entries: &[Entry]) -> Entry {
+    let page = (bytes.len() / page_size) as u64;
+    let start = bytes.len();
+    bytes.extend_from_slice(&kind.to_le_bytes());
+    bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    let mut bbox = [
+        f64::INFINITY,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NEG_INFINITY,
+    ];
+    for entry in entries {
+        if kind == LEAF {
+            bytes.extend_from_slice(&entry.bbox[0].to_le_bytes());
+            bytes.extend_from_slice(&entry.bbox[1].to_le_bytes());
+            bytes.extend_from_slice(&entry.value.to_le_bytes());
+        } else {
+            for coord in entry.bbox {
+                bytes.extend_from_slice(&coord.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(entry.value as u32).to_le_bytes());
+        }
+        bbox = [
+            bbox[0].min(entry.bbox[0]),
+            bbox[1].min(entry.bbox[1]),
+            bbox[2].max(entry.bbox[2]),
+            bbox[3].max(entry.bbox[3]),
+        ];
+    }
+    bytes.resize(start + page_size - 4, 0);
+    let checksum = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    Entry { bbox, value: page }
+}
+
+/// Encodes points as a paged R‑tree, in the format read by `PagedRTree`.
+///
+/// # Arguments
+///
+/// * `points` - The points to store.
+/// * `id` - Returns the id stored for a point.
+/// * `page_size` - The size of a page in bytes, e.g., the block size of the storage.
+///
+/// # Errors
+///
+/// Returns `SpartError::InvalidCapacity` if `page_size` is below `MIN_PAGE_SIZE` or does not
+/// fit in a `u32`, and `SpartError::InvalidCoordinates` if a point has a NaN coordinate.
+pub fn to_pages<T>(
+    points: &[Point2D<T>],
+    id: impl Fn(&Point2D<T>) -> u64,
+    page_size: usize,
+) -> Result<Vec<u8>, SpartError> {
+    if page_size < MIN_PAGE_SIZE || u32::try_from(page_size).is_err() {
+        return Err(SpartError::InvalidCapacity {
+            capacity: page_size,
+        });
+    }
+    if points.iter().any(|p| p.x.is_nan() || p.y.is_nan()) {
+        return Err(SpartError::InvalidCoordinates {
+            reason: "a point has a NaN coordinate".to_string(),
+        });
+    }
+    let slots = page_size - NODE_HEADER_LEN - 4;
+    let mut bytes = vec![0; page_size];
+    let mut entries: Vec<Entry> = points
+        .iter()
+        .map(|p| Entry {
+            bbox: [p.x, p.y, p.x, p.y],
+            value: id(p),
+        })
+        .collect();
+    let mut kind = LEAF;
+    let mut height = 1u32;
+    let root = loop {
+        let capacity = slots
+            / if kind == LEAF {
+                LEAF_ENTRY_LEN
+            } else {
+                INNER_ENTRY_LEN
+            };
+        if entries.len() <= capacity {
+            break write_node(&mut bytes, page_size, kind, &entries);
+        }
+        entries = tile(entries, capacity)
+            .iter()
+            .map(|group| write_node(&mut bytes, page_size, kind, group))
+            .collect();
+        kind = INNER;
+        height += 1;
+    };
+    let page_count = (bytes.len() / page_size) as u32;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    for value in [
+        FORMAT_VERSION,
+        page_size as u32,
+        page_count,
+        root.value as u32,
+        height,
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header.extend_from_slice(&(points.len() as u64).to_le_bytes());
+    header.extend_from_slice(&crc32(&header).to_le_bytes());
+    bytes[..HEADER_LEN].copy_from_slice(&header);
+    Ok(bytes)
+}
+
+/// Writes points to a file as a paged R‑tree, in the format read by `PagedRTree`.
+///
+/// The pages are written to a temporary file next to `path` first, which is then renamed over
+/// `path`, so that readers never see a partially written file.
+///
+/// # Arguments
+///
+/// * `path` - The file to write.
+/// * `points` - The points to store.
+/// * `id` - Returns the id stored for a point.
+/// * `page_size` - The size of a page in bytes.
+///
+/// # Errors
+///
+/// Returns the errors of `to_pages`, and `SpartError::Persistence` if the file cannot be
+/// written.
+pub fn write_file<T>(
+    path: &Path,
+    points: &[Point2D<T>],
+    id: impl Fn(&Point2D<T>) -> u64,
+    page_size: usize,
+) -> Result<(), SpartError> {
+    let bytes = to_pages(points, id, page_size)?;
+    let mut tmp_path = OsString::from(path.as_os_str());
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    let write = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    };
+    write().map_err(|e| SpartError::Persistence {
+        reason: format!("cannot write index {}: {e}", path.display()),
+    })
+}
+
+/// A node page read from a `PageSource`.
+struct Node {
+    page: u32,
+    bytes: Vec<u8>,
+}
+
+impl Node {
+    fn kind(&self) -> u32 {
+        read_u32(&self.bytes, 0)
+    }
+
+    fn len(&self) -> usize {
+        read_u32(&self.bytes, 4) as usize
+    }
+
+    /// Returns the points and ids of a leaf.
+    fn points(&self) -> impl Iterator<Item = ([f64; 2], u64)> + '_ {
+        (0..self.len()).map(|i| {
+            let offset = NODE_HEADER_LEN + i * LEAF_ENTRY_LEN;
+            (
+                [
+                    read_f64(&self.bytes, offset),
+                    read_f64(&self.bytes, offset + 8),
+                ],
+                read_u64(&self.bytes, offset + 16),
+            )
+        })
+    }
+
+    /// Returns the bounding boxes and pages of the children of an inner node, skipping
+    /// references that do not point to an earlier node page.
+    fn children(&self) -> impl Iterator<Item = ([f64; 4], u32)> + '_ {
+        (0..self.len()).filter_map(|i| {
+            let offset = NODE_HEADER_LEN + i * INNER_ENTRY_LEN;
+            let bbox = [0, 1, 2, 3].map(|k| read_f64(&self.bytes, offset + 8 * k));
+            let child = read_u32(&self.bytes, offset + 32);
+            (child > 0 && child < self.page).then_some((bbox, child))
+        })
+    }
+}
+
+/// Returns the squared distance from `target` to the nearest point of `bbox`.
+fn min_distance_sq(bbox: &[f64; 4], target: [f64; 2]) -> f64 {
+    let dx = (bbox[0] - target[0]).max(target[0] - bbox[2]).max(0.0);
+    let dy = (bbox[1] - target[1]).max(target[1] - bbox[3]).max(0.0);
+    dx * dx + dy * dy
+}
+
+/// A read-only R‑tree whose pages are fetched from a `PageSource` as queries visit them.
+#[derive(Debug)]
+pub struct PagedRTree<S> {
+    source: S,
+    page_size: usize,
+    page_count: u32,
+    root: u32,
+    height: usize,
+    len: u64,
+}
+
+impl<S: PageSource> PagedRTree<S> {
+    /// Opens a paged R‑tree, reading and checking its header.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The pages, as written by `to_pages` or `write_file`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the header cannot be read or is not a valid
+    /// header of this format.
+    pub async fn open(source: S) -> Result<Self, SpartError> {
+        let corrupt = |reason: String| SpartError::Persistence { reason };
+        let header = source.read_at(0, HEADER_LEN).await?;
+        if header.len() != HEADER_LEN || &header[..4] != MAGIC {
+            return Err(corrupt("not a paged R-tree".to_string()));
+        }
+        if crc32(&header[..CHECKED_HEADER_LEN]) != read_u32(&header, CHECKED_HEADER_LEN) {
+            return Err(corrupt("header checksum mismatch".to_string()));
+        }
+        let version = read_u32(&header, 4);
+        if version != FORMAT_VERSION {
+            return Err(corrupt(format!("unsupported format version {version}")));
+        }
+        let page_size = read_u32(&header, 8) as usize;
+        let page_count = read_u32(&header, 12);
+        let root = read_u32(&header, 16);
+        if page_size < MIN_PAGE_SIZE || root == 0 || root >= page_count {
+            return Err(corrupt(format!(
+                "root page {root} of {page_count} pages of {page_size} bytes"
+            )));
+        }
+        info!("Opened paged R-tree with {} pages", page_count);
+        Ok(PagedRTree {
+            source,
+            page_size,
+            page_count,
+            root,
+            height: read_u32(&header, 20) as usize,
+            len: read_u64(&header, 24),
+        })
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of levels of nodes.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of pages, including the header page.
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    /// Finds the ids of all points within a rectangle, boundary included.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if a visited page cannot be read or fails its
+    /// checksum.
+    pub async fn range_search_bbox(&self, query: &Rectangle) -> Result<Vec<u64>, SpartError> {
+        let bounds = [
+            query.x,
+            query.y,
+            query.x + query.width,
+            query.y + query.height,
+        ];
+        let overlaps = |bbox: &[f64; 4]| {
+            bbox[0] <= bounds[2]
+                && bounds[0] <= bbox[2]
+                && bbox[1] <= bounds[3]
+                && bounds[1] <= bbox[3]
+        };
+        let mut found = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(page) = stack.pop() {
+            let node = self.read_node(page).await?;
+            if node.kind() == LEAF {
+                found.extend(
+                    node.points()
+                        .filter(|(p, _)| overlaps(&[p[0], p[1], p[0], p[1]]))
+                        .map(|(_, id)| id),
+                );
+            } else {
+                stack.extend(
+                    node.children()
+                        .filter(|(bbox, _)| overlaps(bbox))
+                        .map(|(_, child)| child),
+                );
+            }
+        }
+        Ok(found)
+    }
+
+    /// Finds the ids of all points within `radius` of `center`, with Euclidean distance.
+    ///
+    /// Returns no ids if `radius` is negative or NaN or `center` has a NaN coordinate.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if a visited page cannot be read or fails its
+    /// checksum.
+    pub async fn range_search(
+        &self,
+        center: [f64; 2],
+        radius: f64,
+    ) -> Result<Vec<u64>, SpartError> {
+        let mut found = Vec::new();
+        if radius.is_nan() || radius < 0.0 || center.iter().any(|c| c.is_nan()) {
+            return Ok(found);
+        }
+        let radius_sq = radius * radius;
+        let mut stack = vec![self.root];
+        while let Some(page) = stack.pop() {
+            let node = self.read_node(page).await?;
+            if node.kind() == LEAF {
+                found.extend(
+                    node.points()
+                        .filter(|(p, _)| {
+                            min_distance_sq(&[p[0], p[1], p[0], p[1]], center) <= radius_sq
+                        })
+                        .map(|(_, id)| id),
+                );
+            } else {
+                stack.extend(
+                    node.children()
+                        .filter(|(bbox, _)| min_distance_sq(bbox, center) <= radius_sq)
+                        .map(|(_, child)| child),
+                );
+            }
+        }
+        Ok(found)
+    }
+
+    /// Performs a k‑nearest neighbor search with Euclidean distance.
+    ///
+    /// Pages are visited in order of their distance to `target`, so the search stops reading
+    /// pages once no unvisited page can hold a nearer point.
+    ///
+    /// # Returns
+    ///
+    /// The ids of the nearest points paired with their distances, ordered from nearest to
+    /// farthest; empty if `k` is zero or `target` has a NaN coordinate.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if a visited page cannot be read or fails its
+    /// checksum.
+    pub async fn knn_search(
+        &self,
+        target: [f64; 2],
+        k: usize,
+    ) -> Result<Vec<(u64, f64)>, SpartError> {
+        if k == 0 || target.iter().any(|c| c.is_nan()) {
+            return Ok(Vec::new());
+        }
+        let mut results = KnnResults::new(k);
+        let mut queue = BinaryHeap::from([Reverse((OrderedFloat(0.0), self.root))]);
+        while let Some(Reverse((OrderedFloat(dist_sq), page))) = queue.pop() {
+            if !results.accepts(dist_sq) {
+                break;
+            }
+            let node = self.read_node(page).await?;
+            if node.kind() == LEAF {
+                for (p, id) in node.points() {
+                    let dist_sq = min_distance_sq(&[p[0], p[1], p[0], p[1]], target);
+                    results.push(dist_sq, (id, dist_sq));
+                }
+            } else {
+                for (bbox, child) in node.children() {
+                    let dist_sq = min_distance_sq(&bbox, target);
+                    if results.accepts(dist_sq) {
+                        queue.push(Reverse((OrderedFloat(dist_sq), child)));
+                    }
+                }
+            }
+        }
+        Ok(results
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(id, dist_sq)| (id, dist_sq.sqrt()))
+            .collect())
+    }
+
+    /// Reads a node page and checks its checksum and entry count.
+    async fn read_node(&self, page: u32) -> Result<Node, SpartError> {
+        let corrupt = |reason: String| SpartError::Persistence { reason };
+        if page >= self.page_count {
+            return Err(corrupt(format!("page {page} is past the end of the tree")));
+        }
+        let offset = u64::from(page) * self.page_size as u64;
+        let bytes = self.source.read_at(offset, self.page_size).await?;
+        let body = self.page_size - 4;
+        if bytes.len() != self.page_size || crc32(&bytes[..body]) != read_u32(&bytes, body) {
+            return Err(corrupt(format!("page {page} checksum mismatch")));
+        }
+        let node = Node { page, bytes };
+        let entry_len = match node.kind() {
+            LEAF => LEAF_ENTRY_LEN,
+            INNER => INNER_ENTRY_LEN,
+            kind => return Err(corrupt(format!("page {page} has unknown kind {kind}"))),
+        };
+        if NODE_HEADER_LEN + node.len() * entry_len > body {
+            return Err(corrupt(format!(
+                "page {page} holds more entries than fit in it"
+            )));
+        }
+        Ok(node)
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(read_array(bytes, offset))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(read_array(bytes, offset))
+}
+
+fn read_f64(bytes: &[u8], offset: usize) -> f64 {
+    f64::from_le_bytes(read_array(bytes, offset))
+}
+
+fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> [u8; N] {
+    bytes[offset..offset + N]
+        .try_into()
+        .unwrap_or_else(|_| unreachable!("the slice has length N"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{RandomSource, SplitMix64};
+
+    fn random_points(seed: u64, n: u64) -> Vec<Point2D<u64>> {
+        let mut rng = SplitMix64::new(seed);
+        (0..n)
+            .map(|i| {
+                let mut coord = || rng.next_below(1000) as f64 / 10.0;
+                Point2D::new(coord(), coord(), Some(i))
+            })
+            .collect()
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn open(points: &[Point2D<u64>], page_size: usize) -> PagedRTree<Vec<u8>> {
+        let pages = to_pages(points, |p| p.data.unwrap(), page_size).unwrap();
+        block_on(PagedRTree::open(pages)).unwrap()
+    }
+
+    #[test]
+    fn test_range_search_bbox_matches_scan() {
+        let points = random_points(1, 2000);
+        let tree = open(&points, MIN_PAGE_SIZE);
+        assert_eq!(tree.len(), 2000);
+        assert!(tree.height() > 2);
+        let query = Rectangle {
+            x: 20.0,
+            y: 35.5,
+            width: 30.0,
+            height: 12.5,
+        };
+        let mut found = block_on(tree.range_search_bbox(&query)).unwrap();
+        found.sort_unstable();
+        let expected: Vec<u64> = points
+            .iter()
+            .filter(|p| query.contains(p))
+            .map(|p| p.data.unwrap())
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_range_search_bbox_includes_boundary() {
+        let points: Vec<Point2D<u64>> = (0..100)
+            .map(|i| Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)))
+            .collect();
+        let tree = open(&points, MIN_PAGE_SIZE);
+        let query = Rectangle {
+            x: 2.0,
+            y: 3.0,
+            width: 1.0,
+            height: 0.0,
+        };
+        let mut found = block_on(tree.range_search_bbox(&query)).unwrap();
+        found.sort_unstable();
+        assert_eq!(found, vec![32, 33]);
+    }
+
+    #[test]
+    fn test_range_search_matches_scan() {
+        let points = random_points(2, 1500);
+        let tree = open(&points, 512);
+        let center = [50.0, 50.0];
+        let mut found = block_on(tree.range_search(center, 12.5)).unwrap();
+        found.sort_unstable();
+        let expected: Vec<u64> = points
+            .iter()
+            .filter(|p| (p.x - 50.0).powi(2) + (p.y - 50.0).powi(2) <= 12.5 * 12.5)
+            .map(|p| p.data.unwrap())
+            .collect();
+        assert_eq!(found, expected);
+        assert!(
+            block_on(tree.range_search(center, -1.0))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            block_on(tree.range_search([f64::NAN, 0.0], 1.0))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_knn_search_matches_scan() {
+        let points = random_points(3, 1500);
+        let tree = open(&points, MIN_PAGE_SIZE);
+        let target = [31.4, 15.9];
+        let mut expected: Vec<f64> = points
+            .iter()
+            .map(|p| ((p.x - target[0]).powi(2) + (p.y - target[1]).powi(2)).sqrt())
+            .collect();
+        expected.sort_by(f64::total_cmp);
+        let found = block_on(tree.knn_search(target, 10)).unwrap();
+        assert_eq!(
+            found.iter().map(|n| n.1).collect::<Vec<_>>(),
+            expected[..10]
+        );
+        for (id, distance) in found {
+            let p = &points[id as usize];
+            assert_eq!(
+                ((p.x - target[0]).powi(2) + (p.y - target[1]).powi(2)).sqrt(),
+                distance
+            );
+        }
+        assert!(block_on(tree.knn_search(target, 0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let tree = open(&[], MIN_PAGE_SIZE);
+        assert!(tree.is_empty());
+        assert_eq!(tree.page_count(), 2);
+        let everything = Rectangle {
+            x: -1e9,
+            y: -1e9,
+            width: 2e9,
+            height: 2e9,
+        };
+        assert!(
+            block_on(tree.range_search_bbox(&everything))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(block_on(tree.knn_search([0.0, 0.0], 3)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_input_rejected() {
+        let points = [Point2D::new(1.0, 2.0, Some(0u64))];
+        assert!(matches!(
+            to_pages(&points, |p| p.data.unwrap(), MIN_PAGE_SIZE - 1),
+            Err(SpartError::InvalidCapacity { .. })
+        ));
+        let points = [Point2D::new(f64::NAN, 2.0, Some(0u64))];
+        assert!(matches!(
+            to_pages(&points, |p| p.data.unwrap(), MIN_PAGE_SIZE),
+            Err(SpartError::InvalidCoordinates { .. })
+        ));
+    }
+
+    #[test]
+    fn test_corrupted_pages_reported() {
+        let points = random_points(4, 500);
+        let pages = to_pages(&points, |p| p.data.unwrap(), 256).unwrap();
+        assert!(matches!(
+            block_on(PagedRTree::open(pages[..HEADER_LEN - 1].to_vec())),
+            Err(SpartError::Persistence { .. })
+        ));
+        let mut header = pages.clone();
+        header[12] ^= 1;
+        assert!(matches!(
+            block_on(PagedRTree::open(header)),
+            Err(SpartError::Persistence { .. })
+        ));
+
+        let mut damaged = pages;
+        // Flip a coordinate in the first leaf, right after the header page.
+        damaged[256 + NODE_HEADER_LEN] ^= 1;
+        let tree = block_on(PagedRTree::open(damaged)).unwrap();
+        let everything = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        assert!(matches!(
+            block_on(tree.range_search_bbox(&everything)),
+            Err(SpartError::Persistence { .. })
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_queries_on_file() {
+        let points = random_points(5, 3000);
+        let path = std::env::temp_dir().join(format!("spart-paged-{}.rt", std::process::id()));
+        write_file(&path, &points, |p| p.data.unwrap(), 512).unwrap();
+        let memory = open(&points, 512);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let tree = Arc::new(
+                PagedRTree::open(FilePages::open(&path).await.unwrap())
+                    .await
+                    .unwrap(),
+            );
+            let tasks: Vec<_> = (0..8)
+                .map(|i| {
+                    let tree = Arc::clone(&tree);
+                    tokio::spawn(async move { tree.knn_search([i as f64 * 10.0, 50.0], 5).await })
+                })
+                .collect();
+            for (i, task) in tasks.into_iter().enumerate() {
+                let found = task.await.unwrap().unwrap();
+                let expected = memory.knn_search([i as f64 * 10.0, 50.0], 5).await.unwrap();
+                assert_eq!(found, expected);
+            }
+        });
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+//! ## Payload Lookup
+//!
+//! This module provides `PayloadIndexed`, a wrapper that keeps a hash table from the payload
+//! of each stored point (its `data` field) to the point, next to the spatial index. It makes
+//! finding or removing a point by its payload a hash lookup instead of a scan of the whole
+//! tree, so an entity can be removed without knowing where it currently is.
+//!
+//! The table is enabled with `with_payload_index` on `Quadtree` and `Octree`, which requires
+//! the payload type to implement `Hash` and `Eq`. Points without a payload are stored in the
+//! tree as usual but cannot be looked up.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::quadtree::Quadtree;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let mut tree = Quadtree::new(&boundary, 4).unwrap().with_payload_index();
+//! tree.insert(Point2D::new(10.0, 20.0, Some(42)));
+//! tree.insert(Point2D::new(30.0, 40.0, Some(7)));
+//!
+//! assert_eq!(tree.find_by_data(&42).map(|p| (p.x, p.y)), Some((10.0, 20.0)));
+//! assert_eq!(tree.delete_by_data(&42), 1);
+//! assert!(tree.find_by_data(&42).is_none());
+//! assert!(!tree.tree().contains(&Point2D::new(10.0, 20.0, Some(42))));
+//! ```
+
+use crate::geometry::{Point2D, Point3D};
+use crate::octree::Octree;
+use crate::quadtree::Quadtree;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A tree of points with payloads that `PayloadIndexed` can keep a lookup table for.
+pub trait PayloadTree {
+    /// The type of the points stored in the tree.
+    type Point: Clone;
+    /// The type of the payloads used as lookup keys.
+    type Payload;
+
+    /// Inserts a point, returning whether it was stored.
+    fn insert_point(&mut self, point: Self::Point) -> bool;
+
+    /// Deletes a point matching `point`, returning whether one was found.
+    fn delete_point(&mut self, point: &Self::Point) -> bool;
+
+    /// Returns whether a stored point is matched by `query` in `delete_point`.
+    fn matches(&self, stored: &Self::Point, query: &Self::Point) -> bool;
+
+    /// Returns the payload of a point, if it has one.
+    fn payload(point: &Self::Point) -> Option<&Self::Payload>;
+
+    /// Returns all points stored in the tree.
+    fn stored_points(&self) -> Vec<&Self::Point>;
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> PayloadTree for Quadtree<T> {
+    type Point = Point2D<T>;
+    type Payload = T;
+
+    fn insert_point(&mut self, point: Point2D<T>) -> bool {
+        self.insert(point)
+    }
+
+    fn delete_point(&mut self, point: &Point2D<T>) -> bool {
+        self.delete(point)
+    }
+
+    fn matches(&self, stored: &Point2D<T>, query: &Point2D<T>) -> bool {
+        stored.approx_eq(query, self.tolerance())
+    }
+
+    fn payload(point: &Point2D<T>) -> Option<&T> {
+        point.data.as_ref()
+    }
+
+    fn stored_points(&self) -> Vec<&Point2D<T>> {
+        self.export_sorted().collect()
+    }
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> PayloadTree for Octree<T> {
+    type Point = Point3D<T>;
+    type Payload = T;
+
+    fn insert_point(&mut self, point: Point3D<T>) -> bool {
+        self.insert(point)
+    }
+
+    fn delete_point(&mut self, point: &Point3D<T>) -> bool {
+        self.delete(point)
+    }
+
+    fn matches(&self, stored: &Point3D<T>, query: &Point3D<T>) -> bool {
+        stored.approx_eq(query, self.tolerance())
+    }
+
+    fn payload(point: &Point3D<T>) -> Option<&T> {
+        point.data.as_ref()
+    }
+
+    fn stored_points(&self) -> Vec<&Point3D<T>> {
+        self.export_sorted().collect()
+    }
+}
+
+/// A tree together with a hash table from payloads to the points carrying them.
+///
+/// Updates go through `insert`, `delete`, and `delete_by_data`, which keep the table in sync
+/// with the tree. Spatial queries go through `tree`.
+#[derive(Debug, Clone)]
+pub struct PayloadIndexed<I: PayloadTree> {
+    tree: I,
+    by_payload: HashMap<I::Payload, Vec<I::Point>>,
+}
+
+impl<I> PayloadIndexed<I>
+where
+    I: PayloadTree,
+    I::Payload: Hash + Eq + Clone,
+{
+    /// Wraps a tree, building the table from the points it already holds.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The tree to wrap.
+    pub fn new(tree: I) -> Self {
+        let mut by_payload: HashMap<I::Payload, Vec<I::Point>> = HashMap::new();
+        for point in tree.stored_points() {
+            if let Some(payload) = I::payload(point) {
+                by_payload
+                    .entry(payload.clone())
+                    .or_default()
+                    .push(point.clone());
+            }
+        }
+        PayloadIndexed { tree, by_payload }
+    }
+
+    /// Returns the wrapped tree, for spatial queries.
+    pub fn tree(&self) -> &I {
+        &self.tree
+    }
+
+    /// Unwraps the tree, dropping the table.
+    pub fn into_inner(self) -> I {
+        self.tree
+    }
+
+    /// Inserts a point into the tree and, if it has a payload, into the table.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was stored (e.g., it lies inside the tree's boundary).
+    pub fn insert(&mut self, point: I::Point) -> bool {
+        if !self.tree.insert_point(point.clone()) {
+            return false;
+        }
+        if let Some(payload) = I::payload(&point) {
+            self.by_payload
+                .entry(payload.clone())
+                .or_default()
+                .push(point);
+        }
+        true
+    }
+
+    /// Deletes a point matching `point` from the tree and the table.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and removed.
+    pub fn delete(&mut self, point: &I::Point) -> bool {
+        if !self.tree.delete_point(point) {
+            return false;
+        }
+        if let Some(payload) = I::payload(point) {
+            if let Some(points) = self.by_payload.get_mut(payload) {
+                let position = points
+                    .iter()
+                    .position(|stored| self.tree.matches(stored, point));
+                if let Some(position) = position {
+                    points.swap_remove(position);
+                }
+                if points.is_empty() {
+                    self.by_payload.remove(payload);
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns a stored point carrying `payload`, if there is one.
+    ///
+    /// If several points carry the same payload, which one is returned is unspecified; use
+    /// `find_all_by_data` to get all of them.
+    pub fn find_by_data(&self, payload: &I::Payload) -> Option<&I::Point> {
+        self.find_all_by_data(payload).first()
+    }
+
+    /// Returns all stored points carrying `payload`.
+    pub fn find_all_by_data(&self, payload: &I::Payload) -> &[I::Point] {
+        self.by_payload
+            .get(payload)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Deletes all points carrying `payload` from the tree and the table.
+    ///
+    /// # Returns
+    ///
+    /// The number of points deleted.
+    pub fn delete_by_data(&mut self, payload: &I::Payload) -> usize {
+        let Some(points) = self.by_payload.remove(payload) else {
+            return 0;
+        };
+        points
+            .iter()
+            .filter(|point| self.tree.delete_point(point))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Cube, Rectangle};
+
+    #[test]
+    fn test_lookup_tracks_inserts_and_deletes() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree = Quadtree::new(&boundary, 2).unwrap();
+        tree.insert(Point2D::new(1.0, 1.0, Some("a")));
+        let mut indexed = tree.with_payload_index();
+        assert_eq!(indexed.find_by_data(&"a").map(|p| p.x), Some(1.0));
+
+        for i in 0..20 {
+            assert!(indexed.insert(Point2D::new(i as f64 * 4.0, 50.0, Some("b"))));
+        }
+        assert!(!indexed.insert(Point2D::new(200.0, 0.0, Some("c"))));
+        assert!(indexed.find_by_data(&"c").is_none());
+        assert!(indexed.insert(Point2D::new(5.0, 5.0, None)));
+        assert_eq!(indexed.find_all_by_data(&"b").len(), 20);
+
+        assert!(indexed.delete(&Point2D::new(8.0, 50.0, Some("b"))));
+        assert!(!indexed.delete(&Point2D::new(8.0, 50.0, Some("b"))));
+        assert_eq!(indexed.find_all_by_data(&"b").len(), 19);
+        assert_eq!(indexed.delete_by_data(&"b"), 19);
+        assert_eq!(indexed.delete_by_data(&"b"), 0);
+        assert_eq!(indexed.tree().range_search_bbox(&boundary).len(), 2);
+    }
+
+    #[test]
+    fn test_octree_delete_with_tolerance() {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 10.0,
+            height: 10.0,
+            depth: 10.0,
+        };
+        let tree = Octree::new(&boundary, 4).unwrap().with_tolerance(1e-6);
+        let mut indexed = tree.with_payload_index();
+        indexed.insert(Point3D::new(0.1 + 0.2, 1.0, 1.0, Some(1)));
+        assert!(indexed.delete(&Point3D::new(0.3, 1.0, 1.0, Some(1))));
+        assert!(indexed.find_by_data(&1).is_none());
+        assert!(indexed.into_inner().export_sorted().next().is_none());
+    }
+}
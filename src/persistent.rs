@@ -0,0 +1,794 @@
+//! ## Persistent Trees
+//!
+//! This module provides persistent (immutable) versions of the quadtree and the Kd-tree.
+//! Instead of changing the tree in place, `insert` and `delete` return a new tree and leave
+//! the old one untouched. Only the nodes on the path to the change are copied; the rest of
+//! the nodes are shared between the two versions through `Arc` pointers.
+//!
+//! This makes keeping historical snapshots cheap (each version costs about one path of nodes),
+//! and a version can be cloned (in constant time) and handed to other threads, which can query
+//! it without locks while new versions are being built.
+//!
+//! Points are matched with `==` in `contains` and `delete`; there is no coordinate tolerance.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{EuclideanDistance, Point2D, Rectangle};
+//! use spart::persistent::PersistentQuadtree;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let empty = PersistentQuadtree::new(&boundary, 4).unwrap();
+//! let v1 = empty.insert(Point2D::new(10.0, 10.0, Some("a"))).unwrap();
+//! let v2 = v1.insert(Point2D::new(20.0, 20.0, Some("b"))).unwrap();
+//! let v3 = v2.delete(&Point2D::new(10.0, 10.0, Some("a"))).unwrap();
+//!
+//! // Every version stays valid.
+//! assert_eq!((empty.len(), v1.len(), v2.len(), v3.len()), (0, 1, 2, 1));
+//! let nearest = v3.knn_search::<EuclideanDistance>(&Point2D::new(0.0, 0.0, None), 1);
+//! assert_eq!(nearest[0].data, Some("b"));
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{DistanceMetric, Point2D, Rectangle};
+use crate::kdtree::KdPoint;
+use crate::knn::KnnResults;
+use crate::quadtree::{MAX_DEPTH, child_boundaries, quadrant_index};
+use std::sync::Arc;
+
+/// A node of a `PersistentQuadtree`.
+#[derive(Debug)]
+struct QuadNode<T> {
+    boundary: Rectangle,
+    /// The points of a leaf; empty for internal nodes.
+    points: Vec<Point2D<T>>,
+    /// The children of an internal node, in Morton order (NW, NE, SW, SE).
+    children: Option<[Arc<QuadNode<T>>; 4]>,
+    /// The number of points in the subtree.
+    len: usize,
+}
+
+impl<T: Clone + PartialEq> QuadNode<T> {
+    fn leaf(boundary: Rectangle, points: Vec<Point2D<T>>) -> Self {
+        QuadNode {
+            boundary,
+            len: points.len(),
+            points,
+            children: None,
+        }
+    }
+
+    /// Returns the index of the child a point inside the boundary is routed to.
+    fn child_index(&self, point: &Point2D<T>) -> usize {
+        quadrant_index(&self.boundary, point)
+    }
+
+    /// Returns the boundaries of the four children, in Morton order.
+    fn child_boundaries(&self) -> [Rectangle; 4] {
+        child_boundaries(&self.boundary)
+    }
+
+    /// Returns a copy of this node, at `depth` below the root, with `point` added, sharing the
+    /// untouched children.
+    fn with_point(&self, point: Point2D<T>, capacity: usize, depth: usize) -> Self {
+        match &self.children {
+            None if self.points.len() < capacity || depth >= MAX_DEPTH => {
+                let mut points = self.points.clone();
+                points.push(point);
+                QuadNode::leaf(self.boundary.clone(), points)
+            }
+            None => {
+                let mut points = self.points.clone();
+                points.push(point);
+                Self::build(self.boundary.clone(), points, capacity, depth)
+            }
+            Some(children) => {
+                let index = self.child_index(&point);
+                let mut children = children.clone();
+                children[index] = Arc::new(children[index].with_point(point, capacity, depth + 1));
+                QuadNode {
+                    boundary: self.boundary.clone(),
+                    points: Vec::new(),
+                    children: Some(children),
+                    len: self.len + 1,
+                }
+            }
+        }
+    }
+
+    /// Builds a subtree at `depth` below the root holding `points`, subdividing until every
+    /// leaf is within capacity or at `MAX_DEPTH`.
+    fn build(boundary: Rectangle, points: Vec<Point2D<T>>, capacity: usize, depth: usize) -> Self {
+        if points.len() <= capacity || depth >= MAX_DEPTH {
+            return QuadNode::leaf(boundary, points);
+        }
+        let mut node = QuadNode::leaf(boundary, Vec::new());
+        let len = points.len();
+        let mut parts: [Vec<Point2D<T>>; 4] = Default::default();
+        for point in points {
+            parts[node.child_index(&point)].push(point);
+        }
+        let [nw, ne, sw, se] = node.child_boundaries();
+        let [p_nw, p_ne, p_sw, p_se] = parts;
+        node.children = Some([
+            Arc::new(Self::build(nw, p_nw, capacity, depth + 1)),
+            Arc::new(Self::build(ne, p_ne, capacity, depth + 1)),
+            Arc::new(Self::build(sw, p_sw, capacity, depth + 1)),
+            Arc::new(Self::build(se, p_se, capacity, depth + 1)),
+        ]);
+        node.len = len;
+        node
+    }
+
+    /// Returns a copy of this node without one point equal to `point`, or `None` if there is
+    /// no such point. Children that fit into a single leaf afterwards are merged into it.
+    fn without_point(&self, point: &Point2D<T>, capacity: usize) -> Option<Self> {
+        match &self.children {
+            None => {
+                let position = self.points.iter().position(|p| p == point)?;
+                let mut points = self.points.clone();
+                points.remove(position);
+                Some(QuadNode::leaf(self.boundary.clone(), points))
+            }
+            Some(children) => {
+                let index = self.child_index(point);
+                let child = children[index].without_point(point, capacity)?;
+                if self.len - 1 <= capacity {
+                    let mut points = Vec::with_capacity(self.len - 1);
+                    for (i, c) in children.iter().enumerate() {
+                        if i == index {
+                            child.collect_into(&mut points);
+                        } else {
+                            c.collect_into(&mut points);
+                        }
+                    }
+                    return Some(QuadNode::leaf(self.boundary.clone(), points));
+                }
+                let mut children = children.clone();
+                children[index] = Arc::new(child);
+                Some(QuadNode {
+                    boundary: self.boundary.clone(),
+                    points: Vec::new(),
+                    children: Some(children),
+                    len: self.len - 1,
+                })
+            }
+        }
+    }
+
+    fn collect_into(&self, out: &mut Vec<Point2D<T>>) {
+        out.extend(self.points.iter().cloned());
+        for child in self.children.iter().flatten() {
+            child.collect_into(out);
+        }
+    }
+
+    fn contains(&self, point: &Point2D<T>) -> bool {
+        match &self.children {
+            None => self.points.contains(point),
+            Some(children) => children[self.child_index(point)].contains(point),
+        }
+    }
+
+    fn range_search_bbox(&self, query: &Rectangle, found: &mut Vec<Point2D<T>>) {
+        if !self.boundary.intersects(query) {
+            return;
+        }
+        found.extend(self.points.iter().filter(|p| query.contains(p)).cloned());
+        for child in self.children.iter().flatten() {
+            child.range_search_bbox(query, found);
+        }
+    }
+
+    fn knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        results: &mut KnnResults<Point2D<T>>,
+    ) {
+        for point in &self.points {
+            let dist_sq = M::distance_sq(point, target);
+            if results.accepts(dist_sq) {
+                results.push(dist_sq, point.clone());
+            }
+        }
+        for child in self.children.iter().flatten() {
+            if child.boundary.min_distance(target).powi(2) <= results.bound() {
+                child.knn_search::<M>(target, results);
+            }
+        }
+    }
+}
+
+/// A persistent quadtree for 2D points.
+///
+/// Cloning the tree is cheap: the clone shares all nodes with the original. See the module
+/// documentation for how versions share structure.
+///
+/// # Type Parameters
+///
+/// * `T`: The type of additional data stored in each point.
+#[derive(Debug)]
+pub struct PersistentQuadtree<T> {
+    root: Arc<QuadNode<T>>,
+    capacity: usize,
+}
+
+impl<T> Clone for PersistentQuadtree<T> {
+    fn clone(&self) -> Self {
+        PersistentQuadtree {
+            root: Arc::clone(&self.root),
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl<T: Clone + PartialEq> PersistentQuadtree<T> {
+    /// Creates a new, empty tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The rectangular region covered by the tree.
+    /// * `capacity` - The maximum number of points a leaf can hold before subdividing.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn new(boundary: &Rectangle, capacity: usize) -> Result<Self, SpartError> {
+        if capacity == 0 {
+            return Err(SpartError::InvalidCapacity { capacity });
+        }
+        Ok(PersistentQuadtree {
+            root: Arc::new(QuadNode::leaf(boundary.clone(), Vec::new())),
+            capacity,
+        })
+    }
+
+    /// Returns the region covered by the tree.
+    pub fn boundary(&self) -> &Rectangle {
+        &self.root.boundary
+    }
+
+    /// Returns the maximum number of points a leaf can hold before subdividing.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of points in the tree.
+    pub fn len(&self) -> usize {
+        self.root.len
+    }
+
+    /// Returns `true` if the tree holds no points.
+    pub fn is_empty(&self) -> bool {
+        self.root.len == 0
+    }
+
+    /// Returns a new version of the tree with `point` added.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    ///
+    /// # Returns
+    ///
+    /// The new version, or `None` if the point lies outside the tree's boundary.
+    pub fn insert(&self, point: Point2D<T>) -> Option<Self> {
+        if !self.root.boundary.contains(&point) {
+            return None;
+        }
+        Some(PersistentQuadtree {
+            root: Arc::new(self.root.with_point(point, self.capacity, 0)),
+            capacity: self.capacity,
+        })
+    }
+
+    /// Returns a new version of the tree without one point equal to `point`.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to delete.
+    ///
+    /// # Returns
+    ///
+    /// The new version, or `None` if the tree holds no such point.
+    pub fn delete(&self, point: &Point2D<T>) -> Option<Self> {
+        if !self.root.boundary.contains(point) {
+            return None;
+        }
+        Some(PersistentQuadtree {
+            root: Arc::new(self.root.without_point(point, self.capacity)?),
+            capacity: self.capacity,
+        })
+    }
+
+    /// Returns `true` if the tree holds a point equal to `point`.
+    pub fn contains(&self, point: &Point2D<T>) -> bool {
+        self.root.boundary.contains(point) && self.root.contains(point)
+    }
+
+    /// Returns all points inside the query rectangle (boundary inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    pub fn range_search_bbox(&self, query: &Rectangle) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        self.root.range_search_bbox(query, &mut found);
+        found
+    }
+
+    /// Performs a k-nearest neighbor search for the target point.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point for which to find the k nearest neighbors.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the k nearest points, ordered from nearest to farthest.
+    ///
+    /// # Note
+    ///
+    /// As for `Quadtree::knn_search`, the pruning logic is based on Euclidean distance.
+    pub fn knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+    ) -> Vec<Point2D<T>> {
+        if k == 0 || target.x.is_nan() || target.y.is_nan() {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.root.knn_search::<M>(target, &mut results);
+        results.into_sorted_vec()
+    }
+}
+
+/// A node of a `PersistentKdTree`.
+#[derive(Debug)]
+struct KdNode<P> {
+    point: P,
+    left: Option<Arc<KdNode<P>>>,
+    right: Option<Arc<KdNode<P>>>,
+}
+
+/// Returns the coordinate of a point along an axis below its dimension.
+fn axis_coord<P: KdPoint>(point: &P, axis: usize) -> f64 {
+    point
+        .coord(axis)
+        .unwrap_or_else(|_| unreachable!("axis computed from dims, must be valid"))
+}
+
+impl<P: KdPoint> KdNode<P> {
+    /// Returns a copy of the subtree with `point` added along the path it is routed to.
+    fn with_point(node: &Option<Arc<KdNode<P>>>, point: P, depth: usize, k: usize) -> Arc<Self> {
+        let Some(n) = node else {
+            return Arc::new(KdNode {
+                point,
+                left: None,
+                right: None,
+            });
+        };
+        let axis = depth % k;
+        let (left, right) = if axis_coord(&point, axis) < axis_coord(&n.point, axis) {
+            (
+                Some(Self::with_point(&n.left, point, depth + 1, k)),
+                n.right.clone(),
+            )
+        } else {
+            (
+                n.left.clone(),
+                Some(Self::with_point(&n.right, point, depth + 1, k)),
+            )
+        };
+        Arc::new(KdNode {
+            point: n.point.clone(),
+            left,
+            right,
+        })
+    }
+
+    /// Returns a copy of the subtree without one point equal to `point`, or `None` if there
+    /// is no such point. The subtree below the deleted node is rebuilt from its points.
+    fn without_point(
+        node: &Option<Arc<KdNode<P>>>,
+        point: &P,
+        depth: usize,
+        k: usize,
+    ) -> Option<Option<Arc<Self>>> {
+        let n = node.as_ref()?;
+        if n.point == *point {
+            let mut rest = Vec::new();
+            Self::collect_into(&n.left, &mut rest);
+            Self::collect_into(&n.right, &mut rest);
+            return Some(Self::build(rest, depth, k));
+        }
+        let axis = depth % k;
+        let p_coord = axis_coord(point, axis);
+        let n_coord = axis_coord(&n.point, axis);
+        // Points equal on the axis are inserted to the right, but a rebuild may place them on
+        // the left, so both sides are searched for them.
+        if p_coord <= n_coord {
+            if let Some(left) = Self::without_point(&n.left, point, depth + 1, k) {
+                return Some(Some(Arc::new(KdNode {
+                    point: n.point.clone(),
+                    left,
+                    right: n.right.clone(),
+                })));
+            }
+        }
+        if p_coord >= n_coord {
+            if let Some(right) = Self::without_point(&n.right, point, depth + 1, k) {
+                return Some(Some(Arc::new(KdNode {
+                    point: n.point.clone(),
+                    left: n.left.clone(),
+                    right,
+                })));
+            }
+        }
+        None
+    }
+
+    /// Builds a balanced subtree from `points` with median splits.
+    fn build(mut points: Vec<P>, depth: usize, k: usize) -> Option<Arc<Self>> {
+        if points.is_empty() {
+            return None;
+        }
+        let axis = depth % k;
+        points.sort_by(|a, b| axis_coord(a, axis).total_cmp(&axis_coord(b, axis)));
+        let mid = points.len() / 2;
+        let right = points.split_off(mid + 1);
+        let median = points.pop()?;
+        Some(Arc::new(KdNode {
+            point: median,
+            left: Self::build(points, depth + 1, k),
+            right: Self::build(right, depth + 1, k),
+        }))
+    }
+
+    fn collect_into(node: &Option<Arc<KdNode<P>>>, out: &mut Vec<P>) {
+        if let Some(n) = node {
+            out.push(n.point.clone());
+            Self::collect_into(&n.left, out);
+            Self::collect_into(&n.right, out);
+        }
+    }
+
+    fn contains(node: &Option<Arc<KdNode<P>>>, point: &P, depth: usize, k: usize) -> bool {
+        let Some(n) = node else {
+            return false;
+        };
+        if n.point == *point {
+            return true;
+        }
+        let axis = depth % k;
+        let p_coord = axis_coord(point, axis);
+        let n_coord = axis_coord(&n.point, axis);
+        (p_coord <= n_coord && Self::contains(&n.left, point, depth + 1, k))
+            || (p_coord >= n_coord && Self::contains(&n.right, point, depth + 1, k))
+    }
+
+    fn knn_search<M: DistanceMetric<P>>(
+        node: &Option<Arc<KdNode<P>>>,
+        target: &P,
+        depth: usize,
+        results: &mut KnnResults<P>,
+    ) {
+        let Some(n) = node else {
+            return;
+        };
+        let dist_sq = M::distance_sq(target, &n.point);
+        if results.accepts(dist_sq) {
+            results.push(dist_sq, n.point.clone());
+        }
+        let axis = depth % target.dims();
+        let diff = axis_coord(target, axis) - axis_coord(&n.point, axis);
+        let (first, second) = if diff < 0.0 {
+            (&n.left, &n.right)
+        } else {
+            (&n.right, &n.left)
+        };
+        Self::knn_search::<M>(first, target, depth + 1, results);
+        if !results.is_full() || diff * diff < results.bound() {
+            Self::knn_search::<M>(second, target, depth + 1, results);
+        }
+    }
+
+    fn range_search<M: DistanceMetric<P>>(
+        node: &Option<Arc<KdNode<P>>>,
+        center: &P,
+        radius: f64,
+        depth: usize,
+        found: &mut Vec<P>,
+    ) {
+        let Some(n) = node else {
+            return;
+        };
+        if M::distance_sq(center, &n.point) <= radius * radius {
+            found.push(n.point.clone());
+        }
+        let axis = depth % center.dims();
+        let center_coord = axis_coord(center, axis);
+        let node_coord = axis_coord(&n.point, axis);
+        if center_coord - radius <= node_coord {
+            Self::range_search::<M>(&n.left, center, radius, depth + 1, found);
+        }
+        if center_coord + radius >= node_coord {
+            Self::range_search::<M>(&n.right, center, radius, depth + 1, found);
+        }
+    }
+}
+
+/// A persistent Kd-tree for points implementing `KdPoint`.
+///
+/// Like `KdTree`, the dimension of the tree is set by the first point inserted. Cloning the
+/// tree is cheap: the clone shares all nodes with the original.
+#[derive(Debug)]
+pub struct PersistentKdTree<P> {
+    root: Option<Arc<KdNode<P>>>,
+    k: Option<usize>,
+    len: usize,
+}
+
+impl<P> Clone for PersistentKdTree<P> {
+    fn clone(&self) -> Self {
+        PersistentKdTree {
+            root: self.root.clone(),
+            k: self.k,
+            len: self.len,
+        }
+    }
+}
+
+impl<P: KdPoint> Default for PersistentKdTree<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: KdPoint> PersistentKdTree<P> {
+    /// Creates a new, empty tree.
+    pub fn new() -> Self {
+        PersistentKdTree {
+            root: None,
+            k: None,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of points in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree holds no points.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the dimension of the tree, or `None` if it is empty.
+    pub fn dimension(&self) -> Option<usize> {
+        self.k
+    }
+
+    /// Returns a new version of the tree with `point` added.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::DimensionMismatch` if the point's dimension does not match the
+    /// dimension of the tree.
+    pub fn insert(&self, point: P) -> Result<Self, SpartError> {
+        let k = self.k.unwrap_or_else(|| point.dims());
+        if point.dims() != k {
+            return Err(SpartError::DimensionMismatch {
+                expected: k,
+                actual: point.dims(),
+            });
+        }
+        Ok(PersistentKdTree {
+            root: Some(KdNode::with_point(&self.root, point, 0, k)),
+            k: Some(k),
+            len: self.len + 1,
+        })
+    }
+
+    /// Returns a new version of the tree without one point equal to `point`.
+    ///
+    /// The subtree below the deleted point is rebuilt with median splits, as with
+    /// `DeleteStrategy::Rebuild` in `KdTree`.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to delete.
+    ///
+    /// # Returns
+    ///
+    /// The new version, or `None` if the tree holds no such point.
+    pub fn delete(&self, point: &P) -> Option<Self> {
+        let k = self.k.filter(|&k| k == point.dims())?;
+        let root = KdNode::without_point(&self.root, point, 0, k)?;
+        Some(PersistentKdTree {
+            k: root.as_ref().map(|_| k),
+            root,
+            len: self.len - 1,
+        })
+    }
+
+    /// Returns `true` if the tree holds a point equal to `point`.
+    pub fn contains(&self, point: &P) -> bool {
+        match self.k {
+            Some(k) if k == point.dims() => KdNode::contains(&self.root, point, 0, k),
+            _ => false,
+        }
+    }
+
+    /// Performs a k-nearest neighbor search for the given target point.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k_neighbors` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the nearest points, ordered from nearest to farthest. It is empty if
+    /// `target` does not match the tree's dimension.
+    pub fn knn_search<M: DistanceMetric<P>>(&self, target: &P, k_neighbors: usize) -> Vec<P> {
+        if k_neighbors == 0 || self.k != Some(target.dims()) {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k_neighbors);
+        KdNode::knn_search::<M>(&self.root, target, 0, &mut results);
+        results.into_sorted_vec()
+    }
+
+    /// Performs a range search, returning all points within the specified radius of the center.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points within the specified radius. It is empty if `center` does not match
+    /// the tree's dimension.
+    pub fn range_search<M: DistanceMetric<P>>(&self, center: &P, radius: f64) -> Vec<P> {
+        let mut found = Vec::new();
+        if radius >= 0.0 && self.k == Some(center.dims()) {
+            KdNode::range_search::<M>(&self.root, center, radius, 0, &mut found);
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{EuclideanDistance, Point3D};
+    use crate::kdtree::KdTree;
+    use crate::quadtree::Quadtree;
+    use crate::random::{RandomSource, SplitMix64};
+
+    #[test]
+    fn test_quadtree_versions_are_independent_and_share_nodes() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut rng = SplitMix64::new(7);
+        let mut tree = Quadtree::new(&boundary, 4).unwrap();
+        let mut versions = vec![PersistentQuadtree::new(&boundary, 4).unwrap()];
+        let mut points = Vec::new();
+        for i in 0..300 {
+            let point = Point2D::new(rng.next_f64() * 100.0, rng.next_f64() * 100.0, Some(i));
+            tree.insert(point.clone());
+            versions.push(versions.last().unwrap().insert(point.clone()).unwrap());
+            points.push(point);
+        }
+        for point in points.iter().step_by(3) {
+            assert!(tree.delete(point));
+            versions.push(versions.last().unwrap().delete(point).unwrap());
+        }
+        let latest = versions.last().unwrap();
+        assert!(latest.delete(&points[0]).is_none());
+
+        // The version after the i-th insertion holds exactly the first i points.
+        let snapshot = &versions[150];
+        assert_eq!(snapshot.len(), 150);
+        assert!(points[..150].iter().all(|p| snapshot.contains(p)));
+        assert!(!snapshot.contains(&points[150]));
+
+        let target = Point2D::new(50.0, 50.0, None);
+        let expected = tree.knn_search::<EuclideanDistance>(&target, 10);
+        assert_eq!(
+            latest.knn_search::<EuclideanDistance>(&target, 10),
+            expected
+        );
+        let query = Rectangle {
+            x: 20.0,
+            y: 30.0,
+            width: 25.0,
+            height: 40.0,
+        };
+        let mut found = latest.range_search_bbox(&query);
+        let mut expected = tree.range_search_bbox(&query);
+        found.sort_by_key(|p| p.data);
+        expected.sort_by_key(|p| p.data);
+        assert_eq!(found, expected);
+
+        // Inserting copies one path, so most of the tree is shared with the previous version.
+        let next = latest.insert(Point2D::new(1.0, 1.0, Some(-1))).unwrap();
+        let (Some(old), Some(new)) = (&latest.root.children, &next.root.children) else {
+            panic!("the root is subdivided");
+        };
+        assert!(!Arc::ptr_eq(&old[0], &new[0]));
+        assert!((1..4).all(|i| Arc::ptr_eq(&old[i], &new[i])));
+    }
+
+    #[test]
+    fn test_quadtree_duplicate_points_stop_subdividing_at_max_depth() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let point = Point2D::new(3.0, 7.0, Some(1));
+        let mut tree = PersistentQuadtree::new(&boundary, 2).unwrap();
+        for _ in 0..3 {
+            tree = tree.insert(point.clone()).unwrap();
+        }
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.range_search_bbox(&boundary).len(), 3);
+        let smaller = tree.delete(&point).unwrap();
+        assert_eq!((smaller.len(), tree.len()), (2, 3));
+        assert!(smaller.contains(&point));
+    }
+
+    #[test]
+    fn test_kdtree_snapshots_are_shared_across_threads() {
+        let mut rng = SplitMix64::new(11);
+        let mut reference = KdTree::new();
+        let mut tree = PersistentKdTree::new();
+        let mut points = Vec::new();
+        for i in 0..200 {
+            let point = Point3D::new(rng.next_f64(), rng.next_f64(), rng.next_f64(), Some(i));
+            reference.insert(point.clone()).unwrap();
+            tree = tree.insert(point.clone()).unwrap();
+            points.push(point);
+        }
+        let snapshot = tree.clone();
+        for point in points.iter().take(100) {
+            reference.delete(point);
+            tree = tree.delete(point).unwrap();
+        }
+        assert_eq!((snapshot.len(), tree.len()), (200, 100));
+
+        let target = Point3D::new(0.5, 0.5, 0.5, None);
+        let query = target.clone();
+        let handle = std::thread::spawn(move || {
+            snapshot
+                .range_search::<EuclideanDistance>(&query, 0.3)
+                .len()
+        });
+        let in_range = points
+            .iter()
+            .filter(|p| EuclideanDistance::distance_sq(*p, &target) <= 0.09)
+            .count();
+        assert_eq!(handle.join().unwrap(), in_range);
+        assert_eq!(
+            tree.knn_search::<EuclideanDistance>(&target, 8),
+            reference.knn_search::<EuclideanDistance>(&target, 8)
+        );
+        assert!(points[..100].iter().all(|p| !tree.contains(p)));
+        assert!(points[100..].iter().all(|p| tree.contains(p)));
+    }
+}
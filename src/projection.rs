@@ -0,0 +1,343 @@
+//! ## Map Projections
+//!
+//! This module provides conversions between geographic coordinates (longitude and latitude in
+//! degrees) and planar coordinates, so that geographic data can be indexed by the planar trees,
+//! whose distances and boxes assume a flat plane.
+//!
+//! A map projection implements the `Projection` trait. `WebMercator` is the spherical Mercator
+//! projection used by web maps (EPSG:3857), in meters. Other projections (e.g., a UTM zone or an
+//! adapter over an external projection library) can be added by implementing the trait.
+//!
+//! `ProjectedQuadtree` wraps a `Quadtree` over projected coordinates and applies the projection
+//! transparently: points and queries are given in longitude (`x`) and latitude (`y`), and
+//! results are returned with the exact coordinates they were inserted with.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geo::GeoBBox;
+//! use spart::geometry::Point2D;
+//! use spart::projection::{ProjectedQuadtree, WebMercator};
+//!
+//! let mut tree = ProjectedQuadtree::new(WebMercator, 4).unwrap();
+//! tree.insert(Point2D::new(2.35, 48.86, Some("Paris")));
+//! tree.insert(Point2D::new(-0.13, 51.51, Some("London")));
+//! tree.insert(Point2D::new(13.40, 52.52, Some("Berlin")));
+//!
+//! let nearest = tree.knn_search(&Point2D::new(1.0, 50.0, None), 1);
+//! assert_eq!(nearest[0].data, Some("Paris"));
+//!
+//! let west_europe = GeoBBox::new(-5.0, 45.0, 5.0, 55.0).unwrap();
+//! assert_eq!(tree.range_search_geo_bbox(&west_europe).len(), 2);
+//! ```
+
+use crate::errors::SpartError;
+use crate::geo::{GeoBBox, haversine_distance};
+use crate::geometry::{EuclideanDistance, Point2D, Rectangle, TileScheme};
+use crate::predicates::covering_extent;
+use crate::quadtree::Quadtree;
+
+/// A map projection between geographic and planar coordinates.
+pub trait Projection {
+    /// Returns the planar rectangle that contains the projections of all valid longitudes and
+    /// latitudes.
+    fn bounds(&self) -> Rectangle;
+
+    /// Projects a longitude and latitude (in degrees) to planar `(x, y)` coordinates.
+    fn project(&self, lon: f64, lat: f64) -> (f64, f64);
+
+    /// Converts planar `(x, y)` coordinates back to a longitude and latitude (in degrees).
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64);
+
+    /// Returns a copy of a point (given as longitude `x` and latitude `y`) with projected
+    /// coordinates.
+    fn project_point<T: Clone>(&self, point: &Point2D<T>) -> Point2D<T> {
+        let (x, y) = self.project(point.x, point.y);
+        Point2D::new(x, y, point.data.clone())
+    }
+
+    /// Returns a copy of a projected point with its longitude as `x` and latitude as `y`.
+    fn unproject_point<T: Clone>(&self, point: &Point2D<T>) -> Point2D<T> {
+        let (lon, lat) = self.unproject(point.x, point.y);
+        Point2D::new(lon, lat, point.data.clone())
+    }
+}
+
+/// The spherical Mercator projection of web maps (EPSG:3857), in meters.
+///
+/// Latitudes are clamped to ±`WebMercator::MAX_LATITUDE`, where the projection reaches the
+/// edges of its square extent, so points closer to a pole are projected onto those edges.
+/// Projected coordinates line up with `TileScheme::web_mercator`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WebMercator;
+
+impl WebMercator {
+    /// The radius, in meters, of the sphere the projection is defined on.
+    pub const RADIUS: f64 = 6_378_137.0;
+
+    /// The largest latitude, in degrees, inside the projection's square extent.
+    pub const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+}
+
+impl Projection for WebMercator {
+    fn bounds(&self) -> Rectangle {
+        let half = TileScheme::WEB_MERCATOR_HALF_EXTENT;
+        Rectangle {
+            x: -half,
+            y: -half,
+            width: 2.0 * half,
+            height: 2.0 * half,
+        }
+    }
+
+    fn project(&self, lon: f64, lat: f64) -> (f64, f64) {
+        let half = TileScheme::WEB_MERCATOR_HALF_EXTENT;
+        let lat = lat.clamp(-Self::MAX_LATITUDE, Self::MAX_LATITUDE);
+        let x = Self::RADIUS * lon.to_radians();
+        let y = Self::RADIUS
+            * (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.0)
+                .tan()
+                .ln();
+        // Rounding can put the edges of the extent a hair outside of it.
+        (x.clamp(-half, half), y.clamp(-half, half))
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        let lon = (x / Self::RADIUS).to_degrees();
+        let lat =
+            (2.0 * (y / Self::RADIUS).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees();
+        (lon, lat)
+    }
+}
+
+/// The payload stored in the planar tree: the original longitude and latitude, and the data.
+type GeoPayload<T> = (f64, f64, Option<T>);
+
+/// A quadtree over geographic points that indexes them in projected coordinates.
+///
+/// Points and queries are given as longitude `x` and latitude `y` in degrees. Distances in
+/// `knn_search` are measured in the projected plane, which ranks points the same as on the
+/// sphere only over small areas (Web-Mercator distances are stretched by `1 / cos(latitude)`);
+/// `within_radius` measures great-circle distances instead.
+#[derive(Debug, Clone)]
+pub struct ProjectedQuadtree<T: Clone + PartialEq + std::fmt::Debug, P: Projection = WebMercator> {
+    projection: P,
+    tree: Quadtree<GeoPayload<T>>,
+    len: usize,
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug, P: Projection> ProjectedQuadtree<T, P> {
+    /// Creates an empty tree covering the bounds of a projection.
+    ///
+    /// # Arguments
+    ///
+    /// * `projection` - The projection from geographic to planar coordinates.
+    /// * `capacity` - The maximum number of points per node before it subdivides.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn new(projection: P, capacity: usize) -> Result<Self, SpartError> {
+        let tree = Quadtree::new(&projection.bounds(), capacity)?;
+        Ok(ProjectedQuadtree {
+            projection,
+            tree,
+            len: 0,
+        })
+    }
+
+    /// Returns the projection of the tree.
+    pub fn projection(&self) -> &P {
+        &self.projection
+    }
+
+    /// Returns the number of points in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree has no points.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn to_planar(&self, point: &Point2D<T>) -> Point2D<GeoPayload<T>> {
+        let (x, y) = self.projection.project(point.x, point.y);
+        Point2D::new(x, y, Some((point.x, point.y, point.data.clone())))
+    }
+
+    fn to_geo(point: Point2D<GeoPayload<T>>) -> Point2D<T> {
+        let (lon, lat, data) = point
+            .data
+            .unwrap_or_else(|| unreachable!("projected points carry their coordinates"));
+        Point2D::new(lon, lat, data)
+    }
+
+    /// Inserts a point.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point, with its longitude as `x` and latitude as `y` in degrees.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was inserted, and `false` if its longitude is not between -180 and
+    /// 180 or its latitude is not between -90 and 90.
+    pub fn insert(&mut self, point: Point2D<T>) -> bool {
+        if !((-180.0..=180.0).contains(&point.x) && (-90.0..=90.0).contains(&point.y)) {
+            return false;
+        }
+        let inserted = self.tree.insert(self.to_planar(&point));
+        if inserted {
+            self.len += 1;
+        }
+        inserted
+    }
+
+    /// Inserts multiple points, skipping those that `insert` would reject.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to insert.
+    pub fn insert_bulk(&mut self, points: &[Point2D<T>]) {
+        for point in points {
+            self.insert(point.clone());
+        }
+    }
+
+    /// Deletes one point equal to `point`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and deleted.
+    pub fn delete(&mut self, point: &Point2D<T>) -> bool {
+        let deleted = self.tree.delete(&self.to_planar(point));
+        if deleted {
+            self.len -= 1;
+        }
+        deleted
+    }
+
+    /// Finds the `k` points nearest to the target in the projected plane.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The query point, with its longitude as `x` and latitude as `y`.
+    /// * `k` - The number of neighbors to find.
+    ///
+    /// # Returns
+    ///
+    /// Up to `k` points, sorted from the nearest to the farthest.
+    pub fn knn_search(&self, target: &Point2D<T>, k: usize) -> Vec<Point2D<T>> {
+        let (x, y) = self.projection.project(target.x, target.y);
+        self.tree
+            .knn_search::<EuclideanDistance>(&Point2D::new(x, y, None), k)
+            .into_iter()
+            .map(Self::to_geo)
+            .collect()
+    }
+
+    /// Finds all points inside a geographic bounding box (edges included).
+    ///
+    /// # Arguments
+    ///
+    /// * `bbox` - The box to search, which may cross the antimeridian.
+    pub fn range_search_geo_bbox(&self, bbox: &GeoBBox) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        for rect in bbox.to_rectangles() {
+            // Projections keep the order of longitudes and latitudes, so a box projects onto
+            // the box between its projected corners.
+            let (x1, y1) = self.projection.project(rect.x, rect.y);
+            let (x2, y2) = self
+                .projection
+                .project(rect.x + rect.width, rect.y + rect.height);
+            let query = Rectangle {
+                x: x1,
+                y: y1,
+                width: covering_extent(x1, x2),
+                height: covering_extent(y1, y2),
+            };
+            found.extend(
+                self.tree
+                    .range_search_bbox(&query)
+                    .into_iter()
+                    .map(Self::to_geo),
+            );
+        }
+        // Rounding in the projection can pull in points just outside of the box.
+        found.retain(|p| bbox.contains(p));
+        found
+    }
+
+    /// Finds all points within a great-circle distance of a center point.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center, with its longitude as `x` and latitude as `y`.
+    /// * `radius` - The search radius in meters.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if `center` is not a valid longitude and
+    /// latitude or `radius` is negative or not finite.
+    pub fn within_radius(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> Result<Vec<Point2D<T>>, SpartError> {
+        let bbox = GeoBBox::around(center, radius)?;
+        let mut found = self.range_search_geo_bbox(&bbox);
+        found.retain(|p| haversine_distance(center, p) <= radius);
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_mercator_round_trip() {
+        let mercator = WebMercator;
+        for &(lon, lat) in &[(0.0, 0.0), (-180.0, 85.0), (179.9, -60.5), (13.4, 52.52)] {
+            let (x, y) = mercator.project(lon, lat);
+            let (lon2, lat2) = mercator.unproject(x, y);
+            assert!((lon - lon2).abs() < 1e-9 && (lat - lat2).abs() < 1e-9);
+        }
+        let half = TileScheme::WEB_MERCATOR_HALF_EXTENT;
+        let (x, y) = mercator.project(180.0, 90.0);
+        assert!(x <= half && y <= half && half - y < 1e-6);
+        let (x, y) = mercator.project(-180.0, -90.0);
+        assert!(x >= -half && y >= -half && y + half < 1e-6);
+    }
+
+    #[test]
+    fn test_projected_tree_returns_original_coordinates() {
+        let mut tree = ProjectedQuadtree::new(WebMercator, 2).unwrap();
+        let points: Vec<Point2D<u32>> = (0..50)
+            .map(|i| Point2D::new(-175.0 + i as f64 * 7.1, -80.0 + i as f64 * 3.3, Some(i)))
+            .collect();
+        tree.insert_bulk(&points);
+        assert!(!tree.insert(Point2D::new(0.0, 91.0, Some(99))));
+        assert_eq!(tree.len(), points.len());
+
+        let bbox = GeoBBox::new(170.0, -90.0, -170.0, 90.0).unwrap();
+        let mut found = tree.range_search_geo_bbox(&bbox);
+        found.sort_by_key(|p| p.data);
+        let expected: Vec<_> = points
+            .iter()
+            .filter(|p| bbox.contains(*p))
+            .cloned()
+            .collect();
+        assert_eq!(found, expected);
+
+        let center = points[20].clone();
+        let near = tree.within_radius(&center, 1_000_000.0).unwrap();
+        assert!(near.contains(&center));
+        assert_eq!(tree.knn_search(&center, 1), vec![center.clone()]);
+
+        assert!(tree.delete(&center));
+        assert!(!tree.delete(&center));
+        assert_eq!(tree.len(), points.len() - 1);
+    }
+}
@@ -0,0 +1,274 @@
+//! ## Point Pyramids
+//!
+//! This module provides `PointPyramid`, a multi-resolution index for drawing large 2D point
+//! sets at any zoom level. It keeps several levels of the same points: level 0 holds all of
+//! them, and every following level holds a random sample of the level below it, thinned by a
+//! constant factor (4 by default, which matches the number of map tiles covering the same area
+//! one zoom level deeper). Each level is a `Quadtree`.
+//!
+//! `PointPyramid::points_for_viewport` returns the points of the most detailed level that has
+//! at most a given number of points in the viewport, so zoomed-out views draw a representative
+//! sample and zoomed-in views draw every point, with a bounded amount of work per frame.
+//!
+//! Points are sampled uniformly at random by default. To keep important points visible at
+//! coarse levels (e.g., the largest cities on a map), insert them with
+//! `PointPyramid::insert_at_level`.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::pyramid::PointPyramid;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let mut pyramid = PointPyramid::new(&boundary, 16, 4).unwrap().with_seed(7);
+//! for i in 0..10_000 {
+//!     pyramid.insert(Point2D::new((i % 100) as f64, (i / 100) as f64, Some(i)));
+//! }
+//!
+//! // The whole map: a sample of at most 1,000 points.
+//! let overview = pyramid.points_for_viewport(&boundary, 1_000);
+//! assert!(!overview.is_empty() && overview.len() <= 1_000);
+//!
+//! // A small window: all of its points.
+//! let window = Rectangle { x: 10.0, y: 10.0, width: 9.0, height: 9.0 };
+//! assert_eq!(pyramid.points_for_viewport(&window, 1_000).len(), 100);
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{Point2D, Rectangle};
+use crate::quadtree::Quadtree;
+use crate::random::{RandomSource, SplitMix64};
+
+/// A stack of progressively thinned quadtrees over the same 2D points.
+#[derive(Debug, Clone)]
+pub struct PointPyramid<T: Clone + PartialEq + std::fmt::Debug> {
+    levels: Vec<Quadtree<T>>,
+    level_lens: Vec<usize>,
+    thinning: u32,
+    rng: SplitMix64,
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> PointPyramid<T> {
+    /// Creates an empty pyramid.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The region covered by every level.
+    /// * `capacity` - The maximum number of points per quadtree node before it subdivides.
+    /// * `levels` - The number of levels, including the full-resolution level 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` or `levels` is zero.
+    pub fn new(boundary: &Rectangle, capacity: usize, levels: usize) -> Result<Self, SpartError> {
+        if levels == 0 {
+            return Err(SpartError::InvalidCapacity { capacity: levels });
+        }
+        let levels = (0..levels)
+            .map(|_| Quadtree::new(boundary, capacity))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PointPyramid {
+            level_lens: vec![0; levels.len()],
+            levels,
+            thinning: 4,
+            rng: SplitMix64::new(0),
+        })
+    }
+
+    /// Sets the factor by which each level is thinned relative to the level below it (at least
+    /// 2). It applies to the points inserted afterwards.
+    pub fn with_thinning(mut self, factor: u32) -> Self {
+        self.thinning = factor.max(2);
+        self
+    }
+
+    /// Sets the seed of the random sampling. It applies to the points inserted afterwards.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SplitMix64::new(seed);
+        self
+    }
+
+    /// Returns the number of levels.
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the quadtree of a level, or `None` if the level does not exist.
+    pub fn level(&self, level: usize) -> Option<&Quadtree<T>> {
+        self.levels.get(level)
+    }
+
+    /// Returns the number of points in a level (zero if the level does not exist).
+    pub fn level_len(&self, level: usize) -> usize {
+        self.level_lens.get(level).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of points in the pyramid, i.e., in level 0.
+    pub fn len(&self) -> usize {
+        self.level_lens[0]
+    }
+
+    /// Returns `true` if the pyramid has no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts a point into level 0 and, at random, into the coarser levels: it reaches each
+    /// level with a chance of one in the thinning factor, given that it reached the level
+    /// below.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was inserted, and `false` if it lies outside of the boundary.
+    pub fn insert(&mut self, point: Point2D<T>) -> bool {
+        let mut top = 0;
+        while top + 1 < self.levels.len() && self.rng.next_below(self.thinning as usize) == 0 {
+            top += 1;
+        }
+        self.insert_at_level(point, top)
+    }
+
+    /// Inserts a point into levels 0 through `top`, e.g., to keep an important point visible
+    /// when zoomed out.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    /// * `top` - The coarsest level to insert the point into. Levels past the last one are
+    ///   clamped to it.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was inserted, and `false` if it lies outside of the boundary.
+    pub fn insert_at_level(&mut self, point: Point2D<T>, top: usize) -> bool {
+        let top = top.min(self.levels.len() - 1);
+        for level in 0..=top {
+            if !self.levels[level].insert(point.clone()) {
+                return false;
+            }
+            self.level_lens[level] += 1;
+        }
+        true
+    }
+
+    /// Deletes one point equal to `point` from every level that holds it.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and deleted.
+    pub fn delete(&mut self, point: &Point2D<T>) -> bool {
+        let mut deleted = false;
+        // The levels are nested, so a point missing from a level is missing from all coarser
+        // levels.
+        for (tree, len) in self.levels.iter_mut().zip(&mut self.level_lens) {
+            if !tree.delete(point) {
+                break;
+            }
+            *len -= 1;
+            deleted = true;
+        }
+        deleted
+    }
+
+    /// Finds the points to draw in a viewport.
+    ///
+    /// The levels are searched from the coarsest to the finest, and the points of the finest
+    /// level that has at most `max_points` points in the viewport are returned. If even the
+    /// coarsest level has more, its first `max_points` points in the viewport are returned,
+    /// which may not be spread evenly over it.
+    ///
+    /// # Arguments
+    ///
+    /// * `viewport` - The region to draw.
+    /// * `max_points` - The largest number of points to return.
+    ///
+    /// # Returns
+    ///
+    /// At most `max_points` points inside the viewport.
+    pub fn points_for_viewport(&self, viewport: &Rectangle, max_points: usize) -> Vec<Point2D<T>> {
+        let mut best: Option<Vec<Point2D<T>>> = None;
+        for level in self.levels.iter().rev() {
+            let mut found = level.range_search_bbox(viewport);
+            if found.len() > max_points {
+                return best.unwrap_or_else(|| {
+                    found.truncate(max_points);
+                    found
+                });
+            }
+            best = Some(found);
+        }
+        best.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: Rectangle = Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 64.0,
+        height: 64.0,
+    };
+
+    fn pyramid() -> PointPyramid<usize> {
+        let mut pyramid = PointPyramid::new(&ALL, 8, 4).unwrap().with_seed(3);
+        for i in 0..4096 {
+            pyramid.insert(Point2D::new((i % 64) as f64, (i / 64) as f64, Some(i)));
+        }
+        pyramid
+    }
+
+    #[test]
+    fn test_levels_are_nested_and_thinned() {
+        let pyramid = pyramid();
+        assert_eq!(pyramid.len(), 4096);
+        for level in 1..pyramid.levels() {
+            let coarse = pyramid.level_len(level);
+            let fine = pyramid.level_len(level - 1);
+            assert!(
+                coarse * 2 < fine && coarse > 0,
+                "level {level}: {coarse} of {fine}"
+            );
+            for point in pyramid.level(level).unwrap().range_search_bbox(&ALL) {
+                assert!(pyramid.level(level - 1).unwrap().contains(&point));
+            }
+        }
+    }
+
+    #[test]
+    fn test_viewport_picks_finest_level_within_budget() {
+        let pyramid = pyramid();
+        let sample = pyramid.points_for_viewport(&ALL, 500);
+        assert!(sample.len() <= 500);
+        assert!(
+            (0..pyramid.levels()).any(|level| pyramid.level_len(level) == sample.len()),
+            "the sample is a whole level"
+        );
+        assert_eq!(pyramid.points_for_viewport(&ALL, 2).len(), 2);
+
+        let window = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 3.0,
+            height: 3.0,
+        };
+        assert_eq!(pyramid.points_for_viewport(&window, 500).len(), 16);
+    }
+
+    #[test]
+    fn test_delete_and_insert_at_level() {
+        let mut pyramid = pyramid();
+        let important = Point2D::new(0.5, 0.5, Some(9999));
+        assert!(pyramid.insert_at_level(important.clone(), 10));
+        let top = pyramid.levels() - 1;
+        assert!(pyramid.level(top).unwrap().contains(&important));
+        assert!(pyramid.delete(&important));
+        assert!(!pyramid.delete(&important));
+        for level in 0..pyramid.levels() {
+            assert!(!pyramid.level(level).unwrap().contains(&important));
+        }
+    }
+}
@@ -27,13 +27,104 @@
 //! assert!(!neighbors.is_empty());
 //! ```
 
-use crate::errors::SpartError;
-use crate::geometry::{DistanceMetric, HeapItem, Point2D, Rectangle};
-use ordered_float::OrderedFloat;
+use crate::elevation::{Elevation, in_z_range};
+use crate::errors::{SpartError, check_knn_query, check_range_query};
+use crate::geo::{GeoBBox, sort_by_bearing};
+use crate::geometry::{
+    DistanceMetric, EuclideanDistance, Point2D, Polygon, Rectangle, TileScheme, convex_hull_refs,
+    cross, equi_depth_cells, overlap_fraction,
+};
+#[cfg(feature = "image")]
+use crate::heatmap::Colormap;
+use crate::heatmap::{DensityGrid, thin_by_priority};
+use crate::kdtree::{check_query_point, has_nan_coord};
+use crate::knn::{
+    DistinctKnnResults, ExcludingSink, KnnResults, KnnSink, LimitedResults, QueryContext,
+};
+use crate::logging::hot_trace;
+use crate::observer::Observed;
+use crate::payload::PayloadIndexed;
+use crate::predicates::covering_extent;
+use crate::random::{RandomSource, sample_indices};
+use crate::stats::{BalanceReport, LevelStats, NnDistanceStats, TreeStats, TreeSummary};
+use crate::testing::same_multiset;
+use crate::tuning::{CapacityAdvice, CapacityAdvisor};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::collections::BinaryHeap;
-use tracing::{debug, info};
+use std::ops::RangeBounds;
+#[cfg(feature = "image")]
+use std::path::Path;
+use tracing::info;
+
+/// One of the four child quadrants of a quadtree node.
+///
+/// Quadrants are listed in Morton (Z-order) order: `index()` has bit 0 set for the east half
+/// and bit 1 set for the south half (larger `y`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Quadrant {
+    /// The quadrant with the smaller `x` and `y` coordinates.
+    NorthWest,
+    /// The quadrant with the larger `x` and smaller `y` coordinates.
+    NorthEast,
+    /// The quadrant with the smaller `x` and larger `y` coordinates.
+    SouthWest,
+    /// The quadrant with the larger `x` and `y` coordinates.
+    SouthEast,
+}
+
+impl Quadrant {
+    /// Returns the Morton digit (0 to 3) of the quadrant.
+    pub fn index(self) -> usize {
+        match self {
+            Quadrant::NorthWest => 0,
+            Quadrant::NorthEast => 1,
+            Quadrant::SouthWest => 2,
+            Quadrant::SouthEast => 3,
+        }
+    }
+}
+
+/// What a leaf node does when a point is inserted into it at capacity.
+///
+/// Deletions merge the children of a node back into it once they hold at most `capacity`
+/// points in total. With `Split`, a workload that keeps inserting and deleting points around
+/// that threshold splits and merges the same node over and over; `Spill` leaves a margin of
+/// `extra` points between the two, so such a node is split only once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OverflowStrategy {
+    /// Subdivide as soon as the node is full (the default).
+    #[default]
+    Split,
+    /// Store up to `extra` points beyond the capacity before subdividing.
+    Spill {
+        /// The number of points a leaf may hold beyond its capacity.
+        extra: usize,
+    },
+}
+
+impl OverflowStrategy {
+    /// Returns the number of points a leaf with the given capacity may hold.
+    pub(crate) fn leaf_limit(self, capacity: usize) -> usize {
+        match self {
+            OverflowStrategy::Split => capacity,
+            OverflowStrategy::Spill { extra } => capacity.saturating_add(extra),
+        }
+    }
+}
+
+/// The number of subdivision levels below the root. Leaves at this depth hold all points
+/// routed to them instead of subdividing further, which would never separate duplicate points.
+pub const MAX_DEPTH: usize = 32;
+
+/// The merge threshold of new trees: children are merged once they fit into their parent.
+fn default_merge_threshold() -> f64 {
+    1.0
+}
+
+/// The distance to, node of, and points in the nearest occupied cell found so far.
+type NearestCell<'a, T> = (f64, &'a Quadtree<T>, Vec<&'a Point2D<T>>);
 
 /// A Quadtree for indexing of 2D points.
 ///
@@ -46,10 +137,15 @@ use tracing::{debug, info};
 /// Panics with `SpartError::InvalidCapacity` if `capacity` is zero.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "QuadtreeData<T>"))]
 pub struct Quadtree<T: Clone + PartialEq> {
     boundary: Rectangle,
     points: Vec<Point2D<T>>,
     capacity: usize,
+    tolerance: f64,
+    overflow: OverflowStrategy,
+    merge_threshold: f64,
+    depth: usize,
     divided: bool,
     northeast: Option<Box<Quadtree<T>>>,
     northwest: Option<Box<Quadtree<T>>>,
@@ -57,6 +153,61 @@ pub struct Quadtree<T: Clone + PartialEq> {
     southwest: Option<Box<Quadtree<T>>>,
 }
 
+/// The serialized form of a `Quadtree`.
+///
+/// Trees serialized before the depth of each node was stored lack the `depth` field, so the
+/// depths are recomputed from the position of each node when a tree is restored.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "Quadtree")]
+struct QuadtreeData<T: Clone + PartialEq> {
+    boundary: Rectangle,
+    points: Vec<Point2D<T>>,
+    capacity: usize,
+    #[serde(default)]
+    tolerance: f64,
+    #[serde(default)]
+    overflow: OverflowStrategy,
+    #[serde(default = "default_merge_threshold")]
+    merge_threshold: f64,
+    #[serde(default, rename = "depth")]
+    _depth: usize,
+    divided: bool,
+    northeast: Option<Box<QuadtreeData<T>>>,
+    northwest: Option<Box<QuadtreeData<T>>>,
+    southeast: Option<Box<QuadtreeData<T>>>,
+    southwest: Option<Box<QuadtreeData<T>>>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone + PartialEq> QuadtreeData<T> {
+    /// Converts the node and its descendants, giving the node the depth `depth`.
+    fn into_tree(self, depth: usize) -> Quadtree<T> {
+        let child = |node: Option<Box<Self>>| node.map(|n| Box::new(n.into_tree(depth + 1)));
+        Quadtree {
+            boundary: self.boundary,
+            points: self.points,
+            capacity: self.capacity,
+            tolerance: self.tolerance,
+            overflow: self.overflow,
+            merge_threshold: self.merge_threshold,
+            depth,
+            divided: self.divided,
+            northeast: child(self.northeast),
+            northwest: child(self.northwest),
+            southeast: child(self.southeast),
+            southwest: child(self.southwest),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone + PartialEq> From<QuadtreeData<T>> for Quadtree<T> {
+    fn from(data: QuadtreeData<T>) -> Self {
+        data.into_tree(0)
+    }
+}
+
 impl<T: Clone + PartialEq + std::fmt::Debug> Quadtree<T> {
     /// Creates a new `Quadtree` with the specified boundary and capacity.
     ///
@@ -80,6 +231,10 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Quadtree<T> {
             boundary: boundary.clone(),
             points: Vec::new(),
             capacity,
+            tolerance: 0.0,
+            overflow: OverflowStrategy::Split,
+            merge_threshold: default_merge_threshold(),
+            depth: 0,
             divided: false,
             northeast: None,
             northwest: None,
@@ -88,84 +243,255 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Quadtree<T> {
         })
     }
 
+    /// Sets the coordinate tolerance used to match points in `contains`, `delete`, and `update`.
+    ///
+    /// With a positive tolerance, a stored point matches if its data is equal and each of its
+    /// coordinates differs by at most `tolerance`, so points whose coordinates were rounded
+    /// (e.g., by a serialization round trip) can still be found. Negative and NaN values are
+    /// treated as zero, which means exact matching (the default).
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - The maximum allowed difference per coordinate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+    /// let mut tree = Quadtree::new(&boundary, 4).unwrap().with_tolerance(1e-9);
+    /// tree.insert(Point2D::new(0.1 + 0.2, 5.0, Some(1)));
+    /// assert!(tree.delete(&Point2D::new(0.3, 5.0, Some(1))));
+    /// ```
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance.max(0.0);
+        self
+    }
+
+    /// Returns the coordinate tolerance used to match points.
+    pub fn tolerance(&self) -> f64 {
+        self.tolerance
+    }
+
+    /// Sets what a full leaf does when another point is inserted into it: subdivide right away
+    /// (`OverflowStrategy::Split`, the default), or first hold a bounded number of extra points
+    /// (`OverflowStrategy::Spill`), which avoids splitting and merging the same node over and
+    /// over when points are inserted and deleted around its capacity.
+    ///
+    /// The strategy applies to all nodes, existing and future.
+    ///
+    /// # Arguments
+    ///
+    /// * `overflow` - The overflow strategy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::{OverflowStrategy, Quadtree};
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+    /// let mut tree = Quadtree::new(&boundary, 4)
+    ///     .unwrap()
+    ///     .with_overflow(OverflowStrategy::Spill { extra: 2 });
+    /// for i in 0..6 {
+    ///     tree.insert(Point2D::new(i as f64, 1.0, Some(i)));
+    /// }
+    /// // Six points fit into the root without subdividing it.
+    /// assert_eq!(tree.depth(), 1);
+    /// ```
+    pub fn with_overflow(mut self, overflow: OverflowStrategy) -> Self {
+        self.set_overflow(overflow);
+        self
+    }
+
+    /// Returns the overflow strategy of the tree.
+    pub fn overflow(&self) -> OverflowStrategy {
+        self.overflow
+    }
+
+    /// Sets the overflow strategy of this node and all of its descendants.
+    fn set_overflow(&mut self, overflow: OverflowStrategy) {
+        self.overflow = overflow;
+        for child in self.children_mut() {
+            child.set_overflow(overflow);
+        }
+    }
+
+    /// Sets how empty the children of a node must become before deletions merge them back
+    /// into it, as a fraction of the capacity.
+    ///
+    /// With the default of 1, children are merged as soon as they hold at most `capacity`
+    /// points in total, so a workload deleting and inserting points around that count splits
+    /// and merges the same node over and over. A lower threshold, e.g. 0.5, keeps the children
+    /// until they are half empty. Values are clamped to `[0, 1]`, and NaN is treated as 1; at
+    /// 0, children are only merged once they are empty.
+    ///
+    /// The threshold applies to all nodes, existing and future.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The fraction of the capacity at or below which children are merged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+    /// let mut tree = Quadtree::new(&boundary, 4).unwrap().with_merge_threshold(0.5);
+    /// let points: Vec<_> = (0..5)
+    ///     .map(|i| Point2D::new(i as f64 * 2.0 + 0.5, 1.0, Some(i)))
+    ///     .collect();
+    /// tree.insert_bulk(&points);
+    /// assert_eq!(tree.depth(), 2);
+    /// // Four points would fit into one node again, but only two are merged back.
+    /// tree.delete(&points[4]);
+    /// assert_eq!(tree.depth(), 2);
+    /// tree.delete(&points[3]);
+    /// tree.delete(&points[2]);
+    /// assert_eq!(tree.depth(), 1);
+    /// ```
+    pub fn with_merge_threshold(mut self, threshold: f64) -> Self {
+        let threshold = if threshold.is_nan() {
+            1.0
+        } else {
+            threshold.clamp(0.0, 1.0)
+        };
+        self.set_merge_threshold(threshold);
+        self
+    }
+
+    /// Returns the fraction of the capacity at or below which children are merged.
+    pub fn merge_threshold(&self) -> f64 {
+        self.merge_threshold
+    }
+
+    /// Sets the merge threshold of this node and all of its descendants.
+    fn set_merge_threshold(&mut self, threshold: f64) {
+        self.merge_threshold = threshold;
+        for child in self.children_mut() {
+            child.set_merge_threshold(threshold);
+        }
+    }
+
+    /// Copies the overflow strategy and merge threshold of `parent` to this new node, one level
+    /// below it.
+    fn with_settings_of(mut self, parent: &Self) -> Self {
+        self.overflow = parent.overflow;
+        self.merge_threshold = parent.merge_threshold;
+        self.depth = parent.depth + 1;
+        self
+    }
+
+    /// Adds a hash table from payloads to points, for lookups and deletions by payload.
+    ///
+    /// The points already stored are added to the table. See `payload::PayloadIndexed`.
+    pub fn with_payload_index(self) -> PayloadIndexed<Self>
+    where
+        T: std::hash::Hash + Eq,
+    {
+        PayloadIndexed::new(self)
+    }
+
+    /// Wraps the tree so that its changes are reported to observers. See `observer::Observed`.
+    pub fn with_observers(self) -> Observed<Self> {
+        Observed::new(self)
+    }
+
     /// Subdivides the current quadtree node into four child quadrants.
     ///
     /// After subdivision, all existing points are reinserted into the appropriate children.
     fn subdivide(&mut self) {
-        info!("Subdividing Quadtree at boundary: {:?}", self.boundary);
-        let x = self.boundary.x;
-        let y = self.boundary.y;
-        let w = self.boundary.width / 2.0;
-        let h = self.boundary.height / 2.0;
-        self.northeast = Some(Box::new({
-            let child = Quadtree::new(
-                &Rectangle {
-                    x: x + w,
-                    y,
-                    width: w,
-                    height: h,
-                },
-                self.capacity,
-            );
-            match child {
-                Ok(c) => c,
-                Err(_) => unreachable!("capacity validated at construction"),
-            }
-        }));
-        self.northwest = Some(Box::new({
-            let child = Quadtree::new(
-                &Rectangle {
-                    x,
-                    y,
-                    width: w,
-                    height: h,
-                },
-                self.capacity,
-            );
-            match child {
-                Ok(c) => c,
-                Err(_) => unreachable!("capacity validated at construction"),
-            }
-        }));
-        self.southeast = Some(Box::new({
-            let child = Quadtree::new(
-                &Rectangle {
-                    x: x + w,
-                    y: y + h,
-                    width: w,
-                    height: h,
-                },
-                self.capacity,
-            );
-            match child {
-                Ok(c) => c,
-                Err(_) => unreachable!("capacity validated at construction"),
-            }
-        }));
-        self.southwest = Some(Box::new({
-            let child = Quadtree::new(
-                &Rectangle {
-                    x,
-                    y: y + h,
-                    width: w,
-                    height: h,
-                },
-                self.capacity,
-            );
-            match child {
-                Ok(c) => c,
-                Err(_) => unreachable!("capacity validated at construction"),
-            }
-        }));
+        hot_trace!("Subdividing Quadtree node", boundary = self.boundary);
+        let [nw, ne, sw, se] = child_boundaries(&self.boundary);
+        let child = |boundary: &Rectangle| match Quadtree::new(boundary, self.capacity) {
+            Ok(c) => Some(Box::new(c.with_settings_of(self))),
+            Err(_) => unreachable!("capacity validated at construction"),
+        };
+        let children = [child(&nw), child(&ne), child(&sw), child(&se)];
+        [
+            self.northwest,
+            self.northeast,
+            self.southwest,
+            self.southeast,
+        ] = children;
         self.divided = true;
         // Reinsert existing points into the appropriate children.
         let old_points = std::mem::take(&mut self.points);
         for point in old_points {
-            let inserted = self.insert(point);
-            if !inserted {
-                debug!("Failed to reinsert point during subdivision");
+            self.insert_unchecked(point);
+        }
+    }
+
+    /// Returns the child quadrant a point inside this node's boundary is routed to.
+    ///
+    /// See `quadrant_index` for how points on a split line are routed.
+    fn quadrant_for(&self, point: &Point2D<T>) -> Quadrant {
+        match quadrant_index(&self.boundary, point) {
+            0 => Quadrant::NorthWest,
+            1 => Quadrant::NorthEast,
+            2 => Quadrant::SouthWest,
+            _ => Quadrant::SouthEast,
+        }
+    }
+
+    /// Returns the child node for the given quadrant, if the node is divided.
+    fn child(&self, quadrant: Quadrant) -> Option<&Quadtree<T>> {
+        match quadrant {
+            Quadrant::NorthWest => self.northwest.as_deref(),
+            Quadrant::NorthEast => self.northeast.as_deref(),
+            Quadrant::SouthWest => self.southwest.as_deref(),
+            Quadrant::SouthEast => self.southeast.as_deref(),
+        }
+    }
+
+    /// Returns the mutable child node for the given quadrant, if the node is divided.
+    fn child_mut(&mut self, quadrant: Quadrant) -> Option<&mut Quadtree<T>> {
+        match quadrant {
+            Quadrant::NorthWest => self.northwest.as_deref_mut(),
+            Quadrant::NorthEast => self.northeast.as_deref_mut(),
+            Quadrant::SouthWest => self.southwest.as_deref_mut(),
+            Quadrant::SouthEast => self.southeast.as_deref_mut(),
+        }
+    }
+
+    /// Returns the quadrants that may hold a point matching `point` within `tolerance`,
+    /// starting with the quadrant `point` itself is routed to.
+    fn candidate_quadrants(&self, point: &Point2D<T>, tolerance: f64) -> Vec<Quadrant> {
+        let routed = self.quadrant_for(point);
+        let mut quadrants = vec![routed];
+        if tolerance > 0.0 {
+            let window = Self::tolerance_window(point, tolerance);
+            for quadrant in [
+                Quadrant::NorthWest,
+                Quadrant::NorthEast,
+                Quadrant::SouthWest,
+                Quadrant::SouthEast,
+            ] {
+                if quadrant != routed
+                    && self
+                        .child(quadrant)
+                        .is_some_and(|c| c.boundary.intersects(&window))
+                {
+                    quadrants.push(quadrant);
+                }
             }
         }
+        quadrants
+    }
+
+    /// Returns the region of positions that match `point` within `tolerance`.
+    fn tolerance_window(point: &Point2D<T>, tolerance: f64) -> Rectangle {
+        Rectangle {
+            x: point.x - tolerance,
+            y: point.y - tolerance,
+            width: 2.0 * tolerance,
+            height: 2.0 * tolerance,
+        }
     }
 
     /// Inserts a point into the quadtree.
@@ -184,46 +510,26 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Quadtree<T> {
         if !self.boundary.contains(&point) {
             return false;
         }
+        self.insert_unchecked(point);
+        true
+    }
 
+    /// Inserts a point that is already known to lie inside this node's boundary.
+    fn insert_unchecked(&mut self, point: Point2D<T>) {
         if !self.divided {
-            if self.points.len() < self.capacity {
+            if self.points.len() < self.overflow.leaf_limit(self.capacity)
+                || self.depth >= MAX_DEPTH
+            {
                 self.points.push(point);
-                return true;
+                return;
             }
             self.subdivide();
         }
-
-        if self
-            .northwest
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .northeast
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .southwest
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
-        }
-        if self
-            .southeast
-            .as_mut()
-            .is_some_and(|c| c.insert(point.clone()))
-        {
-            return true;
+        let quadrant = self.quadrant_for(&point);
+        match self.child_mut(quadrant) {
+            Some(child) => child.insert_unchecked(point),
+            None => self.points.push(point),
         }
-
-        // This case should be unreachable if boundary logic is sound.
-        unreachable!("A point within the parent boundary should always fit in a child boundary.");
     }
 
     /// Inserts a bulk of points into the quadtree.
@@ -232,10 +538,6 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Quadtree<T> {
     ///
     /// * `points` - The points to insert.
     pub fn insert_bulk(&mut self, points: &[Point2D<T>]) {
-        if points.is_empty() {
-            return;
-        }
-
         // Filter out points that are not within the boundary
         let points_within_boundary: Vec<Point2D<T>> = points
             .iter()
@@ -246,75 +548,147 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Quadtree<T> {
         if points_within_boundary.is_empty() {
             return;
         }
+        self.insert_bulk_unchecked(points_within_boundary);
+    }
 
-        // If the current node is not divided and has enough capacity, add the points
-        if !self.divided && self.points.len() + points_within_boundary.len() <= self.capacity {
-            self.points.extend(points_within_boundary);
-            return;
-        }
-
-        // If the current node is not divided but adding the new points would exceed the capacity,
-        // subdivide the node and distribute the existing and new points among the children.
+    /// Inserts points that are already known to lie inside this node's boundary.
+    ///
+    /// If the points do not fit into an undivided node, the node is subdivided and the points
+    /// are partitioned among the children, recursing until every point has been stored.
+    fn insert_bulk_unchecked(&mut self, points: Vec<Point2D<T>>) {
         if !self.divided {
+            if self.points.len() + points.len() <= self.overflow.leaf_limit(self.capacity)
+                || self.depth >= MAX_DEPTH
+            {
+                self.points.extend(points);
+                return;
+            }
             self.subdivide();
         }
 
-        // If the node is already divided, distribute the new points among the children.
-        let mut points_to_insert = points_within_boundary;
-        if self.divided {
-            let mut children_points: [Vec<Point2D<T>>; 4] = [vec![], vec![], vec![], vec![]];
-
-            for point in points_to_insert.drain(..) {
-                if self
-                    .northeast
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[0].push(point);
-                } else if self
-                    .northwest
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[1].push(point);
-                } else if self
-                    .southeast
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[2].push(point);
-                } else if self
-                    .southwest
-                    .as_ref()
-                    .map(|c| c.boundary.contains(&point))
-                    .unwrap_or(false)
-                {
-                    children_points[3].push(point);
-                }
+        let mut children_points: [Vec<Point2D<T>>; 4] = Default::default();
+        for point in points {
+            children_points[self.quadrant_for(&point).index()].push(point);
+        }
+        let quadrants = [
+            Quadrant::NorthWest,
+            Quadrant::NorthEast,
+            Quadrant::SouthWest,
+            Quadrant::SouthEast,
+        ];
+        for (quadrant, child_points) in quadrants.into_iter().zip(children_points) {
+            if child_points.is_empty() {
+                continue;
             }
+            match self.child_mut(quadrant) {
+                Some(child) => child.insert_bulk_unchecked(child_points),
+                None => self.points.extend(child_points),
+            }
+        }
+    }
+
+    /// Returns the Morton (Z-order) key of a point relative to the tree's boundary.
+    ///
+    /// Keys order points the way `export_sorted` lists them, so data can be sorted by this key
+    /// externally (e.g., in an on-disk sort) and then loaded with `import_sorted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to compute the key for.
+    ///
+    /// # Returns
+    ///
+    /// The key, or `None` if the point lies outside the tree's boundary.
+    pub fn morton_key(&self, point: &Point2D<T>) -> Option<u64> {
+        if !self.boundary.contains(point) {
+            return None;
+        }
+        Some(morton_code(&self.boundary, point))
+    }
 
-            if !children_points[0].is_empty() {
-                if let Some(c) = self.northeast.as_mut() {
-                    c.insert_bulk(&children_points[0]);
+    /// Returns an iterator over all points in Morton (Z-order) order.
+    ///
+    /// The tree is walked leaf by leaf, sorting only the points of one leaf at a time, so the
+    /// points are streamed without collecting them first. The order is that of `morton_key`.
+    pub fn export_sorted(&self) -> impl Iterator<Item = &Point2D<T>> + '_ {
+        let mut stack = vec![self];
+        let mut leaf_points = Vec::new().into_iter();
+        std::iter::from_fn(move || {
+            loop {
+                if let Some(point) = leaf_points.next() {
+                    return Some(point);
                 }
-            }
-            if !children_points[1].is_empty() {
-                if let Some(c) = self.northwest.as_mut() {
-                    c.insert_bulk(&children_points[1]);
+                let node = stack.pop()?;
+                if node.divided {
+                    // Pushed in reverse so that the children are visited in Morton order.
+                    for quadrant in [
+                        Quadrant::SouthEast,
+                        Quadrant::SouthWest,
+                        Quadrant::NorthEast,
+                        Quadrant::NorthWest,
+                    ] {
+                        if let Some(child) = node.child(quadrant) {
+                            stack.push(child);
+                        }
+                    }
                 }
+                let mut points: Vec<&Point2D<T>> = node.points.iter().collect();
+                points.sort_by_cached_key(|p| morton_code(&node.boundary, p));
+                leaf_points = points.into_iter();
             }
-            if !children_points[2].is_empty() {
-                if let Some(c) = self.southeast.as_mut() {
-                    c.insert_bulk(&children_points[2]);
-                }
+        })
+    }
+
+    /// Builds a tree from points sorted in Morton order, such as the output of `export_sorted`.
+    ///
+    /// Each node's points are split among its children by moving contiguous runs, which takes
+    /// linear time per level for sorted input and never clones a point. Unsorted input is
+    /// accepted too, but is sorted level by level and therefore slower. Points outside the
+    /// boundary are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The region covered by the tree.
+    /// * `capacity` - The maximum number of points a node can hold before subdividing.
+    /// * `points` - The points, ideally sorted by `morton_key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn import_sorted(
+        boundary: &Rectangle,
+        capacity: usize,
+        mut points: Vec<Point2D<T>>,
+    ) -> Result<Self, SpartError> {
+        let mut tree = Self::new(boundary, capacity)?;
+        points.retain(|p| tree.boundary.contains(p));
+        tree.build_sorted(points);
+        Ok(tree)
+    }
+
+    /// Fills an empty node with points that lie inside its boundary.
+    fn build_sorted(&mut self, mut points: Vec<Point2D<T>>) {
+        if points.len() <= self.capacity || self.depth >= MAX_DEPTH {
+            self.points = points;
+            return;
+        }
+        self.subdivide();
+        // A stable sort is linear on input that is already in Morton order.
+        points.sort_by_key(|p| self.quadrant_for(p).index());
+        for quadrant in [
+            Quadrant::SouthEast,
+            Quadrant::SouthWest,
+            Quadrant::NorthEast,
+            Quadrant::NorthWest,
+        ] {
+            let start = points.partition_point(|p| self.quadrant_for(p).index() < quadrant.index());
+            let run = points.split_off(start);
+            if run.is_empty() {
+                continue;
             }
-            if !children_points[3].is_empty() {
-                if let Some(c) = self.southwest.as_mut() {
-                    c.insert_bulk(&children_points[3]);
-                }
+            match self.child_mut(quadrant) {
+                Some(child) => child.build_sorted(run),
+                None => self.points.extend(run),
             }
         }
     }
@@ -378,381 +752,3504 @@ impl<T: Clone + PartialEq + std::fmt::Debug> Quadtree<T> {
         dx * dx + dy * dy
     }
 
-    /// Performs a k-nearest neighbor search for the target point.
+    /// Returns the stored point with the smallest coordinate along `axis`.
+    ///
+    /// Subtrees whose region cannot hold a smaller coordinate than the best point found so far
+    /// are skipped, so usually only the nodes along one side of the boundary are visited. If
+    /// several points share the smallest coordinate, any one of them is returned.
     ///
     /// # Arguments
     ///
-    /// * `target` - The point for which to find the k nearest neighbors.
-    /// * `k` - The number of nearest neighbors to retrieve.
+    /// * `axis` - The axis to compare (0 for `x`, 1 for `y`).
     ///
     /// # Returns
     ///
-    /// A vector of the k nearest points, ordered from nearest to farthest.
+    /// The point, or `None` if the tree is empty.
     ///
-    /// # Note
+    /// # Errors
     ///
-    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
-    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
-    /// performance.
-    pub fn knn_search<M: DistanceMetric<Point2D<T>>>(
-        &self,
-        target: &Point2D<T>,
-        k: usize,
-    ) -> Vec<Point2D<T>> {
-        if k == 0 {
-            return Vec::new();
-        }
-        let mut heap: BinaryHeap<HeapItem<T>> = BinaryHeap::new();
-        self.knn_search_helper::<M>(target, k, &mut heap);
-        heap.into_sorted_vec()
-            .into_iter()
-            .filter_map(|item| item.point_2d)
-            .collect()
+    /// Returns `SpartError::InvalidDimension` if `axis` is greater than 1.
+    pub fn min_by_axis(&self, axis: usize) -> Result<Option<&Point2D<T>>, SpartError> {
+        self.extreme_by_axis(axis, false)
     }
 
-    /// Helper method for performing the recursive k-nearest neighbor search.
-    fn knn_search_helper<M: DistanceMetric<Point2D<T>>>(
-        &self,
-        target: &Point2D<T>,
-        k: usize,
-        heap: &mut BinaryHeap<HeapItem<T>>,
+    /// Returns the stored point with the largest coordinate along `axis`.
+    ///
+    /// This is the counterpart of `min_by_axis` and prunes the tree the same way.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The axis to compare (0 for `x`, 1 for `y`).
+    ///
+    /// # Returns
+    ///
+    /// The point, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is greater than 1.
+    pub fn max_by_axis(&self, axis: usize) -> Result<Option<&Point2D<T>>, SpartError> {
+        self.extreme_by_axis(axis, true)
+    }
+
+    fn extreme_by_axis(&self, axis: usize, max: bool) -> Result<Option<&Point2D<T>>, SpartError> {
+        let (lo, hi) = match axis {
+            0 => (self.boundary.x, self.boundary.x + self.boundary.width),
+            1 => (self.boundary.y, self.boundary.y + self.boundary.height),
+            _ => {
+                return Err(SpartError::InvalidDimension {
+                    requested: axis,
+                    available: 2,
+                });
+            }
+        };
+        let mut best = None;
+        self.extreme_rec(axis, max, lo, hi, &mut best);
+        Ok(best.map(|(_, point)| point))
+    }
+
+    /// Updates `best` with the points of the subtree that beat it along `axis`.
+    ///
+    /// `lo..=hi` is the range of coordinates along `axis` that can be routed to this node; it
+    /// is tracked from the root rather than read from the node's boundary, which may be off by
+    /// rounding. Keys are negated when looking for the minimum, so a larger key is always
+    /// better.
+    fn extreme_rec<'a>(
+        &'a self,
+        axis: usize,
+        max: bool,
+        lo: f64,
+        hi: f64,
+        best: &mut Option<(f64, &'a Point2D<T>)>,
     ) {
-        for point in &self.points {
-            let dist_sq = M::distance_sq(point, target);
-            let item = HeapItem {
-                neg_distance: OrderedFloat(-dist_sq),
-                point_2d: Some(point.clone()),
-                point_3d: None,
+        let bound = if max { hi } else { -lo };
+        if best.is_some_and(|(key, _)| bound <= key) {
+            return;
+        }
+        for p in &self.points {
+            let coord = match axis {
+                0 => p.x,
+                1 => p.y,
+                _ => unreachable!("axis checked by extreme_by_axis"),
             };
-            heap.push(item);
-            if heap.len() > k {
-                heap.pop();
+            let key = if max { coord } else { -coord };
+            if best.is_none_or(|(best_key, _)| key > best_key) {
+                *best = Some((key, p));
             }
         }
-        if self.divided {
-            for child in self.children() {
-                if heap.len() == k {
-                    if let Some(top) = heap.peek() {
-                        let current_farthest = -top.neg_distance.into_inner();
-                        if child.min_distance_sq(target) > current_farthest {
-                            continue;
-                        }
+        if !self.divided {
+            return;
+        }
+        let mid = match axis {
+            0 => self.boundary.x + self.boundary.width / 2.0,
+            1 => self.boundary.y + self.boundary.height / 2.0,
+            _ => unreachable!("axis checked by extreme_by_axis"),
+        };
+        // The half holding the larger (or smaller) coordinates is searched first, which makes
+        // pruning the other half likely.
+        let halves = if max { [1, 0] } else { [0, 1] };
+        for half in halves {
+            let (child_lo, child_hi) = if half == 1 { (mid, hi) } else { (lo, mid) };
+            for quadrant in [
+                Quadrant::NorthWest,
+                Quadrant::NorthEast,
+                Quadrant::SouthWest,
+                Quadrant::SouthEast,
+            ] {
+                if (quadrant.index() >> axis) & 1 == half {
+                    if let Some(child) = self.child(quadrant) {
+                        child.extreme_rec(axis, max, child_lo, child_hi, best);
                     }
                 }
-                child.knn_search_helper::<M>(target, k, heap);
             }
         }
     }
 
-    /// Performs a range search, returning all points within the specified radius of the center point.
-    ///
-    /// # Arguments
+    /// Computes the convex hull of the stored points.
     ///
-    /// * `center` - The center of the search range.
-    /// * `radius` - The search radius.
+    /// The points with the smallest and largest `x` and `y` coordinates are found first with
+    /// the pruned search of `min_by_axis` and `max_by_axis`. No point strictly inside the
+    /// quadrilateral they span can be on the hull, so subtrees whose region lies inside it are
+    /// skipped, and only the remaining points are passed to `geometry::convex_hull`.
     ///
     /// # Returns
     ///
-    /// A vector of points within the range.
-    ///
-    /// # Note
-    ///
-    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
-    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
-    /// performance.
-    pub fn range_search<M: DistanceMetric<Point2D<T>>>(
-        &self,
-        center: &Point2D<T>,
-        radius: f64,
-    ) -> Vec<Point2D<T>> {
-        if radius < 0.0 {
+    /// The hull vertices, in the order described for `geometry::convex_hull`.
+    pub fn convex_hull(&self) -> Vec<Point2D<T>> {
+        let extreme = |axis, max| {
+            self.extreme_by_axis(axis, max)
+                .unwrap_or_else(|_| unreachable!("axis is valid for 2D points"))
+        };
+        let (Some(left), Some(bottom), Some(right), Some(top)) = (
+            extreme(0, false),
+            extreme(1, false),
+            extreme(0, true),
+            extreme(1, true),
+        ) else {
             return Vec::new();
-        }
-        let mut found = Vec::new();
-        let radius_sq = radius * radius;
-        if self.min_distance_sq(center) > radius_sq {
-            return found;
-        }
-        for point in &self.points {
-            if M::distance_sq(point, center) <= radius_sq {
-                found.push(point.clone());
-            }
-        }
+        };
+        // Listed counter-clockwise, so the interior is to the left of every edge.
+        let quad = [left, bottom, right, top];
+        let range = [
+            self.boundary.x,
+            self.boundary.y,
+            self.boundary.x + self.boundary.width,
+            self.boundary.y + self.boundary.height,
+        ];
+        let mut candidates = Vec::new();
+        self.hull_candidates_rec(&quad, range, &mut candidates);
+        convex_hull_refs(candidates).into_iter().cloned().collect()
+    }
+
+    /// Collects the points of the subtree that are not strictly inside `quad`.
+    ///
+    /// `range` holds the smallest `x`, smallest `y`, largest `x`, and largest `y` coordinates
+    /// that can be routed to this node, tracked like in `extreme_rec`.
+    fn hull_candidates_rec<'a>(
+        &'a self,
+        quad: &[&Point2D<T>; 4],
+        range: [f64; 4],
+        out: &mut Vec<&'a Point2D<T>>,
+    ) {
+        let [min_x, min_y, max_x, max_y] = range;
+        let corners = [
+            (min_x, min_y),
+            (max_x, min_y),
+            (min_x, max_y),
+            (max_x, max_y),
+        ];
+        if corners
+            .iter()
+            .all(|&(x, y)| strictly_inside(quad, &Point2D::new(x, y, None)))
+        {
+            return;
+        }
+        out.extend(self.points.iter().filter(|p| !strictly_inside(quad, p)));
+        if !self.divided {
+            return;
+        }
+        let mid_x = self.boundary.x + self.boundary.width / 2.0;
+        let mid_y = self.boundary.y + self.boundary.height / 2.0;
+        for quadrant in [
+            Quadrant::NorthWest,
+            Quadrant::NorthEast,
+            Quadrant::SouthWest,
+            Quadrant::SouthEast,
+        ] {
+            let (lo_x, hi_x) = if quadrant.index() & 1 == 1 {
+                (mid_x, max_x)
+            } else {
+                (min_x, mid_x)
+            };
+            let (lo_y, hi_y) = if quadrant.index() & 2 == 2 {
+                (mid_y, max_y)
+            } else {
+                (min_y, mid_y)
+            };
+            if let Some(child) = self.child(quadrant) {
+                child.hull_candidates_rec(quad, [lo_x, lo_y, hi_x, hi_y], out);
+            }
+        }
+    }
+
+    /// Summarizes the nearest-neighbor distances of a random sample of the stored points.
+    ///
+    /// For each sampled point, the distance to its nearest other stored point is found with a
+    /// kNN query on this tree, so the cost is about `sample_size` queries rather than a scan
+    /// per point. Distances are Euclidean.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_size` - The number of points to sample; all points are used if the tree holds
+    ///   fewer.
+    /// * `rng` - The source of randomness used to draw the sample (e.g., `random::SplitMix64`).
+    ///
+    /// # Returns
+    ///
+    /// The summary, or `None` if the tree holds fewer than two points or `sample_size` is zero.
+    pub fn nn_distance_stats<R: RandomSource>(
+        &self,
+        sample_size: usize,
+        rng: &mut R,
+    ) -> Option<NnDistanceStats> {
+        let mut points = Vec::new();
+        self.collect_points(&mut points);
+        if points.len() < 2 {
+            return None;
+        }
+        let distances = sample_indices(points.len(), sample_size, rng)
+            .into_iter()
+            .filter_map(|i| {
+                let point = points[i];
+                // The nearest result is the point itself (or a copy of it at distance zero).
+                let neighbors = self.knn_search::<EuclideanDistance>(point, 2);
+                neighbors
+                    .get(1)
+                    .map(|n| EuclideanDistance::distance_sq(point, n).sqrt())
+            })
+            .collect();
+        NnDistanceStats::from_distances(distances)
+    }
+
+    /// Recommends a capacity for the points stored in this tree.
+    ///
+    /// The stored points are used as the sample: for each candidate capacity of the advisor, a
+    /// new tree with this tree's boundary is built over them and timed (see
+    /// `tuning::CapacityAdvisor::quadtree`). This tree itself is not modified.
+    ///
+    /// # Arguments
+    ///
+    /// * `advisor` - The advisor holding the candidates and the measurement settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if a candidate capacity is zero.
+    pub fn advise_capacity(&self, advisor: &CapacityAdvisor) -> Result<CapacityAdvice, SpartError> {
+        let mut points = Vec::new();
+        self.collect_points(&mut points);
+        let sample: Vec<Point2D<T>> = points.into_iter().cloned().collect();
+        advisor.quadtree(&self.boundary, &sample)
+    }
+
+    /// Returns statistics about the shape of the tree.
+    ///
+    /// Every node counts towards the fill factor, including divided nodes, which keep the
+    /// points they held before subdividing.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats {
+            size: 0,
+            node_count: 0,
+            leaf_count: 0,
+            depth: 0,
+            fill_factor: None,
+        };
+        self.stats_helper(1, &mut stats);
+        stats.fill_factor = Some(stats.size as f64 / (stats.node_count * self.capacity) as f64);
+        stats
+    }
+
+    /// Returns `true` if both trees hold the same points, counting duplicates, regardless of
+    /// their boundaries, capacities, or how the points are arranged in the nodes.
+    pub fn content_eq(&self, other: &Self) -> bool {
+        let mut mine = Vec::new();
+        self.collect_points(&mut mine);
+        let mut theirs = Vec::new();
+        other.collect_points(&mut theirs);
+        same_multiset(mine, theirs, |p| vec![p.x, p.y])
+    }
+
+    /// Returns a concise description of the tree: its size, depth, capacity, and boundary.
+    pub fn summary(&self) -> TreeSummary {
+        let stats = self.stats();
+        TreeSummary {
+            kind: "Quadtree",
+            size: stats.size,
+            depth: stats.depth,
+            capacity: Some(self.capacity),
+            bounds: vec![
+                (self.boundary.x, self.boundary.x + self.boundary.width),
+                (self.boundary.y, self.boundary.y + self.boundary.height),
+            ],
+        }
+    }
+
+    /// Returns the number of levels of the tree, i.e., the number of nodes on the longest
+    /// path from the root to a leaf (1 for an undivided tree).
+    pub fn depth(&self) -> usize {
+        if self.divided {
+            1 + self
+                .children()
+                .into_iter()
+                .map(|child| child.depth())
+                .max()
+                .unwrap_or(0)
+        } else {
+            1
+        }
+    }
+
+    /// Returns the number of nodes and points at each level of the tree, to detect
+    /// pathological subdivision caused by clustered data.
+    ///
+    /// # Returns
+    ///
+    /// A `BalanceReport` with one entry per level, starting with the root.
+    pub fn balance_report(&self) -> BalanceReport {
+        let mut levels = Vec::new();
+        self.balance_report_helper(0, &mut levels);
+        BalanceReport {
+            levels,
+            capacity: self.capacity,
+            fanout: 4,
+        }
+    }
+
+    /// Helper method for counting the nodes and points of the subtree at `level`.
+    fn balance_report_helper(&self, level: usize, levels: &mut Vec<LevelStats>) {
+        if levels.len() <= level {
+            levels.push(LevelStats::default());
+        }
+        levels[level].nodes += 1;
+        levels[level].points += self.points.len();
+        if self.divided {
+            for child in self.children() {
+                child.balance_report_helper(level + 1, levels);
+            }
+        } else {
+            levels[level].leaves += 1;
+        }
+    }
+
+    /// Helper method for accumulating the statistics of the subtree at `depth`.
+    fn stats_helper(&self, depth: usize, stats: &mut TreeStats) {
+        stats.size += self.points.len();
+        stats.node_count += 1;
+        stats.depth = stats.depth.max(depth);
         if self.divided {
             for child in self.children() {
-                found.extend(child.range_search::<M>(center, radius));
+                child.stats_helper(depth + 1, stats);
             }
+        } else {
+            stats.leaf_count += 1;
         }
-        found
     }
 
-    /// Deletes a point from the quadtree.
+    /// Checks the structural invariants of the tree.
     ///
-    /// Returns `true` if the point was found and deleted.
+    /// Every point lies inside the boundary of its node and, below the root, in the quadrant
+    /// its parent routes it to, and a node has all four children exactly when it is divided.
+    /// A tree built through the public API always passes; the check is meant for trees
+    /// restored from untrusted or possibly corrupted data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvariantViolation` describing the first violation found.
+    pub fn check_invariants(&self) -> Result<(), SpartError> {
+        self.check_invariants_helper(0)
+    }
+
+    /// Helper method for checking the invariants of the subtree at `depth`.
+    fn check_invariants_helper(&self, depth: usize) -> Result<(), SpartError> {
+        let violation = |reason: String| Err(SpartError::InvariantViolation { reason });
+        if let Some(point) = self.points.iter().find(|p| !self.boundary.contains(p)) {
+            return violation(format!(
+                "point ({}, {}) at depth {depth} lies outside its node",
+                point.x, point.y
+            ));
+        }
+        let quadrants = [
+            Quadrant::NorthWest,
+            Quadrant::NorthEast,
+            Quadrant::SouthWest,
+            Quadrant::SouthEast,
+        ];
+        let child_count = quadrants.iter().filter_map(|q| self.child(*q)).count();
+        if child_count != if self.divided { 4 } else { 0 } {
+            return violation(format!(
+                "node at depth {depth} is {} but has {child_count} children",
+                if self.divided {
+                    "divided"
+                } else {
+                    "not divided"
+                }
+            ));
+        }
+        for quadrant in quadrants {
+            let Some(child) = self.child(quadrant) else {
+                continue;
+            };
+            if let Some(point) = child
+                .points
+                .iter()
+                .find(|p| self.quadrant_for(p) != quadrant)
+            {
+                return violation(format!(
+                    "point ({}, {}) at depth {} is stored outside the quadrant it belongs to",
+                    point.x,
+                    point.y,
+                    depth + 1
+                ));
+            }
+            child.check_invariants_helper(depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Appends references to all points of the subtree to `out`.
+    fn collect_points<'a>(&'a self, out: &mut Vec<&'a Point2D<T>>) {
+        out.extend(self.points.iter());
+        for quadrant in [
+            Quadrant::NorthWest,
+            Quadrant::NorthEast,
+            Quadrant::SouthWest,
+            Quadrant::SouthEast,
+        ] {
+            if let Some(child) = self.child(quadrant) {
+                child.collect_points(out);
+            }
+        }
+    }
+
+    /// Returns a new tree holding only the points inside `region`.
+    ///
+    /// The new tree has the same boundary, capacity, and tolerance as this one. Subtrees whose
+    /// region lies entirely inside `region` are cloned as a whole rather than rebuilt point by
+    /// point, and subtrees entirely outside it are skipped, so most of the work is spent on the
+    /// nodes crossing the border of `region`. Nodes left with few points are merged as after a
+    /// deletion. This tree is not modified.
     ///
     /// # Arguments
     ///
-    /// * `point` - The point to delete.
-    pub fn delete(&mut self, point: &Point2D<T>) -> bool {
-        if !self.boundary.contains(point) {
-            return false;
+    /// * `region` - The region to keep. Points on its border are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+    /// let mut tree = Quadtree::new(&boundary, 2).unwrap();
+    /// for i in 0..10 {
+    ///     tree.insert(Point2D::new(i as f64, i as f64, Some(i)));
+    /// }
+    /// let region = Rectangle { x: 0.0, y: 0.0, width: 4.0, height: 4.0 };
+    /// let cropped = tree.crop(&region);
+    /// assert_eq!(cropped.range_search_bbox(&boundary).len(), 5);
+    /// ```
+    pub fn crop(&self, region: &Rectangle) -> Quadtree<T> {
+        let region = (region.min_corner(), region.max_corner());
+        let range = (self.boundary.min_corner(), self.boundary.max_corner());
+        let mut cropped = self.crop_rec(&region, range);
+        cropped.try_merge();
+        cropped
+    }
+
+    /// Returns a copy of the subtree holding only the points inside `region`.
+    ///
+    /// `region` and `range` hold the smallest and largest coordinates per axis; `range` covers
+    /// the positions that can be routed to this node, tracked from the root like in
+    /// `extreme_rec`.
+    fn crop_rec(&self, region: &([f64; 2], [f64; 2]), range: ([f64; 2], [f64; 2])) -> Quadtree<T> {
+        let ((region_min, region_max), (lo, hi)) = (region, range);
+        if (0..2).all(|axis| region_min[axis] <= lo[axis] && hi[axis] <= region_max[axis]) {
+            return self.clone();
+        }
+        let mut node = Quadtree {
+            boundary: self.boundary.clone(),
+            points: Vec::new(),
+            capacity: self.capacity,
+            tolerance: self.tolerance,
+            overflow: self.overflow,
+            merge_threshold: self.merge_threshold,
+            depth: self.depth,
+            divided: false,
+            northeast: None,
+            northwest: None,
+            southeast: None,
+            southwest: None,
+        };
+        if (0..2).any(|axis| lo[axis] > region_max[axis] || hi[axis] < region_min[axis]) {
+            return node;
         }
-        let mut deleted = false;
         if self.divided {
-            for child in self.children_mut() {
-                if child.delete(point) {
-                    deleted = true;
-                    break;
+            node.subdivide();
+            let mid = [
+                self.boundary.x + self.boundary.width / 2.0,
+                self.boundary.y + self.boundary.height / 2.0,
+            ];
+            for quadrant in [
+                Quadrant::NorthWest,
+                Quadrant::NorthEast,
+                Quadrant::SouthWest,
+                Quadrant::SouthEast,
+            ] {
+                let (mut child_lo, mut child_hi) = (lo, hi);
+                for axis in 0..2 {
+                    if (quadrant.index() >> axis) & 1 == 1 {
+                        child_lo[axis] = mid[axis];
+                    } else {
+                        child_hi[axis] = mid[axis];
+                    }
+                }
+                if let (Some(child), Some(slot)) = (self.child(quadrant), node.child_mut(quadrant))
+                {
+                    *slot = child.crop_rec(region, (child_lo, child_hi));
                 }
             }
-            self.try_merge();
-            return deleted;
-        }
-        if let Some(pos) = self.points.iter().position(|p| p == point) {
-            self.points.remove(pos);
-            info!("Deleting point {:?} from Quadtree", point);
-            true
-        } else {
-            false
         }
+        node.points = self
+            .points
+            .iter()
+            .filter(|p| {
+                let coords = [p.x, p.y];
+                (0..2).all(|axis| {
+                    region_min[axis] <= coords[axis] && coords[axis] <= region_max[axis]
+                })
+            })
+            .cloned()
+            .collect();
+        node
     }
 
-    /// Attempts to merge child nodes back into the parent node if possible.
+    /// Splits the tree into independent trees, one for each subtree at the given depth.
     ///
-    /// If all children are not divided and their total number of points is within capacity,
-    /// the children are merged into the parent node.
-    fn try_merge(&mut self) {
-        if !self.divided {
+    /// Each node at `depth` (the root is at depth 0) becomes the root of a returned tree, with
+    /// its own boundary and the capacity and tolerance of this tree. Leaves above `depth` are
+    /// returned as they are, and empty subtrees are left out, so every stored point ends up in
+    /// exactly one returned tree. The trees are returned in Morton order. This tree is
+    /// consumed; clone it first to keep it.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the nodes that become the roots of the returned trees.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+    /// let mut tree = Quadtree::new(&boundary, 1).unwrap();
+    /// tree.insert(Point2D::new(1.0, 1.0, Some(1)));
+    /// tree.insert(Point2D::new(9.0, 9.0, Some(2)));
+    /// let parts = tree.split_at(1);
+    /// // The two points are in opposite quadrants.
+    /// assert_eq!(parts.len(), 2);
+    /// ```
+    pub fn split_at(self, depth: usize) -> Vec<Quadtree<T>> {
+        let mut parts = Vec::new();
+        self.split_rec(depth, &mut parts);
+        parts
+    }
+
+    /// Partitions the boundary into rectangles holding roughly equal numbers of points, e.g.,
+    /// to shard the points or to spread their processing over threads.
+    ///
+    /// The boundary is cut recursively across its longer side, each time at the coordinate
+    /// that splits the points in proportion to the number of rectangles on either side, so
+    /// every rectangle holds about `len / n` points. Points on a cut lie on the edge of two
+    /// rectangles and may be counted by both.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of rectangles.
+    ///
+    /// # Returns
+    ///
+    /// `n` rectangles that tile the boundary.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let mut tree = Quadtree::new(&boundary, 8).unwrap();
+    /// for i in 0..100 {
+    ///     // The points crowd toward the left edge.
+    ///     let x = (i * i) as f64 / 100.0;
+    ///     tree.insert(Point2D::new(x, (i * 37 % 100) as f64, Some(i)));
+    /// }
+    /// let parts = tree.partition(4);
+    /// assert_eq!(parts.len(), 4);
+    /// for part in &parts {
+    ///     assert_eq!(tree.range_search_bbox(part).len(), 25);
+    /// }
+    /// ```
+    pub fn partition(&self, n: usize) -> Vec<Rectangle> {
+        let mut points = Vec::new();
+        self.collect_points(&mut points);
+        let mut coords: Vec<[f64; 2]> = points.iter().map(|p| [p.x, p.y]).collect();
+        let b = &self.boundary;
+        let mut cells = Vec::with_capacity(n);
+        equi_depth_cells(b.min_corner(), b.max_corner(), &mut coords, n, &mut cells);
+        cells
+            .into_iter()
+            .map(|(lo, hi)| Rectangle::from_corners(lo, hi))
+            .collect()
+    }
+
+    /// Appends the non-empty subtrees `depth` levels below this node to `out`.
+    fn split_rec(mut self, depth: usize, out: &mut Vec<Quadtree<T>>) {
+        if depth == 0 || !self.divided {
+            if self.has_points() {
+                out.push(self);
+            }
             return;
         }
-        for child in self.children_mut() {
-            child.try_merge();
+        // Points held by an inner node move down, so that they are not lost with the node.
+        for point in std::mem::take(&mut self.points) {
+            self.insert_unchecked(point);
+        }
+        let children = [
+            self.northwest.take(),
+            self.northeast.take(),
+            self.southwest.take(),
+            self.southeast.take(),
+        ];
+        for child in children.into_iter().flatten() {
+            child.split_rec(depth - 1, out);
+        }
+    }
+
+    /// Returns whether the subtree holds at least one point.
+    fn has_points(&self) -> bool {
+        !self.points.is_empty() || self.children().iter().any(|child| child.has_points())
+    }
+
+    /// Performs a k-nearest neighbor search for the target point.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point for which to find the k nearest neighbors.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the k nearest points, ordered from nearest to farthest.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
+    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
+    /// performance.
+    pub fn knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+    ) -> Vec<Point2D<T>> {
+        if k == 0 || has_nan_coord(target) {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.knn_search_helper::<M>(target, &mut results);
+        results.into_sorted_vec()
+    }
+
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, and `SpartError::InvalidCoordinates` if a coordinate of `target` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+    ) -> Result<Vec<Point2D<T>>, SpartError> {
+        check_knn_query(k, !self.has_points())?;
+        check_query_point(target)?;
+        Ok(self.knn_search::<M>(target, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        targets: &[Point2D<T>],
+        k: usize,
+    ) -> Vec<Vec<Point2D<T>>>
+    where
+        T: Send + Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search around.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// The nearest point of each of the (up to) `k` keys with the nearest points, ordered from
+    /// nearest to farthest.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{EuclideanDistance, Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let mut tree = Quadtree::new(&boundary, 4).unwrap();
+    /// for i in 0..10 {
+    ///     tree.insert(Point2D::new(i as f64, 0.0, Some("cafe")));
+    /// }
+    /// tree.insert(Point2D::new(20.0, 0.0, Some("bakery")));
+    ///
+    /// let query = Point2D::new(0.0, 0.0, None);
+    /// let nearest = tree.knn_distinct_by::<EuclideanDistance, _>(&query, 2, |p| p.data);
+    /// assert_eq!(nearest[0].x, 0.0);
+    /// assert_eq!(nearest[1].data, Some("bakery"));
+    /// ```
+    pub fn knn_distinct_by<M: DistanceMetric<Point2D<T>>, K: PartialEq>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+        key: impl Fn(&Point2D<T>) -> K,
+    ) -> Vec<Point2D<T>> {
+        let mut results = DistinctKnnResults::new(k, key);
+        if k > 0 && !has_nan_coord(target) {
+            self.knn_search_helper::<M>(target, &mut results);
+        }
+        results.into_sorted_vec()
+    }
+
+    /// Helper method for performing the recursive k-nearest neighbor search.
+    fn knn_search_helper<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        results: &mut impl KnnSink<Point2D<T>>,
+    ) {
+        for point in &self.points {
+            let dist_sq = M::distance_sq(point, target);
+            if results.accepts(dist_sq) {
+                results.push(dist_sq, point.clone());
+            }
+        }
+        if self.divided {
+            for child in self.children() {
+                if child.min_distance_sq(target) > results.bound() {
+                    continue;
+                }
+                child.knn_search_helper::<M>(target, results);
+            }
+        }
+    }
+
+    /// Finds the points inside a polygon (boundary included).
+    ///
+    /// The tree is searched with the polygon's bounding rectangle, and the points found are
+    /// then tested against the polygon itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `polygon` - The polygon to search.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the points inside the polygon.
+    pub fn points_in_polygon(&self, polygon: &Polygon) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        self.range_search_bbox_helper(polygon.bbox(), &mut found);
+        found.retain(|p| polygon.contains(p));
+        found
+    }
+
+    /// Finds the k nearest neighbors of a point among the points inside a polygon.
+    ///
+    /// Nodes whose boundary does not intersect the polygon are skipped, so only the part of
+    /// the tree overlapping the polygon is searched.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search near (which may lie outside the polygon).
+    /// * `k` - The number of nearest neighbors to retrieve.
+    /// * `polygon` - The region the neighbors must lie in (boundary included).
+    ///
+    /// # Returns
+    ///
+    /// A vector of up to k points inside the polygon, ordered from nearest to farthest.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance, as for `knn_search`.
+    pub fn knn_in_polygon<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+        polygon: &Polygon,
+    ) -> Vec<Point2D<T>> {
+        if k == 0 || has_nan_coord(target) {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.knn_in_polygon_helper::<M>(target, polygon, &mut results);
+        results.into_sorted_vec()
+    }
+
+    /// Helper method for the recursive polygon-constrained k-nearest neighbor search.
+    fn knn_in_polygon_helper<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        polygon: &Polygon,
+        results: &mut KnnResults<Point2D<T>>,
+    ) {
+        if !polygon.intersects_rect(&self.boundary) {
+            return;
+        }
+        if self.min_distance_sq(target) > results.bound() {
+            return;
+        }
+        for point in &self.points {
+            let dist_sq = M::distance_sq(point, target);
+            if results.accepts(dist_sq) && polygon.contains(point) {
+                results.push(dist_sq, point.clone());
+            }
+        }
+        if self.divided {
+            for child in self.children() {
+                child.knn_in_polygon_helper::<M>(target, polygon, results);
+            }
+        }
+    }
+
+    /// Performs a range search, returning all points within the specified radius of the center point.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search range.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points within the range.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
+    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
+    /// performance.
+    pub fn range_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        self.range_search_into::<M>(center, radius, &mut found);
+        found
+    }
+
+    /// Performs a range search like `range_search`, appending the points to `out` instead of
+    /// returning a new vector.
+    ///
+    /// High-frequency callers can reserve room for the expected number of results once and
+    /// reuse the same buffer for every query, clearing it in between; `out` is never cleared
+    /// here.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    /// * `out` - The buffer that receives the points.
+    pub fn range_search_into<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+        out: &mut Vec<Point2D<T>>,
+    ) {
+        if radius >= 0.0 {
+            self.range_search_helper::<M>(center, radius * radius, usize::MAX, out);
+        }
+    }
+
+    /// Performs a range search like `range_search`, stopping once `max_results` points are
+    /// found.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius.
+    /// * `max_results` - The maximum number of points to return.
+    ///
+    /// # Returns
+    ///
+    /// Up to `max_results` points within the range, and whether more points matched. Which
+    /// points are returned when the search is truncated depends on the layout of the tree.
+    pub fn range_search_limited<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+        max_results: usize,
+    ) -> LimitedResults<Point2D<T>> {
+        let mut found = Vec::new();
+        if radius >= 0.0 {
+            let limit = LimitedResults::<Point2D<T>>::limit_for(max_results);
+            self.range_search_helper::<M>(center, radius * radius, limit, &mut found);
+        }
+        LimitedResults::from_overfull(found, max_results)
+    }
+
+    /// Like `range_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `range_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidRadius` if `radius` is negative or NaN, and
+    /// `SpartError::InvalidCoordinates` if a coordinate of `center` is NaN.
+    pub fn try_range_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> Result<Vec<Point2D<T>>, SpartError> {
+        check_range_query(radius)?;
+        check_query_point(center)?;
+        Ok(self.range_search::<M>(center, radius))
+    }
+
+    /// Performs `range_search` for each of several centers, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `centers` - The query points.
+    /// * `radius` - The search radius around each center.
+    ///
+    /// # Returns
+    ///
+    /// The results of `range_search` for each center, in the order of `centers`.
+    #[cfg(feature = "parallel")]
+    pub fn par_range_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        centers: &[Point2D<T>],
+        radius: f64,
+    ) -> Vec<Vec<Point2D<T>>>
+    where
+        T: Send + Sync,
+    {
+        use rayon::prelude::*;
+        centers
+            .par_iter()
+            .map(|center| self.range_search::<M>(center, radius))
+            .collect()
+    }
+
+    /// Helper method for performing the recursive range search, which stops once `found`
+    /// holds `limit` points.
+    fn range_search_helper<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        center: &Point2D<T>,
+        radius_sq: f64,
+        limit: usize,
+        found: &mut Vec<Point2D<T>>,
+    ) {
+        if found.len() >= limit || self.min_distance_sq(center) > radius_sq {
+            return;
+        }
+        for point in &self.points {
+            if M::distance_sq(point, center) <= radius_sq {
+                found.push(point.clone());
+                if found.len() >= limit {
+                    return;
+                }
+            }
+        }
+        if self.divided {
+            for child in self.children() {
+                child.range_search_helper::<M>(center, radius_sq, limit, found);
+            }
+        }
+    }
+
+    /// Performs a k-nearest neighbor search like `knn_search`, reusing the buffers of `ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The query context, which holds the results until its next use.
+    /// * `target` - The point for which to find the k nearest neighbors.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// The k nearest points, ordered from nearest to farthest.
+    pub fn knn_search_with<'c, M: DistanceMetric<Point2D<T>>>(
+        &self,
+        ctx: &'c mut QueryContext<Point2D<T>>,
+        target: &Point2D<T>,
+        k: usize,
+    ) -> &'c [Point2D<T>] {
+        ctx.knn(k, |results| {
+            if !has_nan_coord(target) {
+                self.knn_search_helper::<M>(target, results);
+            }
+        })
+    }
+
+    /// Performs a range search like `range_search`, reusing the buffers of `ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The query context, which holds the results until its next use.
+    /// * `center` - The center of the search range.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// The points within the range.
+    pub fn range_search_with<'c, M: DistanceMetric<Point2D<T>>>(
+        &self,
+        ctx: &'c mut QueryContext<Point2D<T>>,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> &'c [Point2D<T>] {
+        ctx.collect(|found| self.range_search_into::<M>(center, radius, found))
+    }
+
+    /// Performs a range search, returning all points inside the query rectangle
+    /// (boundary inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points inside the query rectangle.
+    pub fn range_search_bbox(&self, query: &Rectangle) -> Vec<Point2D<T>> {
+        hot_trace!("Performing range search", query = query);
+        let mut found = Vec::new();
+        self.range_search_bbox_helper(query, &mut found);
+        found
+    }
+
+    /// Performs a rectangle range search like `range_search_bbox`, stopping once
+    /// `max_results` points are found.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    /// * `max_results` - The maximum number of points to return.
+    ///
+    /// # Returns
+    ///
+    /// Up to `max_results` points inside the rectangle, and whether more points matched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let mut tree = Quadtree::new(&boundary, 4).unwrap();
+    /// for i in 0..100 {
+    ///     tree.insert(Point2D::new(i as f64, i as f64, None::<()>));
+    /// }
+    /// let page = tree.range_search_bbox_limited(&boundary, 10);
+    /// assert_eq!(page.results.len(), 10);
+    /// assert!(page.truncated);
+    /// ```
+    pub fn range_search_bbox_limited(
+        &self,
+        query: &Rectangle,
+        max_results: usize,
+    ) -> LimitedResults<Point2D<T>> {
+        let mut found = Vec::new();
+        let limit = LimitedResults::<Point2D<T>>::limit_for(max_results);
+        self.range_search_bbox_where(query, &|_| true, limit, &mut found);
+        LimitedResults::from_overfull(found, max_results)
+    }
+
+    /// Estimates the number of points inside `query`, without reading the points.
+    ///
+    /// Each node intersecting the query counts for its number of points times the fraction of
+    /// its boundary inside the query, so the estimate is close when the points are spread evenly
+    /// within each node. It is cheap enough for a query planner to call before choosing between
+    /// the index and another access path.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    ///
+    /// # Returns
+    ///
+    /// The estimated number of points inside `query`.
+    pub fn estimate_count_in_bbox(&self, query: &Rectangle) -> f64 {
+        if !self.boundary.intersects(query) {
+            return 0.0;
+        }
+        let own = self.points.len() as f64 * overlap_fraction(&self.boundary, query);
+        own + self
+            .children()
+            .into_iter()
+            .map(|child| child.estimate_count_in_bbox(query))
+            .sum::<f64>()
+    }
+
+    /// Finds the points inside a geographic bounding box, for trees storing longitude as `x` and
+    /// latitude as `y` (in degrees).
+    ///
+    /// A box crossing the antimeridian is searched as two windows, one on each side of it,
+    /// which do not overlap, so no point is returned twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `bbox` - The geographic bounding box.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points inside the box.
+    pub fn range_search_geo_bbox(&self, bbox: &GeoBBox) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        for window in bbox.to_rectangles() {
+            self.range_search_bbox_helper(&window, &mut found);
+        }
+        found
+    }
+
+    /// Finds the points within `radius` meters (along the surface of the Earth) of `center`,
+    /// for trees storing longitude as `x` and latitude as `y` (in degrees).
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius in meters.
+    ///
+    /// # Returns
+    ///
+    /// The points paired with their initial bearings from `center` (in degrees clockwise from
+    /// north), sorted by bearing and then by distance.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if `center` is not a valid longitude and
+    /// latitude or `radius` is negative or not finite.
+    pub fn within_radius_sorted_by_bearing(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> Result<Vec<(Point2D<T>, f64)>, SpartError> {
+        let bbox = GeoBBox::around(center, radius)?;
+        Ok(sort_by_bearing(
+            center,
+            radius,
+            self.range_search_geo_bbox(&bbox),
+        ))
+    }
+
+    /// Helper method for performing the recursive rectangle range search.
+    fn range_search_bbox_helper(&self, query: &Rectangle, found: &mut Vec<Point2D<T>>) {
+        self.range_search_bbox_where(query, &|_| true, usize::MAX, found);
+    }
+
+    /// Helper method for the recursive rectangle range search, keeping only the points for
+    /// which `keep` returns `true` and stopping once `found` holds `limit` points.
+    fn range_search_bbox_where(
+        &self,
+        query: &Rectangle,
+        keep: &impl Fn(&Point2D<T>) -> bool,
+        limit: usize,
+        found: &mut Vec<Point2D<T>>,
+    ) {
+        if found.len() >= limit || !self.boundary.intersects(query) {
+            return;
+        }
+        let room = limit - found.len();
+        found.extend(
+            self.points
+                .iter()
+                .filter(|p| query.contains(p) && keep(p))
+                .take(room)
+                .cloned(),
+        );
+        if self.divided {
+            for child in self.children() {
+                child.range_search_bbox_where(query, keep, limit, found);
+            }
+        }
+    }
+
+    /// Counts the points inside `bbox` on a grid of `width` by `height` cells.
+    ///
+    /// # Arguments
+    ///
+    /// * `bbox` - The rectangle covered by the grid.
+    /// * `width` - The number of columns (at least one).
+    /// * `height` - The number of rows (at least one).
+    ///
+    /// # Returns
+    ///
+    /// A `DensityGrid` holding the number of points in each cell.
+    pub fn density_grid(&self, bbox: &Rectangle, width: usize, height: usize) -> DensityGrid {
+        let mut grid = DensityGrid::new(bbox, width, height);
+        self.density_grid_helper(&mut grid);
+        grid
+    }
+
+    /// Counts the points of the tree on a grid of square cells covering its boundary.
+    ///
+    /// The grid starts at the lower corner of the boundary and has as many cells of side
+    /// `resolution` as needed to cover it, so its last column and row may extend past the
+    /// boundary. Use `DensityGrid::occupancy` or `DensityGrid::is_occupied` for a boolean
+    /// occupancy grid and `DensityGrid::cell_of` to find the cell of a position.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - The side length of a cell.
+    ///
+    /// # Returns
+    ///
+    /// A `DensityGrid` holding the number of points in each cell.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if `resolution` is not positive and finite, or
+    /// is so small that the grid would have more than `u32::MAX` cells.
+    pub fn to_occupancy_grid(&self, resolution: f64) -> Result<DensityGrid, SpartError> {
+        if !(resolution > 0.0 && resolution.is_finite()) {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("resolution {resolution} is not a positive finite cell size"),
+            });
+        }
+        let columns = (self.boundary.width / resolution).ceil().max(1.0);
+        let rows = (self.boundary.height / resolution).ceil().max(1.0);
+        if columns * rows > u32::MAX as f64 {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("resolution {resolution} needs {columns} by {rows} cells"),
+            });
+        }
+        let bounds = Rectangle {
+            x: self.boundary.x,
+            y: self.boundary.y,
+            width: columns * resolution,
+            height: rows * resolution,
+        };
+        let mut grid = DensityGrid::new(&bounds, columns as usize, rows as usize);
+        self.density_grid_helper(&mut grid);
+        Ok(grid)
+    }
+
+    /// Helper method for counting the points of the subtree into a density grid.
+    fn density_grid_helper(&self, grid: &mut DensityGrid) {
+        if !self.boundary.intersects(grid.bounds()) {
+            return;
+        }
+        for point in &self.points {
+            grid.add(point);
+        }
+        if self.divided {
+            for child in self.children() {
+                child.density_grid_helper(grid);
+            }
+        }
+    }
+
+    /// Finds the parts of a region where no stored point is within `radius`, on a grid.
+    ///
+    /// The region is divided into square cells of side `resolution` (the cells in the last
+    /// column and row are clipped to the region), and a cell is uncovered if no stored point
+    /// is within `radius` of its center. Instead of testing every cell, blocks of cells are
+    /// tested at once: a block is skipped when one point covers all of its cell centers, and
+    /// reported whole when no node of the tree comes within `radius` of them; only the
+    /// other blocks are split further.
+    ///
+    /// # Arguments
+    ///
+    /// * `bbox` - The region to analyze.
+    /// * `radius` - The coverage radius of each point.
+    /// * `resolution` - The side length of the grid cells.
+    ///
+    /// # Returns
+    ///
+    /// Rectangles made of whole uncovered cells (a block found uncovered at once is returned
+    /// as one rectangle), so their total area is the uncovered area at this resolution.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidRadius` if `radius` is negative or NaN, and
+    /// `SpartError::InvalidCoordinates` if `resolution` is not positive and finite.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let region = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+    /// let mut sensors = Quadtree::new(&region, 4).unwrap();
+    /// sensors.insert(Point2D::new(2.5, 5.0, Some("a")));
+    /// sensors.insert(Point2D::new(7.5, 2.5, Some("b")));
+    /// let gaps = sensors.uncovered_area(&region, 3.0, 1.0).unwrap();
+    /// let area: f64 = gaps.iter().map(|r| r.area()).sum();
+    /// assert!(area > 0.0 && area < 100.0);
+    /// ```
+    pub fn uncovered_area(
+        &self,
+        bbox: &Rectangle,
+        radius: f64,
+        resolution: f64,
+    ) -> Result<Vec<Rectangle>, SpartError> {
+        check_range_query(radius)?;
+        if !(resolution.is_finite() && resolution > 0.0) {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("grid resolution {resolution} is not positive and finite"),
+            });
+        }
+        let grid = CoverageGrid {
+            bbox: bbox.clone(),
+            resolution,
+            columns: (bbox.width / resolution).ceil().max(1.0) as usize,
+            rows: (bbox.height / resolution).ceil().max(1.0) as usize,
+        };
+        let mut uncovered = Vec::new();
+        self.uncovered_blocks(
+            &grid,
+            (0, grid.columns),
+            (0, grid.rows),
+            radius * radius,
+            &mut uncovered,
+        );
+        Ok(uncovered)
+    }
+
+    /// Adds the uncovered parts of the block of cells `columns` by `rows` to `out`.
+    fn uncovered_blocks(
+        &self,
+        grid: &CoverageGrid,
+        columns: (usize, usize),
+        rows: (usize, usize),
+        radius_sq: f64,
+        out: &mut Vec<Rectangle>,
+    ) {
+        let centers = grid.centers(columns, rows);
+        let mut near = false;
+        if self.covers(&centers, radius_sq, &mut near) {
+            return;
+        }
+        if !near {
+            out.push(grid.block(columns, rows));
+            return;
+        }
+        // With more than one cell left, at least one side can be halved; a single cell's
+        // centers are one position, which is either covered or not.
+        let (c0, c1) = columns;
+        let (r0, r1) = rows;
+        let (c_mid, r_mid) = ((c0 + c1) / 2, (r0 + r1) / 2);
+        for cols in [(c0, c_mid), (c_mid, c1)] {
+            for rws in [(r0, r_mid), (r_mid, r1)] {
+                if cols.0 < cols.1 && rws.0 < rws.1 && (cols, rws) != (columns, rows) {
+                    self.uncovered_blocks(grid, cols, rws, radius_sq, out);
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if a point of the subtree is within the radius of every position in
+    /// `area`. Sets `near` if a point is within the radius of some position in `area`.
+    fn covers(&self, area: &Rectangle, radius_sq: f64, near: &mut bool) -> bool {
+        if rect_gap_sq(&self.boundary, area) > radius_sq {
+            return false;
+        }
+        for point in &self.points {
+            let (min_sq, max_sq) = rect_distance_sq(area, point);
+            if max_sq <= radius_sq {
+                return true;
+            }
+            *near |= min_sq <= radius_sq;
+        }
+        self.divided
+            && self
+                .children()
+                .into_iter()
+                .any(|child| child.covers(area, radius_sq, near))
+    }
+
+    /// Renders the density of the points inside `bbox` as a PNG heatmap and writes it to a file.
+    ///
+    /// Each pixel shows the number of points in its cell of the `density_grid`, on a
+    /// logarithmic scale.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to write.
+    /// * `bbox` - The rectangle covered by the image.
+    /// * `width` - The width of the image in pixels.
+    /// * `height` - The height of the image in pixels.
+    /// * `colormap` - The color scale to use.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the file cannot be written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::heatmap::Colormap;
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let mut qt = Quadtree::new(&boundary, 4).unwrap();
+    /// for i in 0..100 {
+    ///     qt.insert(Point2D::new(i as f64, (i * i % 100) as f64, None::<()>));
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("spart_heatmap_doctest.png");
+    /// qt.render_heatmap_png(&path, &boundary, 64, 64, Colormap::Heat).unwrap();
+    /// assert!(std::fs::read(&path).unwrap().starts_with(b"\x89PNG"));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    #[cfg(feature = "image")]
+    pub fn render_heatmap_png(
+        &self,
+        path: impl AsRef<Path>,
+        bbox: &Rectangle,
+        width: usize,
+        height: usize,
+        colormap: Colormap,
+    ) -> Result<(), SpartError> {
+        info!(
+            "Rendering {}x{} heatmap of {:?} to {}",
+            width,
+            height,
+            bbox,
+            path.as_ref().display()
+        );
+        self.density_grid(bbox, width, height)
+            .write_png(path, colormap)
+    }
+
+    /// Returns the points inside map tile `(x, y)` at zoom level `z` of the given tiling scheme.
+    ///
+    /// Each point inside the scheme's extent belongs to exactly one tile per zoom level (see
+    /// `TileScheme`), so adjacent tiles never return the same point.
+    ///
+    /// # Arguments
+    ///
+    /// * `scheme` - The tiling scheme, e.g. `TileScheme::web_mercator()`.
+    /// * `z` - The zoom level.
+    /// * `x` - The tile column.
+    /// * `y` - The tile row.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points inside the tile; empty if the tile coordinates are out of range.
+    pub fn query_tile(&self, scheme: &TileScheme, z: u8, x: u32, y: u32) -> Vec<Point2D<T>> {
+        let bounds = match scheme.tile_search_bounds(z, x, y) {
+            Some(bounds) => bounds,
+            None => return Vec::new(),
+        };
+        let mut found = self.range_search_bbox(&bounds);
+        found.retain(|p| scheme.tile_for(z, p) == Some((x, y)));
+        found
+    }
+
+    /// Returns up to `max_points` points inside `bbox`, chosen by priority and spread over the
+    /// box, e.g., to pick the labels or points of interest to draw on a map.
+    ///
+    /// The box is divided into a grid of about `max_points` cells. The highest-priority point
+    /// of every cell is picked before the second-highest of any cell, and so on, so that a
+    /// dense cluster of important points does not crowd out the rest of the box.
+    ///
+    /// # Arguments
+    ///
+    /// * `bbox` - The rectangle to pick points from.
+    /// * `max_points` - The largest number of points to return.
+    /// * `priority` - The importance of a point; higher values are picked first, and NaN
+    ///   values last.
+    ///
+    /// # Returns
+    ///
+    /// At most `max_points` points inside `bbox`, in the order they were picked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let mut tree = Quadtree::new(&boundary, 4).unwrap();
+    /// // A cluster of popular places in one corner, and a quieter one in the other.
+    /// for i in 0..10 {
+    ///     tree.insert(Point2D::new(1.0 + i as f64, 1.0, Some(100 + i)));
+    ///     tree.insert(Point2D::new(90.0 + i as f64, 90.0, Some(i)));
+    /// }
+    ///
+    /// let picked = tree.thinned_in_bbox(&boundary, 4, |p| p.data.unwrap() as f64);
+    /// assert_eq!(picked.len(), 4);
+    /// assert_eq!(picked[0].data, Some(109));
+    /// assert!(picked.iter().any(|p| p.data < Some(100)));
+    /// ```
+    pub fn thinned_in_bbox(
+        &self,
+        bbox: &Rectangle,
+        max_points: usize,
+        priority: impl Fn(&Point2D<T>) -> f64,
+    ) -> Vec<Point2D<T>> {
+        thin_by_priority(bbox, self.range_search_bbox(bbox), max_points, priority)
+    }
+
+    /// Returns `true` if the tree holds a point matching `point`.
+    ///
+    /// Points match if they are equal, or within the tree's tolerance (see `with_tolerance`).
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to look for.
+    pub fn contains(&self, point: &Point2D<T>) -> bool {
+        let window = Self::tolerance_window(point, self.tolerance);
+        if !self.boundary.intersects(&window) {
+            return false;
+        }
+        self.contains_unchecked(point, self.tolerance)
+    }
+
+    /// Checks whether the subtree holds a point matching `point` within `tolerance`.
+    fn contains_unchecked(&self, point: &Point2D<T>, tolerance: f64) -> bool {
+        if self.divided {
+            return self
+                .candidate_quadrants(point, tolerance)
+                .into_iter()
+                .any(|quadrant| {
+                    self.child(quadrant)
+                        .is_some_and(|child| child.contains_unchecked(point, tolerance))
+                });
+        }
+        self.points.iter().any(|p| p.approx_eq(point, tolerance))
+    }
+
+    /// Replaces a point matching `old` with `new`.
+    ///
+    /// Points are matched like in `delete`, so the tree's tolerance applies to `old`.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The point to replace.
+    /// * `new` - The replacement point.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and replaced. If `new` lies outside the boundary,
+    /// the tree is left unchanged and `false` is returned.
+    pub fn update(&mut self, old: &Point2D<T>, new: Point2D<T>) -> bool {
+        if !self.boundary.contains(&new) || !self.delete(old) {
+            return false;
+        }
+        self.insert_unchecked(new);
+        true
+    }
+
+    /// Deletes a point from the quadtree.
+    ///
+    /// Returns `true` if the point was found and deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to delete.
+    pub fn delete(&mut self, point: &Point2D<T>) -> bool {
+        let window = Self::tolerance_window(point, self.tolerance);
+        if !self.boundary.intersects(&window) {
+            return false;
+        }
+        self.delete_unchecked(point, self.tolerance, true)
+    }
+
+    /// Deletes several points from the quadtree.
+    ///
+    /// Each point removes at most one matching stored point, as with `delete`. Nodes are
+    /// merged once after all points have been removed instead of after every deletion, which
+    /// makes this much faster than calling `delete` in a loop.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to delete.
+    ///
+    /// # Returns
+    ///
+    /// The number of points that were found and deleted.
+    pub fn delete_bulk(&mut self, points: &[Point2D<T>]) -> usize {
+        let mut deleted = 0;
+        for point in points {
+            let window = Self::tolerance_window(point, self.tolerance);
+            if self.boundary.intersects(&window)
+                && self.delete_unchecked(point, self.tolerance, false)
+            {
+                deleted += 1;
+            }
+        }
+        self.try_merge();
+        deleted
+    }
+
+    /// Keeps only the points for which `keep` returns `true`, deleting the others.
+    ///
+    /// Nodes are merged once after all points have been visited, as in `delete_bulk`.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - Called once for each stored point.
+    ///
+    /// # Returns
+    ///
+    /// The number of points that were deleted.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Point2D<T>) -> bool) -> usize {
+        let deleted = self.retain_unmerged(&mut keep);
+        self.try_merge();
+        deleted
+    }
+
+    /// Keeps only the points of the subtree for which `keep` returns `true`, without merging
+    /// nodes.
+    fn retain_unmerged(&mut self, keep: &mut impl FnMut(&Point2D<T>) -> bool) -> usize {
+        let before = self.points.len();
+        self.points.retain(|p| keep(p));
+        let mut deleted = before - self.points.len();
+        for child in self.children_mut() {
+            deleted += child.retain_unmerged(keep);
+        }
+        deleted
+    }
+
+    /// Deletes a point matching `point` within `tolerance` from the subtree, merging the
+    /// nodes along the way if `merge` is set.
+    fn delete_unchecked(&mut self, point: &Point2D<T>, tolerance: f64, merge: bool) -> bool {
+        if self.divided {
+            let mut deleted = false;
+            for quadrant in self.candidate_quadrants(point, tolerance) {
+                if self
+                    .child_mut(quadrant)
+                    .is_some_and(|child| child.delete_unchecked(point, tolerance, merge))
+                {
+                    deleted = true;
+                    break;
+                }
+            }
+            if merge {
+                self.try_merge();
+            }
+            return deleted;
+        }
+        if let Some(pos) = self
+            .points
+            .iter()
+            .position(|p| p.approx_eq(point, tolerance))
+        {
+            self.points.remove(pos);
+            hot_trace!("Deleting point from Quadtree", point = point);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the sequence of quadrants leading from the root to the leaf that contains
+    /// (or would contain) the given point.
+    ///
+    /// The path follows the same routing as `insert`, so it identifies the cell a point landed
+    /// in. Concatenating the quadrants' `index()` digits yields the cell's Morton prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to locate.
+    ///
+    /// # Returns
+    ///
+    /// The path of quadrants (empty if the root is a leaf), or `None` if the point lies
+    /// outside the tree's boundary.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::{Quadrant, Quadtree};
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let mut qt = Quadtree::new(&boundary, 1).unwrap();
+    /// qt.insert(Point2D::new(10.0, 10.0, Some(1)));
+    /// qt.insert(Point2D::new(90.0, 90.0, Some(2)));
+    ///
+    /// let path = qt.node_path(&Point2D::new(90.0, 90.0, None)).unwrap();
+    /// assert_eq!(path, vec![Quadrant::SouthEast]);
+    /// let morton = path.iter().fold(0, |key, q| key * 4 + q.index());
+    /// assert_eq!(morton, 3);
+    /// ```
+    pub fn node_path(&self, point: &Point2D<T>) -> Option<Vec<Quadrant>> {
+        self.locate(point).map(|(path, _)| path)
+    }
+
+    /// Returns the boundary of the leaf node that contains (or would contain) the given point.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to locate.
+    ///
+    /// # Returns
+    ///
+    /// The leaf's boundary, or `None` if the point lies outside the tree's boundary.
+    pub fn leaf_boundary(&self, point: &Point2D<T>) -> Option<Rectangle> {
+        self.locate(point).map(|(_, leaf)| leaf.boundary.clone())
+    }
+
+    /// Finds the occupied cell at a given depth that is nearest to a point, e.g., for sparse
+    /// voxel lookups or to fall back to nearby samples when the point's own cell is empty.
+    ///
+    /// The cells are the nodes at `depth` (the root is at depth 0), and the leaves above it
+    /// where the tree is shallower. A cell is occupied if any stored point lies in it, and its
+    /// distance to the point is the distance to its boundary, so a cell containing the point
+    /// is at distance 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The query point.
+    /// * `depth` - The depth of the cells to search.
+    ///
+    /// # Returns
+    ///
+    /// The boundary of the nearest occupied cell and the points in it, or `None` if the tree
+    /// is empty or the point has a NaN coordinate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    /// let mut tree = Quadtree::new(&boundary, 1).unwrap();
+    /// tree.insert(Point2D::new(10.0, 10.0, Some(1)));
+    /// tree.insert(Point2D::new(90.0, 60.0, Some(2)));
+    ///
+    /// // The quadrant around (80, 90) is empty; the nearest occupied one holds point 2.
+    /// let (cell, points) = tree.nearest_occupied_cell(&Point2D::new(80.0, 90.0, None), 1).unwrap();
+    /// assert_eq!((cell.x, cell.y, cell.width), (50.0, 50.0, 50.0));
+    /// assert_eq!(points[0].data, Some(2));
+    /// ```
+    pub fn nearest_occupied_cell(
+        &self,
+        point: &Point2D<T>,
+        depth: usize,
+    ) -> Option<(Rectangle, Vec<Point2D<T>>)> {
+        if has_nan_coord(point) {
+            return None;
+        }
+        let mut nearest = None;
+        self.nearest_cell_rec(point, depth, Vec::new(), &mut nearest);
+        nearest
+            .map(|(_, node, points)| (node.boundary.clone(), points.into_iter().cloned().collect()))
+    }
+
+    /// Visits the cells at `depth` below this node nearest first, skipping those farther away
+    /// than the nearest occupied cell found so far. `inherited` holds the points of the
+    /// ancestors that lie in this node, so that they count toward the cell they fall in.
+    fn nearest_cell_rec<'a>(
+        &'a self,
+        point: &Point2D<T>,
+        depth: usize,
+        mut inherited: Vec<&'a Point2D<T>>,
+        nearest: &mut Option<NearestCell<'a, T>>,
+    ) {
+        let distance = self.boundary.min_distance(point);
+        if nearest
+            .as_ref()
+            .is_some_and(|(best, _, _)| distance >= *best)
+        {
+            return;
+        }
+        if depth == 0 || !self.divided {
+            self.collect_points(&mut inherited);
+            if !inherited.is_empty() {
+                *nearest = Some((distance, self, inherited));
+            }
+            return;
+        }
+        inherited.extend(self.points.iter());
+        let mut children: Vec<_> = [
+            Quadrant::NorthWest,
+            Quadrant::NorthEast,
+            Quadrant::SouthWest,
+            Quadrant::SouthEast,
+        ]
+        .into_iter()
+        .filter_map(|quadrant| self.child(quadrant).map(|child| (quadrant, child)))
+        .collect();
+        children.sort_by(|(_, a), (_, b)| {
+            a.boundary
+                .min_distance(point)
+                .total_cmp(&b.boundary.min_distance(point))
+        });
+        for (quadrant, child) in children {
+            let points = inherited
+                .iter()
+                .filter(|p| self.quadrant_for(p) == quadrant)
+                .copied()
+                .collect();
+            child.nearest_cell_rec(point, depth - 1, points, nearest);
+        }
+    }
+
+    /// Descends to the leaf a point is routed to, recording the quadrants along the way.
+    fn locate(&self, point: &Point2D<T>) -> Option<(Vec<Quadrant>, &Quadtree<T>)> {
+        if !self.boundary.contains(point) {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = self;
+        while node.divided {
+            let quadrant = node.quadrant_for(point);
+            match node.child(quadrant) {
+                Some(child) => {
+                    path.push(quadrant);
+                    node = child;
+                }
+                None => break,
+            }
+        }
+        Some((path, node))
+    }
+
+    /// Returns the stored point at exactly the coordinates of `point`, ignoring its data and
+    /// the tolerance.
+    pub(crate) fn point_at(&self, point: &Point2D<T>) -> Option<&Point2D<T>> {
+        let (_, node) = self.locate(point)?;
+        node.points
+            .iter()
+            .find(|p| p.x == point.x && p.y == point.y)
+    }
+
+    /// Returns the stored point at exactly the coordinates of `point` for modification.
+    ///
+    /// Callers must not change the coordinates, since the point would no longer be where the
+    /// tree routes it.
+    pub(crate) fn point_at_mut(&mut self, point: &Point2D<T>) -> Option<&mut Point2D<T>> {
+        if self.divided {
+            let quadrant = self.quadrant_for(point);
+            if self.child(quadrant).is_some() {
+                return self
+                    .child_mut(quadrant)
+                    .and_then(|child| child.point_at_mut(point));
+            }
+        }
+        self.points
+            .iter_mut()
+            .find(|p| p.x == point.x && p.y == point.y)
+    }
+
+    /// Attempts to merge child nodes back into the parent node if possible.
+    ///
+    /// If all children are not divided and their total number of points is within the merge
+    /// threshold (by default, the capacity), the children are merged into the parent node.
+    fn try_merge(&mut self) {
+        if !self.divided {
+            return;
+        }
+        for child in self.children_mut() {
+            child.try_merge();
+        }
+        let children = self.children();
+        if children.iter().all(|child| !child.divided) {
+            let total_points: usize = children.iter().map(|child| child.points.len()).sum();
+            let limit = (self.capacity as f64 * self.merge_threshold).floor() as usize;
+            if total_points <= limit {
+                let mut merged_points = Vec::with_capacity(total_points);
+                if let Some(child) = self.northeast.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.northwest.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.southeast.take() {
+                    merged_points.extend(child.points);
+                }
+                if let Some(child) = self.southwest.take() {
+                    merged_points.extend(child.points);
+                }
+                hot_trace!(
+                    "Merging children into parent node",
+                    boundary = self.boundary,
+                    points = merged_points.len()
+                );
+                self.points.extend(merged_points);
+                self.divided = false;
+            }
+        }
+    }
+}
+
+/// Returns `true` if `point` lies strictly inside the counter-clockwise polygon `polygon`.
+///
+/// Repeated vertices are skipped; a polygon with fewer than three distinct vertices has no
+/// interior.
+fn strictly_inside<T>(polygon: &[&Point2D<T>], point: &Point2D<T>) -> bool {
+    let mut edges = 0;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        if a.x == b.x && a.y == b.y {
+            continue;
+        }
+        if cross(a, b, point) <= 0.0 {
+            return false;
+        }
+        edges += 1;
+    }
+    edges >= 3
+}
+
+/// Returns the Morton digit of the child quadrant of `boundary` a point inside it is routed to.
+///
+/// Points on a split line go to the north or west side. Routing by the midpoint (rather than by
+/// testing the children's boundaries) assigns every point in the closed boundary to exactly one
+/// child, even when rounding makes the children slightly smaller than the parent.
+pub(crate) fn quadrant_index<T>(boundary: &Rectangle, point: &Point2D<T>) -> usize {
+    let mid_x = boundary.x + boundary.width / 2.0;
+    let mid_y = boundary.y + boundary.height / 2.0;
+    usize::from(point.x > mid_x) | (usize::from(point.y > mid_y) << 1)
+}
+
+/// Returns the boundaries of the four child quadrants of `boundary`, in Morton order.
+///
+/// The eastern and southern children are rounded up to reach the far edges of `boundary`,
+/// which `x + w + w` can fall short of.
+pub(crate) fn child_boundaries(boundary: &Rectangle) -> [Rectangle; 4] {
+    let Rectangle {
+        x,
+        y,
+        width,
+        height,
+    } = *boundary;
+    let w = width / 2.0;
+    let h = height / 2.0;
+    let east_w = covering_extent(x + w, x + width);
+    let south_h = covering_extent(y + h, y + height);
+    [
+        Rectangle {
+            x,
+            y,
+            width: w,
+            height: h,
+        },
+        Rectangle {
+            x: x + w,
+            y,
+            width: east_w,
+            height: h,
+        },
+        Rectangle {
+            x,
+            y: y + h,
+            width: w,
+            height: south_h,
+        },
+        Rectangle {
+            x: x + w,
+            y: y + h,
+            width: east_w,
+            height: south_h,
+        },
+    ]
+}
+
+/// Computes the Morton code of `point` relative to `boundary`, `MAX_DEPTH` levels deep.
+///
+/// Each level contributes one quadrant digit, chosen with the same midpoint routing as
+/// `Quadtree::insert`, so sorting points by their code lists them leaf by leaf in the order
+/// the tree stores them.
+pub(crate) fn morton_code<T>(boundary: &Rectangle, point: &Point2D<T>) -> u64 {
+    let mut x = boundary.x;
+    let mut y = boundary.y;
+    let mut w = boundary.width;
+    let mut h = boundary.height;
+    let mut code = 0u64;
+    for _ in 0..MAX_DEPTH {
+        w /= 2.0;
+        h /= 2.0;
+        let east = point.x > x + w;
+        let south = point.y > y + h;
+        if east {
+            x += w;
+        }
+        if south {
+            y += h;
+        }
+        code = (code << 2) | (east as u64) | ((south as u64) << 1);
+    }
+    code
+}
+
+/// The grid of cells used by `Quadtree::uncovered_area`.
+struct CoverageGrid {
+    bbox: Rectangle,
+    resolution: f64,
+    columns: usize,
+    rows: usize,
+}
+
+impl CoverageGrid {
+    /// Returns the lower and upper coordinates of cell `i` along an axis starting at `start`
+    /// and ending at `end`.
+    fn span(&self, start: f64, end: f64, i: usize) -> (f64, f64) {
+        let lo = start + i as f64 * self.resolution;
+        (lo, (lo + self.resolution).min(end))
+    }
+
+    /// Returns the rectangle covered by the block of cells `columns` by `rows` (end exclusive).
+    fn block(&self, columns: (usize, usize), rows: (usize, usize)) -> Rectangle {
+        let (x_end, y_end) = (
+            self.bbox.x + self.bbox.width,
+            self.bbox.y + self.bbox.height,
+        );
+        let (x0, _) = self.span(self.bbox.x, x_end, columns.0);
+        let (_, x1) = self.span(self.bbox.x, x_end, columns.1 - 1);
+        let (y0, _) = self.span(self.bbox.y, y_end, rows.0);
+        let (_, y1) = self.span(self.bbox.y, y_end, rows.1 - 1);
+        Rectangle::from_corners([x0, y0], [x1, y1])
+    }
+
+    /// Returns the smallest rectangle holding the centers of the block's cells.
+    fn centers(&self, columns: (usize, usize), rows: (usize, usize)) -> Rectangle {
+        let (x_end, y_end) = (
+            self.bbox.x + self.bbox.width,
+            self.bbox.y + self.bbox.height,
+        );
+        let center = |start: f64, end: f64, i: usize| {
+            let (lo, hi) = self.span(start, end, i);
+            (lo + hi) / 2.0
+        };
+        Rectangle::from_corners(
+            [
+                center(self.bbox.x, x_end, columns.0),
+                center(self.bbox.y, y_end, rows.0),
+            ],
+            [
+                center(self.bbox.x, x_end, columns.1 - 1),
+                center(self.bbox.y, y_end, rows.1 - 1),
+            ],
+        )
+    }
+}
+
+/// Returns the smallest squared distance between two rectangles.
+fn rect_gap_sq(a: &Rectangle, b: &Rectangle) -> f64 {
+    let dx = (a.x - (b.x + b.width)).max(b.x - (a.x + a.width)).max(0.0);
+    let dy = (a.y - (b.y + b.height))
+        .max(b.y - (a.y + a.height))
+        .max(0.0);
+    dx * dx + dy * dy
+}
+
+/// Returns the smallest and largest squared distance from `point` to the positions in `rect`.
+fn rect_distance_sq<T>(rect: &Rectangle, point: &Point2D<T>) -> (f64, f64) {
+    let axis = |c: f64, lo: f64, hi: f64| {
+        let gap = (lo - c).max(c - hi).max(0.0);
+        let far = (c - lo).abs().max((hi - c).abs());
+        (gap * gap, far * far)
+    };
+    let (min_x, max_x) = axis(point.x, rect.x, rect.x + rect.width);
+    let (min_y, max_y) = axis(point.y, rect.y, rect.y + rect.height);
+    (min_x + min_y, max_x + max_y)
+}
+
+impl<T: Elevation + Clone + PartialEq + std::fmt::Debug> Quadtree<T> {
+    /// Finds the k nearest neighbors of a point among the points whose elevation lies in
+    /// `z_range`.
+    ///
+    /// Points are indexed by their planar position only, and their elevation is read from
+    /// their payload (see the `elevation` module). Points outside the range are skipped during
+    /// the search, which continues past them.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search near; only its planar position is used.
+    /// * `k` - The number of nearest neighbors to retrieve.
+    /// * `z_range` - The elevations the neighbors must have.
+    ///
+    /// # Returns
+    ///
+    /// A vector of up to k points, ordered from nearest to farthest in the plane.
+    pub fn knn_search_in_z<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+        z_range: impl RangeBounds<f64>,
+    ) -> Vec<Point2D<T>> {
+        if k == 0 || has_nan_coord(target) {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        let skip = |p: &Point2D<T>| !in_z_range(p.data.as_ref(), &z_range);
+        self.knn_search_helper::<M>(target, &mut ExcludingSink::new(&mut results, skip));
+        results.into_sorted_vec()
+    }
+
+    /// Finds the points inside a rectangle whose elevation lies in `z_range`.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle (boundary inclusive).
+    /// * `z_range` - The elevations the points must have.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the matching points.
+    pub fn range_search_bbox_in_z(
+        &self,
+        query: &Rectangle,
+        z_range: impl RangeBounds<f64>,
+    ) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        let keep = |p: &Point2D<T>| in_z_range(p.data.as_ref(), &z_range);
+        self.range_search_bbox_where(query, &keep, usize::MAX, &mut found);
+        found
+    }
+}
+
+impl<T: Clone + PartialEq> Quadtree<T> {
+    /// Returns an iterator over the stored points, without running a query.
+    ///
+    /// The points are visited node by node, depth first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::{Point2D, Rectangle};
+    /// use spart::quadtree::Quadtree;
+    ///
+    /// let boundary = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+    /// let mut tree = Quadtree::new(&boundary, 1).unwrap();
+    /// for i in 0..5 {
+    ///     tree.insert(Point2D::new(i as f64, 9.0 - i as f64, Some(i)));
+    /// }
+    /// let mut ids: Vec<_> = tree.iter().filter_map(|p| p.data).collect();
+    /// ids.sort();
+    /// assert_eq!(ids, [0, 1, 2, 3, 4]);
+    /// assert_eq!(tree.into_iter().count(), 5);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            points: [].iter(),
+            stack: Vec::new(),
+        };
+        iter.enter(self);
+        iter
+    }
+}
+
+impl<'a, T: Clone + PartialEq> IntoIterator for &'a Quadtree<T> {
+    type Item = &'a Point2D<T>;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Clone + PartialEq> IntoIterator for Quadtree<T> {
+    type Item = Point2D<T>;
+    type IntoIter = IntoIter<T>;
+
+    /// Consumes the tree, yielding its points in the same order as `iter`.
+    fn into_iter(self) -> Self::IntoIter {
+        let mut iter = IntoIter {
+            points: Vec::new().into_iter(),
+            stack: Vec::new(),
+        };
+        iter.enter(self);
+        iter
+    }
+}
+
+/// An iterator over the points of a [`Quadtree`], created by [`Quadtree::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a, T: Clone + PartialEq> {
+    points: std::slice::Iter<'a, Point2D<T>>,
+    stack: Vec<&'a Quadtree<T>>,
+}
+
+impl<'a, T: Clone + PartialEq> Iter<'a, T> {
+    /// Yields the points of `node` next and queues its children.
+    fn enter(&mut self, node: &'a Quadtree<T>) {
+        self.points = node.points.iter();
+        self.stack.extend(
+            [
+                node.southeast.as_deref(),
+                node.southwest.as_deref(),
+                node.northeast.as_deref(),
+                node.northwest.as_deref(),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+    }
+}
+
+impl<'a, T: Clone + PartialEq> Iterator for Iter<'a, T> {
+    type Item = &'a Point2D<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(point) = self.points.next() {
+                return Some(point);
+            }
+            let node = self.stack.pop()?;
+            self.enter(node);
+        }
+    }
+}
+
+/// An iterator that moves the points out of a [`Quadtree`], created by its `into_iter`.
+#[derive(Debug)]
+pub struct IntoIter<T: Clone + PartialEq> {
+    points: std::vec::IntoIter<Point2D<T>>,
+    stack: Vec<Quadtree<T>>,
+}
+
+impl<T: Clone + PartialEq> IntoIter<T> {
+    /// Yields the points of `node` next and queues its children.
+    fn enter(&mut self, mut node: Quadtree<T>) {
+        self.points = std::mem::take(&mut node.points).into_iter();
+        self.stack.extend(
+            [
+                node.southeast.take(),
+                node.southwest.take(),
+                node.northeast.take(),
+                node.northwest.take(),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|child| *child),
+        );
+    }
+}
+
+impl<T: Clone + PartialEq> Iterator for IntoIter<T> {
+    type Item = Point2D<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(point) = self.points.next() {
+                return Some(point);
+            }
+            let node = self.stack.pop()?;
+            self.enter(node);
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> std::fmt::Display for Quadtree<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.summary().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::EuclideanDistance;
+
+    #[test]
+    fn test_insert_rejects_outside_boundary() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
+        let outside = Point2D::new(20.0, 20.0, Some("O"));
+        assert!(!tree.insert(outside));
+    }
+
+    #[test]
+    fn test_insert_accepts_boundary_points() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 1).unwrap();
+        let edge = Point2D::new(10.0, 10.0, Some("E"));
+        assert!(tree.insert(edge));
+    }
+
+    #[test]
+    fn test_range_search_zero_radius_returns_exact_match() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
+        let target = Point2D::new(25.0, 25.0, Some("T"));
+        tree.insert(target.clone());
+        tree.insert(Point2D::new(26.0, 25.0, Some("N")));
+
+        let results = tree.range_search::<EuclideanDistance>(&target, 0.0);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0], target);
+    }
+
+    #[test]
+    fn test_delete_existing_point() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
+        let p1 = Point2D::new(10.0, 10.0, Some("A"));
+        let p2 = Point2D::new(20.0, 20.0, Some("B"));
+        tree.insert(p1.clone());
+        tree.insert(p2);
+
+        assert!(tree.delete(&p1));
+        let results = tree.knn_search::<EuclideanDistance>(&p1, 1);
+        assert_ne!(results[0], p1);
+        assert!(!tree.delete(&p1));
+    }
+
+    #[test]
+    fn test_empty_tree_queries() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
+        let target = Point2D::new(5.0, 5.0, None::<&str>);
+
+        let knn_results = tree.knn_search::<EuclideanDistance>(&target, 5);
+        assert!(knn_results.is_empty());
+
+        let range_results = tree.range_search::<EuclideanDistance>(&target, 10.0);
+        assert!(range_results.is_empty());
+
+        assert!(!tree.delete(&target));
+    }
+
+    #[test]
+    fn test_knn_edge_cases() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 4).unwrap();
+        let points = vec![
+            Point2D::new(10.0, 10.0, Some("A")),
+            Point2D::new(20.0, 20.0, Some("B")),
+            Point2D::new(30.0, 30.0, Some("C")),
+        ];
+        let num_points = points.len();
+        tree.insert_bulk(&points);
+
+        let target = Point2D::new(15.0, 15.0, None::<&str>);
+        let knn_results = tree.knn_search::<EuclideanDistance>(&target, 0);
+        assert!(knn_results.is_empty());
+
+        let knn_results = tree.knn_search::<EuclideanDistance>(&target, num_points + 5);
+        assert_eq!(knn_results.len(), num_points);
+    }
+
+    #[test]
+    fn test_duplicates_delete_one() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 4).unwrap();
+        let p1 = Point2D::new(10.0, 10.0, Some("A"));
+        let p2 = p1.clone();
+        tree.insert(p1.clone());
+        tree.insert(p2.clone());
+
+        let results = tree.knn_search::<EuclideanDistance>(&p1, 2);
+        assert_eq!(results.len(), 2);
+
+        assert!(tree.delete(&p1));
+        let results_after_delete = tree.knn_search::<EuclideanDistance>(&p1, 2);
+        assert_eq!(results_after_delete.len(), 1);
+    }
+
+    #[test]
+    fn test_range_search_includes_boundary_point() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 4).unwrap();
+        let center = Point2D::new(50.0, 50.0, Some("C"));
+        let boundary_point = Point2D::new(60.0, 50.0, Some("B"));
+        tree.insert(center.clone());
+        tree.insert(boundary_point.clone());
+
+        let results = tree.range_search::<EuclideanDistance>(&center, 10.0);
+        assert!(results.contains(&boundary_point));
+        assert!(results.contains(&center));
+    }
+
+    #[test]
+    fn test_bulk_insert_empty_noop() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree: Quadtree<i32> = Quadtree::new(&boundary, 4).unwrap();
+        let empty: Vec<Point2D<i32>> = Vec::new();
+        tree.insert_bulk(&empty);
+        let target = Point2D::new(10.0, 10.0, None::<i32>);
+        let results = tree.knn_search::<EuclideanDistance>(&target, 1);
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_zero_capacity_rejected() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let result = Quadtree::<i32>::new(&boundary, 0);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_range_search_negative_radius_empty() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
+        let target = Point2D::new(10.0, 10.0, Some("T"));
+        tree.insert(target.clone());
+
+        let results = tree.range_search::<EuclideanDistance>(&target, -1.0);
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_node_path_and_leaf_boundary() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut qt = Quadtree::new(&boundary, 1).unwrap();
+        let outside = Point2D::new(150.0, 10.0, None);
+        assert!(qt.node_path(&outside).is_none());
+        assert!(qt.leaf_boundary(&outside).is_none());
+
+        let a = Point2D::new(10.0, 10.0, Some("A"));
+        assert!(qt.insert(a.clone()));
+        assert_eq!(qt.node_path(&a), Some(vec![]));
+
+        let b = Point2D::new(20.0, 20.0, Some("B"));
+        let c = Point2D::new(80.0, 30.0, Some("C"));
+        assert!(qt.insert(b.clone()));
+        assert!(qt.insert(c.clone()));
+
+        assert_eq!(
+            qt.node_path(&c),
+            Some(vec![Quadrant::NorthEast]),
+            "C is alone in the north-east quadrant"
+        );
+        assert_eq!(
+            qt.node_path(&a),
+            Some(vec![
+                Quadrant::NorthWest,
+                Quadrant::NorthWest,
+                Quadrant::NorthWest
+            ])
+        );
+        let leaf = qt.leaf_boundary(&b).unwrap();
+        assert!(leaf.contains(&b));
+        assert_eq!(
+            (leaf.x, leaf.y, leaf.width, leaf.height),
+            (12.5, 12.5, 12.5, 12.5)
+        );
+
+        // Points on a split line follow the insertion routing (north-west first).
+        let on_split = Point2D::new(50.0, 50.0, None);
+        assert_eq!(qt.node_path(&on_split).unwrap()[0], Quadrant::NorthWest);
+    }
+
+    #[test]
+    fn test_range_search_bbox_matches_scan() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut qt = Quadtree::new(&boundary, 3).unwrap();
+        let mut points = Vec::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                points.push(Point2D::new(
+                    i as f64 * 10.0,
+                    j as f64 * 10.0,
+                    Some(i * 10 + j),
+                ));
+            }
+        }
+        qt.insert_bulk(&points);
+        let query = Rectangle {
+            x: 20.0,
+            y: 35.0,
+            width: 30.0,
+            height: 25.0,
+        };
+        let mut found: Vec<i32> = qt
+            .range_search_bbox(&query)
+            .into_iter()
+            .filter_map(|p| p.data)
+            .collect();
+        found.sort();
+        let mut expected: Vec<i32> = points
+            .iter()
+            .filter(|p| query.contains(*p))
+            .filter_map(|p| p.data)
+            .collect();
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_query_tile_partitions_points() {
+        use crate::geometry::TileOrigin;
+
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 256.0,
+            height: 256.0,
+        };
+        let scheme = TileScheme::new(boundary.clone(), TileOrigin::MaxY);
+        let mut qt = Quadtree::new(&boundary, 4).unwrap();
+        let mut points = Vec::new();
+        for i in 0..=16 {
+            for j in 0..=16 {
+                // Many of these points sit exactly on tile edges.
+                points.push(Point2D::new(
+                    i as f64 * 16.0,
+                    j as f64 * 16.0,
+                    Some(i * 17 + j),
+                ));
+            }
+        }
+        qt.insert_bulk(&points);
+
+        let z = 2;
+        let mut total = 0;
+        for x in 0..4 {
+            for y in 0..4 {
+                let tile = qt.query_tile(&scheme, z, x, y);
+                let bounds = scheme.tile_bounds(z, x, y).unwrap();
+                for p in &tile {
+                    assert!(bounds.contains(p));
+                }
+                total += tile.len();
+            }
+        }
+        assert_eq!(total, points.len());
+
+        // Row 0 is at the top (largest y) of the extent.
+        let top_left = qt.query_tile(&scheme, 1, 0, 0);
+        assert!(top_left.iter().all(|p| p.x <= 128.0 && p.y >= 128.0));
+        assert!(qt.query_tile(&scheme, 1, 2, 0).is_empty());
+    }
+
+    #[test]
+    fn test_thinned_in_bbox_spreads_picks() {
+        use crate::rtree::RTree;
+
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut qt = Quadtree::new(&boundary, 4).unwrap();
+        let mut rtree = RTree::new(4).unwrap();
+        // A dense cluster of high-priority points, and a sparse grid of low-priority ones.
+        for i in 0..50 {
+            let p = Point2D::new(
+                5.0 + (i % 5) as f64,
+                5.0 + (i / 5) as f64 * 0.5,
+                Some(1000 + i),
+            );
+            qt.insert(p.clone());
+            rtree.insert(p);
+        }
+        for i in 0..100 {
+            let p = Point2D::new(
+                (i % 10) as f64 * 10.0 + 5.0,
+                (i / 10) as f64 * 10.0 + 5.0,
+                Some(i),
+            );
+            qt.insert(p.clone());
+            rtree.insert(p);
+        }
+
+        let priority = |p: &Point2D<i32>| p.data.unwrap() as f64;
+        let picked = qt.thinned_in_bbox(&boundary, 25, priority);
+        assert_eq!(picked.len(), 25);
+        // The best point overall comes first, but the cluster does not take every slot.
+        assert_eq!(picked[0].data, Some(1049));
+        let from_cluster = picked.iter().filter(|p| p.data >= Some(1000)).count();
+        assert!(
+            from_cluster <= 2,
+            "{from_cluster} points picked from the cluster"
+        );
+
+        let from_rtree: Vec<_> = rtree
+            .thinned_in_bbox(&boundary, 25, priority)
+            .into_iter()
+            .cloned()
+            .collect();
+        assert_eq!(from_rtree.len(), 25);
+        assert_eq!(from_rtree[0], picked[0]);
+
+        assert!(qt.thinned_in_bbox(&boundary, 0, priority).is_empty());
+        assert_eq!(qt.thinned_in_bbox(&boundary, 1000, priority).len(), 150);
+    }
+
+    #[test]
+    fn test_spill_delays_split_until_merge_margin() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 16.0,
+            height: 16.0,
+        };
+        let points: Vec<_> = (0..7)
+            .map(|i| Point2D::new(i as f64 * 2.0 + 1.0, 3.0, Some(i)))
+            .collect();
+        let mut split = Quadtree::new(&boundary, 4).unwrap();
+        let mut spill = Quadtree::new(&boundary, 4)
+            .unwrap()
+            .with_overflow(OverflowStrategy::Spill { extra: 2 });
+        for point in &points[..6] {
+            split.insert(point.clone());
+            spill.insert(point.clone());
+        }
+        assert_eq!((split.depth(), spill.depth()), (2, 1));
+        spill.insert(points[6].clone());
+        assert_eq!(spill.depth(), 2);
+        assert_eq!(spill.overflow(), OverflowStrategy::Spill { extra: 2 });
+
+        // Oscillating around the capacity no longer splits and merges the node.
+        for _ in 0..3 {
+            assert!(spill.delete(&points[6]));
+            assert!(spill.delete(&points[5]));
+            assert_eq!(spill.depth(), 2);
+            spill.insert(points[5].clone());
+            spill.insert(points[6].clone());
+            assert_eq!(spill.depth(), 2);
+        }
+        spill.check_invariants().unwrap();
+        assert_eq!(spill.range_search_bbox(&boundary).len(), points.len());
+        assert!(spill.delete(&points[6]) && spill.delete(&points[5]) && spill.delete(&points[4]));
+        assert_eq!(spill.depth(), 1);
+    }
+
+    #[test]
+    fn test_balance_report_flags_clustered_data() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 1024.0,
+            height: 1024.0,
+        };
+        let mut uniform = Quadtree::new(&boundary, 4).unwrap();
+        let mut clustered = Quadtree::new(&boundary, 4).unwrap();
+        for i in 0..256 {
+            let (x, y) = ((i % 16) as f64 * 64.0 + 1.0, (i / 16) as f64 * 64.0 + 1.0);
+            uniform.insert(Point2D::new(x, y, Some(i)));
+            clustered.insert(Point2D::new(x / 1000.0, y / 1000.0, Some(i)));
+        }
+
+        for tree in [&uniform, &clustered] {
+            let report = tree.balance_report();
+            assert_eq!(report.depth(), tree.depth());
+            assert_eq!(tree.depth(), tree.stats().depth);
+            assert_eq!(report.size(), 256);
+            assert_eq!(report.levels[0].nodes, 1);
+            let nodes: usize = report.levels.iter().map(|level| level.nodes).sum();
+            assert_eq!(nodes, tree.stats().node_count);
+        }
+        assert!(uniform.balance_report().imbalance() <= 1.5);
+        assert!(clustered.balance_report().imbalance() > 2.0);
+    }
+
+    #[test]
+    fn test_insert_bulk_keeps_points_on_split_lines() {
+        // 0.3 + 0.35 + 0.35 rounds below 1.0, so the eastern children would end slightly short
+        // of the root's far edge without rounding up their width.
+        let boundary = Rectangle {
+            x: 0.3,
+            y: 0.3,
+            width: 0.7,
+            height: 0.7,
+        };
+        let coords = [0.3, 0.475, 0.65, 0.825, 1.0];
+        let mut points = Vec::new();
+        for &x in &coords {
+            for &y in &coords {
+                points.push(Point2D::new(x, y, Some((x, y))));
+            }
+        }
+
+        let mut bulk: Quadtree<(f64, f64)> = Quadtree::new(&boundary, 1).unwrap();
+        bulk.insert_bulk(&points);
+        let mut single: Quadtree<(f64, f64)> = Quadtree::new(&boundary, 1).unwrap();
+        for p in &points {
+            assert!(single.insert(p.clone()));
         }
-        let children = self.children();
-        if children.iter().all(|child| !child.divided) {
-            let total_points: usize = children.iter().map(|child| child.points.len()).sum();
-            if total_points <= self.capacity {
-                let mut merged_points = Vec::with_capacity(total_points);
-                if let Some(child) = self.northeast.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.northwest.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.southeast.take() {
-                    merged_points.extend(child.points);
-                }
-                if let Some(child) = self.southwest.take() {
-                    merged_points.extend(child.points);
-                }
-                info!(
-                    "Merging children into parent node at boundary {:?} with {} points",
-                    self.boundary,
-                    merged_points.len()
-                );
-                self.points.extend(merged_points);
-                self.divided = false;
-            }
+
+        for tree in [&bulk, &single] {
+            assert_eq!(tree.range_search_bbox(&boundary).len(), points.len());
         }
+        for p in &points {
+            assert_eq!(bulk.node_path(p), single.node_path(p));
+            assert!(bulk.delete(p));
+            assert!(single.delete(p));
+        }
+        assert!(bulk.range_search_bbox(&boundary).is_empty());
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::geometry::EuclideanDistance;
 
     #[test]
-    fn test_insert_rejects_outside_boundary() {
+    fn test_duplicate_points_stop_subdividing_at_max_depth() {
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 10.0,
             height: 10.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
-        let outside = Point2D::new(20.0, 20.0, Some("O"));
-        assert!(!tree.insert(outside));
+        let points = vec![Point2D::new(3.0, 7.0, None::<()>); 3];
+        let mut single = Quadtree::new(&boundary, 2).unwrap();
+        for p in &points {
+            assert!(single.insert(p.clone()));
+        }
+        let mut bulk = Quadtree::new(&boundary, 2).unwrap();
+        bulk.insert_bulk(&points);
+        let mut spill = Quadtree::new(&boundary, 2)
+            .unwrap()
+            .with_overflow(OverflowStrategy::Spill { extra: 1 });
+        for p in points.iter().chain(&points) {
+            assert!(spill.insert(p.clone()));
+        }
+
+        for (tree, count) in [(&single, 3), (&bulk, 3), (&spill, 6)] {
+            assert_eq!(tree.depth(), MAX_DEPTH + 1);
+            assert_eq!(tree.range_search_bbox(&boundary).len(), count);
+            tree.check_invariants().unwrap();
+        }
+        assert!(single.delete(&points[0]));
+        assert_eq!(single.range_search_bbox(&boundary).len(), 2);
     }
 
     #[test]
-    fn test_insert_accepts_boundary_points() {
+    fn test_tolerance_contains_delete_update() {
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 10.0,
             height: 10.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 1).unwrap();
-        let edge = Point2D::new(10.0, 10.0, Some("E"));
-        assert!(tree.insert(edge));
+        let mut tree: Quadtree<i32> = Quadtree::new(&boundary, 2).unwrap().with_tolerance(1e-6);
+        for i in 0..=20 {
+            let v = i as f64 / 2.0;
+            assert!(tree.insert(Point2D::new(v, 5.0, Some(i))));
+        }
+        // Perturbations across the split lines and just outside the boundary still match.
+        assert!(tree.contains(&Point2D::new(5.0 + 1e-9, 5.0 - 1e-9, Some(10))));
+        assert!(tree.contains(&Point2D::new(10.0 + 1e-9, 5.0, Some(20))));
+        assert!(!tree.contains(&Point2D::new(5.0, 5.0, Some(11))));
+
+        assert!(tree.delete(&Point2D::new(2.5 - 1e-9, 5.0 + 1e-9, Some(5))));
+        assert!(!tree.contains(&Point2D::new(2.5, 5.0, Some(5))));
+        assert!(tree.update(
+            &Point2D::new(7.5 + 1e-9, 5.0, Some(15)),
+            Point2D::new(1.0, 1.0, Some(15))
+        ));
+        assert!(tree.contains(&Point2D::new(1.0, 1.0, Some(15))));
+        assert!(!tree.update(
+            &Point2D::new(1.0, 1.0, Some(15)),
+            Point2D::new(20.0, 1.0, Some(15))
+        ));
+        assert!(tree.contains(&Point2D::new(1.0, 1.0, Some(15))));
+
+        let mut exact: Quadtree<i32> = Quadtree::new(&boundary, 2).unwrap();
+        exact.insert(Point2D::new(0.1 + 0.2, 1.0, Some(1)));
+        assert!(!exact.delete(&Point2D::new(0.3, 1.0, Some(1))));
     }
 
     #[test]
-    fn test_range_search_zero_radius_returns_exact_match() {
+    fn test_export_import_sorted_round_trip() {
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 100.0,
             height: 100.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
-        let target = Point2D::new(25.0, 25.0, Some("T"));
-        tree.insert(target.clone());
-        tree.insert(Point2D::new(26.0, 25.0, Some("N")));
+        let mut tree: Quadtree<u32> = Quadtree::new(&boundary, 4).unwrap();
+        let mut seed = 12345u32;
+        for i in 0..500 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let x = (seed >> 8) as f64 % 1000.0 / 10.0;
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let y = (seed >> 8) as f64 % 1000.0 / 10.0;
+            tree.insert(Point2D::new(x, y, Some(i)));
+        }
 
-        let results = tree.range_search::<EuclideanDistance>(&target, 0.0);
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0], target);
+        let exported: Vec<Point2D<u32>> = tree.export_sorted().cloned().collect();
+        assert_eq!(exported.len(), 500);
+        let keys: Vec<u64> = exported
+            .iter()
+            .map(|p| tree.morton_key(p).unwrap())
+            .collect();
+        assert!(keys.windows(2).all(|w| w[0] <= w[1]));
+
+        let imported = Quadtree::import_sorted(&boundary, 4, exported.clone()).unwrap();
+        let reexported: Vec<u64> = imported
+            .export_sorted()
+            .map(|p| imported.morton_key(p).unwrap())
+            .collect();
+        assert_eq!(reexported, keys);
+        for p in &exported {
+            assert!(imported.contains(p));
+        }
+
+        // Unsorted input still produces a complete tree.
+        let mut reversed = exported.clone();
+        reversed.reverse();
+        reversed.push(Point2D::new(200.0, 0.0, Some(999)));
+        let unsorted = Quadtree::import_sorted(&boundary, 4, reversed).unwrap();
+        assert_eq!(unsorted.range_search_bbox(&boundary).len(), 500);
+        assert_eq!(unsorted.export_sorted().count(), 500);
     }
 
     #[test]
-    fn test_delete_existing_point() {
+    fn test_min_max_by_axis_match_full_scan() {
+        let boundary = Rectangle {
+            x: 0.3,
+            y: 0.3,
+            width: 0.7,
+            height: 0.7,
+        };
+        let mut tree: Quadtree<u32> = Quadtree::new(&boundary, 2).unwrap();
+        assert!(tree.min_by_axis(0).unwrap().is_none());
+        let mut points = Vec::new();
+        let mut seed = 777u32;
+        for i in 0..300 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let x = 0.3 + (seed >> 8) as f64 % 700.0 / 1000.0;
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let y = 0.3 + (seed >> 8) as f64 % 700.0 / 1000.0;
+            points.push(Point2D::new(x, y, Some(i)));
+        }
+        // Points on the far edges are routed to children that end short of them by rounding.
+        points.push(Point2D::new(1.0, 0.5, Some(300)));
+        points.push(Point2D::new(0.5, 1.0, Some(301)));
+        tree.insert_bulk(&points);
+
+        let xs: Vec<f64> = points.iter().map(|p| p.x).collect();
+        let ys: Vec<f64> = points.iter().map(|p| p.y).collect();
+        for (axis, coords) in [(0, &xs), (1, &ys)] {
+            let min = coords.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = coords.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let found_min = tree.min_by_axis(axis).unwrap().unwrap();
+            let found_max = tree.max_by_axis(axis).unwrap().unwrap();
+            assert_eq!([found_min.x, found_min.y][axis], min);
+            assert_eq!([found_max.x, found_max.y][axis], max);
+        }
+        assert!(matches!(
+            tree.max_by_axis(2),
+            Err(SpartError::InvalidDimension {
+                requested: 2,
+                available: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_convex_hull_matches_hull_of_all_points() {
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 100.0,
             height: 100.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
-        let p1 = Point2D::new(10.0, 10.0, Some("A"));
-        let p2 = Point2D::new(20.0, 20.0, Some("B"));
-        tree.insert(p1.clone());
-        tree.insert(p2);
+        let mut tree: Quadtree<u32> = Quadtree::new(&boundary, 4).unwrap();
+        assert!(tree.convex_hull().is_empty());
+        let mut points = Vec::new();
+        let mut seed = 2024u32;
+        for i in 0..400 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let angle = (seed >> 8) as f64 % 6283.0 / 1000.0;
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let radius = (seed >> 8) as f64 % 450.0 / 10.0;
+            points.push(Point2D::new(
+                50.0 + radius * angle.cos(),
+                50.0 + radius * angle.sin(),
+                Some(i),
+            ));
+        }
+        tree.insert_bulk(&points);
 
-        assert!(tree.delete(&p1));
-        let results = tree.knn_search::<EuclideanDistance>(&p1, 1);
-        assert_ne!(results[0], p1);
-        assert!(!tree.delete(&p1));
+        let hull = tree.convex_hull();
+        assert_eq!(hull, crate::geometry::convex_hull(&points));
+        assert!(hull.len() >= 3);
     }
 
     #[test]
-    fn test_empty_tree_queries() {
+    fn test_nn_distance_stats_match_brute_force() {
+        use crate::random::SplitMix64;
+
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 100.0,
             height: 100.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
-        let target = Point2D::new(5.0, 5.0, None::<&str>);
+        let mut rng = SplitMix64::new(5);
+        let mut points: Vec<Point2D<usize>> = (0..200)
+            .map(|i| Point2D::new(rng.next_f64() * 100.0, rng.next_f64() * 100.0, Some(i)))
+            .collect();
+        // A duplicate has a nearest-neighbor distance of zero.
+        points.push(Point2D {
+            data: Some(200),
+            ..points[0].clone()
+        });
+        let mut tree = Quadtree::new(&boundary, 4).unwrap();
+        tree.insert_bulk(&points);
 
-        let knn_results = tree.knn_search::<EuclideanDistance>(&target, 5);
-        assert!(knn_results.is_empty());
+        let mut expected: Vec<f64> = points
+            .iter()
+            .map(|p| {
+                points
+                    .iter()
+                    .filter(|q| q.data != p.data)
+                    .map(|q| EuclideanDistance::distance_sq(p, q).sqrt())
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect();
+        expected.sort_by(f64::total_cmp);
 
-        let range_results = tree.range_search::<EuclideanDistance>(&target, 10.0);
-        assert!(range_results.is_empty());
+        // Sampling at least as many points as stored covers every point.
+        let stats = tree.nn_distance_stats(1000, &mut rng).unwrap();
+        assert_eq!(stats.count, points.len());
+        assert_eq!(stats.min, 0.0);
+        assert_eq!(stats.distances(), &expected[..]);
+        assert_eq!(tree.nn_distance_stats(10, &mut rng).unwrap().count, 10);
+        assert!(tree.nn_distance_stats(0, &mut rng).is_none());
+    }
 
-        assert!(!tree.delete(&target));
+    #[test]
+    fn test_advise_capacity_uses_stored_points() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 20.0,
+            height: 20.0,
+        };
+        let mut tree = Quadtree::new(&boundary, 4).unwrap();
+        for i in 0..400 {
+            tree.insert(Point2D::new((i % 20) as f64, (i / 20) as f64, Some(i)));
+        }
+        let advisor = CapacityAdvisor::new()
+            .with_capacities(vec![2, 16])
+            .with_query_count(10)
+            .with_repetitions(1);
+        let advice = tree.advise_capacity(&advisor).unwrap();
+        assert!([2, 16].contains(&advice.recommended));
+        assert_eq!(advice.measurements.len(), 2);
+
+        let invalid = CapacityAdvisor::new().with_capacities(vec![0]);
+        assert!(matches!(
+            tree.advise_capacity(&invalid),
+            Err(SpartError::InvalidCapacity { capacity: 0 })
+        ));
     }
 
     #[test]
-    fn test_knn_edge_cases() {
+    fn test_crop_and_split_at_keep_the_right_points() {
+        use crate::random::SplitMix64;
+
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
-            width: 100.0,
-            height: 100.0,
+            width: 64.0,
+            height: 64.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 4).unwrap();
-        let points = vec![
-            Point2D::new(10.0, 10.0, Some("A")),
-            Point2D::new(20.0, 20.0, Some("B")),
-            Point2D::new(30.0, 30.0, Some("C")),
-        ];
-        let num_points = points.len();
+        let mut rng = SplitMix64::new(9);
+        let points: Vec<Point2D<usize>> = (0..500)
+            .map(|i| Point2D::new(rng.next_f64() * 64.0, rng.next_f64() * 64.0, Some(i)))
+            .collect();
+        let mut tree = Quadtree::new(&boundary, 4).unwrap();
         tree.insert_bulk(&points);
+        let ids = |found: Vec<Point2D<usize>>| {
+            let mut ids: Vec<usize> = found.into_iter().filter_map(|p| p.data).collect();
+            ids.sort_unstable();
+            ids
+        };
 
-        let target = Point2D::new(15.0, 15.0, None::<&str>);
-        let knn_results = tree.knn_search::<EuclideanDistance>(&target, 0);
-        assert!(knn_results.is_empty());
+        // The region covers whole quadrants as well as partial ones.
+        let region = Rectangle {
+            x: 0.0,
+            y: 10.0,
+            width: 40.0,
+            height: 54.0,
+        };
+        let cropped = tree.crop(&region);
+        assert_eq!(
+            ids(cropped.range_search_bbox(&boundary)),
+            ids(tree.range_search_bbox(&region))
+        );
+        let inside = points.iter().find(|p| region.contains(p)).unwrap();
+        let outside = points.iter().find(|p| !region.contains(p)).unwrap();
+        assert!(cropped.contains(inside));
+        assert!(!cropped.contains(outside));
+        assert_eq!(ids(tree.range_search_bbox(&boundary)).len(), 500);
 
-        let knn_results = tree.knn_search::<EuclideanDistance>(&target, num_points + 5);
-        assert_eq!(knn_results.len(), num_points);
+        let parts = tree.split_at(2);
+        assert_eq!(parts.len(), 16);
+        let mut all = Vec::new();
+        for part in &parts {
+            let found = part.range_search_bbox(&boundary);
+            assert!(found.iter().all(|p| part.boundary.contains(p)));
+            all.extend(found);
+        }
+        assert_eq!(ids(all), (0..500).collect::<Vec<_>>());
     }
 
     #[test]
-    fn test_duplicates_delete_one() {
+    fn test_density_grid_counts_points_in_bbox() {
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 100.0,
             height: 100.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 4).unwrap();
-        let p1 = Point2D::new(10.0, 10.0, Some("A"));
-        let p2 = p1.clone();
-        tree.insert(p1.clone());
-        tree.insert(p2.clone());
+        let mut qt = Quadtree::new(&boundary, 2).unwrap();
+        for i in 0..100 {
+            qt.insert(Point2D::new(i as f64, 10.0, Some(i)));
+            qt.insert(Point2D::new(i as f64, 90.0, Some(i)));
+        }
+        let bbox = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 50.0,
+            height: 50.0,
+        };
+        let grid = qt.density_grid(&bbox, 5, 2);
+        assert_eq!(grid.counts(), &[10, 10, 10, 10, 11, 0, 0, 0, 0, 0]);
+        assert_eq!(grid.max(), 11);
+    }
 
-        let results = tree.knn_search::<EuclideanDistance>(&p1, 2);
-        assert_eq!(results.len(), 2);
+    #[test]
+    fn test_occupancy_grid_covers_boundary() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 5.0,
+        };
+        let mut qt = Quadtree::new(&boundary, 2).unwrap();
+        for point in [(0.5, 0.5), (1.5, 0.5), (9.9, 4.9), (10.0, 5.0), (6.0, 2.5)] {
+            qt.insert(Point2D::new(point.0, point.1, None::<()>));
+        }
+        // 10 by 5 with cells of 4 needs 3 columns and 2 rows.
+        let grid = qt.to_occupancy_grid(4.0).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.counts(), &[2, 1, 0, 0, 0, 2]);
+        assert_eq!(
+            grid.occupancy(),
+            vec![true, true, false, false, false, true]
+        );
+        assert_eq!(grid.is_occupied(0, 1), Some(false));
+        assert_eq!(grid.is_occupied(3, 0), None);
+        assert_eq!(
+            grid.cell_of(&Point2D::new(6.0, 2.5, None::<()>)),
+            Some((1, 0))
+        );
 
-        assert!(tree.delete(&p1));
-        let results_after_delete = tree.knn_search::<EuclideanDistance>(&p1, 2);
-        assert_eq!(results_after_delete.len(), 1);
+        assert!(qt.to_occupancy_grid(0.0).is_err());
+        assert!(qt.to_occupancy_grid(f64::NAN).is_err());
+        assert!(qt.to_occupancy_grid(1e-9).is_err());
     }
 
     #[test]
-    fn test_range_search_includes_boundary_point() {
+    fn test_stats_and_invariants() {
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 100.0,
             height: 100.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 4).unwrap();
-        let center = Point2D::new(50.0, 50.0, Some("C"));
-        let boundary_point = Point2D::new(60.0, 50.0, Some("B"));
-        tree.insert(center.clone());
-        tree.insert(boundary_point.clone());
+        let mut qt = Quadtree::new(&boundary, 2).unwrap();
+        let points: Vec<Point2D<i32>> = (0..50)
+            .map(|i| Point2D::new((i * 7 % 100) as f64, (i * 13 % 100) as f64, Some(i)))
+            .collect();
+        qt.insert_bulk(&points[..25]);
+        for point in &points[25..] {
+            qt.insert(point.clone());
+        }
+        for point in points.iter().step_by(3) {
+            qt.delete(point);
+        }
+        assert!(qt.check_invariants().is_ok());
+        let stats = qt.stats();
+        assert_eq!(stats.size, 33);
+        assert_eq!(
+            stats.node_count,
+            1 + 4 * (stats.node_count - stats.leaf_count)
+        );
+        assert!(stats.depth > 2);
+        let fill = stats.fill_factor.unwrap();
+        assert!(fill > 0.0 && fill <= 1.0);
 
-        let results = tree.range_search::<EuclideanDistance>(&center, 10.0);
-        assert!(results.contains(&boundary_point));
-        assert!(results.contains(&center));
+        // Move a stored point out of its quadrant, as a corrupted file could.
+        let leaf = qt.northwest.as_deref_mut().unwrap();
+        let mut node = leaf;
+        while node.points.is_empty() {
+            node = node.southeast.as_deref_mut().unwrap();
+        }
+        node.points[0].x = 99.0;
+        assert!(matches!(
+            qt.check_invariants(),
+            Err(SpartError::InvariantViolation { .. })
+        ));
     }
 
     #[test]
-    fn test_bulk_insert_empty_noop() {
+    fn test_delete_bulk_matches_repeated_delete() {
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 100.0,
             height: 100.0,
         };
-        let mut tree: Quadtree<i32> = Quadtree::new(&boundary, 4).unwrap();
-        let empty: Vec<Point2D<i32>> = Vec::new();
-        tree.insert_bulk(&empty);
-        let target = Point2D::new(10.0, 10.0, None::<i32>);
-        let results = tree.knn_search::<EuclideanDistance>(&target, 1);
-        assert!(results.is_empty());
+        let mut bulk = Quadtree::new(&boundary, 4).unwrap();
+        for i in 0..200 {
+            bulk.insert(Point2D::new(
+                (i % 20) as f64 * 5.0,
+                (i / 20) as f64 * 10.0,
+                Some(i),
+            ));
+        }
+        let mut looped = bulk.clone();
+        let mut doomed: Vec<_> = (0..200)
+            .filter(|i| i % 3 != 0)
+            .map(|i| Point2D::new((i % 20) as f64 * 5.0, (i / 20) as f64 * 10.0, Some(i)))
+            .collect();
+        doomed.push(Point2D::new(500.0, 500.0, Some(0)));
+        doomed.push(doomed[0].clone());
+
+        assert_eq!(bulk.delete_bulk(&doomed), 133);
+        let deleted = doomed.iter().filter(|p| looped.delete(p)).count();
+        assert_eq!(deleted, 133);
+        assert_eq!(bulk.stats(), looped.stats());
+        assert!(bulk.check_invariants().is_ok());
+        assert_eq!(bulk.range_search_bbox(&boundary).len(), 67);
     }
 
     #[test]
-    fn test_zero_capacity_rejected() {
+    fn test_knn_in_polygon_matches_filtered_scan() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let polygon = Polygon::new(vec![
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 3.0],
+            [3.0, 3.0],
+            [3.0, 7.0],
+            [10.0, 7.0],
+            [10.0, 10.0],
+            [0.0, 10.0],
+        ])
+        .unwrap();
+        let mut tree = Quadtree::new(&boundary, 2).unwrap();
+        let points: Vec<Point2D<i32>> = (0..100)
+            .map(|i| Point2D::new((i % 10) as f64 + 0.5, (i / 10) as f64 + 0.5, Some(i)))
+            .collect();
+        for point in &points {
+            tree.insert(point.clone());
+        }
+
+        // The query lies in the cut-out, so its nearest points are all outside the polygon.
+        let target = Point2D::new(6.0, 5.0, None);
+        let mut expected: Vec<&Point2D<i32>> =
+            points.iter().filter(|p| polygon.contains(*p)).collect();
+        expected.sort_by(|a, b| a.distance_sq(&target).total_cmp(&b.distance_sq(&target)));
+        let found = tree.knn_in_polygon::<EuclideanDistance>(&target, 5, &polygon);
+        assert_eq!(found.len(), 5);
+        for (point, expected) in found.iter().zip(&expected) {
+            assert!(polygon.contains(point));
+            assert_eq!(point.distance_sq(&target), expected.distance_sq(&target));
+        }
+        assert!(
+            tree.knn_in_polygon::<EuclideanDistance>(&target, 0, &polygon)
+                .is_empty()
+        );
+        assert_eq!(
+            tree.knn_in_polygon::<EuclideanDistance>(&target, 1000, &polygon)
+                .len(),
+            expected.len()
+        );
+    }
+
+    #[test]
+    fn test_range_search_into_appends_to_buffer() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let mut tree = Quadtree::new(&boundary, 4).unwrap();
+        for i in 0..100 {
+            tree.insert(Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)));
+        }
+        let center = Point2D::new(5.0, 5.0, None);
+        let mut buffer = vec![Point2D::new(-1.0, -1.0, Some(-1))];
+        tree.range_search_into::<EuclideanDistance>(&center, 1.5, &mut buffer);
+        assert_eq!(buffer[0].data, Some(-1));
+        assert_eq!(
+            buffer[1..],
+            tree.range_search::<EuclideanDistance>(&center, 1.5)[..]
+        );
+        tree.range_search_into::<EuclideanDistance>(&center, -1.0, &mut buffer);
+        assert_eq!(buffer.len(), 10);
+    }
+
+    #[test]
+    fn test_uncovered_area_matches_cell_by_cell_check() {
+        use crate::random::{RandomSource, SplitMix64};
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 100.0,
             height: 100.0,
         };
-        let result = Quadtree::<i32>::new(&boundary, 0);
-        assert!(result.is_err());
+        let mut rng = SplitMix64::new(9);
+        let mut tree = Quadtree::new(&boundary, 4).unwrap();
+        for i in 0..60 {
+            tree.insert(Point2D::new(
+                rng.next_f64() * 100.0,
+                rng.next_f64() * 100.0,
+                Some(i),
+            ));
+        }
+        // 37 columns and rows of 2.5, the last ones clipped to 0.5.
+        let region = Rectangle {
+            x: 5.0,
+            y: 5.0,
+            width: 90.5,
+            height: 90.5,
+        };
+        let (radius, resolution) = (8.0, 2.5);
+        let gaps = tree.uncovered_area(&region, radius, resolution).unwrap();
+
+        let points = tree.range_search_bbox(&boundary);
+        let center = |i: usize| {
+            let lo = 5.0 + i as f64 * resolution;
+            (lo + (lo + resolution).min(95.5)) / 2.0
+        };
+        let mut expected = Vec::new();
+        let mut found = Vec::new();
+        for column in 0..37 {
+            for row in 0..37 {
+                let c = Point2D::new(center(column), center(row), None);
+                if points
+                    .iter()
+                    .all(|p| EuclideanDistance::distance_sq(p, &c) > radius * radius)
+                {
+                    expected.push((column, row));
+                }
+                let holders = gaps.iter().filter(|g| g.contains(&c)).count();
+                assert!(holders <= 1);
+                if holders == 1 {
+                    found.push((column, row));
+                }
+            }
+        }
+        assert!(!expected.is_empty() && expected.len() < 37 * 37);
+        assert_eq!(found, expected);
+        assert!(gaps.len() < expected.len());
+        assert!(tree.uncovered_area(&region, -1.0, 1.0).is_err());
+        assert!(tree.uncovered_area(&region, 1.0, 0.0).is_err());
     }
 
     #[test]
-    fn test_range_search_negative_radius_empty() {
+    fn test_limited_searches_stop_at_cap() {
         let boundary = Rectangle {
             x: 0.0,
             y: 0.0,
             width: 100.0,
             height: 100.0,
         };
-        let mut tree: Quadtree<&str> = Quadtree::new(&boundary, 2).unwrap();
-        let target = Point2D::new(10.0, 10.0, Some("T"));
-        tree.insert(target.clone());
+        let mut tree = Quadtree::new(&boundary, 4).unwrap();
+        for i in 0..400 {
+            tree.insert(Point2D::new(
+                (i % 20) as f64 * 5.0,
+                (i / 20) as f64 * 5.0,
+                Some(i),
+            ));
+        }
+        let query = Rectangle {
+            x: 10.0,
+            y: 10.0,
+            width: 20.0,
+            height: 20.0,
+        };
+        let all = tree.range_search_bbox(&query);
+        assert_eq!(all.len(), 25);
+        for (cap, truncated) in [(0, true), (7, true), (25, false), (100, false)] {
+            let limited = tree.range_search_bbox_limited(&query, cap);
+            assert_eq!(limited.results.len(), cap.min(25));
+            assert_eq!(limited.truncated, truncated);
+            assert!(limited.results.iter().all(|p| all.contains(p)));
+        }
 
-        let results = tree.range_search::<EuclideanDistance>(&target, -1.0);
-        assert!(results.is_empty());
+        let center = Point2D::new(50.0, 50.0, None);
+        let within = tree.range_search::<EuclideanDistance>(&center, 12.0).len();
+        let limited = tree.range_search_limited::<EuclideanDistance>(&center, 12.0, 5);
+        assert_eq!((limited.results.len(), limited.truncated), (5, true));
+        let limited = tree.range_search_limited::<EuclideanDistance>(&center, 12.0, within);
+        assert_eq!((limited.results.len(), limited.truncated), (within, false));
+    }
+
+    #[test]
+    fn test_retain_keeps_matching_points_and_merges() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree = Quadtree::new(&boundary, 4).unwrap();
+        for i in 0..200 {
+            tree.insert(Point2D::new(
+                (i % 20) as f64 * 5.0,
+                (i / 20) as f64 * 10.0,
+                Some(i),
+            ));
+        }
+        assert_eq!(tree.retain(|p| p.data.is_some_and(|i| i % 50 == 0)), 196);
+        assert!(tree.check_invariants().is_ok());
+        let mut left: Vec<_> = tree
+            .range_search_bbox(&boundary)
+            .into_iter()
+            .filter_map(|p| p.data)
+            .collect();
+        left.sort();
+        assert_eq!(left, [0, 50, 100, 150]);
+        // The four remaining points fit into the root again.
+        assert_eq!(tree.stats().node_count, 1);
+    }
+    #[test]
+    fn test_nearest_occupied_cell_matches_split_cells() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree = Quadtree::new(&boundary, 2).unwrap();
+        // The points cluster in the lower left, leaving most cells empty.
+        for i in 0..60 {
+            let x = (i * 7 % 40) as f64;
+            let y = (i * 13 % 35) as f64;
+            tree.insert(Point2D::new(x, y, Some(i)));
+        }
+        for depth in 0..5 {
+            let cells = tree.clone().split_at(depth);
+            for query in [(95.0, 95.0), (10.0, 90.0), (20.0, 20.0), (70.0, 5.0)] {
+                let query = Point2D::new(query.0, query.1, None);
+                let (cell, mut points) = tree.nearest_occupied_cell(&query, depth).unwrap();
+                let nearest = cells
+                    .iter()
+                    .map(|part| part.boundary.min_distance(&query))
+                    .fold(f64::INFINITY, f64::min);
+                assert_eq!(cell.min_distance(&query), nearest);
+                let corners = |b: &Rectangle| (b.x, b.y, b.width, b.height);
+                let part = cells
+                    .iter()
+                    .find(|part| corners(&part.boundary) == corners(&cell))
+                    .unwrap();
+                let mut expected = Vec::new();
+                part.collect_points(&mut expected);
+                let mut expected: Vec<_> = expected.into_iter().cloned().collect();
+                expected.sort_by_key(|p| p.data);
+                points.sort_by_key(|p| p.data);
+                assert_eq!(points, expected);
+            }
+        }
+        let empty = Quadtree::<i32>::new(&boundary, 2).unwrap();
+        assert!(
+            empty
+                .nearest_occupied_cell(&Point2D::new(1.0, 1.0, None), 1)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_iter_and_into_iter_yield_every_point() {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut tree = Quadtree::new(&boundary, 3).unwrap();
+        for i in 0..150 {
+            tree.insert(Point2D::new(
+                (i * 7 % 100) as f64,
+                (i * 13 % 100) as f64,
+                Some(i),
+            ));
+        }
+        let mut ids: Vec<_> = (&tree).into_iter().filter_map(|p| p.data).collect();
+        ids.sort();
+        assert_eq!(ids, (0..150).collect::<Vec<_>>());
+        let borrowed: Vec<_> = tree.iter().cloned().collect();
+        let owned: Vec<_> = tree.into_iter().collect();
+        assert_eq!(owned, borrowed);
     }
 }
@@ -0,0 +1,131 @@
+//! ## Random Number Sources
+//!
+//! This module defines `RandomSource`, the minimal interface used by the randomized
+//! algorithms in Spart (for example k-means++ seeding), and `SplitMix64`, a small, fast,
+//! seedable generator that implements it.
+//!
+//! Any other generator can be used by implementing `RandomSource` for it, which usually takes
+//! a single line forwarding to the generator's own `next_u64`.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::random::{RandomSource, SplitMix64};
+//!
+//! let mut rng = SplitMix64::new(42);
+//! let x = rng.next_f64();
+//! assert!((0.0..1.0).contains(&x));
+//! assert!(rng.next_below(10) < 10);
+//! ```
+
+/// A source of uniformly distributed random numbers.
+pub trait RandomSource {
+    /// Returns the next random 64-bit value.
+    fn next_u64(&mut self) -> u64;
+
+    /// Returns a random number uniformly distributed in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        // The 53 high bits fill the mantissa of an `f64` exactly.
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a random index uniformly distributed in `[0, n)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    fn next_below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "cannot pick a random index below zero");
+        // Multiply-shift maps the full 64-bit range onto `[0, n)` with negligible bias.
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+impl<R: RandomSource + ?Sized> RandomSource for &mut R {
+    fn next_u64(&mut self) -> u64 {
+        (**self).next_u64()
+    }
+}
+
+/// The SplitMix64 generator.
+///
+/// It is not cryptographically secure, but it is fast, passes common statistical test
+/// suites, and produces the same sequence for the same seed on every platform, which keeps
+/// randomized results reproducible.
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a generator from a seed.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - Any value; equal seeds produce equal sequences.
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Picks `count` distinct indices in `[0, n)` uniformly at random, in random order.
+///
+/// All indices are returned (shuffled) if `count` is at least `n`.
+pub(crate) fn sample_indices<R: RandomSource>(n: usize, count: usize, rng: &mut R) -> Vec<usize> {
+    let count = count.min(n);
+    let mut indices: Vec<usize> = (0..n).collect();
+    // A partial Fisher-Yates shuffle: the first `count` slots end up holding the sample.
+    for i in 0..count {
+        let j = i + rng.next_below(n - i);
+        indices.swap(i, j);
+    }
+    indices.truncate(count);
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splitmix64_reference_values() {
+        // Reference outputs of SplitMix64 for seed 0.
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+    }
+
+    #[test]
+    fn test_next_f64_and_next_below_ranges() {
+        let mut rng = SplitMix64::new(7);
+        let mut counts = [0usize; 5];
+        for _ in 0..10_000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            counts[rng.next_below(5)] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 1_500));
+    }
+
+    #[test]
+    fn test_sample_indices_are_distinct() {
+        let mut rng = SplitMix64::new(1);
+        let mut sample = sample_indices(100, 30, &mut rng);
+        assert_eq!(sample.len(), 30);
+        sample.sort_unstable();
+        sample.dedup();
+        assert_eq!(sample.len(), 30);
+        assert!(sample.iter().all(|&i| i < 100));
+        assert_eq!(sample_indices(5, 10, &mut rng).len(), 5);
+    }
+}
@@ -1,6 +1,7 @@
 //! ## R*‑tree Implementation
 //!
-//! This module implements an R*‑tree for indexing 2D and 3D points.
+//! This module implements an R*‑tree for indexing 2D and 3D points, and D-dimensional points
+//! (`PointND`, bounded by `Aabb`) for more dimensions.
 //! The implementation supports insertion, deletion, range search, and k‑nearest
 //! neighbor (kNN) search. Points stored in the R*‑tree must implement the `RStarTreeObject` trait,
 //! which requires an implementation of a method to get a minimum bounding rectangle (for 2D)
@@ -29,28 +30,42 @@
 //! assert!(!results3d.is_empty());
 //! ```
 
-use crate::errors::SpartError;
+use crate::errors::{SpartError, check_knn_query, check_query_coords, check_range_query};
+use crate::geo::{GeoBBox, sort_by_bearing};
 use crate::geometry::{
-    BSPBounds, BoundingVolume, BoundingVolumeFromPoint, Cube, DistanceMetric, HasMinDistance,
-    Point2D, Point3D, Rectangle,
+    BSPBounds, Bounded, BoundingVolume, BoundingVolumeFromPoint, DistanceMetric, HasMinDistance,
+    Point2D, Point3D, PointND, Polygon, Rectangle,
 };
+use crate::knn::{DistinctKnnResults, KnnResults, KnnSink, LimitedResults};
+use crate::logging::hot_trace;
+#[cfg(feature = "parallel")]
+use crate::rtree_common::par_search_node as common_par_search_node;
 use crate::rtree_common::{
-    KnnCandidate, compute_group_mbr as common_compute_group_mbr,
-    delete_entry as common_delete_entry, search_node as common_search_node,
+    KnnCandidate, box_distance_sq as common_box_distance_sq, build_quality as common_build_quality,
+    check_tree as common_check_tree, compute_group_mbr as common_compute_group_mbr,
+    delete_entry as common_delete_entry, entry_height as common_entry_height,
+    estimate_count as common_estimate_count, extreme_by_axis as common_extreme_by_axis,
+    knn_filtered as common_knn_filtered, node_height as common_node_height,
+    refit_node as common_refit_node, search_node as common_search_node,
+    search_node_limited as common_search_node_limited, self_join as common_self_join,
+    tree_stats as common_tree_stats, walk_nodes as common_walk_nodes,
 };
-use ordered_float::OrderedFloat;
+use crate::stats::{BuildQuality, TreeStats, TreeSummary};
+use crate::testing::same_multiset;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use tracing::info;
 
-// Epsilon value for zero-sizes bounding boxes/cubes.
+// Tolerance for treating the overlaps of two candidate splits as equal.
 const EPSILON: f64 = 1e-10;
 
 /// Trait for points stored in an R*‑tree.
 ///
 /// Each object must provide its minimum bounding rectangle (or cube) via the `mbr()` method.
+/// Types implementing `geometry::Bounded` implement this trait automatically, which is the
+/// simplest way to make a type usable in every bounding-volume tree.
 #[cfg(feature = "serde")]
 pub trait RStarTreeObject: std::fmt::Debug + Clone {
     /// The type of the bounding volume (e.g. `Rectangle` for 2D objects or `Cube` for 3D objects).
@@ -92,6 +107,22 @@ impl<T: RStarTreeObject> RStarTreeEntry<T> {
             RStarTreeEntry::Node { mbr, .. } => mbr,
         }
     }
+
+    /// Returns the stored object of a leaf entry.
+    pub fn object(&self) -> Option<&T> {
+        match self {
+            RStarTreeEntry::Leaf { object, .. } => Some(object),
+            RStarTreeEntry::Node { .. } => None,
+        }
+    }
+
+    /// Returns the child node of an inner entry.
+    pub fn child(&self) -> Option<&RStarTreeNode<T>> {
+        match self {
+            RStarTreeEntry::Leaf { .. } => None,
+            RStarTreeEntry::Node { child, .. } => Some(child),
+        }
+    }
 }
 
 /// A node in the R*‑tree.
@@ -104,6 +135,28 @@ pub struct RStarTreeNode<T: RStarTreeObject> {
     pub is_leaf: bool,
 }
 
+impl<T: RStarTreeObject> RStarTreeNode<T> {
+    /// Returns the child nodes of an inner node, in stored order (none for a leaf node).
+    pub fn children(&self) -> impl Iterator<Item = &RStarTreeNode<T>> {
+        self.entries.iter().filter_map(RStarTreeEntry::child)
+    }
+
+    /// Returns the objects stored in a leaf node, in stored order (none for an inner node).
+    pub fn objects(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().filter_map(RStarTreeEntry::object)
+    }
+
+    /// Returns the bounding volume covering all entries, or `None` for an empty node.
+    pub fn mbr(&self) -> Option<T::B> {
+        common_compute_group_mbr(&self.entries)
+    }
+
+    /// Returns the height of the subtree rooted at this node: 1 for a leaf node.
+    pub fn height(&self) -> usize {
+        common_node_height(self)
+    }
+}
+
 /// R*‑tree data structure for indexing 2D or 3D points.
 ///
 /// The tree is initialized with a maximum number of entries per node. If a node exceeds this
@@ -114,6 +167,9 @@ pub struct RStarTree<T: RStarTreeObject> {
     root: RStarTreeNode<T>,
     max_entries: usize,
     min_entries: usize,
+    /// Whether overflowing nodes are always split instead of first reinserting some entries.
+    #[cfg_attr(feature = "serde", serde(default))]
+    split_only: bool,
 }
 
 // Common trait implementations for R*-tree to reuse shared algorithms.
@@ -131,6 +187,12 @@ impl<T: RStarTreeObject> crate::rtree_common::EntryAccess for RStarTreeEntry<T>
             _ => None,
         }
     }
+    fn as_leaf_mut(&mut self) -> Option<(&mut Self::BV, &mut Self::Obj)> {
+        match self {
+            RStarTreeEntry::Leaf { mbr, object } => Some((mbr, object)),
+            _ => None,
+        }
+    }
     fn child(&self) -> Option<&<Self as crate::rtree_common::EntryAccess>::Node> {
         match self {
             RStarTreeEntry::Node { child, .. } => Some(child),
@@ -196,9 +258,31 @@ impl<T: RStarTreeObject> RStarTree<T> {
             },
             max_entries,
             min_entries: (max_entries as f64 * 0.4).ceil() as usize,
+            split_only: false,
         })
     }
 
+    /// Enables or disables forced reinsertion (enabled by default).
+    ///
+    /// When a node overflows during an insertion, the R*-tree first removes some of its
+    /// entries and inserts them again, which often finds them a better place and keeps the
+    /// tree tight. This makes some insertions much slower than others, though; with forced
+    /// reinsertion disabled, overflowing nodes are always split, which gives steadier
+    /// insertion times at the cost of a somewhat looser tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether overflowing nodes reinsert some entries before splitting.
+    pub fn with_forced_reinsert(mut self, enabled: bool) -> Self {
+        self.split_only = !enabled;
+        self
+    }
+
+    /// Returns `true` if overflowing nodes reinsert some entries before splitting.
+    pub fn forced_reinsert(&self) -> bool {
+        !self.split_only
+    }
+
     /// Inserts an object into the R*‑tree.
     ///
     /// # Arguments
@@ -209,76 +293,110 @@ impl<T: RStarTreeObject> RStarTree<T> {
         T: Clone,
         T::B: BSPBounds,
     {
-        info!("Inserting object into RStarTree: {:?}", object);
+        hot_trace!("Inserting object into RStarTree", object = object);
         let entry = RStarTreeEntry::Leaf {
             mbr: object.mbr(),
             object,
         };
-        self.insert_entry(entry, None);
+        self.insert_entry(entry);
     }
 
-    fn insert_entry(&mut self, entry: RStarTreeEntry<T>, reinsert_from_level: Option<usize>)
+    fn insert_entry(&mut self, entry: RStarTreeEntry<T>)
     where
         T: Clone,
         T::B: BSPBounds,
     {
-        let mut to_insert = vec![(entry, 0)];
-        let mut reinsert_level = reinsert_from_level;
+        let mut to_insert = vec![entry];
+        let mut reinsertion = Reinsertion {
+            enabled: !self.split_only,
+            done_at: Vec::new(),
+        };
 
-        while let Some((item, level)) = to_insert.pop() {
+        while let Some(item) = to_insert.pop() {
+            // Entries taken out by forced reinsertion may be whole subtrees; they must go back
+            // at their own height, not into a leaf.
+            let target_height = common_entry_height(&item) + 1;
+            let root_height = common_node_height(&self.root);
             let overflow = insert_recursive(
                 &mut self.root,
                 item,
                 self.max_entries,
-                level,
-                &mut reinsert_level,
+                root_height,
+                target_height,
+                &mut reinsertion,
                 &mut to_insert,
             );
 
-            if let Some((overflowed_node, overflow_level)) = overflow {
-                if reinsert_level == Some(overflow_level) {
-                    let old_entries = overflowed_node;
-                    let (group1, group2) = split_entries(old_entries, self.max_entries);
-                    let child1 = RStarTreeNode {
-                        entries: group1,
-                        is_leaf: self.root.is_leaf,
-                    };
-                    let child2 = RStarTreeNode {
-                        entries: group2,
-                        is_leaf: self.root.is_leaf,
-                    };
-                    let mbr1 = common_compute_group_mbr(&child1.entries)
-                        .unwrap_or_else(|| unreachable!("non-empty group must have MBR"));
-                    let mbr2 = common_compute_group_mbr(&child2.entries)
-                        .unwrap_or_else(|| unreachable!("non-empty group must have MBR"));
-                    self.root.is_leaf = false;
-                    self.root.entries.clear();
-                    self.root.entries.push(RStarTreeEntry::Node {
-                        mbr: mbr1,
-                        child: Box::new(child1),
-                    });
-                    self.root.entries.push(RStarTreeEntry::Node {
-                        mbr: mbr2,
-                        child: Box::new(child2),
-                    });
-                } else {
-                    if reinsert_level.is_none() {
-                        reinsert_level = Some(overflow_level);
-                    }
-                    let mut node = RStarTreeNode {
-                        entries: overflowed_node,
-                        is_leaf: self.root.is_leaf,
-                    };
-                    let reinserted_entries = forced_reinsert(&mut node, self.max_entries);
-                    self.root.entries = node.entries;
-                    for entry in reinserted_entries {
-                        to_insert.push((entry, 0));
-                    }
-                }
+            // An overflowing root is always split, growing the tree by one level.
+            if let Some(old_entries) = overflow {
+                let (group1, group2) = split_entries(old_entries, self.max_entries);
+                let child1 = RStarTreeNode {
+                    entries: group1,
+                    is_leaf: self.root.is_leaf,
+                };
+                let child2 = RStarTreeNode {
+                    entries: group2,
+                    is_leaf: self.root.is_leaf,
+                };
+                let mbr1 = common_compute_group_mbr(&child1.entries)
+                    .unwrap_or_else(|| unreachable!("non-empty group must have MBR"));
+                let mbr2 = common_compute_group_mbr(&child2.entries)
+                    .unwrap_or_else(|| unreachable!("non-empty group must have MBR"));
+                self.root.is_leaf = false;
+                self.root.entries.clear();
+                self.root.entries.push(RStarTreeEntry::Node {
+                    mbr: mbr1,
+                    child: Box::new(child1),
+                });
+                self.root.entries.push(RStarTreeEntry::Node {
+                    mbr: mbr2,
+                    child: Box::new(child2),
+                });
             }
         }
     }
 
+    /// Recomputes all bounding volumes bottom-up without restructuring the tree.
+    ///
+    /// Use this after the stored objects have moved slightly (for example through interior
+    /// mutability) to make the tree consistent again; this is much cheaper than deleting and
+    /// reinserting every object. Since the structure is not changed, query performance degrades
+    /// as objects drift away from their original neighbors, so the tree should still be rebuilt
+    /// periodically (e.g., with `insert_bulk` into a fresh tree).
+    pub fn refit(&mut self) {
+        self.refit_with(|_| {});
+    }
+
+    /// Applies `update` to every stored object and then recomputes all bounding volumes
+    /// bottom-up, without restructuring the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - A function that moves or resizes an object in place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Rectangle;
+    /// use spart::rstar_tree::RStarTree;
+    ///
+    /// let mut tree: RStarTree<Rectangle> = RStarTree::new(4).unwrap();
+    /// for i in 0..10 {
+    ///     tree.insert(Rectangle { x: i as f64 * 3.0, y: 0.0, width: 1.0, height: 1.0 });
+    /// }
+    /// // Move every box one unit to the right.
+    /// tree.refit_with(|r| r.x += 1.0);
+    /// let query = Rectangle { x: 0.0, y: 0.0, width: 0.5, height: 1.0 };
+    /// assert!(tree.range_search_bbox(&query).is_empty());
+    /// ```
+    pub fn refit_with<F: FnMut(&mut T)>(&mut self, mut update: F) {
+        info!("Refitting R*-tree bounding volumes");
+        common_refit_node(&mut self.root, &mut |object: &mut T| {
+            update(object);
+            object.mbr()
+        });
+    }
+
     /// Performs a range search with a given query bounding volume.
     ///
     /// # Arguments
@@ -289,14 +407,200 @@ impl<T: RStarTreeObject> RStarTree<T> {
     ///
     /// A vector of references to the objects whose minimum bounding volumes intersect the query.
     pub fn range_search_bbox(&self, query: &T::B) -> Vec<&T> {
-        info!("Performing range search with query: {:?}", query);
+        hot_trace!("Performing range search", query = query);
         let mut result = Vec::new();
         common_search_node(&self.root, query, &mut result);
         result
     }
 
+    /// Performs a range search like `range_search_bbox`, stopping once `max_results` objects
+    /// are found.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The bounding volume to search against.
+    /// * `max_results` - The maximum number of objects to return.
+    ///
+    /// # Returns
+    ///
+    /// Up to `max_results` objects whose minimum bounding volumes intersect the query, and
+    /// whether more objects matched.
+    pub fn range_search_bbox_limited(
+        &self,
+        query: &T::B,
+        max_results: usize,
+    ) -> LimitedResults<&T> {
+        let mut result = Vec::new();
+        let limit = LimitedResults::<&T>::limit_for(max_results);
+        common_search_node_limited(&self.root, query, limit, &mut result);
+        LimitedResults::from_overfull(result, max_results)
+    }
+
+    /// Estimates the number of objects `range_search_bbox` would return for `query`, without
+    /// reading the objects.
+    ///
+    /// Only the inner nodes are visited: each leaf node whose MBR intersects the query counts
+    /// for its number of objects times the fraction of its MBR inside the query. The estimate
+    /// is close when the objects are spread evenly within each leaf, and is cheap enough for a
+    /// query planner to call before choosing between the index and another access path.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query bounding volume.
+    ///
+    /// # Returns
+    ///
+    /// The estimated number of objects intersecting `query`.
+    pub fn estimate_count_in_bbox(&self, query: &T::B) -> f64
+    where
+        T::B: BSPBounds,
+    {
+        common_estimate_count(&self.root, query)
+    }
+
+    /// Performs `range_search_bbox`, searching the subtrees of large nodes in parallel.
+    ///
+    /// The entries of a node are searched as parallel tasks (with Rayon) when each of its
+    /// subtrees is estimated to hold at least `min_subtree_size` objects, judging by their
+    /// height and the minimum number of entries per node. Smaller subtrees are searched
+    /// sequentially, so small trees and the lower levels of large trees avoid the overhead of
+    /// spawning tasks. The results are the same, and in the same order, as those of
+    /// `range_search_bbox`. Requires the `parallel` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query bounding volume.
+    /// * `min_subtree_size` - The estimated subtree size below which the search is sequential
+    ///   (e.g., a few thousand objects).
+    #[cfg(feature = "parallel")]
+    pub fn range_search_bbox_parallel(&self, query: &T::B, min_subtree_size: usize) -> Vec<&T>
+    where
+        T: Sync,
+        T::B: Sync,
+    {
+        hot_trace!("Performing parallel range search", query = query);
+        common_par_search_node(
+            &self.root,
+            common_node_height(&self.root),
+            query,
+            min_subtree_size,
+            self.min_entries,
+        )
+    }
+
+    /// Returns the stored object whose MBR has the smallest lower bound along `axis`.
+    ///
+    /// For points this is the point with the smallest coordinate. Subtrees whose MBR cannot
+    /// reach below the best object found so far are skipped. If several objects tie, any one
+    /// of them is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The axis to compare.
+    ///
+    /// # Returns
+    ///
+    /// The object, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is not below the dimension of the
+    /// bounding volumes.
+    pub fn min_by_axis(&self, axis: usize) -> Result<Option<&T>, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        self.extreme_by_axis(axis, false)
+    }
+
+    /// Returns the stored object whose MBR has the largest upper bound along `axis`.
+    ///
+    /// This is the counterpart of `min_by_axis` and prunes the tree the same way.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The axis to compare.
+    ///
+    /// # Returns
+    ///
+    /// The object, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is not below the dimension of the
+    /// bounding volumes.
+    pub fn max_by_axis(&self, axis: usize) -> Result<Option<&T>, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        self.extreme_by_axis(axis, true)
+    }
+
+    fn extreme_by_axis(&self, axis: usize, max: bool) -> Result<Option<&T>, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        if axis >= T::B::DIM {
+            return Err(SpartError::InvalidDimension {
+                requested: axis,
+                available: T::B::DIM,
+            });
+        }
+        let mut best = None;
+        common_extreme_by_axis(&self.root, axis, max, &mut best);
+        Ok(best.map(|(_, object)| object))
+    }
+
+    /// Finds the k objects whose minimum bounding volumes are nearest to a query box.
+    ///
+    /// Boxes are compared by the smallest distance between them (zero when they intersect),
+    /// which is used both to rank the objects and to prune the subtrees, so extended objects
+    /// like building footprints are found by their nearest edge rather than by their center.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query box.
+    /// * `k` - The number of objects to return.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to up to k objects, from nearest to farthest. Objects at the
+    /// same distance (e.g., all objects intersecting the query) come in no particular order.
+    pub fn nearest_bbox(&self, query: &T::B, k: usize) -> Vec<&T>
+    where
+        T::B: BSPBounds,
+    {
+        common_knn_filtered(
+            &self.root,
+            k,
+            |mbr: &T::B| common_box_distance_sq(mbr, query),
+            |object: &T| Some(common_box_distance_sq(&object.mbr(), query)),
+            |_: &T::B| true,
+        )
+    }
+
+    /// Returns every pair of stored objects whose minimum bounding volumes overlap.
+    ///
+    /// This is the broad phase of a collision detection step: the tree is joined with itself
+    /// in a single traversal, each overlapping pair is reported exactly once (in no particular
+    /// order), and no object is paired with itself. Objects that are stored more than once are
+    /// treated as distinct objects.
+    ///
+    /// # Returns
+    ///
+    /// A vector of pairs of references to overlapping objects.
+    pub fn broad_phase_pairs(&self) -> Vec<(&T, &T)> {
+        let mut pairs = Vec::new();
+        common_self_join(&self.root, &mut pairs);
+        pairs
+    }
+
     /// Inserts a bulk of objects into the R*-tree.
     ///
+    /// An empty tree is packed bottom-up from the objects in their given order, with the
+    /// entries spread evenly over the nodes of each level. A tree that already holds objects
+    /// inserts them one by one.
+    ///
     /// # Arguments
     ///
     /// * `objects` - The objects to insert.
@@ -308,6 +612,12 @@ impl<T: RStarTreeObject> RStarTree<T> {
         if objects.is_empty() {
             return;
         }
+        if !self.root.entries.is_empty() {
+            for object in objects {
+                self.insert(object);
+            }
+            return;
+        }
 
         let mut entries: Vec<RStarTreeEntry<T>> = objects
             .into_iter()
@@ -317,30 +627,80 @@ impl<T: RStarTreeObject> RStarTree<T> {
             })
             .collect();
 
+        let mut is_leaf = true;
         while entries.len() > self.max_entries {
-            let mut new_level_entries = Vec::new();
-            let chunks = entries.chunks(self.max_entries);
-
-            for chunk in chunks {
+            let count = entries.len();
+            let groups = count.div_ceil(self.max_entries);
+            let mut rest = entries.into_iter();
+            let mut new_level_entries = Vec::with_capacity(groups);
+            for i in 0..groups {
+                let size = count / groups + usize::from(i < count % groups);
                 let child_node = RStarTreeNode {
-                    entries: chunk.to_vec(),
-                    is_leaf: self.root.is_leaf,
+                    entries: rest.by_ref().take(size).collect(),
+                    is_leaf,
                 };
-                if let Some(mbr) = common_compute_group_mbr(&child_node.entries) {
-                    new_level_entries.push(RStarTreeEntry::Node {
-                        mbr,
-                        child: Box::new(child_node),
-                    });
-                }
+                let mbr = common_compute_group_mbr(&child_node.entries)
+                    .unwrap_or_else(|| unreachable!("bulk-loaded nodes are never empty"));
+                new_level_entries.push(RStarTreeEntry::Node {
+                    mbr,
+                    child: Box::new(child_node),
+                });
             }
             entries = new_level_entries;
-            self.root.is_leaf = false;
+            is_leaf = false;
         }
 
-        self.root.entries.extend(entries);
+        self.root = RStarTreeNode { entries, is_leaf };
     }
 
-    #[doc(hidden)]
+    /// Builds a tree holding `objects`, packed with Sort-Tile-Recursive.
+    ///
+    /// This is a shortcut for `RStarTreeBuilder::new(max_entries).bulk_load(objects)`; use the
+    /// builder to choose another packing algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_entries` - The maximum number of entries allowed in a node.
+    /// * `objects` - The objects to store.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `max_entries` is less than 2.
+    pub fn bulk_load(max_entries: usize, objects: Vec<T>) -> Result<Self, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        RStarTreeBuilder::new(max_entries).bulk_load(objects)
+    }
+
+    /// Returns the root node, for custom traversals of the tree.
+    ///
+    /// Nodes and entries are read-only; use the methods of the tree to modify it.
+    pub fn root(&self) -> &RStarTreeNode<T> {
+        &self.root
+    }
+
+    /// Returns all nodes of the tree in pre-order, each with its depth (0 for the root).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::rstar_tree::RStarTree;
+    ///
+    /// let mut tree = RStarTree::new(4).unwrap();
+    /// for i in 0..20 {
+    ///     tree.insert(Point2D::new(i as f64, 0.0, None::<()>));
+    /// }
+    /// let leaves = tree.nodes().filter(|(_, node)| node.is_leaf);
+    /// assert_eq!(leaves.map(|(_, node)| node.objects().count()).sum::<usize>(), 20);
+    /// assert!(tree.nodes().all(|(depth, node)| depth + node.height() == tree.height()));
+    /// ```
+    pub fn nodes(&self) -> impl Iterator<Item = (usize, &RStarTreeNode<T>)> {
+        common_walk_nodes(&self.root)
+    }
+
+    /// Returns the height of the tree: 1 while the root is a leaf node.
     pub fn height(&self) -> usize {
         let mut height = 1;
         let mut current_node = &self.root;
@@ -355,8 +715,134 @@ impl<T: RStarTreeObject> RStarTree<T> {
         }
         height
     }
+
+    /// Returns statistics about the shape of the tree.
+    ///
+    /// The fill factor is the mean number of entries per node divided by the maximum number
+    /// of entries.
+    pub fn stats(&self) -> TreeStats {
+        common_tree_stats(&self.root, self.max_entries)
+    }
+
+    /// Returns measures of how well the nodes fit their entries: the sibling overlap, the
+    /// dead space, and the average fill.
+    ///
+    /// Checking the report after a series of insertions and deletions, against the one
+    /// returned by `rebuild`, tells whether the tree has degraded enough to be rebuilt.
+    pub fn quality_report(&self) -> BuildQuality {
+        common_build_quality(&self.root, self.max_entries)
+    }
+
+    /// Rebuilds the tree from scratch by bulk loading its objects with Sort-Tile-Recursive,
+    /// which packs the nodes full again after deletions have left them sparse and insertions
+    /// have enlarged them.
+    ///
+    /// The maximum number of entries per node is kept. Rebuilding takes about as long as the
+    /// original bulk load.
+    ///
+    /// # Returns
+    ///
+    /// The quality report of the rebuilt tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::rstar_tree::RStarTree;
+    ///
+    /// let mut tree = RStarTree::new(8).unwrap();
+    /// for i in 0..500 {
+    ///     tree.insert(Point2D::new((i * 37 % 101) as f64, (i * 53 % 97) as f64, Some(i)));
+    /// }
+    /// let before = tree.quality_report();
+    /// let after = tree.rebuild();
+    /// assert!(after.average_fill >= before.average_fill);
+    /// assert_eq!(tree.stats().size, 500);
+    /// ```
+    pub fn rebuild(&mut self) -> BuildQuality
+    where
+        T::B: BSPBounds,
+    {
+        let mut objects = Vec::new();
+        Self::drain_objects(std::mem::take(&mut self.root.entries), &mut objects);
+        info!("Rebuilding RStarTree with {} objects", objects.len());
+        *self = RStarTreeBuilder::new(self.max_entries)
+            .with_forced_reinsert(self.forced_reinsert())
+            .bulk_load(objects)
+            .unwrap_or_else(|_| unreachable!("the maximum number of entries was validated"));
+        self.quality_report()
+    }
+
+    /// Moves the objects stored below `entries` into `objects`.
+    fn drain_objects(entries: Vec<RStarTreeEntry<T>>, objects: &mut Vec<T>) {
+        for entry in entries {
+            match entry {
+                RStarTreeEntry::Leaf { object, .. } => objects.push(object),
+                RStarTreeEntry::Node { child, .. } => Self::drain_objects(child.entries, objects),
+            }
+        }
+    }
+
+    /// Returns `true` if both trees hold the same objects, counting duplicates, regardless of
+    /// their node sizes or how the objects are arranged in the nodes.
+    pub fn content_eq(&self, other: &Self) -> bool
+    where
+        T: PartialEq,
+        T::B: BSPBounds,
+    {
+        let mine = common_walk_nodes(&self.root).flat_map(|(_, node)| node.objects());
+        let theirs = common_walk_nodes(&other.root).flat_map(|(_, node)| node.objects());
+        same_multiset(mine.collect(), theirs.collect(), |object| {
+            let mbr = object.mbr();
+            (0..T::B::DIM)
+                .filter_map(|dim| mbr.center(dim).ok())
+                .collect()
+        })
+    }
+
+    /// Returns a concise description of the tree: its size, depth, maximum number of entries
+    /// per node, and the bounding volume of its objects.
+    pub fn summary(&self) -> TreeSummary
+    where
+        T::B: BSPBounds,
+    {
+        let stats = self.stats();
+        TreeSummary {
+            kind: "RStarTree",
+            size: stats.size,
+            depth: stats.depth,
+            capacity: Some(self.max_entries),
+            bounds: self
+                .root
+                .mbr()
+                .map_or_else(Vec::new, |mbr| TreeSummary::axis_bounds(&mbr)),
+        }
+    }
+
+    /// Checks the structural invariants of the tree.
+    ///
+    /// Every node holds between the minimum and maximum number of entries (the root may hold
+    /// fewer), all leaf nodes are at the same depth, and the MBR of every node entry encloses
+    /// the entries of its child. A tree built through the public API always passes; the check
+    /// is meant for trees restored from untrusted or possibly corrupted data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvariantViolation` describing the first violation found.
+    pub fn check_invariants(&self) -> Result<(), SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        common_check_tree(&self.root, self.max_entries, self.min_entries)
+    }
 }
 
+/// Picks the entry of `node` whose subtree should receive `entry`.
+///
+/// Above the leaves, the entry whose MBR needs the least overlap with its siblings after
+/// enlargement is preferred; otherwise the one needing the least enlargement, then the one
+/// with the smallest area. Remaining ties go to the lowest index, so the same insertion
+/// sequence always produces the same tree.
 fn choose_subtree<T: RStarTreeObject>(node: &RStarTreeNode<T>, entry: &RStarTreeEntry<T>) -> usize {
     let children_are_leaves = if let Some(RStarTreeEntry::Node { child, .. }) = node.entries.first()
     {
@@ -365,88 +851,66 @@ fn choose_subtree<T: RStarTreeObject>(node: &RStarTreeNode<T>, entry: &RStarTree
         false
     };
 
-    if children_are_leaves {
-        node.entries
-            .iter()
-            .enumerate()
-            .min_by(|&(_, a), &(_, b)| {
-                let mbr_a = a.mbr();
-                let mbr_b = b.mbr();
-
-                let overlap_a = node
-                    .entries
-                    .iter()
-                    .filter(|e| !std::ptr::eq(*e, a))
-                    .map(|e| e.mbr().union(entry.mbr()).overlap(e.mbr()))
-                    .sum::<f64>();
-
-                let overlap_b = node
-                    .entries
-                    .iter()
-                    .filter(|e| !std::ptr::eq(*e, b))
-                    .map(|e| e.mbr().union(entry.mbr()).overlap(e.mbr()))
-                    .sum::<f64>();
-
-                let overlap_cmp = overlap_a.partial_cmp(&overlap_b).unwrap_or(Ordering::Equal);
-                if overlap_cmp != Ordering::Equal {
-                    return overlap_cmp;
-                }
-
-                let enlargement_a = mbr_a.enlargement(entry.mbr());
-                let enlargement_b = mbr_b.enlargement(entry.mbr());
-                let enlargement_cmp = enlargement_a
-                    .partial_cmp(&enlargement_b)
-                    .unwrap_or(Ordering::Equal);
-                if enlargement_cmp != Ordering::Equal {
-                    return enlargement_cmp;
-                }
-
-                mbr_a
-                    .area()
-                    .partial_cmp(&mbr_b.area())
-                    .unwrap_or(Ordering::Equal)
-            })
-            .map(|(i, _)| i)
-            .unwrap_or(0)
-    } else {
-        node.entries
-            .iter()
-            .enumerate()
-            .min_by(|(_, a), (_, b)| {
-                let mbr_a = a.mbr();
-                let mbr_b = b.mbr();
-
-                let enlargement_a = mbr_a.enlargement(entry.mbr());
-                let enlargement_b = mbr_b.enlargement(entry.mbr());
-
-                let enlargement_cmp = enlargement_a
-                    .partial_cmp(&enlargement_b)
-                    .unwrap_or(Ordering::Equal);
-                if enlargement_cmp != Ordering::Equal {
-                    return enlargement_cmp;
-                }
-                mbr_a
-                    .area()
-                    .partial_cmp(&mbr_b.area())
-                    .unwrap_or(Ordering::Equal)
-            })
-            .map(|(i, _)| i)
-            .unwrap_or(0)
+    let mut best_index = 0;
+    let mut best_key: Option<(f64, f64, f64)> = None;
+    for (i, candidate) in node.entries.iter().enumerate() {
+        let mbr = candidate.mbr();
+        let overlap = if children_are_leaves {
+            let enlarged = mbr.union(entry.mbr());
+            node.entries
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, other)| enlarged.overlap(other.mbr()))
+                .sum::<f64>()
+        } else {
+            0.0
+        };
+        let key = (overlap, mbr.enlargement(entry.mbr()), mbr.area());
+        let is_better = best_key.is_none_or(|best| {
+            key.0
+                .total_cmp(&best.0)
+                .then(key.1.total_cmp(&best.1))
+                .then(key.2.total_cmp(&best.2))
+                == Ordering::Less
+        });
+        if is_better {
+            best_index = i;
+            best_key = Some(key);
+        }
     }
+    best_index
 }
 
+/// The forced reinsertions allowed while inserting one entry.
+struct Reinsertion {
+    /// Whether overflows may be resolved by forced reinsertion at all.
+    enabled: bool,
+    /// Heights at which a forced reinsertion already happened while inserting this entry;
+    /// a second overflow at the same height is resolved by a split instead.
+    done_at: Vec<usize>,
+}
+
+/// Inserts `entry` into the subtree of height `height` rooted at `node`, placing it in the
+/// node of height `target_height` on the chosen path.
+///
+/// Overflowing nodes below `node` are handled here: if `reinsertion` allows it, the first
+/// overflow at a given height triggers a forced reinsertion (the removed entries are pushed to
+/// `to_insert_queue`); other overflows split the node. If `node` itself overflows, its entries are taken and returned so the
+/// caller can deal with them.
 fn insert_recursive<T: RStarTreeObject + Clone>(
     node: &mut RStarTreeNode<T>,
     entry: RStarTreeEntry<T>,
     max_entries: usize,
-    level: usize,
-    reinsert_level: &mut Option<usize>,
-    to_insert_queue: &mut Vec<(RStarTreeEntry<T>, usize)>,
-) -> Option<(Vec<RStarTreeEntry<T>>, usize)>
+    height: usize,
+    target_height: usize,
+    reinsertion: &mut Reinsertion,
+    to_insert_queue: &mut Vec<RStarTreeEntry<T>>,
+) -> Option<Vec<RStarTreeEntry<T>>>
 where
     T::B: BSPBounds,
 {
-    if node.is_leaf {
+    if node.is_leaf || height <= target_height {
         node.entries.push(entry);
     } else {
         let best_index = choose_subtree(node, &entry);
@@ -456,15 +920,17 @@ where
             unreachable!()
         };
 
-        if let Some((overflow, overflow_level)) = insert_recursive(
+        if let Some(overflow) = insert_recursive(
             child,
             entry,
             max_entries,
-            level + 1,
-            reinsert_level,
+            height - 1,
+            target_height,
+            reinsertion,
             to_insert_queue,
         ) {
-            if reinsert_level.is_some() && *reinsert_level == Some(overflow_level) {
+            let child_height = height - 1;
+            if !reinsertion.enabled || reinsertion.done_at.contains(&child_height) {
                 let (g1, g2) = split_entries(overflow, max_entries);
                 let child1 = RStarTreeNode {
                     entries: g1,
@@ -487,17 +953,13 @@ where
                     child: Box::new(child2),
                 });
             } else {
-                if reinsert_level.is_none() {
-                    *reinsert_level = Some(overflow_level);
-                }
+                reinsertion.done_at.push(child_height);
                 let mut overflowed_node = RStarTreeNode {
                     entries: overflow,
                     is_leaf: child.is_leaf,
                 };
                 let reinserted = forced_reinsert(&mut overflowed_node, max_entries);
-                for item in reinserted {
-                    to_insert_queue.push((item, 0));
-                }
+                to_insert_queue.extend(reinserted);
                 if let RStarTreeEntry::Node { child, .. } = &mut node.entries[best_index] {
                     child.entries = overflowed_node.entries;
                 }
@@ -517,7 +979,7 @@ where
     }
 
     if node.entries.len() > max_entries {
-        return Some((std::mem::take(&mut node.entries), level));
+        return Some(std::mem::take(&mut node.entries));
     }
     None
 }
@@ -535,47 +997,56 @@ where
         return Vec::new();
     };
     let reinsert_count = (max_entries as f64 * 0.3).ceil() as usize;
-
-    node.entries.sort_by(|a, b| {
-        let center_a: Vec<f64> = (0..T::B::DIM)
-            .map(|d| {
-                a.mbr()
-                    .center(d)
-                    .unwrap_or_else(|_| unreachable!("dim valid"))
-            })
-            .collect();
-        let center_b: Vec<f64> = (0..T::B::DIM)
-            .map(|d| {
-                b.mbr()
+    let node_center: Vec<f64> = (0..T::B::DIM)
+        .map(|d| {
+            node_mbr
+                .center(d)
+                .unwrap_or_else(|_| unreachable!("dim valid"))
+        })
+        .collect();
+    let center_dist_sq = |entry: &RStarTreeEntry<T>| {
+        node_center
+            .iter()
+            .enumerate()
+            .map(|(d, c)| {
+                let ec = entry
+                    .mbr()
                     .center(d)
-                    .unwrap_or_else(|_| unreachable!("dim valid"))
+                    .unwrap_or_else(|_| unreachable!("dim valid"));
+                (ec - c).powi(2)
             })
-            .collect();
-        let node_center: Vec<f64> = (0..T::B::DIM)
-            .map(|d| {
-                node_mbr
-                    .center(d)
-                    .unwrap_or_else(|_| unreachable!("dim valid"))
-            })
-            .collect();
-
-        let dist_a = center_a
-            .iter()
-            .zip(node_center.iter())
-            .map(|(ca, cb)| (ca - cb).powi(2))
-            .sum::<f64>();
-        let dist_b = center_b
-            .iter()
-            .zip(node_center.iter())
-            .map(|(ca, cb)| (ca - cb).powi(2))
-            .sum::<f64>();
+            .sum::<f64>()
+    };
 
-        dist_b.partial_cmp(&dist_a).unwrap_or(Ordering::Equal)
-    });
+    // Farthest from the node's center first; the sort is stable, so entries at the same
+    // distance keep their order.
+    node.entries
+        .sort_by(|a, b| center_dist_sq(b).total_cmp(&center_dist_sq(a)));
 
     node.entries.drain(0..reinsert_count).collect()
 }
 
+/// Sorts entries by the center of their MBR along `axis`.
+///
+/// The sort is stable and uses a total order, so entries with equal centers keep their
+/// relative order and the result is the same on every run.
+fn sort_by_center<T: RStarTreeObject>(entries: &mut [RStarTreeEntry<T>], axis: usize)
+where
+    T::B: BSPBounds,
+{
+    entries.sort_by(|a, b| {
+        let ca = a
+            .mbr()
+            .center(axis)
+            .unwrap_or_else(|_| unreachable!("dim valid"));
+        let cb = b
+            .mbr()
+            .center(axis)
+            .unwrap_or_else(|_| unreachable!("dim valid"));
+        ca.total_cmp(&cb)
+    });
+}
+
 fn split_entries<T: RStarTreeObject + Clone>(
     mut entries: Vec<RStarTreeEntry<T>>,
     max_entries: usize,
@@ -589,17 +1060,7 @@ where
     let mut min_margin = f64::INFINITY;
 
     for dim in 0..T::B::DIM {
-        entries.sort_by(|a, b| {
-            let ca = a
-                .mbr()
-                .center(dim)
-                .unwrap_or_else(|_| unreachable!("dim valid"));
-            let cb = b
-                .mbr()
-                .center(dim)
-                .unwrap_or_else(|_| unreachable!("dim valid"));
-            ca.partial_cmp(&cb).unwrap_or(Ordering::Equal)
-        });
+        sort_by_center(&mut entries, dim);
 
         for k in min_entries..=entries.len() - min_entries {
             let group1 = &entries[..k];
@@ -617,17 +1078,7 @@ where
         }
     }
 
-    entries.sort_by(|a, b| {
-        let ca = a
-            .mbr()
-            .center(best_axis)
-            .unwrap_or_else(|_| unreachable!("dim valid"));
-        let cb = b
-            .mbr()
-            .center(best_axis)
-            .unwrap_or_else(|_| unreachable!("dim valid"));
-        ca.partial_cmp(&cb).unwrap_or(Ordering::Equal)
-    });
+    sort_by_center(&mut entries, best_axis);
 
     let mut best_overlap = f64::INFINITY;
     let mut best_area = f64::INFINITY;
@@ -669,9 +1120,10 @@ where
     ///
     /// # Returns
     ///
-    /// `true` if at least one matching object was found and removed.
+    /// `true` if a matching object was found and removed. If several stored objects are equal
+    /// to `object`, only one of them is removed.
     pub fn delete(&mut self, object: &T) -> bool {
-        info!("Attempting to delete object: {:?}", object);
+        hot_trace!("Deleting object from RStarTree", object = object);
         let object_mbr = object.mbr();
         let mut reinsert_list = Vec::new();
         let deleted = common_delete_entry(
@@ -684,7 +1136,7 @@ where
 
         if deleted {
             for entry in reinsert_list {
-                self.insert_entry(entry, None);
+                self.insert_entry(entry);
             }
 
             if !self.root.is_leaf && self.root.entries.len() == 1 {
@@ -695,35 +1147,392 @@ where
         }
         deleted
     }
+
+    /// Deletes several objects from the R*‑tree.
+    ///
+    /// Each object removes at most one matching stored object, as with `delete`.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects` - The objects to delete.
+    ///
+    /// # Returns
+    ///
+    /// The number of objects that were found and deleted.
+    pub fn delete_bulk(&mut self, objects: &[T]) -> usize {
+        objects.iter().filter(|object| self.delete(object)).count()
+    }
+
+    /// Keeps only the objects for which `keep` returns `true`, deleting the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - Called once for each stored object.
+    ///
+    /// # Returns
+    ///
+    /// The number of objects that were deleted.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) -> usize {
+        let rejected: Vec<T> = common_walk_nodes(&self.root)
+            .flat_map(|(_, node)| node.objects())
+            .filter(|object| !keep(object))
+            .cloned()
+            .collect();
+        self.delete_bulk(&rejected)
+    }
 }
 
-impl<T: std::fmt::Debug + Clone> RStarTreeObject for Point2D<T> {
-    type B = Rectangle;
+impl<T: Bounded> RStarTreeObject for T {
+    type B = <T as Bounded>::B;
     fn mbr(&self) -> Self::B {
-        Rectangle {
-            x: self.x,
-            y: self.y,
-            width: EPSILON,
-            height: EPSILON,
+        Bounded::mbr(self)
+    }
+}
+
+/// The algorithm used to pack objects into nodes when bulk loading an R*‑tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BulkLoadStrategy {
+    /// Sort-Tile-Recursive: the objects are sorted into slabs along each axis in turn and
+    /// packed into full leaves, and the leaves are packed the same way level by level, from the
+    /// bottom up. It is fast and works well for roughly uniform data.
+    #[default]
+    SortTileRecursive,
+    /// Overlap-Minimizing Top-down: the objects are split into as many slabs as the root needs
+    /// entries, and each slab is split again for the level below, from the top down. The upper
+    /// levels cover balanced, non-overlapping regions, which gives better trees for skewed
+    /// or clustered data.
+    OverlapMinimizingTopDown,
+}
+
+/// Builder for `RStarTree`, for choosing how a tree is bulk loaded.
+///
+/// # Example
+///
+/// ```
+/// use spart::geometry::{Point2D, Rectangle};
+/// use spart::rstar_tree::{BulkLoadStrategy, RStarTreeBuilder};
+///
+/// let points: Vec<Point2D<usize>> = (0..1000)
+///     .map(|i| Point2D::new((i % 10) as f64, (i * i % 997) as f64, Some(i)))
+///     .collect();
+/// let tree = RStarTreeBuilder::new(8)
+///     .with_strategy(BulkLoadStrategy::OverlapMinimizingTopDown)
+///     .bulk_load(points)
+///     .unwrap();
+/// let query = Rectangle { x: 0.0, y: 0.0, width: 9.0, height: 997.0 };
+/// assert_eq!(tree.range_search_bbox(&query).len(), 1000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RStarTreeBuilder {
+    max_entries: usize,
+    strategy: BulkLoadStrategy,
+    forced_reinsert: bool,
+}
+
+impl RStarTreeBuilder {
+    /// Creates a builder for trees with the given maximum number of entries per node.
+    ///
+    /// The value is validated when the tree is built.
+    pub fn new(max_entries: usize) -> Self {
+        RStarTreeBuilder {
+            max_entries,
+            strategy: BulkLoadStrategy::default(),
+            forced_reinsert: true,
         }
     }
+
+    /// Sets the algorithm used by `bulk_load`.
+    pub fn with_strategy(mut self, strategy: BulkLoadStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Enables or disables forced reinsertion in the built tree (enabled by default); see
+    /// `RStarTree::with_forced_reinsert`.
+    ///
+    /// Disabling it suits latency-sensitive workloads that insert objects online, one at a
+    /// time, after the tree is built.
+    pub fn with_forced_reinsert(mut self, enabled: bool) -> Self {
+        self.forced_reinsert = enabled;
+        self
+    }
+
+    /// Builds an empty tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if the maximum number of entries is less than 2.
+    pub fn build<T: RStarTreeObject>(self) -> Result<RStarTree<T>, SpartError> {
+        Ok(RStarTree::new(self.max_entries)?.with_forced_reinsert(self.forced_reinsert))
+    }
+
+    /// Builds a tree holding `objects`, packed with the chosen strategy.
+    ///
+    /// Bulk loading is much faster than inserting the objects one at a time, and the nodes
+    /// are fuller and overlap less. All leaves end up at the same depth.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects` - The objects to store.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if the maximum number of entries is less than 2.
+    pub fn bulk_load<T>(self, objects: Vec<T>) -> Result<RStarTree<T>, SpartError>
+    where
+        T: RStarTreeObject,
+        T::B: BSPBounds,
+    {
+        let mut tree = RStarTree::new(self.max_entries)?.with_forced_reinsert(self.forced_reinsert);
+        info!(
+            "Bulk loading {} objects into RStarTree with strategy {:?}",
+            objects.len(),
+            self.strategy
+        );
+        let entries: Vec<RStarTreeEntry<T>> = objects
+            .into_iter()
+            .map(|object| RStarTreeEntry::Leaf {
+                mbr: object.mbr(),
+                object,
+            })
+            .collect();
+        tree.root = match self.strategy {
+            BulkLoadStrategy::SortTileRecursive => pack_str(entries, self.max_entries),
+            BulkLoadStrategy::OverlapMinimizingTopDown => {
+                let mut height = 1;
+                let mut capacity = self.max_entries;
+                while capacity < entries.len() {
+                    capacity = capacity.saturating_mul(self.max_entries);
+                    height += 1;
+                }
+                pack_omt(entries, self.max_entries, height)
+            }
+        };
+        Ok(tree)
+    }
 }
 
-impl<T: std::fmt::Debug + Clone> RStarTreeObject for Point3D<T> {
-    type B = Cube;
-    fn mbr(&self) -> Self::B {
-        Cube {
-            x: self.x,
-            y: self.y,
-            z: self.z,
-            width: EPSILON,
-            height: EPSILON,
-            depth: EPSILON,
+/// Packs entries bottom-up with Sort-Tile-Recursive and returns the root node.
+fn pack_str<T>(mut entries: Vec<RStarTreeEntry<T>>, max_entries: usize) -> RStarTreeNode<T>
+where
+    T: RStarTreeObject,
+    T::B: BSPBounds,
+{
+    let mut is_leaf = true;
+    while entries.len() > max_entries {
+        let sizes = even_sizes(entries.len(), entries.len().div_ceil(max_entries));
+        entries = tile(entries, &sizes, 0)
+            .into_iter()
+            .map(|group| {
+                node_entry(RStarTreeNode {
+                    entries: group,
+                    is_leaf,
+                })
+            })
+            .collect();
+        is_leaf = false;
+    }
+    RStarTreeNode { entries, is_leaf }
+}
+
+/// Packs entries top-down with Overlap-Minimizing Top-down into a subtree of the given height
+/// (1 for a leaf node) and returns its root node.
+///
+/// `entries` must fit into the subtree, i.e., there are at most `max_entries^height` of them.
+fn pack_omt<T>(entries: Vec<RStarTreeEntry<T>>, max_entries: usize, height: u32) -> RStarTreeNode<T>
+where
+    T: RStarTreeObject,
+    T::B: BSPBounds,
+{
+    if height <= 1 {
+        return RStarTreeNode {
+            entries,
+            is_leaf: true,
+        };
+    }
+    // Each child subtree holds up to `max_entries^(height - 1)` objects.
+    let child_capacity = max_entries.saturating_pow(height - 1);
+    let sizes = even_sizes(entries.len(), entries.len().div_ceil(child_capacity));
+    let children = tile(entries, &sizes, 0)
+        .into_iter()
+        .map(|group| node_entry(pack_omt(group, max_entries, height - 1)))
+        .collect();
+    RStarTreeNode {
+        entries: children,
+        is_leaf: false,
+    }
+}
+
+/// Splits `n` into `parts` sizes that differ by at most one.
+fn even_sizes(n: usize, parts: usize) -> Vec<usize> {
+    (0..parts)
+        .map(|i| n / parts + usize::from(i < n % parts))
+        .collect()
+}
+
+/// Splits entries into spatially coherent groups with the given sizes.
+///
+/// The entries are sorted by the center of their bounding volumes along `dim` and cut into
+/// slabs, each slab holding whole groups; the slabs are then tiled along the next axes. On the
+/// last axis, the sorted entries are cut into the groups directly.
+fn tile<T>(
+    mut entries: Vec<RStarTreeEntry<T>>,
+    sizes: &[usize],
+    dim: usize,
+) -> Vec<Vec<RStarTreeEntry<T>>>
+where
+    T: RStarTreeObject,
+    T::B: BSPBounds,
+{
+    let center = |entry: &RStarTreeEntry<T>| {
+        entry
+            .mbr()
+            .center(dim)
+            .unwrap_or_else(|_| unreachable!("dim is below BSPBounds::DIM"))
+    };
+    entries.sort_by(|a, b| center(a).total_cmp(&center(b)));
+    let remaining_dims = <T::B as BSPBounds>::DIM - dim;
+    if remaining_dims <= 1 || sizes.len() <= 1 {
+        let mut groups = Vec::with_capacity(sizes.len());
+        for &size in sizes.iter().rev() {
+            groups.push(entries.split_off(entries.len() - size));
         }
+        groups.reverse();
+        return groups;
+    }
+    // As many slabs per axis as needed for roughly square tiles.
+    let slabs = ((sizes.len() as f64)
+        .powf(1.0 / remaining_dims as f64)
+        .ceil() as usize)
+        .clamp(1, sizes.len());
+    let mut groups = Vec::with_capacity(sizes.len());
+    let mut rest = entries.into_iter();
+    let mut start = 0;
+    for slab_groups in even_sizes(sizes.len(), slabs) {
+        let slab_sizes = &sizes[start..start + slab_groups];
+        let slab: Vec<_> = rest.by_ref().take(slab_sizes.iter().sum()).collect();
+        groups.extend(tile(slab, slab_sizes, dim + 1));
+        start += slab_groups;
+    }
+    groups
+}
+
+/// Wraps a node into an entry of its parent.
+fn node_entry<T: RStarTreeObject>(node: RStarTreeNode<T>) -> RStarTreeEntry<T> {
+    let mbr = common_compute_group_mbr(&node.entries)
+        .unwrap_or_else(|| unreachable!("bulk loaded nodes are never empty"));
+    RStarTreeEntry::Node {
+        mbr,
+        child: Box::new(node),
     }
 }
 
 impl<T: std::fmt::Debug + Clone> RStarTree<Point2D<T>> {
+    /// Finds the points inside a geographic bounding box, for trees storing longitude as `x` and
+    /// latitude as `y` (in degrees).
+    ///
+    /// A box crossing the antimeridian is searched as two windows, one on each side of it,
+    /// which do not overlap, so no point is returned twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `bbox` - The geographic bounding box.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the points inside the box.
+    pub fn range_search_geo_bbox(&self, bbox: &GeoBBox) -> Vec<&Point2D<T>> {
+        bbox.to_rectangles()
+            .iter()
+            .flat_map(|window| self.range_search_bbox(window))
+            .collect()
+    }
+
+    /// Finds the points within `radius` meters (along the surface of the Earth) of `center`,
+    /// for trees storing longitude as `x` and latitude as `y` (in degrees).
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius in meters.
+    ///
+    /// # Returns
+    ///
+    /// The points paired with their initial bearings from `center` (in degrees clockwise from
+    /// north), sorted by bearing and then by distance.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if `center` is not a valid longitude and
+    /// latitude or `radius` is negative or not finite.
+    pub fn within_radius_sorted_by_bearing(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> Result<Vec<(&Point2D<T>, f64)>, SpartError> {
+        let bbox = GeoBBox::around(center, radius)?;
+        Ok(sort_by_bearing(
+            center,
+            radius,
+            self.range_search_geo_bbox(&bbox),
+        ))
+    }
+
+    /// Finds the points inside a polygon (boundary included).
+    ///
+    /// The tree is searched with the polygon's bounding rectangle, and the points found are
+    /// then tested against the polygon itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `polygon` - The polygon to search.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the points inside the polygon.
+    pub fn points_in_polygon(&self, polygon: &Polygon) -> Vec<&Point2D<T>> {
+        let mut found = self.range_search_bbox(polygon.bbox());
+        found.retain(|p| polygon.contains(*p));
+        found
+    }
+
+    /// Finds the k nearest neighbors of a point among the points inside a polygon.
+    ///
+    /// Subtrees whose bounding rectangle does not intersect the polygon are skipped, so only
+    /// the part of the tree overlapping the polygon is searched.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The 2D point to search near (which may lie outside the polygon).
+    /// * `k` - The number of nearest neighbors to return.
+    /// * `polygon` - The region the neighbors must lie in (boundary included).
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to up to k points inside the polygon, from nearest to farthest.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance, as for `knn_search`.
+    pub fn knn_in_polygon<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        query: &Point2D<T>,
+        k: usize,
+        polygon: &Polygon,
+    ) -> Vec<&Point2D<T>> {
+        common_knn_filtered(
+            &self.root,
+            k,
+            |mbr: &Rectangle| mbr.min_distance(query).powi(2),
+            |point: &Point2D<T>| {
+                polygon
+                    .contains(point)
+                    .then(|| M::distance_sq(query, point))
+            },
+            |mbr: &Rectangle| polygon.intersects_rect(mbr),
+        )
+    }
+
     /// Performs a k‑nearest neighbor search on an R*‑tree of 2D points.
     ///
     /// # Arguments
@@ -748,7 +1557,100 @@ impl<T: std::fmt::Debug + Clone> RStarTree<Point2D<T>> {
         if k == 0 {
             return Vec::new();
         }
+        let mut results = KnnResults::new(k);
+        self.knn_search_into::<M>(query, &mut results);
+        results.into_sorted_vec()
+    }
 
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, and `SpartError::InvalidCoordinates` if a coordinate of `query` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        query: &Point2D<T>,
+        k: usize,
+    ) -> Result<Vec<&Point2D<T>>, SpartError> {
+        check_knn_query(k, self.root.entries.is_empty())?;
+        check_query_coords(&[query.x, query.y])?;
+        Ok(self.knn_search::<M>(query, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        targets: &[Point2D<T>],
+        k: usize,
+    ) -> Vec<Vec<&Point2D<T>>>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The point to search near.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// References to the nearest point of each of the (up to) `k` keys with the nearest
+    /// points, ordered from nearest to farthest.
+    pub fn knn_distinct_by<M: DistanceMetric<Point2D<T>>, K: PartialEq>(
+        &self,
+        query: &Point2D<T>,
+        k: usize,
+        key: impl Fn(&Point2D<T>) -> K,
+    ) -> Vec<&Point2D<T>> {
+        let mut results = DistinctKnnResults::new(k, |p: &&Point2D<T>| key(p));
+        if k > 0 {
+            self.knn_search_into::<M>(query, &mut results);
+        }
+        results.into_sorted_vec()
+    }
+
+    /// Visits the entries in order of their distance to `query`, offering the points to
+    /// `results` until no nearer ones can be found.
+    fn knn_search_into<'a, M: DistanceMetric<Point2D<T>>>(
+        &'a self,
+        query: &Point2D<T>,
+        results: &mut impl KnnSink<&'a Point2D<T>>,
+    ) {
+        if [query.x, query.y].iter().any(|c| c.is_nan()) {
+            return;
+        }
         let mut heap: BinaryHeap<KnnCandidate<RStarTreeEntry<Point2D<T>>>> = BinaryHeap::new();
         for entry in &self.root.entries {
             let dist_sq = entry.mbr().min_distance(query).powi(2);
@@ -758,126 +1660,302 @@ impl<T: std::fmt::Debug + Clone> RStarTree<Point2D<T>> {
             });
         }
 
-        type OrdDist = OrderedFloat<f64>;
-        #[inline]
-        #[allow(non_snake_case)]
-        fn OrdDist(x: f64) -> OrderedFloat<f64> {
-            OrderedFloat(x)
+        while let Some(KnnCandidate { dist, entry }) = heap.pop() {
+            if dist > results.bound() {
+                break;
+            }
+            match entry {
+                RStarTreeEntry::Leaf { object, .. } => {
+                    results.push(M::distance_sq(query, object), object);
+                }
+                RStarTreeEntry::Node { child, .. } => {
+                    for child_entry in &child.entries {
+                        let d_sq = child_entry.mbr().min_distance(query).powi(2);
+                        if results.accepts(d_sq) {
+                            heap.push(KnnCandidate {
+                                dist: d_sq,
+                                entry: child_entry,
+                            });
+                        }
+                    }
+                }
+            }
         }
+    }
+}
 
-        struct HeapItem<'a, P> {
-            key: OrdDist,
-            idx: usize,
-            obj: &'a P,
+impl<T: std::fmt::Debug + Clone> RStarTree<Point3D<T>> {
+    /// Performs a k‑nearest neighbor search on an R*‑tree of 3D points.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The 3D point to search near.
+    /// * `k` - The number of nearest neighbors to return.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the k nearest 3D points.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
+    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
+    /// performance.
+    pub fn knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        query: &Point3D<T>,
+        k: usize,
+    ) -> Vec<&Point3D<T>> {
+        if k == 0 {
+            return Vec::new();
         }
-        impl<P> PartialEq for HeapItem<'_, P> {
-            fn eq(&self, other: &Self) -> bool {
-                self.key == other.key && self.idx == other.idx
-            }
+        let mut results = KnnResults::new(k);
+        self.knn_search_into::<M>(query, &mut results);
+        results.into_sorted_vec()
+    }
+
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, and `SpartError::InvalidCoordinates` if a coordinate of `query` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        query: &Point3D<T>,
+        k: usize,
+    ) -> Result<Vec<&Point3D<T>>, SpartError> {
+        check_knn_query(k, self.root.entries.is_empty())?;
+        check_query_coords(&[query.x, query.y, query.z])?;
+        Ok(self.knn_search::<M>(query, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        targets: &[Point3D<T>],
+        k: usize,
+    ) -> Vec<Vec<&Point3D<T>>>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The point to search near.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// References to the nearest point of each of the (up to) `k` keys with the nearest
+    /// points, ordered from nearest to farthest.
+    pub fn knn_distinct_by<M: DistanceMetric<Point3D<T>>, K: PartialEq>(
+        &self,
+        query: &Point3D<T>,
+        k: usize,
+        key: impl Fn(&Point3D<T>) -> K,
+    ) -> Vec<&Point3D<T>> {
+        let mut results = DistinctKnnResults::new(k, |p: &&Point3D<T>| key(p));
+        if k > 0 {
+            self.knn_search_into::<M>(query, &mut results);
         }
-        impl<P> Eq for HeapItem<'_, P> {}
-        impl<P> Ord for HeapItem<'_, P> {
-            fn cmp(&self, other: &Self) -> Ordering {
-                match self.key.cmp(&other.key) {
-                    Ordering::Equal => self.idx.cmp(&other.idx),
-                    ord => ord,
-                }
-            }
+        results.into_sorted_vec()
+    }
+
+    /// Visits the entries in order of their distance to `query`, offering the points to
+    /// `results` until no nearer ones can be found.
+    fn knn_search_into<'a, M: DistanceMetric<Point3D<T>>>(
+        &'a self,
+        query: &Point3D<T>,
+        results: &mut impl KnnSink<&'a Point3D<T>>,
+    ) {
+        if [query.x, query.y, query.z].iter().any(|c| c.is_nan()) {
+            return;
         }
-        impl<P> PartialOrd for HeapItem<'_, P> {
-            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                Some(self.cmp(other))
-            }
+        let mut heap: BinaryHeap<KnnCandidate<RStarTreeEntry<Point3D<T>>>> = BinaryHeap::new();
+        for entry in &self.root.entries {
+            let dist_sq = entry.mbr().min_distance(query).powi(2);
+            heap.push(KnnCandidate {
+                dist: dist_sq,
+                entry,
+            });
         }
 
-        let mut results: BinaryHeap<HeapItem<Point2D<T>>> = BinaryHeap::new();
-        let mut counter: usize = 0;
-
         while let Some(KnnCandidate { dist, entry }) = heap.pop() {
-            if results.len() >= k {
-                if let Some(worst_result) = results.peek() {
-                    if dist > worst_result.key.0 {
-                        break;
-                    }
-                }
+            if dist > results.bound() {
+                break;
             }
-
             match entry {
                 RStarTreeEntry::Leaf { object, .. } => {
-                    let d_sq = M::distance_sq(query, object);
-                    if results.len() < k {
-                        counter += 1;
-                        results.push(HeapItem {
-                            key: OrdDist(d_sq),
-                            idx: counter,
-                            obj: object,
-                        });
-                    } else if let Some(peek) = results.peek() {
-                        if d_sq < peek.key.0 {
-                            results.pop();
-                            counter += 1;
-                            results.push(HeapItem {
-                                key: OrdDist(d_sq),
-                                idx: counter,
-                                obj: object,
-                            });
-                        }
-                    }
+                    results.push(M::distance_sq(query, object), object);
                 }
                 RStarTreeEntry::Node { child, .. } => {
                     for child_entry in &child.entries {
                         let d_sq = child_entry.mbr().min_distance(query).powi(2);
-                        if results.len() < k {
+                        if results.accepts(d_sq) {
                             heap.push(KnnCandidate {
                                 dist: d_sq,
                                 entry: child_entry,
                             });
-                        } else if let Some(peek) = results.peek() {
-                            if d_sq < peek.key.0 {
-                                heap.push(KnnCandidate {
-                                    dist: d_sq,
-                                    entry: child_entry,
-                                });
-                            }
                         }
                     }
                 }
             }
         }
-
-        let mut sorted_results = results.into_vec();
-        sorted_results.sort_by(|a, b| a.key.partial_cmp(&b.key).unwrap_or(Ordering::Equal));
-        sorted_results.into_iter().map(|r| r.obj).collect()
     }
 }
 
-impl<T: std::fmt::Debug + Clone> RStarTree<Point3D<T>> {
-    /// Performs a k‑nearest neighbor search on an R*‑tree of 3D points.
+impl<T: std::fmt::Debug + Clone, const D: usize> RStarTree<PointND<T, D>> {
+    /// Performs a k‑nearest neighbor search on an R*‑tree of D-dimensional points.
     ///
     /// # Arguments
     ///
-    /// * `query` - The 3D point to search near.
+    /// * `query` - The point to search near.
     /// * `k` - The number of nearest neighbors to return.
     ///
     /// # Returns
     ///
-    /// A vector of references to the k nearest 3D points.
+    /// A vector of references to the k nearest points.
     ///
     /// # Note
     ///
     /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
     /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
     /// performance.
-    pub fn knn_search<M: DistanceMetric<Point3D<T>>>(
+    pub fn knn_search<M: DistanceMetric<PointND<T, D>>>(
         &self,
-        query: &Point3D<T>,
+        query: &PointND<T, D>,
         k: usize,
-    ) -> Vec<&Point3D<T>> {
+    ) -> Vec<&PointND<T, D>> {
         if k == 0 {
             return Vec::new();
         }
+        let mut results = KnnResults::new(k);
+        self.knn_search_into::<M>(query, &mut results);
+        results.into_sorted_vec()
+    }
 
-        let mut heap: BinaryHeap<KnnCandidate<RStarTreeEntry<Point3D<T>>>> = BinaryHeap::new();
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, and `SpartError::InvalidCoordinates` if a coordinate of `query` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<PointND<T, D>>>(
+        &self,
+        query: &PointND<T, D>,
+        k: usize,
+    ) -> Result<Vec<&PointND<T, D>>, SpartError> {
+        check_knn_query(k, self.root.entries.is_empty())?;
+        check_query_coords(&query.coords)?;
+        Ok(self.knn_search::<M>(query, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<PointND<T, D>>>(
+        &self,
+        targets: &[PointND<T, D>],
+        k: usize,
+    ) -> Vec<Vec<&PointND<T, D>>>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The point to search near.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// References to the nearest point of each of the (up to) `k` keys with the nearest
+    /// points, ordered from nearest to farthest.
+    pub fn knn_distinct_by<M: DistanceMetric<PointND<T, D>>, K: PartialEq>(
+        &self,
+        query: &PointND<T, D>,
+        k: usize,
+        key: impl Fn(&PointND<T, D>) -> K,
+    ) -> Vec<&PointND<T, D>> {
+        let mut results = DistinctKnnResults::new(k, |p: &&PointND<T, D>| key(p));
+        if k > 0 {
+            self.knn_search_into::<M>(query, &mut results);
+        }
+        results.into_sorted_vec()
+    }
+
+    /// Visits the entries in order of their distance to `query`, offering the points to
+    /// `results` until no nearer ones can be found.
+    fn knn_search_into<'a, M: DistanceMetric<PointND<T, D>>>(
+        &'a self,
+        query: &PointND<T, D>,
+        results: &mut impl KnnSink<&'a PointND<T, D>>,
+    ) {
+        if query.coords.iter().any(|c| c.is_nan()) {
+            return;
+        }
+        let mut heap: BinaryHeap<KnnCandidate<RStarTreeEntry<PointND<T, D>>>> = BinaryHeap::new();
         for entry in &self.root.entries {
             let dist_sq = entry.mbr().min_distance(query).powi(2);
             heap.push(KnnCandidate {
@@ -886,96 +1964,27 @@ impl<T: std::fmt::Debug + Clone> RStarTree<Point3D<T>> {
             });
         }
 
-        type OrdDist = OrderedFloat<f64>;
-        #[inline]
-        #[allow(non_snake_case)]
-        fn OrdDist(x: f64) -> OrderedFloat<f64> {
-            OrderedFloat(x)
-        }
-
-        struct HeapItem<'a, P> {
-            key: OrdDist,
-            idx: usize,
-            obj: &'a P,
-        }
-        impl<P> PartialEq for HeapItem<'_, P> {
-            fn eq(&self, other: &Self) -> bool {
-                self.key == other.key && self.idx == other.idx
-            }
-        }
-        impl<P> Eq for HeapItem<'_, P> {}
-        impl<P> Ord for HeapItem<'_, P> {
-            fn cmp(&self, other: &Self) -> Ordering {
-                match self.key.cmp(&other.key) {
-                    Ordering::Equal => self.idx.cmp(&other.idx),
-                    ord => ord,
-                }
-            }
-        }
-        impl<P> PartialOrd for HeapItem<'_, P> {
-            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                Some(self.cmp(other))
-            }
-        }
-
-        let mut results: BinaryHeap<HeapItem<Point3D<T>>> = BinaryHeap::new();
-        let mut counter: usize = 0;
-
         while let Some(KnnCandidate { dist, entry }) = heap.pop() {
-            if results.len() >= k {
-                if let Some(worst_result) = results.peek() {
-                    if dist > worst_result.key.0 {
-                        break;
-                    }
-                }
+            if dist > results.bound() {
+                break;
             }
-
             match entry {
                 RStarTreeEntry::Leaf { object, .. } => {
-                    let d_sq = M::distance_sq(query, object);
-                    if results.len() < k {
-                        counter += 1;
-                        results.push(HeapItem {
-                            key: OrdDist(d_sq),
-                            idx: counter,
-                            obj: object,
-                        });
-                    } else if let Some(peek) = results.peek() {
-                        if d_sq < peek.key.0 {
-                            results.pop();
-                            counter += 1;
-                            results.push(HeapItem {
-                                key: OrdDist(d_sq),
-                                idx: counter,
-                                obj: object,
-                            });
-                        }
-                    }
+                    results.push(M::distance_sq(query, object), object);
                 }
                 RStarTreeEntry::Node { child, .. } => {
                     for child_entry in &child.entries {
                         let d_sq = child_entry.mbr().min_distance(query).powi(2);
-                        if results.len() < k {
+                        if results.accepts(d_sq) {
                             heap.push(KnnCandidate {
                                 dist: d_sq,
                                 entry: child_entry,
                             });
-                        } else if let Some(peek) = results.peek() {
-                            if d_sq < peek.key.0 {
-                                heap.push(KnnCandidate {
-                                    dist: d_sq,
-                                    entry: child_entry,
-                                });
-                            }
                         }
                     }
                 }
             }
         }
-
-        let mut sorted_results = results.into_vec();
-        sorted_results.sort_by(|a, b| a.key.partial_cmp(&b.key).unwrap_or(Ordering::Equal));
-        sorted_results.into_iter().map(|r| r.obj).collect()
     }
 }
 
@@ -1003,22 +2012,206 @@ where
     /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
     /// performance.
     pub fn range_search<M: DistanceMetric<T>>(&self, query: &T, radius: f64) -> Vec<&T> {
-        if radius < 0.0 {
-            return Vec::new();
+        let mut found = Vec::new();
+        self.range_search_into::<M>(query, radius, &mut found);
+        found
+    }
+
+    /// Performs a range search like `range_search`, appending the objects to `out` instead of
+    /// returning a new vector.
+    ///
+    /// High-frequency callers can reserve room for the expected number of results once and
+    /// reuse the same buffer for every query, clearing it in between; `out` is never cleared
+    /// here.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query object.
+    /// * `radius` - The search radius.
+    /// * `out` - The buffer that receives the objects.
+    pub fn range_search_into<'a, M: DistanceMetric<T>>(
+        &'a self,
+        query: &T,
+        radius: f64,
+        out: &mut Vec<&'a T>,
+    ) {
+        if radius.is_nan() || radius < 0.0 {
+            return;
         }
         let query_volume = T::B::from_point_radius(query, radius);
-        let candidates = self.range_search_bbox(&query_volume);
-        candidates
-            .into_iter()
-            .filter(|object| M::distance_sq(query, object) <= radius * radius)
+        let start = out.len();
+        common_search_node(&self.root, &query_volume, out);
+        // Keep only the candidates within the radius, in place.
+        let mut kept = start;
+        for i in start..out.len() {
+            if M::distance_sq(query, out[i]) <= radius * radius {
+                out.swap(kept, i);
+                kept += 1;
+            }
+        }
+        out.truncate(kept);
+    }
+
+    /// Like `range_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same objects as `range_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidRadius` if `radius` is negative or NaN, and
+    /// `SpartError::InvalidCoordinates` if a coordinate of `query` is NaN.
+    pub fn try_range_search<M: DistanceMetric<T>>(
+        &self,
+        query: &T,
+        radius: f64,
+    ) -> Result<Vec<&T>, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        check_range_query(radius)?;
+        let point = T::B::from_point_radius(query, 0.0);
+        let coords: Vec<f64> = (0..T::B::DIM)
+            .filter_map(|dim| point.center(dim).ok())
+            .collect();
+        check_query_coords(&coords)?;
+        Ok(self.range_search::<M>(query, radius))
+    }
+
+    /// Performs `range_search` for each of several centers, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `centers` - The query points.
+    /// * `radius` - The search radius around each center.
+    ///
+    /// # Returns
+    ///
+    /// The results of `range_search` for each center, in the order of `centers`.
+    #[cfg(feature = "parallel")]
+    pub fn par_range_search<M: DistanceMetric<T>>(&self, centers: &[T], radius: f64) -> Vec<Vec<&T>>
+    where
+        T: Sync,
+        T::B: Sync,
+    {
+        use rayon::prelude::*;
+        centers
+            .par_iter()
+            .map(|center| self.range_search::<M>(center, radius))
             .collect()
     }
 }
 
+impl<T: RStarTreeObject> RStarTree<T> {
+    /// Returns an iterator over the stored objects, without running a query.
+    ///
+    /// The objects are visited leaf by leaf, depth first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::rstar_tree::RStarTree;
+    ///
+    /// let mut tree = RStarTree::new(4).unwrap();
+    /// for i in 0..20 {
+    ///     tree.insert(Point2D::new(i as f64, 9.0 - i as f64, Some(i)));
+    /// }
+    /// let mut ids: Vec<_> = tree.iter().filter_map(|p| p.data).collect();
+    /// ids.sort();
+    /// assert_eq!(ids, (0..20).collect::<Vec<_>>());
+    /// assert_eq!(tree.into_iter().count(), 20);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            stack: vec![self.root.entries.iter()],
+        }
+    }
+}
+
+impl<'a, T: RStarTreeObject> IntoIterator for &'a RStarTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: RStarTreeObject> IntoIterator for RStarTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    /// Consumes the tree, yielding its objects in the same order as `iter`.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            stack: vec![self.root.entries.into_iter()],
+        }
+    }
+}
+
+/// An iterator over the objects of an [`RStarTree`], created by [`RStarTree::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a, T: RStarTreeObject> {
+    stack: Vec<std::slice::Iter<'a, RStarTreeEntry<T>>>,
+}
+
+impl<'a, T: RStarTreeObject> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(RStarTreeEntry::Leaf { object, .. }) => return Some(object),
+                Some(RStarTreeEntry::Node { child, .. }) => self.stack.push(child.entries.iter()),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// An iterator that moves the objects out of an [`RStarTree`], created by its `into_iter`.
+#[derive(Debug)]
+pub struct IntoIter<T: RStarTreeObject> {
+    stack: Vec<std::vec::IntoIter<RStarTreeEntry<T>>>,
+}
+
+impl<T: RStarTreeObject> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(RStarTreeEntry::Leaf { object, .. }) => return Some(object),
+                Some(RStarTreeEntry::Node { child, .. }) => {
+                    self.stack.push(child.entries.into_iter())
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl<T: RStarTreeObject> std::fmt::Display for RStarTree<T>
+where
+    T::B: BSPBounds,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.summary().fmt(f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::geometry::{EuclideanDistance, Rectangle};
+    use crate::geometry::{Cube, EuclideanDistance, Rectangle};
 
     #[test]
     fn test_range_search_radius_zero_2d() {
@@ -1098,6 +2291,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_range_loop)]
     fn test_delete_underflow() {
         let mut tree: RStarTree<Point2D<i32>> = RStarTree::new(4).unwrap();
         let points: Vec<_> = (0..10)
@@ -1183,6 +2377,51 @@ mod tests {
         assert_eq!(results_after_delete.len(), 1);
     }
 
+    #[test]
+    fn test_delete_removes_one_of_duplicates_in_several_nodes() {
+        let mut tree = RStarTree::new(4).unwrap();
+        for i in 0..20 {
+            tree.insert(Point3D::new(i as f64, 0.0, i as f64, None::<()>));
+        }
+        // Together with the point inserted for i = 5, the tree holds four copies.
+        let duplicate = Point3D::new(5.0, 0.0, 5.0, None);
+        for _ in 0..3 {
+            tree.insert(duplicate.clone());
+        }
+        assert!(tree.delete(&duplicate));
+        assert_eq!(tree.iter().count(), 22);
+        let found = tree.range_search::<EuclideanDistance>(&duplicate, 0.0);
+        assert_eq!(found.len(), 3);
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_delete_bulk_and_retain_count_duplicates() {
+        let build = || {
+            let mut tree = RStarTree::new(4).unwrap();
+            for i in 0..20 {
+                tree.insert(Point3D::new(i as f64, 0.0, i as f64, None::<()>));
+            }
+            for _ in 0..3 {
+                tree.insert(Point3D::new(5.0, 0.0, 5.0, None));
+            }
+            tree
+        };
+        let duplicate = Point3D::new(5.0, 0.0, 5.0, None);
+
+        let mut tree = build();
+        assert_eq!(tree.delete_bulk(std::slice::from_ref(&duplicate)), 1);
+        assert_eq!(tree.iter().count(), 22);
+        assert_eq!(tree.delete_bulk(&vec![duplicate.clone(); 5]), 3);
+        assert_eq!(tree.iter().count(), 19);
+        assert!(tree.check_invariants().is_ok());
+
+        let mut tree = build();
+        assert_eq!(tree.retain(|p| p.x != 5.0), 4);
+        assert_eq!(tree.iter().count(), 19);
+        assert!(tree.check_invariants().is_ok());
+    }
+
     #[test]
     fn test_range_search_negative_radius_empty() {
         let mut tree: RStarTree<Point2D<&str>> = RStarTree::new(4).unwrap();
@@ -1192,4 +2431,343 @@ mod tests {
         let results = tree.range_search::<EuclideanDistance>(&target, -1.0);
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_broad_phase_pairs_matches_brute_force() {
+        let mut boxes = Vec::new();
+        for i in 0..12 {
+            for j in 0..12 {
+                let size = 1.0 + ((i * 7 + j * 3) % 5) as f64 * 0.6;
+                boxes.push(Rectangle {
+                    x: i as f64 * 2.0,
+                    y: j as f64 * 2.0,
+                    width: size,
+                    height: size,
+                });
+            }
+        }
+        let mut tree: RStarTree<Rectangle> = RStarTree::new(4).unwrap();
+        for b in &boxes {
+            tree.insert(b.clone());
+        }
+
+        let key = |r: &Rectangle| ((r.x * 10.0) as i64, (r.y * 10.0) as i64);
+        let mut found: Vec<_> = tree
+            .broad_phase_pairs()
+            .into_iter()
+            .map(|(a, b)| (key(a).min(key(b)), key(a).max(key(b))))
+            .collect();
+        found.sort();
+        let before = found.len();
+        found.dedup();
+        assert_eq!(before, found.len(), "pairs must be unique");
+
+        let mut expected = Vec::new();
+        for (i, a) in boxes.iter().enumerate() {
+            for b in &boxes[i + 1..] {
+                if a.intersects(b) {
+                    expected.push((key(a).min(key(b)), key(a).max(key(b))));
+                }
+            }
+        }
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_refit_with_moved_objects() {
+        let mut tree: RStarTree<Rectangle> = RStarTree::new(4).unwrap();
+        let mut boxes = Vec::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                let r = Rectangle {
+                    x: i as f64 * 10.0,
+                    y: j as f64 * 10.0,
+                    width: 2.0,
+                    height: 2.0,
+                };
+                tree.insert(r.clone());
+                boxes.push(r);
+            }
+        }
+        let shift = |r: &mut Rectangle| {
+            r.x += 3.0 + r.y / 100.0;
+            r.y -= 1.5;
+        };
+        tree.refit_with(shift);
+        for r in boxes.iter_mut() {
+            shift(r);
+        }
+
+        let queries = [
+            Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 4.0,
+                height: 100.0,
+            },
+            Rectangle {
+                x: 32.0,
+                y: 17.0,
+                width: 21.0,
+                height: 30.0,
+            },
+        ];
+        for q in &queries {
+            let found = tree.range_search_bbox(q).len();
+            let expected = boxes.iter().filter(|b| b.intersects(q)).count();
+            assert_eq!(found, expected);
+        }
+        tree.refit();
+        let everything = Rectangle {
+            x: -100.0,
+            y: -100.0,
+            width: 400.0,
+            height: 400.0,
+        };
+        assert_eq!(tree.range_search_bbox(&everything).len(), 100);
+    }
+
+    #[test]
+    fn test_insert_keeps_all_objects_through_forced_reinsert() {
+        let mut tree: RStarTree<Point2D<usize>> = RStarTree::new(4).unwrap();
+        let mut points = Vec::new();
+        for i in 0..400 {
+            let p = Point2D::new((i / 20) as f64, (i % 20) as f64, Some(i));
+            tree.insert(p.clone());
+            points.push(p);
+        }
+        let everything = Rectangle {
+            x: -1.0,
+            y: -1.0,
+            width: 30.0,
+            height: 30.0,
+        };
+        assert_eq!(tree.range_search_bbox(&everything).len(), 400);
+
+        for p in points.iter().step_by(3) {
+            assert!(tree.delete(p));
+        }
+        let remaining = tree.range_search_bbox(&everything).len();
+        assert_eq!(remaining, 400 - points.iter().step_by(3).count());
+    }
+
+    #[test]
+    fn test_same_insertion_sequence_builds_identical_tree() {
+        let build = || {
+            let mut tree: RStarTree<Point2D<usize>> = RStarTree::new(5).unwrap();
+            // Many duplicate coordinates produce ties in subtree choice and splits.
+            for i in 0..300 {
+                tree.insert(Point2D::new((i % 7) as f64, (i % 5) as f64, Some(i)));
+            }
+            tree
+        };
+        assert_eq!(format!("{:?}", build()), format!("{:?}", build()));
+    }
+
+    #[test]
+    fn test_min_max_by_axis_match_full_scan() {
+        let mut tree: RStarTree<Rectangle> = RStarTree::new(4).unwrap();
+        assert!(tree.min_by_axis(0).unwrap().is_none());
+        let mut seed = 99u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 8) % 1000) as f64 / 10.0
+        };
+        let rects: Vec<Rectangle> = (0..200)
+            .map(|_| Rectangle {
+                x: next(),
+                y: next(),
+                width: next() / 10.0,
+                height: next() / 10.0,
+            })
+            .collect();
+        for r in &rects {
+            tree.insert(r.clone());
+        }
+
+        let min_x = rects.iter().map(|r| r.x).fold(f64::INFINITY, f64::min);
+        let max_y = rects
+            .iter()
+            .map(|r| r.y + r.height)
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(tree.min_by_axis(0).unwrap().unwrap().x, min_x);
+        let top = tree.max_by_axis(1).unwrap().unwrap();
+        assert!((top.y + top.height - max_y).abs() < 1e-9);
+        assert!(matches!(
+            tree.min_by_axis(2),
+            Err(SpartError::InvalidDimension {
+                requested: 2,
+                available: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_bulk_load_strategies_build_valid_trees() {
+        use crate::random::{RandomSource, SplitMix64};
+
+        let mut rng = SplitMix64::new(3);
+        // Skewed data: most points in a small cluster, the rest spread out.
+        let points: Vec<Point2D<usize>> = (0..2000)
+            .map(|i| {
+                let scale = if i % 10 == 0 { 1000.0 } else { 10.0 };
+                Point2D::new(rng.next_f64() * scale, rng.next_f64() * scale, Some(i))
+            })
+            .collect();
+        for strategy in [
+            BulkLoadStrategy::SortTileRecursive,
+            BulkLoadStrategy::OverlapMinimizingTopDown,
+        ] {
+            let mut tree = RStarTreeBuilder::new(8)
+                .with_strategy(strategy)
+                .bulk_load(points.clone())
+                .unwrap();
+            for (depth, node) in tree.nodes() {
+                assert!(node.entries.len() <= 8);
+                assert!(!node.entries.is_empty());
+                assert_eq!(depth + node.height(), tree.height());
+            }
+            assert_eq!(
+                tree.nodes()
+                    .map(|(_, n)| n.objects().count())
+                    .sum::<usize>(),
+                2000
+            );
+
+            let query = Rectangle {
+                x: 2.0,
+                y: 3.0,
+                width: 300.0,
+                height: 5.0,
+            };
+            let mut found: Vec<usize> = tree
+                .range_search_bbox(&query)
+                .into_iter()
+                .filter_map(|p| p.data)
+                .collect();
+            found.sort_unstable();
+            let expected: Vec<usize> = points
+                .iter()
+                .filter(|p| query.contains(p))
+                .filter_map(|p| p.data)
+                .collect();
+            assert_eq!(found, expected);
+
+            // The tree stays usable for updates.
+            assert!(tree.delete(&points[7]));
+            tree.insert(Point2D::new(5.0, 5.0, Some(2000)));
+            let nearest = tree.knn_search::<EuclideanDistance>(&Point2D::new(5.0, 5.0, None), 1);
+            assert_eq!(nearest[0].data, Some(2000));
+        }
+        assert!(
+            RStarTree::<Point2D<()>>::bulk_load(4, Vec::new())
+                .unwrap()
+                .root()
+                .entries
+                .is_empty()
+        );
+        assert!(RStarTree::<Point2D<()>>::bulk_load(1, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_invariants_hold_after_bulk_load_and_delete() {
+        let points: Vec<Point2D<i32>> = (0..100)
+            .map(|i| Point2D::new(i as f64, (i % 7) as f64, Some(i)))
+            .collect();
+        let mut tree = RStarTree::bulk_load(4, points.clone()).unwrap();
+        assert!(tree.check_invariants().is_ok());
+        for point in points.iter().step_by(2) {
+            tree.delete(point);
+        }
+        assert!(tree.check_invariants().is_ok());
+        assert_eq!(tree.stats().size, 50);
+
+        tree.root.entries.clear();
+        assert!(tree.check_invariants().is_ok());
+        assert_eq!(tree.stats().size, 0);
+    }
+
+    #[test]
+    fn test_rebuild_improves_quality_after_churn() {
+        use crate::random::{RandomSource, SplitMix64};
+        let mut rng = SplitMix64::new(11);
+        let mut tree = RStarTree::new(6).unwrap();
+        let mut points = Vec::new();
+        for i in 0..2000 {
+            let point = Point2D::new(rng.next_f64() * 100.0, rng.next_f64() * 100.0, Some(i));
+            tree.insert(point.clone());
+            points.push(point);
+        }
+        for point in points.iter().filter(|p| p.data.unwrap() % 3 != 0) {
+            assert!(tree.delete(point));
+        }
+        let churned = tree.quality_report();
+        assert_eq!(churned.node_count, tree.stats().node_count);
+
+        let rebuilt = tree.rebuild();
+        assert_eq!(rebuilt, tree.quality_report());
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.stats().size, points.len().div_ceil(3));
+        assert!(rebuilt.node_count < churned.node_count);
+        assert!(rebuilt.average_fill > churned.average_fill);
+        assert!(rebuilt.node_volume < churned.node_volume);
+
+        let mut empty: RStarTree<Point2D<()>> = RStarTree::new(4).unwrap();
+        let report = empty.rebuild();
+        assert_eq!(report.overlap_ratio(), 0.0);
+        assert_eq!(report.dead_space_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_split_only_insertion_keeps_invariants() {
+        use crate::random::{RandomSource, SplitMix64};
+        let mut tree = RStarTreeBuilder::new(5)
+            .with_forced_reinsert(false)
+            .build()
+            .unwrap();
+        assert!(!tree.forced_reinsert());
+        let mut rng = SplitMix64::new(5);
+        let points: Vec<Point2D<usize>> = (0..1500)
+            .map(|i| Point2D::new(rng.next_f64() * 50.0, rng.next_f64() * 50.0, Some(i)))
+            .collect();
+        for point in &points {
+            tree.insert(point.clone());
+        }
+        tree.check_invariants().unwrap();
+        let mut reference = RStarTree::new(5).unwrap();
+        reference.insert_bulk(points.clone());
+        assert!(tree.content_eq(&reference));
+
+        let query = Rectangle {
+            x: 10.0,
+            y: 20.0,
+            width: 15.0,
+            height: 7.5,
+        };
+        let expected = points.iter().filter(|p| query.contains(*p)).count();
+        assert_eq!(tree.range_search_bbox(&query).len(), expected);
+
+        tree.rebuild();
+        assert!(!tree.forced_reinsert());
+        assert!(RStarTree::<Point2D<()>>::new(4).unwrap().forced_reinsert());
+    }
+
+    #[test]
+    fn test_iter_and_into_iter_yield_every_object() {
+        let mut tree = RStarTree::new(4).unwrap();
+        for i in 0..150 {
+            tree.insert(Point2D::new(
+                (i * 7 % 100) as f64,
+                (i * 13 % 100) as f64,
+                Some(i),
+            ));
+        }
+        let mut ids: Vec<_> = (&tree).into_iter().filter_map(|p| p.data).collect();
+        ids.sort();
+        assert_eq!(ids, (0..150).collect::<Vec<_>>());
+        let borrowed: Vec<_> = tree.iter().cloned().collect();
+        let owned: Vec<_> = tree.into_iter().collect();
+        assert_eq!(owned, borrowed);
+    }
 }
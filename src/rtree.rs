@@ -1,6 +1,7 @@
 //! ## R‑tree Implementation
 //!
-//! This module implements an R‑tree for indexing 2D and 3D points.
+//! This module implements an R‑tree for indexing 2D and 3D points, and D-dimensional points
+//! (`PointND`, bounded by `Aabb`) for more dimensions.
 //! The implementation supports insertion, deletion, range search, and k‑nearest
 //! neighbor (kNN) search. Points stored in the R‑tree must implement the `RTreeObject` trait,
 //! which requires an implementation of a method to get a minimum bounding rectangle (for 2D)
@@ -29,28 +30,39 @@
 //! assert!(!results3d.is_empty());
 //! ```
 
-use crate::errors::SpartError;
+use crate::errors::{SpartError, check_knn_query, check_query_coords, check_range_query};
+use crate::geo::{GeoBBox, sort_by_bearing};
 use crate::geometry::{
-    BoundingVolume, BoundingVolumeFromPoint, Cube, DistanceMetric, HasMinDistance, Point2D,
-    Point3D, Rectangle,
+    BSPBounds, Bounded, BoundingVolume, BoundingVolumeFromPoint, Cube, DistanceMetric,
+    HasMinDistance, Point2D, Point3D, PointND, Polygon, Rectangle, TileScheme,
 };
+use crate::heatmap::thin_by_priority;
+use crate::knn::{DistinctKnnResults, KnnResults, KnnSink, LimitedResults};
+use crate::logging::hot_trace;
+#[cfg(feature = "parallel")]
+use crate::rtree_common::par_search_node as common_par_search_node;
 use crate::rtree_common::{
-    KnnCandidate, compute_group_mbr as common_compute_group_mbr,
-    delete_entry as common_delete_entry, search_node as common_search_node,
+    KnnCandidate, box_distance_sq as common_box_distance_sq, check_tree as common_check_tree,
+    compute_group_mbr as common_compute_group_mbr, delete_entry as common_delete_entry,
+    entry_height as common_entry_height, estimate_count as common_estimate_count,
+    extreme_by_axis as common_extreme_by_axis, knn_filtered as common_knn_filtered,
+    node_height as common_node_height, refit_node as common_refit_node,
+    search_node as common_search_node, search_node_limited as common_search_node_limited,
+    self_join as common_self_join, tree_stats as common_tree_stats,
+    walk_nodes as common_walk_nodes,
 };
-use ordered_float::OrderedFloat;
+use crate::stats::{TreeStats, TreeSummary};
+use crate::testing::same_multiset;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
 use std::collections::BinaryHeap;
-use tracing::{debug, info};
-
-// Epsilon value for zero-sizes bounding boxes/cubes.
-const EPSILON: f64 = 1e-10;
+use tracing::info;
 
 /// Trait for points stored in an R‑tree.
 ///
 /// Each object must provide its minimum bounding rectangle (or cube) via the `mbr()` method.
+/// Types implementing `geometry::Bounded` implement this trait automatically, which is the
+/// simplest way to make a type usable in every bounding-volume tree.
 #[cfg(feature = "serde")]
 pub trait RTreeObject: std::fmt::Debug + Clone {
     /// The type of the bounding volume (e.g. `Rectangle` for 2D objects or `Cube` for 3D objects).
@@ -86,6 +98,22 @@ impl<T: RTreeObject> RTreeEntry<T> {
             RTreeEntry::Node { mbr, .. } => mbr,
         }
     }
+
+    /// Returns the stored object of a leaf entry.
+    pub fn object(&self) -> Option<&T> {
+        match self {
+            RTreeEntry::Leaf { object, .. } => Some(object),
+            RTreeEntry::Node { .. } => None,
+        }
+    }
+
+    /// Returns the child node of an inner entry.
+    pub fn child(&self) -> Option<&RTreeNode<T>> {
+        match self {
+            RTreeEntry::Leaf { .. } => None,
+            RTreeEntry::Node { child, .. } => Some(child),
+        }
+    }
 }
 
 /// A node in the R‑tree.
@@ -98,6 +126,28 @@ pub struct RTreeNode<T: RTreeObject> {
     pub is_leaf: bool,
 }
 
+impl<T: RTreeObject> RTreeNode<T> {
+    /// Returns the child nodes of an inner node, in stored order (none for a leaf node).
+    pub fn children(&self) -> impl Iterator<Item = &RTreeNode<T>> {
+        self.entries.iter().filter_map(RTreeEntry::child)
+    }
+
+    /// Returns the objects stored in a leaf node, in stored order (none for an inner node).
+    pub fn objects(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().filter_map(RTreeEntry::object)
+    }
+
+    /// Returns the bounding volume covering all entries, or `None` for an empty node.
+    pub fn mbr(&self) -> Option<T::B> {
+        common_compute_group_mbr(&self.entries)
+    }
+
+    /// Returns the height of the subtree rooted at this node: 1 for a leaf node.
+    pub fn height(&self) -> usize {
+        common_node_height(self)
+    }
+}
+
 /// R‑tree data structure for indexing 2D or 3D points.
 ///
 /// The tree is initialized with a maximum number of entries per node. If a node exceeds this
@@ -127,6 +177,13 @@ impl<T: RTreeObject> crate::rtree_common::EntryAccess for RTreeEntry<T> {
         }
     }
 
+    fn as_leaf_mut(&mut self) -> Option<(&mut Self::BV, &mut Self::Obj)> {
+        match self {
+            RTreeEntry::Leaf { mbr, object } => Some((mbr, object)),
+            _ => None,
+        }
+    }
+
     fn child(&self) -> Option<&<Self as crate::rtree_common::EntryAccess>::Node> {
         match self {
             RTreeEntry::Node { child, .. } => Some(child),
@@ -199,29 +256,138 @@ impl<T: RTreeObject> RTree<T> {
         })
     }
 
+    /// Returns the root node, for custom traversals of the tree.
+    ///
+    /// Nodes and entries are read-only; use the methods of the tree to modify it.
+    pub fn root(&self) -> &RTreeNode<T> {
+        &self.root
+    }
+
+    /// Returns all nodes of the tree in pre-order, each with its depth (0 for the root).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::rtree::RTree;
+    ///
+    /// let mut tree = RTree::new(4).unwrap();
+    /// for i in 0..20 {
+    ///     tree.insert(Point2D::new(i as f64, 0.0, None::<()>));
+    /// }
+    /// let leaves = tree.nodes().filter(|(_, node)| node.is_leaf);
+    /// assert_eq!(leaves.map(|(_, node)| node.objects().count()).sum::<usize>(), 20);
+    /// assert!(tree.nodes().all(|(depth, node)| depth + node.height() == tree.height()));
+    /// ```
+    pub fn nodes(&self) -> impl Iterator<Item = (usize, &RTreeNode<T>)> {
+        common_walk_nodes(&self.root)
+    }
+
+    /// Returns the height of the tree: 1 while the root is a leaf node.
+    pub fn height(&self) -> usize {
+        common_node_height(&self.root)
+    }
+
+    /// Returns statistics about the shape of the tree.
+    ///
+    /// The fill factor is the mean number of entries per node divided by the maximum number
+    /// of entries.
+    pub fn stats(&self) -> TreeStats {
+        common_tree_stats(&self.root, self.max_entries)
+    }
+
+    /// Returns `true` if both trees hold the same objects, counting duplicates, regardless of
+    /// their node sizes or how the objects are arranged in the nodes.
+    pub fn content_eq(&self, other: &Self) -> bool
+    where
+        T: PartialEq,
+        T::B: BSPBounds,
+    {
+        let mine = common_walk_nodes(&self.root).flat_map(|(_, node)| node.objects());
+        let theirs = common_walk_nodes(&other.root).flat_map(|(_, node)| node.objects());
+        same_multiset(mine.collect(), theirs.collect(), |object| {
+            let mbr = object.mbr();
+            (0..T::B::DIM)
+                .filter_map(|dim| mbr.center(dim).ok())
+                .collect()
+        })
+    }
+
+    /// Returns a concise description of the tree: its size, depth, maximum number of entries
+    /// per node, and the bounding volume of its objects.
+    pub fn summary(&self) -> TreeSummary
+    where
+        T::B: BSPBounds,
+    {
+        let stats = self.stats();
+        TreeSummary {
+            kind: "RTree",
+            size: stats.size,
+            depth: stats.depth,
+            capacity: Some(self.max_entries),
+            bounds: self
+                .root
+                .mbr()
+                .map_or_else(Vec::new, |mbr| TreeSummary::axis_bounds(&mbr)),
+        }
+    }
+
+    /// Checks the structural invariants of the tree.
+    ///
+    /// Every node holds between the minimum and maximum number of entries (the root may hold
+    /// fewer), all leaf nodes are at the same depth, and the MBR of every node entry encloses
+    /// the entries of its child. A tree built through the public API always passes; the check
+    /// is meant for trees restored from untrusted or possibly corrupted data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvariantViolation` describing the first violation found.
+    pub fn check_invariants(&self) -> Result<(), SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        common_check_tree(&self.root, self.max_entries, self.min_entries)
+    }
+
     /// Inserts an object into the R‑tree.
     ///
     /// # Arguments
     ///
     /// * `object` - The object to insert.
     pub fn insert(&mut self, object: T) {
-        info!("Inserting object into RTree: {:?}", object);
+        hot_trace!("Inserting object into RTree", object = object);
         let entry = RTreeEntry::Leaf {
             mbr: object.mbr(),
             object,
         };
-        insert_entry_node(&mut self.root, entry);
+        self.insert_entry(entry);
+    }
+
+    /// Inserts an entry at the height it belongs to and splits the root if it overflows.
+    ///
+    /// Entries removed while condensing the tree after a deletion may be whole subtrees, which
+    /// must be reinserted into a node one level above them rather than into a leaf.
+    fn insert_entry(&mut self, entry: RTreeEntry<T>) {
+        let target_height = common_entry_height(&entry) + 1;
+        let root_height = common_node_height(&self.root);
+        insert_entry_node(
+            &mut self.root,
+            entry,
+            root_height,
+            target_height,
+            (self.max_entries, self.min_entries),
+        );
         if self.root.entries.len() > self.max_entries {
-            info!("Root has exceeded max_entries; splitting root");
+            hot_trace!("Root has exceeded max_entries; splitting root");
             self.split_root();
         }
     }
 
     /// Splits the root node into two child nodes when it exceeds the maximum number of entries.
     fn split_root(&mut self) {
-        info!("Splitting root node");
+        hot_trace!("Splitting root node");
         let old_entries = std::mem::take(&mut self.root.entries);
-        let (group1, group2) = split_entries(old_entries, self.max_entries);
+        let (group1, group2) = split_entries(old_entries, self.min_entries);
         let child1 = RTreeNode {
             entries: group1,
             is_leaf: self.root.is_leaf,
@@ -245,6 +411,47 @@ impl<T: RTreeObject> RTree<T> {
         });
     }
 
+    /// Recomputes all bounding volumes bottom-up without restructuring the tree.
+    ///
+    /// Use this after the stored objects have moved slightly (for example through interior
+    /// mutability) to make the tree consistent again; this is much cheaper than deleting and
+    /// reinserting every object. Since the structure is not changed, query performance degrades
+    /// as objects drift away from their original neighbors, so the tree should still be rebuilt
+    /// periodically (e.g., with `insert_bulk` into a fresh tree).
+    pub fn refit(&mut self) {
+        self.refit_with(|_| {});
+    }
+
+    /// Applies `update` to every stored object and then recomputes all bounding volumes
+    /// bottom-up, without restructuring the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - A function that moves or resizes an object in place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Rectangle;
+    /// use spart::rtree::RTree;
+    ///
+    /// let mut tree: RTree<Rectangle> = RTree::new(4).unwrap();
+    /// for i in 0..10 {
+    ///     tree.insert(Rectangle { x: i as f64 * 3.0, y: 0.0, width: 1.0, height: 1.0 });
+    /// }
+    /// // Move every box one unit to the right.
+    /// tree.refit_with(|r| r.x += 1.0);
+    /// let query = Rectangle { x: 0.0, y: 0.0, width: 0.5, height: 1.0 };
+    /// assert!(tree.range_search_bbox(&query).is_empty());
+    /// ```
+    pub fn refit_with<F: FnMut(&mut T)>(&mut self, mut update: F) {
+        info!("Refitting R-tree bounding volumes");
+        common_refit_node(&mut self.root, &mut |object: &mut T| {
+            update(object);
+            object.mbr()
+        });
+    }
+
     /// Performs a range search with a given query bounding volume.
     ///
     /// # Arguments
@@ -255,14 +462,200 @@ impl<T: RTreeObject> RTree<T> {
     ///
     /// A vector of references to the objects whose minimum bounding volumes intersect the query.
     pub fn range_search_bbox(&self, query: &T::B) -> Vec<&T> {
-        info!("Performing range search with query: {:?}", query);
+        hot_trace!("Performing range search", query = query);
         let mut result = Vec::new();
         common_search_node(&self.root, query, &mut result);
         result
     }
 
+    /// Performs a range search like `range_search_bbox`, stopping once `max_results` objects
+    /// are found.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The bounding volume to search against.
+    /// * `max_results` - The maximum number of objects to return.
+    ///
+    /// # Returns
+    ///
+    /// Up to `max_results` objects whose minimum bounding volumes intersect the query, and
+    /// whether more objects matched.
+    pub fn range_search_bbox_limited(
+        &self,
+        query: &T::B,
+        max_results: usize,
+    ) -> LimitedResults<&T> {
+        let mut result = Vec::new();
+        let limit = LimitedResults::<&T>::limit_for(max_results);
+        common_search_node_limited(&self.root, query, limit, &mut result);
+        LimitedResults::from_overfull(result, max_results)
+    }
+
+    /// Estimates the number of objects `range_search_bbox` would return for `query`, without
+    /// reading the objects.
+    ///
+    /// Only the inner nodes are visited: each leaf node whose MBR intersects the query counts
+    /// for its number of objects times the fraction of its MBR inside the query. The estimate
+    /// is close when the objects are spread evenly within each leaf, and is cheap enough for a
+    /// query planner to call before choosing between the index and another access path.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query bounding volume.
+    ///
+    /// # Returns
+    ///
+    /// The estimated number of objects intersecting `query`.
+    pub fn estimate_count_in_bbox(&self, query: &T::B) -> f64
+    where
+        T::B: BSPBounds,
+    {
+        common_estimate_count(&self.root, query)
+    }
+
+    /// Performs `range_search_bbox`, searching the subtrees of large nodes in parallel.
+    ///
+    /// The entries of a node are searched as parallel tasks (with Rayon) when each of its
+    /// subtrees is estimated to hold at least `min_subtree_size` objects, judging by their
+    /// height and the minimum number of entries per node. Smaller subtrees are searched
+    /// sequentially, so small trees and the lower levels of large trees avoid the overhead of
+    /// spawning tasks. The results are the same, and in the same order, as those of
+    /// `range_search_bbox`. Requires the `parallel` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query bounding volume.
+    /// * `min_subtree_size` - The estimated subtree size below which the search is sequential
+    ///   (e.g., a few thousand objects).
+    #[cfg(feature = "parallel")]
+    pub fn range_search_bbox_parallel(&self, query: &T::B, min_subtree_size: usize) -> Vec<&T>
+    where
+        T: Sync,
+        T::B: Sync,
+    {
+        hot_trace!("Performing parallel range search", query = query);
+        common_par_search_node(
+            &self.root,
+            common_node_height(&self.root),
+            query,
+            min_subtree_size,
+            self.min_entries,
+        )
+    }
+
+    /// Returns the stored object whose MBR has the smallest lower bound along `axis`.
+    ///
+    /// For points this is the point with the smallest coordinate. Subtrees whose MBR cannot
+    /// reach below the best object found so far are skipped. If several objects tie, any one
+    /// of them is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The axis to compare.
+    ///
+    /// # Returns
+    ///
+    /// The object, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is not below the dimension of the
+    /// bounding volumes.
+    pub fn min_by_axis(&self, axis: usize) -> Result<Option<&T>, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        self.extreme_by_axis(axis, false)
+    }
+
+    /// Returns the stored object whose MBR has the largest upper bound along `axis`.
+    ///
+    /// This is the counterpart of `min_by_axis` and prunes the tree the same way.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The axis to compare.
+    ///
+    /// # Returns
+    ///
+    /// The object, or `None` if the tree is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidDimension` if `axis` is not below the dimension of the
+    /// bounding volumes.
+    pub fn max_by_axis(&self, axis: usize) -> Result<Option<&T>, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        self.extreme_by_axis(axis, true)
+    }
+
+    fn extreme_by_axis(&self, axis: usize, max: bool) -> Result<Option<&T>, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        if axis >= T::B::DIM {
+            return Err(SpartError::InvalidDimension {
+                requested: axis,
+                available: T::B::DIM,
+            });
+        }
+        let mut best = None;
+        common_extreme_by_axis(&self.root, axis, max, &mut best);
+        Ok(best.map(|(_, object)| object))
+    }
+
+    /// Finds the k objects whose minimum bounding volumes are nearest to a query box.
+    ///
+    /// Boxes are compared by the smallest distance between them (zero when they intersect),
+    /// which is used both to rank the objects and to prune the subtrees, so extended objects
+    /// like building footprints are found by their nearest edge rather than by their center.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query box.
+    /// * `k` - The number of objects to return.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to up to k objects, from nearest to farthest. Objects at the
+    /// same distance (e.g., all objects intersecting the query) come in no particular order.
+    pub fn nearest_bbox(&self, query: &T::B, k: usize) -> Vec<&T>
+    where
+        T::B: BSPBounds,
+    {
+        common_knn_filtered(
+            &self.root,
+            k,
+            |mbr: &T::B| common_box_distance_sq(mbr, query),
+            |object: &T| Some(common_box_distance_sq(&object.mbr(), query)),
+            |_: &T::B| true,
+        )
+    }
+
+    /// Returns every pair of stored objects whose minimum bounding volumes overlap.
+    ///
+    /// This is the broad phase of a collision detection step: the tree is joined with itself
+    /// in a single traversal, each overlapping pair is reported exactly once (in no particular
+    /// order), and no object is paired with itself. Objects that are stored more than once are
+    /// treated as distinct objects.
+    ///
+    /// # Returns
+    ///
+    /// A vector of pairs of references to overlapping objects.
+    pub fn broad_phase_pairs(&self) -> Vec<(&T, &T)> {
+        let mut pairs = Vec::new();
+        common_self_join(&self.root, &mut pairs);
+        pairs
+    }
+
     /// Inserts a bulk of objects into the R-tree.
     ///
+    /// An empty tree is packed bottom-up from the objects in their given order, with the
+    /// entries spread evenly over the nodes of each level. A tree that already holds objects
+    /// inserts them one by one.
+    ///
     /// # Arguments
     ///
     /// * `objects` - The objects to insert.
@@ -270,6 +663,12 @@ impl<T: RTreeObject> RTree<T> {
         if objects.is_empty() {
             return;
         }
+        if !self.root.entries.is_empty() {
+            for object in objects {
+                self.insert(object);
+            }
+            return;
+        }
 
         let mut entries: Vec<RTreeEntry<T>> = objects
             .into_iter()
@@ -279,33 +678,47 @@ impl<T: RTreeObject> RTree<T> {
             })
             .collect();
 
+        let mut is_leaf = true;
         while entries.len() > self.max_entries {
-            let mut new_level_entries = Vec::new();
-            let chunks = entries.chunks(self.max_entries);
-
-            for chunk in chunks {
+            let count = entries.len();
+            let groups = count.div_ceil(self.max_entries);
+            let mut rest = entries.into_iter();
+            let mut new_level_entries = Vec::with_capacity(groups);
+            for i in 0..groups {
+                let size = count / groups + usize::from(i < count % groups);
                 let child_node = RTreeNode {
-                    entries: chunk.to_vec(),
-                    is_leaf: self.root.is_leaf,
+                    entries: rest.by_ref().take(size).collect(),
+                    is_leaf,
                 };
-                if let Some(mbr) = common_compute_group_mbr(&child_node.entries) {
-                    new_level_entries.push(RTreeEntry::Node {
-                        mbr,
-                        child: Box::new(child_node),
-                    });
-                }
+                let mbr = common_compute_group_mbr(&child_node.entries)
+                    .unwrap_or_else(|| unreachable!("bulk-loaded nodes are never empty"));
+                new_level_entries.push(RTreeEntry::Node {
+                    mbr,
+                    child: Box::new(child_node),
+                });
             }
             entries = new_level_entries;
-            self.root.is_leaf = false;
+            is_leaf = false;
         }
 
-        self.root.entries.extend(entries);
+        self.root = RTreeNode { entries, is_leaf };
     }
 }
 
-fn insert_entry_node<T: RTreeObject>(node: &mut RTreeNode<T>, entry: RTreeEntry<T>) {
-    if node.is_leaf {
-        debug!("Inserting entry into leaf node");
+/// Descends to the node of height `target_height` (a leaf for objects) and adds `entry` there.
+///
+/// At each level the child needing the least enlargement is chosen, then the one with the
+/// smallest area; remaining ties go to the lowest index so that the same insertion sequence
+/// always produces the same tree.
+fn insert_entry_node<T: RTreeObject>(
+    node: &mut RTreeNode<T>,
+    entry: RTreeEntry<T>,
+    height: usize,
+    target_height: usize,
+    (max_entries, min_entries): (usize, usize),
+) {
+    if node.is_leaf || height <= target_height {
+        hot_trace!("Inserting entry into node", height = height);
         node.entries.push(entry);
     } else {
         let mut best_index: Option<usize> = None;
@@ -328,20 +741,62 @@ fn insert_entry_node<T: RTreeObject>(node: &mut RTreeNode<T>, entry: RTreeEntry<
         if let Some(best_index) = best_index {
             if let RTreeEntry::Node { mbr, child } = &mut node.entries[best_index] {
                 *mbr = mbr.union(entry.mbr());
-                insert_entry_node(child, entry);
+                insert_entry_node(
+                    child,
+                    entry,
+                    height - 1,
+                    target_height,
+                    (max_entries, min_entries),
+                );
                 if let Some(new_mbr) = common_compute_group_mbr(&child.entries) {
                     *mbr = new_mbr;
                 }
             }
+            split_overflowing_child(node, best_index, (max_entries, min_entries));
         } else {
             node.entries.push(entry);
         }
     }
 }
 
+/// Splits the child of `node` at `index` in two if it holds more than `max_entries` entries.
+///
+/// The child keeps the first group of `split_entries` and the second group becomes a new
+/// sibling appended to `node`, which may then overflow in turn and is split by its parent
+/// (or by `RTree::split_root`).
+fn split_overflowing_child<T: RTreeObject>(
+    node: &mut RTreeNode<T>,
+    index: usize,
+    (max_entries, min_entries): (usize, usize),
+) {
+    let RTreeEntry::Node { mbr, child } = &mut node.entries[index] else {
+        return;
+    };
+    if child.entries.len() <= max_entries {
+        return;
+    }
+    hot_trace!("Splitting overflowing node", entries = child.entries.len());
+    let entries = std::mem::take(&mut child.entries);
+    let (group1, group2) = split_entries(entries, min_entries);
+    child.entries = group1;
+    let sibling = RTreeNode {
+        entries: group2,
+        is_leaf: child.is_leaf,
+    };
+    *mbr = common_compute_group_mbr(&child.entries)
+        .unwrap_or_else(|| unreachable!("split groups are never empty"));
+    let sibling_mbr = common_compute_group_mbr(&sibling.entries)
+        .unwrap_or_else(|| unreachable!("split groups are never empty"));
+    node.entries.push(RTreeEntry::Node {
+        mbr: sibling_mbr,
+        child: Box::new(sibling),
+    });
+}
+
+/// Splits the entries of an overflowing node into two groups of at least `min_entries` each.
 fn split_entries<T: RTreeObject>(
     entries: Vec<RTreeEntry<T>>,
-    _max_entries: usize,
+    min_entries: usize,
 ) -> (Vec<RTreeEntry<T>>, Vec<RTreeEntry<T>>) {
     let mut entries = entries;
     if entries.len() < 2 {
@@ -351,18 +806,27 @@ fn split_entries<T: RTreeObject>(
     let seed2 = entries.remove(0);
     let mut group1 = vec![seed1];
     let mut group2 = vec![seed2];
+    let mut remaining = entries.len();
     for entry in entries {
-        let mbr1 = common_compute_group_mbr(&group1)
-            .unwrap_or_else(|| unreachable!("non-empty group must have MBR"));
-        let mbr2 = common_compute_group_mbr(&group2)
-            .unwrap_or_else(|| unreachable!("non-empty group must have MBR"));
-        let enlargement1 = mbr1.enlargement(entry.mbr());
-        let enlargement2 = mbr2.enlargement(entry.mbr());
-        if enlargement1 < enlargement2 {
+        // A group that needs all remaining entries to reach the minimum gets them.
+        if group1.len() + remaining <= min_entries {
             group1.push(entry);
-        } else {
+        } else if group2.len() + remaining <= min_entries {
             group2.push(entry);
+        } else {
+            let mbr1 = common_compute_group_mbr(&group1)
+                .unwrap_or_else(|| unreachable!("non-empty group must have MBR"));
+            let mbr2 = common_compute_group_mbr(&group2)
+                .unwrap_or_else(|| unreachable!("non-empty group must have MBR"));
+            let enlargement1 = mbr1.enlargement(entry.mbr());
+            let enlargement2 = mbr2.enlargement(entry.mbr());
+            if enlargement1 < enlargement2 {
+                group1.push(entry);
+            } else {
+                group2.push(entry);
+            }
         }
+        remaining -= 1;
     }
     (group1, group2)
 }
@@ -379,9 +843,10 @@ where
     ///
     /// # Returns
     ///
-    /// `true` if at least one matching object was found and removed.
+    /// `true` if a matching object was found and removed. If several stored objects are equal
+    /// to `object`, only one of them is removed.
     pub fn delete(&mut self, object: &T) -> bool {
-        info!("Attempting to delete object: {:?}", object);
+        hot_trace!("Deleting object from RTree", object = object);
         let object_mbr = object.mbr();
         let mut reinsert_list = Vec::new();
         let deleted = common_delete_entry(
@@ -406,37 +871,45 @@ where
         deleted
     }
 
-    fn insert_entry(&mut self, entry: RTreeEntry<T>) {
-        insert_entry_node(&mut self.root, entry);
-        if self.root.entries.len() > self.max_entries {
-            self.split_root();
-        }
+    /// Deletes several objects from the R‑tree.
+    ///
+    /// Each object removes at most one matching stored object, as with `delete`.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects` - The objects to delete.
+    ///
+    /// # Returns
+    ///
+    /// The number of objects that were found and deleted.
+    pub fn delete_bulk(&mut self, objects: &[T]) -> usize {
+        objects.iter().filter(|object| self.delete(object)).count()
     }
-}
 
-impl<T: std::fmt::Debug + Clone> RTreeObject for Point2D<T> {
-    type B = Rectangle;
-    fn mbr(&self) -> Self::B {
-        Rectangle {
-            x: self.x,
-            y: self.y,
-            width: EPSILON,
-            height: EPSILON,
-        }
+    /// Keeps only the objects for which `keep` returns `true`, deleting the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - Called once for each stored object.
+    ///
+    /// # Returns
+    ///
+    /// The number of objects that were deleted.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) -> usize {
+        let rejected: Vec<T> = self
+            .nodes()
+            .flat_map(|(_, node)| node.objects())
+            .filter(|object| !keep(object))
+            .cloned()
+            .collect();
+        self.delete_bulk(&rejected)
     }
 }
 
-impl<T: std::fmt::Debug + Clone> RTreeObject for Point3D<T> {
-    type B = Cube;
+impl<T: Bounded> RTreeObject for T {
+    type B = <T as Bounded>::B;
     fn mbr(&self) -> Self::B {
-        Cube {
-            x: self.x,
-            y: self.y,
-            z: self.z,
-            width: EPSILON,
-            height: EPSILON,
-            depth: EPSILON,
-        }
+        Bounded::mbr(self)
     }
 }
 
@@ -490,6 +963,111 @@ impl Cube {
 }
 
 impl<T: std::fmt::Debug + Clone> RTree<Point2D<T>> {
+    /// Finds the points inside a geographic bounding box, for trees storing longitude as `x` and
+    /// latitude as `y` (in degrees).
+    ///
+    /// A box crossing the antimeridian is searched as two windows, one on each side of it,
+    /// which do not overlap, so no point is returned twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `bbox` - The geographic bounding box.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the points inside the box.
+    pub fn range_search_geo_bbox(&self, bbox: &GeoBBox) -> Vec<&Point2D<T>> {
+        bbox.to_rectangles()
+            .iter()
+            .flat_map(|window| self.range_search_bbox(window))
+            .collect()
+    }
+
+    /// Finds the points within `radius` meters (along the surface of the Earth) of `center`,
+    /// for trees storing longitude as `x` and latitude as `y` (in degrees).
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The search radius in meters.
+    ///
+    /// # Returns
+    ///
+    /// The points paired with their initial bearings from `center` (in degrees clockwise from
+    /// north), sorted by bearing and then by distance.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if `center` is not a valid longitude and
+    /// latitude or `radius` is negative or not finite.
+    pub fn within_radius_sorted_by_bearing(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> Result<Vec<(&Point2D<T>, f64)>, SpartError> {
+        let bbox = GeoBBox::around(center, radius)?;
+        Ok(sort_by_bearing(
+            center,
+            radius,
+            self.range_search_geo_bbox(&bbox),
+        ))
+    }
+
+    /// Finds the points inside a polygon (boundary included).
+    ///
+    /// The tree is searched with the polygon's bounding rectangle, and the points found are
+    /// then tested against the polygon itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `polygon` - The polygon to search.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the points inside the polygon.
+    pub fn points_in_polygon(&self, polygon: &Polygon) -> Vec<&Point2D<T>> {
+        let mut found = self.range_search_bbox(polygon.bbox());
+        found.retain(|p| polygon.contains(*p));
+        found
+    }
+
+    /// Finds the k nearest neighbors of a point among the points inside a polygon.
+    ///
+    /// Subtrees whose bounding rectangle does not intersect the polygon are skipped, so only
+    /// the part of the tree overlapping the polygon is searched.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The 2D point to search near (which may lie outside the polygon).
+    /// * `k` - The number of nearest neighbors to return.
+    /// * `polygon` - The region the neighbors must lie in (boundary included).
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to up to k points inside the polygon, from nearest to farthest.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance, as for `knn_search`.
+    pub fn knn_in_polygon<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        query: &Point2D<T>,
+        k: usize,
+        polygon: &Polygon,
+    ) -> Vec<&Point2D<T>> {
+        common_knn_filtered(
+            &self.root,
+            k,
+            |mbr: &Rectangle| mbr.min_distance(query).powi(2),
+            |point: &Point2D<T>| {
+                polygon
+                    .contains(point)
+                    .then(|| M::distance_sq(query, point))
+            },
+            |mbr: &Rectangle| polygon.intersects_rect(mbr),
+        )
+    }
+
     /// Performs a k‑nearest neighbor search on an R‑tree of 2D points.
     ///
     /// # Arguments
@@ -514,107 +1092,182 @@ impl<T: std::fmt::Debug + Clone> RTree<Point2D<T>> {
         if k == 0 {
             return Vec::new();
         }
+        let mut results = KnnResults::new(k);
+        self.knn_search_into::<M>(query, &mut results);
+        results.into_sorted_vec()
+    }
 
-        let mut heap: BinaryHeap<crate::rtree_common::KnnCandidate<RTreeEntry<Point2D<T>>>> =
-            BinaryHeap::new();
-        for entry in &self.root.entries {
-            let dist_sq = entry.mbr().min_distance(query).powi(2);
-            heap.push(KnnCandidate {
-                dist: dist_sq,
-                entry,
-            });
-        }
-
-        type OrdDist = OrderedFloat<f64>;
-        #[inline]
-        #[allow(non_snake_case)]
-        fn OrdDist(x: f64) -> OrderedFloat<f64> {
-            OrderedFloat(x)
-        }
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, and `SpartError::InvalidCoordinates` if a coordinate of `query` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        query: &Point2D<T>,
+        k: usize,
+    ) -> Result<Vec<&Point2D<T>>, SpartError> {
+        check_knn_query(k, self.root.entries.is_empty())?;
+        check_query_coords(&[query.x, query.y])?;
+        Ok(self.knn_search::<M>(query, k))
+    }
 
-        struct HeapItem<'a, P> {
-            key: OrdDist,
-            idx: usize,
-            obj: &'a P,
-        }
-        impl<P> PartialEq for HeapItem<'_, P> {
-            fn eq(&self, other: &Self) -> bool {
-                self.key == other.key && self.idx == other.idx
-            }
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        targets: &[Point2D<T>],
+        k: usize,
+    ) -> Vec<Vec<&Point2D<T>>>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The point to search near.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// References to the nearest point of each of the (up to) `k` keys with the nearest
+    /// points, ordered from nearest to farthest.
+    pub fn knn_distinct_by<M: DistanceMetric<Point2D<T>>, K: PartialEq>(
+        &self,
+        query: &Point2D<T>,
+        k: usize,
+        key: impl Fn(&Point2D<T>) -> K,
+    ) -> Vec<&Point2D<T>> {
+        let mut results = DistinctKnnResults::new(k, |p: &&Point2D<T>| key(p));
+        if k > 0 {
+            self.knn_search_into::<M>(query, &mut results);
         }
-        impl<P> Eq for HeapItem<'_, P> {}
-        impl<P> Ord for HeapItem<'_, P> {
-            fn cmp(&self, other: &Self) -> Ordering {
-                match self.key.cmp(&other.key) {
-                    Ordering::Equal => self.idx.cmp(&other.idx),
-                    ord => ord,
-                }
-            }
+        results.into_sorted_vec()
+    }
+
+    /// Visits the entries in order of their distance to `query`, offering the points to
+    /// `results` until no nearer ones can be found.
+    fn knn_search_into<'a, M: DistanceMetric<Point2D<T>>>(
+        &'a self,
+        query: &Point2D<T>,
+        results: &mut impl KnnSink<&'a Point2D<T>>,
+    ) {
+        if [query.x, query.y].iter().any(|c| c.is_nan()) {
+            return;
         }
-        impl<P> PartialOrd for HeapItem<'_, P> {
-            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                Some(self.cmp(other))
-            }
+        let mut heap: BinaryHeap<crate::rtree_common::KnnCandidate<RTreeEntry<Point2D<T>>>> =
+            BinaryHeap::new();
+        for entry in &self.root.entries {
+            let dist_sq = entry.mbr().min_distance(query).powi(2);
+            heap.push(KnnCandidate {
+                dist: dist_sq,
+                entry,
+            });
         }
 
-        let mut results: BinaryHeap<HeapItem<Point2D<T>>> = BinaryHeap::new();
-        let mut counter: usize = 0;
-
         while let Some(KnnCandidate { dist, entry }) = heap.pop() {
-            if results.len() >= k {
-                if let Some(worst_result) = results.peek() {
-                    if dist > worst_result.key.0 {
-                        break;
-                    }
-                }
+            if dist > results.bound() {
+                break;
             }
-
             match entry {
                 RTreeEntry::Leaf { object, .. } => {
-                    let d_sq = M::distance_sq(query, object);
-                    if results.len() < k {
-                        counter += 1;
-                        results.push(HeapItem {
-                            key: OrdDist(d_sq),
-                            idx: counter,
-                            obj: object,
-                        });
-                    } else if let Some(peek) = results.peek() {
-                        if d_sq < peek.key.0 {
-                            results.pop();
-                            counter += 1;
-                            results.push(HeapItem {
-                                key: OrdDist(d_sq),
-                                idx: counter,
-                                obj: object,
-                            });
-                        }
-                    }
+                    results.push(M::distance_sq(query, object), object);
                 }
                 RTreeEntry::Node { child, .. } => {
                     for child_entry in &child.entries {
                         let d_sq = child_entry.mbr().min_distance(query).powi(2);
-                        if results.len() < k {
+                        if results.accepts(d_sq) {
                             heap.push(KnnCandidate {
                                 dist: d_sq,
                                 entry: child_entry,
                             });
-                        } else if let Some(peek) = results.peek() {
-                            if d_sq < peek.key.0 {
-                                heap.push(KnnCandidate {
-                                    dist: d_sq,
-                                    entry: child_entry,
-                                });
-                            }
                         }
                     }
                 }
             }
         }
+    }
 
-        let mut sorted_results = results.into_vec();
-        sorted_results.sort_by(|a, b| a.key.partial_cmp(&b.key).unwrap_or(Ordering::Equal));
-        sorted_results.into_iter().map(|r| r.obj).collect()
+    /// Returns the points inside map tile `(x, y)` at zoom level `z` of the given tiling scheme.
+    ///
+    /// Each point inside the scheme's extent belongs to exactly one tile per zoom level (see
+    /// `TileScheme`), so adjacent tiles never return the same point.
+    ///
+    /// # Arguments
+    ///
+    /// * `scheme` - The tiling scheme, e.g. `TileScheme::web_mercator()`.
+    /// * `z` - The zoom level.
+    /// * `x` - The tile column.
+    /// * `y` - The tile row.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the points inside the tile; empty if the tile coordinates
+    /// are out of range.
+    pub fn query_tile(&self, scheme: &TileScheme, z: u8, x: u32, y: u32) -> Vec<&Point2D<T>> {
+        let bounds = match scheme.tile_search_bounds(z, x, y) {
+            Some(bounds) => bounds,
+            None => return Vec::new(),
+        };
+        let mut found = self.range_search_bbox(&bounds);
+        found.retain(|p| scheme.tile_for(z, *p) == Some((x, y)));
+        found
+    }
+
+    /// Returns up to `max_points` points inside `bbox`, chosen by priority and spread over the
+    /// box, e.g., to pick the labels or points of interest to draw on a map.
+    ///
+    /// See `Quadtree::thinned_in_bbox` for how the points are picked.
+    ///
+    /// # Arguments
+    ///
+    /// * `bbox` - The rectangle to pick points from.
+    /// * `max_points` - The largest number of points to return.
+    /// * `priority` - The importance of a point; higher values are picked first, and NaN
+    ///   values last.
+    ///
+    /// # Returns
+    ///
+    /// References to at most `max_points` points inside `bbox`, in the order they were
+    /// picked.
+    pub fn thinned_in_bbox(
+        &self,
+        bbox: &Rectangle,
+        max_points: usize,
+        priority: impl Fn(&Point2D<T>) -> f64,
+    ) -> Vec<&Point2D<T>> {
+        thin_by_priority(bbox, self.range_search_bbox(bbox), max_points, priority)
     }
 }
 
@@ -643,7 +1296,100 @@ impl<T: std::fmt::Debug + Clone> RTree<Point3D<T>> {
         if k == 0 {
             return Vec::new();
         }
+        let mut results = KnnResults::new(k);
+        self.knn_search_into::<M>(query, &mut results);
+        results.into_sorted_vec()
+    }
+
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, and `SpartError::InvalidCoordinates` if a coordinate of `query` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        query: &Point3D<T>,
+        k: usize,
+    ) -> Result<Vec<&Point3D<T>>, SpartError> {
+        check_knn_query(k, self.root.entries.is_empty())?;
+        check_query_coords(&[query.x, query.y, query.z])?;
+        Ok(self.knn_search::<M>(query, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        targets: &[Point3D<T>],
+        k: usize,
+    ) -> Vec<Vec<&Point3D<T>>>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The point to search near.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// References to the nearest point of each of the (up to) `k` keys with the nearest
+    /// points, ordered from nearest to farthest.
+    pub fn knn_distinct_by<M: DistanceMetric<Point3D<T>>, K: PartialEq>(
+        &self,
+        query: &Point3D<T>,
+        k: usize,
+        key: impl Fn(&Point3D<T>) -> K,
+    ) -> Vec<&Point3D<T>> {
+        let mut results = DistinctKnnResults::new(k, |p: &&Point3D<T>| key(p));
+        if k > 0 {
+            self.knn_search_into::<M>(query, &mut results);
+        }
+        results.into_sorted_vec()
+    }
 
+    /// Visits the entries in order of their distance to `query`, offering the points to
+    /// `results` until no nearer ones can be found.
+    fn knn_search_into<'a, M: DistanceMetric<Point3D<T>>>(
+        &'a self,
+        query: &Point3D<T>,
+        results: &mut impl KnnSink<&'a Point3D<T>>,
+    ) {
+        if [query.x, query.y, query.z].iter().any(|c| c.is_nan()) {
+            return;
+        }
         let mut heap: BinaryHeap<crate::rtree_common::KnnCandidate<RTreeEntry<Point3D<T>>>> =
             BinaryHeap::new();
         for entry in &self.root.entries {
@@ -654,132 +1400,398 @@ impl<T: std::fmt::Debug + Clone> RTree<Point3D<T>> {
             });
         }
 
-        type OrdDist = OrderedFloat<f64>;
-        #[inline]
-        #[allow(non_snake_case)]
-        fn OrdDist(x: f64) -> OrderedFloat<f64> {
-            OrderedFloat(x)
-        }
-
-        struct HeapItem<'a, P> {
-            key: OrdDist,
-            idx: usize,
-            obj: &'a P,
-        }
-        impl<P> PartialEq for HeapItem<'_, P> {
-            fn eq(&self, other: &Self) -> bool {
-                self.key == other.key && self.idx == other.idx
+        while let Some(KnnCandidate { dist, entry }) = heap.pop() {
+            if dist > results.bound() {
+                break;
             }
-        }
-        impl<P> Eq for HeapItem<'_, P> {}
-        impl<P> Ord for HeapItem<'_, P> {
-            fn cmp(&self, other: &Self) -> Ordering {
-                match self.key.cmp(&other.key) {
-                    Ordering::Equal => self.idx.cmp(&other.idx),
-                    ord => ord,
+            match entry {
+                RTreeEntry::Leaf { object, .. } => {
+                    results.push(M::distance_sq(query, object), object);
+                }
+                RTreeEntry::Node { child, .. } => {
+                    for child_entry in &child.entries {
+                        let d_sq = child_entry.mbr().min_distance(query).powi(2);
+                        if results.accepts(d_sq) {
+                            heap.push(KnnCandidate {
+                                dist: d_sq,
+                                entry: child_entry,
+                            });
+                        }
+                    }
                 }
             }
         }
-        impl<P> PartialOrd for HeapItem<'_, P> {
-            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                Some(self.cmp(other))
-            }
+    }
+}
+
+impl<T: std::fmt::Debug + Clone, const D: usize> RTree<PointND<T, D>> {
+    /// Performs a k‑nearest neighbor search on an R‑tree of D-dimensional points.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The point to search near.
+    /// * `k` - The number of nearest neighbors to return.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the k nearest points.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
+    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
+    /// performance.
+    pub fn knn_search<M: DistanceMetric<PointND<T, D>>>(
+        &self,
+        query: &PointND<T, D>,
+        k: usize,
+    ) -> Vec<&PointND<T, D>> {
+        if k == 0 {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.knn_search_into::<M>(query, &mut results);
+        results.into_sorted_vec()
+    }
+
+    /// Like `knn_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same points as `knn_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidK` if `k` is zero, `SpartError::EmptyTree` if the tree holds
+    /// no points, and `SpartError::InvalidCoordinates` if a coordinate of `query` is NaN.
+    pub fn try_knn_search<M: DistanceMetric<PointND<T, D>>>(
+        &self,
+        query: &PointND<T, D>,
+        k: usize,
+    ) -> Result<Vec<&PointND<T, D>>, SpartError> {
+        check_knn_query(k, self.root.entries.is_empty())?;
+        check_query_coords(&query.coords)?;
+        Ok(self.knn_search::<M>(query, k))
+    }
+
+    /// Performs `knn_search` for each of several targets, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// The tree is only read, so all tasks share it without locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The query points.
+    /// * `k` - The number of nearest neighbors to retrieve for each target.
+    ///
+    /// # Returns
+    ///
+    /// The results of `knn_search` for each target, in the order of `targets`.
+    #[cfg(feature = "parallel")]
+    pub fn par_knn_search<M: DistanceMetric<PointND<T, D>>>(
+        &self,
+        targets: &[PointND<T, D>],
+        k: usize,
+    ) -> Vec<Vec<&PointND<T, D>>>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+        targets
+            .par_iter()
+            .map(|target| self.knn_search::<M>(target, k))
+            .collect()
+    }
+
+    /// Finds the `k` nearest points with distinct keys, e.g., the nearest point of each of the
+    /// `k` nearest categories.
+    ///
+    /// Points whose key was already found at a smaller distance are skipped and the search
+    /// continues past them, so the results do not depend on how many points share a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The point to search near.
+    /// * `k` - The number of distinct keys to find.
+    /// * `key` - Returns the key of a point, e.g., its category or the entity it belongs to.
+    ///
+    /// # Returns
+    ///
+    /// References to the nearest point of each of the (up to) `k` keys with the nearest
+    /// points, ordered from nearest to farthest.
+    pub fn knn_distinct_by<M: DistanceMetric<PointND<T, D>>, K: PartialEq>(
+        &self,
+        query: &PointND<T, D>,
+        k: usize,
+        key: impl Fn(&PointND<T, D>) -> K,
+    ) -> Vec<&PointND<T, D>> {
+        let mut results = DistinctKnnResults::new(k, |p: &&PointND<T, D>| key(p));
+        if k > 0 {
+            self.knn_search_into::<M>(query, &mut results);
         }
+        results.into_sorted_vec()
+    }
 
-        let mut results: BinaryHeap<HeapItem<Point3D<T>>> = BinaryHeap::new();
-        let mut counter: usize = 0;
+    /// Visits the entries in order of their distance to `query`, offering the points to
+    /// `results` until no nearer ones can be found.
+    fn knn_search_into<'a, M: DistanceMetric<PointND<T, D>>>(
+        &'a self,
+        query: &PointND<T, D>,
+        results: &mut impl KnnSink<&'a PointND<T, D>>,
+    ) {
+        if query.coords.iter().any(|c| c.is_nan()) {
+            return;
+        }
+        let mut heap: BinaryHeap<crate::rtree_common::KnnCandidate<RTreeEntry<PointND<T, D>>>> =
+            BinaryHeap::new();
+        for entry in &self.root.entries {
+            let dist_sq = entry.mbr().min_distance(query).powi(2);
+            heap.push(KnnCandidate {
+                dist: dist_sq,
+                entry,
+            });
+        }
 
         while let Some(KnnCandidate { dist, entry }) = heap.pop() {
-            if results.len() >= k {
-                if let Some(worst_result) = results.peek() {
-                    if dist > worst_result.key.0 {
-                        break;
-                    }
-                }
+            if dist > results.bound() {
+                break;
             }
-
             match entry {
                 RTreeEntry::Leaf { object, .. } => {
-                    let d_sq = M::distance_sq(query, object);
-                    if results.len() < k {
-                        counter += 1;
-                        results.push(HeapItem {
-                            key: OrdDist(d_sq),
-                            idx: counter,
-                            obj: object,
-                        });
-                    } else if let Some(peek) = results.peek() {
-                        if d_sq < peek.key.0 {
-                            results.pop();
-                            counter += 1;
-                            results.push(HeapItem {
-                                key: OrdDist(d_sq),
-                                idx: counter,
-                                obj: object,
-                            });
-                        }
-                    }
+                    results.push(M::distance_sq(query, object), object);
                 }
                 RTreeEntry::Node { child, .. } => {
                     for child_entry in &child.entries {
                         let d_sq = child_entry.mbr().min_distance(query).powi(2);
-                        if results.len() < k {
+                        if results.accepts(d_sq) {
                             heap.push(KnnCandidate {
                                 dist: d_sq,
                                 entry: child_entry,
                             });
-                        } else if let Some(peek) = results.peek() {
-                            if d_sq < peek.key.0 {
-                                heap.push(KnnCandidate {
-                                    dist: d_sq,
-                                    entry: child_entry,
-                                });
-                            }
                         }
                     }
                 }
             }
         }
+    }
+}
+
+impl<T> RTree<T>
+where
+    T: RTreeObject + PartialEq + std::fmt::Debug,
+    T::B: BoundingVolumeFromPoint<T> + HasMinDistance<T> + Clone,
+{
+    /// Performs a range search on the R‑tree using a query object and radius.
+    ///
+    /// The query object is wrapped into a bounding volume using `from_point_radius`.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query object.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of references to the objects within the given radius.
+    ///
+    /// # Note
+    ///
+    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
+    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
+    /// performance.
+    pub fn range_search<M: DistanceMetric<T>>(&self, query: &T, radius: f64) -> Vec<&T> {
+        let mut found = Vec::new();
+        self.range_search_into::<M>(query, radius, &mut found);
+        found
+    }
+
+    /// Performs a range search like `range_search`, appending the objects to `out` instead of
+    /// returning a new vector.
+    ///
+    /// High-frequency callers can reserve room for the expected number of results once and
+    /// reuse the same buffer for every query, clearing it in between; `out` is never cleared
+    /// here.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query object.
+    /// * `radius` - The search radius.
+    /// * `out` - The buffer that receives the objects.
+    pub fn range_search_into<'a, M: DistanceMetric<T>>(
+        &'a self,
+        query: &T,
+        radius: f64,
+        out: &mut Vec<&'a T>,
+    ) {
+        if radius.is_nan() || radius < 0.0 {
+            return;
+        }
+        let query_volume = T::B::from_point_radius(query, radius);
+        let start = out.len();
+        common_search_node(&self.root, &query_volume, out);
+        // Keep only the candidates within the radius, in place.
+        let mut kept = start;
+        for i in start..out.len() {
+            if M::distance_sq(query, out[i]) <= radius * radius {
+                out.swap(kept, i);
+                kept += 1;
+            }
+        }
+        out.truncate(kept);
+    }
+
+    /// Like `range_search`, but reports invalid queries as errors instead of returning no
+    /// results.
+    ///
+    /// # Returns
+    ///
+    /// The same objects as `range_search`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidRadius` if `radius` is negative or NaN, and
+    /// `SpartError::InvalidCoordinates` if a coordinate of `query` is NaN.
+    pub fn try_range_search<M: DistanceMetric<T>>(
+        &self,
+        query: &T,
+        radius: f64,
+    ) -> Result<Vec<&T>, SpartError>
+    where
+        T::B: BSPBounds,
+    {
+        check_range_query(radius)?;
+        let point = T::B::from_point_radius(query, 0.0);
+        let coords: Vec<f64> = (0..T::B::DIM)
+            .filter_map(|dim| point.center(dim).ok())
+            .collect();
+        check_query_coords(&coords)?;
+        Ok(self.range_search::<M>(query, radius))
+    }
+
+    /// Performs `range_search` for each of several centers, searching them in parallel (with
+    /// Rayon). Requires the `parallel` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `centers` - The query points.
+    /// * `radius` - The search radius around each center.
+    ///
+    /// # Returns
+    ///
+    /// The results of `range_search` for each center, in the order of `centers`.
+    #[cfg(feature = "parallel")]
+    pub fn par_range_search<M: DistanceMetric<T>>(&self, centers: &[T], radius: f64) -> Vec<Vec<&T>>
+    where
+        T: Sync,
+        T::B: Sync,
+    {
+        use rayon::prelude::*;
+        centers
+            .par_iter()
+            .map(|center| self.range_search::<M>(center, radius))
+            .collect()
+    }
+}
+
+impl<T: RTreeObject> RTree<T> {
+    /// Returns an iterator over the stored objects, without running a query.
+    ///
+    /// The objects are visited leaf by leaf, depth first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use spart::geometry::Point2D;
+    /// use spart::rtree::RTree;
+    ///
+    /// let mut tree = RTree::new(4).unwrap();
+    /// for i in 0..20 {
+    ///     tree.insert(Point2D::new(i as f64, 9.0 - i as f64, Some(i)));
+    /// }
+    /// let mut ids: Vec<_> = tree.iter().filter_map(|p| p.data).collect();
+    /// ids.sort();
+    /// assert_eq!(ids, (0..20).collect::<Vec<_>>());
+    /// assert_eq!(tree.into_iter().count(), 20);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            stack: vec![self.root.entries.iter()],
+        }
+    }
+}
+
+impl<'a, T: RTreeObject> IntoIterator for &'a RTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: RTreeObject> IntoIterator for RTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    /// Consumes the tree, yielding its objects in the same order as `iter`.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            stack: vec![self.root.entries.into_iter()],
+        }
+    }
+}
+
+/// An iterator over the objects of an [`RTree`], created by [`RTree::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a, T: RTreeObject> {
+    stack: Vec<std::slice::Iter<'a, RTreeEntry<T>>>,
+}
+
+impl<'a, T: RTreeObject> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(RTreeEntry::Leaf { object, .. }) => return Some(object),
+                Some(RTreeEntry::Node { child, .. }) => self.stack.push(child.entries.iter()),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// An iterator that moves the objects out of an [`RTree`], created by its `into_iter`.
+#[derive(Debug)]
+pub struct IntoIter<T: RTreeObject> {
+    stack: Vec<std::vec::IntoIter<RTreeEntry<T>>>,
+}
 
-        let mut sorted_results = results.into_vec();
-        sorted_results.sort_by(|a, b| a.key.partial_cmp(&b.key).unwrap_or(Ordering::Equal));
-        sorted_results.into_iter().map(|r| r.obj).collect()
+impl<T: RTreeObject> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(RTreeEntry::Leaf { object, .. }) => return Some(object),
+                Some(RTreeEntry::Node { child, .. }) => self.stack.push(child.entries.into_iter()),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
     }
 }
 
-impl<T> RTree<T>
+impl<T: RTreeObject> std::fmt::Display for RTree<T>
 where
-    T: RTreeObject + PartialEq + std::fmt::Debug,
-    T::B: BoundingVolumeFromPoint<T> + HasMinDistance<T> + Clone,
+    T::B: BSPBounds,
 {
-    /// Performs a range search on the R‑tree using a query object and radius.
-    ///
-    /// The query object is wrapped into a bounding volume using `from_point_radius`.
-    ///
-    /// # Arguments
-    ///
-    /// * `query` - The query object.
-    /// * `radius` - The search radius.
-    ///
-    /// # Returns
-    ///
-    /// A vector of references to the objects within the given radius.
-    ///
-    /// # Note
-    ///
-    /// The pruning logic for the search is based on Euclidean distance. Custom distance metrics
-    /// that are not compatible with Euclidean distance may lead to incorrect results or reduced
-    /// performance.
-    pub fn range_search<M: DistanceMetric<T>>(&self, query: &T, radius: f64) -> Vec<&T> {
-        if radius < 0.0 {
-            return Vec::new();
-        }
-        let query_volume = T::B::from_point_radius(query, radius);
-        let candidates = self.range_search_bbox(&query_volume);
-        candidates
-            .into_iter()
-            .filter(|object| M::distance_sq(query, object) <= radius * radius)
-            .collect()
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.summary().fmt(f)
     }
 }
 
@@ -836,6 +1848,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_range_loop)]
     fn test_delete_underflow() {
         let mut tree: RTree<Point2D<i32>> = RTree::new(4).unwrap();
         let points: Vec<_> = (0..10)
@@ -921,6 +1934,51 @@ mod tests {
         assert_eq!(results_after_delete.len(), 1);
     }
 
+    #[test]
+    fn test_delete_removes_one_of_duplicates_in_several_nodes() {
+        let mut tree = RTree::new(4).unwrap();
+        for i in 0..20 {
+            tree.insert(Point3D::new(i as f64, 0.0, i as f64, None::<()>));
+        }
+        // Together with the point inserted for i = 5, the tree holds four copies.
+        let duplicate = Point3D::new(5.0, 0.0, 5.0, None);
+        for _ in 0..3 {
+            tree.insert(duplicate.clone());
+        }
+        assert!(tree.delete(&duplicate));
+        assert_eq!(tree.iter().count(), 22);
+        let found = tree.range_search::<EuclideanDistance>(&duplicate, 0.0);
+        assert_eq!(found.len(), 3);
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_delete_bulk_and_retain_count_duplicates() {
+        let build = || {
+            let mut tree = RTree::new(4).unwrap();
+            for i in 0..20 {
+                tree.insert(Point3D::new(i as f64, 0.0, i as f64, None::<()>));
+            }
+            for _ in 0..3 {
+                tree.insert(Point3D::new(5.0, 0.0, 5.0, None));
+            }
+            tree
+        };
+        let duplicate = Point3D::new(5.0, 0.0, 5.0, None);
+
+        let mut tree = build();
+        assert_eq!(tree.delete_bulk(std::slice::from_ref(&duplicate)), 1);
+        assert_eq!(tree.iter().count(), 22);
+        assert_eq!(tree.delete_bulk(&vec![duplicate.clone(); 5]), 3);
+        assert_eq!(tree.iter().count(), 19);
+        assert!(tree.check_invariants().is_ok());
+
+        let mut tree = build();
+        assert_eq!(tree.retain(|p| p.x != 5.0), 4);
+        assert_eq!(tree.iter().count(), 19);
+        assert!(tree.check_invariants().is_ok());
+    }
+
     #[test]
     fn test_range_search_negative_radius_empty() {
         let mut tree: RTree<Point2D<&str>> = RTree::new(4).unwrap();
@@ -930,4 +1988,747 @@ mod tests {
         let results = tree.range_search::<EuclideanDistance>(&target, -1.0);
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_query_tile_web_mercator() {
+        let scheme = TileScheme::web_mercator();
+        let mut tree: RTree<Point2D<&str>> = RTree::new(4).unwrap();
+        tree.insert(Point2D::new(-1000.0, 1000.0, Some("NW")));
+        tree.insert(Point2D::new(1000.0, -1000.0, Some("SE")));
+        tree.insert(Point2D::new(0.0, 0.0, Some("origin")));
+
+        let nw = tree.query_tile(&scheme, 1, 0, 0);
+        assert_eq!(nw.len(), 1);
+        assert_eq!(nw[0].data, Some("NW"));
+
+        let ne = tree.query_tile(&scheme, 1, 1, 0);
+        assert_eq!(ne.len(), 1);
+        assert_eq!(ne[0].data, Some("origin"));
+
+        let se = tree.query_tile(&scheme, 1, 1, 1);
+        assert_eq!(se.len(), 1);
+        assert_eq!(se[0].data, Some("SE"));
+
+        assert_eq!(tree.query_tile(&scheme, 0, 0, 0).len(), 3);
+        assert!(tree.query_tile(&scheme, 1, 0, 2).is_empty());
+    }
+
+    #[test]
+    fn test_broad_phase_pairs_matches_brute_force() {
+        let mut boxes = Vec::new();
+        for i in 0..12 {
+            for j in 0..12 {
+                let size = 1.0 + ((i * 7 + j * 3) % 5) as f64 * 0.6;
+                boxes.push(Rectangle {
+                    x: i as f64 * 2.0,
+                    y: j as f64 * 2.0,
+                    width: size,
+                    height: size,
+                });
+            }
+        }
+        let mut tree: RTree<Rectangle> = RTree::new(4).unwrap();
+        for b in &boxes {
+            tree.insert(b.clone());
+        }
+
+        let key = |r: &Rectangle| ((r.x * 10.0) as i64, (r.y * 10.0) as i64);
+        let mut found: Vec<_> = tree
+            .broad_phase_pairs()
+            .into_iter()
+            .map(|(a, b)| (key(a).min(key(b)), key(a).max(key(b))))
+            .collect();
+        found.sort();
+        let before = found.len();
+        found.dedup();
+        assert_eq!(before, found.len(), "pairs must be unique");
+
+        let mut expected = Vec::new();
+        for (i, a) in boxes.iter().enumerate() {
+            for b in &boxes[i + 1..] {
+                if a.intersects(b) {
+                    expected.push((key(a).min(key(b)), key(a).max(key(b))));
+                }
+            }
+        }
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_refit_with_moved_objects() {
+        let mut tree: RTree<Rectangle> = RTree::new(4).unwrap();
+        let mut boxes = Vec::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                let r = Rectangle {
+                    x: i as f64 * 10.0,
+                    y: j as f64 * 10.0,
+                    width: 2.0,
+                    height: 2.0,
+                };
+                tree.insert(r.clone());
+                boxes.push(r);
+            }
+        }
+        let shift = |r: &mut Rectangle| {
+            r.x += 3.0 + r.y / 100.0;
+            r.y -= 1.5;
+        };
+        tree.refit_with(shift);
+        for r in boxes.iter_mut() {
+            shift(r);
+        }
+
+        let queries = [
+            Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 4.0,
+                height: 100.0,
+            },
+            Rectangle {
+                x: 32.0,
+                y: 17.0,
+                width: 21.0,
+                height: 30.0,
+            },
+        ];
+        for q in &queries {
+            let found = tree.range_search_bbox(q).len();
+            let expected = boxes.iter().filter(|b| b.intersects(q)).count();
+            assert_eq!(found, expected);
+        }
+        tree.refit();
+        let everything = Rectangle {
+            x: -100.0,
+            y: -100.0,
+            width: 400.0,
+            height: 400.0,
+        };
+        assert_eq!(tree.range_search_bbox(&everything).len(), 100);
+    }
+
+    #[test]
+    fn test_delete_reinserts_subtrees_at_their_level() {
+        let mut tree: RTree<Point2D<usize>> = RTree::new(4).unwrap();
+        let mut points = Vec::new();
+        for i in 0..400 {
+            let p = Point2D::new((i / 20) as f64, (i % 20) as f64, Some(i));
+            tree.insert(p.clone());
+            points.push(p);
+        }
+        let everything = Rectangle {
+            x: -1.0,
+            y: -1.0,
+            width: 30.0,
+            height: 30.0,
+        };
+        for p in points.iter().step_by(2) {
+            assert!(tree.delete(p));
+        }
+        let found = tree.range_search_bbox(&everything).len();
+        assert_eq!(found, 200);
+        for p in points.iter().skip(1).step_by(2) {
+            assert!(tree.delete(p));
+        }
+        assert!(tree.range_search_bbox(&everything).is_empty());
+    }
+
+    #[test]
+    fn test_same_insertion_sequence_builds_identical_tree() {
+        let build = || {
+            let mut tree: RTree<Point2D<usize>> = RTree::new(5).unwrap();
+            for i in 0..300 {
+                tree.insert(Point2D::new((i % 7) as f64, (i % 5) as f64, Some(i)));
+            }
+            tree
+        };
+        assert_eq!(format!("{:?}", build()), format!("{:?}", build()));
+    }
+
+    #[test]
+    fn test_min_max_by_axis_match_full_scan() {
+        let mut tree: RTree<Rectangle> = RTree::new(4).unwrap();
+        assert!(tree.min_by_axis(0).unwrap().is_none());
+        let mut seed = 99u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 8) % 1000) as f64 / 10.0
+        };
+        let rects: Vec<Rectangle> = (0..200)
+            .map(|_| Rectangle {
+                x: next(),
+                y: next(),
+                width: next() / 10.0,
+                height: next() / 10.0,
+            })
+            .collect();
+        for r in &rects {
+            tree.insert(r.clone());
+        }
+
+        let min_x = rects.iter().map(|r| r.x).fold(f64::INFINITY, f64::min);
+        let max_y = rects
+            .iter()
+            .map(|r| r.y + r.height)
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(tree.min_by_axis(0).unwrap().unwrap().x, min_x);
+        let top = tree.max_by_axis(1).unwrap().unwrap();
+        assert!((top.y + top.height - max_y).abs() < 1e-9);
+        assert!(matches!(
+            tree.min_by_axis(2),
+            Err(SpartError::InvalidDimension {
+                requested: 2,
+                available: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_node_walk_exposes_structure() {
+        let mut tree = RTree::new(4).unwrap();
+        for i in 0..100 {
+            tree.insert(Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)));
+        }
+        let nodes: Vec<_> = tree.nodes().collect();
+        assert_eq!(nodes[0].0, 0);
+        assert!(std::ptr::eq(nodes[0].1, tree.root()));
+        let mut found = Vec::new();
+        for (depth, node) in &nodes {
+            assert_eq!(depth + node.height(), tree.height());
+            let mbr = node.mbr().unwrap();
+            // Every entry lies within the node's bounding volume.
+            for entry in &node.entries {
+                assert!(mbr.contains_rect(entry.mbr()));
+            }
+            assert_eq!(node.children().count() > 0, !node.is_leaf);
+            found.extend(node.objects().filter_map(|p| p.data));
+        }
+        found.sort_unstable();
+        assert_eq!(found, (0..100).collect::<Vec<_>>());
+        assert!(RTree::<Point2D<()>>::new(4).unwrap().root().mbr().is_none());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_bbox_search_matches_sequential() {
+        let mut tree = RTree::new(4).unwrap();
+        for i in 0..3000 {
+            tree.insert(Point2D::new((i % 60) as f64, (i / 60) as f64, Some(i)));
+        }
+        let query = Rectangle {
+            x: 10.0,
+            y: 5.0,
+            width: 30.5,
+            height: 40.0,
+        };
+        let expected: Vec<Option<usize>> = tree
+            .range_search_bbox(&query)
+            .into_iter()
+            .map(|p| p.data)
+            .collect();
+        assert_eq!(expected.len(), 31 * 41);
+        for min_subtree_size in [0, 1, 100, usize::MAX] {
+            let found: Vec<Option<usize>> = tree
+                .range_search_bbox_parallel(&query, min_subtree_size)
+                .into_iter()
+                .map(|p| p.data)
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_estimate_count_in_bbox_tracks_actual_count() {
+        use crate::datasets::{Distribution, points_2d};
+        use crate::random::SplitMix64;
+
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let points = points_2d(
+            &mut SplitMix64::new(5),
+            &boundary,
+            5000,
+            &Distribution::Uniform,
+        );
+        let mut tree = RTree::new(8).unwrap();
+        for point in points {
+            tree.insert(point);
+        }
+        let queries = [
+            (10.0, 10.0, 50.0, 30.0),
+            (0.0, 0.0, 100.0, 100.0),
+            (40.0, 5.0, 20.0, 90.0),
+        ];
+        for (x, y, width, height) in queries {
+            let query = Rectangle {
+                x,
+                y,
+                width,
+                height,
+            };
+            let actual = tree.range_search_bbox(&query).len() as f64;
+            let estimate = tree.estimate_count_in_bbox(&query);
+            assert!(
+                (estimate - actual).abs() <= 0.1 * actual,
+                "{estimate} vs {actual}"
+            );
+        }
+        let outside = Rectangle {
+            x: 200.0,
+            y: 200.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        assert_eq!(tree.estimate_count_in_bbox(&outside), 0.0);
+
+        // A single leaf is counted exactly.
+        let mut small = RTree::new(8).unwrap();
+        small.insert(Point2D::new(1.0, 1.0, Some(0)));
+        small.insert(Point2D::new(9.0, 9.0, Some(1)));
+        let half = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 5.0,
+            height: 5.0,
+        };
+        assert_eq!(small.estimate_count_in_bbox(&half), 1.0);
+    }
+
+    #[test]
+    fn test_insert_splits_nodes_below_the_root() {
+        let mut tree = RTree::new(4).unwrap();
+        for i in 0..500 {
+            tree.insert(Point2D::new((i % 25) as f64, (i / 25) as f64, Some(i)));
+        }
+        // Three or more levels mean that nodes other than the root have been split.
+        assert!(tree.height() >= 3);
+        for (depth, node) in tree.nodes() {
+            assert!(node.entries.len() <= 4);
+            if depth > 0 {
+                assert!(node.entries.len() >= 2);
+            }
+        }
+        assert_eq!(
+            tree.range_search_bbox(&tree.root().mbr().unwrap()).len(),
+            500
+        );
+    }
+
+    #[test]
+    fn test_split_entries_fills_both_groups_to_min_entries() {
+        // The first two entries seed the groups; every other entry is closest to the first
+        // seed, so a split by enlargement alone would leave the second group with one entry.
+        let entries: Vec<RTreeEntry<Point2D<i32>>> = [0.0, 100.0, 1.0, 2.0, 3.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let point = Point2D::new(x, 0.0, Some(i as i32));
+                RTreeEntry::Leaf {
+                    mbr: RTreeObject::mbr(&point),
+                    object: point,
+                }
+            })
+            .collect();
+        let (group1, group2) = split_entries(entries, 2);
+        assert_eq!(group1.len(), 3);
+        assert_eq!(group2.len(), 2);
+        let mut ids: Vec<i32> = group1
+            .iter()
+            .chain(&group2)
+            .filter_map(|e| e.object().and_then(|p| p.data))
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_stats_and_invariants() {
+        let mut tree = RTree::new(4).unwrap();
+        for i in 0..100 {
+            tree.insert(Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)));
+        }
+        for i in (0..100).step_by(3) {
+            tree.delete(&Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)));
+        }
+        assert!(tree.check_invariants().is_ok());
+        let stats = tree.stats();
+        assert_eq!(stats.size, 66);
+        assert_eq!(stats.depth, tree.height());
+        assert_eq!(stats.node_count, tree.nodes().count());
+        let fill = stats.fill_factor.unwrap();
+        assert!((0.4..=1.0).contains(&fill));
+
+        // Shrink the MBR of a node entry so that it no longer encloses its child.
+        if let Some(RTreeEntry::Node { mbr, .. }) = tree.root.entries.first_mut() {
+            mbr.width = 0.0;
+            mbr.height = 0.0;
+        }
+        assert!(matches!(
+            tree.check_invariants(),
+            Err(SpartError::InvariantViolation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_insert_bulk_keeps_tree_valid() {
+        let points: Vec<_> = (0..100)
+            .map(|i| Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)))
+            .collect();
+        let everything = Rectangle {
+            x: -1.0,
+            y: -1.0,
+            width: 12.0,
+            height: 12.0,
+        };
+
+        let mut tree = RTree::new(4).unwrap();
+        tree.insert_bulk(points[..41].to_vec());
+        assert!(tree.check_invariants().is_ok());
+        tree.insert_bulk(points[41..].to_vec());
+        assert!(tree.check_invariants().is_ok());
+        assert_eq!(tree.stats().size, 100);
+        assert_eq!(tree.range_search_bbox(&everything).len(), 100);
+    }
+
+    #[test]
+    fn test_points_in_polygon_matches_scan() {
+        // A triangle with its hypotenuse passing through grid points.
+        let polygon = Polygon::new(vec![[0.0, 0.0], [10.0, 0.0], [0.0, 10.0]]).unwrap();
+        let points: Vec<Point2D<i32>> = (0..121)
+            .map(|i| Point2D::new((i % 11) as f64, (i / 11) as f64, Some(i)))
+            .collect();
+        let mut rtree = RTree::new(4).unwrap();
+        let mut rstar = crate::rstar_tree::RStarTree::new(4).unwrap();
+        let mut quadtree = crate::quadtree::Quadtree::new(&polygon.bbox().clone(), 4).unwrap();
+        for point in &points {
+            rtree.insert(point.clone());
+            rstar.insert(point.clone());
+            quadtree.insert(point.clone());
+        }
+
+        let expected: Vec<i32> = (0..121).filter(|i| i % 11 + i / 11 <= 10).collect();
+        let ids = |mut ids: Vec<i32>| {
+            ids.sort();
+            ids
+        };
+        let data = |p: &Point2D<i32>| p.data.unwrap();
+        assert_eq!(
+            ids(rtree
+                .points_in_polygon(&polygon)
+                .into_iter()
+                .map(data)
+                .collect()),
+            expected
+        );
+        assert_eq!(
+            ids(rstar
+                .points_in_polygon(&polygon)
+                .into_iter()
+                .map(data)
+                .collect()),
+            expected
+        );
+        assert_eq!(
+            ids(quadtree
+                .points_in_polygon(&polygon)
+                .iter()
+                .map(data)
+                .collect()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_knn_in_polygon_matches_filtered_scan() {
+        let polygon = Polygon::new(vec![
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 3.0],
+            [3.0, 3.0],
+            [3.0, 7.0],
+            [10.0, 7.0],
+            [10.0, 10.0],
+            [0.0, 10.0],
+        ])
+        .unwrap();
+        let points: Vec<Point2D<i32>> = (0..100)
+            .map(|i| Point2D::new((i % 10) as f64 + 0.5, (i / 10) as f64 + 0.5, Some(i)))
+            .collect();
+        let mut rtree = RTree::new(4).unwrap();
+        let mut rstar = crate::rstar_tree::RStarTree::new(4).unwrap();
+        for point in &points {
+            rtree.insert(point.clone());
+            rstar.insert(point.clone());
+        }
+
+        let target = Point2D::new(6.0, 5.0, None);
+        let mut expected: Vec<f64> = points
+            .iter()
+            .filter(|p| polygon.contains(*p))
+            .map(|p| p.distance_sq(&target))
+            .collect();
+        expected.sort_by(f64::total_cmp);
+        expected.truncate(5);
+        let distances = |found: Vec<&Point2D<i32>>| {
+            assert!(found.iter().all(|p| polygon.contains(*p)));
+            found
+                .iter()
+                .map(|p| p.distance_sq(&target))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            distances(rtree.knn_in_polygon::<EuclideanDistance>(&target, 5, &polygon)),
+            expected
+        );
+        assert_eq!(
+            distances(rstar.knn_in_polygon::<EuclideanDistance>(&target, 5, &polygon)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_try_searches_report_invalid_queries() {
+        let mut tree: RTree<Point2D<i32>> = RTree::new(4).unwrap();
+        let query = Point2D::new(0.0, 0.0, None);
+        assert!(matches!(
+            tree.try_knn_search::<EuclideanDistance>(&query, 2),
+            Err(SpartError::EmptyTree)
+        ));
+        for i in 0..10 {
+            tree.insert(Point2D::new(i as f64, 0.0, Some(i)));
+        }
+        assert!(matches!(
+            tree.try_knn_search::<EuclideanDistance>(&query, 0),
+            Err(SpartError::InvalidK { k: 0 })
+        ));
+        assert!(matches!(
+            tree.try_range_search::<EuclideanDistance>(&query, f64::NAN),
+            Err(SpartError::InvalidRadius { .. })
+        ));
+        assert_eq!(
+            tree.try_knn_search::<EuclideanDistance>(&query, 2)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            tree.try_range_search::<EuclideanDistance>(&query, 2.5)
+                .unwrap()
+                .len(),
+            3
+        );
+
+        let nan = Point2D::new(0.0, f64::NAN, None);
+        assert!(tree.knn_search::<EuclideanDistance>(&nan, 2).is_empty());
+        assert!(tree.range_search::<EuclideanDistance>(&nan, 2.0).is_empty());
+        assert!(matches!(
+            tree.try_knn_search::<EuclideanDistance>(&nan, 2),
+            Err(SpartError::InvalidCoordinates { .. })
+        ));
+        assert!(matches!(
+            tree.try_range_search::<EuclideanDistance>(&nan, 2.0),
+            Err(SpartError::InvalidCoordinates { .. })
+        ));
+    }
+
+    #[test]
+    fn test_range_search_into_appends_to_buffer() {
+        let mut tree: RTree<Point2D<i32>> = RTree::new(4).unwrap();
+        for i in 0..100 {
+            tree.insert(Point2D::new((i % 10) as f64, (i / 10) as f64, Some(i)));
+        }
+        let mut buffer = Vec::with_capacity(64);
+        for i in 0..10 {
+            let center = Point2D::new(i as f64, 4.5, None);
+            buffer.clear();
+            tree.range_search_into::<EuclideanDistance>(&center, 2.0, &mut buffer);
+            let mut expected = tree.range_search::<EuclideanDistance>(&center, 2.0);
+            expected.sort_by_key(|p| p.data);
+            buffer.sort_by_key(|p| p.data);
+            assert_eq!(buffer, expected);
+        }
+        let kept = buffer.len();
+        tree.range_search_into::<EuclideanDistance>(
+            &Point2D::new(0.0, 0.0, None),
+            0.0,
+            &mut buffer,
+        );
+        assert_eq!(buffer.len(), kept + 1);
+        assert_eq!(buffer[kept].data, Some(0));
+    }
+
+    #[test]
+    fn test_four_dimensional_points_match_brute_force() {
+        let points: Vec<PointND<usize, 4>> = (0..300)
+            .map(|i| {
+                let coords = [
+                    (i * 37 % 101) as f64,
+                    (i * 53 % 97) as f64,
+                    (i * 11 % 89) as f64,
+                    (i % 7) as f64 * 10.0,
+                ];
+                PointND::new(coords, Some(i))
+            })
+            .collect();
+        let mut tree: RTree<PointND<usize, 4>> = RTree::new(6).unwrap();
+        for p in &points {
+            tree.insert(p.clone());
+        }
+        assert!(tree.check_invariants().is_ok());
+
+        let query = PointND::new([50.0, 50.0, 40.0, 30.0], None);
+        let mut expected: Vec<_> = points
+            .iter()
+            .map(|p| (query.distance_sq(p), p.data))
+            .collect();
+        expected.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let found = tree.knn_search::<EuclideanDistance>(&query, 10);
+        let found_dists: Vec<f64> = found.iter().map(|p| query.distance_sq(p)).collect();
+        let expected_dists: Vec<f64> = expected[..10].iter().map(|e| e.0).collect();
+        assert_eq!(found_dists, expected_dists);
+
+        let mut in_range: Vec<_> = tree
+            .range_search::<EuclideanDistance>(&query, 25.0)
+            .iter()
+            .map(|p| p.data)
+            .collect();
+        in_range.sort();
+        let mut expected_range: Vec<_> = expected
+            .iter()
+            .filter(|e| e.0 <= 625.0)
+            .map(|e| e.1)
+            .collect();
+        expected_range.sort();
+        assert_eq!(in_range, expected_range);
+
+        assert!(tree.delete(&points[0]));
+        assert!(!tree.delete(&points[0]));
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_nearest_bbox_ranks_by_box_distance() {
+        use crate::random::{RandomSource, SplitMix64};
+        let mut rng = SplitMix64::new(21);
+        let mut tree = RTree::new(6).unwrap();
+        let mut footprints = Vec::new();
+        for _ in 0..400 {
+            let footprint = Rectangle {
+                x: rng.next_f64() * 1000.0,
+                y: rng.next_f64() * 1000.0,
+                width: 1.0 + rng.next_f64() * 40.0,
+                height: 1.0 + rng.next_f64() * 40.0,
+            };
+            tree.insert(footprint.clone());
+            footprints.push(footprint);
+        }
+        let parcel = Rectangle {
+            x: 480.0,
+            y: 510.0,
+            width: 30.0,
+            height: 12.0,
+        };
+        let found: Vec<f64> = tree
+            .nearest_bbox(&parcel, 10)
+            .into_iter()
+            .map(|f| common_box_distance_sq(f, &parcel))
+            .collect();
+        let mut expected: Vec<f64> = footprints
+            .iter()
+            .map(|f| common_box_distance_sq(f, &parcel))
+            .collect();
+        expected.sort_by(f64::total_cmp);
+        assert_eq!(found, expected[..10]);
+
+        let touching = Rectangle {
+            x: 10.0,
+            y: 0.0,
+            width: 5.0,
+            height: 5.0,
+        };
+        let apart = Rectangle {
+            x: 13.0,
+            y: 9.0,
+            width: 1.0,
+            height: 1.0,
+        };
+        assert_eq!(common_box_distance_sq(&parcel_at_origin(), &touching), 0.0);
+        assert_eq!(
+            common_box_distance_sq(&parcel_at_origin(), &apart),
+            9.0 + 16.0
+        );
+    }
+
+    fn parcel_at_origin() -> Rectangle {
+        Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 5.0,
+        }
+    }
+
+    #[test]
+    fn test_range_search_bbox_limited_reports_truncation() {
+        let mut tree = RTree::new(4).unwrap();
+        for i in 0..300 {
+            tree.insert(Point2D::new((i % 30) as f64, (i / 30) as f64, Some(i)));
+        }
+        let query = Rectangle {
+            x: 5.0,
+            y: 2.0,
+            width: 10.0,
+            height: 4.0,
+        };
+        let all = tree.range_search_bbox(&query);
+        assert_eq!(all.len(), 55);
+        let limited = tree.range_search_bbox_limited(&query, 20);
+        assert_eq!((limited.results.len(), limited.truncated), (20, true));
+        assert!(limited.results.iter().all(|p| all.contains(p)));
+        let limited = tree.range_search_bbox_limited(&query, 55);
+        assert_eq!((limited.results.len(), limited.truncated), (55, false));
+    }
+
+    #[test]
+    fn test_retain_and_delete_bulk() {
+        let mut tree = RTree::new(4).unwrap();
+        let points: Vec<_> = (0..60)
+            .map(|i| Point2D::new((i % 8) as f64, (i / 8) as f64, Some(i)))
+            .collect();
+        tree.insert_bulk(points.clone());
+        assert_eq!(tree.delete_bulk(&points[50..]), 10);
+        assert_eq!(tree.retain(|p| p.data.is_some_and(|i| i < 5)), 45);
+        assert!(tree.check_invariants().is_ok());
+        let mut left: Vec<_> = tree
+            .nodes()
+            .flat_map(|(_, node)| node.objects())
+            .filter_map(|p| p.data)
+            .collect();
+        left.sort();
+        assert_eq!(left, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_iter_and_into_iter_yield_every_object() {
+        let mut tree = RTree::new(4).unwrap();
+        for i in 0..150 {
+            tree.insert(Point2D::new(
+                (i * 7 % 100) as f64,
+                (i * 13 % 100) as f64,
+                Some(i),
+            ));
+        }
+        let mut ids: Vec<_> = (&tree).into_iter().filter_map(|p| p.data).collect();
+        ids.sort();
+        assert_eq!(ids, (0..150).collect::<Vec<_>>());
+        let borrowed: Vec<_> = tree.iter().cloned().collect();
+        let owned: Vec<_> = tree.into_iter().collect();
+        assert_eq!(owned, borrowed);
+    }
 }
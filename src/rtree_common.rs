@@ -1,20 +1,163 @@
-use crate::geometry::BoundingVolume;
+//! ## Building Blocks for R-tree Variants
+//!
+//! This module exposes the algorithms shared by `RTree` and `RStarTree`, so that other trees
+//! in the R-tree family (e.g., a time-parameterized TPR-tree) can reuse them instead of
+//! reimplementing them. A tree only has to describe its layout by implementing `EntryAccess`
+//! for its entries and `NodeAccess` for its nodes; the functions here then provide range
+//! search (`search_node`), filtered best-first kNN search (`knn_filtered`), deletion with
+//! reinsertion of underfilled nodes (`delete_entry`), MBR refitting (`refit_node`), and
+//! invariant checks (`check_tree`). Insertion and node splitting are left to the tree, as
+//! they are what tells the variants apart.
+//!
+//! The functions never allocate nodes, and they treat the bounding volumes (`EntryAccess::BV`)
+//! only through the `BoundingVolume` trait (and `BSPBounds` where noted), so any box type
+//! works, including `Aabb` for more dimensions.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::Rectangle;
+//! use spart::rtree_common::{EntryAccess, NodeAccess, check_tree, delete_entry, search_node};
+//!
+//! #[derive(Debug, Clone)]
+//! enum Entry {
+//!     Leaf { mbr: Rectangle, id: u32 },
+//!     Node { mbr: Rectangle, child: Box<Node> },
+//! }
+//!
+//! #[derive(Debug, Clone)]
+//! struct Node {
+//!     is_leaf: bool,
+//!     entries: Vec<Entry>,
+//! }
+//!
+//! impl EntryAccess for Entry {
+//!     type BV = Rectangle;
+//!     type Node = Node;
+//!     type Obj = u32;
+//!
+//!     fn mbr(&self) -> &Rectangle {
+//!         match self {
+//!             Entry::Leaf { mbr, .. } | Entry::Node { mbr, .. } => mbr,
+//!         }
+//!     }
+//!     fn as_leaf_obj(&self) -> Option<&u32> {
+//!         match self {
+//!             Entry::Leaf { id, .. } => Some(id),
+//!             Entry::Node { .. } => None,
+//!         }
+//!     }
+//!     fn as_leaf_mut(&mut self) -> Option<(&mut Rectangle, &mut u32)> {
+//!         match self {
+//!             Entry::Leaf { mbr, id } => Some((mbr, id)),
+//!             Entry::Node { .. } => None,
+//!         }
+//!     }
+//!     fn child(&self) -> Option<&Node> {
+//!         match self {
+//!             Entry::Node { child, .. } => Some(child),
+//!             Entry::Leaf { .. } => None,
+//!         }
+//!     }
+//!     fn child_mut(&mut self) -> Option<&mut Node> {
+//!         match self {
+//!             Entry::Node { child, .. } => Some(child),
+//!             Entry::Leaf { .. } => None,
+//!         }
+//!     }
+//!     fn set_mbr(&mut self, new_mbr: Rectangle) {
+//!         if let Entry::Node { mbr, .. } = self {
+//!             *mbr = new_mbr;
+//!         }
+//!     }
+//!     fn into_child(self) -> Option<Box<Node>> {
+//!         match self {
+//!             Entry::Node { child, .. } => Some(child),
+//!             Entry::Leaf { .. } => None,
+//!         }
+//!     }
+//! }
+//!
+//! impl NodeAccess for Node {
+//!     type Entry = Entry;
+//!
+//!     fn is_leaf(&self) -> bool {
+//!         self.is_leaf
+//!     }
+//!     fn entries(&self) -> &Vec<Entry> {
+//!         &self.entries
+//!     }
+//!     fn entries_mut(&mut self) -> &mut Vec<Entry> {
+//!         &mut self.entries
+//!     }
+//! }
+//!
+//! // A root with two leaf nodes of two unit squares each.
+//! let square = |x: f64, id: u32| Entry::Leaf {
+//!     mbr: Rectangle { x, y: 0.0, width: 1.0, height: 1.0 },
+//!     id,
+//! };
+//! let leaf = |x: f64, ids: [u32; 2]| Entry::Node {
+//!     mbr: Rectangle { x, y: 0.0, width: 3.0, height: 1.0 },
+//!     child: Box::new(Node {
+//!         is_leaf: true,
+//!         entries: vec![square(x, ids[0]), square(x + 2.0, ids[1])],
+//!     }),
+//! };
+//! let mut root = Node {
+//!     is_leaf: false,
+//!     entries: vec![leaf(0.0, [1, 2]), leaf(10.0, [3, 4])],
+//! };
+//! assert!(check_tree(&root, 4, 2).is_ok());
+//!
+//! let query = Rectangle { x: 1.5, y: 0.5, width: 9.0, height: 1.0 };
+//! let mut found = Vec::new();
+//! search_node(&root, &query, &mut found);
+//! assert_eq!(found, vec![&2, &3]);
+//!
+//! // Deleting 1 leaves its node underfilled, so the node is removed and its remaining
+//! // entries are handed back for reinsertion.
+//! let mut reinsert = Vec::new();
+//! let mbr = Rectangle { x: 0.0, y: 0.0, width: 1.0, height: 1.0 };
+//! assert!(delete_entry(&mut root, &1, &mbr, 2, &mut reinsert));
+//! assert_eq!(root.entries.len(), 1);
+//! assert_eq!(reinsert.len(), 1);
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{BSPBounds, BoundingVolume, overlap_fraction};
+use crate::stats::{BuildQuality, TreeStats};
 use std::cmp::Ordering;
 
 /// Abstraction over an entry in a spatial tree (R-tree family).
+///
+/// An entry is either a leaf entry, holding a stored object, or a node entry, holding a child
+/// node; both carry the MBR of what they hold.
 pub trait EntryAccess {
+    /// The type of the bounding volumes (e.g. `Rectangle`, `Cube`, or `Aabb`).
     type BV: BoundingVolume + Clone;
+    /// The type of the nodes holding the entries.
     type Node: NodeAccess<Entry = Self>;
+    /// The type of the stored objects.
     type Obj;
 
+    /// Returns the MBR of the object or child node.
     fn mbr(&self) -> &Self::BV;
 
+    /// Returns the object of a leaf entry, or `None` for a node entry.
     fn as_leaf_obj(&self) -> Option<&Self::Obj>;
 
+    /// Mutable access to the MBR and object of a Leaf entry.
+    fn as_leaf_mut(&mut self) -> Option<(&mut Self::BV, &mut Self::Obj)>;
+
+    /// Returns the child node of a node entry, or `None` for a leaf entry.
     fn child(&self) -> Option<&Self::Node>;
 
+    /// Mutable access to the child node of a node entry.
     fn child_mut(&mut self) -> Option<&mut Self::Node>;
 
+    /// Replaces the MBR of a node entry, after its child has changed. The MBRs of leaf entries
+    /// are only changed through `as_leaf_mut`, so implementations may ignore them here.
     fn set_mbr(&mut self, new_mbr: Self::BV);
 
     /// Consume the entry and return its child node if it is a Node entry.
@@ -24,13 +167,19 @@ pub trait EntryAccess {
 }
 
 /// Abstraction over a node in a spatial tree (R-tree family).
+///
+/// A leaf node holds only leaf entries, and an inner node only node entries.
 pub trait NodeAccess {
+    /// The type of the entries in the node.
     type Entry: EntryAccess;
 
+    /// Returns `true` if the node holds leaf entries.
     fn is_leaf(&self) -> bool;
 
+    /// Returns the entries of the node.
     fn entries(&self) -> &Vec<Self::Entry>;
 
+    /// Mutable access to the entries of the node.
     fn entries_mut(&mut self) -> &mut Vec<Self::Entry>;
 }
 
@@ -41,7 +190,212 @@ pub fn compute_group_mbr<E: EntryAccess>(entries: &[E]) -> Option<E::BV> {
     Some(iter.fold(first, |acc, entry| acc.union(entry.mbr())))
 }
 
+/// Returns the height of `node`: 1 for a leaf node, one more than its children otherwise.
+pub fn node_height<N: NodeAccess>(node: &N) -> usize {
+    let mut height = 1;
+    let mut current = node.entries().first().and_then(|e| e.child());
+    while let Some(child) = current {
+        height += 1;
+        current = child.entries().first().and_then(|e| e.child());
+    }
+    height
+}
+
+/// Returns the height of the subtree an entry refers to: 0 for a leaf entry, and the height of
+/// its child node for a node entry.
+///
+/// An entry must be stored in a node whose height is exactly one more than its own, so entries
+/// taken out of the tree (e.g., for reinsertion) must be put back at that height.
+pub fn entry_height<E: EntryAccess>(entry: &E) -> usize {
+    entry.child().map_or(0, node_height)
+}
+
+/// Returns the nodes of the subtree rooted at `root` in pre-order, each with its depth (0 for
+/// `root`). Children are visited in the order they are stored.
+pub fn walk_nodes<N>(root: &N) -> impl Iterator<Item = (usize, &N)>
+where
+    N: NodeAccess,
+    N::Entry: EntryAccess<Node = N>,
+{
+    let mut stack = vec![(0, root)];
+    std::iter::from_fn(move || {
+        let (depth, node) = stack.pop()?;
+        let children = node
+            .entries()
+            .iter()
+            .rev()
+            .filter_map(|entry| entry.child());
+        stack.extend(children.map(|child| (depth + 1, child)));
+        Some((depth, node))
+    })
+}
+
+/// Returns the shape statistics of the tree rooted at `root`.
+pub(crate) fn tree_stats<N>(root: &N, max_entries: usize) -> TreeStats
+where
+    N: NodeAccess,
+    N::Entry: EntryAccess<Node = N>,
+{
+    let (mut node_count, mut leaf_count, mut size, mut entries) = (0, 0, 0, 0);
+    for (_, node) in walk_nodes(root) {
+        node_count += 1;
+        entries += node.entries().len();
+        if node.is_leaf() {
+            leaf_count += 1;
+            size += node.entries().len();
+        }
+    }
+    TreeStats {
+        size,
+        node_count,
+        leaf_count,
+        depth: node_height(root),
+        fill_factor: Some(entries as f64 / (node_count * max_entries) as f64),
+    }
+}
+
+/// Returns the overlap, dead space, and fill measures of the tree rooted at `root`.
+pub(crate) fn build_quality<N>(root: &N, max_entries: usize) -> BuildQuality
+where
+    N: NodeAccess,
+    N::Entry: EntryAccess<Node = N>,
+{
+    let (mut node_count, mut entry_count) = (0, 0);
+    let (mut node_volume, mut overlap, mut dead_space) = (0.0, 0.0, 0.0);
+    for (_, node) in walk_nodes(root) {
+        node_count += 1;
+        let entries = node.entries();
+        entry_count += entries.len();
+        let Some(mbr) = compute_group_mbr(entries) else {
+            continue;
+        };
+        node_volume += mbr.area();
+        let covered: f64 = entries.iter().map(|entry| entry.mbr().area()).sum();
+        dead_space += (mbr.area() - covered).max(0.0);
+        if !node.is_leaf() {
+            for (i, a) in entries.iter().enumerate() {
+                for b in &entries[i + 1..] {
+                    overlap += a.mbr().overlap(b.mbr());
+                }
+            }
+        }
+    }
+    BuildQuality {
+        node_count,
+        average_fill: entry_count as f64 / (node_count * max_entries) as f64,
+        node_volume,
+        overlap,
+        dead_space,
+    }
+}
+
+/// Checks the structural invariants of the tree rooted at `root`: every node holds at most
+/// `max_entries` entries (and every node but the root at least `min_entries`), leaf nodes
+/// hold only objects and all sit at the same depth, inner nodes hold only child nodes, and
+/// the MBR of every node entry encloses the entries of its child.
+pub fn check_tree<N>(root: &N, max_entries: usize, min_entries: usize) -> Result<(), SpartError>
+where
+    N: NodeAccess,
+    N::Entry: EntryAccess<Node = N>,
+    <N::Entry as EntryAccess>::BV: BSPBounds,
+{
+    let violation = |reason: String| Err(SpartError::InvariantViolation { reason });
+    let mut leaf_depth = None;
+    for (depth, node) in walk_nodes(root) {
+        let count = node.entries().len();
+        if count > max_entries {
+            return violation(format!(
+                "node at depth {depth} holds {count} entries, more than the maximum of {max_entries}"
+            ));
+        }
+        if depth > 0 && count < min_entries {
+            return violation(format!(
+                "node at depth {depth} holds {count} entries, fewer than the minimum of {min_entries}"
+            ));
+        }
+        if node.is_leaf() {
+            if node.entries().iter().any(|e| e.as_leaf_obj().is_none()) {
+                return violation(format!("leaf node at depth {depth} holds a child node"));
+            }
+            if *leaf_depth.get_or_insert(depth) != depth {
+                return violation(format!(
+                    "leaf nodes at depths {} and {depth}",
+                    leaf_depth.unwrap_or(depth)
+                ));
+            }
+            continue;
+        }
+        for entry in node.entries() {
+            let Some(child) = entry.child() else {
+                return violation(format!("inner node at depth {depth} holds an object"));
+            };
+            let enclosed = child
+                .entries()
+                .iter()
+                .all(|e| encloses(entry.mbr(), e.mbr()));
+            if !enclosed {
+                return violation(format!(
+                    "MBR of a node entry at depth {depth} does not enclose its child"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns `true` if `inner` lies inside `outer`, up to rounding errors.
+fn encloses<B: BSPBounds>(outer: &B, inner: &B) -> bool {
+    (0..B::DIM).all(|dim| {
+        let (Ok(oc), Ok(oe), Ok(ic), Ok(ie)) = (
+            outer.center(dim),
+            outer.extent(dim),
+            inner.center(dim),
+            inner.extent(dim),
+        ) else {
+            return false;
+        };
+        // The bounds are rebuilt from centers and extents, which costs a few units of rounding.
+        let slack = 8.0 * f64::EPSILON * (oc.abs() + oe + ic.abs() + ie);
+        ic - ie / 2.0 >= oc - oe / 2.0 - slack && ic + ie / 2.0 <= oc + oe / 2.0 + slack
+    })
+}
+
+/// Estimates the number of objects under `node` whose MBRs intersect `query`.
+///
+/// Only inner nodes are visited: each leaf node whose MBR intersects `query` counts for its
+/// number of entries times the fraction of its MBR inside `query`. The objects of a tree that
+/// is a single leaf node are counted exactly.
+pub(crate) fn estimate_count<N>(node: &N, query: &<N::Entry as EntryAccess>::BV) -> f64
+where
+    N: NodeAccess,
+    N::Entry: EntryAccess<Node = N>,
+    <N::Entry as EntryAccess>::BV: BSPBounds,
+{
+    if node.is_leaf() {
+        return node
+            .entries()
+            .iter()
+            .filter(|entry| entry.mbr().intersects(query))
+            .count() as f64;
+    }
+    node.entries()
+        .iter()
+        .filter(|entry| entry.mbr().intersects(query))
+        .filter_map(|entry| Some((entry.mbr(), entry.child()?)))
+        .map(|(mbr, child)| {
+            if child.is_leaf() {
+                child.entries().len() as f64 * overlap_fraction(mbr, query)
+            } else {
+                estimate_count(child, query)
+            }
+        })
+        .sum()
+}
+
 /// Generic range search on a node.
+///
+/// Appends to `result` the objects under `node` whose MBRs intersect `query`, in the order
+/// they are stored.
 pub fn search_node<'a, N>(
     node: &'a N,
     query: &<N::Entry as EntryAccess>::BV,
@@ -49,26 +403,86 @@ pub fn search_node<'a, N>(
 ) where
     N: NodeAccess,
 {
-    if node.is_leaf() {
-        for entry in node.entries() {
-            if let Some(obj) = entry.as_leaf_obj() {
-                if entry.mbr().intersects(query) {
-                    result.push(obj);
-                }
-            }
+    search_node_limited(node, query, usize::MAX, result);
+}
+
+/// Generic range search on a node that stops once `result` holds `limit` objects.
+///
+/// Appends to `result` the objects under `node` whose MBRs intersect `query`, in the order
+/// they are stored, until `result` holds `limit` objects.
+pub fn search_node_limited<'a, N>(
+    node: &'a N,
+    query: &<N::Entry as EntryAccess>::BV,
+    limit: usize,
+    result: &mut Vec<&'a <N::Entry as EntryAccess>::Obj>,
+) where
+    N: NodeAccess,
+{
+    for entry in node.entries() {
+        if result.len() >= limit {
+            return;
         }
-    } else {
-        for entry in node.entries() {
-            if let Some(child) = entry.child() {
-                if entry.mbr().intersects(query) {
-                    search_node(child, query, result);
-                }
+        if !entry.mbr().intersects(query) {
+            continue;
+        }
+        if node.is_leaf() {
+            if let Some(obj) = entry.as_leaf_obj() {
+                result.push(obj);
             }
+        } else if let Some(child) = entry.child() {
+            search_node_limited(child, query, limit, result);
         }
     }
 }
 
+/// Generic range search on a node that searches the subtrees of large nodes in parallel.
+///
+/// `height` is the height of `node` (1 for a leaf node). The children of a node are searched
+/// as parallel tasks only if each of them is estimated to hold at least `min_subtree_size`
+/// objects, taking `min_entries` entries per node; smaller subtrees are searched sequentially.
+/// The objects are returned in the same order as with `search_node`.
+#[cfg(feature = "parallel")]
+pub fn par_search_node<'a, N>(
+    node: &'a N,
+    height: usize,
+    query: &<N::Entry as EntryAccess>::BV,
+    min_subtree_size: usize,
+    min_entries: usize,
+) -> Vec<&'a <N::Entry as EntryAccess>::Obj>
+where
+    N: NodeAccess + Sync,
+    N::Entry: EntryAccess<Node = N> + Sync,
+    <N::Entry as EntryAccess>::BV: Sync,
+    <N::Entry as EntryAccess>::Obj: Sync,
+{
+    use rayon::prelude::*;
+
+    let child_size = min_entries
+        .max(1)
+        .saturating_pow((height.max(1) - 1) as u32);
+    if node.is_leaf() || child_size < min_subtree_size.max(1) {
+        let mut result = Vec::new();
+        search_node(node, query, &mut result);
+        return result;
+    }
+    node.entries()
+        .par_iter()
+        .filter(|entry| entry.mbr().intersects(query))
+        .filter_map(|entry| entry.child())
+        .map(|child| par_search_node(child, height - 1, query, min_subtree_size, min_entries))
+        .reduce(Vec::new, |mut left, right| {
+            left.extend(right);
+            left
+        })
+}
+
 /// Generic delete logic that mirrors both R-tree and R*-tree implementations.
+///
+/// Removes the first object equal to `object`, searching only the subtrees whose MBR
+/// intersects `object_mbr`, and returns `true` if one was found. Child nodes left with fewer
+/// than `min_entries` entries are removed, and their entries appended to `reinsert_list`; the
+/// caller must reinsert them at their original height (see `entry_height`) and shrink the root
+/// if it is left with a single child.
 pub fn delete_entry<N>(
     node: &mut N,
     object: &<N::Entry as EntryAccess>::Obj,
@@ -82,57 +496,245 @@ where
     <<N as NodeAccess>::Entry as EntryAccess>::BV: Clone,
     <<N as NodeAccess>::Entry as EntryAccess>::Obj: PartialEq,
 {
-    let mut deleted = false;
     if node.is_leaf() {
         let entries = node.entries_mut();
-        if let Some(pos) = entries.iter().position(|e| match e.as_leaf_obj() {
+        let Some(pos) = entries.iter().position(|e| match e.as_leaf_obj() {
             Some(o) => o == object,
             None => false,
-        }) {
-            entries.remove(pos);
-            deleted = true;
+        }) else {
+            return false;
+        };
+        entries.remove(pos);
+        return true;
+    }
+    let entries = node.entries_mut();
+    for i in 0..entries.len() {
+        let entry = &mut entries[i];
+        // Only descend into child nodes if MBR intersects object MBR
+        if !entry.mbr().intersects(object_mbr) {
+            continue;
         }
-    } else {
-        let entries = node.entries_mut();
-        let mut to_delete_indices = Vec::new();
-        for (i, entry) in entries.iter_mut().enumerate() {
-            // Only descend into child nodes if MBR intersects object MBR
-            let do_descend = {
-                let mbr_clone = entry.mbr().clone();
-                mbr_clone.intersects(object_mbr)
-            };
-            if do_descend {
-                if let Some(child) = entry.child_mut() {
-                    if delete_entry(child, object, object_mbr, min_entries, reinsert_list) {
-                        deleted = true;
-                        if child.entries().len() < min_entries {
-                            to_delete_indices.push(i);
-                        } else if let Some(new_mbr) = compute_group_mbr(child.entries()) {
-                            entry.set_mbr(new_mbr);
-                        }
-                    }
+        let Some(child) = entry.child_mut() else {
+            continue;
+        };
+        // Stop at the first subtree that held a match, so that one copy is removed even if
+        // equal objects are stored in several subtrees.
+        if !delete_entry(child, object, object_mbr, min_entries, reinsert_list) {
+            continue;
+        }
+        if child.entries().len() >= min_entries {
+            if let Some(new_mbr) = compute_group_mbr(child.entries()) {
+                entry.set_mbr(new_mbr);
+            }
+        } else if let Some(child_box) = entries.remove(i).into_child() {
+            // Remove the underfilled child and move its entries into the reinsert list
+            let mut child = *child_box;
+            reinsert_list.append(child.entries_mut());
+        }
+        return true;
+    }
+    false
+}
+
+/// Generic bottom-up MBR recomputation.
+///
+/// `update` is applied to every stored object and returns the object's new MBR; the MBRs of
+/// all node entries are then recomputed from their children. The tree structure is unchanged.
+/// Returns the MBR of all entries of `node`, or `None` if the node is empty.
+pub fn refit_node<N, F>(node: &mut N, update: &mut F) -> Option<<N::Entry as EntryAccess>::BV>
+where
+    N: NodeAccess,
+    F: FnMut(&mut <N::Entry as EntryAccess>::Obj) -> <N::Entry as EntryAccess>::BV,
+{
+    for entry in node.entries_mut().iter_mut() {
+        if let Some((mbr, object)) = entry.as_leaf_mut() {
+            *mbr = update(object);
+        } else if let Some(child) = entry.child_mut() {
+            if let Some(new_mbr) = refit_node(child, update) {
+                entry.set_mbr(new_mbr);
+            }
+        }
+    }
+    compute_group_mbr(node.entries())
+}
+
+/// A stored object together with the key it was ranked by.
+pub(crate) type KeyedObject<'a, E> = (f64, &'a <E as EntryAccess>::Obj);
+
+/// Generic branch-and-bound search for the object whose MBR reaches furthest along `axis`.
+///
+/// With `max` set, objects are ranked by the upper end of their MBR along `axis`; otherwise by
+/// the negated lower end, so a larger key is always better. Entries are visited best bound
+/// first and skipped once their MBR cannot beat `best`, which is updated in place.
+pub(crate) fn extreme_by_axis<'a, N>(
+    node: &'a N,
+    axis: usize,
+    max: bool,
+    best: &mut Option<KeyedObject<'a, N::Entry>>,
+) where
+    N: NodeAccess,
+    <N::Entry as EntryAccess>::BV: BSPBounds,
+{
+    let key = |mbr: &<N::Entry as EntryAccess>::BV| {
+        let center = mbr
+            .center(axis)
+            .unwrap_or_else(|_| unreachable!("axis checked by caller"));
+        let half = mbr
+            .extent(axis)
+            .unwrap_or_else(|_| unreachable!("axis checked by caller"))
+            / 2.0;
+        if max { center + half } else { half - center }
+    };
+    let mut entries: Vec<(f64, &N::Entry)> = node
+        .entries()
+        .iter()
+        .map(|entry| (key(entry.mbr()), entry))
+        .collect();
+    entries.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (entry_key, entry) in entries {
+        // A node's MBR may fall short of its children's by rounding, so only entries that are
+        // strictly worse are pruned.
+        if best.is_some_and(|(best_key, _)| entry_key < best_key) {
+            break;
+        }
+        if let Some(obj) = entry.as_leaf_obj() {
+            if best.is_none_or(|(best_key, _)| entry_key > best_key) {
+                *best = Some((entry_key, obj));
+            }
+        } else if let Some(child) = entry.child() {
+            extreme_by_axis(child, axis, max, best);
+        }
+    }
+}
+
+/// A pair of references to objects stored in leaf entries.
+pub(crate) type ObjectPair<'a, E> = (&'a <E as EntryAccess>::Obj, &'a <E as EntryAccess>::Obj);
+
+/// Generic self-join reporting every pair of stored objects whose MBRs intersect.
+///
+/// Each unordered pair of distinct entries is reported exactly once and no object is paired
+/// with itself: sibling entries are only paired as `(i, j)` with `i < j`, and the pairs found
+/// across two subtrees can never be found again inside either of them.
+pub(crate) fn self_join<'a, N>(node: &'a N, pairs: &mut Vec<ObjectPair<'a, N::Entry>>)
+where
+    N: NodeAccess,
+{
+    let entries = node.entries();
+    for (i, a) in entries.iter().enumerate() {
+        for b in &entries[i + 1..] {
+            if a.mbr().intersects(b.mbr()) {
+                join_entries(a, b, pairs);
+            }
+        }
+    }
+    for entry in entries {
+        if let Some(child) = entry.child() {
+            self_join(child, pairs);
+        }
+    }
+}
+
+/// Reports all pairs of objects, one from each entry's subtree, whose MBRs intersect.
+fn join_entries<'a, E>(a: &'a E, b: &'a E, pairs: &mut Vec<ObjectPair<'a, E>>)
+where
+    E: EntryAccess,
+{
+    match (a.as_leaf_obj(), b.as_leaf_obj(), a.child(), b.child()) {
+        (Some(obj_a), Some(obj_b), _, _) => pairs.push((obj_a, obj_b)),
+        (_, _, Some(child_a), _) => {
+            for entry in child_a.entries() {
+                if entry.mbr().intersects(b.mbr()) {
+                    join_entries(entry, b, pairs);
+                }
+            }
+        }
+        (_, _, None, Some(child_b)) => {
+            for entry in child_b.entries() {
+                if a.mbr().intersects(entry.mbr()) {
+                    join_entries(a, entry, pairs);
                 }
             }
         }
+        _ => {}
+    }
+}
 
-        // Remove underfilled children and reinsert their entries
-        for &index in to_delete_indices.iter().rev() {
-            // We need to move the entry out to get ownership and extract its child
-            let removed = entries.remove(index);
-            if let Some(child_box) = removed.into_child() {
-                // Move all child entries into the reinsert list
-                let mut child = *child_box;
-                reinsert_list.append(child.entries_mut());
+/// Generic best-first search for the `k` nearest objects that pass a filter.
+///
+/// `bound` gives a lower bound of the distance to anything inside an MBR, and `distance` the
+/// distance to an object, or `None` if the object is filtered out. Subtrees whose MBR fails
+/// `keep` are skipped. The objects are returned from nearest to farthest.
+pub fn knn_filtered<'a, N, B, D, K>(
+    root: &'a N,
+    k: usize,
+    bound: B,
+    distance: D,
+    keep: K,
+) -> Vec<&'a <N::Entry as EntryAccess>::Obj>
+where
+    N: NodeAccess,
+    B: Fn(&<N::Entry as EntryAccess>::BV) -> f64,
+    D: Fn(&<N::Entry as EntryAccess>::Obj) -> Option<f64>,
+    K: Fn(&<N::Entry as EntryAccess>::BV) -> bool,
+{
+    let mut heap = std::collections::BinaryHeap::new();
+    let push = |heap: &mut std::collections::BinaryHeap<KnnCandidate<'a, N::Entry>>,
+                entry: &'a N::Entry| {
+        let dist = match entry.as_leaf_obj() {
+            Some(obj) => distance(obj),
+            None => keep(entry.mbr()).then(|| bound(entry.mbr())),
+        };
+        if let Some(dist) = dist {
+            heap.push(KnnCandidate { dist, entry });
+        }
+    };
+    for entry in root.entries() {
+        push(&mut heap, entry);
+    }
+    let mut found = Vec::new();
+    while found.len() < k {
+        let Some(KnnCandidate { entry, .. }) = heap.pop() else {
+            break;
+        };
+        if let Some(obj) = entry.as_leaf_obj() {
+            found.push(obj);
+        } else if let Some(child) = entry.child() {
+            for child_entry in child.entries() {
+                push(&mut heap, child_entry);
             }
         }
     }
-    deleted
+    found
+}
+
+/// Returns the smallest squared Euclidean distance between two bounding volumes, which is
+/// zero if they intersect.
+///
+/// This ranks boxes against a query box (e.g., for `knn_filtered`), and, being a lower bound
+/// of the distance between anything inside them, also prunes subtrees.
+pub fn box_distance_sq<B: BSPBounds>(a: &B, b: &B) -> f64 {
+    (0..B::DIM)
+        .map(
+            |dim| match (a.center(dim), b.center(dim), a.extent(dim), b.extent(dim)) {
+                (Ok(ca), Ok(cb), Ok(ea), Ok(eb)) => {
+                    let gap = ((ca - cb).abs() - (ea + eb) / 2.0).max(0.0);
+                    gap * gap
+                }
+                _ => 0.0,
+            },
+        )
+        .sum()
 }
 
 /// Shared KNN candidate wrapper for priority queues.
+///
+/// Candidates are ordered by reversed distance, so a `BinaryHeap` of them pops the nearest
+/// candidate first.
 #[derive(Debug)]
 pub struct KnnCandidate<'a, E: EntryAccess> {
+    /// The distance (or squared distance) of the entry, used as its priority.
     pub dist: f64,
+    /// The entry.
     pub entry: &'a E,
 }
 
@@ -193,6 +795,13 @@ mod tests {
             self.obj.as_ref()
         }
 
+        fn as_leaf_mut(&mut self) -> Option<(&mut Self::BV, &mut Self::Obj)> {
+            match self.obj.as_mut() {
+                Some(obj) => Some((&mut self.mbr, obj)),
+                None => None,
+            }
+        }
+
         fn child(&self) -> Option<&Self::Node> {
             self.child.as_deref()
         }
@@ -305,4 +914,47 @@ mod tests {
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].id, 1);
     }
+
+    #[test]
+    fn test_self_join_reports_each_pair_once() {
+        let leaf = |id: i32, x: f64| {
+            let rect = Rectangle {
+                x,
+                y: 0.0,
+                width: 1.5,
+                height: 1.0,
+            };
+            TestEntry {
+                mbr: rect.clone(),
+                obj: Some(TestObj { id, rect }),
+                child: None,
+            }
+        };
+        let left = TestNode {
+            is_leaf: true,
+            entries: vec![leaf(1, 0.0), leaf(2, 1.0)],
+        };
+        let right = TestNode {
+            is_leaf: true,
+            entries: vec![leaf(3, 2.0), leaf(4, 10.0)],
+        };
+        let node_entry = |node: TestNode| TestEntry {
+            mbr: compute_group_mbr(&node.entries).unwrap(),
+            obj: None,
+            child: Some(Box::new(node)),
+        };
+        let root = TestNode {
+            is_leaf: false,
+            entries: vec![node_entry(left), node_entry(right)],
+        };
+
+        let mut pairs = Vec::new();
+        self_join(&root, &mut pairs);
+        let mut ids: Vec<(i32, i32)> = pairs
+            .iter()
+            .map(|(a, b)| (a.id.min(b.id), a.id.max(b.id)))
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![(1, 2), (2, 3)]);
+    }
 }
@@ -0,0 +1,663 @@
+//! ## Slab-Backed Octree
+//!
+//! This module implements `SlabOctree`, the 3D counterpart of `SlabQuadtree`: an octree that
+//! keeps all of its points in one contiguous vector (the slab) and all of its nodes in another,
+//! linked by indices. Large point clouds are indexed without a heap allocation per node or a
+//! copy of each point per level, which greatly reduces the allocation churn of `Octree`.
+//!
+//! `insert_bulk` copies each point into the slab once, and then builds the tree by
+//! partitioning slot indices between the octants. Deleting a point leaves a tombstone in its
+//! slot, which the next insert resurrects; `compact` rebuilds the tree once many points have
+//! been deleted. `iter` reads the points straight from the slab.
+//!
+//! Nodes are subdivided and points routed by the same rules as in `Octree`, down to
+//! `MAX_DEPTH` levels, where a leaf holds all points falling into its cell.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Cube, EuclideanDistance, Point3D};
+//! use spart::slab_octree::SlabOctree;
+//!
+//! let boundary = Cube { x: 0.0, y: 0.0, z: 0.0, width: 100.0, height: 100.0, depth: 100.0 };
+//! let mut cloud = SlabOctree::new(&boundary, 8).unwrap();
+//! // A 10 x 10 x 10 grid with a spacing of 10.
+//! let points: Vec<_> = (0..1000)
+//!     .map(|i| {
+//!         let (x, y, z) = (i % 10, i / 10 % 10, i / 100);
+//!         Point3D::new(x as f64 * 10.0, y as f64 * 10.0, z as f64 * 10.0, Some(i))
+//!     })
+//!     .collect();
+//! assert_eq!(cloud.insert_bulk(&points), 1000);
+//!
+//! let target = Point3D::new(41.0, 52.0, 29.0, None);
+//! let nearest = cloud.knn_search::<EuclideanDistance>(&target, 1);
+//! assert_eq!(nearest[0].data, Some(354));
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{Cube, DistanceMetric, Point3D};
+use crate::knn::KnnResults;
+use crate::logging::hot_trace;
+use crate::predicates::covering_extent;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// The number of subdivision levels below the root.
+pub const MAX_DEPTH: usize = 32;
+
+/// An octree storing its points in a slab and its nodes in a vector, linked by indices.
+///
+/// # Type Parameters
+///
+/// * `T`: The type of additional data stored in each point.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SlabOctree<T: Clone + PartialEq> {
+    capacity: usize,
+    /// The stored points; `None` marks the tombstone of a deleted point.
+    slab: Vec<Option<Point3D<T>>>,
+    /// The tombstoned slots, reused by the next inserts.
+    free: Vec<usize>,
+    /// The nodes, with the root at index 0.
+    nodes: Vec<SlabNode>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct SlabNode {
+    boundary: Cube,
+    depth: usize,
+    /// The index of the first of the eight children, which are stored next to each other in
+    /// Morton order (see `Octant::index`), or `None` for a leaf.
+    children: Option<usize>,
+    /// The slots of the points stored in a leaf.
+    slots: Vec<usize>,
+}
+
+impl SlabNode {
+    fn leaf(boundary: Cube, depth: usize) -> Self {
+        SlabNode {
+            boundary,
+            depth,
+            children: None,
+            slots: Vec::new(),
+        }
+    }
+
+    /// Returns the Morton digit of the child a point inside this node is routed to.
+    ///
+    /// Points on a split plane go to the smaller side, as in `Octree`.
+    fn digit<T>(&self, point: &Point3D<T>) -> usize {
+        let b = &self.boundary;
+        usize::from(point.x > b.x + b.width / 2.0)
+            | (usize::from(point.y > b.y + b.height / 2.0) << 1)
+            | (usize::from(point.z > b.z + b.depth / 2.0) << 2)
+    }
+
+    /// Returns the boundaries of the eight children, in Morton order.
+    fn child_boundaries(&self) -> [Cube; 8] {
+        let b = &self.boundary;
+        let (w, h, d) = (b.width / 2.0, b.height / 2.0, b.depth / 2.0);
+        // The far children reach the far faces of this node, which `x + w + w` can fall
+        // short of.
+        let far = [
+            covering_extent(b.x + w, b.x + b.width),
+            covering_extent(b.y + h, b.y + b.height),
+            covering_extent(b.z + d, b.z + b.depth),
+        ];
+        std::array::from_fn(|digit| {
+            let right = digit & 1 == 1;
+            let bottom = digit & 2 == 2;
+            let back = digit & 4 == 4;
+            Cube {
+                x: if right { b.x + w } else { b.x },
+                y: if bottom { b.y + h } else { b.y },
+                z: if back { b.z + d } else { b.z },
+                width: if right { far[0] } else { w },
+                height: if bottom { far[1] } else { h },
+                depth: if back { far[2] } else { d },
+            }
+        })
+    }
+
+    fn min_distance_sq<T>(&self, target: &Point3D<T>) -> f64 {
+        let b = &self.boundary;
+        let dx = (b.x - target.x).max(target.x - (b.x + b.width)).max(0.0);
+        let dy = (b.y - target.y).max(target.y - (b.y + b.height)).max(0.0);
+        let dz = (b.z - target.z).max(target.z - (b.z + b.depth)).max(0.0);
+        dx * dx + dy * dy + dz * dz
+    }
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> SlabOctree<T> {
+    /// Creates a new, empty slab-backed octree.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The cuboid region covered by the tree.
+    /// * `capacity` - The maximum number of points a leaf can hold before it is split.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn new(boundary: &Cube, capacity: usize) -> Result<Self, SpartError> {
+        if capacity == 0 {
+            return Err(SpartError::InvalidCapacity { capacity });
+        }
+        info!(
+            "Creating new SlabOctree with boundary: {:?} and capacity: {}",
+            boundary, capacity
+        );
+        Ok(SlabOctree {
+            capacity,
+            slab: Vec::new(),
+            free: Vec::new(),
+            nodes: vec![SlabNode::leaf(boundary.clone(), 0)],
+            len: 0,
+        })
+    }
+
+    /// Returns the boundary of the tree.
+    pub fn boundary(&self) -> &Cube {
+        &self.nodes[0].boundary
+    }
+
+    /// Returns the maximum number of points a leaf holds before it is split.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the total number of nodes in the tree.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the number of tombstoned slots in the slab, waiting to be reused.
+    pub fn tombstones(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Returns an iterator over all stored points, in slab order.
+    pub fn iter(&self) -> impl Iterator<Item = &Point3D<T>> + '_ {
+        self.slab.iter().flatten()
+    }
+
+    /// Inserts a point into the tree, reusing the slot of a deleted point if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was inserted, `false` if it lies outside the boundary.
+    pub fn insert(&mut self, point: Point3D<T>) -> bool {
+        if !self.boundary().contains(&point) {
+            return false;
+        }
+        let slot = self.store(point);
+        self.distribute(0, vec![slot]);
+        self.len += 1;
+        true
+    }
+
+    /// Inserts a bulk of points into the tree.
+    ///
+    /// Each point is copied into the slab once; the tree is then built by partitioning their
+    /// slots between the octants, level by level.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to insert. Points outside the boundary are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The number of inserted points.
+    pub fn insert_bulk(&mut self, points: &[Point3D<T>]) -> usize {
+        self.insert_owned(points.iter().cloned())
+    }
+
+    /// Moves points into the slab and builds the tree over them, as in `insert_bulk`.
+    fn insert_owned(&mut self, points: impl IntoIterator<Item = Point3D<T>>) -> usize {
+        let mut slots = Vec::new();
+        for point in points {
+            if self.boundary().contains(&point) {
+                slots.push(self.store(point));
+            }
+        }
+        let inserted = slots.len();
+        self.distribute(0, slots);
+        self.len += inserted;
+        inserted
+    }
+
+    /// Returns `true` if a point equal to `point` (coordinates and data) is stored.
+    pub fn contains(&self, point: &Point3D<T>) -> bool {
+        self.find(point).is_some()
+    }
+
+    /// Deletes a point equal to `point` (coordinates and data) from the tree.
+    ///
+    /// The point's slot is tombstoned and reused by a later insert. Nodes are not merged, see
+    /// `compact`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and removed.
+    pub fn delete(&mut self, point: &Point3D<T>) -> bool {
+        let Some((node, position)) = self.find(point) else {
+            return false;
+        };
+        let slot = self.nodes[node].slots.swap_remove(position);
+        self.slab[slot] = None;
+        self.free.push(slot);
+        self.len -= 1;
+        true
+    }
+
+    /// Rebuilds the tree from the stored points, dropping the tombstones and the nodes left
+    /// empty or underfull by deletions.
+    pub fn compact(&mut self) {
+        let points: Vec<Point3D<T>> = std::mem::take(&mut self.slab)
+            .into_iter()
+            .flatten()
+            .collect();
+        let root = SlabNode::leaf(self.boundary().clone(), 0);
+        self.nodes = vec![root];
+        self.free.clear();
+        self.len = 0;
+        self.insert_owned(points);
+    }
+
+    /// Performs a range search, returning all points inside the query cube (boundary
+    /// inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query cube.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points inside the query cube.
+    pub fn range_search_bbox(&self, query: &Cube) -> Vec<Point3D<T>> {
+        hot_trace!("Performing range search", query = query);
+        let mut found = Vec::new();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.boundary.intersects(query) {
+                continue;
+            }
+            match node.children {
+                Some(first) => stack.extend(first..first + 8),
+                None => found.extend(
+                    self.points_of(node)
+                        .filter(|(_, p)| query.contains(p))
+                        .map(|(_, p)| p.clone()),
+                ),
+            }
+        }
+        found
+    }
+
+    /// Performs a range search, returning all points within the specified radius of the center point.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search range.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points within the range (empty if `radius` is negative).
+    pub fn range_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        center: &Point3D<T>,
+        radius: f64,
+    ) -> Vec<Point3D<T>> {
+        let mut found = Vec::new();
+        if radius < 0.0 {
+            return found;
+        }
+        let radius_sq = radius * radius;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.min_distance_sq(center) > radius_sq {
+                continue;
+            }
+            match node.children {
+                Some(first) => stack.extend(first..first + 8),
+                None => found.extend(
+                    self.points_of(node)
+                        .filter(|(_, p)| M::distance_sq(p, center) <= radius_sq)
+                        .map(|(_, p)| p.clone()),
+                ),
+            }
+        }
+        found
+    }
+
+    /// Performs a k-nearest neighbor search.
+    ///
+    /// Nodes are pruned by their Euclidean distance to the target, so the metric should be
+    /// Euclidean or never report smaller distances than it.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The target point.
+    /// * `k` - The number of nearest neighbors to return.
+    ///
+    /// # Returns
+    ///
+    /// A vector of up to `k` points, ordered from nearest to farthest.
+    pub fn knn_search<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        target: &Point3D<T>,
+        k: usize,
+    ) -> Vec<Point3D<T>> {
+        if k == 0 || self.is_empty() {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.knn_search_node::<M>(0, target, &mut results);
+        results
+            .into_sorted_vec()
+            .into_iter()
+            .filter_map(|slot| self.slab[slot].clone())
+            .collect()
+    }
+
+    fn knn_search_node<M: DistanceMetric<Point3D<T>>>(
+        &self,
+        index: usize,
+        target: &Point3D<T>,
+        results: &mut KnnResults<usize>,
+    ) {
+        let node = &self.nodes[index];
+        match node.children {
+            None => {
+                for (slot, point) in self.points_of(node) {
+                    let dist_sq = M::distance_sq(point, target);
+                    if results.accepts(dist_sq) {
+                        results.push(dist_sq, slot);
+                    }
+                }
+            }
+            Some(first) => {
+                // Visiting the closest children first tightens the pruning bound early.
+                let mut ordered: Vec<(f64, usize)> = (first..first + 8)
+                    .map(|child| (self.nodes[child].min_distance_sq(target), child))
+                    .collect();
+                ordered.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (min_distance_sq, child) in ordered {
+                    if min_distance_sq > results.bound() {
+                        break;
+                    }
+                    self.knn_search_node::<M>(child, target, results);
+                }
+            }
+        }
+    }
+
+    /// Returns the stored points of a leaf with their slots.
+    fn points_of<'a>(
+        &'a self,
+        node: &'a SlabNode,
+    ) -> impl Iterator<Item = (usize, &'a Point3D<T>)> + 'a {
+        node.slots
+            .iter()
+            .filter_map(move |&slot| self.slab[slot].as_ref().map(|point| (slot, point)))
+    }
+
+    /// Returns the point in a filled slot.
+    fn point(&self, slot: usize) -> &Point3D<T> {
+        self.slab[slot]
+            .as_ref()
+            .unwrap_or_else(|| unreachable!("slots in the tree are filled"))
+    }
+
+    /// Moves a point into the slab, resurrecting a tombstoned slot if there is one.
+    fn store(&mut self, point: Point3D<T>) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                self.slab[slot] = Some(point);
+                slot
+            }
+            None => {
+                self.slab.push(Some(point));
+                self.slab.len() - 1
+            }
+        }
+    }
+
+    /// Adds the points in `slots`, which lie inside the node's boundary, to the subtree of a
+    /// node, splitting leaves that overflow.
+    fn distribute(&mut self, index: usize, mut slots: Vec<usize>) {
+        let first = match self.nodes[index].children {
+            Some(first) => first,
+            None => {
+                let node = &mut self.nodes[index];
+                if node.slots.len() + slots.len() <= self.capacity || node.depth >= MAX_DEPTH {
+                    node.slots.append(&mut slots);
+                    return;
+                }
+                hot_trace!("Subdividing SlabOctree node", index = index);
+                let first = self.nodes.len();
+                let depth = self.nodes[index].depth + 1;
+                for boundary in self.nodes[index].child_boundaries() {
+                    self.nodes.push(SlabNode::leaf(boundary, depth));
+                }
+                let node = &mut self.nodes[index];
+                node.children = Some(first);
+                slots.append(&mut node.slots);
+                first
+            }
+        };
+        // Group the slots by octant, then hand each group to its child.
+        let node = &self.nodes[index];
+        let mut keyed: Vec<(usize, usize)> = slots
+            .into_iter()
+            .map(|slot| (node.digit(self.point(slot)), slot))
+            .collect();
+        keyed.sort_unstable_by_key(|&(digit, _)| digit);
+        let mut rest = keyed.as_slice();
+        while let Some(&(digit, _)) = rest.first() {
+            let end = rest.partition_point(|&(d, _)| d == digit);
+            let group = rest[..end].iter().map(|&(_, slot)| slot).collect();
+            self.distribute(first + digit, group);
+            rest = &rest[end..];
+        }
+    }
+
+    /// Returns the leaf holding a point equal to `point` and the position of its slot there.
+    fn find(&self, point: &Point3D<T>) -> Option<(usize, usize)> {
+        if !self.boundary().contains(point) {
+            return None;
+        }
+        let mut index = 0;
+        while let Some(first) = self.nodes[index].children {
+            index = first + self.nodes[index].digit(point);
+        }
+        let position = self.nodes[index]
+            .slots
+            .iter()
+            .position(|&slot| self.slab[slot].as_ref() == Some(point))?;
+        Some((index, position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::EuclideanDistance;
+    use crate::octree::Octree;
+    use crate::testing::fixtures::{sorted_ids, uniform_3d};
+
+    const BOUNDARY: Cube = Cube {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        width: 100.0,
+        height: 100.0,
+        depth: 100.0,
+    };
+
+    /// A slab octree and a plain octree holding the same 2000 random points.
+    fn trees() -> (Vec<Point3D<usize>>, SlabOctree<usize>, Octree<usize>) {
+        let points = uniform_3d(3502, &BOUNDARY, 2000);
+        let mut slab = SlabOctree::new(&BOUNDARY, 8).unwrap();
+        let mut plain = Octree::new(&BOUNDARY, 8).unwrap();
+        assert_eq!(slab.insert_bulk(&points), 2000);
+        plain.insert_bulk(&points);
+        (points, slab, plain)
+    }
+
+    #[test]
+    fn test_new_rejects_zero_capacity() {
+        assert!(matches!(
+            SlabOctree::<usize>::new(&BOUNDARY, 0),
+            Err(SpartError::InvalidCapacity { capacity: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_empty_tree_finds_nothing() {
+        let mut slab: SlabOctree<usize> = SlabOctree::new(&BOUNDARY, 8).unwrap();
+        let target = Point3D::new(50.0, 50.0, 50.0, None);
+        assert!(slab.is_empty());
+        assert!(slab.range_search_bbox(&BOUNDARY).is_empty());
+        assert!(slab.knn_search::<EuclideanDistance>(&target, 3).is_empty());
+        assert!(!slab.delete(&Point3D::new(50.0, 50.0, 50.0, Some(0))));
+    }
+
+    #[test]
+    fn test_insert_bulk_skips_points_outside_boundary() {
+        let mut slab = SlabOctree::new(&BOUNDARY, 8).unwrap();
+        let points = [
+            Point3D::new(-1.0, 5.0, 5.0, Some(0)),
+            Point3D::new(5.0, 5.0, 5.0, Some(1)),
+            Point3D::new(5.0, 5.0, 100.5, Some(2)),
+        ];
+        assert_eq!(slab.insert_bulk(&points), 1);
+        assert_eq!(sorted_ids(slab.iter()), [1]);
+    }
+
+    #[test]
+    fn test_bulk_build_respects_capacity() {
+        let (_, slab, _) = trees();
+        assert_eq!(slab.len(), 2000);
+        assert!(
+            slab.nodes
+                .iter()
+                .all(|n| n.slots.len() <= 8 || n.depth == MAX_DEPTH)
+        );
+    }
+
+    #[test]
+    fn test_duplicates_stop_splitting_at_max_depth() {
+        let mut slab = SlabOctree::new(&BOUNDARY, 8).unwrap();
+        let points: Vec<_> = (0..20)
+            .map(|i| Point3D::new(5.0, 5.0, 5.0, Some(i)))
+            .collect();
+        assert_eq!(slab.insert_bulk(&points), 20);
+        assert!(slab.nodes.iter().any(|n| n.slots.len() > 8));
+        assert!(
+            slab.nodes
+                .iter()
+                .all(|n| n.slots.len() <= 8 || n.depth == MAX_DEPTH)
+        );
+    }
+
+    #[test]
+    fn test_points_on_boundary_are_found() {
+        let mut slab = SlabOctree::new(&BOUNDARY, 1).unwrap();
+        let corners = [(0.0, 0.0, 0.0), (100.0, 100.0, 100.0), (100.0, 0.0, 100.0)];
+        for (i, (x, y, z)) in corners.into_iter().enumerate() {
+            assert!(slab.insert(Point3D::new(x, y, z, Some(i))));
+        }
+        assert_eq!(sorted_ids(slab.range_search_bbox(&BOUNDARY)), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_delete_missing_point_returns_false() {
+        let (points, mut slab, _) = trees();
+        assert!(slab.delete(&points[0]));
+        assert!(!slab.delete(&points[0]));
+        assert!(!slab.delete(&Point3D::new(50.0, 50.0, 50.0, Some(5000))));
+        assert_eq!((slab.len(), slab.tombstones()), (1999, 1));
+    }
+
+    #[test]
+    fn test_searches_match_octree_after_deletes() {
+        let (points, mut slab, mut plain) = trees();
+        for point in &points[..500] {
+            assert!(slab.delete(point));
+            assert!(plain.delete(point));
+        }
+        let query = Cube {
+            x: 10.0,
+            y: 20.0,
+            z: 30.0,
+            width: 40.0,
+            height: 30.0,
+            depth: 50.0,
+        };
+        assert_eq!(
+            sorted_ids(slab.range_search_bbox(&query)),
+            sorted_ids(points[500..].iter().filter(|p| query.contains(p)))
+        );
+        let target = Point3D::new(60.0, 40.0, 20.0, None);
+        assert_eq!(
+            sorted_ids(slab.range_search::<EuclideanDistance>(&target, 15.0)),
+            sorted_ids(plain.range_search::<EuclideanDistance>(&target, 15.0))
+        );
+        let distances = |ps: Vec<Point3D<usize>>| -> Vec<f64> {
+            ps.iter().map(|p| p.distance_sq(&target)).collect()
+        };
+        assert_eq!(
+            distances(slab.knn_search::<EuclideanDistance>(&target, 12)),
+            distances(plain.knn_search::<EuclideanDistance>(&target, 12))
+        );
+    }
+
+    #[test]
+    fn test_reinsert_reuses_tombstones() {
+        let (points, mut slab, _) = trees();
+        for point in &points[..500] {
+            slab.delete(point);
+        }
+        let slots = slab.slab.len();
+        for point in &points[..300] {
+            assert!(slab.insert(point.clone()));
+        }
+        assert_eq!((slab.slab.len(), slab.tombstones()), (slots, 200));
+        assert!(slab.contains(&points[0]));
+        assert_eq!(slab.iter().count(), 1800);
+    }
+
+    #[test]
+    fn test_compact_drops_tombstones() {
+        let (points, mut slab, _) = trees();
+        for point in &points[..500] {
+            slab.delete(point);
+        }
+        slab.compact();
+        assert_eq!((slab.len(), slab.tombstones()), (1500, 0));
+        assert_eq!(slab.slab.len(), 1500);
+        assert_eq!(sorted_ids(slab.iter()), (500..2000).collect::<Vec<_>>());
+    }
+}
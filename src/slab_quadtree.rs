@@ -0,0 +1,583 @@
+//! ## Slab-Backed Quadtree
+//!
+//! This module implements `SlabQuadtree`, a quadtree that keeps all of its points in one
+//! contiguous vector (the slab) and all of its nodes in another. Nodes refer to their children
+//! and leaves to their points by index, so the tree holds no boxed nodes, and subdividing a
+//! leaf moves indices rather than points. Compared with `Quadtree`, inserts and deletes
+//! allocate less and queries walk memory that is laid out compactly.
+//!
+//! Deleting a point leaves a tombstone in its slot of the slab, and the next insert resurrects
+//! the slot instead of growing the slab. Nodes are not merged on deletion; `compact` rebuilds
+//! the tree from the stored points once many of them have been deleted. Iterating over all
+//! points with `iter` reads the slab directly, without walking the tree.
+//!
+//! Nodes are subdivided and points routed by the same rules as in `Quadtree`, down to
+//! `MAX_DEPTH` levels, where a leaf holds all points falling into its cell.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{EuclideanDistance, Point2D, Rectangle};
+//! use spart::slab_quadtree::SlabQuadtree;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let mut tree = SlabQuadtree::new(&boundary, 4).unwrap();
+//! for i in 0..10 {
+//!     tree.insert(Point2D::new(i as f64 * 10.0, 50.0, Some(i)));
+//! }
+//! assert!(tree.delete(&Point2D::new(30.0, 50.0, Some(3))));
+//! assert_eq!(tree.len(), 9);
+//!
+//! let nearest = tree.knn_search::<EuclideanDistance>(&Point2D::new(32.0, 50.0, None), 1);
+//! assert_eq!(nearest[0].data, Some(4));
+//! // The next insert reuses the slot of the deleted point.
+//! tree.insert(Point2D::new(35.0, 50.0, Some(10)));
+//! assert_eq!(tree.iter().count(), 10);
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{DistanceMetric, Point2D, Rectangle};
+use crate::knn::KnnResults;
+use crate::logging::hot_trace;
+use crate::quadtree::{child_boundaries, quadrant_index};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+pub use crate::quadtree::MAX_DEPTH;
+
+/// A quadtree storing its points in a slab and its nodes in a vector, linked by indices.
+///
+/// # Type Parameters
+///
+/// * `T`: The type of additional data stored in each point.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SlabQuadtree<T: Clone + PartialEq> {
+    capacity: usize,
+    /// The stored points; `None` marks the tombstone of a deleted point.
+    slab: Vec<Option<Point2D<T>>>,
+    /// The tombstoned slots, reused by the next inserts.
+    free: Vec<usize>,
+    /// The nodes, with the root at index 0.
+    nodes: Vec<SlabNode>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct SlabNode {
+    boundary: Rectangle,
+    depth: usize,
+    /// The index of the first of the four children, which are stored next to each other in
+    /// Morton order (see `Quadrant::index`), or `None` for a leaf.
+    children: Option<usize>,
+    /// The slots of the points stored in a leaf.
+    slots: Vec<usize>,
+}
+
+impl SlabNode {
+    fn leaf(boundary: Rectangle, depth: usize) -> Self {
+        SlabNode {
+            boundary,
+            depth,
+            children: None,
+            slots: Vec::new(),
+        }
+    }
+
+    /// Returns the Morton digit of the child a point inside this node is routed to.
+    fn digit<T>(&self, point: &Point2D<T>) -> usize {
+        quadrant_index(&self.boundary, point)
+    }
+
+    /// Returns the boundaries of the four children, in Morton order.
+    fn child_boundaries(&self) -> [Rectangle; 4] {
+        child_boundaries(&self.boundary)
+    }
+
+    fn min_distance_sq<T>(&self, target: &Point2D<T>) -> f64 {
+        let b = &self.boundary;
+        let dx = (b.x - target.x).max(target.x - (b.x + b.width)).max(0.0);
+        let dy = (b.y - target.y).max(target.y - (b.y + b.height)).max(0.0);
+        dx * dx + dy * dy
+    }
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> SlabQuadtree<T> {
+    /// Creates a new, empty slab-backed quadtree.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The rectangular region covered by the tree.
+    /// * `capacity` - The maximum number of points a leaf can hold before it is split.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `capacity` is zero.
+    pub fn new(boundary: &Rectangle, capacity: usize) -> Result<Self, SpartError> {
+        if capacity == 0 {
+            return Err(SpartError::InvalidCapacity { capacity });
+        }
+        info!(
+            "Creating new SlabQuadtree with boundary: {:?} and capacity: {}",
+            boundary, capacity
+        );
+        Ok(SlabQuadtree {
+            capacity,
+            slab: Vec::new(),
+            free: Vec::new(),
+            nodes: vec![SlabNode::leaf(boundary.clone(), 0)],
+            len: 0,
+        })
+    }
+
+    /// Returns the boundary of the tree.
+    pub fn boundary(&self) -> &Rectangle {
+        &self.nodes[0].boundary
+    }
+
+    /// Returns the maximum number of points a leaf holds before it is split.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the total number of nodes in the tree.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the number of tombstoned slots in the slab, waiting to be reused.
+    pub fn tombstones(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Returns an iterator over all stored points, in slab order.
+    pub fn iter(&self) -> impl Iterator<Item = &Point2D<T>> + '_ {
+        self.slab.iter().flatten()
+    }
+
+    /// Inserts a point into the tree, reusing the slot of a deleted point if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was inserted, `false` if it lies outside the boundary.
+    pub fn insert(&mut self, point: Point2D<T>) -> bool {
+        if !self.boundary().contains(&point) {
+            return false;
+        }
+        let mut node = self.leaf_for(&point);
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slab[slot] = Some(point);
+                slot
+            }
+            None => {
+                self.slab.push(Some(point));
+                self.slab.len() - 1
+            }
+        };
+        // Split full leaves until the point reaches one with room.
+        while self.nodes[node].slots.len() >= self.capacity && self.nodes[node].depth < MAX_DEPTH {
+            self.subdivide(node);
+            let point = self.slab[slot]
+                .as_ref()
+                .unwrap_or_else(|| unreachable!("the slot was just filled"));
+            node = self.leaf_for(point);
+        }
+        self.nodes[node].slots.push(slot);
+        self.len += 1;
+        true
+    }
+
+    /// Inserts a bulk of points into the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points to insert. Points outside the boundary are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The number of inserted points.
+    pub fn insert_bulk(&mut self, points: &[Point2D<T>]) -> usize {
+        points
+            .iter()
+            .filter(|point| self.insert((*point).clone()))
+            .count()
+    }
+
+    /// Returns `true` if a point equal to `point` (coordinates and data) is stored.
+    pub fn contains(&self, point: &Point2D<T>) -> bool {
+        self.find(point).is_some()
+    }
+
+    /// Deletes a point equal to `point` (coordinates and data) from the tree.
+    ///
+    /// The point's slot is tombstoned and reused by a later insert. Nodes are not merged, see
+    /// `compact`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and removed.
+    pub fn delete(&mut self, point: &Point2D<T>) -> bool {
+        let Some((node, position)) = self.find(point) else {
+            return false;
+        };
+        let slot = self.nodes[node].slots.swap_remove(position);
+        self.slab[slot] = None;
+        self.free.push(slot);
+        self.len -= 1;
+        true
+    }
+
+    /// Rebuilds the tree from the stored points, dropping the tombstones and the nodes left
+    /// empty or underfull by deletions.
+    pub fn compact(&mut self) {
+        let points: Vec<Point2D<T>> = std::mem::take(&mut self.slab)
+            .into_iter()
+            .flatten()
+            .collect();
+        let root = SlabNode::leaf(self.boundary().clone(), 0);
+        self.nodes = vec![root];
+        self.free.clear();
+        self.len = 0;
+        for point in points {
+            self.insert(point);
+        }
+    }
+
+    /// Performs a range search, returning all points inside the query rectangle
+    /// (boundary inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query rectangle.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points inside the query rectangle.
+    pub fn range_search_bbox(&self, query: &Rectangle) -> Vec<Point2D<T>> {
+        hot_trace!("Performing range search", query = query);
+        let mut found = Vec::new();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.boundary.intersects(query) {
+                continue;
+            }
+            match node.children {
+                Some(first) => stack.extend(first..first + 4),
+                None => found.extend(
+                    self.points_of(node)
+                        .filter(|(_, p)| query.contains(p))
+                        .map(|(_, p)| p.clone()),
+                ),
+            }
+        }
+        found
+    }
+
+    /// Performs a range search, returning all points within the specified radius of the center point.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search range.
+    /// * `radius` - The search radius.
+    ///
+    /// # Returns
+    ///
+    /// A vector of points within the range (empty if `radius` is negative).
+    pub fn range_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        center: &Point2D<T>,
+        radius: f64,
+    ) -> Vec<Point2D<T>> {
+        let mut found = Vec::new();
+        if radius < 0.0 {
+            return found;
+        }
+        let radius_sq = radius * radius;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.min_distance_sq(center) > radius_sq {
+                continue;
+            }
+            match node.children {
+                Some(first) => stack.extend(first..first + 4),
+                None => found.extend(
+                    self.points_of(node)
+                        .filter(|(_, p)| M::distance_sq(p, center) <= radius_sq)
+                        .map(|(_, p)| p.clone()),
+                ),
+            }
+        }
+        found
+    }
+
+    /// Performs a k-nearest neighbor search.
+    ///
+    /// Nodes are pruned by their Euclidean distance to the target, so the metric should be
+    /// Euclidean or never report smaller distances than it.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The target point.
+    /// * `k` - The number of nearest neighbors to return.
+    ///
+    /// # Returns
+    ///
+    /// A vector of up to `k` points, ordered from nearest to farthest.
+    pub fn knn_search<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        target: &Point2D<T>,
+        k: usize,
+    ) -> Vec<Point2D<T>> {
+        if k == 0 || self.is_empty() {
+            return Vec::new();
+        }
+        let mut results = KnnResults::new(k);
+        self.knn_search_node::<M>(0, target, &mut results);
+        results
+            .into_sorted_vec()
+            .into_iter()
+            .filter_map(|slot| self.slab[slot].clone())
+            .collect()
+    }
+
+    fn knn_search_node<M: DistanceMetric<Point2D<T>>>(
+        &self,
+        index: usize,
+        target: &Point2D<T>,
+        results: &mut KnnResults<usize>,
+    ) {
+        let node = &self.nodes[index];
+        match node.children {
+            None => {
+                for (slot, point) in self.points_of(node) {
+                    let dist_sq = M::distance_sq(point, target);
+                    if results.accepts(dist_sq) {
+                        results.push(dist_sq, slot);
+                    }
+                }
+            }
+            Some(first) => {
+                // Visiting the closest children first tightens the pruning bound early.
+                let mut ordered: Vec<(f64, usize)> = (first..first + 4)
+                    .map(|child| (self.nodes[child].min_distance_sq(target), child))
+                    .collect();
+                ordered.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (min_distance_sq, child) in ordered {
+                    if min_distance_sq > results.bound() {
+                        break;
+                    }
+                    self.knn_search_node::<M>(child, target, results);
+                }
+            }
+        }
+    }
+
+    /// Returns the stored points of a leaf with their slots.
+    fn points_of<'a>(
+        &'a self,
+        node: &'a SlabNode,
+    ) -> impl Iterator<Item = (usize, &'a Point2D<T>)> + 'a {
+        node.slots
+            .iter()
+            .filter_map(move |&slot| self.slab[slot].as_ref().map(|point| (slot, point)))
+    }
+
+    /// Returns the leaf a point inside the boundary is routed to.
+    fn leaf_for(&self, point: &Point2D<T>) -> usize {
+        let mut index = 0;
+        while let Some(first) = self.nodes[index].children {
+            index = first + self.nodes[index].digit(point);
+        }
+        index
+    }
+
+    /// Returns the leaf holding a point equal to `point` and the position of its slot there.
+    fn find(&self, point: &Point2D<T>) -> Option<(usize, usize)> {
+        if !self.boundary().contains(point) {
+            return None;
+        }
+        let node = self.leaf_for(point);
+        let position = self.nodes[node]
+            .slots
+            .iter()
+            .position(|&slot| self.slab[slot].as_ref() == Some(point))?;
+        Some((node, position))
+    }
+
+    /// Splits a leaf into four children and moves its slots into them.
+    fn subdivide(&mut self, index: usize) {
+        hot_trace!("Subdividing SlabQuadtree node", index = index);
+        let first = self.nodes.len();
+        let depth = self.nodes[index].depth + 1;
+        for boundary in self.nodes[index].child_boundaries() {
+            self.nodes.push(SlabNode::leaf(boundary, depth));
+        }
+        let slots = std::mem::take(&mut self.nodes[index].slots);
+        self.nodes[index].children = Some(first);
+        for slot in slots {
+            let Some(point) = &self.slab[slot] else {
+                continue;
+            };
+            let child = first + self.nodes[index].digit(point);
+            self.nodes[child].slots.push(slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::EuclideanDistance;
+    use crate::quadtree::Quadtree;
+    use crate::testing::fixtures::{sorted_ids, uniform_2d};
+
+    const BOUNDARY: Rectangle = Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    /// A slab quadtree and a plain quadtree holding the same 500 random points.
+    fn trees() -> (Vec<Point2D<usize>>, SlabQuadtree<usize>, Quadtree<usize>) {
+        let points = uniform_2d(3501, &BOUNDARY, 500);
+        let mut slab = SlabQuadtree::new(&BOUNDARY, 4).unwrap();
+        let mut plain = Quadtree::new(&BOUNDARY, 4).unwrap();
+        assert_eq!(slab.insert_bulk(&points), 500);
+        plain.insert_bulk(&points);
+        (points, slab, plain)
+    }
+
+    #[test]
+    fn test_new_rejects_zero_capacity() {
+        assert!(matches!(
+            SlabQuadtree::<usize>::new(&BOUNDARY, 0),
+            Err(SpartError::InvalidCapacity { capacity: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_empty_tree_finds_nothing() {
+        let mut slab: SlabQuadtree<usize> = SlabQuadtree::new(&BOUNDARY, 4).unwrap();
+        let target = Point2D::new(50.0, 50.0, None);
+        assert!(slab.is_empty());
+        assert!(slab.range_search_bbox(&BOUNDARY).is_empty());
+        assert!(slab.knn_search::<EuclideanDistance>(&target, 3).is_empty());
+        assert!(!slab.delete(&Point2D::new(50.0, 50.0, Some(0))));
+    }
+
+    #[test]
+    fn test_insert_rejects_points_outside_boundary() {
+        let mut slab = SlabQuadtree::new(&BOUNDARY, 4).unwrap();
+        assert!(!slab.insert(Point2D::new(101.0, 0.0, Some(0))));
+        assert_eq!(slab.insert_bulk(&[Point2D::new(-1.0, 5.0, Some(1))]), 0);
+        assert!(slab.is_empty());
+    }
+
+    #[test]
+    fn test_points_on_boundary_are_found() {
+        let mut slab = SlabQuadtree::new(&BOUNDARY, 1).unwrap();
+        let corners = [(0.0, 0.0), (100.0, 0.0), (0.0, 100.0), (100.0, 100.0)];
+        for (i, (x, y)) in corners.into_iter().enumerate() {
+            assert!(slab.insert(Point2D::new(x, y, Some(i))));
+        }
+        assert_eq!(sorted_ids(slab.range_search_bbox(&BOUNDARY)), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_delete_missing_point_returns_false() {
+        let (points, mut slab, _) = trees();
+        assert!(slab.delete(&points[0]));
+        assert!(!slab.delete(&points[0]));
+        assert!(!slab.delete(&Point2D::new(50.0, 50.0, Some(1000))));
+        assert_eq!((slab.len(), slab.tombstones()), (499, 1));
+    }
+
+    #[test]
+    fn test_searches_match_quadtree_after_deletes() {
+        let (points, mut slab, mut plain) = trees();
+        for point in &points[..200] {
+            assert!(slab.delete(point));
+            assert!(plain.delete(point));
+        }
+        let query = Rectangle {
+            x: 20.0,
+            y: 10.0,
+            width: 35.0,
+            height: 60.0,
+        };
+        assert_eq!(
+            sorted_ids(slab.range_search_bbox(&query)),
+            sorted_ids(plain.range_search_bbox(&query))
+        );
+        let target = Point2D::new(40.0, 60.0, None);
+        assert_eq!(
+            sorted_ids(slab.range_search::<EuclideanDistance>(&target, 20.0)),
+            sorted_ids(plain.range_search::<EuclideanDistance>(&target, 20.0))
+        );
+        let distances = |ps: Vec<Point2D<usize>>| -> Vec<f64> {
+            ps.iter().map(|p| p.distance_sq(&target)).collect()
+        };
+        assert_eq!(
+            distances(slab.knn_search::<EuclideanDistance>(&target, 9)),
+            distances(plain.knn_search::<EuclideanDistance>(&target, 9))
+        );
+    }
+
+    #[test]
+    fn test_reinsert_reuses_tombstones() {
+        let (points, mut slab, _) = trees();
+        for point in &points[..200] {
+            slab.delete(point);
+        }
+        let slots = slab.slab.len();
+        assert_eq!(slab.insert_bulk(&points[..150]), 150);
+        assert_eq!((slab.slab.len(), slab.tombstones()), (slots, 50));
+        assert!(slab.contains(&points[0]));
+        assert_eq!(slab.iter().count(), 450);
+    }
+
+    #[test]
+    fn test_compact_drops_tombstones_and_nodes() {
+        let (points, mut slab, _) = trees();
+        let nodes = slab.node_count();
+        for point in &points[..400] {
+            slab.delete(point);
+        }
+        slab.compact();
+        assert_eq!((slab.len(), slab.tombstones()), (100, 0));
+        assert!(slab.node_count() < nodes);
+        assert_eq!(
+            sorted_ids(slab.range_search_bbox(&BOUNDARY)),
+            (400..500).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_duplicates_stop_splitting_at_max_depth() {
+        let mut dense = SlabQuadtree::new(&BOUNDARY, 1).unwrap();
+        for i in 0..5 {
+            assert!(dense.insert(Point2D::new(7.0, 7.0, Some(i))));
+        }
+        assert_eq!(dense.len(), 5);
+    }
+}
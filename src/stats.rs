@@ -0,0 +1,328 @@
+//! ## Statistics About Indexed Data
+//!
+//! This module defines the summary types returned by the statistics helpers of the trees,
+//! such as `NnDistanceStats` for the distribution of nearest-neighbor distances,
+//! `TreeStats` for the shape of a tree, `BalanceReport` for how its nodes and points are
+//! spread over its levels, and `BuildQuality` for how tightly the nodes of an R*-tree fit
+//! their entries. `TreeSummary` is the one-line description of a tree printed by its
+//! `Display` implementation, which is cheap to log compared to the `Debug` output of the whole
+//! tree.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::Point2D;
+//! use spart::kdtree::KdTree;
+//! use spart::random::SplitMix64;
+//!
+//! let mut tree = KdTree::new();
+//! for i in 0..100 {
+//!     tree.insert(Point2D::new(i as f64, 0.0, None::<()>)).unwrap();
+//! }
+//! let stats = tree.nn_distance_stats(50, &mut SplitMix64::new(0)).unwrap();
+//! // Every point of the grid has a neighbor at distance 1.
+//! assert_eq!(stats.min, 1.0);
+//! assert_eq!(stats.percentile(95.0), 1.0);
+//! ```
+
+use crate::geometry::BSPBounds;
+use std::fmt;
+
+/// Summary of the shape of a tree, as returned by the `stats` method of each tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeStats {
+    /// The number of stored points or objects.
+    pub size: usize,
+    /// The number of nodes, including the root.
+    pub node_count: usize,
+    /// The number of nodes without children.
+    pub leaf_count: usize,
+    /// The number of nodes on the longest path from the root to a leaf (0 for an empty
+    /// Kd-tree, which has no nodes).
+    pub depth: usize,
+    /// The fraction of the node capacity in use, over all nodes: the number of stored
+    /// entries divided by the node count times the capacity (or maximum number of entries).
+    /// `None` for Kd-trees, whose nodes hold exactly one point.
+    pub fill_factor: Option<f64>,
+}
+
+/// A concise description of a tree, as returned by the `summary` method of each tree and
+/// printed by its `Display` implementation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeSummary {
+    /// The name of the tree type, e.g., `"Quadtree"`.
+    pub kind: &'static str,
+    /// The number of stored points or objects.
+    pub size: usize,
+    /// The number of nodes on the longest path from the root to a leaf.
+    pub depth: usize,
+    /// The maximum number of points per node (or entries, for R-trees); `None` for Kd-trees.
+    pub capacity: Option<usize>,
+    /// The `(min, max)` bounds along each axis: the boundary of a quadtree or octree, and the
+    /// extent of the stored data for the other trees (empty if they hold no data).
+    pub bounds: Vec<(f64, f64)>,
+}
+
+impl TreeSummary {
+    /// Returns the `(min, max)` bounds along each axis of a bounding volume.
+    pub(crate) fn axis_bounds<B: BSPBounds>(volume: &B) -> Vec<(f64, f64)> {
+        (0..B::DIM)
+            .filter_map(|dim| {
+                let center = volume.center(dim).ok()?;
+                let half = volume.extent(dim).ok()? / 2.0;
+                Some((center - half, center + half))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for TreeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (points: {}, depth: {}",
+            self.kind, self.size, self.depth
+        )?;
+        if let Some(capacity) = self.capacity {
+            write!(f, ", capacity: {capacity}")?;
+        }
+        if self.bounds.is_empty() {
+            return write!(f, ", bounds: none)");
+        }
+        write!(f, ", bounds: ")?;
+        for (axis, (min, max)) in self.bounds.iter().enumerate() {
+            if axis > 0 {
+                write!(f, " x ")?;
+            }
+            write!(f, "[{min}, {max}]")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The nodes and points at one level of a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LevelStats {
+    /// The number of nodes at the level.
+    pub nodes: usize,
+    /// The number of nodes at the level without children.
+    pub leaves: usize,
+    /// The number of points stored in the nodes at the level.
+    pub points: usize,
+}
+
+/// A per-level histogram of the nodes and points of a space-partitioning tree, as returned
+/// by `Quadtree::balance_report` and `Octree::balance_report`.
+///
+/// Clustered data makes these trees subdivide deeply where the points are dense: a tree that
+/// is much deeper than a balanced tree holding the same points (see `imbalance`), or that
+/// keeps many points at deep levels, calls for a larger node capacity or for a tree that
+/// adapts to the data, such as a Kd-tree or an R*-tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceReport {
+    /// The statistics of each level, starting with the root at index 0.
+    pub levels: Vec<LevelStats>,
+    /// The maximum number of points per node before it subdivides.
+    pub capacity: usize,
+    /// The number of children of a divided node.
+    pub fanout: usize,
+}
+
+impl BalanceReport {
+    /// Returns the number of levels.
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the number of stored points.
+    pub fn size(&self) -> usize {
+        self.levels.iter().map(|level| level.points).sum()
+    }
+
+    /// Returns the number of levels a balanced tree with the same capacity and fanout needs
+    /// to hold the stored points (at least 1).
+    pub fn balanced_depth(&self) -> usize {
+        let mut depth = 1;
+        let mut room = self.capacity.max(1);
+        while room < self.size() {
+            room = room.saturating_mul(self.fanout.max(2));
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Returns the depth of the tree divided by its balanced depth: 1 for a balanced tree,
+    /// and more the more the tree is skewed towards dense regions.
+    pub fn imbalance(&self) -> f64 {
+        self.depth() as f64 / self.balanced_depth() as f64
+    }
+}
+
+/// Measures of how well the nodes of an R*-tree fit their entries, as returned by
+/// `RStarTree::quality_report` and `RStarTree::rebuild`.
+///
+/// Volumes are areas for 2D trees. Insertions and deletions gradually enlarge the nodes and
+/// make siblings overlap, so searches visit more nodes; comparing the ratios of a tree with
+/// those reported right after a rebuild tells whether rebuilding again is worthwhile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuildQuality {
+    /// The number of nodes, including the root.
+    pub node_count: usize,
+    /// The mean number of entries per node divided by the maximum number of entries.
+    pub average_fill: f64,
+    /// The total volume of the bounding volumes of all nodes.
+    pub node_volume: f64,
+    /// The total volume shared by pairs of sibling nodes.
+    pub overlap: f64,
+    /// The total volume of the nodes not covered by their entries, estimated as the volume of
+    /// each node minus the volumes of its entries (at least zero). Parts covered by several
+    /// entries are subtracted more than once, so this underestimates dead space in trees with
+    /// overlapping siblings.
+    pub dead_space: f64,
+}
+
+impl BuildQuality {
+    /// Returns the sibling overlap as a fraction of the total node volume (0 for an empty
+    /// tree).
+    pub fn overlap_ratio(&self) -> f64 {
+        ratio(self.overlap, self.node_volume)
+    }
+
+    /// Returns the dead space as a fraction of the total node volume (0 for an empty tree).
+    pub fn dead_space_ratio(&self) -> f64 {
+        ratio(self.dead_space, self.node_volume)
+    }
+}
+
+fn ratio(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 { part / whole } else { 0.0 }
+}
+
+/// Summary of the nearest-neighbor distances of a sample of the stored points.
+///
+/// For each sampled point, the distance to the nearest *other* stored point is measured
+/// (points stored more than once have a nearest-neighbor distance of zero). A percentile of
+/// these distances is a common starting value for the `epsilon` parameter of DBSCAN, and the
+/// typical spacing they show helps to pick node capacities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NnDistanceStats {
+    /// The number of sampled points.
+    pub count: usize,
+    /// The smallest nearest-neighbor distance.
+    pub min: f64,
+    /// The mean nearest-neighbor distance.
+    pub mean: f64,
+    /// The largest nearest-neighbor distance.
+    pub max: f64,
+    distances: Vec<f64>,
+}
+
+impl NnDistanceStats {
+    /// Builds the summary of a non-empty set of distances.
+    pub(crate) fn from_distances(mut distances: Vec<f64>) -> Option<Self> {
+        distances.retain(|d| !d.is_nan());
+        if distances.is_empty() {
+            return None;
+        }
+        distances.sort_by(f64::total_cmp);
+        let count = distances.len();
+        Some(NnDistanceStats {
+            count,
+            min: distances[0],
+            mean: distances.iter().sum::<f64>() / count as f64,
+            max: distances[count - 1],
+            distances,
+        })
+    }
+
+    /// Returns the `p`-th percentile of the nearest-neighbor distances.
+    ///
+    /// Values between two sampled distances are interpolated linearly, so `percentile(0.0)`
+    /// is `min`, `percentile(50.0)` is the median, and `percentile(100.0)` is `max`.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - The percentile, between 0 and 100 (values outside are clamped).
+    pub fn percentile(&self, p: f64) -> f64 {
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (self.count - 1) as f64;
+        let below = rank.floor() as usize;
+        let above = rank.ceil() as usize;
+        let fraction = rank - below as f64;
+        self.distances[below] + (self.distances[above] - self.distances[below]) * fraction
+    }
+
+    /// Returns the median nearest-neighbor distance.
+    pub fn median(&self) -> f64 {
+        self.percentile(50.0)
+    }
+
+    /// Returns the sampled nearest-neighbor distances in ascending order.
+    ///
+    /// Plotting them gives the sorted k-distance graph used to find the "knee" for DBSCAN.
+    pub fn distances(&self) -> &[f64] {
+        &self.distances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_interpolate_between_samples() {
+        let stats = NnDistanceStats::from_distances(vec![4.0, 1.0, 3.0, 2.0, f64::NAN]).unwrap();
+        assert_eq!(stats.count, 4);
+        assert_eq!((stats.min, stats.max, stats.mean), (1.0, 4.0, 2.5));
+        assert_eq!(stats.median(), 2.5);
+        assert_eq!(stats.percentile(0.0), 1.0);
+        assert_eq!(stats.percentile(100.0), 4.0);
+        assert_eq!(stats.percentile(200.0), 4.0);
+        assert!((stats.percentile(90.0) - 3.7).abs() < 1e-12);
+        assert_eq!(stats.distances(), &[1.0, 2.0, 3.0, 4.0]);
+        assert!(NnDistanceStats::from_distances(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_tree_summaries_display_one_line() {
+        use crate::geometry::{Point2D, Rectangle};
+        use crate::kdtree::KdTree;
+        use crate::quadtree::Quadtree;
+        use crate::rtree::RTree;
+
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 20.0,
+        };
+        let mut quadtree = Quadtree::new(&boundary, 4).unwrap();
+        assert_eq!(
+            quadtree.to_string(),
+            "Quadtree (points: 0, depth: 1, capacity: 4, bounds: [0, 10] x [0, 20])"
+        );
+        let mut kdtree = KdTree::new();
+        let mut rtree = RTree::new(4).unwrap();
+        assert_eq!(
+            rtree.to_string(),
+            "RTree (points: 0, depth: 1, capacity: 4, bounds: none)"
+        );
+        for i in 0..10 {
+            let point = Point2D::new(i as f64, 2.0 * i as f64, Some(i));
+            quadtree.insert(point.clone());
+            kdtree.insert(point.clone()).unwrap();
+            rtree.insert(point);
+        }
+
+        let summary = kdtree.summary();
+        assert_eq!(
+            (summary.kind, summary.size, summary.capacity),
+            ("KdTree", 10, None)
+        );
+        assert_eq!(summary.bounds, vec![(0.0, 9.0), (0.0, 18.0)]);
+        for (rtree, kdtree) in rtree.summary().bounds.iter().zip(&summary.bounds) {
+            assert!((rtree.0 - kdtree.0).abs() < 1e-6 && (rtree.1 - kdtree.1).abs() < 1e-6);
+        }
+        assert_eq!(quadtree.summary().depth, quadtree.depth());
+        assert!(!quadtree.to_string().contains('\n'));
+    }
+}
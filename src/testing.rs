@@ -0,0 +1,766 @@
+//! ## Testing Utilities
+//!
+//! This module provides the randomized checks Spart uses to test its trees, for applications
+//! that want to run the same checks against their own payload types and tree configurations:
+//!
+//! - `random_ops` generates a random sequence of insertions, deletions, kNN searches, and
+//!   range searches (`Op`) from a seed, so a failing sequence can always be reproduced.
+//! - `check_ops` applies a sequence to a tree and to a `LinearIndex`, which answers every
+//!   query by scanning all points, and reports the first step at which the two disagree or
+//!   the tree fails its `check_invariants`.
+//!
+//! Any tree can be checked by implementing `FuzzTarget` for it; the trees of this crate
+//! already implement it for 2D and 3D points.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::Rectangle;
+//! use spart::quadtree::Quadtree;
+//! use spart::random::{RandomSource, SplitMix64};
+//! use spart::testing::{OpConfig, check_ops, random_ops, random_point_2d};
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let config = OpConfig::new(200).with_max_radius(20.0);
+//! for seed in 0..10 {
+//!     let mut rng = SplitMix64::new(seed);
+//!     let ops = random_ops(&mut rng, &config, |rng| {
+//!         let label = format!("point {}", rng.next_below(1000));
+//!         random_point_2d(rng, &boundary, label)
+//!     });
+//!     let mut tree = Quadtree::new(&boundary, 4).unwrap();
+//!     if let Err(failure) = check_ops(&mut tree, &ops) {
+//!         panic!("seed {seed}: {failure}");
+//!     }
+//! }
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{Cube, DistanceMetric, EuclideanDistance, Point2D, Point3D, Rectangle};
+use crate::kdtree::{KdPoint, KdTree};
+use crate::linear::LinearIndex;
+use crate::octree::Octree;
+use crate::quadtree::Quadtree;
+use crate::random::RandomSource;
+use crate::rstar_tree::RStarTree;
+use crate::rtree::RTree;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+/// An operation on an index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op<P> {
+    /// Inserts the point.
+    Insert(P),
+    /// Deletes one point equal to the given one.
+    Delete(P),
+    /// Finds the `k` points nearest to `target`.
+    Knn {
+        /// The query point.
+        target: P,
+        /// The number of neighbors to find.
+        k: usize,
+    },
+    /// Finds all points within `radius` of `center`.
+    Range {
+        /// The query point.
+        center: P,
+        /// The search radius.
+        radius: f64,
+    },
+}
+
+/// An index that `check_ops` can compare against a `LinearIndex`.
+///
+/// Searches use the Euclidean distance.
+pub trait FuzzTarget {
+    /// The type of the points stored in the index.
+    type Point: Clone + PartialEq + fmt::Debug;
+
+    /// Inserts a point, returning whether it was stored.
+    fn fuzz_insert(&mut self, point: Self::Point) -> Result<bool, SpartError>;
+
+    /// Deletes one point equal to `point`, returning whether one was found.
+    fn fuzz_delete(&mut self, point: &Self::Point) -> bool;
+
+    /// Returns the `k` points nearest to `target`, in any order.
+    fn fuzz_knn(&self, target: &Self::Point, k: usize) -> Vec<Self::Point>;
+
+    /// Returns all points within `radius` of `center`, in any order.
+    fn fuzz_range(&self, center: &Self::Point, radius: f64) -> Vec<Self::Point>;
+
+    /// Returns the number of stored points.
+    fn fuzz_len(&self) -> usize;
+
+    /// Checks the structural invariants of the index.
+    fn fuzz_check_invariants(&self) -> Result<(), SpartError>;
+}
+
+impl<P> FuzzTarget for LinearIndex<P>
+where
+    P: Clone + PartialEq + fmt::Debug,
+    EuclideanDistance: DistanceMetric<P>,
+{
+    type Point = P;
+
+    fn fuzz_insert(&mut self, point: P) -> Result<bool, SpartError> {
+        self.insert(point);
+        Ok(true)
+    }
+
+    fn fuzz_delete(&mut self, point: &P) -> bool {
+        self.delete(point)
+    }
+
+    fn fuzz_knn(&self, target: &P, k: usize) -> Vec<P> {
+        self.knn_search::<EuclideanDistance>(target, k)
+    }
+
+    fn fuzz_range(&self, center: &P, radius: f64) -> Vec<P> {
+        self.range_search::<EuclideanDistance>(center, radius)
+    }
+
+    fn fuzz_len(&self) -> usize {
+        self.len()
+    }
+
+    fn fuzz_check_invariants(&self) -> Result<(), SpartError> {
+        Ok(())
+    }
+}
+
+/// The shape of the operation sequences generated by `random_ops`.
+#[derive(Debug, Clone)]
+pub struct OpConfig {
+    len: usize,
+    weights: [u32; 4],
+    max_k: usize,
+    max_radius: f64,
+}
+
+impl OpConfig {
+    /// Creates a configuration for sequences of `len` operations.
+    ///
+    /// By default, half of the operations are insertions and the rest are split evenly
+    /// between deletions, kNN searches, and range searches, with `k` up to 10 and radii up
+    /// to 10.
+    pub fn new(len: usize) -> Self {
+        OpConfig {
+            len,
+            weights: [3, 1, 1, 1],
+            max_k: 10,
+            max_radius: 10.0,
+        }
+    }
+
+    /// Sets the relative frequencies of insertions, deletions, kNN searches, and range
+    /// searches. If all are zero, only insertions are generated.
+    pub fn with_weights(mut self, insert: u32, delete: u32, knn: u32, range: u32) -> Self {
+        self.weights = [insert, delete, knn, range];
+        self
+    }
+
+    /// Sets the largest `k` of the generated kNN searches.
+    pub fn with_max_k(mut self, max_k: usize) -> Self {
+        self.max_k = max_k;
+        self
+    }
+
+    /// Sets the largest radius of the generated range searches.
+    pub fn with_max_radius(mut self, max_radius: f64) -> Self {
+        self.max_radius = max_radius;
+        self
+    }
+}
+
+/// Generates a random sequence of operations.
+///
+/// Most deletions target a point inserted earlier in the sequence (and not yet deleted), the
+/// others a fresh point that is usually not stored, so both outcomes of a deletion are
+/// exercised. Some insertions store another copy of a point that is still stored, so that
+/// deletions of one of several equal points are exercised as well.
+///
+/// # Arguments
+///
+/// * `rng` - The source of randomness; the same seed gives the same sequence.
+/// * `config` - The length and mix of the sequence.
+/// * `point` - Generates a random point, used for insertions, fresh deletions, and queries.
+///
+/// # Returns
+///
+/// The operations, in the order they should be applied.
+pub fn random_ops<P, R, F>(rng: &mut R, config: &OpConfig, mut point: F) -> Vec<Op<P>>
+where
+    P: Clone,
+    R: RandomSource,
+    F: FnMut(&mut R) -> P,
+{
+    let total: u32 = config.weights.iter().sum();
+    let mut live: Vec<P> = Vec::new();
+    let mut ops = Vec::with_capacity(config.len);
+    for _ in 0..config.len {
+        let mut pick = if total == 0 {
+            0
+        } else {
+            rng.next_below(total as usize) as u32
+        };
+        let kind = config
+            .weights
+            .iter()
+            .position(|&w| {
+                if pick < w {
+                    return true;
+                }
+                pick -= w;
+                false
+            })
+            .unwrap_or(0);
+        let op = match kind {
+            1 if !live.is_empty() && rng.next_below(4) != 0 => {
+                let pos = rng.next_below(live.len());
+                Op::Delete(live.swap_remove(pos))
+            }
+            1 => Op::Delete(point(rng)),
+            2 => Op::Knn {
+                target: point(rng),
+                k: rng.next_below(config.max_k + 1),
+            },
+            3 => Op::Range {
+                center: point(rng),
+                radius: rng.next_f64() * config.max_radius,
+            },
+            _ => {
+                let p = if !live.is_empty() && rng.next_below(5) == 0 {
+                    live[rng.next_below(live.len())].clone()
+                } else {
+                    point(rng)
+                };
+                live.push(p.clone());
+                Op::Insert(p)
+            }
+        };
+        ops.push(op);
+    }
+    ops
+}
+
+/// Returns a random point inside `boundary`.
+pub fn random_point_2d<T, R: RandomSource>(
+    rng: &mut R,
+    boundary: &Rectangle,
+    data: T,
+) -> Point2D<T> {
+    let x = boundary.x + rng.next_f64() * boundary.width;
+    let y = boundary.y + rng.next_f64() * boundary.height;
+    Point2D::new(x, y, Some(data))
+}
+
+/// Returns a random point inside `boundary`.
+pub fn random_point_3d<T, R: RandomSource>(rng: &mut R, boundary: &Cube, data: T) -> Point3D<T> {
+    let x = boundary.x + rng.next_f64() * boundary.width;
+    let y = boundary.y + rng.next_f64() * boundary.height;
+    let z = boundary.z + rng.next_f64() * boundary.depth;
+    Point3D::new(x, y, z, Some(data))
+}
+
+/// The first disagreement found by `check_ops`.
+#[derive(Debug, Clone)]
+pub struct FuzzFailure {
+    /// The index of the operation after which the disagreement was found.
+    pub step: usize,
+    /// A description of the operation and the disagreement.
+    pub reason: String,
+}
+
+impl fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {}: {}", self.step, self.reason)
+    }
+}
+
+impl Error for FuzzFailure {}
+
+/// Applies a sequence of operations to an index and to a `LinearIndex` as the reference,
+/// checking that they agree.
+///
+/// After every operation, the index must report the same number of points as the reference
+/// and pass its invariant check. Insertions and deletions must report the same outcome, and
+/// searches must return the same points, except that kNN searches may break ties between
+/// equally distant points differently.
+///
+/// # Arguments
+///
+/// * `index` - The index to check; usually empty, as the reference starts empty.
+/// * `ops` - The operations to apply, e.g., from `random_ops`.
+///
+/// # Errors
+///
+/// Returns a `FuzzFailure` describing the first disagreement.
+pub fn check_ops<I>(index: &mut I, ops: &[Op<I::Point>]) -> Result<(), FuzzFailure>
+where
+    I: FuzzTarget,
+    EuclideanDistance: DistanceMetric<I::Point>,
+{
+    let mut reference = LinearIndex::new();
+    for (step, op) in ops.iter().enumerate() {
+        let fail = |reason: String| FuzzFailure {
+            step,
+            reason: format!("{op:?}: {reason}"),
+        };
+        match op {
+            Op::Insert(point) => {
+                let stored = index
+                    .fuzz_insert(point.clone())
+                    .map_err(|e| fail(format!("insertion failed: {e}")))?;
+                if !stored {
+                    return Err(fail("the point was not stored".to_string()));
+                }
+                reference.insert(point.clone());
+            }
+            Op::Delete(point) => {
+                let expected = reference.fuzz_delete(point);
+                let actual = index.fuzz_delete(point);
+                if actual != expected {
+                    return Err(fail(format!(
+                        "deletion returned {actual}, expected {expected}"
+                    )));
+                }
+            }
+            Op::Knn { target, k } => {
+                let expected = reference.fuzz_knn(target, *k);
+                let actual = index.fuzz_knn(target, *k);
+                if distances(target, &actual) != distances(target, &expected)
+                    || !is_sub_multiset(&actual, reference.as_slice())
+                {
+                    return Err(fail(format!("found {actual:?}, expected {expected:?}")));
+                }
+            }
+            Op::Range { center, radius } => {
+                let expected = reference.fuzz_range(center, *radius);
+                let actual = index.fuzz_range(center, *radius);
+                if actual.len() != expected.len() || !is_sub_multiset(&actual, &expected) {
+                    return Err(fail(format!("found {actual:?}, expected {expected:?}")));
+                }
+            }
+        }
+        if index.fuzz_len() != reference.fuzz_len() {
+            return Err(fail(format!(
+                "the index holds {} points, expected {}",
+                index.fuzz_len(),
+                reference.fuzz_len()
+            )));
+        }
+        index
+            .fuzz_check_invariants()
+            .map_err(|e| fail(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Returns the sorted squared distances from `target` to `points`.
+fn distances<P>(target: &P, points: &[P]) -> Vec<f64>
+where
+    EuclideanDistance: DistanceMetric<P>,
+{
+    let mut distances: Vec<f64> = points
+        .iter()
+        .map(|p| EuclideanDistance::distance_sq(target, p))
+        .collect();
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    distances
+}
+
+/// Returns whether every point of `part` can be matched to a distinct point of `whole`.
+fn is_sub_multiset<P: PartialEq>(part: &[P], whole: &[P]) -> bool {
+    let mut used = vec![false; whole.len()];
+    part.iter().all(
+        |p| match (0..whole.len()).find(|&i| !used[i] && whole[i] == *p) {
+            Some(i) => {
+                used[i] = true;
+                true
+            }
+            None => false,
+        },
+    )
+}
+
+/// Returns whether `a` and `b` hold the same points, counting duplicates.
+///
+/// The points are sorted by the coordinates returned by `key`, so only points with equal
+/// coordinates are compared with each other.
+pub(crate) fn same_multiset<'a, P: PartialEq>(
+    a: Vec<&'a P>,
+    b: Vec<&'a P>,
+    key: impl Fn(&P) -> Vec<f64>,
+) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let a = sorted_by_coords(a, &key);
+    let b = sorted_by_coords(b, &key);
+    let mut start = 0;
+    while start < a.len() {
+        let coords = &a[start].0;
+        let same = |(c, _): &(Vec<f64>, &P)| compare_coords(c, coords).is_eq();
+        let end = start + a[start..].iter().take_while(|entry| same(entry)).count();
+        if !b[start..end].iter().all(same) || b.get(end).is_some_and(same) {
+            return false;
+        }
+        let group = |entries: &[(Vec<f64>, &'a P)]| entries.iter().map(|(_, p)| *p).collect();
+        let (mine, theirs): (Vec<&P>, Vec<&P>) = (group(&a[start..end]), group(&b[start..end]));
+        if !is_sub_multiset(&mine, &theirs) {
+            return false;
+        }
+        start = end;
+    }
+    true
+}
+
+fn sorted_by_coords<'a, P>(
+    points: Vec<&'a P>,
+    key: &impl Fn(&P) -> Vec<f64>,
+) -> Vec<(Vec<f64>, &'a P)> {
+    let mut keyed: Vec<(Vec<f64>, &P)> = points
+        .into_iter()
+        .map(|p| {
+            // `-0.0` and `0.0` (and all NaNs) are equal coordinates, so they must sort together.
+            let coords = key(p)
+                .into_iter()
+                .map(|c| if c.is_nan() { f64::NAN } else { c + 0.0 })
+                .collect();
+            (coords, p)
+        })
+        .collect();
+    keyed.sort_by(|(x, _), (y, _)| compare_coords(x, y));
+    keyed
+}
+
+fn compare_coords(a: &[f64], b: &[f64]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| x.total_cmp(y))
+        .find(|order| order.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+impl<T: Clone + PartialEq + fmt::Debug> FuzzTarget for Quadtree<T> {
+    type Point = Point2D<T>;
+
+    fn fuzz_insert(&mut self, point: Point2D<T>) -> Result<bool, SpartError> {
+        Ok(self.insert(point))
+    }
+
+    fn fuzz_delete(&mut self, point: &Point2D<T>) -> bool {
+        self.delete(point)
+    }
+
+    fn fuzz_knn(&self, target: &Point2D<T>, k: usize) -> Vec<Point2D<T>> {
+        self.knn_search::<EuclideanDistance>(target, k)
+    }
+
+    fn fuzz_range(&self, center: &Point2D<T>, radius: f64) -> Vec<Point2D<T>> {
+        self.range_search::<EuclideanDistance>(center, radius)
+    }
+
+    fn fuzz_len(&self) -> usize {
+        self.stats().size
+    }
+
+    fn fuzz_check_invariants(&self) -> Result<(), SpartError> {
+        self.check_invariants()
+    }
+}
+
+impl<T: Clone + PartialEq + fmt::Debug> FuzzTarget for Octree<T> {
+    type Point = Point3D<T>;
+
+    fn fuzz_insert(&mut self, point: Point3D<T>) -> Result<bool, SpartError> {
+        Ok(self.insert(point))
+    }
+
+    fn fuzz_delete(&mut self, point: &Point3D<T>) -> bool {
+        self.delete(point)
+    }
+
+    fn fuzz_knn(&self, target: &Point3D<T>, k: usize) -> Vec<Point3D<T>> {
+        self.knn_search::<EuclideanDistance>(target, k)
+    }
+
+    fn fuzz_range(&self, center: &Point3D<T>, radius: f64) -> Vec<Point3D<T>> {
+        self.range_search::<EuclideanDistance>(center, radius)
+    }
+
+    fn fuzz_len(&self) -> usize {
+        self.stats().size
+    }
+
+    fn fuzz_check_invariants(&self) -> Result<(), SpartError> {
+        self.check_invariants()
+    }
+}
+
+impl<P> FuzzTarget for KdTree<P>
+where
+    P: KdPoint,
+    EuclideanDistance: DistanceMetric<P>,
+{
+    type Point = P;
+
+    fn fuzz_insert(&mut self, point: P) -> Result<bool, SpartError> {
+        self.insert(point).map(|_| true)
+    }
+
+    fn fuzz_delete(&mut self, point: &P) -> bool {
+        self.delete(point)
+    }
+
+    fn fuzz_knn(&self, target: &P, k: usize) -> Vec<P> {
+        self.knn_search::<EuclideanDistance>(target, k)
+    }
+
+    fn fuzz_range(&self, center: &P, radius: f64) -> Vec<P> {
+        self.range_search::<EuclideanDistance>(center, radius)
+    }
+
+    fn fuzz_len(&self) -> usize {
+        self.stats().size
+    }
+
+    fn fuzz_check_invariants(&self) -> Result<(), SpartError> {
+        self.check_invariants()
+    }
+}
+
+/// Implements `FuzzTarget` for an R-tree variant over one point type.
+macro_rules! impl_fuzz_target_for_rtree {
+    ($tree:ident, $point:ident) => {
+        impl<T: Clone + PartialEq + fmt::Debug> FuzzTarget for $tree<$point<T>> {
+            type Point = $point<T>;
+
+            fn fuzz_insert(&mut self, point: $point<T>) -> Result<bool, SpartError> {
+                self.insert(point);
+                Ok(true)
+            }
+
+            fn fuzz_delete(&mut self, point: &$point<T>) -> bool {
+                self.delete(point)
+            }
+
+            fn fuzz_knn(&self, target: &$point<T>, k: usize) -> Vec<$point<T>> {
+                self.knn_search::<EuclideanDistance>(target, k)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            }
+
+            fn fuzz_range(&self, center: &$point<T>, radius: f64) -> Vec<$point<T>> {
+                self.range_search::<EuclideanDistance>(center, radius)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            }
+
+            fn fuzz_len(&self) -> usize {
+                self.stats().size
+            }
+
+            fn fuzz_check_invariants(&self) -> Result<(), SpartError> {
+                self.check_invariants()
+            }
+        }
+    };
+}
+
+impl_fuzz_target_for_rtree!(RTree, Point2D);
+impl_fuzz_target_for_rtree!(RTree, Point3D);
+impl_fuzz_target_for_rtree!(RStarTree, Point2D);
+impl_fuzz_target_for_rtree!(RStarTree, Point3D);
+
+/// Fixtures shared by the unit tests of the trees.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+    use crate::random::SplitMix64;
+
+    /// A point carrying an integer id as its data.
+    pub(crate) trait HasId {
+        fn id(&self) -> Option<usize>;
+    }
+
+    impl HasId for Point2D<usize> {
+        fn id(&self) -> Option<usize> {
+            self.data
+        }
+    }
+
+    impl HasId for Point3D<usize> {
+        fn id(&self) -> Option<usize> {
+            self.data
+        }
+    }
+
+    impl<P: HasId> HasId for &P {
+        fn id(&self) -> Option<usize> {
+            (*self).id()
+        }
+    }
+
+    /// Returns the ids of `points` in ascending order.
+    pub(crate) fn sorted_ids<P: HasId>(points: impl IntoIterator<Item = P>) -> Vec<usize> {
+        let mut ids: Vec<usize> = points.into_iter().filter_map(|p| p.id()).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Returns `n` points with ids `0..n` in a few tiny clusters, far apart from each other
+    /// inside a 1000 by 1000 square at the origin.
+    pub(crate) fn clustered(n: usize) -> Vec<Point2D<usize>> {
+        let centers = [(1.0, 1.0), (700.3, 12.5), (333.3, 901.0), (998.0, 998.0)];
+        let mut rng = SplitMix64::new(7);
+        (0..n)
+            .map(|i| {
+                let (cx, cy) = centers[i % centers.len()];
+                let dx = rng.next_below(1000) as f64 * 1e-6;
+                let dy = rng.next_below(1000) as f64 * 1e-6;
+                Point2D::new(cx + dx, cy + dy, Some(i))
+            })
+            .collect()
+    }
+
+    /// Returns `n` random points with ids `0..n` inside `boundary`.
+    pub(crate) fn uniform_2d(seed: u64, boundary: &Rectangle, n: usize) -> Vec<Point2D<usize>> {
+        let mut rng = SplitMix64::new(seed);
+        (0..n)
+            .map(|i| random_point_2d(&mut rng, boundary, i))
+            .collect()
+    }
+
+    /// Returns `n` random points with ids `0..n` inside `boundary`.
+    pub(crate) fn uniform_3d(seed: u64, boundary: &Cube, n: usize) -> Vec<Point3D<usize>> {
+        let mut rng = SplitMix64::new(seed);
+        (0..n)
+            .map(|i| random_point_3d(&mut rng, boundary, i))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::SplitMix64;
+
+    const BOUNDARY: Rectangle = Rectangle {
+        x: -50.0,
+        y: -50.0,
+        width: 100.0,
+        height: 100.0,
+    };
+    const CUBE: Cube = Cube {
+        x: -50.0,
+        y: -50.0,
+        z: -50.0,
+        width: 100.0,
+        height: 100.0,
+        depth: 100.0,
+    };
+
+    fn ops_2d(seed: u64) -> Vec<Op<Point2D<u32>>> {
+        let mut rng = SplitMix64::new(seed);
+        let config = OpConfig::new(300).with_max_radius(25.0);
+        // Few distinct payloads, so that deletions of equal points are exercised.
+        random_ops(&mut rng, &config, |rng| {
+            let data = rng.next_below(4) as u32;
+            random_point_2d(rng, &BOUNDARY, data)
+        })
+    }
+
+    fn ops_3d(seed: u64) -> Vec<Op<Point3D<u32>>> {
+        let mut rng = SplitMix64::new(seed);
+        let config = OpConfig::new(300).with_max_radius(25.0);
+        random_ops(&mut rng, &config, |rng| {
+            let data = rng.next_below(4) as u32;
+            random_point_3d(rng, &CUBE, data)
+        })
+    }
+
+    #[test]
+    fn test_all_trees_agree_with_brute_force() {
+        for seed in 0..8 {
+            let ops = ops_2d(seed);
+            check_ops(&mut Quadtree::new(&BOUNDARY, 3).unwrap(), &ops).unwrap();
+            check_ops(&mut KdTree::<Point2D<u32>>::new(), &ops).unwrap();
+            check_ops(&mut RTree::<Point2D<u32>>::new(4).unwrap(), &ops).unwrap();
+            check_ops(&mut RStarTree::<Point2D<u32>>::new(4).unwrap(), &ops).unwrap();
+
+            let ops = ops_3d(seed);
+            check_ops(&mut Octree::new(&CUBE, 3).unwrap(), &ops).unwrap();
+            check_ops(&mut KdTree::<Point3D<u32>>::new(), &ops).unwrap();
+            check_ops(&mut RTree::<Point3D<u32>>::new(4).unwrap(), &ops).unwrap();
+            check_ops(&mut RStarTree::<Point3D<u32>>::new(4).unwrap(), &ops).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_content_eq_ignores_insertion_order_and_structure() {
+        let mut rng = SplitMix64::new(11);
+        let mut points: Vec<_> = (0..200)
+            .map(|_| {
+                let data = rng.next_below(3) as u32;
+                random_point_2d(&mut rng, &BOUNDARY, data)
+            })
+            .collect();
+        // Duplicates, and coordinates shared by points with different payloads.
+        points.extend(points[..20].to_vec());
+        points.push(Point2D::new(0.0, 0.0, Some(1)));
+        points.push(Point2D::new(-0.0, 0.0, Some(2)));
+        let mut reversed = points.clone();
+        reversed.reverse();
+
+        let mut a = Quadtree::new(&BOUNDARY, 3).unwrap();
+        let mut b = Quadtree::new(&BOUNDARY, 8).unwrap();
+        let mut c = RTree::new(4).unwrap();
+        let mut d = RTree::new(9).unwrap();
+        let mut e = KdTree::new();
+        let mut f = KdTree::new();
+        for (p, q) in points.iter().zip(&reversed) {
+            a.insert(p.clone());
+            b.insert(q.clone());
+            c.insert(p.clone());
+            d.insert(q.clone());
+            e.insert(p.clone()).unwrap();
+            f.insert(q.clone()).unwrap();
+        }
+        assert!(a.content_eq(&b) && c.content_eq(&d) && e.content_eq(&f));
+
+        // One copy of a duplicated point less is a different multiset.
+        let removed = points[0].clone();
+        assert!(a.delete(&removed) && c.delete(&removed) && e.delete(&removed));
+        assert!(!a.content_eq(&b) && !c.content_eq(&d) && !e.content_eq(&f));
+        let swapped = Point2D::new(removed.x, removed.y, Some(3));
+        a.insert(swapped.clone());
+        c.insert(swapped.clone());
+        e.insert(swapped).unwrap();
+        assert!(!a.content_eq(&b) && !c.content_eq(&d) && !e.content_eq(&f));
+        a.insert(removed);
+        assert!(!a.content_eq(&b));
+        assert!(a.clone().content_eq(&a));
+    }
+
+    #[test]
+    fn test_random_ops_is_reproducible() {
+        assert_eq!(ops_2d(7), ops_2d(7));
+        assert_ne!(ops_2d(7), ops_2d(8));
+    }
+
+    #[test]
+    fn test_check_ops_reports_first_disagreement() {
+        // A quadtree rejects points outside its boundary, which the reference stores.
+        let inside = Point2D::new(1.0, 1.0, Some(0));
+        let outside = Point2D::new(100.0, 100.0, Some(0));
+        let ops = vec![Op::Insert(inside), Op::Insert(outside)];
+        let failure = check_ops(&mut Quadtree::new(&BOUNDARY, 3).unwrap(), &ops).unwrap_err();
+        assert_eq!(failure.step, 1);
+        assert!(failure.to_string().starts_with("step 1: Insert"));
+    }
+}
@@ -0,0 +1,884 @@
+//! ## TPR-tree for Moving Objects
+//!
+//! This module implements a time-parameterized R-tree (TPR-tree), which indexes 2D points
+//! moving at constant velocities and answers predictive queries such as "which vehicles will
+//! be inside this box at time `t`" or "... at some moment between `t1` and `t2`".
+//!
+//! Instead of fixed rectangles, every node is bounded by a rectangle whose edges move: at the
+//! tree's reference time it covers the positions of all points below it, and its edges move
+//! at the smallest and largest velocities of those points, so it keeps covering them at any
+//! later (or earlier) time. The bounds grow as time moves away from the reference time, so
+//! long-lived trees should be re-tightened now and then with `TprTree::tighten`.
+//!
+//! Insertion chooses subtrees and splits nodes so as to keep the mean area of the bounding
+//! rectangles small over the next `horizon` time units, following the original TPR-tree.
+//! Deletion and invariant checks reuse the algorithms of `rtree_common`.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::Rectangle;
+//! use spart::tpr_tree::{MovingPoint, TprTree};
+//!
+//! let mut tree = TprTree::new(8).unwrap();
+//! // A truck at (0, 0) at time 0, driving east at 10 units per time unit.
+//! tree.insert(MovingPoint::new(0.0, 0.0, 10.0, 0.0, 0.0, Some("truck")));
+//! // A van at (100, 50) at time 0, parked.
+//! tree.insert(MovingPoint::new(100.0, 50.0, 0.0, 0.0, 0.0, Some("van")));
+//!
+//! let depot = Rectangle { x: 90.0, y: -5.0, width: 20.0, height: 10.0 };
+//! assert!(tree.range_search_at(&depot, 0.0).is_empty());
+//! let at_depot = tree.range_search_at(&depot, 10.0);
+//! assert_eq!(at_depot[0].data, Some("truck"));
+//!
+//! let lot = Rectangle { x: 0.0, y: 0.0, width: 200.0, height: 100.0 };
+//! assert_eq!(tree.range_search_during(&lot, 0.0, 30.0).len(), 2);
+//! ```
+
+use ordered_float::OrderedFloat;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::errors::SpartError;
+use crate::geometry::{BSPBounds, BoundingVolume, Rectangle};
+use crate::logging::hot_trace;
+use crate::rtree_common::{
+    EntryAccess, NodeAccess, check_tree, compute_group_mbr, delete_entry, entry_height,
+    node_height, refit_node,
+};
+
+/// The default length of the time window that insertion optimizes the tree for.
+pub const DEFAULT_HORIZON: f64 = 60.0;
+
+/// A 2D point moving at a constant velocity, with an optional payload.
+///
+/// The point is at (`x`, `y`) at time `time`, and at
+/// (`x + vx * (t - time)`, `y + vy * (t - time)`) at any time `t`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MovingPoint<T> {
+    /// The x-coordinate of the point at `time`.
+    pub x: f64,
+    /// The y-coordinate of the point at `time`.
+    pub y: f64,
+    /// The velocity along the x-axis.
+    pub vx: f64,
+    /// The velocity along the y-axis.
+    pub vy: f64,
+    /// The time at which the point is at (`x`, `y`).
+    pub time: f64,
+    /// Optional associated data.
+    pub data: Option<T>,
+}
+
+impl<T: PartialEq> PartialEq for MovingPoint<T> {
+    fn eq(&self, other: &Self) -> bool {
+        [self.x, self.y, self.vx, self.vy, self.time]
+            .iter()
+            .zip([other.x, other.y, other.vx, other.vy, other.time])
+            .all(|(a, b)| OrderedFloat(*a) == OrderedFloat(b))
+            && self.data == other.data
+    }
+}
+
+impl<T> MovingPoint<T> {
+    /// Creates a new `MovingPoint`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x-coordinate at `time`.
+    /// * `y` - The y-coordinate at `time`.
+    /// * `vx` - The velocity along the x-axis.
+    /// * `vy` - The velocity along the y-axis.
+    /// * `time` - The time at which the point is at (`x`, `y`).
+    /// * `data` - Optional data associated with the point.
+    pub fn new(x: f64, y: f64, vx: f64, vy: f64, time: f64, data: Option<T>) -> Self {
+        MovingPoint {
+            x,
+            y,
+            vx,
+            vy,
+            time,
+            data,
+        }
+    }
+
+    /// Returns the position of the point at time `t`, as `[x, y]`.
+    pub fn position_at(&self, t: f64) -> [f64; 2] {
+        let dt = t - self.time;
+        [self.x + self.vx * dt, self.y + self.vy * dt]
+    }
+
+    /// Returns `true` if the point is inside `rect` (boundary included) at some time in
+    /// `t1..=t2`.
+    fn inside_during(&self, rect: &Rectangle, t1: f64, t2: f64) -> bool {
+        let (mut from, mut to) = (t1, t2);
+        for (p, v, lo, hi) in [
+            (self.x, self.vx, rect.x, rect.x + rect.width),
+            (self.y, self.vy, rect.y, rect.y + rect.height),
+        ] {
+            if v == 0.0 {
+                if p < lo || p > hi {
+                    return false;
+                }
+                continue;
+            }
+            // The times at which the coordinate reaches either side of the rectangle.
+            let (a, b) = (self.time + (lo - p) / v, self.time + (hi - p) / v);
+            from = from.max(a.min(b));
+            to = to.min(a.max(b));
+        }
+        from <= to
+    }
+}
+
+/// A time-parameterized bounding rectangle: the box covering some moving points at the tree's
+/// reference time, with edges moving at the extreme velocities of those points.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Tpbr {
+    min: [f64; 2],
+    max: [f64; 2],
+    vmin: [f64; 2],
+    vmax: [f64; 2],
+}
+
+impl Tpbr {
+    /// Returns the bounds of `point`, whose box has no extent, at the reference time.
+    fn of_point<T>(point: &MovingPoint<T>, reference_time: f64) -> Tpbr {
+        let position = point.position_at(reference_time);
+        let velocity = [point.vx, point.vy];
+        Tpbr {
+            min: position,
+            max: position,
+            vmin: velocity,
+            vmax: velocity,
+        }
+    }
+
+    /// Returns the smallest and largest corner of the box `dt` time units after the reference
+    /// time (before it for a negative `dt`).
+    ///
+    /// Before the reference time, the edges move backwards, so the lower edge follows the
+    /// largest velocity and the upper edge the smallest one.
+    fn corners_at(&self, dt: f64) -> ([f64; 2], [f64; 2]) {
+        let lo = std::array::from_fn(|axis| {
+            self.min[axis] + (self.vmin[axis] * dt).min(self.vmax[axis] * dt)
+        });
+        let hi = std::array::from_fn(|axis| {
+            self.max[axis] + (self.vmin[axis] * dt).max(self.vmax[axis] * dt)
+        });
+        (lo, hi)
+    }
+
+    /// Returns the smallest and largest corner of the region the box sweeps between `dt1` and
+    /// `dt2` time units after the reference time.
+    ///
+    /// The lower edges are concave in time and the upper edges convex, so their extremes over
+    /// an interval are reached at its ends.
+    fn swept_corners(&self, dt1: f64, dt2: f64) -> ([f64; 2], [f64; 2]) {
+        let (lo1, hi1) = self.corners_at(dt1);
+        let (lo2, hi2) = self.corners_at(dt2);
+        (
+            std::array::from_fn(|axis| lo1[axis].min(lo2[axis])),
+            std::array::from_fn(|axis| hi1[axis].max(hi2[axis])),
+        )
+    }
+
+    /// Returns the mean area of the box over the `horizon` time units after the reference
+    /// time, or its area at the reference time for a zero horizon.
+    fn mean_area(&self, horizon: f64) -> f64 {
+        let (a0, a1) = (self.max[0] - self.min[0], self.max[1] - self.min[1]);
+        let (b0, b1) = (self.vmax[0] - self.vmin[0], self.vmax[1] - self.vmin[1]);
+        a0 * a1 + (a0 * b1 + a1 * b0) * horizon / 2.0 + b0 * b1 * horizon * horizon / 3.0
+    }
+}
+
+/// Returns `true` if the boxes `lo..=hi` and `rect` touch or overlap.
+fn overlaps_rect((lo, hi): ([f64; 2], [f64; 2]), rect: &Rectangle) -> bool {
+    lo[0] <= rect.x + rect.width
+        && rect.x <= hi[0]
+        && lo[1] <= rect.y + rect.height
+        && rect.y <= hi[1]
+}
+
+impl BoundingVolume for Tpbr {
+    /// The area of the box at the reference time.
+    fn area(&self) -> f64 {
+        self.mean_area(0.0)
+    }
+    fn union(&self, other: &Self) -> Self {
+        Tpbr {
+            min: std::array::from_fn(|axis| self.min[axis].min(other.min[axis])),
+            max: std::array::from_fn(|axis| self.max[axis].max(other.max[axis])),
+            vmin: std::array::from_fn(|axis| self.vmin[axis].min(other.vmin[axis])),
+            vmax: std::array::from_fn(|axis| self.vmax[axis].max(other.vmax[axis])),
+        }
+    }
+    /// Whether the boxes at the reference time and the velocity ranges both intersect, as they
+    /// do when one bounds the other.
+    fn intersects(&self, other: &Self) -> bool {
+        (0..2).all(|axis| {
+            self.min[axis] <= other.max[axis]
+                && other.min[axis] <= self.max[axis]
+                && self.vmin[axis] <= other.vmax[axis]
+                && other.vmin[axis] <= self.vmax[axis]
+        })
+    }
+    fn overlap(&self, other: &Self) -> f64 {
+        (0..2)
+            .map(|axis| {
+                (self.max[axis].min(other.max[axis]) - self.min[axis].max(other.min[axis])).max(0.0)
+            })
+            .product()
+    }
+    fn margin(&self) -> f64 {
+        2.0 * (self.max[0] - self.min[0] + self.max[1] - self.min[1])
+    }
+}
+
+/// The position bounds at the reference time are dimensions 0 and 1, and the velocity bounds
+/// dimensions 2 and 3, so that `check_tree` verifies both.
+impl BSPBounds for Tpbr {
+    const DIM: usize = 4;
+    fn center(&self, dim: usize) -> Result<f64, SpartError> {
+        match dim {
+            0 | 1 => Ok((self.min[dim] + self.max[dim]) / 2.0),
+            2 | 3 => Ok((self.vmin[dim - 2] + self.vmax[dim - 2]) / 2.0),
+            _ => Err(SpartError::InvalidDimension {
+                requested: dim,
+                available: 4,
+            }),
+        }
+    }
+    fn extent(&self, dim: usize) -> Result<f64, SpartError> {
+        match dim {
+            0 | 1 => Ok(self.max[dim] - self.min[dim]),
+            2 | 3 => Ok(self.vmax[dim - 2] - self.vmin[dim - 2]),
+            _ => Err(SpartError::InvalidDimension {
+                requested: dim,
+                available: 4,
+            }),
+        }
+    }
+}
+
+/// An entry in a TPR-tree node: a moving point or a child node, with its bounds.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum TprEntry<T> {
+    Leaf { mbr: Tpbr, point: MovingPoint<T> },
+    Node { mbr: Tpbr, child: Box<TprNode<T>> },
+}
+
+impl<T> EntryAccess for TprEntry<T> {
+    type BV = Tpbr;
+    type Node = TprNode<T>;
+    type Obj = MovingPoint<T>;
+
+    fn mbr(&self) -> &Tpbr {
+        match self {
+            TprEntry::Leaf { mbr, .. } | TprEntry::Node { mbr, .. } => mbr,
+        }
+    }
+
+    fn as_leaf_obj(&self) -> Option<&MovingPoint<T>> {
+        match self {
+            TprEntry::Leaf { point, .. } => Some(point),
+            TprEntry::Node { .. } => None,
+        }
+    }
+
+    fn as_leaf_mut(&mut self) -> Option<(&mut Tpbr, &mut MovingPoint<T>)> {
+        match self {
+            TprEntry::Leaf { mbr, point } => Some((mbr, point)),
+            TprEntry::Node { .. } => None,
+        }
+    }
+
+    fn child(&self) -> Option<&TprNode<T>> {
+        match self {
+            TprEntry::Node { child, .. } => Some(child),
+            TprEntry::Leaf { .. } => None,
+        }
+    }
+
+    fn child_mut(&mut self) -> Option<&mut TprNode<T>> {
+        match self {
+            TprEntry::Node { child, .. } => Some(child),
+            TprEntry::Leaf { .. } => None,
+        }
+    }
+
+    fn set_mbr(&mut self, new_mbr: Tpbr) {
+        if let TprEntry::Node { mbr, .. } = self {
+            *mbr = new_mbr;
+        }
+    }
+
+    fn into_child(self) -> Option<Box<TprNode<T>>> {
+        match self {
+            TprEntry::Node { child, .. } => Some(child),
+            TprEntry::Leaf { .. } => None,
+        }
+    }
+}
+
+/// A node of a TPR-tree.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct TprNode<T> {
+    entries: Vec<TprEntry<T>>,
+    is_leaf: bool,
+}
+
+impl<T> NodeAccess for TprNode<T> {
+    type Entry = TprEntry<T>;
+
+    fn is_leaf(&self) -> bool {
+        self.is_leaf
+    }
+    fn entries(&self) -> &Vec<TprEntry<T>> {
+        &self.entries
+    }
+    fn entries_mut(&mut self) -> &mut Vec<TprEntry<T>> {
+        &mut self.entries
+    }
+}
+
+/// A time-parameterized R-tree indexing 2D points that move at constant velocities.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TprTree<T> {
+    root: TprNode<T>,
+    max_entries: usize,
+    min_entries: usize,
+    reference_time: f64,
+    horizon: f64,
+    len: usize,
+}
+
+impl<T> TprTree<T> {
+    /// Creates a new TPR-tree with the specified maximum number of entries per node, the
+    /// reference time 0, and a horizon of `DEFAULT_HORIZON` time units.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_entries` - The maximum number of entries allowed in a node.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `max_entries` is less than 2.
+    pub fn new(max_entries: usize) -> Result<Self, SpartError> {
+        if max_entries < 2 {
+            return Err(SpartError::InvalidCapacity {
+                capacity: max_entries,
+            });
+        }
+        info!("Creating new TprTree with max_entries: {}", max_entries);
+        Ok(TprTree {
+            root: TprNode {
+                entries: Vec::new(),
+                is_leaf: true,
+            },
+            max_entries,
+            min_entries: (max_entries as f64 * 0.4).ceil() as usize,
+            reference_time: 0.0,
+            horizon: DEFAULT_HORIZON,
+            len: 0,
+        })
+    }
+
+    /// Sets the length of the time window, starting at the reference time, over which
+    /// insertion keeps the bounding rectangles small. Negative values are treated as zero.
+    ///
+    /// It should match how far ahead queries look before the tree is next re-tightened.
+    ///
+    /// # Arguments
+    ///
+    /// * `horizon` - The length of the time window.
+    pub fn with_horizon(mut self, horizon: f64) -> Self {
+        self.horizon = horizon.max(0.0);
+        self
+    }
+
+    /// Sets the reference time of the tree, as `tighten` does.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The new reference time.
+    pub fn with_reference_time(mut self, time: f64) -> Self {
+        self.tighten(time);
+        self
+    }
+
+    /// Returns the reference time, at which the bounding rectangles are tightest.
+    pub fn reference_time(&self) -> f64 {
+        self.reference_time
+    }
+
+    /// Returns the length of the time window insertion optimizes the tree for.
+    pub fn horizon(&self) -> f64 {
+        self.horizon
+    }
+
+    /// Returns the number of points in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree holds no points.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the height of the tree: 1 while the root is a leaf node.
+    pub fn height(&self) -> usize {
+        node_height(&self.root)
+    }
+
+    /// Checks the structural invariants of the tree: node sizes, leaf depths, and that the
+    /// bounds of every node entry (positions at the reference time and velocities) enclose
+    /// the entries of its child.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvariantViolation` describing the first violation found.
+    pub fn check_invariants(&self) -> Result<(), SpartError> {
+        check_tree(&self.root, self.max_entries, self.min_entries)
+    }
+
+    /// Moves the reference time to `time` and recomputes all bounding rectangles from the
+    /// points, without restructuring the tree.
+    ///
+    /// The bounds of a node only grow as time moves away from the reference time, so queries
+    /// slow down over time; moving the reference time close to the times being queried makes
+    /// them tight again.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The new reference time, typically the current time.
+    pub fn tighten(&mut self, time: f64) {
+        info!("Tightening TprTree bounds at time {}", time);
+        self.reference_time = time;
+        refit_node(&mut self.root, &mut |point: &mut MovingPoint<T>| {
+            Tpbr::of_point(point, time)
+        });
+    }
+
+    /// Inserts a moving point into the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to insert.
+    pub fn insert(&mut self, point: MovingPoint<T>) {
+        hot_trace!("Inserting point into TprTree");
+        let entry = TprEntry::Leaf {
+            mbr: Tpbr::of_point(&point, self.reference_time),
+            point,
+        };
+        self.insert_entry(entry);
+        self.len += 1;
+    }
+
+    /// Inserts an entry at the height it belongs to and splits the root if it overflows.
+    fn insert_entry(&mut self, entry: TprEntry<T>) {
+        let target_height = entry_height(&entry) + 1;
+        let root_height = node_height(&self.root);
+        let limits = (self.max_entries, self.min_entries, self.horizon);
+        insert_rec(&mut self.root, entry, root_height, target_height, limits);
+        if self.root.entries.len() > self.max_entries {
+            let entries = std::mem::take(&mut self.root.entries);
+            let (group1, group2) = split_entries(entries, self.min_entries, self.horizon);
+            let is_leaf = self.root.is_leaf;
+            self.root.is_leaf = false;
+            self.root.entries = vec![node_entry(group1, is_leaf), node_entry(group2, is_leaf)];
+        }
+    }
+
+    /// Finds the points that are inside `rect` (boundary included) at time `t`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` - The query rectangle.
+    /// * `t` - The time of the query.
+    ///
+    /// # Returns
+    ///
+    /// References to the points found.
+    pub fn range_search_at(&self, rect: &Rectangle, t: f64) -> Vec<&MovingPoint<T>> {
+        self.range_search_during(rect, t, t)
+    }
+
+    /// Finds the points that are inside `rect` (boundary included) at some time between `t1`
+    /// and `t2`, e.g., the vehicles that will pass through an area within the next hour.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` - The query rectangle.
+    /// * `t1` - The start of the time interval.
+    /// * `t2` - The end of the time interval. Nothing is found if it is before `t1`.
+    ///
+    /// # Returns
+    ///
+    /// References to the points found.
+    pub fn range_search_during(&self, rect: &Rectangle, t1: f64, t2: f64) -> Vec<&MovingPoint<T>> {
+        let mut found = Vec::new();
+        if t1 <= t2 {
+            let (dt1, dt2) = (t1 - self.reference_time, t2 - self.reference_time);
+            search_rec(&self.root, rect, (t1, t2), (dt1, dt2), &mut found);
+        }
+        found
+    }
+}
+
+impl<T: PartialEq> TprTree<T> {
+    /// Deletes a point from the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point to delete, with the same position, velocity, time, and data as
+    ///   when it was inserted.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point was found and removed.
+    pub fn delete(&mut self, point: &MovingPoint<T>) -> bool {
+        let mbr = Tpbr::of_point(point, self.reference_time);
+        let mut reinsert_list = Vec::new();
+        let deleted = delete_entry(
+            &mut self.root,
+            point,
+            &mbr,
+            self.min_entries,
+            &mut reinsert_list,
+        );
+        if deleted {
+            self.len -= 1;
+            for entry in reinsert_list {
+                self.insert_entry(entry);
+            }
+            if !self.root.is_leaf && self.root.entries.len() == 1 {
+                if let Some(TprEntry::Node { child, .. }) = self.root.entries.pop() {
+                    self.root = *child;
+                }
+            }
+        }
+        deleted
+    }
+
+    /// Replaces `old` with `new`, e.g., when a vehicle reports a new position or velocity.
+    ///
+    /// # Returns
+    ///
+    /// `true` if `old` was found; `new` is only inserted in that case.
+    pub fn update(&mut self, old: &MovingPoint<T>, new: MovingPoint<T>) -> bool {
+        let found = self.delete(old);
+        if found {
+            self.insert(new);
+        }
+        found
+    }
+}
+
+/// Wraps a group of entries into a node entry.
+fn node_entry<T>(entries: Vec<TprEntry<T>>, is_leaf: bool) -> TprEntry<T> {
+    let mbr =
+        compute_group_mbr(&entries).unwrap_or_else(|| unreachable!("split groups are never empty"));
+    TprEntry::Node {
+        mbr,
+        child: Box::new(TprNode { entries, is_leaf }),
+    }
+}
+
+/// Descends to the node of height `target_height` (a leaf for points) and adds `entry` there,
+/// splitting the nodes that overflow on the way back.
+///
+/// At each level the child whose mean area over the horizon grows least is chosen, then the
+/// one with the smallest mean area, then the first one.
+fn insert_rec<T>(
+    node: &mut TprNode<T>,
+    entry: TprEntry<T>,
+    height: usize,
+    target_height: usize,
+    (max_entries, min_entries, horizon): (usize, usize, f64),
+) {
+    if node.is_leaf || height <= target_height {
+        node.entries.push(entry);
+        return;
+    }
+    let cost = |mbr: &Tpbr| {
+        let area = mbr.mean_area(horizon);
+        (mbr.union(entry.mbr()).mean_area(horizon) - area, area)
+    };
+    let Some(best) = (0..node.entries.len()).min_by(|&a, &b| {
+        let (ca, cb) = (cost(node.entries[a].mbr()), cost(node.entries[b].mbr()));
+        ca.0.total_cmp(&cb.0).then(ca.1.total_cmp(&cb.1))
+    }) else {
+        node.entries.push(entry);
+        return;
+    };
+    let mut sibling = None;
+    if let TprEntry::Node { mbr, child } = &mut node.entries[best] {
+        insert_rec(
+            child,
+            entry,
+            height - 1,
+            target_height,
+            (max_entries, min_entries, horizon),
+        );
+        if child.entries.len() > max_entries {
+            let entries = std::mem::take(&mut child.entries);
+            let (group1, group2) = split_entries(entries, min_entries, horizon);
+            child.entries = group1;
+            sibling = Some(node_entry(group2, child.is_leaf));
+        }
+        if let Some(new_mbr) = compute_group_mbr(&child.entries) {
+            *mbr = new_mbr;
+        }
+    }
+    if let Some(sibling) = sibling {
+        node.entries.push(sibling);
+    }
+}
+
+/// Splits the entries of an overflowing node into two groups of at least `min_entries` each.
+///
+/// The entries are sorted along each of the four dimensions (the lower position bounds at the
+/// reference time, and the lower velocity bounds), and the cut with the smallest total mean
+/// area of the two groups over the horizon is chosen.
+fn split_entries<T>(
+    mut entries: Vec<TprEntry<T>>,
+    min_entries: usize,
+    horizon: f64,
+) -> (Vec<TprEntry<T>>, Vec<TprEntry<T>>) {
+    let n = entries.len();
+    let min_entries = min_entries.clamp(1, n / 2);
+    let key = |entry: &TprEntry<T>, dim: usize| {
+        let mbr = entry.mbr();
+        if dim < 2 {
+            mbr.min[dim]
+        } else {
+            mbr.vmin[dim - 2]
+        }
+    };
+    let mut best: Option<(f64, usize, usize)> = None;
+    for dim in 0..4 {
+        entries.sort_by(|a, b| key(a, dim).total_cmp(&key(b, dim)));
+        // The bounds of the first `i` entries, and of the last `n - i`.
+        let mut prefix: Vec<Tpbr> = Vec::with_capacity(n);
+        for entry in &entries {
+            let mbr = prefix
+                .last()
+                .map_or(entry.mbr().clone(), |acc| acc.union(entry.mbr()));
+            prefix.push(mbr);
+        }
+        let mut suffix: Vec<Tpbr> = Vec::with_capacity(n);
+        for entry in entries.iter().rev() {
+            let mbr = suffix
+                .last()
+                .map_or(entry.mbr().clone(), |acc| acc.union(entry.mbr()));
+            suffix.push(mbr);
+        }
+        suffix.reverse();
+        for cut in min_entries..=n - min_entries {
+            let cost = prefix[cut - 1].mean_area(horizon) + suffix[cut].mean_area(horizon);
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, dim, cut));
+            }
+        }
+    }
+    let (_, dim, cut) = best.unwrap_or((0.0, 0, n / 2));
+    entries.sort_by(|a, b| key(a, dim).total_cmp(&key(b, dim)));
+    let rest = entries.split_off(cut);
+    (entries, rest)
+}
+
+/// Collects the points under `node` that are inside `rect` at some time in `t1..=t2`, where
+/// `dt1` and `dt2` are the same times relative to the reference time.
+fn search_rec<'a, T>(
+    node: &'a TprNode<T>,
+    rect: &Rectangle,
+    (t1, t2): (f64, f64),
+    (dt1, dt2): (f64, f64),
+    found: &mut Vec<&'a MovingPoint<T>>,
+) {
+    for entry in &node.entries {
+        if !overlaps_rect(entry.mbr().swept_corners(dt1, dt2), rect) {
+            continue;
+        }
+        match entry {
+            TprEntry::Leaf { point, .. } => {
+                if point.inside_during(rect, t1, t2) {
+                    found.push(point);
+                }
+            }
+            TprEntry::Node { child, .. } => search_rec(child, rect, (t1, t2), (dt1, dt2), found),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{HasId, sorted_ids};
+
+    fn fleet() -> Vec<MovingPoint<usize>> {
+        (0..400)
+            .map(|i| {
+                let x = (i * 37 % 101) as f64;
+                let y = (i * 53 % 97) as f64;
+                let vx = (i % 7) as f64 - 3.0;
+                let vy = (i % 5) as f64 * 0.5 - 1.0;
+                MovingPoint::new(x, y, vx, vy, (i % 3) as f64, Some(i))
+            })
+            .collect()
+    }
+
+    fn brute_force(
+        points: &[MovingPoint<usize>],
+        rect: &Rectangle,
+        t1: f64,
+        t2: f64,
+    ) -> Vec<usize> {
+        // Sample the interval finely; the rectangles below are large enough that no point
+        // passes through between two samples.
+        let steps = 2000;
+        let mut ids: Vec<usize> = points
+            .iter()
+            .filter(|p| {
+                (0..=steps).any(|s| {
+                    let t = t1 + (t2 - t1) * s as f64 / steps as f64;
+                    let [x, y] = p.position_at(t);
+                    rect.x <= x
+                        && x <= rect.x + rect.width
+                        && rect.y <= y
+                        && y <= rect.y + rect.height
+                })
+            })
+            .filter_map(|p| p.data)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    impl HasId for MovingPoint<usize> {
+        fn id(&self) -> Option<usize> {
+            self.data
+        }
+    }
+
+    const RECT: Rectangle = Rectangle {
+        x: 20.0,
+        y: 30.0,
+        width: 25.0,
+        height: 15.0,
+    };
+
+    fn fleet_tree(points: &[MovingPoint<usize>]) -> TprTree<usize> {
+        let mut tree = TprTree::new(6).unwrap().with_horizon(20.0);
+        for p in points {
+            tree.insert(p.clone());
+        }
+        tree
+    }
+
+    #[test]
+    fn test_new_rejects_small_capacity() {
+        assert!(matches!(
+            TprTree::<usize>::new(1),
+            Err(SpartError::InvalidCapacity { capacity: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_empty_tree_finds_nothing() {
+        let mut tree: TprTree<usize> = TprTree::new(4).unwrap();
+        assert!(tree.is_empty());
+        assert!(tree.range_search_at(&RECT, 0.0).is_empty());
+        assert!(tree.range_search_during(&RECT, 0.0, 10.0).is_empty());
+        assert!(!tree.delete(&fleet()[0]));
+    }
+
+    #[test]
+    fn test_insert_keeps_invariants() {
+        let points = fleet();
+        let tree = fleet_tree(&points);
+        assert_eq!(tree.len(), points.len());
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_range_search_at_matches_brute_force() {
+        let points = fleet();
+        let tree = fleet_tree(&points);
+        for t in [-5.0, 0.0, 3.5, 12.0] {
+            assert_eq!(
+                sorted_ids(tree.range_search_at(&RECT, t)),
+                brute_force(&points, &RECT, t, t)
+            );
+        }
+    }
+
+    #[test]
+    fn test_range_search_during_matches_brute_force() {
+        let points = fleet();
+        let tree = fleet_tree(&points);
+        assert_eq!(
+            sorted_ids(tree.range_search_during(&RECT, 2.0, 6.0)),
+            brute_force(&points, &RECT, 2.0, 6.0)
+        );
+    }
+
+    #[test]
+    fn test_range_search_during_reversed_interval_is_empty() {
+        let tree = fleet_tree(&fleet());
+        assert!(tree.range_search_during(&RECT, 6.0, 2.0).is_empty());
+    }
+
+    #[test]
+    fn test_point_on_edge_is_found() {
+        let mut tree = TprTree::new(4).unwrap();
+        tree.insert(MovingPoint::new(45.0, 45.0, 0.0, 0.0, 0.0, Some(0)));
+        tree.insert(MovingPoint::new(10.0, 30.0, 1.0, 0.0, 0.0, Some(1)));
+        assert_eq!(sorted_ids(tree.range_search_at(&RECT, 0.0)), [0]);
+        assert_eq!(sorted_ids(tree.range_search_at(&RECT, 10.0)), [0, 1]);
+    }
+
+    #[test]
+    fn test_delete_removes_points_once() {
+        let points = fleet();
+        let mut tree = fleet_tree(&points);
+        for p in points.iter().step_by(2) {
+            assert!(tree.delete(p));
+        }
+        assert!(!tree.delete(&points[0]));
+        assert_eq!(tree.len(), points.len() / 2);
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_tighten_keeps_results() {
+        let points = fleet();
+        let rest: Vec<_> = points.iter().skip(1).step_by(2).cloned().collect();
+        let mut tree = fleet_tree(&rest);
+        tree.tighten(10.0);
+        assert!(tree.check_invariants().is_ok());
+        assert_eq!(
+            sorted_ids(tree.range_search_during(&RECT, 8.0, 11.0)),
+            brute_force(&rest, &RECT, 8.0, 11.0)
+        );
+    }
+
+    #[test]
+    fn test_update_moves_point() {
+        let points = fleet();
+        let mut tree = fleet_tree(&points);
+        let moved = MovingPoint::new(30.0, 35.0, 0.0, 0.0, 10.0, Some(1));
+        assert!(tree.update(&points[1], moved.clone()));
+        assert!(sorted_ids(tree.range_search_at(&RECT, 50.0)).contains(&1));
+        assert!(!tree.update(&points[1], moved));
+        assert_eq!(tree.len(), points.len());
+    }
+}
@@ -0,0 +1,356 @@
+//! ## Trajectories
+//!
+//! This module provides `TrajectoryIndex`, which stores trajectories (polylines, e.g., GPS
+//! tracks) and finds the ones passing within a distance of a query path. Each segment of a
+//! trajectory is stored in an R-tree with its own bounding rectangle, so long trajectories do
+//! not turn into one huge rectangle covering everything they pass by.
+//!
+//! A query looks up, for each segment of the query path, the stored segments whose rectangles
+//! come within the tolerance of it, and computes the exact segment-to-segment distance only for
+//! those pairs. Trajectories already found are not tested again.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::trajectory::TrajectoryIndex;
+//!
+//! let mut index = TrajectoryIndex::new(8).unwrap();
+//! let bus = index.insert(vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]], "bus").unwrap();
+//! let ferry = index.insert(vec![[0.0, 20.0], [10.0, 20.0]], "ferry").unwrap();
+//!
+//! let near: Vec<usize> = index
+//!     .trajectories_near(&[[5.0, 1.0], [5.0, 5.0]], 1.5)
+//!     .unwrap()
+//!     .iter()
+//!     .map(|t| t.id)
+//!     .collect();
+//! assert_eq!(near, vec![bus]);
+//! assert_eq!(index.get(ferry).unwrap().data, "ferry");
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::errors::SpartError;
+use crate::geometry::{Bounded, POINT_MBR_SIZE, Rectangle, on_segment, segments_intersect};
+use crate::predicates::covering_extent;
+use crate::rtree::RTree;
+
+/// A segment of a stored trajectory, as kept in the R-tree of a `TrajectoryIndex`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Segment {
+    /// The id of the trajectory the segment belongs to.
+    pub trajectory: usize,
+    /// The position of the segment in the trajectory (0 for the first segment).
+    pub index: usize,
+    /// The start of the segment.
+    pub start: [f64; 2],
+    /// The end of the segment.
+    pub end: [f64; 2],
+}
+
+impl Bounded for Segment {
+    type B = Rectangle;
+    fn mbr(&self) -> Rectangle {
+        let x = self.start[0].min(self.end[0]);
+        let y = self.start[1].min(self.end[1]);
+        // Axis-aligned segments are padded like points, so that no rectangle is degenerate.
+        Rectangle {
+            x,
+            y,
+            width: covering_extent(x, self.start[0].max(self.end[0])) + POINT_MBR_SIZE,
+            height: covering_extent(y, self.start[1].max(self.end[1])) + POINT_MBR_SIZE,
+        }
+    }
+}
+
+/// A stored trajectory with its payload.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Trajectory<T> {
+    /// The id assigned to the trajectory when it was inserted.
+    pub id: usize,
+    /// The vertices of the trajectory, in order.
+    pub path: Vec<[f64; 2]>,
+    /// The payload of the trajectory.
+    pub data: T,
+}
+
+/// An index of trajectories supporting proximity queries with a path.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TrajectoryIndex<T> {
+    segments: RTree<Segment>,
+    trajectories: BTreeMap<usize, Trajectory<T>>,
+    next_id: usize,
+}
+
+impl<T> TrajectoryIndex<T> {
+    /// Creates an empty index.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_entries` - The maximum number of entries per node of the segment R-tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if `max_entries` is less than 2.
+    pub fn new(max_entries: usize) -> Result<Self, SpartError> {
+        Ok(TrajectoryIndex {
+            segments: RTree::new(max_entries)?,
+            trajectories: BTreeMap::new(),
+            next_id: 0,
+        })
+    }
+
+    /// Returns the number of stored trajectories.
+    pub fn len(&self) -> usize {
+        self.trajectories.len()
+    }
+
+    /// Returns `true` if no trajectories are stored.
+    pub fn is_empty(&self) -> bool {
+        self.trajectories.is_empty()
+    }
+
+    /// Returns the R-tree of the stored segments.
+    pub fn segments(&self) -> &RTree<Segment> {
+        &self.segments
+    }
+
+    /// Returns the trajectory with the given id.
+    pub fn get(&self, id: usize) -> Option<&Trajectory<T>> {
+        self.trajectories.get(&id)
+    }
+
+    /// Returns the stored trajectories, ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = &Trajectory<T>> {
+        self.trajectories.values()
+    }
+
+    /// Stores a trajectory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The vertices of the trajectory. A single vertex is stored as a stationary
+    ///   trajectory.
+    /// * `data` - The payload of the trajectory.
+    ///
+    /// # Returns
+    ///
+    /// The id assigned to the trajectory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if the path is empty or has a vertex that is
+    /// not finite.
+    pub fn insert(&mut self, path: Vec<[f64; 2]>, data: T) -> Result<usize, SpartError> {
+        validate_path(&path)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        for segment in path_segments(&path, id) {
+            self.segments.insert(segment);
+        }
+        self.trajectories.insert(id, Trajectory { id, path, data });
+        Ok(id)
+    }
+
+    /// Removes a trajectory.
+    ///
+    /// # Returns
+    ///
+    /// The removed trajectory, or `None` if there is no trajectory with the given id.
+    pub fn remove(&mut self, id: usize) -> Option<Trajectory<T>> {
+        let trajectory = self.trajectories.remove(&id)?;
+        for segment in path_segments(&trajectory.path, id) {
+            self.segments.delete(&segment);
+        }
+        Some(trajectory)
+    }
+
+    /// Finds the stored trajectories that pass within `tolerance` of a query path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The vertices of the query path (a single vertex queries around a point).
+    /// * `tolerance` - The largest allowed distance between the query path and a trajectory.
+    ///
+    /// # Returns
+    ///
+    /// The matching trajectories, ordered by id.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if the path is empty or has a vertex that is
+    /// not finite, or if `tolerance` is negative or not finite.
+    pub fn trajectories_near(
+        &self,
+        path: &[[f64; 2]],
+        tolerance: f64,
+    ) -> Result<Vec<&Trajectory<T>>, SpartError> {
+        validate_path(path)?;
+        if !(tolerance >= 0.0 && tolerance.is_finite()) {
+            return Err(SpartError::InvalidCoordinates {
+                reason: format!("tolerance {tolerance} is not a finite non-negative distance"),
+            });
+        }
+        let mut found = HashSet::new();
+        for query in path_segments(path, usize::MAX) {
+            let mbr = query.mbr();
+            let window = Rectangle {
+                x: mbr.x - tolerance,
+                y: mbr.y - tolerance,
+                width: mbr.width + 2.0 * tolerance,
+                height: mbr.height + 2.0 * tolerance,
+            };
+            for candidate in self.segments.range_search_bbox(&window) {
+                if !found.contains(&candidate.trajectory)
+                    && segment_distance(&query, candidate) <= tolerance
+                {
+                    found.insert(candidate.trajectory);
+                }
+            }
+        }
+        let mut ids: Vec<usize> = found.into_iter().collect();
+        ids.sort_unstable();
+        Ok(ids
+            .iter()
+            .filter_map(|id| self.trajectories.get(id))
+            .collect())
+    }
+}
+
+fn validate_path(path: &[[f64; 2]]) -> Result<(), SpartError> {
+    if path.is_empty() {
+        return Err(SpartError::InvalidCoordinates {
+            reason: "a path needs at least one vertex".to_string(),
+        });
+    }
+    if let Some(v) = path.iter().find(|v| !v[0].is_finite() || !v[1].is_finite()) {
+        return Err(SpartError::InvalidCoordinates {
+            reason: format!("path vertex {v:?} is not finite"),
+        });
+    }
+    Ok(())
+}
+
+/// Splits a path into its segments; a single vertex gives one zero-length segment.
+fn path_segments(path: &[[f64; 2]], trajectory: usize) -> Vec<Segment> {
+    if path.len() == 1 {
+        return vec![Segment {
+            trajectory,
+            index: 0,
+            start: path[0],
+            end: path[0],
+        }];
+    }
+    path.windows(2)
+        .enumerate()
+        .map(|(index, pair)| Segment {
+            trajectory,
+            index,
+            start: pair[0],
+            end: pair[1],
+        })
+        .collect()
+}
+
+/// Returns the distance between a point and a segment.
+fn point_segment_distance(p: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let len_sq = dx * dx + dy * dy;
+    let t = if len_sq == 0.0 {
+        0.0
+    } else {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / len_sq).clamp(0.0, 1.0)
+    };
+    let (cx, cy) = (a[0] + t * dx, a[1] + t * dy);
+    ((p[0] - cx).powi(2) + (p[1] - cy).powi(2)).sqrt()
+}
+
+/// Returns the distance between two segments (zero if they intersect).
+fn segment_distance(a: &Segment, b: &Segment) -> f64 {
+    if segments_intersect(a.start, a.end, b.start, b.end)
+        || on_segment(b.start, b.end, a.start)
+        || on_segment(a.start, a.end, b.start)
+    {
+        return 0.0;
+    }
+    point_segment_distance(a.start, b.start, b.end)
+        .min(point_segment_distance(a.end, b.start, b.end))
+        .min(point_segment_distance(b.start, a.start, a.end))
+        .min(point_segment_distance(b.end, a.start, a.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trajectories_near_matches_brute_force() {
+        let mut index = TrajectoryIndex::new(4).unwrap();
+        // Diagonal zigzags, one per row, plus a stationary one.
+        let mut paths = Vec::new();
+        for row in 0..20 {
+            let y = row as f64 * 3.0;
+            let path: Vec<[f64; 2]> = (0..10)
+                .map(|i| [i as f64 * 2.0, y + (i % 2) as f64])
+                .collect();
+            index.insert(path.clone(), row).unwrap();
+            paths.push(path);
+        }
+        let parked = index.insert(vec![[40.0, 40.0]], 99).unwrap();
+        paths.push(vec![[40.0, 40.0]]);
+        assert_eq!(index.len(), 21);
+
+        let query = [[-1.0, 10.0], [25.0, 25.0], [41.0, 41.0]];
+        let tolerance = 1.5;
+        let query_segments = path_segments(&query, usize::MAX);
+        let expected: Vec<usize> = paths
+            .iter()
+            .enumerate()
+            .filter(|(id, path)| {
+                path_segments(path, *id).iter().any(|s| {
+                    query_segments
+                        .iter()
+                        .any(|q| segment_distance(q, s) <= tolerance)
+                })
+            })
+            .map(|(id, _)| id)
+            .collect();
+        let found: Vec<usize> = index
+            .trajectories_near(&query, tolerance)
+            .unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(found, expected);
+        assert!(found.contains(&parked));
+
+        assert_eq!(index.remove(parked).unwrap().data, 99);
+        assert!(index.remove(parked).is_none());
+        let found = index.trajectories_near(&query, tolerance).unwrap();
+        assert!(found.iter().all(|t| t.id != parked));
+        assert_eq!(index.segments().stats().size, 20 * 9);
+
+        assert!(index.trajectories_near(&query, -1.0).is_err());
+        assert!(index.insert(Vec::new(), 0).is_err());
+    }
+
+    #[test]
+    fn test_segment_distance() {
+        let segment = |start, end| Segment {
+            trajectory: 0,
+            index: 0,
+            start,
+            end,
+        };
+        let a = segment([0.0, 0.0], [4.0, 0.0]);
+        assert_eq!(segment_distance(&a, &segment([2.0, -1.0], [2.0, 1.0])), 0.0);
+        assert_eq!(segment_distance(&a, &segment([1.0, 2.0], [3.0, 2.0])), 2.0);
+        assert_eq!(segment_distance(&a, &segment([7.0, 4.0], [7.0, 4.0])), 5.0);
+    }
+}
@@ -0,0 +1,416 @@
+//! ## Capacity Tuning
+//!
+//! This module provides `CapacityAdvisor`, which recommends a node capacity (for quadtrees and
+//! octrees) or a maximum number of entries per node (for R-trees and R*-trees) for a given
+//! dataset. It builds a tree over a sample of the data for each candidate value, times the
+//! build and a batch of kNN queries drawn from the sample, and recommends the candidate with
+//! the lowest estimated total cost. The measurements are returned alongside the
+//! recommendation, so they can be inspected or logged.
+//!
+//! The advice is only as good as the sample: it should be drawn from the real data and be
+//! large enough (thousands of points) for the trees to reach their typical depth.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::tuning::CapacityAdvisor;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let sample: Vec<Point2D<()>> = (0..1000)
+//!     .map(|i| Point2D::new((i % 40) as f64 * 2.5, (i / 40) as f64 * 4.0, None))
+//!     .collect();
+//!
+//! let advice = CapacityAdvisor::new()
+//!     .with_capacities(vec![4, 16, 64])
+//!     .quadtree(&boundary, &sample)
+//!     .unwrap();
+//! assert!([4, 16, 64].contains(&advice.recommended));
+//! assert_eq!(advice.measurements.len(), 3);
+//! ```
+
+use crate::errors::SpartError;
+use crate::geometry::{Cube, EuclideanDistance, Point2D, Point3D, Rectangle};
+use crate::octree::Octree;
+use crate::quadtree::Quadtree;
+use crate::random::{RandomSource, SplitMix64};
+use crate::rstar_tree::RStarTree;
+use crate::rtree::RTree;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// The candidate capacities tried when none are given.
+const DEFAULT_CAPACITIES: [usize; 6] = [4, 8, 16, 32, 64, 128];
+
+/// The timings measured for one candidate capacity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityMeasurement {
+    /// The capacity (or maximum number of entries per node) that was measured.
+    pub capacity: usize,
+    /// The time it took to build a tree over the whole sample.
+    pub build_time: Duration,
+    /// The mean time of one kNN query.
+    pub mean_query_time: Duration,
+    /// The estimated cost of the workload: the build time plus the query time scaled to the
+    /// expected number of queries (see `CapacityAdvisor::with_queries_per_point`).
+    pub estimated_cost: Duration,
+}
+
+/// A recommended capacity together with the measurements it is based on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityAdvice {
+    /// The candidate with the lowest estimated cost.
+    pub recommended: usize,
+    /// The measurements of all candidates, in the order they were given.
+    pub measurements: Vec<CapacityMeasurement>,
+}
+
+/// Measures candidate capacities on a sample of points and recommends one.
+///
+/// The advisor is configured with consuming setters and then run with the method for the
+/// tree type in question. Every measurement is repeated a few times and the fastest run is
+/// kept, to reduce the effect of noise.
+#[derive(Debug, Clone)]
+pub struct CapacityAdvisor {
+    capacities: Vec<usize>,
+    query_count: usize,
+    k: usize,
+    queries_per_point: f64,
+    repetitions: usize,
+    seed: u64,
+}
+
+impl Default for CapacityAdvisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CapacityAdvisor {
+    /// Creates an advisor with the default settings.
+    ///
+    /// By default, the capacities 4, 8, 16, 32, 64, and 128 are tried, with 100 queries for
+    /// the 10 nearest neighbors, one expected query per stored point, and 3 repetitions.
+    pub fn new() -> Self {
+        CapacityAdvisor {
+            capacities: DEFAULT_CAPACITIES.to_vec(),
+            query_count: 100,
+            k: 10,
+            queries_per_point: 1.0,
+            repetitions: 3,
+            seed: 0,
+        }
+    }
+
+    /// Sets the candidate capacities. An empty list keeps the defaults.
+    pub fn with_capacities(mut self, capacities: Vec<usize>) -> Self {
+        if !capacities.is_empty() {
+            self.capacities = capacities;
+        }
+        self
+    }
+
+    /// Sets the number of kNN queries timed per candidate (at least one).
+    pub fn with_query_count(mut self, query_count: usize) -> Self {
+        self.query_count = query_count.max(1);
+        self
+    }
+
+    /// Sets the number of neighbors requested by each timed query.
+    pub fn with_k(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Sets how many queries the real workload runs per stored point.
+    ///
+    /// This balances build time against query time: use a small value for indexes that are
+    /// rebuilt often and queried little, and a large one for long-lived, query-heavy indexes.
+    /// Negative and NaN values are treated as zero, which means only the build time counts.
+    pub fn with_queries_per_point(mut self, queries_per_point: f64) -> Self {
+        self.queries_per_point = queries_per_point.max(0.0);
+        self
+    }
+
+    /// Sets how many times each measurement is repeated (at least once).
+    pub fn with_repetitions(mut self, repetitions: usize) -> Self {
+        self.repetitions = repetitions.max(1);
+        self
+    }
+
+    /// Sets the seed used to pick the query points from the sample.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Recommends a capacity for a `Quadtree` over `boundary`.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The boundary the tree will be created with.
+    /// * `sample` - A sample of the points to be indexed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if a candidate capacity is zero.
+    pub fn quadtree<T>(
+        &self,
+        boundary: &Rectangle,
+        sample: &[Point2D<T>],
+    ) -> Result<CapacityAdvice, SpartError>
+    where
+        T: Clone + PartialEq + std::fmt::Debug,
+    {
+        self.run(
+            sample,
+            |capacity| {
+                let mut tree = Quadtree::new(boundary, capacity)?;
+                tree.insert_bulk(sample);
+                Ok(tree)
+            },
+            |tree, point| tree.knn_search::<EuclideanDistance>(point, self.k).len(),
+        )
+    }
+
+    /// Recommends a capacity for an `Octree` over `boundary`.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The boundary the tree will be created with.
+    /// * `sample` - A sample of the points to be indexed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if a candidate capacity is zero.
+    pub fn octree<T>(
+        &self,
+        boundary: &Cube,
+        sample: &[Point3D<T>],
+    ) -> Result<CapacityAdvice, SpartError>
+    where
+        T: Clone + PartialEq + std::fmt::Debug,
+    {
+        self.run(
+            sample,
+            |capacity| {
+                let mut tree = Octree::new(boundary, capacity)?;
+                tree.insert_bulk(sample);
+                Ok(tree)
+            },
+            |tree, point| tree.knn_search::<EuclideanDistance>(point, self.k).len(),
+        )
+    }
+
+    /// Recommends a maximum number of entries per node for an `RTree` of 2D points.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if a candidate is less than 2.
+    pub fn rtree_2d<T>(&self, sample: &[Point2D<T>]) -> Result<CapacityAdvice, SpartError>
+    where
+        T: Clone + std::fmt::Debug,
+    {
+        self.run(
+            sample,
+            |max_entries| {
+                let mut tree = RTree::new(max_entries)?;
+                tree.insert_bulk(sample.to_vec());
+                Ok(tree)
+            },
+            |tree, point| tree.knn_search::<EuclideanDistance>(point, self.k).len(),
+        )
+    }
+
+    /// Recommends a maximum number of entries per node for an `RTree` of 3D points.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if a candidate is less than 2.
+    pub fn rtree_3d<T>(&self, sample: &[Point3D<T>]) -> Result<CapacityAdvice, SpartError>
+    where
+        T: Clone + std::fmt::Debug,
+    {
+        self.run(
+            sample,
+            |max_entries| {
+                let mut tree = RTree::new(max_entries)?;
+                tree.insert_bulk(sample.to_vec());
+                Ok(tree)
+            },
+            |tree, point| tree.knn_search::<EuclideanDistance>(point, self.k).len(),
+        )
+    }
+
+    /// Recommends a maximum number of entries per node for an `RStarTree` of 2D points.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if a candidate is less than 2.
+    pub fn rstar_tree_2d<T>(&self, sample: &[Point2D<T>]) -> Result<CapacityAdvice, SpartError>
+    where
+        T: Clone + std::fmt::Debug,
+    {
+        self.run(
+            sample,
+            |max_entries| {
+                let mut tree = RStarTree::new(max_entries)?;
+                tree.insert_bulk(sample.to_vec());
+                Ok(tree)
+            },
+            |tree, point| tree.knn_search::<EuclideanDistance>(point, self.k).len(),
+        )
+    }
+
+    /// Recommends a maximum number of entries per node for an `RStarTree` of 3D points.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCapacity` if a candidate is less than 2.
+    pub fn rstar_tree_3d<T>(&self, sample: &[Point3D<T>]) -> Result<CapacityAdvice, SpartError>
+    where
+        T: Clone + std::fmt::Debug,
+    {
+        self.run(
+            sample,
+            |max_entries| {
+                let mut tree = RStarTree::new(max_entries)?;
+                tree.insert_bulk(sample.to_vec());
+                Ok(tree)
+            },
+            |tree, point| tree.knn_search::<EuclideanDistance>(point, self.k).len(),
+        )
+    }
+
+    /// Measures every candidate with the given build and query functions.
+    fn run<P, I>(
+        &self,
+        sample: &[P],
+        build: impl Fn(usize) -> Result<I, SpartError>,
+        query: impl Fn(&I, &P) -> usize,
+    ) -> Result<CapacityAdvice, SpartError> {
+        let queries: Vec<&P> = if sample.is_empty() {
+            Vec::new()
+        } else {
+            let mut rng = SplitMix64::new(self.seed);
+            (0..self.query_count)
+                .map(|_| &sample[rng.next_below(sample.len())])
+                .collect()
+        };
+        let expected_queries = self.queries_per_point * sample.len() as f64;
+
+        let mut measurements = Vec::with_capacity(self.capacities.len());
+        for &capacity in &self.capacities {
+            let mut build_time = Duration::MAX;
+            let mut query_time = Duration::MAX;
+            for _ in 0..self.repetitions {
+                let start = Instant::now();
+                let tree = build(capacity)?;
+                build_time = build_time.min(start.elapsed());
+
+                let start = Instant::now();
+                let found: usize = queries.iter().map(|point| query(&tree, point)).sum();
+                query_time = query_time.min(start.elapsed());
+                std::hint::black_box(found);
+            }
+            let mean_query_time = query_time / queries.len().max(1) as u32;
+            let estimated_cost = build_time + mean_query_time.mul_f64(expected_queries);
+            info!(
+                "Capacity {}: build {:?}, query {:?}, estimated cost {:?}",
+                capacity, build_time, mean_query_time, estimated_cost
+            );
+            measurements.push(CapacityMeasurement {
+                capacity,
+                build_time,
+                mean_query_time,
+                estimated_cost,
+            });
+        }
+
+        // Ties go to the first candidate given.
+        let recommended = measurements
+            .iter()
+            .min_by_key(|m| m.estimated_cost)
+            .map(|m| m.capacity)
+            .unwrap_or_else(|| unreachable!("the candidate list is never empty"));
+        Ok(CapacityAdvice {
+            recommended,
+            measurements,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_2d(n: usize) -> Vec<Point2D<usize>> {
+        (0..n)
+            .map(|i| Point2D::new((i % 50) as f64, (i / 50) as f64, Some(i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_advice_covers_all_candidates() {
+        let sample = grid_2d(500);
+        let advisor = CapacityAdvisor::new()
+            .with_capacities(vec![2, 8, 32])
+            .with_query_count(20)
+            .with_repetitions(1);
+
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 50.0,
+            height: 10.0,
+        };
+        for advice in [
+            advisor.quadtree(&boundary, &sample).unwrap(),
+            advisor.rtree_2d(&sample).unwrap(),
+            advisor.rstar_tree_2d(&sample).unwrap(),
+        ] {
+            let capacities: Vec<usize> = advice.measurements.iter().map(|m| m.capacity).collect();
+            assert_eq!(capacities, vec![2, 8, 32]);
+            assert!(capacities.contains(&advice.recommended));
+            let best = advice
+                .measurements
+                .iter()
+                .map(|m| m.estimated_cost)
+                .min()
+                .unwrap();
+            assert!(
+                advice
+                    .measurements
+                    .iter()
+                    .any(|m| m.capacity == advice.recommended && m.estimated_cost == best)
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_candidates_are_reported() {
+        let sample = grid_2d(10);
+        let advisor = CapacityAdvisor::new().with_capacities(vec![1, 4]);
+        assert!(matches!(
+            advisor.rtree_2d(&sample),
+            Err(SpartError::InvalidCapacity { capacity: 1 })
+        ));
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 50.0,
+            height: 50.0,
+        };
+        assert!(advisor.quadtree(&boundary, &sample).is_ok());
+        // An empty sample still yields one measurement per candidate.
+        let empty: Vec<Point2D<usize>> = Vec::new();
+        assert_eq!(
+            advisor
+                .quadtree(&boundary, &empty)
+                .unwrap()
+                .measurements
+                .len(),
+            2
+        );
+    }
+}
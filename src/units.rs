@@ -0,0 +1,414 @@
+//! ## Typed Units
+//!
+//! The trees treat coordinates as plain numbers, so nothing stops a caller from storing
+//! longitudes and latitudes in a `Point2D` and then running a Euclidean radius query with a
+//! radius in meters. Such a query compiles, runs, and silently returns the wrong points, as
+//! one degree is about 111 km. This module makes that mistake a compile error by giving
+//! geographic data its own types:
+//!
+//! * `Meters` is a distance along the surface of the Earth. It is not an `f64`, so it cannot
+//!   be passed where a planar radius is expected, and the other way around.
+//! * `GeoPoint` is a longitude and latitude in degrees, validated on construction. It is not a
+//!   `Point2D` and implements no `DistanceMetric`, so it cannot be stored in the planar trees
+//!   or measured with `EuclideanDistance`.
+//! * `GeoIndex` stores `GeoPoint`s and only offers great-circle queries, with radii in
+//!   `Meters`.
+//!
+//! Planar data keeps using `Point2D` and the trees directly, in whatever unit it is in.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::units::{GeoIndex, GeoPoint, Meters};
+//!
+//! let mut cities = GeoIndex::new(8);
+//! cities.insert(GeoPoint::new(2.35, 48.86, Some("Paris")).unwrap());
+//! cities.insert(GeoPoint::new(2.30, 49.89, Some("Amiens")).unwrap());
+//! cities.insert(GeoPoint::new(-0.13, 51.51, Some("London")).unwrap());
+//!
+//! let paris = GeoPoint::new(2.35, 48.86, None).unwrap();
+//! let near: Vec<_> = cities
+//!     .within(&paris, Meters::from_km(150.0))
+//!     .unwrap()
+//!     .into_iter()
+//!     .map(|(city, _)| *city.data().unwrap())
+//!     .collect();
+//! assert_eq!(near, ["Paris", "Amiens"]);
+//! ```
+//!
+//! A planar radius cannot be used by mistake:
+//!
+//! ```compile_fail
+//! use spart::units::{GeoIndex, GeoPoint};
+//!
+//! let cities: GeoIndex<&str> = GeoIndex::new(8);
+//! let paris = GeoPoint::new(2.35, 48.86, None).unwrap();
+//! cities.within(&paris, 150_000.0);
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::errors::SpartError;
+use crate::geo::{EARTH_RADIUS_M, GeoBBox, haversine_distance};
+use crate::geometry::{Point2D, Rectangle};
+use crate::quadtree::Quadtree;
+
+/// A distance along the surface of the Earth, in meters.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Meters(pub f64);
+
+impl Meters {
+    /// Returns the distance of `km` kilometers.
+    pub fn from_km(km: f64) -> Self {
+        Meters(km * 1000.0)
+    }
+
+    /// Returns the distance as a number of meters.
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+/// A point on the Earth given by its longitude and latitude in degrees.
+///
+/// # Type Parameters
+///
+/// * `T`: The type of the optional data carried by the point.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GeoPoint<T> {
+    point: Point2D<T>,
+}
+
+impl<T> GeoPoint<T> {
+    /// Creates a point from its longitude and latitude.
+    ///
+    /// # Arguments
+    ///
+    /// * `lon` - The longitude, between -180° and 180°.
+    /// * `lat` - The latitude, between -90° and 90°.
+    /// * `data` - Optional data carried by the point.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if a coordinate is not finite or out of range.
+    pub fn new(lon: f64, lat: f64, data: Option<T>) -> Result<Self, SpartError> {
+        GeoBBox::new(lon, lat, lon, lat)?;
+        Ok(GeoPoint {
+            point: Point2D::new(lon, lat, data),
+        })
+    }
+
+    /// Returns the longitude in degrees.
+    pub fn lon(&self) -> f64 {
+        self.point.x
+    }
+
+    /// Returns the latitude in degrees.
+    pub fn lat(&self) -> f64 {
+        self.point.y
+    }
+
+    /// Returns the data carried by the point.
+    pub fn data(&self) -> Option<&T> {
+        self.point.data.as_ref()
+    }
+
+    /// Returns the point as a `Point2D` with the longitude as `x` and the latitude as `y`, as
+    /// used by the functions in the `geo` module.
+    pub fn as_point(&self) -> &Point2D<T> {
+        &self.point
+    }
+
+    /// Converts the point into a `Point2D` with the longitude as `x` and the latitude as `y`.
+    pub fn into_point(self) -> Point2D<T> {
+        self.point
+    }
+
+    /// Returns the great-circle distance to `other`.
+    pub fn distance(&self, other: &GeoPoint<T>) -> Meters {
+        Meters(haversine_distance(&self.point, &other.point))
+    }
+}
+
+impl<T> TryFrom<Point2D<T>> for GeoPoint<T> {
+    type Error = SpartError;
+
+    /// Reads `x` as the longitude and `y` as the latitude, checking that they are in range.
+    fn try_from(point: Point2D<T>) -> Result<Self, SpartError> {
+        GeoBBox::new(point.x, point.y, point.x, point.y)?;
+        Ok(GeoPoint { point })
+    }
+}
+
+/// An index of geographic points that only answers great-circle queries.
+///
+/// The points are kept in a `Quadtree` over longitude and latitude; the distances and radii of
+/// all queries are in `Meters`.
+///
+/// # Type Parameters
+///
+/// * `T`: The type of the data carried by the points.
+#[derive(Debug, Clone)]
+pub struct GeoIndex<T: Clone + PartialEq + std::fmt::Debug> {
+    tree: Quadtree<T>,
+    len: usize,
+}
+
+impl<T: Clone + PartialEq + std::fmt::Debug> GeoIndex<T> {
+    /// Creates a new, empty index covering the whole Earth.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The node capacity of the underlying quadtree. Values below one are
+    ///   treated as one.
+    pub fn new(capacity: usize) -> Self {
+        let world = Rectangle {
+            x: -180.0,
+            y: -90.0,
+            width: 360.0,
+            height: 180.0,
+        };
+        GeoIndex {
+            tree: Quadtree::new(&world, capacity.max(1))
+                .unwrap_or_else(|_| unreachable!("the world rectangle is a valid boundary")),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of stored points.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no points are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts a point.
+    pub fn insert(&mut self, point: GeoPoint<T>) {
+        let inserted = self.tree.insert(point.point);
+        debug_assert!(inserted, "a valid geographic point lies inside the world");
+        self.len += usize::from(inserted);
+    }
+
+    /// Deletes a point.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching point was found and deleted.
+    pub fn delete(&mut self, point: &GeoPoint<T>) -> bool {
+        let deleted = self.tree.delete(&point.point);
+        self.len -= usize::from(deleted);
+        deleted
+    }
+
+    /// Finds all points within `radius` of `center`.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the search.
+    /// * `radius` - The great-circle search radius.
+    ///
+    /// # Returns
+    ///
+    /// The points within the radius, paired with their distances from `center` and sorted from
+    /// the nearest to the farthest.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::InvalidCoordinates` if `radius` is negative or not finite.
+    pub fn within(
+        &self,
+        center: &GeoPoint<T>,
+        radius: Meters,
+    ) -> Result<Vec<(GeoPoint<T>, Meters)>, SpartError> {
+        let bbox = GeoBBox::around(&center.point, radius.0)?;
+        let mut found: Vec<(GeoPoint<T>, Meters)> = self
+            .tree
+            .range_search_geo_bbox(&bbox)
+            .into_iter()
+            .filter_map(|point| {
+                let distance = haversine_distance(&center.point, &point);
+                (distance <= radius.0).then_some((GeoPoint { point }, Meters(distance)))
+            })
+            .collect();
+        found.sort_by(|a, b| a.1.0.total_cmp(&b.1.0));
+        Ok(found)
+    }
+
+    /// Finds the `k` points nearest to `target` along the surface of the Earth.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The point to search from.
+    /// * `k` - The number of neighbors to find.
+    ///
+    /// # Returns
+    ///
+    /// Up to `k` points paired with their distances from `target`, sorted from the nearest to
+    /// the farthest.
+    pub fn nearest(&self, target: &GeoPoint<T>, k: usize) -> Vec<(GeoPoint<T>, Meters)> {
+        if k == 0 || self.is_empty() {
+            return Vec::new();
+        }
+        // Widen the search until it holds `k` points; the `k` nearest then lie inside it.
+        // Half the circumference of the Earth reaches every point.
+        let farthest = std::f64::consts::PI * EARTH_RADIUS_M;
+        let mut radius = Meters::from_km(10.0);
+        loop {
+            let mut found = self
+                .within(target, radius)
+                .unwrap_or_else(|_| unreachable!("the radius is finite and positive"));
+            if found.len() >= k || radius.0 >= farthest {
+                found.truncate(k);
+                return found;
+            }
+            radius = Meters((radius.0 * 4.0).min(farthest));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{RandomSource, SplitMix64};
+
+    /// 400 random points over the whole Earth, with ids `0..400`.
+    fn world_points() -> Vec<GeoPoint<usize>> {
+        let mut rng = SplitMix64::new(3494);
+        (0..400)
+            .map(|i| {
+                let lon = rng.next_f64() * 360.0 - 180.0;
+                let lat = rng.next_f64() * 180.0 - 90.0;
+                GeoPoint::new(lon, lat, Some(i)).unwrap()
+            })
+            .collect()
+    }
+
+    fn index_of(points: &[GeoPoint<usize>]) -> GeoIndex<usize> {
+        let mut index = GeoIndex::new(4);
+        for point in points {
+            index.insert(point.clone());
+        }
+        index
+    }
+
+    fn distances(found: &[(GeoPoint<usize>, Meters)]) -> Vec<f64> {
+        found.iter().map(|(_, d)| d.get()).collect()
+    }
+
+    #[test]
+    fn test_geo_point_rejects_out_of_range_coordinates() {
+        for (lon, lat) in [(181.0, 0.0), (0.0, -91.0), (f64::NAN, 0.0)] {
+            assert!(matches!(
+                GeoPoint::new(lon, lat, None::<()>),
+                Err(SpartError::InvalidCoordinates { .. })
+            ));
+        }
+        assert!(matches!(
+            GeoPoint::try_from(Point2D::new(0.0, -91.0, None::<()>)),
+            Err(SpartError::InvalidCoordinates { .. })
+        ));
+    }
+
+    #[test]
+    fn test_within_rejects_invalid_radius() {
+        let index: GeoIndex<usize> = GeoIndex::new(4);
+        let center = GeoPoint::new(0.0, 0.0, None).unwrap();
+        for radius in [Meters(-1.0), Meters(f64::INFINITY)] {
+            assert!(matches!(
+                index.within(&center, radius),
+                Err(SpartError::InvalidCoordinates { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_empty_index_finds_nothing() {
+        let mut index: GeoIndex<usize> = GeoIndex::new(0);
+        let center = GeoPoint::new(0.0, 0.0, Some(0)).unwrap();
+        assert!(index.is_empty());
+        assert!(
+            index
+                .within(&center, Meters::from_km(100.0))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(index.nearest(&center, 3).is_empty());
+        assert!(!index.delete(&center));
+    }
+
+    #[test]
+    fn test_points_at_world_edges_are_found() {
+        let corners = [(-180.0, -90.0), (180.0, 90.0), (180.0, 0.0)];
+        let points: Vec<_> = corners
+            .into_iter()
+            .enumerate()
+            .map(|(i, (lon, lat))| GeoPoint::new(lon, lat, Some(i)).unwrap())
+            .collect();
+        let index = index_of(&points);
+        assert_eq!(index.len(), 3);
+        let center = GeoPoint::new(-180.0, 0.0, None).unwrap();
+        let found = index.within(&center, Meters::from_km(1.0)).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0.data(), Some(&2));
+    }
+
+    #[test]
+    fn test_within_includes_points_at_radius() {
+        let points = [GeoPoint::new(1.0, 0.0, Some(0)).unwrap()];
+        let index = index_of(&points);
+        let center = GeoPoint::new(0.0, 0.0, None).unwrap();
+        let radius = center.distance(&points[0]);
+        assert_eq!(index.within(&center, radius).unwrap().len(), 1);
+        assert!(
+            index
+                .within(&center, Meters(radius.get() - 1.0))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_within_matches_great_circle_scan_across_antimeridian() {
+        let points = world_points();
+        let index = index_of(&points);
+        // Near the antimeridian, so the search box wraps around.
+        let center = GeoPoint::new(179.5, 10.0, None).unwrap();
+        let radius = Meters::from_km(2500.0);
+        let mut expected: Vec<f64> = points
+            .iter()
+            .map(|p| center.distance(p).get())
+            .filter(|&d| d <= radius.get())
+            .collect();
+        expected.sort_by(f64::total_cmp);
+        let found = index.within(&center, radius).unwrap();
+        assert!(!found.is_empty());
+        assert_eq!(distances(&found), expected);
+    }
+
+    #[test]
+    fn test_nearest_matches_great_circle_scan() {
+        let points = world_points();
+        let index = index_of(&points);
+        let center = GeoPoint::new(179.5, 10.0, None).unwrap();
+        let mut all: Vec<f64> = points.iter().map(|p| center.distance(p).get()).collect();
+        all.sort_by(f64::total_cmp);
+        assert_eq!(distances(&index.nearest(&center, 25)), all[..25]);
+        assert_eq!(index.nearest(&center, 1000).len(), 400);
+        assert!(index.nearest(&center, 0).is_empty());
+    }
+
+    #[test]
+    fn test_delete_missing_point_returns_false() {
+        let points = world_points();
+        let mut index = index_of(&points);
+        assert!(index.delete(&points[0]));
+        assert!(!index.delete(&points[0]));
+        assert_eq!(index.len(), 399);
+    }
+}
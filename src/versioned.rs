@@ -0,0 +1,286 @@
+//! ## Versioned Indexes
+//!
+//! This module provides `VersionedIndex`, which numbers the successive states of a persistent
+//! tree (see the `persistent` module) and keeps the most recent ones, so that queries can be
+//! run against the index as it was at an earlier version ("time travel").
+//!
+//! Every successful insertion or deletion creates a new version, one higher than the previous
+//! one. Because versions of a persistent tree share their unchanged nodes, keeping a version
+//! costs about one path of nodes. The number of versions kept is bounded by the retention
+//! (see `with_retention`); older versions are dropped as new ones are created.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::persistent::PersistentQuadtree;
+//! use spart::versioned::VersionedIndex;
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+//! let mut index = VersionedIndex::new(PersistentQuadtree::new(&boundary, 4).unwrap());
+//! let v1 = index.insert(Point2D::new(1.0, 1.0, Some("a"))).unwrap();
+//! let v2 = index.delete(&Point2D::new(1.0, 1.0, Some("a"))).unwrap();
+//!
+//! assert_eq!(index.query_at(v1, |tree| tree.len()), Some(1));
+//! assert_eq!(index.query_at(v2, |tree| tree.len()), Some(0));
+//! ```
+
+use crate::geometry::Point2D;
+use crate::kdtree::KdPoint;
+use crate::persistent::{PersistentKdTree, PersistentQuadtree};
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+
+/// The number of versions kept by new indexes.
+pub const DEFAULT_RETENTION: usize = 64;
+
+/// A persistent tree that `VersionedIndex` can keep versions of.
+pub trait PersistentIndex: Clone {
+    /// The type of the points stored in the tree.
+    type Point;
+
+    /// Returns a new version of the tree with `point` added, or `None` if it cannot be stored.
+    fn with_inserted(&self, point: Self::Point) -> Option<Self>;
+
+    /// Returns a new version of the tree without `point`, or `None` if it is not stored.
+    fn with_deleted(&self, point: &Self::Point) -> Option<Self>;
+}
+
+impl<T: Clone + PartialEq> PersistentIndex for PersistentQuadtree<T> {
+    type Point = Point2D<T>;
+
+    fn with_inserted(&self, point: Point2D<T>) -> Option<Self> {
+        self.insert(point)
+    }
+
+    fn with_deleted(&self, point: &Point2D<T>) -> Option<Self> {
+        self.delete(point)
+    }
+}
+
+impl<P: KdPoint> PersistentIndex for PersistentKdTree<P> {
+    type Point = P;
+
+    fn with_inserted(&self, point: P) -> Option<Self> {
+        self.insert(point).ok()
+    }
+
+    fn with_deleted(&self, point: &P) -> Option<Self> {
+        self.delete(point)
+    }
+}
+
+/// A persistent tree together with its most recent versions.
+#[derive(Debug, Clone)]
+pub struct VersionedIndex<I> {
+    /// The retained versions, oldest first; the last one is the current version.
+    versions: VecDeque<I>,
+    /// The version number of the oldest retained version.
+    oldest: u64,
+    retention: usize,
+}
+
+impl<I: PersistentIndex> VersionedIndex<I> {
+    /// Creates an index whose version 0 is `tree`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The initial state of the index.
+    pub fn new(tree: I) -> Self {
+        VersionedIndex {
+            versions: VecDeque::from([tree]),
+            oldest: 0,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+
+    /// Sets the number of versions kept, including the current one.
+    ///
+    /// Values below one are treated as one, which keeps only the current version. Versions
+    /// beyond the new retention are dropped right away, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `retention` - The maximum number of versions to keep.
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention.max(1);
+        self.evict();
+        self
+    }
+
+    /// Returns the number of versions kept, including the current one.
+    pub fn retention(&self) -> usize {
+        self.retention
+    }
+
+    /// Returns the current version number.
+    pub fn version(&self) -> u64 {
+        self.oldest + self.versions.len() as u64 - 1
+    }
+
+    /// Returns the range of version numbers that can still be queried.
+    pub fn versions(&self) -> RangeInclusive<u64> {
+        self.oldest..=self.version()
+    }
+
+    /// Returns the current state of the index.
+    pub fn current(&self) -> &I {
+        self.versions
+            .back()
+            .unwrap_or_else(|| unreachable!("at least the current version is retained"))
+    }
+
+    /// Returns the state of the index at `version`.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or `None` if `version` was dropped or has not been created yet.
+    pub fn at(&self, version: u64) -> Option<&I> {
+        let offset = version.checked_sub(self.oldest)?;
+        self.versions.get(usize::try_from(offset).ok()?)
+    }
+
+    /// Runs a query against the state of the index at `version`.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version to query.
+    /// * `query` - The function that queries the tree.
+    ///
+    /// # Returns
+    ///
+    /// The query result, or `None` if `version` was dropped or has not been created yet.
+    pub fn query_at<R>(&self, version: u64, query: impl FnOnce(&I) -> R) -> Option<R> {
+        self.at(version).map(query)
+    }
+
+    /// Inserts a point, creating a new version.
+    ///
+    /// # Returns
+    ///
+    /// The new version number, or `None` if the tree could not store the point (e.g., a point
+    /// outside a quadtree's boundary); no version is created in that case.
+    pub fn insert(&mut self, point: I::Point) -> Option<u64> {
+        let next = self.current().with_inserted(point)?;
+        Some(self.push(next))
+    }
+
+    /// Deletes a point, creating a new version.
+    ///
+    /// # Returns
+    ///
+    /// The new version number, or `None` if the point was not found; no version is created in
+    /// that case.
+    pub fn delete(&mut self, point: &I::Point) -> Option<u64> {
+        let next = self.current().with_deleted(point)?;
+        Some(self.push(next))
+    }
+
+    fn push(&mut self, tree: I) -> u64 {
+        self.versions.push_back(tree);
+        self.evict();
+        self.version()
+    }
+
+    /// Drops the oldest versions beyond the retention.
+    fn evict(&mut self) {
+        while self.versions.len() > self.retention {
+            self.versions.pop_front();
+            self.oldest += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{EuclideanDistance, Point3D, Rectangle};
+
+    const BOUNDARY: Rectangle = Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 10.0,
+        height: 10.0,
+    };
+
+    fn point(x: f64, id: i32) -> Point3D<i32> {
+        Point3D::new(x, 0.0, 0.0, Some(id))
+    }
+
+    /// An index of points `0..8` on the x-axis, with version `i + 1` adding point `i`.
+    fn line_index(retention: usize) -> VersionedIndex<PersistentKdTree<Point3D<i32>>> {
+        let mut index = VersionedIndex::new(PersistentKdTree::new()).with_retention(retention);
+        for i in 0..8 {
+            assert_eq!(index.insert(point(i as f64, i)), Some(i as u64 + 1));
+        }
+        index
+    }
+
+    #[test]
+    fn test_new_index_is_at_version_zero() {
+        let mut index = VersionedIndex::new(PersistentKdTree::<Point3D<i32>>::new());
+        assert_eq!(index.version(), 0);
+        assert_eq!(index.versions(), 0..=0);
+        assert_eq!(index.retention(), DEFAULT_RETENTION);
+        assert_eq!(index.query_at(0, |tree| tree.len()), Some(0));
+        assert_eq!(index.delete(&point(0.0, 0)), None);
+        assert_eq!(index.version(), 0);
+    }
+
+    #[test]
+    fn test_missing_delete_creates_no_version() {
+        let mut index = line_index(DEFAULT_RETENTION);
+        assert_eq!(index.delete(&point(0.0, 7)), None);
+        assert_eq!(index.version(), 8);
+        assert_eq!(index.delete(&point(7.0, 7)), Some(9));
+        assert_eq!(index.current().len(), 7);
+    }
+
+    #[test]
+    fn test_rejected_insert_creates_no_version() {
+        let mut index = VersionedIndex::new(PersistentQuadtree::new(&BOUNDARY, 4).unwrap());
+        assert_eq!(index.insert(Point2D::new(10.5, 1.0, Some(0))), None);
+        assert_eq!(index.insert(Point2D::new(f64::NAN, 1.0, Some(1))), None);
+        assert_eq!(index.version(), 0);
+    }
+
+    #[test]
+    fn test_points_on_boundary_are_versioned() {
+        let mut index = VersionedIndex::new(PersistentQuadtree::new(&BOUNDARY, 1).unwrap());
+        assert_eq!(index.insert(Point2D::new(0.0, 0.0, Some(0))), Some(1));
+        assert_eq!(index.insert(Point2D::new(10.0, 10.0, Some(1))), Some(2));
+        assert_eq!(index.query_at(1, |tree| tree.len()), Some(1));
+        assert_eq!(
+            index.query_at(2, |tree| tree.range_search_bbox(&BOUNDARY).len()),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_query_at_sees_past_states() {
+        let mut index = line_index(5);
+        index.delete(&point(7.0, 7));
+        let target = point(10.0, 0);
+        let nearest =
+            |tree: &PersistentKdTree<_>| tree.knn_search::<EuclideanDistance>(&target, 1)[0].data;
+        assert_eq!(index.query_at(5, nearest), Some(Some(4)));
+        assert_eq!(index.query_at(8, nearest), Some(Some(7)));
+        assert_eq!(index.query_at(9, nearest), Some(Some(6)));
+    }
+
+    #[test]
+    fn test_retention_drops_oldest_versions() {
+        let index = line_index(5);
+        assert_eq!(index.versions(), 4..=8);
+        assert!(index.at(3).is_none() && index.at(9).is_none());
+        assert!(index.at(4).is_some());
+    }
+
+    #[test]
+    fn test_zero_retention_keeps_current_version() {
+        let index = line_index(5).with_retention(0);
+        assert_eq!(index.retention(), 1);
+        assert_eq!(index.versions(), 8..=8);
+        assert_eq!(index.current().len(), 8);
+    }
+}
@@ -0,0 +1,731 @@
+//! ## Write-Ahead Log
+//!
+//! This module provides `WriteAheadLog`, a wrapper that makes incremental updates to an index
+//! durable. Every insertion and deletion is appended to a log file before it is applied in
+//! memory, so after a crash the index can be restored by loading the last snapshot and
+//! replaying the log on top of it. `checkpoint` writes a fresh snapshot and empties the log.
+//!
+//! Any tree of this crate can be wrapped, as long as its points or objects can be serialized;
+//! see the `WalIndex` trait. The module is only available with the `serde` feature.
+//!
+//! ### File Format
+//!
+//! The snapshot holds a little-endian `u32` CRC-32 checksum followed by the bincode-encoded
+//! pair of the index and the sequence number of the last logged operation it contains. The log
+//! is a sequence of records, each a little-endian `u32` length and `u32` checksum followed by a
+//! bincode-encoded `(sequence, operation)` pair. When restoring, records already covered by
+//! the snapshot are skipped, so a crash in the middle of a checkpoint never applies an
+//! operation twice.
+//!
+//! A snapshot whose checksum does not match is rejected, as is a damaged record in the middle
+//! of the log. A record at the end of the log that is cut short or fails its checksum was being
+//! written during a crash and is discarded. `verify` checks both files without restoring the
+//! index.
+//!
+//! ### Example
+//!
+//! ```
+//! use spart::geometry::{Point2D, Rectangle};
+//! use spart::quadtree::Quadtree;
+//! use spart::wal::WriteAheadLog;
+//!
+//! let dir = std::env::temp_dir();
+//! let snapshot = dir.join(format!("spart-doc-{}.snapshot", std::process::id()));
+//! let log = dir.join(format!("spart-doc-{}.wal", std::process::id()));
+//!
+//! let boundary = Rectangle { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+//! let tree: Quadtree<u32> = Quadtree::new(&boundary, 4).unwrap();
+//! let mut wal = WriteAheadLog::create(tree, &snapshot, &log).unwrap();
+//! wal.insert(Point2D::new(10.0, 20.0, Some(1))).unwrap();
+//! wal.insert(Point2D::new(30.0, 40.0, Some(2))).unwrap();
+//! drop(wal);
+//!
+//! // Restoring replays both insertions on top of the (empty) snapshot.
+//! let restored: WriteAheadLog<Quadtree<u32>> = WriteAheadLog::open(&snapshot, &log).unwrap();
+//! assert!(restored.index().contains(&Point2D::new(30.0, 40.0, Some(2))));
+//! # std::fs::remove_file(&snapshot).unwrap();
+//! # std::fs::remove_file(&log).unwrap();
+//! ```
+
+use crate::checksum::crc32;
+use crate::errors::SpartError;
+use crate::geometry::{BSPBounds, Point2D, Point3D};
+use crate::kdtree::{KdPoint, KdTree};
+use crate::octree::Octree;
+use crate::quadtree::Quadtree;
+use crate::rstar_tree::{RStarTree, RStarTreeObject};
+use crate::rtree::{RTree, RTreeObject};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// The size in bytes of the length and checksum preceding each log record.
+const RECORD_HEADER_LEN: usize = 8;
+
+/// An operation recorded in the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WalOp<I> {
+    /// The item was inserted.
+    Insert(I),
+    /// The item was deleted.
+    Delete(I),
+}
+
+/// An index whose updates can be recorded by a `WriteAheadLog`.
+///
+/// The operations must be deterministic: applying the same sequence of operations to the same
+/// snapshot must always produce the same index.
+pub trait WalIndex: Serialize + DeserializeOwned {
+    /// The type of the points or objects stored in the index.
+    type Item: Serialize + DeserializeOwned;
+
+    /// Inserts an item, returning whether it was stored.
+    fn wal_insert(&mut self, item: Self::Item) -> Result<bool, SpartError>;
+
+    /// Deletes an item, returning whether a matching one was found.
+    fn wal_delete(&mut self, item: &Self::Item) -> bool;
+}
+
+impl<T> WalIndex for Quadtree<T>
+where
+    T: Clone + PartialEq + std::fmt::Debug + Serialize + DeserializeOwned,
+{
+    type Item = Point2D<T>;
+
+    fn wal_insert(&mut self, item: Point2D<T>) -> Result<bool, SpartError> {
+        Ok(self.insert(item))
+    }
+
+    fn wal_delete(&mut self, item: &Point2D<T>) -> bool {
+        self.delete(item)
+    }
+}
+
+impl<T> WalIndex for Octree<T>
+where
+    T: Clone + PartialEq + std::fmt::Debug + Serialize + DeserializeOwned,
+{
+    type Item = Point3D<T>;
+
+    fn wal_insert(&mut self, item: Point3D<T>) -> Result<bool, SpartError> {
+        Ok(self.insert(item))
+    }
+
+    fn wal_delete(&mut self, item: &Point3D<T>) -> bool {
+        self.delete(item)
+    }
+}
+
+impl<P> WalIndex for KdTree<P>
+where
+    P: KdPoint + Serialize + DeserializeOwned,
+{
+    type Item = P;
+
+    fn wal_insert(&mut self, item: P) -> Result<bool, SpartError> {
+        self.insert(item).map(|_| true)
+    }
+
+    fn wal_delete(&mut self, item: &P) -> bool {
+        self.delete(item)
+    }
+}
+
+impl<T> WalIndex for RTree<T>
+where
+    T: RTreeObject + PartialEq + Serialize + DeserializeOwned,
+{
+    type Item = T;
+
+    fn wal_insert(&mut self, item: T) -> Result<bool, SpartError> {
+        self.insert(item);
+        Ok(true)
+    }
+
+    fn wal_delete(&mut self, item: &T) -> bool {
+        self.delete(item)
+    }
+}
+
+impl<T> WalIndex for RStarTree<T>
+where
+    T: RStarTreeObject + PartialEq + Serialize + DeserializeOwned,
+    T::B: BSPBounds,
+{
+    type Item = T;
+
+    fn wal_insert(&mut self, item: T) -> Result<bool, SpartError> {
+        self.insert(item);
+        Ok(true)
+    }
+
+    fn wal_delete(&mut self, item: &T) -> bool {
+        self.delete(item)
+    }
+}
+
+/// An index whose insertions and deletions are recorded in a write-ahead log.
+///
+/// Updates go through `insert` and `delete`, which append the operation to the log before
+/// applying it. Queries go through `index`.
+#[derive(Debug)]
+pub struct WriteAheadLog<I: WalIndex> {
+    index: I,
+    snapshot_path: PathBuf,
+    log_path: PathBuf,
+    log: File,
+    sequence: u64,
+    pending: usize,
+    sync_on_write: bool,
+}
+
+impl<I: WalIndex> WriteAheadLog<I> {
+    /// Starts logging updates to `index`.
+    ///
+    /// The index is written to `snapshot_path` right away and the log at `log_path` is
+    /// emptied, replacing any index previously stored there.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to wrap.
+    /// * `snapshot_path` - Where snapshots of the index are written.
+    /// * `log_path` - Where the log is written.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if either file cannot be written.
+    pub fn create(
+        index: I,
+        snapshot_path: impl AsRef<Path>,
+        log_path: impl AsRef<Path>,
+    ) -> Result<Self, SpartError> {
+        let snapshot_path = snapshot_path.as_ref().to_path_buf();
+        let log_path = log_path.as_ref().to_path_buf();
+        write_snapshot(&snapshot_path, 0, &index)?;
+        let log = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&log_path)
+            .map_err(|e| persistence_error("cannot create log", &log_path, e))?;
+        Ok(WriteAheadLog {
+            index,
+            snapshot_path,
+            log_path,
+            log,
+            sequence: 0,
+            pending: 0,
+            sync_on_write: true,
+        })
+    }
+
+    /// Restores an index from its last snapshot and log.
+    ///
+    /// The logged operations not yet covered by the snapshot are replayed in order. A record
+    /// cut short or damaged by a crash while it was being written (the last record of the
+    /// log) is discarded and removed from the log. If no log file exists, the snapshot is used
+    /// as it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot_path` - The snapshot written by `create` or `checkpoint`.
+    /// * `log_path` - The log written alongside it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if a file cannot be read or is corrupted, including
+    /// when a checksum does not match.
+    pub fn open(
+        snapshot_path: impl AsRef<Path>,
+        log_path: impl AsRef<Path>,
+    ) -> Result<Self, SpartError> {
+        let snapshot_path = snapshot_path.as_ref().to_path_buf();
+        let log_path = log_path.as_ref().to_path_buf();
+
+        let bytes = fs::read(&snapshot_path)
+            .map_err(|e| persistence_error("cannot read snapshot", &snapshot_path, e))?;
+        let (snapshot_sequence, mut index): (u64, I) =
+            bincode::deserialize(checked_snapshot(&bytes, &snapshot_path)?)
+                .map_err(|e| persistence_error("corrupted snapshot", &snapshot_path, e))?;
+
+        let mut log = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(&log_path)
+            .map_err(|e| persistence_error("cannot open log", &log_path, e))?;
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes)
+            .map_err(|e| persistence_error("cannot read log", &log_path, e))?;
+
+        let mut sequence = snapshot_sequence;
+        let mut pending = 0;
+        let mut offset = 0;
+        while let Some((length, record)) = next_record(&bytes, offset, &log_path)? {
+            let (record_sequence, op): (u64, WalOp<I::Item>) = bincode::deserialize(record)
+                .map_err(|e| persistence_error("corrupted log record", &log_path, e))?;
+            offset += length;
+            if record_sequence <= snapshot_sequence {
+                // Already in the snapshot; left over from an interrupted checkpoint.
+                continue;
+            }
+            // Failed operations failed the same way when they were first applied.
+            let _ = apply(&mut index, op);
+            sequence = record_sequence;
+            pending += 1;
+        }
+
+        if offset < bytes.len() {
+            info!(
+                "Discarding {} bytes of an incomplete log record",
+                bytes.len() - offset
+            );
+            log.set_len(offset as u64)
+                .map_err(|e| persistence_error("cannot truncate log", &log_path, e))?;
+        }
+        info!("Replayed {} log records", pending);
+
+        Ok(WriteAheadLog {
+            index,
+            snapshot_path,
+            log_path,
+            log,
+            sequence,
+            pending,
+            sync_on_write: true,
+        })
+    }
+
+    /// Sets whether every logged operation is synced to disk before it is applied.
+    ///
+    /// Syncing (the default) guarantees that an operation that returned is never lost, at the
+    /// cost of one disk flush per operation. Without it, the most recent operations may be
+    /// lost if the machine (not only the process) crashes.
+    ///
+    /// # Arguments
+    ///
+    /// * `sync_on_write` - Whether to sync after every operation.
+    pub fn with_sync_on_write(mut self, sync_on_write: bool) -> Self {
+        self.sync_on_write = sync_on_write;
+        self
+    }
+
+    /// Returns the wrapped index.
+    pub fn index(&self) -> &I {
+        &self.index
+    }
+
+    /// Returns the number of operations logged since the last snapshot.
+    ///
+    /// This is the amount of work a restore would have to replay, which can be used to decide
+    /// when to call `checkpoint`.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Logs an insertion and applies it to the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The point or object to insert.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the item was stored; `false` if the index rejected it (e.g., a point outside
+    /// a quadtree's boundary).
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the log cannot be written, in which case the index
+    /// is left unchanged. Errors returned by the index itself (e.g.,
+    /// `SpartError::DimensionMismatch`) are passed through.
+    pub fn insert(&mut self, item: I::Item) -> Result<bool, SpartError> {
+        let op = WalOp::Insert(item);
+        self.append(&op)?;
+        apply(&mut self.index, op)
+    }
+
+    /// Logs a deletion and applies it to the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The point or object to delete.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a matching item was found and removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the log cannot be written, in which case the index
+    /// is left unchanged.
+    pub fn delete(&mut self, item: I::Item) -> Result<bool, SpartError> {
+        let op = WalOp::Delete(item);
+        self.append(&op)?;
+        apply(&mut self.index, op)
+    }
+
+    /// Writes a snapshot of the current index and empties the log.
+    ///
+    /// The snapshot is written to a temporary file first and then renamed over the previous
+    /// one, so a crash during a checkpoint leaves either the old or the new snapshot in place;
+    /// both restore to the same index.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SpartError::Persistence` if the snapshot cannot be written or the log cannot
+    /// be truncated.
+    pub fn checkpoint(&mut self) -> Result<(), SpartError> {
+        write_snapshot(&self.snapshot_path, self.sequence, &self.index)?;
+        self.log
+            .set_len(0)
+            .and_then(|_| self.log.sync_all())
+            .map_err(|e| persistence_error("cannot truncate log", &self.log_path, e))?;
+        info!("Checkpointed index after {} operations", self.pending);
+        self.pending = 0;
+        Ok(())
+    }
+
+    /// Stops logging and returns the wrapped index.
+    ///
+    /// The files are left as they are, so the index can still be restored with `open`.
+    pub fn into_inner(self) -> I {
+        self.index
+    }
+
+    /// Appends an operation to the log.
+    fn append(&mut self, op: &WalOp<I::Item>) -> Result<(), SpartError> {
+        let sequence = self.sequence + 1;
+        let payload = bincode::serialize(&(sequence, op))
+            .map_err(|e| persistence_error("cannot encode log record", &self.log_path, e))?;
+        let length = u32::try_from(payload.len()).map_err(|_| SpartError::Persistence {
+            reason: format!("log record of {} bytes is too large", payload.len()),
+        })?;
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+        record.extend_from_slice(&length.to_le_bytes());
+        record.extend_from_slice(&crc32(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+
+        // The file was truncated to zero by `checkpoint` or opened for reading and writing by
+        // `open`, so appending means writing at the end.
+        let mut write = || -> std::io::Result<()> {
+            use std::io::{Seek, SeekFrom};
+            self.log.seek(SeekFrom::End(0))?;
+            self.log.write_all(&record)?;
+            if self.sync_on_write {
+                self.log.sync_data()?;
+            }
+            Ok(())
+        };
+        write().map_err(|e| persistence_error("cannot append to log", &self.log_path, e))?;
+        self.sequence = sequence;
+        self.pending += 1;
+        Ok(())
+    }
+}
+
+/// Applies a logged operation to an index.
+fn apply<I: WalIndex>(index: &mut I, op: WalOp<I::Item>) -> Result<bool, SpartError> {
+    match op {
+        WalOp::Insert(item) => index.wal_insert(item),
+        WalOp::Delete(item) => Ok(index.wal_delete(&item)),
+    }
+}
+
+/// Reads the record starting at `offset` in the log `bytes`.
+///
+/// Returns the total length of the record (including its length and checksum) and its
+/// payload, or `None` if the log ends with an incomplete or damaged record, or with no record
+/// at all.
+///
+/// # Errors
+///
+/// Returns `SpartError::Persistence` if a record that is not the last one fails its checksum.
+fn next_record<'b>(
+    bytes: &'b [u8],
+    offset: usize,
+    path: &Path,
+) -> Result<Option<(usize, &'b [u8])>, SpartError> {
+    let rest = &bytes[offset..];
+    let Some(header) = rest.get(..RECORD_HEADER_LEN) else {
+        return Ok(None);
+    };
+    let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let checksum = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let Some(payload) = rest.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + length) else {
+        return Ok(None);
+    };
+    let total = RECORD_HEADER_LEN + length;
+    if crc32(payload) != checksum {
+        if total == rest.len() {
+            return Ok(None);
+        }
+        return Err(persistence_error(
+            "corrupted log record",
+            path,
+            format!("checksum mismatch at byte {offset}"),
+        ));
+    }
+    Ok(Some((total, payload)))
+}
+
+/// Returns the encoded contents of a snapshot file after checking its checksum.
+fn checked_snapshot<'b>(bytes: &'b [u8], path: &Path) -> Result<&'b [u8], SpartError> {
+    let (checksum, payload) = bytes
+        .split_first_chunk::<4>()
+        .ok_or_else(|| persistence_error("corrupted snapshot", path, "file is too short"))?;
+    if crc32(payload) != u32::from_le_bytes(*checksum) {
+        return Err(persistence_error(
+            "corrupted snapshot",
+            path,
+            "checksum mismatch",
+        ));
+    }
+    Ok(payload)
+}
+
+/// Checks the checksums of a snapshot and its log without restoring the index.
+///
+/// A damaged or incomplete record at the end of the log is accepted, as `WriteAheadLog::open`
+/// discards it; a missing log counts as an empty one.
+///
+/// # Arguments
+///
+/// * `snapshot_path` - The snapshot written by `WriteAheadLog::create` or `checkpoint`.
+/// * `log_path` - The log written alongside it.
+///
+/// # Returns
+///
+/// The number of complete records in the log.
+///
+/// # Errors
+///
+/// Returns `SpartError::Persistence` if a file cannot be read or a checksum does not match.
+pub fn verify(
+    snapshot_path: impl AsRef<Path>,
+    log_path: impl AsRef<Path>,
+) -> Result<usize, SpartError> {
+    let (snapshot_path, log_path) = (snapshot_path.as_ref(), log_path.as_ref());
+    let bytes = fs::read(snapshot_path)
+        .map_err(|e| persistence_error("cannot read snapshot", snapshot_path, e))?;
+    checked_snapshot(&bytes, snapshot_path)?;
+    let bytes = match fs::read(log_path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(persistence_error("cannot read log", log_path, e)),
+    };
+    let (mut offset, mut records) = (0, 0);
+    while let Some((length, _)) = next_record(&bytes, offset, log_path)? {
+        offset += length;
+        records += 1;
+    }
+    Ok(records)
+}
+
+/// Atomically replaces the snapshot at `path`.
+fn write_snapshot<I: Serialize>(path: &Path, sequence: u64, index: &I) -> Result<(), SpartError> {
+    let payload = bincode::serialize(&(sequence, index))
+        .map_err(|e| persistence_error("cannot encode snapshot", path, e))?;
+    let mut bytes = Vec::with_capacity(4 + payload.len());
+    bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    let mut tmp_path = OsString::from(path.as_os_str());
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    let write = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    };
+    write().map_err(|e| persistence_error("cannot write snapshot", path, e))
+}
+
+fn persistence_error(what: &str, path: &Path, error: impl std::fmt::Display) -> SpartError {
+    SpartError::Persistence {
+        reason: format!("{what} {}: {error}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Rectangle;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+    /// Returns fresh snapshot and log paths in the temporary directory.
+    fn temp_paths() -> (PathBuf, PathBuf) {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let base = std::env::temp_dir().join(format!("spart-wal-{}-{id}", std::process::id()));
+        (base.with_extension("snapshot"), base.with_extension("wal"))
+    }
+
+    fn cleanup(paths: &(PathBuf, PathBuf)) {
+        let _ = fs::remove_file(&paths.0);
+        let _ = fs::remove_file(&paths.1);
+    }
+
+    fn boundary() -> Rectangle {
+        Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        }
+    }
+
+    fn all_points(tree: &Quadtree<i32>) -> Vec<Point2D<i32>> {
+        let mut points = tree.range_search_bbox(&boundary());
+        points.sort_by(|a, b| a.data.cmp(&b.data));
+        points
+    }
+
+    #[test]
+    fn test_restore_replays_log_after_crash() {
+        let paths = temp_paths();
+        let tree = Quadtree::new(&boundary(), 2).unwrap();
+        let mut wal = WriteAheadLog::create(tree, &paths.0, &paths.1).unwrap();
+        for i in 0..20 {
+            let p = Point2D::new(i as f64 * 4.0, 100.0 - i as f64 * 3.0, Some(i));
+            assert!(wal.insert(p).unwrap());
+        }
+        assert!(wal.delete(Point2D::new(20.0, 85.0, Some(5))).unwrap());
+        assert!(!wal.delete(Point2D::new(1.0, 1.0, Some(99))).unwrap());
+        let expected = all_points(wal.index());
+        // Simulate a crash: nothing but the log records the updates.
+        drop(wal);
+
+        let restored: WriteAheadLog<Quadtree<i32>> =
+            WriteAheadLog::open(&paths.0, &paths.1).unwrap();
+        assert_eq!(all_points(restored.index()), expected);
+        assert_eq!(restored.pending(), 22);
+        cleanup(&paths);
+    }
+
+    #[test]
+    fn test_checkpoint_compacts_log() {
+        let paths = temp_paths();
+        let mut wal = WriteAheadLog::create(KdTree::new(), &paths.0, &paths.1)
+            .unwrap()
+            .with_sync_on_write(false);
+        for i in 0..10 {
+            wal.insert(Point2D::new(i as f64, 0.0, Some(i))).unwrap();
+        }
+        wal.checkpoint().unwrap();
+        assert_eq!(wal.pending(), 0);
+        assert_eq!(fs::metadata(&paths.1).unwrap().len(), 0);
+
+        wal.delete(Point2D::new(3.0, 0.0, Some(3))).unwrap();
+        wal.insert(Point2D::new(3.5, 0.0, Some(10))).unwrap();
+        drop(wal);
+
+        let restored: WriteAheadLog<KdTree<Point2D<i32>>> =
+            WriteAheadLog::open(&paths.0, &paths.1).unwrap();
+        assert_eq!(restored.pending(), 2);
+        let tree = restored.index();
+        assert!(!tree.contains(&Point2D::new(3.0, 0.0, Some(3))));
+        assert!(tree.contains(&Point2D::new(3.5, 0.0, Some(10))));
+        assert!(tree.contains(&Point2D::new(9.0, 0.0, Some(9))));
+        cleanup(&paths);
+    }
+
+    #[test]
+    fn test_interrupted_checkpoint_does_not_apply_operations_twice() {
+        let paths = temp_paths();
+        let tree = Quadtree::new(&boundary(), 4).unwrap();
+        let mut wal = WriteAheadLog::create(tree, &paths.0, &paths.1).unwrap();
+        wal.insert(Point2D::new(1.0, 1.0, Some(1))).unwrap();
+        wal.insert(Point2D::new(1.0, 1.0, Some(1))).unwrap();
+        wal.delete(Point2D::new(1.0, 1.0, Some(1))).unwrap();
+        let log_before = fs::read(&paths.1).unwrap();
+        wal.checkpoint().unwrap();
+        drop(wal);
+        // Simulate a crash after the snapshot was renamed but before the log was truncated.
+        fs::write(&paths.1, &log_before).unwrap();
+
+        let restored: WriteAheadLog<Quadtree<i32>> =
+            WriteAheadLog::open(&paths.0, &paths.1).unwrap();
+        assert_eq!(restored.pending(), 0);
+        assert_eq!(all_points(restored.index()).len(), 1);
+        cleanup(&paths);
+    }
+
+    #[test]
+    fn test_incomplete_record_is_discarded() {
+        let paths = temp_paths();
+        let tree = Quadtree::new(&boundary(), 4).unwrap();
+        let mut wal = WriteAheadLog::create(tree, &paths.0, &paths.1).unwrap();
+        wal.insert(Point2D::new(1.0, 1.0, Some(1))).unwrap();
+        wal.insert(Point2D::new(2.0, 2.0, Some(2))).unwrap();
+        drop(wal);
+        let mut bytes = fs::read(&paths.1).unwrap();
+        bytes.truncate(bytes.len() - 3);
+        fs::write(&paths.1, &bytes).unwrap();
+
+        let mut restored: WriteAheadLog<Quadtree<i32>> =
+            WriteAheadLog::open(&paths.0, &paths.1).unwrap();
+        assert_eq!(
+            all_points(restored.index()),
+            vec![Point2D::new(1.0, 1.0, Some(1))]
+        );
+        // New records go after the last complete one.
+        restored.insert(Point2D::new(3.0, 3.0, Some(3))).unwrap();
+        drop(restored);
+        let restored: WriteAheadLog<Quadtree<i32>> =
+            WriteAheadLog::open(&paths.0, &paths.1).unwrap();
+        assert_eq!(all_points(restored.index()).len(), 2);
+        cleanup(&paths);
+    }
+
+    #[test]
+    fn test_open_reports_missing_snapshot() {
+        let paths = temp_paths();
+        let result: Result<WriteAheadLog<RTree<Point2D<i32>>>, _> =
+            WriteAheadLog::open(&paths.0, &paths.1);
+        assert!(matches!(result, Err(SpartError::Persistence { .. })));
+        cleanup(&paths);
+    }
+
+    #[test]
+    fn test_checksums_reject_damaged_files() {
+        let paths = temp_paths();
+        let tree = Quadtree::new(&boundary(), 4).unwrap();
+        let mut wal = WriteAheadLog::create(tree, &paths.0, &paths.1).unwrap();
+        for i in 0..3 {
+            wal.insert(Point2D::new(i as f64, 1.0, Some(i))).unwrap();
+        }
+        drop(wal);
+        assert_eq!(verify(&paths.0, &paths.1).unwrap(), 3);
+        let flip = |path: &Path, index: usize| {
+            let mut bytes = fs::read(path).unwrap();
+            bytes[index] ^= 0x40;
+            fs::write(path, &bytes).unwrap();
+        };
+        let open = || WriteAheadLog::<Quadtree<i32>>::open(&paths.0, &paths.1);
+
+        // A damaged first record is followed by others, so it is not a torn write.
+        flip(&paths.1, RECORD_HEADER_LEN + 2);
+        assert!(verify(&paths.0, &paths.1).is_err());
+        assert!(matches!(open(), Err(SpartError::Persistence { .. })));
+        flip(&paths.1, RECORD_HEADER_LEN + 2);
+
+        // A damaged last record is discarded.
+        let log_len = fs::metadata(&paths.1).unwrap().len() as usize;
+        flip(&paths.1, log_len - 1);
+        assert_eq!(verify(&paths.0, &paths.1).unwrap(), 2);
+        assert_eq!(all_points(open().unwrap().index()).len(), 2);
+
+        let snapshot_len = fs::metadata(&paths.0).unwrap().len() as usize;
+        flip(&paths.0, snapshot_len - 1);
+        assert!(verify(&paths.0, &paths.1).is_err());
+        assert!(matches!(open(), Err(SpartError::Persistence { .. })));
+        cleanup(&paths);
+    }
+}
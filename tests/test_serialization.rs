@@ -3,10 +3,10 @@ mod helpers;
 #[cfg(test)]
 mod tests {
     use super::helpers::Anyhow;
-    use spart::geometry::{Cube, Point2D, Point3D, Rectangle};
+    use spart::geometry::{Aabb, Cube, Point2D, Point3D, PointND, Rectangle};
     use spart::kdtree::KdTree;
-    use spart::octree::Octree;
-    use spart::quadtree::Quadtree;
+    use spart::octree::{self, Octree};
+    use spart::quadtree::{self, Quadtree};
     use spart::rstar_tree::RStarTree;
     use spart::rtree::RTree;
 
@@ -127,4 +127,91 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_rstartree_nd_serialization() -> Anyhow {
+        let mut tree: RStarTree<PointND<u32, 4>> = RStarTree::new(4).unwrap();
+        for i in 0..20 {
+            let c = i as f64;
+            tree.insert(PointND::new([c, 2.0 * c, -c, c * c], Some(i)));
+        }
+
+        let encoded: Vec<u8> = bincode::serialize(&tree)?;
+        let decoded: RStarTree<PointND<u32, 4>> = bincode::deserialize(&encoded[..])?;
+
+        let query = Aabb {
+            min: [2.0, 0.0, -10.0, 0.0],
+            max: [8.0, 20.0, 0.0, 40.0],
+        };
+        assert_eq!(decoded.range_search_bbox(&query).len(), 5);
+        assert_eq!(
+            tree.range_search_bbox(&query),
+            decoded.range_search_bbox(&query)
+        );
+        Ok(())
+    }
+
+    /// Removes the `depth` field of every tree node, as in trees serialized before it existed.
+    fn strip_node_depths(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if map.contains_key("divided") {
+                    map.remove("depth");
+                }
+                map.values_mut().for_each(strip_node_depths);
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(strip_node_depths),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_quadtree_without_depth_field_keeps_depth_cap() -> Anyhow {
+        let boundary = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let point = Point2D::new(3.0, 7.0, Some(1));
+        let mut qt = Quadtree::new(&boundary, 1).unwrap();
+        qt.insert(point.clone());
+        qt.insert(point.clone());
+        assert_eq!(qt.depth(), quadtree::MAX_DEPTH + 1);
+
+        let mut value = serde_json::to_value(&qt)?;
+        strip_node_depths(&mut value);
+        let mut decoded: Quadtree<i32> = serde_json::from_value(value)?;
+        assert!(decoded.insert(point));
+        assert_eq!(decoded.depth(), quadtree::MAX_DEPTH + 1);
+        assert_eq!(decoded.range_search_bbox(&boundary).len(), 3);
+        decoded.check_invariants()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_octree_without_depth_field_keeps_depth_cap() -> Anyhow {
+        let boundary = Cube {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            width: 10.0,
+            height: 10.0,
+            depth: 10.0,
+        };
+        let point = Point3D::new(3.0, 7.0, 5.0, Some(1));
+        let mut octree = Octree::new(&boundary, 1).unwrap();
+        octree.insert(point.clone());
+        octree.insert(point.clone());
+        assert_eq!(octree.depth(), octree::MAX_DEPTH + 1);
+
+        let mut value = serde_json::to_value(&octree)?;
+        strip_node_depths(&mut value);
+        let mut decoded: Octree<i32> = serde_json::from_value(value)?;
+        assert!(decoded.insert(point));
+        assert_eq!(decoded.depth(), octree::MAX_DEPTH + 1);
+        assert_eq!(decoded.iter().count(), 3);
+        decoded.check_invariants()?;
+        Ok(())
+    }
 }